@@ -1,6 +1,6 @@
-use teza::{input::*, node::*};
+use teza::{error::GraphError, input::*, node::*};
 
-fn main() {
+fn main() -> Result<(), GraphError> {
     fn round(x: f32, precision: u32) -> f32 {
         let m = 10i32.pow(precision) as f32;
         (x * m).round() / m
@@ -17,16 +17,17 @@ fn main() {
         ),
     );
 
-    let mut result = graph.borrow_mut().compute();
+    let mut result = graph.compute()?;
     result = round(result, 5);
     println!("Graph output = {}", result);
     assert_eq!(result, -0.32727);
 
-    x1.borrow_mut().set(2.0);
-    x2.borrow_mut().set(3.0);
-    x3.borrow_mut().set(4.0);
-    result = graph.borrow_mut().compute();
+    x1.set(2.0)?;
+    x2.set(3.0)?;
+    x3.set(4.0)?;
+    result = graph.compute()?;
     result = round(result, 5);
     println!("Graph output = {}", result);
     assert_eq!(result, -0.56656);
+    Ok(())
 }
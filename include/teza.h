@@ -0,0 +1,61 @@
+/* C interface of teza, built with `ffi` feature, see `src/ffi.rs`.
+ * Shared library is built into `target/release/deps` with
+ *     cargo rustc --release --lib --features ffi -- --crate-type cdylib
+ * and static library with `--crate-type staticlib` instead. */
+#ifndef TEZA_H
+#define TEZA_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Handle returned in place of a node when arguments are invalid. */
+#define TEZA_INVALID UINT32_MAX
+
+/* Graph owning nodes referred to by handles. */
+typedef struct TezaGraph TezaGraph;
+
+TezaGraph *teza_graph_new(void);
+void teza_graph_free(TezaGraph *graph);
+
+uint32_t teza_input(TezaGraph *graph, double val);
+uint32_t teza_constant(TezaGraph *graph, double val);
+
+uint32_t teza_add(TezaGraph *graph, uint32_t x, uint32_t y);
+uint32_t teza_sub(TezaGraph *graph, uint32_t x, uint32_t y);
+uint32_t teza_mul(TezaGraph *graph, uint32_t x, uint32_t y);
+uint32_t teza_div(TezaGraph *graph, uint32_t x, uint32_t y);
+uint32_t teza_min(TezaGraph *graph, uint32_t x, uint32_t y);
+uint32_t teza_max(TezaGraph *graph, uint32_t x, uint32_t y);
+uint32_t teza_hypot(TezaGraph *graph, uint32_t x, uint32_t y);
+uint32_t teza_atan2(TezaGraph *graph, uint32_t x, uint32_t y);
+uint32_t teza_rem(TezaGraph *graph, uint32_t x, uint32_t y);
+
+uint32_t teza_neg(TezaGraph *graph, uint32_t x);
+uint32_t teza_abs(TezaGraph *graph, uint32_t x);
+uint32_t teza_sqrt(TezaGraph *graph, uint32_t x);
+uint32_t teza_exp(TezaGraph *graph, uint32_t x);
+uint32_t teza_ln(TezaGraph *graph, uint32_t x);
+uint32_t teza_sin(TezaGraph *graph, uint32_t x);
+uint32_t teza_cos(TezaGraph *graph, uint32_t x);
+uint32_t teza_tan(TezaGraph *graph, uint32_t x);
+uint32_t teza_asin(TezaGraph *graph, uint32_t x);
+uint32_t teza_acos(TezaGraph *graph, uint32_t x);
+uint32_t teza_atan(TezaGraph *graph, uint32_t x);
+uint32_t teza_tanh(TezaGraph *graph, uint32_t x);
+uint32_t teza_sigmoid(TezaGraph *graph, uint32_t x);
+uint32_t teza_pow(TezaGraph *graph, uint32_t x, double pow);
+
+/* Returns false if handle doesn't refer to an input. */
+bool teza_set(TezaGraph *graph, uint32_t input, double val);
+/* Returns NaN for unknown handles. */
+double teza_compute(TezaGraph *graph, uint32_t node);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TEZA_H */
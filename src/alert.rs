@@ -0,0 +1,143 @@
+//! Stateful nodes comparing value to the one of previous evaluation.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    float::Float,
+    node::{
+        dependents, remove_dependency, reset_dependencies, Computable, Dependencies, Handle, Kind,
+    },
+};
+
+/// Condition raising alert.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger<T: Float = f32> {
+    /// Value crossed threshold in either direction.
+    Crossing(T),
+    /// Value changed by more than given limit.
+    Delta(T),
+}
+
+/// Node computing `1.0` when its operand meets trigger compared to previous evaluation
+/// and `0.0` otherwise, including the first evaluation.
+/// Previous value is updated each time the node is recomputed.
+pub struct AlertNode<T: Float = f32> {
+    source: Handle<T>,
+    trigger: Trigger<T>,
+    /// Operand value at previous evaluation.
+    previous: Option<T>,
+    /// Cached result.
+    cache: Option<T>,
+    /// Holds references to nodes that depend from this node.
+    dependencies: Dependencies<T>,
+}
+
+impl<T: Float> AlertNode<T> {
+    /// Builds alert node over given operand.
+    pub fn new(source: Handle<T>, trigger: Trigger<T>) -> Rc<RefCell<Self>> {
+        let obj = Rc::new(RefCell::new(Self {
+            source: source.clone(),
+            trigger,
+            previous: None,
+            cache: None,
+            dependencies: Dependencies::default(),
+        }));
+        source.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Returns operand value at previous evaluation.
+    pub fn previous(&self) -> Option<T> {
+        self.previous
+    }
+}
+
+impl<T: Float> Computable<T> for AlertNode<T> {
+    fn compute(&mut self) -> T {
+        if let Some(val) = self.cache {
+            return val;
+        }
+        let val = self.source.borrow_mut().compute();
+        let raised = self.previous.map_or(false, |prev| match self.trigger {
+            Trigger::Crossing(threshold) => (prev < threshold) != (val < threshold),
+            Trigger::Delta(limit) => (val - prev).abs() > limit,
+        });
+        self.previous = Some(val);
+        let res = if raised { T::ONE } else { T::ZERO };
+        self.cache = Some(res);
+        res
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependencies.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Handle<T>> {
+        dependents(&self.dependencies)
+    }
+
+    fn reset_cache(&mut self) {
+        if self.cache.take().is_none() {
+            return;
+        }
+        reset_dependencies(&mut self.dependencies);
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.cache.is_none()
+    }
+
+    fn operands(&self) -> Vec<Handle<T>> {
+        vec![self.source.clone()]
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Boolean
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graph::Graph, input::Input, node::Node};
+
+    #[test]
+    fn crossing() {
+        let x = Node::input(1.0f32);
+        let alert = AlertNode::new(
+            Node::mul(x.clone(), Node::constant(2.0)),
+            Trigger::Crossing(5.0),
+        );
+        assert_eq!(alert.borrow_mut().compute(), 0.0);
+
+        let steps = [(2.0, 0.0), (3.0, 1.0), (3.5, 0.0), (1.0, 1.0)];
+        for (val, expected) in steps {
+            x.borrow_mut().set(val);
+            assert_eq!(alert.borrow_mut().compute(), expected);
+            // cached until next change
+            assert_eq!(alert.borrow_mut().compute(), expected);
+        }
+        assert_eq!(alert.borrow().previous(), Some(2.0));
+        assert_eq!(alert.borrow().kind(), Kind::Boolean);
+    }
+
+    #[test]
+    fn delta_observer() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 10.0);
+        g.add_output("jump", AlertNode::new(x.clone(), Trigger::Delta(1.0)));
+        let alerts = Rc::new(RefCell::new(0));
+        let sink = alerts.clone();
+        g.observe("jump", move |v| *sink.borrow_mut() += v as i32);
+
+        for val in [10.5, 12.0, 12.5, 10.0] {
+            x.borrow_mut().set(val);
+            g.recompute();
+        }
+        assert_eq!(*alerts.borrow(), 2);
+    }
+}
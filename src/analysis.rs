@@ -0,0 +1,557 @@
+//! Analysis of how graph output responds to its inputs.
+
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    input::Input,
+    node::{Computable, Node},
+};
+
+/// Probability distribution of an input value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    /// Uniform distribution over `[low, high]`.
+    Uniform { low: f32, high: f32 },
+    /// Normal distribution.
+    Normal { mean: f32, std_dev: f32 },
+}
+
+impl Distribution {
+    /// Maps probability `u` from `(0, 1)` to distribution value using inverse CDF.
+    pub fn quantile(&self, u: f64) -> f32 {
+        match *self {
+            Distribution::Uniform { low, high } => low + (high - low) * u as f32,
+            Distribution::Normal { mean, std_dev } => mean + std_dev * normal_quantile(u) as f32,
+        }
+    }
+}
+
+/// Inverse CDF of standard normal distribution (Acklam's rational approximation).
+#[allow(clippy::excessive_precision)]
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e+01,
+        2.209460984245205e+02,
+        -2.759285104469687e+02,
+        1.383577518672690e+02,
+        -3.066479806614716e+01,
+        2.506628277459239e+00,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e+01,
+        1.615858368580409e+02,
+        -1.556989798598866e+02,
+        6.680131188771972e+01,
+        -1.328068155288572e+01,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-03,
+        -3.223964580411365e-01,
+        -2.400758277161838e+00,
+        -2.549732539343734e+00,
+        4.374664141464968e+00,
+        2.938163982698783e+00,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-03,
+        3.224671290700398e-01,
+        2.445134137142996e+00,
+        3.754408661907416e+00,
+    ];
+    const LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+/// Returns first `n` prime numbers.
+fn primes(n: usize) -> Vec<u64> {
+    let mut primes: Vec<u64> = Vec::with_capacity(n);
+    let mut candidate = 2;
+    while primes.len() < n {
+        if primes.iter().all(|p| candidate % p != 0) {
+            primes.push(candidate);
+        }
+        candidate += 1;
+    }
+    primes
+}
+
+/// Returns `index`-th element of van der Corput sequence in given base, lying in `(0, 1)` for `index > 0`.
+fn radical_inverse(mut index: u64, base: u64) -> f64 {
+    let mut result = 0.0;
+    let mut scale = 1.0 / base as f64;
+    while index > 0 {
+        result += (index % base) as f64 * scale;
+        index /= base;
+        scale /= base as f64;
+    }
+    result
+}
+
+/// Variance-based sensitivity indices of graph output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SobolIndices {
+    /// First-order index per input: share of output variance caused by that input alone.
+    pub first_order: Vec<f32>,
+    /// Total index per input: share of output variance involving that input, including interactions.
+    pub total: Vec<f32>,
+    /// Estimated output variance.
+    pub variance: f32,
+    /// Amount of samples indices are estimated from.
+    pub samples: usize,
+}
+
+/// Progress of long-running analysis, passed to progress callbacks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Amount of completed items.
+    pub completed: usize,
+    /// Total amount of items.
+    pub total: usize,
+    /// Time passed since start.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Returns amount of completed items per second.
+    pub fn throughput(&self) -> f64 {
+        self.completed as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Estimates first-order and total Sobol indices of `root` with respect to given inputs.
+///
+/// Inputs are sampled from their distributions with a Halton quasi-random sequence and
+/// indices are computed with Saltelli's estimators, which takes `n_samples * (inputs + 2)`
+/// evaluations. Input values are restored afterwards.
+pub fn sobol<C: Computable + ?Sized>(
+    root: &Rc<RefCell<C>>,
+    inputs: &[(Rc<RefCell<dyn Input>>, Distribution)],
+    n_samples: usize,
+) -> SobolIndices {
+    sobol_with_progress(root, inputs, n_samples, |_| true)
+}
+
+/// Same as `sobol`, but calls `progress` after every sample.
+/// Sampling stops early once `progress` returns false, and indices are estimated
+/// from samples completed so far.
+pub fn sobol_with_progress<C: Computable + ?Sized>(
+    root: &Rc<RefCell<C>>,
+    inputs: &[(Rc<RefCell<dyn Input>>, Distribution)],
+    n_samples: usize,
+    mut progress: impl FnMut(&Progress) -> bool,
+) -> SobolIndices {
+    let start = Instant::now();
+    let d = inputs.len();
+    let original: Vec<f32> = inputs
+        .iter()
+        .map(|(input, _)| input.borrow_mut().compute())
+        .collect();
+    let bases = primes(2 * d);
+
+    let eval = |point: &[f32]| {
+        inputs
+            .iter()
+            .zip(point)
+            .for_each(|((input, _), val)| input.borrow_mut().probe(*val));
+        root.borrow_mut().compute() as f64
+    };
+
+    let mut var_sum = 0.0;
+    let mut var_sq_sum = 0.0;
+    let mut first = vec![0.0; d];
+    let mut total = vec![0.0; d];
+    let mut completed = 0;
+    for sample in 1..=n_samples as u64 {
+        let point = |offset: usize| -> Vec<f32> {
+            inputs
+                .iter()
+                .enumerate()
+                .map(|(i, (_, dist))| dist.quantile(radical_inverse(sample, bases[offset + i])))
+                .collect()
+        };
+        let a = point(0);
+        let b = point(d);
+        let f_a = eval(&a);
+        let f_b = eval(&b);
+        var_sum += f_a + f_b;
+        var_sq_sum += f_a * f_a + f_b * f_b;
+        for (i, (first, total)) in first.iter_mut().zip(&mut total).enumerate() {
+            let mut ab = a.clone();
+            ab[i] = b[i];
+            let f_ab = eval(&ab);
+            *first += f_b * (f_ab - f_a);
+            *total += (f_a - f_ab) * (f_a - f_ab);
+        }
+        completed += 1;
+        let proceed = progress(&Progress {
+            completed,
+            total: n_samples,
+            elapsed: start.elapsed(),
+        });
+        if !proceed {
+            break;
+        }
+    }
+    eval(&original);
+
+    let n = completed.max(1) as f64;
+    let mean = var_sum / (2.0 * n);
+    let variance = var_sq_sum / (2.0 * n) - mean * mean;
+    let index = |sum: f64, scale: f64| {
+        if variance > 0.0 {
+            (scale * sum / n / variance) as f32
+        } else {
+            0.0
+        }
+    };
+    SobolIndices {
+        first_order: first.iter().map(|s| index(*s, 1.0)).collect(),
+        total: total.iter().map(|s| index(*s, 0.5)).collect(),
+        variance: variance as f32,
+        samples: completed,
+    }
+}
+
+/// Method used to split output change between inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributionMethod {
+    /// Each input is moved alone from its old value to its new one, others keep old values.
+    /// Takes `inputs + 1` evaluations, interactions are left unattributed.
+    OneAtATime,
+    /// Exact Shapley values: each input gets its marginal contribution averaged over
+    /// all orders of applying changes. Takes `2^inputs` evaluations.
+    Shapley,
+}
+
+/// Output change split into per-input contributions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribution {
+    /// Output for old input values.
+    pub before: f32,
+    /// Output for new input values.
+    pub after: f32,
+    /// Contribution of each input to the change.
+    pub contributions: Vec<f32>,
+    /// Part of the change not explained by contributions, caused by input interactions.
+    pub interaction: f32,
+}
+
+/// Decomposes change of `root` output between two input states into per-input contributions.
+///
+/// Inputs are switched one by one between `before` and `after` values, so only subgraphs
+/// depending on a switched input are recomputed. Input values are restored afterwards.
+///
+/// # Panics
+///
+/// Panics if `before` or `after` length differs from amount of inputs, or if Shapley
+/// attribution is requested for more than 20 inputs.
+pub fn attribute_delta<C: Computable + ?Sized>(
+    root: &Rc<RefCell<C>>,
+    inputs: &[Rc<RefCell<dyn Input>>],
+    before: &[f32],
+    after: &[f32],
+    method: AttributionMethod,
+) -> Attribution {
+    let n = inputs.len();
+    assert!(before.len() == n && after.len() == n);
+    let original: Vec<f32> = inputs
+        .iter()
+        .map(|input| input.borrow_mut().compute())
+        .collect();
+    inputs
+        .iter()
+        .zip(before)
+        .for_each(|(input, val)| input.borrow_mut().probe(*val));
+    let eval = || root.borrow_mut().compute() as f64;
+    let base = eval();
+
+    let (end, contributions): (f64, Vec<f64>) = match method {
+        AttributionMethod::OneAtATime => {
+            let contributions = (0..n)
+                .map(|i| {
+                    inputs[i].borrow_mut().probe(after[i]);
+                    let val = eval();
+                    inputs[i].borrow_mut().probe(before[i]);
+                    val - base
+                })
+                .collect();
+            inputs
+                .iter()
+                .zip(after)
+                .for_each(|(input, val)| input.borrow_mut().probe(*val));
+            (eval(), contributions)
+        }
+        AttributionMethod::Shapley => {
+            assert!(n <= 20, "too many inputs for Shapley attribution");
+            // walk all subsets of switched inputs in Gray code order, flipping one input per step
+            let mut values = vec![0.0; 1 << n];
+            values[0] = base;
+            for k in 1..1usize << n {
+                let mask = k ^ (k >> 1);
+                let i = (mask ^ ((k - 1) ^ ((k - 1) >> 1))).trailing_zeros() as usize;
+                let val = if mask & (1 << i) != 0 {
+                    after[i]
+                } else {
+                    before[i]
+                };
+                inputs[i].borrow_mut().probe(val);
+                values[mask] = eval();
+            }
+
+            let factorial: Vec<f64> = (0..=n)
+                .scan(1.0, |acc, k| {
+                    *acc *= k.max(1) as f64;
+                    Some(*acc)
+                })
+                .collect();
+            let contributions = (0..n)
+                .map(|i| {
+                    (0..1usize << n)
+                        .filter(|mask| mask & (1 << i) == 0)
+                        .map(|mask| {
+                            let size = mask.count_ones() as usize;
+                            let weight = factorial[size] * factorial[n - size - 1] / factorial[n];
+                            weight * (values[mask | (1 << i)] - values[mask])
+                        })
+                        .sum()
+                })
+                .collect();
+            (values[(1 << n) - 1], contributions)
+        }
+    };
+
+    inputs
+        .iter()
+        .zip(&original)
+        .for_each(|(input, val)| input.borrow_mut().probe(*val));
+    let explained: f64 = contributions.iter().sum();
+    Attribution {
+        before: base as f32,
+        after: end as f32,
+        contributions: contributions.iter().map(|c| *c as f32).collect(),
+        interaction: (end - base - explained) as f32,
+    }
+}
+
+impl Node {
+    /// Estimates derivatives of `root` by given inputs with central differences,
+    /// perturbing each input by `epsilon` scaled by its magnitude when above 1.
+    /// Inputs are perturbed one by one with `Input::probe`, so only subgraphs depending
+    /// on a perturbed input are recomputed. Input values are restored afterwards.
+    /// Perturbation violating input rules is replaced by current value, falling back
+    /// to one-sided difference, or `NaN` if rules reject both directions.
+    pub fn sensitivity<C: Computable + ?Sized>(
+        root: &Rc<RefCell<C>>,
+        inputs: &[Rc<RefCell<dyn Input>>],
+        epsilon: f32,
+    ) -> Vec<f32> {
+        inputs
+            .iter()
+            .map(|input| {
+                let x = input.borrow_mut().compute();
+                let h = epsilon * x.abs().max(1.0);
+                let within = |val: f32| if input.borrow().accepts(val) { val } else { x };
+                let (hi, lo) = (within(x + h), within(x - h));
+                if hi == lo {
+                    return f32::NAN;
+                }
+                input.borrow_mut().probe(hi);
+                let up = root.borrow_mut().compute();
+                input.borrow_mut().probe(lo);
+                let down = root.borrow_mut().compute();
+                input.borrow_mut().probe(x);
+                (up - down) / (hi - lo)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::{InputNode, Rule},
+        node::Node,
+    };
+
+    #[test]
+    fn quantiles() {
+        let uniform = Distribution::Uniform {
+            low: 2.0,
+            high: 4.0,
+        };
+        assert_eq!(uniform.quantile(0.25), 2.5);
+
+        let normal = Distribution::Normal {
+            mean: 1.0,
+            std_dev: 2.0,
+        };
+        assert_eq!(normal.quantile(0.5), 1.0);
+        assert!((normal.quantile(0.975) - (1.0 + 2.0 * 1.959964)).abs() < 1e-4);
+        assert!((normal.quantile(0.01) - (1.0 - 2.0 * 2.326348)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn halton() {
+        assert_eq!(primes(5), vec![2, 3, 5, 7, 11]);
+        assert_eq!(radical_inverse(1, 2), 0.5);
+        assert_eq!(radical_inverse(3, 2), 0.75);
+        assert!((radical_inverse(5, 3) - 7.0 / 9.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn additive_model() {
+        let x1 = InputNode::from_val(0.5);
+        let x2 = InputNode::from_val(0.5);
+        let x3 = InputNode::from_val(0.5);
+        let root = Node::add_var(vec![
+            x1.clone(),
+            Node::mul(Node::constant(2.0), x2.clone()),
+            Node::mul(Node::constant(0.0), x3.clone()),
+        ]);
+        let uniform = Distribution::Uniform {
+            low: 0.0,
+            high: 1.0,
+        };
+
+        let indices = sobol(
+            &root,
+            &[(x1.clone(), uniform), (x2, uniform), (x3, uniform)],
+            4096,
+        );
+        let expected = [0.2, 0.8, 0.0];
+        for (i, expected) in expected.iter().enumerate() {
+            assert!((indices.first_order[i] - expected).abs() < 0.02);
+            assert!((indices.total[i] - expected).abs() < 0.02);
+        }
+        assert!((indices.variance - 5.0 / 12.0).abs() < 0.01);
+        assert_eq!(x1.borrow_mut().compute(), 0.5);
+    }
+
+    #[test]
+    fn interaction() {
+        let x1 = InputNode::from_val(0.0);
+        let x2 = InputNode::from_val(0.0);
+        let root = Node::mul(x1.clone(), x2.clone());
+        let normal = Distribution::Normal {
+            mean: 0.0,
+            std_dev: 1.0,
+        };
+
+        // with zero means, product variance is caused by interaction only
+        let indices = sobol(&root, &[(x1, normal), (x2, normal)], 4096);
+        for i in 0..2 {
+            assert!(indices.first_order[i].abs() < 0.05);
+            assert!((indices.total[i] - 1.0).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn sobol_progress() {
+        let x1 = InputNode::from_val(0.0);
+        let root = Node::sin(x1.clone());
+        let uniform = Distribution::Uniform {
+            low: 0.0,
+            high: 1.0,
+        };
+
+        let mut reported = vec![];
+        let indices = sobol_with_progress(&root, &[(x1, uniform)], 100, |progress| {
+            assert_eq!(progress.total, 100);
+            reported.push(progress.completed);
+            progress.completed < 10
+        });
+        assert_eq!(reported, (1..=10).collect::<Vec<_>>());
+        assert_eq!(indices.samples, 10);
+        assert!(indices.variance > 0.0);
+    }
+
+    #[test]
+    fn attribution_one_at_a_time() {
+        let x1 = InputNode::from_val(7.0);
+        let x2 = InputNode::from_val(7.0);
+        let root = Node::mul(x1.clone(), x2.clone());
+
+        let attribution = attribute_delta(
+            &root,
+            &[x1.clone(), x2],
+            &[1.0, 2.0],
+            &[3.0, 5.0],
+            AttributionMethod::OneAtATime,
+        );
+        assert_eq!(attribution.before, 2.0);
+        assert_eq!(attribution.after, 15.0);
+        assert_eq!(attribution.contributions, vec![4.0, 3.0]);
+        assert_eq!(attribution.interaction, 6.0);
+        assert_eq!(x1.borrow_mut().compute(), 7.0);
+    }
+
+    #[test]
+    fn attribution_shapley() {
+        let x1 = InputNode::from_val(0.0);
+        let x2 = InputNode::from_val(0.0);
+        let x3 = InputNode::from_val(0.0);
+        let root = Node::add(Node::mul(x1.clone(), x2.clone()), x3.clone());
+
+        let attribution = attribute_delta(
+            &root,
+            &[x1, x2, x3],
+            &[1.0, 2.0, 0.0],
+            &[3.0, 5.0, 1.0],
+            AttributionMethod::Shapley,
+        );
+        assert_eq!(attribution.before, 2.0);
+        assert_eq!(attribution.after, 16.0);
+        // interaction of x1 and x2 is split evenly between them
+        assert_eq!(attribution.contributions, vec![7.0, 6.0, 1.0]);
+        assert_eq!(attribution.interaction, 0.0);
+    }
+
+    #[test]
+    fn sensitivity() {
+        let x = InputNode::from_val(2.0);
+        let y = InputNode::from_val(0.5);
+        let root = Node::mul(Node::pow(x.clone(), 2.0), Node::sin(y.clone()));
+        let derivatives = Node::sensitivity(&root, &[x.clone(), y.clone()], 1e-3);
+        let grad = crate::grad::Gradient::of(&root);
+        assert!((derivatives[0] - grad.wrt(&x)).abs() < 1e-3);
+        assert!((derivatives[1] - grad.wrt(&y)).abs() < 1e-3);
+        assert_eq!(x.borrow_mut().compute(), 2.0);
+
+        // perturbation below tolerance still moves the input
+        let sets = Rc::new(RefCell::new(0));
+        let counter = sets.clone();
+        x.borrow_mut().set_tolerance(0.01);
+        x.borrow_mut()
+            .on_set(move |_, _| *counter.borrow_mut() += 1);
+        let derivatives = Node::sensitivity(&root, &[x.clone()], 1e-3);
+        assert!((derivatives[0] - grad.wrt(&x)).abs() < 1e-3);
+        assert_eq!(*sets.borrow(), 0);
+
+        // one-sided difference at the boundary of allowed range
+        let z = InputNode::from_val(1.0);
+        z.borrow_mut().add_rule(Rule::Range(0.0, 1.0));
+        let root = Node::pow(z.clone(), 2.0);
+        let derivatives = Node::sensitivity(&root, &[z.clone()], 1e-3);
+        assert!((derivatives[0] - 2.0).abs() < 1e-2);
+        assert_eq!(z.borrow().value(), 1.0);
+    }
+}
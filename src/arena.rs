@@ -0,0 +1,844 @@
+//! Arena-backed graph implementation.
+//!
+//! Nodes are stored in a single `Vec` owned by the `Graph` and addressed by `NodeId`,
+//! so building and dropping large graphs doesn't allocate every node separately.
+//! Storage of removed nodes is recycled for new ones.
+//! Computation doesn't recurse, so arbitrarily deep graphs can be computed.
+
+use crate::{
+    node::FnOp,
+    ops::{CustomOp, DivPolicy, Op, Operation, Reduction, Rounding},
+    smallvec::SmallVec,
+};
+
+/// Index of a node inside its `Graph`.
+/// Ids of removed nodes are never reused, as each slot reuse bumps its generation,
+/// and slots whose generation runs out are retired instead of reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: usize,
+    generation: u32,
+}
+
+/// Content of a graph node.
+enum Kind {
+    /// Slot is free for reuse.
+    Vacant,
+    /// Input value.
+    Input(f32),
+    /// Operation over other nodes of the same graph.
+    Operation(Operation<NodeId>),
+}
+
+/// Storage slot of a single node.
+struct Slot {
+    kind: Kind,
+    /// Generation of node currently stored in this slot.
+    generation: u32,
+    /// Cached result.
+    cache: Option<f32>,
+    /// Holds ids of nodes that depend from this node.
+    dependencies: SmallVec<NodeId, 2>,
+}
+
+/// Graph which owns storage for all its nodes.
+#[derive(Default)]
+pub struct Graph {
+    slots: Vec<Slot>,
+    /// Indices of vacant slots.
+    free: Vec<usize>,
+    /// Amount of vacant slots retired, since their generation ran out.
+    retired: usize,
+}
+
+impl Graph {
+    /// Creates empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates empty graph with storage preallocated for `capacity` nodes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::default(),
+            retired: 0,
+        }
+    }
+
+    /// Returns amount of nodes in graph.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len() - self.retired
+    }
+
+    /// Returns `true` if graph has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns amount of nodes graph can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    /// Returns `true` if `id` refers to a node of this graph which wasn't removed.
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.slots.get(id.index).map_or(false, |slot| {
+            slot.generation == id.generation && !matches!(slot.kind, Kind::Vacant)
+        })
+    }
+
+    /// Returns slot of a live node.
+    fn slot(&self, id: NodeId) -> &Slot {
+        assert!(self.contains(id), "node {:?} was removed", id);
+        &self.slots[id.index]
+    }
+
+    /// Returns mutable slot of a live node.
+    fn slot_mut(&mut self, id: NodeId) -> &mut Slot {
+        assert!(self.contains(id), "node {:?} was removed", id);
+        &mut self.slots[id.index]
+    }
+
+    /// Stores new node, reusing a vacant slot if there is one, and returns its id.
+    /// Operands are checked before a slot is taken, so failed push leaves graph intact.
+    fn push(&mut self, kind: Kind) -> NodeId {
+        if let Kind::Operation(opp) = &kind {
+            for x in opp.operands() {
+                assert!(self.contains(*x), "node {:?} was removed", x);
+            }
+        }
+        let id = match self.free.pop() {
+            Some(index) => NodeId {
+                index,
+                generation: self.slots[index].generation,
+            },
+            None => {
+                self.slots.push(Slot {
+                    kind: Kind::Vacant,
+                    generation: 0,
+                    cache: None,
+                    dependencies: SmallVec::default(),
+                });
+                NodeId {
+                    index: self.slots.len() - 1,
+                    generation: 0,
+                }
+            }
+        };
+        if let Kind::Operation(opp) = &kind {
+            for x in opp.operands() {
+                self.slot_mut(*x).dependencies.push(id);
+            }
+        }
+        self.slots[id.index].kind = kind;
+        id
+    }
+
+    /// Removes node which no other node depends from, making its storage available for reuse.
+    /// Returns `false` if node has dependencies or was already removed.
+    pub fn remove(&mut self, id: NodeId) -> bool {
+        if !self.contains(id) || !self.slots[id.index].dependencies.is_empty() {
+            return false;
+        }
+        let kind = std::mem::replace(&mut self.slots[id.index].kind, Kind::Vacant);
+        self.vacate(id.index);
+        if let Kind::Operation(opp) = kind {
+            for x in opp.operands() {
+                self.slots[x.index].dependencies.retain(|d| *d != id);
+            }
+        }
+        true
+    }
+
+    /// Removes all nodes, keeping allocated storage for reuse.
+    pub fn clear(&mut self) {
+        self.free.clear();
+        for index in (0..self.slots.len()).rev() {
+            let slot = &mut self.slots[index];
+            slot.dependencies.clear();
+            match slot.kind {
+                Kind::Vacant if slot.generation == u32::MAX => {}
+                Kind::Vacant => self.free.push(index),
+                _ => self.vacate(index),
+            }
+        }
+    }
+
+    /// Empties slot, making it available for reuse unless its generation ran out,
+    /// in which case the slot is retired.
+    fn vacate(&mut self, index: usize) {
+        let slot = &mut self.slots[index];
+        slot.kind = Kind::Vacant;
+        slot.cache = None;
+        match slot.generation.checked_add(1) {
+            Some(generation) => {
+                slot.generation = generation;
+                self.free.push(index);
+            }
+            None => self.retired += 1,
+        }
+    }
+
+    /// Adds input node with given value.
+    pub fn input(&mut self, val: f32) -> NodeId {
+        self.push(Kind::Input(val))
+    }
+
+    /// Adds node holding constant value.
+    pub fn constant(&mut self, val: f32) -> NodeId {
+        self.push(Kind::Operation(Operation::Const(val)))
+    }
+
+    /// Adds node computing user-defined operation over given nodes.
+    pub fn from_op(&mut self, op: Box<dyn Op<f32> + Send + Sync>, operands: Vec<NodeId>) -> NodeId {
+        self.push(Kind::Operation(Operation::Custom(
+            CustomOp(op.into()),
+            operands,
+        )))
+    }
+
+    /// Adds node applying closure to value of given node.
+    pub fn unary_fn(
+        &mut self,
+        x: NodeId,
+        f: impl Fn(f32) -> f32 + Send + Sync + 'static,
+    ) -> NodeId {
+        self.nary_fn(vec![x], move |vals| f(vals[0]))
+    }
+
+    /// Adds node applying closure to values of given nodes.
+    pub fn binary_fn(
+        &mut self,
+        x: NodeId,
+        y: NodeId,
+        f: impl Fn(f32, f32) -> f32 + Send + Sync + 'static,
+    ) -> NodeId {
+        self.nary_fn(vec![x, y], move |vals| f(vals[0], vals[1]))
+    }
+
+    /// Adds node applying closure to values of variable amount of nodes.
+    pub fn nary_fn(
+        &mut self,
+        args: Vec<NodeId>,
+        f: impl Fn(&[f32]) -> f32 + Send + Sync + 'static,
+    ) -> NodeId {
+        let n = args.len();
+        self.from_op(Box::new(FnOp(f, n)), args)
+    }
+
+    /// Adds node for sum of two nodes.
+    pub fn add(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Add(x, y)))
+    }
+
+    /// Adds node for sum of variable amount of nodes.
+    pub fn add_var(&mut self, args: Vec<NodeId>) -> NodeId {
+        self.push(Kind::Operation(Operation::AddVar(args)))
+    }
+
+    /// Adds node for subtraction of two nodes.
+    pub fn sub(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Sub(x, y)))
+    }
+
+    /// Adds node for multiplication of two nodes.
+    pub fn mul(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Mul(x, y)))
+    }
+
+    /// Adds node for product of variable amount of nodes.
+    pub fn mul_var(&mut self, args: Vec<NodeId>) -> NodeId {
+        self.push(Kind::Operation(Operation::MulVar(args)))
+    }
+
+    /// Adds node reducing variable amount of nodes to single value, see `Node::reduce`.
+    pub fn reduce(&mut self, args: Vec<NodeId>, reduction: Reduction) -> NodeId {
+        match reduction {
+            Reduction::Sum => self.add_var(args),
+            Reduction::Prod => self.mul_var(args),
+            _ => self.push(Kind::Operation(Operation::Reduce(args, reduction))),
+        }
+    }
+
+    /// Adds node for division of two nodes, producing `inf` or `NaN` on division by zero.
+    pub fn div(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.div_with_policy(x, y, DivPolicy::default())
+    }
+
+    /// Adds node for division of two nodes with given division by zero policy.
+    pub fn div_with_policy(&mut self, x: NodeId, y: NodeId, policy: DivPolicy) -> NodeId {
+        self.push(Kind::Operation(Operation::Div(x, y, policy)))
+    }
+
+    /// Adds node for negation of given node.
+    pub fn neg(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Neg(x)))
+    }
+
+    /// Adds node for reciprocal of given node.
+    pub fn recip(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Recip(x)))
+    }
+
+    /// Adds node passing value of given node through, but not its gradient.
+    pub fn detach(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Detach(x)))
+    }
+
+    /// Adds node for length of hypotenuse with legs of two nodes.
+    pub fn hypot(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Hypot(x, y)))
+    }
+
+    /// Adds node for angle of point with coordinates computed by two nodes, `y` first.
+    pub fn atan2(&mut self, y: NodeId, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Atan2(y, x)))
+    }
+
+    /// Adds node for remainder of division of first node by second, with sign of the first.
+    pub fn rem(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Rem(x, y)))
+    }
+
+    /// Adds node for Euclidean norm of variable amount of nodes.
+    pub fn norm(&mut self, args: Vec<NodeId>) -> NodeId {
+        self.push(Kind::Operation(Operation::Norm(args)))
+    }
+
+    /// Adds node for logarithm of sum of exponents of variable amount of nodes.
+    pub fn logsumexp(&mut self, args: Vec<NodeId>) -> NodeId {
+        self.push(Kind::Operation(Operation::LogSumExp(args)))
+    }
+
+    /// Adds node for component `i` of softmax over variable amount of nodes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of `args` bounds.
+    pub fn softmax_component(&mut self, args: Vec<NodeId>, i: usize) -> NodeId {
+        assert!(i < args.len(), "softmax component index out of bounds");
+        self.push(Kind::Operation(Operation::Softmax(args, i)))
+    }
+
+    /// Adds node selecting `x` if `cond` is positive and `y` otherwise.
+    /// Only the taken branch is computed.
+    pub fn lazy_select(&mut self, cond: NodeId, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Select(cond, x, y)))
+    }
+
+    /// Adds node selecting `if_true` if boolean `cond` is true and `if_false` otherwise,
+    /// same as `lazy_select`. Only the taken branch is computed.
+    pub fn select(&mut self, cond: NodeId, if_true: NodeId, if_false: NodeId) -> NodeId {
+        self.lazy_select(cond, if_true, if_false)
+    }
+
+    /// Adds node taking value of the first piece whose breakpoint is greater than `x`,
+    /// or of `default` if there's no such piece. Only the taken piece is computed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if breakpoints aren't increasing.
+    pub fn piecewise(&mut self, x: NodeId, pieces: &[(f32, NodeId)], default: NodeId) -> NodeId {
+        assert!(
+            pieces.windows(2).all(|w| w[0].0 < w[1].0),
+            "breakpoints of piecewise aren't increasing"
+        );
+        self.push(Kind::Operation(Operation::Piecewise(
+            x,
+            pieces.to_vec(),
+            default,
+        )))
+    }
+
+    /// Adds node returning `value` if `cond` is positive and `fallback` otherwise.
+    /// `value` isn't computed while `cond` isn't positive.
+    pub fn guard(&mut self, cond: NodeId, value: NodeId, fallback: f32) -> NodeId {
+        let fallback = self.constant(fallback);
+        self.lazy_select(cond, value, fallback)
+    }
+
+    /// Adds node for exponentiation of node to given exponent.
+    pub fn pow(&mut self, x: NodeId, pow: f32) -> NodeId {
+        self.push(Kind::Operation(Operation::Pow(x, pow)))
+    }
+
+    /// Adds node for sin value of given node.
+    pub fn sin(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Sin(x)))
+    }
+
+    /// Adds node for arcsine of given node.
+    pub fn asin(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Asin(x)))
+    }
+
+    /// Adds node for arccosine of given node.
+    pub fn acos(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Acos(x)))
+    }
+
+    /// Adds node for arctangent of given node.
+    pub fn atan(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Atan(x)))
+    }
+
+    /// Adds node for cosine of given node.
+    pub fn cos(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Cos(x)))
+    }
+
+    /// Adds node for tangent of given node.
+    pub fn tan(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Tan(x)))
+    }
+
+    /// Adds node for exponent of given node.
+    pub fn exp(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Exp(x)))
+    }
+
+    /// Adds node for natural logarithm of given node.
+    pub fn ln(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Ln(x)))
+    }
+
+    /// Adds node for logarithm of given node to given base.
+    pub fn log(&mut self, x: NodeId, base: f32) -> NodeId {
+        self.push(Kind::Operation(Operation::Log(x, base)))
+    }
+
+    /// Adds node rounding given node to multiple of `step` with given rounding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if step isn't positive and finite.
+    pub fn quantize(&mut self, x: NodeId, step: f32, rounding: Rounding) -> NodeId {
+        assert!(
+            step > 0.0 && step.is_finite(),
+            "quantization step isn't positive"
+        );
+        self.push(Kind::Operation(Operation::Quantize(x, step, rounding)))
+    }
+
+    /// Adds node computing polynomial of given node with given coefficients,
+    /// lowest degree first.
+    pub fn polynomial(&mut self, x: NodeId, coeffs: Vec<f32>) -> NodeId {
+        self.push(Kind::Operation(Operation::Polynomial(x, coeffs)))
+    }
+
+    /// Adds node rounding given node to given amount of decimal places with given rounding.
+    pub fn round_dp(&mut self, x: NodeId, decimals: i32, rounding: Rounding) -> NodeId {
+        self.quantize(x, 10f64.powi(-decimals) as f32, rounding)
+    }
+
+    /// Adds node for square root of given node.
+    pub fn sqrt(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Sqrt(x)))
+    }
+
+    /// Adds node for absolute value of given node.
+    pub fn abs(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Abs(x)))
+    }
+
+    /// Adds node for sign of given node, `-1`, `0` or `1`.
+    pub fn sign(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Sign(x)))
+    }
+
+    /// Adds node for fractional part of given node, with sign of the node.
+    pub fn fract(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Fract(x)))
+    }
+
+    /// Adds node for largest integer not greater than given node.
+    pub fn floor(&mut self, x: NodeId) -> NodeId {
+        self.quantize(x, 1.0, Rounding::Floor)
+    }
+
+    /// Adds node for smallest integer not less than given node.
+    pub fn ceil(&mut self, x: NodeId) -> NodeId {
+        self.quantize(x, 1.0, Rounding::Ceiling)
+    }
+
+    /// Adds node for given node rounded to nearest integer, ties away from zero.
+    pub fn round(&mut self, x: NodeId) -> NodeId {
+        self.quantize(x, 1.0, Rounding::HalfAwayFromZero)
+    }
+
+    /// Adds node for smaller of two nodes.
+    pub fn min(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Min(x, y)))
+    }
+
+    /// Adds node for larger of two nodes.
+    pub fn max(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Max(x, y)))
+    }
+
+    /// Adds node limiting value of node to range between values of `lo` and `hi`.
+    pub fn clamp(&mut self, x: NodeId, lo: NodeId, hi: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Clamp(x, lo, hi)))
+    }
+
+    /// Adds node checking whether `x` is less than `y`.
+    pub fn lt(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Lt(x, y)))
+    }
+
+    /// Adds node checking whether `x` is less than or equal to `y`.
+    pub fn le(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Le(x, y)))
+    }
+
+    /// Adds node checking whether `x` is greater than `y`.
+    pub fn gt(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Gt(x, y)))
+    }
+
+    /// Adds node checking whether `x` is greater than or equal to `y`.
+    pub fn ge(&mut self, x: NodeId, y: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Ge(x, y)))
+    }
+
+    /// Adds node checking whether `x` and `y` differ at most by `tolerance`.
+    pub fn eq(&mut self, x: NodeId, y: NodeId, tolerance: f32) -> NodeId {
+        self.push(Kind::Operation(Operation::Eq(x, y, tolerance)))
+    }
+
+    /// Adds node for hyperbolic tangent of given node.
+    pub fn tanh(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Tanh(x)))
+    }
+
+    /// Adds node for logistic function of given node.
+    pub fn sigmoid(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Sigmoid(x)))
+    }
+
+    /// Adds node for rectified linear unit `max(x, 0)` of given node.
+    pub fn relu(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Relu(x)))
+    }
+
+    /// Adds node for softplus `ln(1 + exp(x))` of given node.
+    pub fn softplus(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Softplus(x)))
+    }
+
+    /// Adds node for `exp(x) - 1` of given node.
+    pub fn expm1(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::ExpM1(x)))
+    }
+
+    /// Adds node for `ln(1 + x)` of given node.
+    pub fn ln_1p(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Ln1p(x)))
+    }
+
+    /// Adds node for error function of given node.
+    pub fn erf(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Erf(x)))
+    }
+
+    /// Adds node for complementary error function of given node.
+    pub fn erfc(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::Erfc(x)))
+    }
+
+    /// Adds node for standard normal cumulative distribution function of given node.
+    pub fn normal_cdf(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::NormalCdf(x)))
+    }
+
+    /// Adds node for standard normal probability density function of given node.
+    pub fn normal_pdf(&mut self, x: NodeId) -> NodeId {
+        self.push(Kind::Operation(Operation::NormalPdf(x)))
+    }
+
+    /// Sets new value to input node and resets cache for all dependable nodes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` doesn't refer to an input node.
+    pub fn set(&mut self, id: NodeId, val: f32) {
+        match &mut self.slot_mut(id).kind {
+            Kind::Input(v) => *v = val,
+            _ => panic!("node {:?} is not an input", id),
+        }
+        self.reset_dependencies(id);
+    }
+
+    /// Returns computation result of given node.
+    /// Takes cached value if available, otherwise computes the result and stores it in cache.
+    /// Uncached nodes it depends on are computed first, operands before nodes using them.
+    pub fn compute(&mut self, id: NodeId) -> f32 {
+        assert!(self.contains(id), "node {:?} was removed", id);
+        // nodes along with flag whether their operands were already pushed
+        let mut stack = vec![(id, false)];
+        while let Some((id, expanded)) = stack.pop() {
+            let slot = &self.slots[id.index];
+            let opp = match &slot.kind {
+                Kind::Operation(opp) if slot.cache.is_none() => opp,
+                _ => continue,
+            };
+            if !expanded {
+                stack.push((id, true));
+                stack.extend(opp.eager_operands().into_iter().rev().map(|x| (*x, false)));
+                continue;
+            }
+            // selected branch of lazy operation may still be uncomputed
+            let mut missing = None;
+            let val = opp.eval(|x| {
+                self.value(*x).unwrap_or_else(|| {
+                    missing.get_or_insert(*x);
+                    0.0
+                })
+            });
+            match missing {
+                Some(x) => stack.extend([(id, true), (x, false)]),
+                None => self.slots[id.index].cache = Some(val),
+            }
+        }
+        self.value(id).expect("node is computed")
+    }
+
+    /// Returns value of input or cached result of operation.
+    fn value(&self, id: NodeId) -> Option<f32> {
+        let slot = &self.slots[id.index];
+        match slot.kind {
+            Kind::Input(val) => Some(val),
+            _ => slot.cache,
+        }
+    }
+
+    /// Resets cache of all nodes depending from given node.
+    fn reset_dependencies(&mut self, id: NodeId) {
+        let mut stack: Vec<NodeId> = self.slot(id).dependencies.iter().copied().collect();
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.slots[id.index];
+            if slot.cache.take().is_some() {
+                stack.extend(slot.dependencies.iter().copied());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(x: f32, precision: u32) -> f32 {
+        let m = 10i32.pow(precision) as f32;
+        (x * m).round() / m
+    }
+
+    #[test]
+    fn compute_set() {
+        let mut g = Graph::new();
+        let x1 = g.input(1.0);
+        let x2 = g.input(2.0);
+        let x3 = g.input(3.0);
+        let p = g.pow(x3, 2.0);
+        let m = g.mul(x2, p);
+        let x = g.add(x1, m);
+        assert_eq!(g.compute(x), 19.0);
+        assert_eq!(g.slots[x.index].cache, Some(19.0));
+
+        g.set(x1, 4.0);
+        g.set(x2, 5.0);
+        g.set(x3, 6.0);
+        assert_eq!(g.slots[x.index].cache, None);
+        assert_eq!(g.compute(x), 184.0);
+    }
+
+    #[test]
+    fn all_operations() {
+        let mut g = Graph::with_capacity(8);
+        let x1 = g.input(2.0);
+        let s = g.sin(x1);
+        let p = g.pow(s, 3.0);
+        let m = g.mul(x1, p);
+        let d = g.sub(x1, m);
+        let v = g.add_var(vec![x1, x1, d]);
+        let x = g.add(x1, v);
+        assert_eq!(g.len(), 7);
+        let c = g.constant(1.5);
+        assert_eq!(g.compute(c), 1.5);
+        assert_eq!(round(g.compute(x), 5), 6.49635);
+
+        g.set(x1, 3.0);
+        assert_eq!(round(g.compute(x), 5), 11.99157);
+
+        let prod = g.mul_var(vec![x1, c, c]);
+        let mean = g.reduce(vec![x1, c, prod], Reduction::Mean);
+        let inv = g.recip(c);
+        let neg = g.neg(inv);
+        assert_eq!(g.compute(neg), -1.0 / 1.5);
+        assert_eq!(g.compute(prod), 6.75);
+        assert_eq!(g.compute(mean), 3.75);
+    }
+
+    #[test]
+    fn set_keeps_unrelated_cache() {
+        let mut g = Graph::new();
+        let x1 = g.input(1.0);
+        let x2 = g.input(2.0);
+        let a = g.sin(x1);
+        let b = g.sin(x2);
+        g.compute(a);
+        g.compute(b);
+
+        g.set(x1, 0.0);
+        assert_eq!(g.slots[a.index].cache, None);
+        assert!(g.slots[b.index].cache.is_some());
+    }
+
+    #[test]
+    fn lazy_select() {
+        let mut g = Graph::new();
+        let cond = g.input(1.0);
+        let x1 = g.input(2.0);
+        let x2 = g.input(3.0);
+        let a = g.sin(x1);
+        let b = g.sin(x2);
+        let x = g.lazy_select(cond, a, b);
+        assert_eq!(g.compute(x), 2f32.sin());
+        assert_eq!(g.slots[b.index].cache, None);
+
+        // untaken branch change leaves selection cached
+        g.set(x2, 4.0);
+        assert!(g.slots[x.index].cache.is_some());
+
+        g.set(cond, -1.0);
+        assert_eq!(g.compute(x), 4f32.sin());
+    }
+
+    #[test]
+    fn guard() {
+        let mut g = Graph::new();
+        let x1 = g.input(0.5);
+        let value = g.asin(x1);
+        let x = g.guard(x1, value, -1.0);
+        assert_eq!(g.compute(x), 0.5f32.asin());
+
+        g.set(x1, -2.0);
+        assert_eq!(g.slots[value.index].cache, None);
+        assert_eq!(g.compute(x), -1.0);
+        assert_eq!(g.slots[value.index].cache, None);
+    }
+
+    #[test]
+    fn remove_recycles_storage() {
+        let mut g = Graph::new();
+        let x1 = g.input(1.0);
+        let x2 = g.input(2.0);
+        let x = g.add(x1, x2);
+        assert!(!g.remove(x1));
+        assert!(g.remove(x));
+        assert!(!g.remove(x));
+        assert!(!g.contains(x));
+        assert_eq!(g.len(), 2);
+        assert!(g.slots[x1.index].dependencies.is_empty());
+
+        let y = g.mul(x1, x2);
+        assert_eq!(y.index, x.index);
+        assert_ne!(y, x);
+        assert_eq!(g.slots.len(), 3);
+        assert_eq!(g.compute(y), 2.0);
+
+        // slot with exhausted generation isn't reused
+        g.slots[y.index].generation = u32::MAX;
+        let y = NodeId {
+            generation: u32::MAX,
+            ..y
+        };
+        assert!(g.remove(y));
+        assert_eq!(g.len(), 2);
+        let z = g.mul(x1, x2);
+        assert_ne!(z.index, y.index);
+        g.clear();
+        assert!(g.is_empty());
+        assert_ne!(g.input(1.0).index, y.index);
+    }
+
+    #[test]
+    fn clear_retains_capacity() {
+        let mut g = Graph::with_capacity(16);
+        let x1 = g.input(1.0);
+        let x2 = g.input(2.0);
+        let x = g.add(x1, x2);
+        g.compute(x);
+        let capacity = g.capacity();
+
+        g.clear();
+        assert!(g.is_empty());
+        assert!(!g.contains(x1));
+        assert_eq!(g.capacity(), capacity);
+
+        let y1 = g.input(3.0);
+        let y2 = g.input(4.0);
+        let y = g.add(y1, y2);
+        assert_eq!(y1.index, x1.index);
+        assert_eq!(g.slots.len(), 3);
+        assert_eq!(g.compute(y), 7.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn compute_removed() {
+        let mut g = Graph::new();
+        let x1 = g.input(1.0);
+        let x = g.sin(x1);
+        g.remove(x);
+        g.compute(x);
+    }
+
+    #[test]
+    fn push_removed_operand() {
+        let mut g = Graph::new();
+        let x1 = g.input(1.0);
+        let x = g.sin(x1);
+        g.remove(x);
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| g.sin(x)));
+        assert!(res.is_err());
+        assert_eq!(g.free, vec![x.index]);
+        let y = g.cos(x1);
+        assert_eq!(y.index, x.index);
+        assert_eq!(g.compute(y), 1.0f32.cos());
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_non_input() {
+        let mut g = Graph::new();
+        let x1 = g.input(1.0);
+        let x = g.sin(x1);
+        g.set(x, 1.0);
+    }
+
+    #[test]
+    fn custom_and_deep() {
+        let mut g = Graph::new();
+        let x = g.input(2.0);
+        let y = g.input(3.0);
+        let f = g.binary_fn(x, y, |x, y| x * 10.0 + y);
+        let cond = g.gt(x, y);
+        let root = g.select(cond, x, f);
+        assert_eq!(g.compute(root), 23.0);
+        g.set(x, 4.0);
+        assert_eq!(g.compute(root), 4.0);
+        // taken branch only
+        assert_eq!(g.slots[f.index].cache, None);
+
+        let mut deep = x;
+        for _ in 0..100_000 {
+            let one = g.constant(1.0);
+            deep = g.add(deep, one);
+        }
+        assert_eq!(g.compute(deep), 100_004.0);
+        g.set(x, 0.0);
+        assert_eq!(g.compute(deep), 100_000.0);
+    }
+}
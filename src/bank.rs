@@ -0,0 +1,118 @@
+//! Banks of indexed inputs fed from slices, e.g. telemetry frames.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    float::Float,
+    input::{InputNode, ValidationError},
+    reactive,
+};
+
+/// Fixed amount of inputs addressed by index, set together from slices of values.
+#[derive(Clone)]
+pub struct InputBank<T: Float = f32> {
+    inputs: Vec<Rc<RefCell<InputNode<T>>>>,
+}
+
+impl<T: Float> InputBank<T> {
+    /// Creates bank of `n` inputs holding zero.
+    pub fn new(n: usize) -> Self {
+        Self::from_values(&vec![T::ZERO; n])
+    }
+
+    /// Creates bank of inputs holding given values, which also become their defaults.
+    pub fn from_values(vals: &[T]) -> Self {
+        Self {
+            inputs: vals.iter().map(|val| InputNode::from_val(*val)).collect(),
+        }
+    }
+
+    /// Returns amount of inputs.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Returns true if bank has no inputs.
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Returns input with given index, for use as operand.
+    ///
+    /// # Panics
+    ///
+    /// Panics if index is out of bounds.
+    pub fn get(&self, i: usize) -> Rc<RefCell<InputNode<T>>> {
+        self.inputs[i].clone()
+    }
+
+    /// Returns current values of inputs.
+    pub fn values(&self) -> Vec<T> {
+        self.inputs.iter().map(|x| x.borrow().value()).collect()
+    }
+
+    /// Sets inputs to given values, touching only inputs whose value differs, and returns
+    /// amount of changed ones. Caches of dependents are reset in a single pass once all
+    /// values are stored, like by `Graph::set_many`. If some value violates rules of its
+    /// input, no input is changed. Subscribed nodes are recomputed afterwards, see `reactive`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if amount of values differs from amount of inputs.
+    pub fn set_slice(&self, vals: &[T]) -> Result<usize, ValidationError> {
+        assert_eq!(
+            vals.len(),
+            self.inputs.len(),
+            "amount of values doesn't match input bank"
+        );
+        let changed: Vec<_> = self
+            .inputs
+            .iter()
+            .zip(vals)
+            .filter(|(input, val)| input.borrow().value() != **val)
+            .collect();
+        for (input, val) in &changed {
+            input.borrow().validate(**val)?;
+        }
+        let old: Vec<_> = changed
+            .iter()
+            .map(|(input, val)| input.borrow_mut().replace(**val))
+            .collect();
+        for ((input, _), old) in changed.iter().zip(old) {
+            // listeners are called once input is released, so they can compute dependents
+            let calls = input.borrow_mut().invalidate(old);
+            calls.into_iter().for_each(|call| call());
+        }
+        reactive::flush();
+        Ok(changed.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::Rule,
+        node::{Computable, Handle, Node},
+    };
+
+    #[test]
+    fn sets_changed() {
+        let bank = InputBank::new(3);
+        let sum: Handle = Node::add_var((0..3).map(|i| bank.get(i) as Handle).collect());
+        let doubled = Node::mul(bank.get(2), Node::constant(2.0f32));
+        assert_eq!(bank.set_slice(&[1.0, 2.0, 3.0]), Ok(3));
+        assert_eq!(sum.borrow_mut().compute(), 6.0);
+        assert_eq!(doubled.borrow_mut().compute(), 6.0);
+
+        assert_eq!(bank.set_slice(&[1.0, 5.0, 3.0]), Ok(1));
+        assert!(sum.borrow().is_dirty());
+        assert!(!doubled.borrow().is_dirty());
+        assert_eq!(sum.borrow_mut().compute(), 9.0);
+
+        bank.get(0).borrow_mut().add_rule(Rule::Range(0.0, 1.0));
+        assert!(bank.set_slice(&[2.0, 0.0, 0.0]).is_err());
+        assert_eq!(bank.values(), vec![1.0, 5.0, 3.0]);
+        assert_eq!((bank.len(), bank.is_empty()), (3, false));
+    }
+}
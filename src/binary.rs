@@ -0,0 +1,444 @@
+//! Compact binary format of graphs, for caching large generated graphs between runs.
+//!
+//! Format is versioned and consists of little-endian fields:
+//!
+//! * header: magic `TEZA` and `u16` version,
+//! * operations table: `u32` count followed by names of operations used by graph,
+//! * node records: `u32` count followed by nodes ordered so operands precede nodes using
+//!   them; input records hold current and default value, operation records hold index into
+//!   operations table, float parameters and indices of operand nodes,
+//! * inputs and outputs: `u32` count followed by names with indices of their nodes.
+//!
+//! Names are stored as `u32` length and UTF-8 bytes, floats as `f64`. Operations are
+//! referenced by name rather than by their position in `Operation`, so files stay valid
+//! as operations are added. Graphs with user-defined operations can't be stored.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    float::Float,
+    graph::Graph,
+    ops::{DivPolicy, Operation, Reduction, Rounding},
+    snapshot::{Snapshot, SnapshotNode},
+};
+
+const MAGIC: &[u8; 4] = b"TEZA";
+const VERSION: u16 = 1;
+
+const INPUT: u8 = 0;
+const OPERATION: u8 = 1;
+
+/// Division policies and roundings by their stored indices.
+const POLICIES: [DivPolicy; 3] = [DivPolicy::Propagate, DivPolicy::Error, DivPolicy::Clamp];
+const ROUNDINGS: [Rounding; 6] = [
+    Rounding::HalfEven,
+    Rounding::HalfAwayFromZero,
+    Rounding::TowardZero,
+    Rounding::AwayFromZero,
+    Rounding::Floor,
+    Rounding::Ceiling,
+];
+
+impl<T: Float> Graph<T> {
+    /// Writes structure and input values of graph in binary format, see `binary` module.
+    /// Fails with `InvalidInput` if graph contains user-defined operations.
+    /// Writer isn't buffered, wrap it in `BufWriter` when writing to file.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn save(&self, mut writer: impl Write) -> io::Result<()> {
+        let snapshot = Snapshot::of(self);
+        let mut ops: Vec<&'static str> = Vec::new();
+        let mut op_ids: HashMap<&'static str, u32> = HashMap::new();
+        let mut records = Vec::with_capacity(snapshot.nodes.len() * 16);
+        for node in &snapshot.nodes {
+            match node {
+                SnapshotNode::Input { value, default } => {
+                    records.push(INPUT);
+                    put_float(&mut records, *value);
+                    put_float(&mut records, *default);
+                }
+                SnapshotNode::Operation(opp) => {
+                    let params = params(opp)?;
+                    let name = opp.name();
+                    let id = *op_ids.entry(name).or_insert_with(|| {
+                        ops.push(name);
+                        ops.len() as u32 - 1
+                    });
+                    records.push(OPERATION);
+                    put_u32(&mut records, id);
+                    put_u32(&mut records, params.len() as u32);
+                    for param in params {
+                        records.extend_from_slice(&param.to_le_bytes());
+                    }
+                    let operands = opp.operands();
+                    put_u32(&mut records, operands.len() as u32);
+                    for x in operands {
+                        put_u32(&mut records, *x as u32);
+                    }
+                }
+            }
+        }
+
+        let mut buf = Vec::with_capacity(records.len() + 64);
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&VERSION.to_le_bytes());
+        put_u32(&mut buf, ops.len() as u32);
+        for name in ops {
+            put_str(&mut buf, name);
+        }
+        put_u32(&mut buf, snapshot.nodes.len() as u32);
+        buf.extend_from_slice(&records);
+        for named in [&snapshot.inputs, &snapshot.outputs] {
+            put_u32(&mut buf, named.len() as u32);
+            for (name, id) in named {
+                put_str(&mut buf, name);
+                put_u32(&mut buf, *id as u32);
+            }
+        }
+        writer.write_all(&buf)
+    }
+
+    /// Reads graph written by `save`. Fails with `InvalidData` if data is malformed,
+    /// of unsupported version or refers to unknown operations.
+    pub fn load(mut reader: impl Read) -> io::Result<Self> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        let mut r = Reader { buf: &buf, pos: 0 };
+        if r.bytes(4)? != MAGIC {
+            return Err(invalid("not a graph file"));
+        }
+        let version = u16::from_le_bytes([r.u8()?, r.u8()?]);
+        if version != VERSION {
+            return Err(invalid(format!("unsupported version {}", version)));
+        }
+        let ops = (0..r.u32()?)
+            .map(|_| r.str())
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let count = r.u32()? as usize;
+        let mut nodes = Vec::with_capacity(count.min(buf.len()));
+        for id in 0..count {
+            let node = match r.u8()? {
+                INPUT => SnapshotNode::Input {
+                    value: T::from_f64(r.f64()?),
+                    default: T::from_f64(r.f64()?),
+                },
+                OPERATION => {
+                    let name = ops
+                        .get(r.u32()? as usize)
+                        .ok_or_else(|| invalid("operation index out of range"))?;
+                    let params = (0..r.u32()?)
+                        .map(|_| r.f64())
+                        .collect::<io::Result<Vec<_>>>()?;
+                    let operands = (0..r.u32()?)
+                        .map(|_| r.index(id))
+                        .collect::<io::Result<Vec<_>>>()?;
+                    let opp = operation(name, &params, &operands)
+                        .ok_or_else(|| invalid(format!("malformed {} record", name)))?;
+                    SnapshotNode::Operation(opp)
+                }
+                tag => return Err(invalid(format!("unknown record kind {}", tag))),
+            };
+            nodes.push(node);
+        }
+
+        let mut named = || {
+            (0..r.u32()?)
+                .map(|_| Ok((r.str()?, r.index(count)?)))
+                .collect::<io::Result<Vec<_>>>()
+        };
+        let inputs = named()?;
+        let outputs = named()?;
+        if inputs
+            .iter()
+            .any(|(_, id)| !matches!(nodes[*id], SnapshotNode::Input { .. }))
+        {
+            return Err(invalid("input refers to operation"));
+        }
+        let snapshot = Snapshot {
+            nodes,
+            inputs,
+            outputs,
+        };
+        snapshot
+            .try_restore()
+            .map_err(|err| invalid(err.to_string()))
+    }
+}
+
+/// Returns parameters of operation which aren't operands, as stored.
+fn params<H, T: Float>(opp: &Operation<H, T>) -> io::Result<Vec<f64>> {
+    use Operation::*;
+
+    Ok(match opp {
+        Const(val) => vec![val.to_f64()],
+        Div(_, _, policy) => vec![POLICIES.iter().position(|p| p == policy).unwrap() as f64],
+        Pow(_, param) | Log(_, param) | Eq(_, _, param) => vec![param.to_f64()],
+        Softmax(_, i) => vec![*i as f64],
+        Piecewise(_, pieces, _) => pieces.iter().map(|(b, _)| b.to_f64()).collect(),
+        Polynomial(_, coeffs) => coeffs.iter().map(|c| c.to_f64()).collect(),
+        Quantize(_, step, rounding) => vec![
+            step.to_f64(),
+            ROUNDINGS.iter().position(|r| r == rounding).unwrap() as f64,
+        ],
+        Custom(op, _) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("user-defined operation {} can't be stored", op.0.name()),
+            ))
+        }
+        _ => Vec::new(),
+    })
+}
+
+/// Constructors of operations over one or two operands.
+type Unary<T> = fn(usize) -> Operation<usize, T>;
+type Binary<T> = fn(usize, usize) -> Operation<usize, T>;
+
+/// Builds operation with given name from stored parameters and operands,
+/// returning `None` if they don't fit the operation.
+fn operation<T: Float>(name: &str, params: &[f64], args: &[usize]) -> Option<Operation<usize, T>> {
+    use Operation::*;
+
+    let unary: Option<Unary<T>> = match name {
+        "neg" => Some(Neg),
+        "recip" => Some(Recip),
+        "detach" => Some(Detach),
+        "sin" => Some(Sin),
+        "asin" => Some(Asin),
+        "acos" => Some(Acos),
+        "atan" => Some(Atan),
+        "cos" => Some(Cos),
+        "tan" => Some(Tan),
+        "exp" => Some(Exp),
+        "ln" => Some(Ln),
+        "sqrt" => Some(Sqrt),
+        "abs" => Some(Abs),
+        "sign" => Some(Sign),
+        "fract" => Some(Fract),
+        "tanh" => Some(Tanh),
+        "sigmoid" => Some(Sigmoid),
+        "relu" => Some(Relu),
+        "softplus" => Some(Softplus),
+        "expm1" => Some(ExpM1),
+        "ln_1p" => Some(Ln1p),
+        "erf" => Some(Erf),
+        "erfc" => Some(Erfc),
+        "normal_cdf" => Some(NormalCdf),
+        "normal_pdf" => Some(NormalPdf),
+        _ => None,
+    };
+    let binary: Option<Binary<T>> = match name {
+        "add" => Some(Add),
+        "sub" => Some(Sub),
+        "mul" => Some(Mul),
+        "min" => Some(Min),
+        "max" => Some(Max),
+        "lt" => Some(Lt),
+        "le" => Some(Le),
+        "gt" => Some(Gt),
+        "ge" => Some(Ge),
+        "hypot" => Some(Hypot),
+        "atan2" => Some(Atan2),
+        "rem" => Some(Rem),
+        _ => None,
+    };
+    let reduction = match name {
+        "reduce_sum" => Some(Reduction::Sum),
+        "reduce_prod" => Some(Reduction::Prod),
+        "reduce_min" => Some(Reduction::Min),
+        "reduce_max" => Some(Reduction::Max),
+        "reduce_mean" => Some(Reduction::Mean),
+        _ => None,
+    };
+    let float = |val: &f64| T::from_f64(*val);
+    let args_vec = args.to_vec();
+
+    Some(match (name, params, args) {
+        (_, [], [x]) if unary.is_some() => unary?(*x),
+        (_, [], [x, y]) if binary.is_some() => binary?(*x, *y),
+        (_, [], _) if reduction.is_some() => Reduce(args_vec, reduction?),
+        ("constant", [val], []) => Const(float(val)),
+        ("add_var", [], _) => AddVar(args_vec),
+        ("mul_var", [], _) => MulVar(args_vec),
+        ("norm", [], _) => Norm(args_vec),
+        ("logsumexp", [], _) => LogSumExp(args_vec),
+        ("softmax_component", [i], _) if index(*i).map_or(false, |i| i < args.len()) => {
+            Softmax(args_vec, index(*i)?)
+        }
+        ("div", [policy], [x, y]) => Div(*x, *y, *POLICIES.get(index(*policy)?)?),
+        ("pow", [exp], [x]) => Pow(*x, float(exp)),
+        ("log", [base], [x]) => Log(*x, float(base)),
+        ("eq", [tol], [x, y]) => Eq(*x, *y, float(tol)),
+        ("clamp", [], [x, lo, hi]) => Clamp(*x, *lo, *hi),
+        ("polynomial", coeffs, [x]) => Polynomial(*x, coeffs.iter().map(float).collect()),
+        ("lazy_select", [], [cond, x, y]) => Select(*cond, *x, *y),
+        ("quantize", [step, rounding], [x]) => {
+            Quantize(*x, float(step), *ROUNDINGS.get(index(*rounding)?)?)
+        }
+        ("piecewise", breakpoints, [x, pieces @ .., last]) if pieces.len() == breakpoints.len() => {
+            let pieces = breakpoints.iter().map(float).zip(pieces.iter().copied());
+            Piecewise(*x, pieces.collect(), *last)
+        }
+        _ => return None,
+    })
+}
+
+/// Converts stored index to `usize`, if it's a non-negative integer.
+fn index(val: f64) -> Option<usize> {
+    if val >= 0.0 && val.fract() == 0.0 && val <= u32::MAX as f64 {
+        Some(val as usize)
+    } else {
+        None
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn put_u32(buf: &mut Vec<u8>, val: u32) {
+    buf.extend_from_slice(&val.to_le_bytes());
+}
+
+fn put_float<T: Float>(buf: &mut Vec<u8>, val: T) {
+    buf.extend_from_slice(&val.to_f64().to_le_bytes());
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    put_u32(buf, s.len() as u32);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Cursor over loaded bytes, failing with `InvalidData` at their end.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        let bytes = self.bytes(8)?;
+        Ok(f64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("name isn't UTF-8"))
+    }
+
+    /// Reads index of node, which must be below `bound`.
+    fn index(&mut self, bound: usize) -> io::Result<usize> {
+        let id = self.u32()? as usize;
+        if id < bound {
+            Ok(id)
+        } else {
+            Err(invalid("node index out of range"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::Input,
+        node::{Handle, Node},
+    };
+
+    fn graph() -> Graph {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        let y = g.add_input("y", 2.0);
+        x.borrow_mut().set(3.0);
+        let shared: Handle = Node::sin(Node::mul(x.clone(), y.clone()));
+        g.add_output(
+            "a",
+            Node::add(shared.clone(), Node::pow(shared.clone(), 2.0)),
+        );
+        g.add_output(
+            "b",
+            Node::quantize(
+                Node::div_with_policy(x.clone(), y.clone(), DivPolicy::Clamp),
+                0.25,
+                Rounding::Floor,
+            ),
+        );
+        g.add_output("c", Node::softmax_component(vec![shared, x, y], 1));
+        g
+    }
+
+    #[test]
+    fn round_trip() {
+        let g = graph();
+        let mut buf = Vec::new();
+        g.save(&mut buf).unwrap();
+        let loaded = Graph::load(buf.as_slice()).unwrap();
+        assert_eq!(Snapshot::of(&loaded), Snapshot::of(&g));
+        assert_eq!(loaded.outputs(), g.outputs());
+
+        loaded.reset_inputs();
+        assert_eq!(loaded.input("x").unwrap().borrow().value(), 1.0);
+    }
+
+    #[test]
+    fn malformed() {
+        let mut buf = Vec::new();
+        graph().save(&mut buf).unwrap();
+        let err = |bytes: &[u8]| Graph::<f32>::load(bytes).err().map(|err| err.kind());
+
+        assert_eq!(err(&buf[..buf.len() - 1]), Some(io::ErrorKind::InvalidData));
+        assert_eq!(err(b"ZETA\x01\x00"), Some(io::ErrorKind::InvalidData));
+        let mut future = buf.clone();
+        future[4] = 2;
+        assert_eq!(err(&future), Some(io::ErrorKind::InvalidData));
+
+        // softmax component index beyond its 3 operands
+        let record: Vec<u8> = [
+            &1u32.to_le_bytes()[..],
+            &1f64.to_le_bytes(),
+            &3u32.to_le_bytes(),
+        ]
+        .concat();
+        let at = buf
+            .windows(record.len())
+            .position(|w| w == record.as_slice())
+            .unwrap();
+        let mut corrupted = buf.clone();
+        corrupted[at + 4..at + 12].copy_from_slice(&3f64.to_le_bytes());
+        let err = Graph::<f32>::load(corrupted.as_slice()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "malformed softmax_component record");
+
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        g.add_output("y", Node::unary_fn(x, |v| v * 2.0));
+        assert_eq!(
+            g.save(&mut Vec::new()).err().map(|err| err.kind()),
+            Some(io::ErrorKind::InvalidInput)
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Assertion nodes checking that intermediate values stay within expected ranges.
+//!
+//! Assertions pass value of their operand through. `try_compute` fails when the value
+//! is out of range, naming the offending expression, while `compute` passes it unchecked.
+//! Dependents cached by `compute` return their results without reaching assertions.
+//! Ranges of inputs are enforced when they're set, see `Rule::Range`.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    error::GraphError,
+    eval::Evaluation,
+    float::Float,
+    infix::to_string_expr,
+    node::{
+        dependents, remove_dependency, reset_dependencies, Computable, Dependencies, Handle, Kind,
+        Node,
+    },
+};
+
+/// Node passing value of its operand through, asserting it's within inclusive range.
+pub struct AssertNode<T: Float = f32> {
+    source: Handle<T>,
+    lo: T,
+    hi: T,
+    /// Cached result.
+    cache: Option<T>,
+    /// Holds references to nodes that depend from this node.
+    dependencies: Dependencies<T>,
+}
+
+impl<T: Float> Node<T> {
+    /// Builds node passing value of given node through, asserting it's between `lo` and `hi`
+    /// inclusive when computed with `try_compute`, see `bounds` module.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lo` is greater than `hi`.
+    pub fn assert_range(x: Handle<T>, lo: T, hi: T) -> Rc<RefCell<AssertNode<T>>> {
+        assert!(lo <= hi, "assertion range is empty");
+        let obj = Rc::new(RefCell::new(AssertNode {
+            source: x.clone(),
+            lo,
+            hi,
+            cache: None,
+            dependencies: Dependencies::default(),
+        }));
+        x.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+}
+
+impl<T: Float> AssertNode<T> {
+    /// Returns asserted range.
+    pub fn range(&self) -> (T, T) {
+        (self.lo, self.hi)
+    }
+
+    /// Fails if value is out of range, naming the operand by its label or expression.
+    fn check(&self, val: T) -> Result<T, GraphError> {
+        if self.lo <= val && val <= self.hi {
+            return Ok(val);
+        }
+        let node = match self.source.borrow().label() {
+            Some(label) => label.to_string(),
+            None => to_string_expr(&self.source),
+        };
+        Err(GraphError::OutOfRange {
+            node,
+            value: val.to_f64(),
+            lo: self.lo.to_f64(),
+            hi: self.hi.to_f64(),
+        })
+    }
+}
+
+impl<T: Float> Computable<T> for AssertNode<T> {
+    /// Returns value of operand, even if it's out of range.
+    fn compute(&mut self) -> T {
+        if let Some(val) = self.cache {
+            return val;
+        }
+        let val = self.source.borrow_mut().compute();
+        self.cache = Some(val);
+        val
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependencies.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Handle<T>> {
+        dependents(&self.dependencies)
+    }
+
+    fn reset_cache(&mut self) {
+        if self.cache.take().is_none() {
+            return;
+        }
+        reset_dependencies(&mut self.dependencies);
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.cache.is_none()
+    }
+
+    fn operands(&self) -> Vec<Handle<T>> {
+        vec![self.source.clone()]
+    }
+
+    fn kind(&self) -> Kind {
+        self.source.borrow().kind()
+    }
+
+    /// Returns value of operand, failing if it's out of range.
+    /// Cached values are checked too, since `compute` caches them unchecked.
+    fn try_compute(&mut self, eval: &mut Evaluation) -> Result<T, GraphError> {
+        let val = match self.cache {
+            Some(val) => val,
+            None => self.source.borrow_mut().try_compute(eval)?,
+        };
+        self.cache = Some(val);
+        self.check(val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Input, InputNode};
+
+    #[test]
+    fn asserts_range() {
+        let x = InputNode::with_label(0.5f32, "x");
+        let ratio: Handle = Node::assert_range(Node::mul(x.clone(), Node::constant(2.0)), 0.0, 1.0);
+        let root: Handle = Node::sqrt(ratio.clone());
+        assert_eq!(
+            root.borrow_mut().try_compute(&mut Evaluation::new()),
+            Ok(1.0)
+        );
+
+        x.borrow_mut().set(0.75);
+        let err = root
+            .borrow_mut()
+            .try_compute(&mut Evaluation::new())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "1.5 of x * 2 is outside of [0, 1]");
+        assert_eq!(root.borrow_mut().compute(), 1.5f32.sqrt());
+        // cached values are checked too
+        assert!(ratio
+            .borrow_mut()
+            .try_compute(&mut Evaluation::new())
+            .is_err());
+
+        let labeled: Handle = Node::assert_range(x, -1.0, 0.0);
+        assert!(matches!(
+            labeled.borrow_mut().try_compute(&mut Evaluation::new()),
+            Err(GraphError::OutOfRange { node, .. }) if node == "x"
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+//! Gradient checkpointing of memory-heavy subgraphs.
+//!
+//! Checkpointed subgraph is replaced by single node keeping only its result. Values
+//! of intermediate nodes exist only while the node computes, and are recomputed when
+//! `grad` asks for partial derivatives, trading computation for memory.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
+
+use crate::{
+    grad,
+    node::{node_addr, Handle, Node},
+    ops::{CustomOp, Op, Operation},
+};
+
+/// Subgraph compiled into instruction tape over values of its leaves.
+pub(crate) struct Segment {
+    /// Operations in evaluation order, result of each one is stored after
+    /// values of leaves and results of preceding operations.
+    tape: Vec<Operation<usize>>,
+    /// Slot of subgraph root.
+    output: usize,
+}
+
+impl Segment {
+    /// Computes values of leaves and all operations.
+    fn forward(&self, leaves: &[f32]) -> Vec<f32> {
+        let mut slab = Vec::with_capacity(leaves.len() + self.tape.len());
+        slab.extend_from_slice(leaves);
+        for opp in &self.tape {
+            let val = opp.eval(|i| slab[*i]);
+            slab.push(val);
+        }
+        slab
+    }
+
+    /// Computes result along with its partial derivatives with respect to leaves.
+    pub(crate) fn gradient(&self, leaves: &[f32]) -> (f32, Vec<f32>) {
+        let slab = self.forward(leaves);
+        let mut adjoints = vec![0.0; slab.len()];
+        adjoints[self.output] = 1.0;
+        for (i, opp) in self.tape.iter().enumerate().rev() {
+            let adjoint = adjoints[leaves.len() + i];
+            if adjoint == 0.0 {
+                continue;
+            }
+            for (x, d) in grad::partials(opp, |x| slab[*x]) {
+                adjoints[*x] += adjoint * d;
+            }
+        }
+        adjoints.truncate(leaves.len());
+        (slab[self.output], adjoints)
+    }
+
+    /// Compiles subgraph with given root, returning it with its leaves, which are
+    /// nodes without operation such as inputs. Subgraph must be acyclic.
+    pub(crate) fn compile(root: &Handle) -> (Self, Vec<Handle>) {
+        let mut leaves: Vec<Handle> = Vec::new();
+        let mut ops: Vec<Operation<usize>> = Vec::new();
+        // indices of nodes among leaves or operations, by address
+        let mut slots: HashMap<usize, Slot> = HashMap::new();
+        // nodes along with flag whether their operands were already pushed
+        let mut stack = vec![(root.clone(), false)];
+        while let Some((node, expanded)) = stack.pop() {
+            let addr = node_addr(&node);
+            if slots.contains_key(&addr) {
+                continue;
+            }
+            let opp = node.borrow().operation().cloned();
+            match opp {
+                Some(opp) if !expanded => {
+                    stack.push((node, true));
+                    stack.extend(opp.operands().into_iter().map(|x| (x.clone(), false)));
+                }
+                Some(opp) => {
+                    ops.push(opp.map_operands(|x| node_addr(x)));
+                    slots.insert(addr, Slot::Operation(ops.len() - 1));
+                }
+                None => {
+                    leaves.push(node);
+                    slots.insert(addr, Slot::Leaf(leaves.len() - 1));
+                }
+            }
+        }
+        let n = leaves.len();
+        // operation results are stored after values of leaves
+        let slot = |addr: &usize| match slots[addr] {
+            Slot::Leaf(i) => i,
+            Slot::Operation(i) => n + i,
+        };
+        let segment = Self {
+            tape: ops.iter().map(|opp| opp.map_operands(slot)).collect(),
+            output: slot(&node_addr(root)),
+        };
+        (segment, leaves)
+    }
+}
+
+impl Op for Segment {
+    fn name(&self) -> &'static str {
+        "checkpoint"
+    }
+
+    fn eval(&self, operands: &[f32]) -> f32 {
+        self.forward(operands)[self.output]
+    }
+
+    /// Recomputes intermediate values and propagates derivatives back through the tape.
+    fn partials(&self, operands: &[f32]) -> Vec<f32> {
+        self.gradient(operands).1
+    }
+}
+
+/// Position of subgraph node among leaves or operations.
+#[derive(Clone, Copy)]
+enum Slot {
+    Leaf(usize),
+    Operation(usize),
+}
+
+impl Node {
+    /// Builds node computing subgraph with given root, keeping only its result.
+    /// Nodes without operation, such as inputs, become operands of the checkpoint,
+    /// other nodes of the subgraph aren't referenced by it, so they're freed
+    /// once dropped by caller. Subgraph must be acyclic, see `Graph::validate`.
+    pub fn checkpoint(root: &Handle) -> Rc<RefCell<Node>> {
+        let (segment, leaves) = Segment::compile(root);
+        Node::new(Operation::Custom(CustomOp(Arc::new(segment)), leaves))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grad::Gradient,
+        input::{Input, InputNode},
+        node::Computable,
+    };
+
+    #[test]
+    fn recomputes_for_gradient() {
+        let x = InputNode::from_val(0.5);
+        let y = InputNode::from_val(2.0);
+        let shared = Node::mul(x.clone(), y.clone());
+        let root: Handle = Node::add(
+            Node::sin(shared.clone()),
+            Node::mul(shared.clone(), Node::exp(x.clone())),
+        );
+        let weak = Rc::downgrade(&shared);
+        let checkpoint = Node::checkpoint(&root);
+        assert_eq!(checkpoint.borrow().operands().len(), 2);
+
+        let expected = Gradient::of(&root);
+        let root_val = root.borrow_mut().compute();
+        drop((root, shared));
+        assert!(weak.upgrade().is_none());
+
+        let loss = Node::mul(checkpoint.clone(), Node::constant(2.0));
+        assert_eq!(checkpoint.borrow_mut().compute(), root_val);
+        let grad = Gradient::of(&loss);
+        for input in [&x, &y] {
+            let d = 2.0 * expected.wrt(input);
+            assert!((grad.wrt(input) - d).abs() < 1e-5 * d.abs());
+        }
+
+        // inputs keep invalidating the checkpoint
+        x.borrow_mut().set(1.0);
+        assert!(checkpoint.borrow().is_dirty());
+        assert_eq!(
+            checkpoint.borrow_mut().compute(),
+            2.0f32.sin() + 2.0 * 1f32.exp()
+        );
+    }
+}
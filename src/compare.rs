@@ -0,0 +1,201 @@
+//! Comparison of graphs, e.g. to check that simplification or a hand refactor
+//! preserved the model.
+//!
+//! Graphs are walked in parallel from outputs with the same name, pairing operands
+//! by position. Paired nodes with different operations are reported as changed,
+//! nodes left without pair as removed from the first graph or added to the second one.
+
+use std::collections::HashSet;
+
+use crate::{
+    eval::Evaluation,
+    float::Float,
+    graph::Graph,
+    node::{node_addr, post_order, Handle},
+};
+
+/// Structural difference of two nodes, see `Graph::diff`.
+#[derive(Clone)]
+pub enum NodeChange<T: Float = f32> {
+    /// Node of the second graph without counterpart in the first one.
+    Added(Handle<T>),
+    /// Node of the first graph without counterpart in the second one.
+    Removed(Handle<T>),
+    /// Nodes at the same position computing different operations.
+    Changed(Handle<T>, Handle<T>),
+}
+
+/// Differing values of nodes at the same position, see `Graph::diff`.
+#[derive(Clone)]
+pub struct ValueDelta<T: Float = f32> {
+    /// Node of the first graph.
+    pub a: Handle<T>,
+    /// Node of the second graph.
+    pub b: Handle<T>,
+    /// Value of node of the first graph.
+    pub a_value: T,
+    /// Value of node of the second graph.
+    pub b_value: T,
+}
+
+/// Differences of two graphs returned by `Graph::diff`.
+#[derive(Clone)]
+pub struct GraphDiff<T: Float = f32> {
+    /// Structural changes, in order of discovery.
+    pub changes: Vec<NodeChange<T>>,
+    /// Values differing by more than tolerance, empty if some graph fails to evaluate.
+    pub deltas: Vec<ValueDelta<T>>,
+}
+
+impl<T: Float> GraphDiff<T> {
+    /// Returns true if graphs have the same structure and values within tolerance.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.deltas.is_empty()
+    }
+}
+
+impl<T: Float> Graph<T> {
+    /// Compares graphs walking them in parallel from outputs with the same name, see
+    /// `compare` module. If both graphs evaluate, paired nodes whose values differ by
+    /// more than `tolerance` are reported too. Outputs present in one graph only count
+    /// as removed or added along with nodes not paired otherwise. Graphs must be acyclic.
+    pub fn diff(a: &Graph<T>, b: &Graph<T>, tolerance: T) -> GraphDiff<T> {
+        let evaluates =
+            a.try_outputs(Evaluation::new()).is_ok() && b.try_outputs(Evaluation::new()).is_ok();
+        let mut changes = Vec::new();
+        let mut deltas = Vec::new();
+        let mut visited = HashSet::new();
+        let (mut paired_a, mut paired_b) = (HashSet::new(), HashSet::new());
+        let (mut unpaired_a, mut unpaired_b) = (Vec::new(), Vec::new());
+
+        let mut stack: Vec<(Handle<T>, Handle<T>)> = Vec::new();
+        for (name, x) in a.output_nodes() {
+            match b.output(name) {
+                Some(y) => stack.push((x.clone(), y.clone())),
+                None => unpaired_a.push(x.clone()),
+            }
+        }
+        unpaired_b.extend(
+            b.output_nodes()
+                .filter(|(name, _)| a.output(name).is_none())
+                .map(|(_, y)| y.clone()),
+        );
+
+        while let Some((x, y)) = stack.pop() {
+            if !visited.insert((node_addr(&x), node_addr(&y))) {
+                continue;
+            }
+            paired_a.insert(node_addr(&x));
+            paired_b.insert(node_addr(&y));
+            if signature(&x) != signature(&y) {
+                changes.push(NodeChange::Changed(x.clone(), y.clone()));
+            }
+            if evaluates {
+                let a_value = x.borrow_mut().compute();
+                let b_value = y.borrow_mut().compute();
+                if differ(a_value, b_value, tolerance) {
+                    deltas.push(ValueDelta {
+                        a: x.clone(),
+                        b: y.clone(),
+                        a_value,
+                        b_value,
+                    });
+                }
+            }
+            let (xs, ys) = (x.borrow().operands(), y.borrow().operands());
+            let n = xs.len().min(ys.len());
+            unpaired_a.extend(xs[n..].iter().cloned());
+            unpaired_b.extend(ys[n..].iter().cloned());
+            stack.extend(xs.into_iter().zip(ys).rev());
+        }
+
+        for (roots, paired, change) in [
+            (&unpaired_a, &paired_a, NodeChange::Removed as fn(_) -> _),
+            (&unpaired_b, &paired_b, NodeChange::Added),
+        ] {
+            let mut seen = paired.clone();
+            for root in roots {
+                changes.extend(
+                    post_order(root)
+                        .into_iter()
+                        .filter(|node| seen.insert(node_addr(node)))
+                        .map(change),
+                );
+            }
+        }
+        GraphDiff { changes, deltas }
+    }
+}
+
+/// Describes node without its operands, leaves by their label and kind only.
+fn signature<T: Float>(node: &Handle<T>) -> String {
+    let node = node.borrow();
+    match node.operation() {
+        // float parameters have no `Eq`, their debug form tells them apart
+        Some(opp) => format!("{:?}", opp.map_operands(|_| ())),
+        None => format!(
+            "leaf {:?} {:?} {}",
+            node.label(),
+            node.kind(),
+            node.operands().len()
+        ),
+    }
+}
+
+/// Returns true if values differ by more than tolerance, `NaN`s being equal to each other.
+fn differ<T: Float>(a: T, b: T, tolerance: T) -> bool {
+    let (a, b) = (a.to_f64(), b.to_f64());
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => false,
+        (false, false) => (a - b).abs() > tolerance.to_f64(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Node;
+
+    #[test]
+    fn diffs() {
+        let build = |scale: f32, refactored: bool| {
+            let mut g = Graph::new();
+            let x = g.add_input("x", 2.0f32);
+            let y = g.add_input("y", 3.0);
+            let sum: Handle = Node::add(x.clone(), y);
+            let scaled: Handle = if refactored {
+                Node::add(sum.clone(), sum)
+            } else {
+                Node::mul(sum, Node::constant(scale))
+            };
+            g.add_output("z", scaled);
+            g.add_output("w", Node::sin(x));
+            g
+        };
+        let a = build(2.0, false);
+        assert!(Graph::diff(&a, &build(2.0, false), 0.0).is_empty());
+
+        let scaled = Graph::diff(&a, &build(2.5, false), 0.1);
+        assert_eq!(scaled.changes.len(), 1);
+        assert!(matches!(scaled.changes[0], NodeChange::Changed(..)));
+        assert_eq!(scaled.deltas.len(), 2);
+        assert!(Graph::diff(&a, &build(2.5, false), 3.0).deltas.is_empty());
+
+        // equal outputs computed by another operation, over operands paired by position
+        let refactored = Graph::diff(&a, &build(2.0, true), 0.0);
+        assert_eq!(refactored.deltas.len(), 1);
+        assert_eq!(refactored.changes.len(), 2);
+        assert!(matches!(refactored.changes[0], NodeChange::Changed(..)));
+        assert!(matches!(refactored.changes[1], NodeChange::Changed(..)));
+
+        let mut b = build(2.0, false);
+        b.add_output("v", Node::cos(b.input("y").unwrap().clone()));
+        let added = Graph::diff(&a, &b, 0.0);
+        assert_eq!(added.changes.len(), 1);
+        assert!(matches!(&added.changes[0], NodeChange::Added(node)
+            if node_addr(node) == node_addr(b.output("v").unwrap())));
+        let removed = Graph::diff(&b, &a, 0.0);
+        assert!(matches!(removed.changes[..], [NodeChange::Removed(_)]));
+    }
+}
@@ -0,0 +1,323 @@
+//! Compilation of graphs into flat instruction tapes for fast repeated evaluation.
+//!
+//! Compiled graph stores values of all nodes in a single slab and evaluates instructions
+//! in topological order, without pointer chasing, borrow checks or allocation.
+//! It doesn't cache, each evaluation computes every instruction, including both
+//! branches of `lazy_select`.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    float::Float,
+    input::InputId,
+    node::{node_addr, Handle, Node},
+    ops::{self, Operation},
+};
+
+/// Graph compiled into instruction tape, detached from original nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledGraph<T: Float = f32> {
+    /// Operations in evaluation order, paired with slots receiving their results.
+    /// Operands reference slots of preceding instructions or inputs.
+    tape: Vec<(usize, Operation<usize, T>)>,
+    /// Values of all nodes.
+    slab: Vec<T>,
+    /// Slots of inputs, in order of their indices.
+    inputs: Vec<usize>,
+    /// Addresses of original input nodes, in order of their indices.
+    addrs: Vec<usize>,
+    /// Slot of root.
+    output: usize,
+}
+
+impl<T: Float> CompiledGraph<T> {
+    /// Compiles graph with given root.
+    /// Nodes without operation become inputs initialized with their current value,
+    /// so custom nodes are frozen at their current value.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn new(root: &Handle<T>) -> Self {
+        let mut graph = Self {
+            tape: Vec::new(),
+            slab: Vec::new(),
+            inputs: Vec::new(),
+            addrs: Vec::new(),
+            output: 0,
+        };
+        let mut slots = HashMap::new();
+        // nodes along with flag whether their operands were already pushed
+        let mut stack = vec![(root.clone(), false)];
+        while let Some((node, expanded)) = stack.pop() {
+            let addr = node_addr(&node);
+            if slots.contains_key(&addr) {
+                continue;
+            }
+            let opp = node.borrow().operation().cloned();
+            let slot = graph.slab.len();
+            match opp {
+                Some(opp) if !expanded => {
+                    stack.push((node, true));
+                    stack.extend(opp.operands().into_iter().map(|x| (x.clone(), false)));
+                    continue;
+                }
+                Some(opp) => {
+                    let opp = opp.map_operands(|x| slots[&node_addr(x)]);
+                    graph.slab.push(T::ZERO);
+                    graph.tape.push((slot, opp));
+                }
+                None => {
+                    let val = node.borrow_mut().compute();
+                    graph.slab.push(val);
+                    graph.inputs.push(slot);
+                    graph.addrs.push(addr);
+                }
+            }
+            slots.insert(addr, slot);
+        }
+        graph.output = slots[&node_addr(root)];
+        graph
+    }
+
+    /// Returns amount of inputs.
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Returns index of input compiled from given node, if it is part of the graph.
+    pub fn input_index<C: ?Sized>(&self, node: &Rc<RefCell<C>>) -> Option<usize> {
+        self.index_of(InputId::of(node))
+    }
+
+    /// Returns index of input with given identifier, if it is part of the graph.
+    pub fn index_of(&self, id: InputId) -> Option<usize> {
+        self.addrs.iter().position(|a| *a == id.0)
+    }
+
+    /// Sets value of input with given index.
+    pub fn set_input(&mut self, idx: usize, val: T) {
+        self.slab[self.inputs[idx]] = val;
+    }
+
+    /// Evaluates all instructions, returning value of root.
+    pub fn eval(&mut self) -> T {
+        for (slot, opp) in &self.tape {
+            let slab = &self.slab;
+            let val = match opp {
+                Operation::Norm(args) => ops::norm(|| args.iter().map(|i| slab[*i])),
+                Operation::LogSumExp(args) => ops::log_sum_exp(|| args.iter().map(|i| slab[*i])),
+                Operation::Softmax(args, i) => ops::softmax(|| args.iter().map(|i| slab[*i]), *i),
+                opp => opp.eval(|i| slab[*i]),
+            };
+            self.slab[*slot] = val;
+        }
+        self.slab[self.output]
+    }
+
+    /// Evaluates graph for each set of input values, given by input index.
+    /// Inputs without values keep their current value.
+    /// Panics if value vectors differ in length.
+    pub fn eval_batch(&mut self, inputs: &HashMap<usize, Vec<T>>) -> Vec<T> {
+        let len = inputs.values().map(Vec::len).next().unwrap_or(0);
+        assert!(
+            inputs.values().all(|vals| vals.len() == len),
+            "batch inputs differ in length"
+        );
+        (0..len)
+            .map(|i| {
+                for (idx, vals) in inputs {
+                    self.set_input(*idx, vals[i]);
+                }
+                self.eval()
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "simd")]
+impl<T: Float> CompiledGraph<T> {
+    /// Evaluates graph for each set of input values like `eval_batch`, computing `N` sets
+    /// at once. Arithmetic runs as straight loops over lanes, which compiler vectorizes,
+    /// other operations are computed lane by lane. Panics if `N` is zero.
+    pub fn eval_batch_lanes<const N: usize>(&mut self, inputs: &HashMap<usize, Vec<T>>) -> Vec<T> {
+        assert!(N > 0, "batch needs at least one lane");
+        let len = inputs.values().map(Vec::len).next().unwrap_or(0);
+        assert!(
+            inputs.values().all(|vals| vals.len() == len),
+            "batch inputs differ in length"
+        );
+        let mut lanes: Vec<[T; N]> = self.slab.iter().map(|val| [*val; N]).collect();
+        let mut res = Vec::with_capacity(len);
+        for start in (0..len).step_by(N) {
+            let count = N.min(len - start);
+            for (idx, vals) in inputs {
+                let slot = &mut lanes[self.inputs[*idx]];
+                slot[..count].copy_from_slice(&vals[start..start + count]);
+            }
+            self.eval_lanes(&mut lanes);
+            res.extend_from_slice(&lanes[self.output][..count]);
+        }
+        res
+    }
+
+    /// Evaluates all instructions over lanes of values.
+    fn eval_lanes<const N: usize>(&self, lanes: &mut [[T; N]]) {
+        for (slot, opp) in &self.tape {
+            let mut out = [T::ZERO; N];
+            match opp {
+                Operation::Add(x, y) => {
+                    let (x, y) = (&lanes[*x], &lanes[*y]);
+                    (0..N).for_each(|k| out[k] = x[k] + y[k]);
+                }
+                Operation::Sub(x, y) => {
+                    let (x, y) = (&lanes[*x], &lanes[*y]);
+                    (0..N).for_each(|k| out[k] = x[k] - y[k]);
+                }
+                Operation::Mul(x, y) => {
+                    let (x, y) = (&lanes[*x], &lanes[*y]);
+                    (0..N).for_each(|k| out[k] = x[k] * y[k]);
+                }
+                Operation::AddVar(args) => {
+                    for arg in args {
+                        let x = &lanes[*arg];
+                        (0..N).for_each(|k| out[k] = out[k] + x[k]);
+                    }
+                }
+                Operation::MulVar(args) => {
+                    out = [T::ONE; N];
+                    for arg in args {
+                        let x = &lanes[*arg];
+                        (0..N).for_each(|k| out[k] = out[k] * x[k]);
+                    }
+                }
+                opp => {
+                    for (k, out) in out.iter_mut().enumerate() {
+                        *out = match opp {
+                            Operation::Norm(args) => {
+                                ops::norm(|| args.iter().map(|i| lanes[*i][k]))
+                            }
+                            Operation::LogSumExp(args) => {
+                                ops::log_sum_exp(|| args.iter().map(|i| lanes[*i][k]))
+                            }
+                            Operation::Softmax(args, i) => {
+                                ops::softmax(|| args.iter().map(|i| lanes[*i][k]), *i)
+                            }
+                            opp => opp.eval(|i| lanes[*i][k]),
+                        };
+                    }
+                }
+            }
+            lanes[*slot] = out;
+        }
+    }
+}
+
+impl<T: Float> Node<T> {
+    /// Compiles graph with given root into instruction tape, see `CompiledGraph`.
+    pub fn compile(root: &Handle<T>) -> CompiledGraph<T> {
+        CompiledGraph::new(root)
+    }
+
+    /// Computes graph with given root for each set of input values.
+    /// Graph is compiled once and evaluated without touching original nodes,
+    /// so their values and caches stay intact.
+    /// Inputs without values keep their current value.
+    /// Panics if input isn't part of the graph or value vectors differ in length.
+    pub fn compute_batch(root: &Handle<T>, inputs: &HashMap<InputId, Vec<T>>) -> Vec<T> {
+        let mut compiled = CompiledGraph::new(root);
+        let inputs = inputs
+            .iter()
+            .map(|(id, vals)| {
+                let idx = compiled.index_of(*id).expect("input isn't part of graph");
+                (idx, vals.clone())
+            })
+            .collect();
+        compiled.eval_batch(&inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    #[test]
+    fn evaluation() {
+        let x = Node::input(0.5f32);
+        let y = Node::input(2.0f32);
+        let shared = Node::mul(x.clone(), y.clone());
+        let root: Handle = Node::add_var(vec![
+            Node::sin(shared.clone()),
+            Node::pow(shared.clone(), 2.0),
+            Node::norm(vec![x.clone(), y.clone()]),
+            Node::softmax_component(vec![shared.clone(), x.clone(), Node::constant(1.0)], 1),
+            Node::lazy_select(
+                Node::sub(x.clone(), y.clone()),
+                shared,
+                Node::logsumexp(vec![x.clone(), y.clone()]),
+            ),
+        ]);
+        let mut compiled = Node::compile(&root);
+        assert_eq!(compiled.input_count(), 2);
+        assert_eq!(compiled.eval(), root.borrow_mut().compute());
+
+        let (ix, iy) = (
+            compiled.input_index(&x).unwrap(),
+            compiled.input_index(&y).unwrap(),
+        );
+        for (vx, vy) in [(1.0, 3.0), (-2.0, 0.5), (4.0, 1.0)] {
+            compiled.set_input(ix, vx);
+            compiled.set_input(iy, vy);
+            x.borrow_mut().set(vx);
+            y.borrow_mut().set(vy);
+            assert_eq!(compiled.eval(), root.borrow_mut().compute());
+        }
+        assert_eq!(compiled.input_index(&Node::input(1.0f32)), None);
+    }
+
+    #[test]
+    fn batch() {
+        let x = Node::input(1.0f32);
+        let y = Node::input(10.0f32);
+        let root: Handle = Node::add(Node::mul(x.clone(), x.clone()), y.clone());
+        root.borrow_mut().compute();
+
+        let inputs = HashMap::from([(InputId::of(&x), vec![1.0, 2.0, 3.0])]);
+        assert_eq!(Node::compute_batch(&root, &inputs), vec![11.0, 14.0, 19.0]);
+        // original graph is untouched
+        assert!(!root.borrow().is_dirty());
+
+        let inputs = HashMap::from([
+            (InputId::of(&x), vec![0.0, 1.0]),
+            (InputId::of(&y), vec![5.0, 6.0]),
+        ]);
+        assert_eq!(Node::compute_batch(&root, &inputs), vec![5.0, 7.0]);
+        assert!(Node::compute_batch(&root, &HashMap::new()).is_empty());
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn lanes() {
+        let x = Node::input(1.0f32);
+        let y = Node::input(2.0f32);
+        let root: Handle = Node::add_var(vec![
+            Node::mul(x.clone(), y.clone()),
+            Node::sub(Node::sin(x.clone()), y.clone()),
+            Node::norm(vec![x.clone(), y]),
+        ]);
+        let mut compiled = Node::compile(&root);
+        let xs: Vec<f32> = (0..19).map(|i| i as f32 * 0.25).collect();
+        let inputs = HashMap::from([(compiled.input_index(&x).unwrap(), xs)]);
+        let expected = compiled.eval_batch(&inputs);
+        assert_eq!(compiled.eval_batch_lanes::<8>(&inputs), expected);
+        assert_eq!(compiled.eval_batch_lanes::<16>(&inputs), expected);
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    #[should_panic(expected = "batch needs at least one lane")]
+    fn no_lanes() {
+        let x = Node::input(1.0f32);
+        let mut compiled = Node::compile(&(x.clone() as Handle));
+        let inputs = HashMap::from([(compiled.input_index(&x).unwrap(), vec![2.0])]);
+        compiled.eval_batch_lanes::<0>(&inputs);
+    }
+}
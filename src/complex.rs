@@ -0,0 +1,451 @@
+//! Complex numbers and graphs computing them, e.g. for signal processing with `e^{ix}`.
+//!
+//! Complex values have no ordering, so they can't be computed by `Node`, which relies
+//! on comparisons for its operations. Complex graphs are built from `ComplexNode`s
+//! instead, caching results and invalidating dependents like `Node`s.
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    ops::{Add, Div, Mul, Neg, Sub},
+    rc::{Rc, Weak},
+};
+
+use crate::float::Float;
+
+/// Complex number `re + im * i`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex<T: Float = f32> {
+    /// Real part.
+    pub re: T,
+    /// Imaginary part.
+    pub im: T,
+}
+
+impl<T: Float> Complex<T> {
+    /// Creates complex number from its parts.
+    pub fn new(re: T, im: T) -> Self {
+        Self { re, im }
+    }
+
+    /// Creates real number.
+    pub fn real(re: T) -> Self {
+        Self::new(re, T::ZERO)
+    }
+
+    /// Returns imaginary unit.
+    pub fn i() -> Self {
+        Self::new(T::ZERO, T::ONE)
+    }
+
+    /// Creates complex number from its magnitude and argument.
+    pub fn from_polar(norm: T, arg: T) -> Self {
+        Self::new(norm * arg.cos(), norm * arg.sin())
+    }
+
+    /// Returns magnitude `|z|`.
+    pub fn norm(&self) -> T {
+        self.re.hypot(self.im)
+    }
+
+    /// Returns argument in `(-π, π]`.
+    pub fn arg(&self) -> T {
+        self.im.atan2(self.re)
+    }
+
+    /// Returns complex conjugate.
+    pub fn conj(&self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    /// Returns `e^z`.
+    pub fn exp(&self) -> Self {
+        Self::from_polar(self.re.exp(), self.im)
+    }
+
+    /// Returns principal value of natural logarithm.
+    pub fn ln(&self) -> Self {
+        Self::new(self.norm().ln(), self.arg())
+    }
+
+    /// Returns sine.
+    pub fn sin(&self) -> Self {
+        let (sinh, cosh) = sinh_cosh(self.im);
+        Self::new(self.re.sin() * cosh, self.re.cos() * sinh)
+    }
+
+    /// Returns cosine.
+    pub fn cos(&self) -> Self {
+        let (sinh, cosh) = sinh_cosh(self.im);
+        Self::new(self.re.cos() * cosh, -(self.re.sin() * sinh))
+    }
+
+    /// Returns principal value of `z^w`, zero for zero base.
+    pub fn pow(&self, exponent: Self) -> Self {
+        if self.re == T::ZERO && self.im == T::ZERO {
+            return Self::new(T::ZERO, T::ZERO);
+        }
+        (self.ln() * exponent).exp()
+    }
+
+    /// Returns principal square root.
+    pub fn sqrt(&self) -> Self {
+        Self::from_polar(self.norm().sqrt(), self.arg() / T::from_f64(2.0))
+    }
+}
+
+/// Returns hyperbolic sine and cosine.
+fn sinh_cosh<T: Float>(x: T) -> (T, T) {
+    let (e, inv) = (x.exp(), (-x).exp());
+    let half = T::from_f64(0.5);
+    ((e - inv) * half, (e + inv) * half)
+}
+
+impl<T: Float> From<T> for Complex<T> {
+    fn from(re: T) -> Self {
+        Self::real(re)
+    }
+}
+
+impl<T: Float> Add for Complex<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl<T: Float> Sub for Complex<T> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl<T: Float> Mul for Complex<T> {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl<T: Float> Div for Complex<T> {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let denominator = other.re * other.re + other.im * other.im;
+        let numerator = self * other.conj();
+        Self::new(numerator.re / denominator, numerator.im / denominator)
+    }
+}
+
+impl<T: Float> Neg for Complex<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+impl<T: Float> fmt::Display for Complex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.im < T::ZERO {
+            write!(f, "{}-{}i", self.re, -self.im)
+        } else {
+            write!(f, "{}+{}i", self.re, self.im)
+        }
+    }
+}
+
+/// Counterpart of `Computable` for complex graphs.
+pub trait ComplexComputable<T: Float = f32> {
+    /// Computes result of this type.
+    fn compute(&self) -> Complex<T>;
+    /// Adds dependency from another `ComplexComputable` object.
+    fn add_dependency(&self, dependency: ComplexHandle<T>);
+    /// Resets cache for this node.
+    fn reset_cache(&self);
+}
+
+/// Shared handle to complex node.
+pub type ComplexHandle<T = f32> = Rc<dyn ComplexComputable<T>>;
+
+/// Weak references to dependent nodes, which hold strong references to their operands.
+type ComplexDependencies<T> = RefCell<Vec<Weak<dyn ComplexComputable<T>>>>;
+
+/// Resets caches of dependent nodes, dropping references to already freed ones.
+fn reset_dependencies<T: Float>(dependencies: &ComplexDependencies<T>) {
+    let alive: Vec<_> = dependencies
+        .borrow()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    dependencies.borrow_mut().retain(|d| d.strong_count() > 0);
+    alive.iter().for_each(|d| d.reset_cache());
+}
+
+/// Operation over complex values.
+#[derive(Clone)]
+pub enum ComplexOperation<T: Float = f32> {
+    /// Constant value.
+    Const(Complex<T>),
+    /// Sum of two values.
+    Add(ComplexHandle<T>, ComplexHandle<T>),
+    /// Subtraction of two values.
+    Sub(ComplexHandle<T>, ComplexHandle<T>),
+    /// Multiplication of two values.
+    Mul(ComplexHandle<T>, ComplexHandle<T>),
+    /// Division of two values.
+    Div(ComplexHandle<T>, ComplexHandle<T>),
+    /// Negation of value.
+    Neg(ComplexHandle<T>),
+    /// Complex conjugate of value.
+    Conj(ComplexHandle<T>),
+    /// Principal value of first value raised to second value.
+    Pow(ComplexHandle<T>, ComplexHandle<T>),
+    /// Exponent of value.
+    Exp(ComplexHandle<T>),
+    /// Principal value of natural logarithm of value.
+    Ln(ComplexHandle<T>),
+    /// Sine of value.
+    Sin(ComplexHandle<T>),
+    /// Cosine of value.
+    Cos(ComplexHandle<T>),
+    /// Principal square root of value.
+    Sqrt(ComplexHandle<T>),
+    /// Real part of value, as complex number.
+    Re(ComplexHandle<T>),
+    /// Imaginary part of value, as real complex number.
+    Im(ComplexHandle<T>),
+    /// Magnitude of value, as real complex number.
+    Abs(ComplexHandle<T>),
+    /// Argument of value, as real complex number.
+    Arg(ComplexHandle<T>),
+}
+
+impl<T: Float> ComplexOperation<T> {
+    /// Returns operands of operation.
+    pub fn operands(&self) -> Vec<&ComplexHandle<T>> {
+        use ComplexOperation::*;
+
+        match self {
+            Const(_) => vec![],
+            Add(x, y) | Sub(x, y) | Mul(x, y) | Div(x, y) | Pow(x, y) => vec![x, y],
+            Neg(x) | Conj(x) | Exp(x) | Ln(x) | Sin(x) | Cos(x) | Sqrt(x) | Re(x) | Im(x)
+            | Abs(x) | Arg(x) => vec![x],
+        }
+    }
+
+    /// Computes result from operands.
+    pub fn eval(&self) -> Complex<T> {
+        use ComplexOperation::*;
+
+        match self {
+            Const(val) => *val,
+            Add(x, y) => x.compute() + y.compute(),
+            Sub(x, y) => x.compute() - y.compute(),
+            Mul(x, y) => x.compute() * y.compute(),
+            Div(x, y) => x.compute() / y.compute(),
+            Neg(x) => -x.compute(),
+            Conj(x) => x.compute().conj(),
+            Pow(x, y) => x.compute().pow(y.compute()),
+            Exp(x) => x.compute().exp(),
+            Ln(x) => x.compute().ln(),
+            Sin(x) => x.compute().sin(),
+            Cos(x) => x.compute().cos(),
+            Sqrt(x) => x.compute().sqrt(),
+            Re(x) => Complex::real(x.compute().re),
+            Im(x) => Complex::real(x.compute().im),
+            Abs(x) => Complex::real(x.compute().norm()),
+            Arg(x) => Complex::real(x.compute().arg()),
+        }
+    }
+}
+
+/// Complex graph expression node.
+pub struct ComplexNode<T: Float = f32> {
+    /// Cached result.
+    cache: Cell<Option<Complex<T>>>,
+    /// Holds references to nodes that depend from this node.
+    dependencies: ComplexDependencies<T>,
+    /// Holds operation for this node.
+    opp: ComplexOperation<T>,
+}
+
+impl<T: Float> ComplexNode<T> {
+    /// Builds node computing given operation and registers it as dependency of its operands.
+    pub fn new(opp: ComplexOperation<T>) -> Rc<Self> {
+        let obj = Rc::new(Self {
+            cache: Cell::new(None),
+            dependencies: RefCell::default(),
+            opp,
+        });
+        obj.opp
+            .operands()
+            .into_iter()
+            .for_each(|x| x.add_dependency(obj.clone()));
+        obj
+    }
+
+    /// Builds node holding constant value.
+    pub fn constant(val: Complex<T>) -> Rc<Self> {
+        Self::new(ComplexOperation::Const(val))
+    }
+
+    /// Builds node for sum of two nodes.
+    pub fn add(x: ComplexHandle<T>, y: ComplexHandle<T>) -> Rc<Self> {
+        Self::new(ComplexOperation::Add(x, y))
+    }
+
+    /// Builds node for subtraction of two nodes.
+    pub fn sub(x: ComplexHandle<T>, y: ComplexHandle<T>) -> Rc<Self> {
+        Self::new(ComplexOperation::Sub(x, y))
+    }
+
+    /// Builds node for multiplication of two nodes.
+    pub fn mul(x: ComplexHandle<T>, y: ComplexHandle<T>) -> Rc<Self> {
+        Self::new(ComplexOperation::Mul(x, y))
+    }
+
+    /// Builds node for division of two nodes.
+    pub fn div(x: ComplexHandle<T>, y: ComplexHandle<T>) -> Rc<Self> {
+        Self::new(ComplexOperation::Div(x, y))
+    }
+
+    /// Builds node raising first node to power of second one.
+    pub fn pow(x: ComplexHandle<T>, y: ComplexHandle<T>) -> Rc<Self> {
+        Self::new(ComplexOperation::Pow(x, y))
+    }
+
+    /// Builds node for exponent of node.
+    pub fn exp(x: ComplexHandle<T>) -> Rc<Self> {
+        Self::new(ComplexOperation::Exp(x))
+    }
+
+    /// Builds node for sine of node.
+    pub fn sin(x: ComplexHandle<T>) -> Rc<Self> {
+        Self::new(ComplexOperation::Sin(x))
+    }
+
+    /// Builds node for cosine of node.
+    pub fn cos(x: ComplexHandle<T>) -> Rc<Self> {
+        Self::new(ComplexOperation::Cos(x))
+    }
+
+    /// Returns operation computed by this node.
+    pub fn operation(&self) -> &ComplexOperation<T> {
+        &self.opp
+    }
+}
+
+impl<T: Float> ComplexComputable<T> for ComplexNode<T> {
+    fn compute(&self) -> Complex<T> {
+        if let Some(val) = self.cache.get() {
+            return val;
+        }
+        let val = self.opp.eval();
+        self.cache.set(Some(val));
+        val
+    }
+
+    fn add_dependency(&self, dependency: ComplexHandle<T>) {
+        self.dependencies
+            .borrow_mut()
+            .push(Rc::downgrade(&dependency));
+    }
+
+    fn reset_cache(&self) {
+        if self.cache.take().is_some() {
+            reset_dependencies(&self.dependencies);
+        }
+    }
+}
+
+/// Complex graph input node.
+pub struct ComplexInputNode<T: Float = f32> {
+    val: Cell<Complex<T>>,
+    /// Holds references to nodes that depend from this node.
+    dependencies: ComplexDependencies<T>,
+}
+
+impl<T: Float> ComplexInputNode<T> {
+    /// Builds input holding given value.
+    pub fn from_val(val: Complex<T>) -> Rc<Self> {
+        Rc::new(Self {
+            val: Cell::new(val),
+            dependencies: RefCell::default(),
+        })
+    }
+
+    /// Sets new input value and resets cache for all dependable nodes.
+    pub fn set(&self, val: Complex<T>) {
+        self.val.set(val);
+        reset_dependencies(&self.dependencies);
+    }
+}
+
+impl<T: Float> ComplexComputable<T> for ComplexInputNode<T> {
+    /// Just returns stored value.
+    fn compute(&self) -> Complex<T> {
+        self.val.get()
+    }
+
+    fn add_dependency(&self, dependency: ComplexHandle<T>) {
+        self.dependencies
+            .borrow_mut()
+            .push(Rc::downgrade(&dependency));
+    }
+
+    /// Doesn't have cache, so doing nothing.
+    fn reset_cache(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    fn close(a: Complex<f64>, b: Complex<f64>) -> bool {
+        (a - b).norm() < 1e-12
+    }
+
+    #[test]
+    fn arithmetic() {
+        let z = Complex::new(1.0, 2.0);
+        let w = Complex::new(3.0, -1.0);
+        assert_eq!(z * w, Complex::new(5.0, 5.0));
+        assert!(close(z / w * w, z));
+        assert!(close(Complex::new(0.0, PI).exp(), Complex::real(-1.0)));
+        assert!(close(
+            Complex::i().pow(Complex::real(2.0)),
+            Complex::real(-1.0)
+        ));
+        assert!(close(Complex::real(-4.0).sqrt(), Complex::new(0.0, 2.0)));
+        assert!(close(z.ln().exp(), z));
+        let (s, c) = (z.sin(), z.cos());
+        assert!(close(s * s + c * c, Complex::real(1.0)));
+        assert_eq!(w.to_string(), "3-1i");
+    }
+
+    #[test]
+    fn euler_graph() {
+        // e^{ix} = cos(x) + i sin(x)
+        let x = ComplexInputNode::from_val(Complex::real(0.0));
+        let ix = ComplexNode::mul(ComplexNode::constant(Complex::i()), x.clone());
+        let root = ComplexNode::exp(ix);
+        assert!(close(root.compute(), Complex::real(1.0)));
+
+        x.set(Complex::real(PI / 2.0));
+        assert!(close(root.compute(), Complex::i()));
+        let magnitude = ComplexNode::new(ComplexOperation::Abs(root.clone()));
+        assert!((magnitude.compute().re - 1.0).abs() < 1e-12);
+        x.set(Complex::real(PI));
+        assert!(close(root.compute(), Complex::real(-1.0)));
+    }
+}
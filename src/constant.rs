@@ -0,0 +1,74 @@
+//! Leaf nodes holding fixed values.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    float::Float,
+    node::{Computable, Handle, Node, NodeOperation},
+    ops::Operation,
+};
+
+/// Node holding value which never changes.
+/// Unlike `InputNode` it can't be set, so it keeps no dependents to invalidate.
+/// Its operation is `Operation::Const`, so exporters and simplification treat it as constant.
+pub struct ConstNode<T: Float = f32> {
+    opp: NodeOperation<T>,
+}
+
+impl<T: Float> ConstNode<T> {
+    /// Returns held value.
+    pub fn value(&self) -> T {
+        match self.opp {
+            Operation::Const(val) => val,
+            _ => unreachable!("constant node holds non-constant operation"),
+        }
+    }
+}
+
+impl<T: Float> Node<T> {
+    /// Builds node holding constant value.
+    pub fn constant(val: T) -> Rc<RefCell<ConstNode<T>>> {
+        Rc::new(RefCell::new(ConstNode {
+            opp: Operation::Const(val),
+        }))
+    }
+}
+
+impl<T: Float> Computable<T> for ConstNode<T> {
+    fn compute(&mut self) -> T {
+        self.value()
+    }
+
+    /// Dependents are never invalidated by constant, so they aren't kept.
+    fn add_dependency(&mut self, _dependency: Handle<T>) {}
+
+    fn reset_cache(&mut self) {}
+
+    fn operation(&self) -> Option<&NodeOperation<T>> {
+        Some(&self.opp)
+    }
+
+    fn depth(&self) -> usize {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed() {
+        let c = Node::constant(2.0f32);
+        let x = Node::input(3.0);
+        let root = Node::mul(c.clone(), x);
+        assert_eq!(root.borrow_mut().compute(), 6.0);
+        assert_eq!(c.borrow().value(), 2.0);
+        assert!(!c.borrow().is_dirty());
+        assert_eq!(c.borrow().operation().unwrap().name(), "constant");
+
+        // shared constant folds like any other
+        let folded = Node::simplify(&(Node::add(c.clone(), c) as Handle));
+        assert!(matches!(folded.borrow().operation(), Some(Operation::Const(v)) if *v == 4.0));
+    }
+}
@@ -0,0 +1,189 @@
+//! Estimation of graph evaluation cost.
+//!
+//! Each operation is weighted by its kind, since transcendental functions are
+//! far more expensive than arithmetic. Shared subgraphs are counted once,
+//! as their results are cached.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    float::Float,
+    node::{node_addr, Handle},
+    ops::Operation,
+};
+
+/// Weights of evaluating nodes, by operation name as given by `Operation::name`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostModel {
+    weights: HashMap<&'static str, f64>,
+    /// Weight of operations without explicit one.
+    default: f64,
+    /// Weight of each operand of variadic operations, in addition to operation weight.
+    per_operand: f64,
+    /// Weight of nodes without operation, such as inputs.
+    leaf: f64,
+}
+
+impl Default for CostModel {
+    /// Rough relative costs, with addition costing one.
+    fn default() -> Self {
+        let weights = [
+            ("constant", 0.0),
+            ("add", 1.0),
+            ("add_var", 0.0),
+            ("sub", 1.0),
+            ("mul", 1.0),
+            ("mul_var", 0.0),
+            ("div", 4.0),
+            ("neg", 0.5),
+            ("recip", 4.0),
+            ("detach", 0.0),
+            ("pow", 20.0),
+            ("sin", 15.0),
+            ("asin", 20.0),
+            ("acos", 20.0),
+            ("atan", 20.0),
+            ("cos", 15.0),
+            ("tan", 20.0),
+            ("exp", 15.0),
+            ("ln", 15.0),
+            ("log", 16.0),
+            ("sqrt", 5.0),
+            ("abs", 1.0),
+            ("sign", 1.0),
+            ("fract", 2.0),
+            ("tanh", 20.0),
+            ("sigmoid", 16.0),
+            ("relu", 1.0),
+            ("softplus", 30.0),
+            ("expm1", 15.0),
+            ("ln_1p", 15.0),
+            ("min", 1.0),
+            ("max", 1.0),
+            ("clamp", 2.0),
+            ("lt", 1.0),
+            ("le", 1.0),
+            ("gt", 1.0),
+            ("ge", 1.0),
+            ("eq", 2.0),
+            ("hypot", 10.0),
+            ("atan2", 25.0),
+            ("rem", 10.0),
+            ("norm", 5.0),
+            ("erf", 20.0),
+            ("erfc", 20.0),
+            ("normal_cdf", 25.0),
+            ("normal_pdf", 20.0),
+            ("logsumexp", 15.0),
+            ("softmax_component", 15.0),
+            ("reduce_sum", 0.0),
+            ("reduce_prod", 0.0),
+            ("reduce_min", 0.0),
+            ("reduce_max", 0.0),
+            ("reduce_mean", 4.0),
+            ("lazy_select", 1.0),
+            ("piecewise", 1.0),
+            ("quantize", 4.0),
+            ("polynomial", 4.0),
+        ];
+        Self {
+            weights: weights.into_iter().collect(),
+            default: 10.0,
+            per_operand: 1.0,
+            leaf: 0.0,
+        }
+    }
+}
+
+impl CostModel {
+    /// Sets weight of operation with given name.
+    pub fn with_weight(mut self, operation: &'static str, weight: f64) -> Self {
+        self.weights.insert(operation, weight);
+        self
+    }
+
+    /// Sets weight of each operand of variadic operations.
+    pub fn with_per_operand(mut self, weight: f64) -> Self {
+        self.per_operand = weight;
+        self
+    }
+
+    /// Sets weight of nodes without operation.
+    pub fn with_leaf(mut self, weight: f64) -> Self {
+        self.leaf = weight;
+        self
+    }
+
+    /// Returns weight of single operation.
+    pub fn weight<H, T: Float>(&self, opp: &Operation<H, T>) -> f64 {
+        let base = self
+            .weights
+            .get(opp.name())
+            .copied()
+            .unwrap_or(self.default);
+        match opp {
+            Operation::AddVar(args)
+            | Operation::MulVar(args)
+            | Operation::Reduce(args, _)
+            | Operation::Norm(args)
+            | Operation::LogSumExp(args)
+            | Operation::Softmax(args, _) => base + self.per_operand * args.len() as f64,
+            _ => base,
+        }
+    }
+
+    /// Estimates cost of computing graph with given root from scratch.
+    pub fn cost<T: Float>(&self, root: &Handle<T>) -> f64 {
+        let mut visited = HashSet::new();
+        let mut stack = vec![root.clone()];
+        let mut total = 0.0;
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node_addr(&node)) {
+                continue;
+            }
+            let node = node.borrow();
+            total += node.operation().map_or(self.leaf, |opp| self.weight(opp));
+            stack.extend(node.operands());
+        }
+        total
+    }
+}
+
+/// Estimates cost of computing graph with given root using default weights.
+pub fn cost<T: Float>(root: &Handle<T>) -> f64 {
+    CostModel::default().cost(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Node;
+
+    #[test]
+    fn weights() {
+        let x = Node::input(1.0f32);
+        let y = Node::input(2.0f32);
+        let shared = Node::sin(x.clone());
+        let root: Handle = Node::add(
+            Node::mul(shared.clone(), shared),
+            Node::add_var(vec![x, y, Node::constant(3.0)]),
+        );
+        // add, mul, sin counted once, add_var with 3 operands
+        assert_eq!(cost(&root), 1.0 + 1.0 + 15.0 + 3.0);
+
+        let model = CostModel::default()
+            .with_weight("sin", 100.0)
+            .with_per_operand(0.0)
+            .with_leaf(1.0);
+        assert_eq!(model.cost(&root), 1.0 + 1.0 + 100.0 + 2.0);
+    }
+
+    #[test]
+    fn simplification_reduces_cost() {
+        let x = Node::input(1.0f32);
+        let root: Handle = Node::mul(Node::pow(x, 1.0), Node::sin(Node::constant(0.5)));
+        let simple = Node::simplify(&root);
+        assert!(cost(&simple) < cost(&root));
+        assert_eq!(simple.borrow_mut().compute(), root.borrow_mut().compute());
+    }
+}
@@ -0,0 +1,93 @@
+//! Per-node counts of computations and cache hits, for finding where caching,
+//! freezing or restructuring pays off. Available with `counters` feature.
+//!
+//! Operation nodes count since their construction, each `compute` or `try_compute`
+//! call either computes the node or takes its cached result.
+
+use crate::{
+    float::Float,
+    graph::Graph,
+    node::{post_order, Handle},
+};
+
+/// Counts of computations and cache hits of node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// Amount of times result was computed.
+    pub computed: u64,
+    /// Amount of times cached result was returned.
+    pub cached: u64,
+}
+
+/// Returns up to `n` counting nodes reachable from root which were computed most often,
+/// along with their counters, most computed first.
+pub fn top_recomputed<T: Float>(root: &Handle<T>, n: usize) -> Vec<(Handle<T>, Counters)> {
+    top(post_order(root), n)
+}
+
+/// Orders counting nodes by amount of computations, keeping the first `n`.
+fn top<T: Float>(nodes: Vec<Handle<T>>, n: usize) -> Vec<(Handle<T>, Counters)> {
+    let mut counted: Vec<_> = nodes
+        .into_iter()
+        .filter_map(|node| {
+            let counters = node.borrow().counters()?;
+            Some((node, counters))
+        })
+        .collect();
+    // stable, so equally computed nodes keep post-order
+    counted.sort_by(|(_, a), (_, b)| b.computed.cmp(&a.computed));
+    counted.truncate(n);
+    counted
+}
+
+impl<T: Float> Graph<T> {
+    /// Returns up to `n` counting nodes reachable from outputs which were computed
+    /// most often, see `counters` module.
+    pub fn top_recomputed(&self, n: usize) -> Vec<(Handle<T>, Counters)> {
+        top(self.reachable(), n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::Input,
+        node::{Computable, Node},
+    };
+
+    #[test]
+    fn counts() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        let y = g.add_input("y", 2.0);
+        let sin = Node::sin(x.clone());
+        let cos = Node::cos(y);
+        g.add_output("z", Node::add(sin.clone(), cos.clone()));
+        for val in [2.0, 3.0, 4.0] {
+            x.borrow_mut().set(val);
+            g.outputs();
+            g.outputs();
+        }
+        assert_eq!(
+            sin.borrow().counters(),
+            Some(Counters {
+                computed: 3,
+                cached: 0
+            })
+        );
+        assert_eq!(
+            cos.borrow().counters(),
+            Some(Counters {
+                computed: 1,
+                cached: 2
+            })
+        );
+        assert_eq!(x.borrow().counters(), None);
+
+        let top = g.top_recomputed(2);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].1.computed, 3);
+        assert_eq!((top[0].1.cached, top[1].1.cached), (0, 3));
+    }
+}
@@ -0,0 +1,430 @@
+//! Fixed-point decimal scalar for monetary graphs.
+//!
+//! Values are integers scaled by `10^DP`, so sums and differences are exact and
+//! products and quotients are rounded to `DP` decimal places explicitly, half to even
+//! by default. Functions without exact decimal form, such as `exp`, are computed in
+//! `f64` and rounded back. Graphs become monetary by using `Decimal` as their scalar,
+//! e.g. `Node<Decimal<2>>`.
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    iter::Sum,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+use crate::{float::Float, ops::Rounding};
+
+/// Decimal with `DP` places stored as scaled integer. Besides finite values, it
+/// represents infinities and `NaN`, which results overflowing the range become.
+#[derive(Clone, Copy)]
+pub struct Decimal<const DP: u32>(i128);
+
+impl<const DP: u32> Decimal<DP> {
+    /// Factor values are scaled by.
+    const SCALE: i128 = 10i128.pow(DP);
+    const NAN_RAW: i128 = i128::MIN;
+    const NEG_INFINITY_RAW: i128 = i128::MIN + 1;
+    const INFINITY_RAW: i128 = i128::MAX;
+    /// Not a number.
+    pub const NAN: Self = Self(Self::NAN_RAW);
+    /// Positive infinity.
+    pub const INFINITY: Self = Self(Self::INFINITY_RAW);
+
+    /// Creates value from integer scaled by `10^DP`, e.g. amount in cents for two places.
+    /// Integers reserved for infinities and `NaN` become them.
+    pub fn from_scaled(scaled: i128) -> Self {
+        Self(scaled)
+    }
+
+    /// Returns value as integer scaled by `10^DP`, `None` if it isn't finite.
+    pub fn scaled(self) -> Option<i128> {
+        self.is_finite().then(|| self.0)
+    }
+
+    /// Returns true if value is `NaN`.
+    pub fn is_nan(self) -> bool {
+        self.0 == Self::NAN_RAW
+    }
+
+    /// Multiplies values, rounding product to `DP` places with given rounding.
+    pub fn mul_round(self, other: Self, rounding: Rounding) -> Self {
+        match self.finite_pair(other) {
+            Some((x, y)) => match x.checked_mul(y) {
+                Some(product) => Self::finite(div_round(product, Self::SCALE, rounding)),
+                None => Self::from_f64(self.to_f64() * other.to_f64()),
+            },
+            None => Self::from_f64(self.to_f64() * other.to_f64()),
+        }
+    }
+
+    /// Divides values, rounding quotient to `DP` places with given rounding.
+    pub fn div_round(self, other: Self, rounding: Rounding) -> Self {
+        match self.finite_pair(other) {
+            Some((x, y)) if y != 0 => match x.checked_mul(Self::SCALE) {
+                Some(dividend) => Self::finite(div_round(dividend, y, rounding)),
+                None => Self::from_f64(self.to_f64() / other.to_f64()),
+            },
+            _ => Self::from_f64(self.to_f64() / other.to_f64()),
+        }
+    }
+
+    /// Returns scaled integers of both values if they're finite.
+    fn finite_pair(self, other: Self) -> Option<(i128, i128)> {
+        (self.is_finite() && other.is_finite()).then(|| (self.0, other.0))
+    }
+
+    /// Wraps scaled integer, turning integers reserved for special values into infinities.
+    fn finite(scaled: i128) -> Self {
+        match scaled {
+            Self::INFINITY_RAW => Self::INFINITY,
+            s if s <= Self::NEG_INFINITY_RAW => Self::NEG_INFINITY,
+            s => Self(s),
+        }
+    }
+
+    /// Applies function to values in `f64`, for operations without exact decimal form.
+    fn via_f64(self, f: impl FnOnce(f64) -> f64) -> Self {
+        Self::from_f64(f(self.to_f64()))
+    }
+}
+
+/// Divides integers, rounding quotient with given rounding.
+fn div_round(n: i128, d: i128, rounding: Rounding) -> i128 {
+    let (q, r) = (n / d, n % d);
+    if r == 0 {
+        return q;
+    }
+    let step = if (n < 0) != (d < 0) { -1 } else { 1 };
+    // remainder compared with the rest of divisor, so doubling it can't overflow
+    let half = r.abs().cmp(&(d.abs() - r.abs()));
+    let away = match rounding {
+        Rounding::HalfEven => half == Ordering::Greater || half == Ordering::Equal && q % 2 != 0,
+        Rounding::HalfAwayFromZero => half != Ordering::Less,
+        Rounding::TowardZero => false,
+        Rounding::AwayFromZero => true,
+        Rounding::Floor => step < 0,
+        Rounding::Ceiling => step > 0,
+    };
+    if away {
+        q + step
+    } else {
+        q
+    }
+}
+
+impl<const DP: u32> Float for Decimal<DP> {
+    const ZERO: Self = Self(0);
+    const ONE: Self = Self(Self::SCALE);
+    const MIN: Self = Self(i128::MIN + 2);
+    const MAX: Self = Self(i128::MAX - 1);
+    const NEG_INFINITY: Self = Self(Self::NEG_INFINITY_RAW);
+
+    /// Rounds to `DP` places half to even.
+    fn from_f64(val: f64) -> Self {
+        if val.is_nan() {
+            return Self::NAN;
+        }
+        let scaled = Rounding::HalfEven.apply(val * Self::SCALE as f64);
+        if scaled >= Self::MAX.0 as f64 {
+            Self::INFINITY
+        } else if scaled <= Self::MIN.0 as f64 {
+            Self::NEG_INFINITY
+        } else {
+            Self(scaled as i128)
+        }
+    }
+
+    fn to_f64(self) -> f64 {
+        match self.0 {
+            Self::NAN_RAW => f64::NAN,
+            Self::NEG_INFINITY_RAW => f64::NEG_INFINITY,
+            Self::INFINITY_RAW => f64::INFINITY,
+            s => s as f64 / Self::SCALE as f64,
+        }
+    }
+
+    fn abs(self) -> Self {
+        match self.0 {
+            Self::NAN_RAW => self,
+            Self::NEG_INFINITY_RAW => Self::INFINITY,
+            s => Self(s.abs()),
+        }
+    }
+
+    fn min(self, other: Self) -> Self {
+        if other < self || self.is_nan() {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn max(self, other: Self) -> Self {
+        if other > self || self.is_nan() {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn sqrt(self) -> Self {
+        self.via_f64(f64::sqrt)
+    }
+
+    fn powf(self, pow: Self) -> Self {
+        self.via_f64(|x| x.powf(pow.to_f64()))
+    }
+
+    fn fract(self) -> Self {
+        if self.is_finite() {
+            Self(self.0 % Self::SCALE)
+        } else {
+            Self::NAN
+        }
+    }
+
+    fn exp(self) -> Self {
+        self.via_f64(f64::exp)
+    }
+
+    fn exp_m1(self) -> Self {
+        self.via_f64(f64::exp_m1)
+    }
+
+    fn ln(self) -> Self {
+        self.via_f64(f64::ln)
+    }
+
+    fn ln_1p(self) -> Self {
+        self.via_f64(f64::ln_1p)
+    }
+
+    fn sin(self) -> Self {
+        self.via_f64(f64::sin)
+    }
+
+    fn cos(self) -> Self {
+        self.via_f64(f64::cos)
+    }
+
+    fn tan(self) -> Self {
+        self.via_f64(f64::tan)
+    }
+
+    fn asin(self) -> Self {
+        self.via_f64(f64::asin)
+    }
+
+    fn acos(self) -> Self {
+        self.via_f64(f64::acos)
+    }
+
+    fn atan(self) -> Self {
+        self.via_f64(f64::atan)
+    }
+
+    fn tanh(self) -> Self {
+        self.via_f64(f64::tanh)
+    }
+
+    fn hypot(self, other: Self) -> Self {
+        self.via_f64(|x| x.hypot(other.to_f64()))
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        self.via_f64(|y| y.atan2(other.to_f64()))
+    }
+
+    /// Exact for finite values, since both are scaled by the same factor.
+    fn rem(self, other: Self) -> Self {
+        match self.finite_pair(other) {
+            Some((x, y)) if y != 0 => Self(x % y),
+            _ => self.via_f64(|x| x % other.to_f64()),
+        }
+    }
+
+    fn is_finite(self) -> bool {
+        !matches!(
+            self.0,
+            Self::NAN_RAW | Self::NEG_INFINITY_RAW | Self::INFINITY_RAW
+        )
+    }
+
+    fn is_infinite(self) -> bool {
+        matches!(self.0, Self::NEG_INFINITY_RAW | Self::INFINITY_RAW)
+    }
+}
+
+impl<const DP: u32> PartialEq for Decimal<DP> {
+    fn eq(&self, other: &Self) -> bool {
+        !self.is_nan() && self.0 == other.0
+    }
+}
+
+impl<const DP: u32> PartialOrd for Decimal<DP> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        // infinities are the extreme integers, so they're ordered correctly
+        (!self.is_nan() && !other.is_nan()).then(|| self.0.cmp(&other.0))
+    }
+}
+
+impl<const DP: u32> Add for Decimal<DP> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        match self.finite_pair(other).and_then(|(x, y)| x.checked_add(y)) {
+            Some(sum) => Self::finite(sum),
+            None => Self::from_f64(self.to_f64() + other.to_f64()),
+        }
+    }
+}
+
+impl<const DP: u32> Sub for Decimal<DP> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl<const DP: u32> Mul for Decimal<DP> {
+    type Output = Self;
+
+    /// Multiplies values rounding half to even, see `mul_round`.
+    fn mul(self, other: Self) -> Self {
+        self.mul_round(other, Rounding::HalfEven)
+    }
+}
+
+impl<const DP: u32> Div for Decimal<DP> {
+    type Output = Self;
+
+    /// Divides values rounding half to even, see `div_round`.
+    fn div(self, other: Self) -> Self {
+        self.div_round(other, Rounding::HalfEven)
+    }
+}
+
+impl<const DP: u32> Neg for Decimal<DP> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        match self.0 {
+            Self::NAN_RAW => self,
+            Self::NEG_INFINITY_RAW => Self::INFINITY,
+            Self::INFINITY_RAW => Self::NEG_INFINITY,
+            s => Self(-s),
+        }
+    }
+}
+
+impl<const DP: u32> Sum for Decimal<DP> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, |acc, x| acc + x)
+    }
+}
+
+impl<const DP: u32> fmt::Display for Decimal<DP> {
+    /// Writes all `DP` places, e.g. `-12.50`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Self::NAN_RAW => return f.write_str("NaN"),
+            Self::NEG_INFINITY_RAW => return f.write_str("-inf"),
+            Self::INFINITY_RAW => return f.write_str("inf"),
+            _ => {}
+        }
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let (int, fract) = (
+            self.0.unsigned_abs() / Self::SCALE as u128,
+            self.0.unsigned_abs() % Self::SCALE as u128,
+        );
+        match DP {
+            0 => write!(f, "{}{}", sign, int),
+            _ => write!(f, "{}{}.{:0width$}", sign, int, fract, width = DP as usize),
+        }
+    }
+}
+
+impl<const DP: u32> fmt::Debug for Decimal<DP> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::{Input, InputNode},
+        node::{Computable, Node},
+    };
+
+    type Money = Decimal<2>;
+
+    #[test]
+    fn exact_sums() {
+        let cents = Money::from_f64;
+        let args = || {
+            (0..10)
+                .map(|_| InputNode::from_val(cents(0.1)) as _)
+                .collect()
+        };
+        let total = Node::add_var(args());
+        assert_eq!(total.borrow_mut().compute(), cents(1.0));
+        assert_eq!(cents(0.1) + cents(0.2), cents(0.3));
+        assert_eq!(cents(-12.5).to_string(), "-12.50");
+        assert_eq!(Money::from_scaled(5).scaled(), Some(5));
+
+        // products and quotients are rounded half to even
+        assert_eq!(cents(0.25) * cents(0.5), cents(0.12));
+        assert_eq!(cents(0.35) * cents(0.5), cents(0.18));
+        assert_eq!(cents(1.0) / cents(3.0), cents(0.33));
+        assert_eq!(cents(-2.0) / cents(3.0), cents(-0.67));
+        assert_eq!(
+            cents(1.0).div_round(cents(3.0), Rounding::Ceiling),
+            cents(0.34)
+        );
+        assert_eq!(
+            cents(0.25).mul_round(cents(0.5), Rounding::HalfAwayFromZero),
+            cents(0.13)
+        );
+
+        let price = InputNode::from_val(cents(19.99));
+        let gross = Node::round_dp(
+            Node::mul(price.clone(), Node::constant(cents(1.19))),
+            1,
+            Rounding::HalfEven,
+        );
+        assert_eq!(gross.borrow_mut().compute(), cents(23.8));
+        price.borrow_mut().set(cents(10.0));
+        assert_eq!(gross.borrow_mut().compute(), cents(11.9));
+    }
+
+    #[test]
+    fn special_values() {
+        let zero = Money::ZERO;
+        assert!((zero / zero).is_nan());
+        assert_eq!(Money::ONE / zero, Money::INFINITY);
+        assert_eq!(-Money::INFINITY, Money::NEG_INFINITY);
+        assert_ne!(Money::NAN, Money::NAN);
+        assert_eq!(Money::MAX + Money::ONE, Money::INFINITY);
+        assert_eq!(Money::from_f64(1e40), Money::INFINITY);
+        assert!(Money::from_f64(2.0).sqrt() == Money::from_f64(1.41));
+    }
+
+    #[test]
+    fn remainders() {
+        let cents = Money::from_f64;
+        assert_eq!(cents(0.3).rem(cents(0.1)), Money::ZERO);
+        assert_eq!(cents(-7.25).rem(cents(2.0)), cents(-1.25));
+        assert!(cents(1.0).rem(Money::ZERO).is_nan());
+        assert_eq!(cents(1.0).rem(Money::INFINITY), cents(1.0));
+
+        let x = InputNode::from_val(cents(0.3));
+        let y = Node::rem(x, Node::constant(cents(0.1)));
+        assert_eq!(y.borrow_mut().compute(), Money::ZERO);
+        let angle = Node::atan2(Node::constant(cents(1.0)), Node::constant(cents(1.0)));
+        assert_eq!(angle.borrow_mut().compute(), cents(0.79));
+    }
+}
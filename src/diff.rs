@@ -0,0 +1,331 @@
+//! Symbolic differentiation, building derivatives as graphs of `Node`s.
+//!
+//! Unlike `grad`, which propagates numbers, derivatives are nodes computed from the same
+//! inputs, so they follow input changes and can be differentiated again, e.g. for Hessians
+//! used by Newton-type optimization. Operations without symbolic derivative rule, such as
+//! user-defined ones, are rejected.
+//!
+//! `Node::differentiate` simplifies the derivative too, so it reads well when printed,
+//! e.g. with `infix::to_string_expr` or `latex::to_latex`.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    error::GraphError,
+    float::Float,
+    node::{node_addr, post_order, Handle, Node, NodeOperation},
+    ops::{derivative_coeffs, Operation, Reduction, Rounding},
+    simplify::simplify,
+};
+
+/// Operands of node with nodes computing partial derivatives with respect to them.
+type Partials<T> = Vec<(Handle<T>, Handle<T>)>;
+
+/// Returns operands of operation along with nodes computing partial derivatives
+/// of its result, computed by `this`, with respect to them.
+/// Operands result doesn't depend on continuously, like conditions, are omitted.
+fn partials<T: Float>(opp: &NodeOperation<T>, this: &Handle<T>) -> Result<Partials<T>, GraphError> {
+    use Operation::*;
+
+    let c = |val: f64| -> Handle<T> { Node::constant(T::from_f64(val)) };
+    let one = || c(1.0);
+    let unary = |x: &Handle<T>, d: Handle<T>| vec![(x.clone(), d)];
+    Ok(match opp {
+        Const(_) | Detach(_) | Quantize(..) | Sign(_) => vec![],
+        Fract(x) => unary(x, one()),
+        Lt(..) | Le(..) | Gt(..) | Ge(..) | Eq(..) => vec![],
+        Add(x, y) => vec![(x.clone(), one()), (y.clone(), one())],
+        AddVar(args) | Reduce(args, Reduction::Sum) => {
+            args.iter().map(|x| (x.clone(), one())).collect()
+        }
+        Reduce(args, Reduction::Mean) => {
+            let d = 1.0 / args.len() as f64;
+            args.iter().map(|x| (x.clone(), c(d))).collect()
+        }
+        Sub(x, y) => vec![(x.clone(), one()), (y.clone(), c(-1.0))],
+        Mul(x, y) => vec![(x.clone(), y.clone()), (y.clone(), x.clone())],
+        MulVar(args) | Reduce(args, Reduction::Prod) => (0..args.len())
+            .map(|i| {
+                let others: Vec<Handle<T>> = args
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, x)| x.clone())
+                    .collect();
+                (args[i].clone(), Node::mul_var(others) as Handle<T>)
+            })
+            .collect(),
+        Div(x, y, _) => vec![
+            (x.clone(), Node::recip(y.clone())),
+            (y.clone(), Node::neg(Node::div(this.clone(), y.clone()))),
+        ],
+        Neg(x) => unary(x, c(-1.0)),
+        Recip(x) => unary(x, Node::neg(Node::pow(this.clone(), T::from_f64(2.0)))),
+        Polynomial(x, coeffs) => unary(x, Node::polynomial(x.clone(), derivative_coeffs(coeffs))),
+        Pow(x, pow) => {
+            let d: Handle<T> = if *pow == T::ZERO {
+                c(0.0)
+            } else {
+                Node::mul(Node::constant(*pow), Node::pow(x.clone(), *pow - T::ONE))
+            };
+            unary(x, d)
+        }
+        Sin(x) => unary(x, Node::cos(x.clone())),
+        Cos(x) => unary(x, Node::neg(Node::sin(x.clone()))),
+        Tan(x) => unary(
+            x,
+            Node::recip(Node::pow(Node::cos(x.clone()), T::from_f64(2.0))),
+        ),
+        Asin(x) | Acos(x) => {
+            let d = Node::recip(Node::sqrt(Node::sub(
+                one(),
+                Node::pow(x.clone(), T::from_f64(2.0)),
+            )));
+            match opp {
+                Acos(_) => unary(x, Node::neg(d)),
+                _ => unary(x, d),
+            }
+        }
+        Atan(x) => unary(
+            x,
+            Node::recip(Node::add(one(), Node::pow(x.clone(), T::from_f64(2.0)))),
+        ),
+        Exp(x) => unary(x, this.clone()),
+        ExpM1(x) => unary(x, Node::exp(x.clone())),
+        Ln(x) => unary(x, Node::recip(x.clone())),
+        Ln1p(x) => unary(x, Node::recip(Node::add(one(), x.clone()))),
+        Log(x, base) => unary(
+            x,
+            Node::recip(Node::mul(x.clone(), Node::constant(base.ln()))),
+        ),
+        Sqrt(x) => unary(x, Node::div(c(0.5), this.clone())),
+        Abs(x) => {
+            let sign = Node::sub(Node::gt(x.clone(), c(0.0)), Node::lt(x.clone(), c(0.0)));
+            unary(x, sign)
+        }
+        Tanh(x) => unary(
+            x,
+            Node::sub(one(), Node::pow(this.clone(), T::from_f64(2.0))),
+        ),
+        Sigmoid(x) => unary(x, Node::mul(this.clone(), Node::sub(one(), this.clone()))),
+        Relu(x) => unary(x, Node::gt(x.clone(), c(0.0))),
+        Softplus(x) => unary(x, Node::sigmoid(x.clone())),
+        Erf(x) | Erfc(x) => {
+            let scale = match opp {
+                Erfc(_) => -std::f64::consts::FRAC_2_SQRT_PI,
+                _ => std::f64::consts::FRAC_2_SQRT_PI,
+            };
+            let gauss = Node::exp(Node::neg(Node::pow(x.clone(), T::from_f64(2.0))));
+            unary(x, Node::mul(c(scale), gauss))
+        }
+        NormalCdf(x) => unary(x, Node::normal_pdf(x.clone())),
+        NormalPdf(x) => unary(x, Node::neg(Node::mul(x.clone(), this.clone()))),
+        Hypot(..) | Norm(_) => opp
+            .operands()
+            .into_iter()
+            .map(|x| {
+                // zero at the origin, like numeric gradient
+                let positive = Node::gt(this.clone(), c(0.0));
+                let d = Node::lazy_select(positive, Node::div(x.clone(), this.clone()), c(0.0));
+                (x.clone(), d as Handle<T>)
+            })
+            .collect(),
+        Atan2(y, x) => {
+            let r2: Handle<T> = Node::add(
+                Node::mul(x.clone(), x.clone()),
+                Node::mul(y.clone(), y.clone()),
+            );
+            // zero at the origin, like numeric gradient
+            let d = |x: Handle<T>| -> Handle<T> {
+                let positive = Node::gt(r2.clone(), c(0.0));
+                Node::lazy_select(positive, Node::div(x, r2.clone()), c(0.0))
+            };
+            vec![
+                (y.clone(), d(x.clone())),
+                (x.clone(), d(Node::neg(y.clone()))),
+            ]
+        }
+        Rem(x, y) => {
+            let quotient = Node::quantize(
+                Node::div(x.clone(), y.clone()),
+                T::ONE,
+                Rounding::TowardZero,
+            );
+            vec![(x.clone(), one()), (y.clone(), Node::neg(quotient))]
+        }
+        Select(cond, x, y) => vec![
+            (x.clone(), Node::gt(cond.clone(), c(0.0))),
+            (y.clone(), Node::le(cond.clone(), c(0.0))),
+        ],
+        // whole derivative goes to the operand passed through
+        Min(x, y) => {
+            let takes_y: Handle<T> = Node::lt(y.clone(), x.clone());
+            vec![
+                (x.clone(), Node::sub(one(), takes_y.clone())),
+                (y.clone(), takes_y),
+            ]
+        }
+        Max(x, y) => {
+            let takes_y: Handle<T> = Node::gt(y.clone(), x.clone());
+            vec![
+                (x.clone(), Node::sub(one(), takes_y.clone())),
+                (y.clone(), takes_y),
+            ]
+        }
+        Reduce(_, Reduction::Min | Reduction::Max)
+        | Clamp(..)
+        | Piecewise(..)
+        | LogSumExp(_)
+        | Softmax(..)
+        | Custom(..) => {
+            return Err(GraphError::NotDifferentiable {
+                operation: opp.name(),
+            })
+        }
+    })
+}
+
+/// Builds nodes computing partial derivatives of root with respect to given nodes,
+/// zero constants for nodes root doesn't depend on. Derivatives are built by reverse
+/// accumulation, so shared subexpressions are differentiated once.
+/// Fails if some operation has no symbolic derivative rule.
+pub fn derivatives<T: Float, C: ?Sized>(
+    root: &Handle<T>,
+    wrt: &[Rc<RefCell<C>>],
+) -> Result<Vec<Handle<T>>, GraphError> {
+    // contributions to derivative of root with respect to each node, by node address
+    let mut contributions: HashMap<usize, Vec<Handle<T>>> = HashMap::new();
+    contributions.insert(node_addr(root), vec![Node::constant(T::ONE)]);
+    let mut adjoints: HashMap<usize, Handle<T>> = HashMap::new();
+    for node in post_order(root).iter().rev() {
+        let adjoint: Handle<T> = match contributions.remove(&node_addr(node)) {
+            Some(mut terms) if terms.len() == 1 => terms.pop().unwrap(),
+            Some(terms) => Node::add_var(terms),
+            None => continue,
+        };
+        let opp = node.borrow().operation().cloned();
+        if let Some(opp) = opp {
+            for (x, d) in partials(&opp, node)? {
+                contributions
+                    .entry(node_addr(&x))
+                    .or_default()
+                    .push(Node::mul(adjoint.clone(), d));
+            }
+        }
+        adjoints.insert(node_addr(node), adjoint);
+    }
+    Ok(wrt
+        .iter()
+        .map(|x| {
+            adjoints
+                .get(&node_addr(x))
+                .cloned()
+                .unwrap_or_else(|| Node::constant(T::ZERO))
+        })
+        .collect())
+}
+
+/// Computes matrix of second partial derivatives of root with respect to given nodes,
+/// by differentiating derivative graphs again.
+pub fn hessian<T: Float, C: ?Sized>(
+    root: &Handle<T>,
+    wrt: &[Rc<RefCell<C>>],
+) -> Result<Vec<Vec<T>>, GraphError> {
+    derivatives(root, wrt)?
+        .iter()
+        .map(|d| {
+            let row = derivatives(d, wrt)?;
+            Ok(row.iter().map(|x| x.borrow_mut().compute()).collect())
+        })
+        .collect()
+}
+
+impl<T: Float> Node<T> {
+    /// Builds nodes computing partial derivatives of root, see `diff::derivatives`.
+    pub fn derivatives<C: ?Sized>(
+        root: &Handle<T>,
+        wrt: &[Rc<RefCell<C>>],
+    ) -> Result<Vec<Handle<T>>, GraphError> {
+        derivatives(root, wrt)
+    }
+
+    /// Builds simplified derivative of root with respect to given node as a new graph
+    /// over the same inputs. Fails if some operation has no symbolic derivative rule.
+    pub fn differentiate<C: ?Sized>(
+        root: &Handle<T>,
+        wrt: &Rc<RefCell<C>>,
+    ) -> Result<Handle<T>, GraphError> {
+        let derivative = derivatives(root, std::slice::from_ref(wrt))?;
+        Ok(simplify(&derivative[0]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grad::Gradient,
+        infix::to_string_expr,
+        input::{Input, InputNode},
+    };
+
+    #[test]
+    fn derivative_graphs() {
+        let x = InputNode::from_val(1.5f32);
+        let y = InputNode::from_val(0.5f32);
+        let xy: Handle = Node::mul(x.clone(), y.clone());
+        let root: Handle = Node::add_var(vec![
+            Node::sin(xy.clone()),
+            Node::pow(x.clone(), 3.0),
+            Node::div(Node::exp(y.clone()), x.clone()),
+            Node::sigmoid(xy),
+            Node::hypot(x.clone(), y.clone()),
+            Node::atan2(y.clone(), x.clone()),
+            Node::rem(x.clone(), y.clone()),
+        ]);
+        let ds = Node::derivatives(&root, &[x.clone(), y.clone()]).unwrap();
+        for _ in 0..2 {
+            let grad = Gradient::of(&root);
+            let (dx, dy) = (ds[0].borrow_mut().compute(), ds[1].borrow_mut().compute());
+            assert!((dx - grad.wrt(&x)).abs() < 1e-5);
+            assert!((dy - grad.wrt(&y)).abs() < 1e-5);
+            // derivative graphs follow inputs
+            x.borrow_mut().set(-0.7);
+        }
+        let unrelated = InputNode::from_val(1.0f32);
+        let d = derivatives(&root, &[unrelated]).unwrap();
+        assert_eq!(d[0].borrow_mut().compute(), 0.0);
+    }
+
+    #[test]
+    fn second_order() {
+        // f = x^2 y + y^3
+        let x = InputNode::from_val(2.0f64);
+        let y = InputNode::from_val(3.0f64);
+        let root: Handle<f64> = Node::add(
+            Node::mul(Node::pow(x.clone(), 2.0), y.clone()),
+            Node::pow(y.clone(), 3.0),
+        );
+        let h = hessian(&root, &[x, y]).unwrap();
+        assert_eq!(h, vec![vec![6.0, 4.0], vec![4.0, 18.0]]);
+
+        let z = InputNode::from_val(1.0f64);
+        let custom: Handle<f64> = Node::unary_fn(z.clone(), |v| v * 2.0);
+        assert_eq!(
+            derivatives(&custom, &[z]).err(),
+            Some(GraphError::NotDifferentiable { operation: "fn" })
+        );
+    }
+
+    #[test]
+    fn printable() {
+        let x = InputNode::with_label(2.0f32, "x");
+        let y = InputNode::with_label(3.0f32, "y");
+        let root: Handle = Node::add(Node::mul(x.clone(), y.clone()), Node::sin(x.clone()));
+        let dx = Node::differentiate(&root, &x).unwrap();
+        assert_eq!(to_string_expr(&dx), "y + cos(x)");
+        assert_eq!(dx.borrow_mut().compute(), 3.0 + 2f32.cos());
+        let dy = Node::differentiate(&root, &y).unwrap();
+        assert_eq!(to_string_expr(&dy), "x");
+    }
+}
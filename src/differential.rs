@@ -0,0 +1,269 @@
+//! Differential testing of evaluation backends against each other.
+//!
+//! Graph is evaluated over sampled input values by the `Node` interpreter, which is
+//! the reference, and by each other backend, reporting samples where they diverge.
+//! Original inputs are restored afterwards.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    analysis::Distribution,
+    compile::CompiledGraph,
+    eval::Rng,
+    float::Float,
+    input::{Input, InputNode},
+    node::Handle,
+};
+
+/// Backend evaluating graphs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Cached evaluation of `Node`s with `Computable::compute`, the reference.
+    Interpreter,
+    /// Flat instruction tape, see `CompiledGraph::eval`.
+    Compiled,
+    /// Flat instruction tape evaluated over lanes, see `CompiledGraph::eval_batch_lanes`.
+    #[cfg(feature = "simd")]
+    Lanes,
+}
+
+/// Sample where backend produced value differing from the interpreter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence<T: Float = f32> {
+    /// Diverging backend.
+    pub backend: Backend,
+    /// Index of sample.
+    pub sample: usize,
+    /// Input values of sample, in order of given inputs.
+    pub inputs: Vec<T>,
+    /// Value computed by the interpreter.
+    pub expected: T,
+    /// Value computed by the backend.
+    pub actual: T,
+}
+
+/// Result of differential testing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report<T: Float = f32> {
+    /// Backends compared with the interpreter.
+    pub backends: Vec<Backend>,
+    /// Amount of samples evaluated.
+    pub samples: usize,
+    /// Divergences beyond tolerance, by backend and sample.
+    pub divergences: Vec<Divergence<T>>,
+}
+
+impl<T: Float> Report<T> {
+    /// Returns true if no backend diverged.
+    pub fn is_consistent(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Settings of differential testing.
+#[derive(Debug, Clone)]
+pub struct Harness {
+    /// Amount of sampled input sets.
+    pub samples: usize,
+    /// Allowed difference, relative to magnitude of values above 1 and absolute below.
+    pub tolerance: f64,
+    /// Seed of input sampling, so divergences can be reproduced.
+    pub seed: u64,
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self {
+            samples: 100,
+            tolerance: 1e-6,
+            seed: 0,
+        }
+    }
+}
+
+impl Harness {
+    /// Evaluates graph with given root over inputs sampled from their distributions
+    /// with every backend, reporting divergences from the interpreter.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn run<T: Float>(
+        &self,
+        root: &Handle<T>,
+        inputs: &[(Rc<RefCell<InputNode<T>>>, Distribution)],
+    ) -> Report<T> {
+        let mut rng = Rng::new(self.seed);
+        let samples: Vec<Vec<T>> = (0..self.samples)
+            .map(|_| {
+                inputs
+                    .iter()
+                    .map(|(_, distribution)| {
+                        // shifted by half of the resolution into `(0, 1)`, where quantiles are finite
+                        let u = ((rng.next_u64() >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+                        T::from_f64(distribution.quantile(u) as f64)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let original: Vec<T> = inputs.iter().map(|(x, _)| x.borrow().value()).collect();
+        let expected: Vec<T> = samples
+            .iter()
+            .map(|sample| {
+                for ((x, _), val) in inputs.iter().zip(sample) {
+                    x.borrow_mut().probe(*val);
+                }
+                root.borrow_mut().compute()
+            })
+            .collect();
+        for ((x, _), val) in inputs.iter().zip(&original) {
+            x.borrow_mut().probe(*val);
+        }
+
+        let mut compiled = CompiledGraph::new(root);
+        // inputs the root doesn't depend on aren't compiled
+        let indices: Vec<Option<usize>> = inputs
+            .iter()
+            .map(|(x, _)| compiled.input_index(x))
+            .collect();
+        let compiled = samples
+            .iter()
+            .map(|sample| {
+                for (idx, val) in indices.iter().zip(sample) {
+                    if let Some(idx) = idx {
+                        compiled.set_input(*idx, *val);
+                    }
+                }
+                compiled.eval()
+            })
+            .collect();
+        let results: Vec<(Backend, Vec<T>)> = std::iter::once((Backend::Compiled, compiled))
+            .chain(lanes(root, &indices, &samples))
+            .collect();
+
+        let mut divergences = Vec::new();
+        for (backend, actual) in &results {
+            for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                if !self.agree(*expected, *actual) {
+                    divergences.push(Divergence {
+                        backend: *backend,
+                        sample: i,
+                        inputs: samples[i].clone(),
+                        expected: *expected,
+                        actual: *actual,
+                    });
+                }
+            }
+        }
+        Report {
+            backends: results.iter().map(|(backend, _)| *backend).collect(),
+            samples: self.samples,
+            divergences,
+        }
+    }
+
+    /// Returns true if values are equal within tolerance, treating `NaN`s as equal.
+    fn agree<T: Float>(&self, a: T, b: T) -> bool {
+        let (a, b) = (a.to_f64(), b.to_f64());
+        if a.is_nan() || b.is_nan() {
+            return a.is_nan() && b.is_nan();
+        }
+        a == b || (a - b).abs() <= self.tolerance * a.abs().max(b.abs()).max(1.0)
+    }
+}
+
+/// Evaluates samples with compiled graph over lanes.
+#[cfg(feature = "simd")]
+fn lanes<T: Float>(
+    root: &Handle<T>,
+    indices: &[Option<usize>],
+    samples: &[Vec<T>],
+) -> Option<(Backend, Vec<T>)> {
+    let batch: std::collections::HashMap<usize, Vec<T>> = indices
+        .iter()
+        .enumerate()
+        .filter_map(|(i, idx)| Some(((*idx)?, samples.iter().map(|s| s[i]).collect())))
+        .collect();
+    let mut compiled = CompiledGraph::new(root);
+    Some((Backend::Lanes, compiled.eval_batch_lanes::<4>(&batch)))
+}
+
+#[cfg(not(feature = "simd"))]
+fn lanes<T: Float>(
+    _root: &Handle<T>,
+    _indices: &[Option<usize>],
+    _samples: &[Vec<T>],
+) -> Option<(Backend, Vec<T>)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{node::Node, stats::StatsNode};
+
+    #[test]
+    fn backends_agree() {
+        let x = InputNode::from_val(1.0f32);
+        let y = InputNode::from_val(2.0);
+        let root: Handle = Node::add(
+            Node::mul(Node::sin(x.clone()), y.clone()),
+            Node::lazy_select(x.clone(), Node::sqrt(x.clone()), Node::constant(0.0)),
+        );
+        let inputs = [
+            (
+                x.clone(),
+                Distribution::Uniform {
+                    low: -2.0,
+                    high: 2.0,
+                },
+            ),
+            (
+                y,
+                Distribution::Normal {
+                    mean: 0.0,
+                    std_dev: 3.0,
+                },
+            ),
+        ];
+        let report = Harness::default().run(&root, &inputs);
+        assert!(report.is_consistent(), "{:?}", report.divergences);
+        assert_eq!(report.samples, 100);
+        assert_eq!(x.borrow().value(), 1.0);
+        assert_eq!(root.borrow_mut().compute(), 1.0f32.sin() * 2.0 + 1.0);
+    }
+
+    #[test]
+    fn reports_divergence() {
+        // custom nodes are frozen at their current value by compilation
+        let x = InputNode::from_val(1.0f32);
+        let root: Handle = Node::neg(StatsNode::new(x.clone()));
+        let inputs = [(
+            x,
+            Distribution::Uniform {
+                low: 2.0,
+                high: 3.0,
+            },
+        )];
+        let report = Harness {
+            samples: 3,
+            ..Default::default()
+        }
+        .run(&root, &inputs);
+        let compiled: Vec<_> = report
+            .divergences
+            .iter()
+            .filter(|d| d.backend == Backend::Compiled)
+            .collect();
+        assert_eq!(compiled.len(), 3);
+        assert_eq!(compiled[0].actual, -1.0);
+        assert_eq!(compiled[0].expected, -compiled[0].inputs[0]);
+    }
+
+    #[test]
+    fn tolerance() {
+        let harness = Harness::default();
+        assert!(harness.agree(1e7f32, 1e7 + 1.0));
+        assert!(harness.agree(f32::NAN, f32::NAN));
+        assert!(!harness.agree(1.0f32, 1.001));
+        assert!(!harness.agree(f32::NAN, 0.0));
+    }
+}
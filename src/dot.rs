@@ -0,0 +1,150 @@
+//! Export of graphs to Graphviz DOT for visualization.
+//!
+//! Operation nodes are labeled with operation name and cache state, uncomputed ones
+//! are drawn dashed. Input nodes are labeled with caller-provided names and current values,
+//! other nodes without operation, like subgraphs, with their labels and drawn as 3D boxes.
+//! Edges point from operands to nodes using them.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    rc::Rc,
+};
+
+use crate::{
+    float::Float,
+    input::InputNode,
+    node::{node_addr, Handle},
+    ops::Operation,
+};
+
+/// Exports graph with given root as DOT digraph.
+/// `names` maps variable names to input nodes that appear in the graph.
+pub fn to_dot<T: Float>(
+    root: &Handle<T>,
+    names: &HashMap<String, Rc<RefCell<InputNode<T>>>>,
+) -> String {
+    let names: HashMap<usize, &str> = names
+        .iter()
+        .map(|(name, input)| (node_addr(input), name.as_str()))
+        .collect();
+    // sequential ids in discovery order, so output doesn't depend on addresses
+    let mut ids = HashMap::new();
+    let mut id = |node: &Handle<T>| {
+        let next = ids.len();
+        *ids.entry(node_addr(node)).or_insert(next)
+    };
+    let mut out = String::from("digraph {\n");
+    let mut visited = HashSet::new();
+    let mut stack = vec![root.clone()];
+    while let Some(node) = stack.pop() {
+        if !visited.insert(node_addr(&node)) {
+            continue;
+        }
+        let n = id(&node);
+        let label = {
+            let node = node.borrow();
+            node.operation()
+                .map(|opp| (operation_label(opp), node.is_dirty()))
+        };
+        let _ = match label {
+            Some((label, dirty)) => writeln!(
+                out,
+                "    n{} [label=\"{}\\n{}\"{}];",
+                n,
+                label,
+                if dirty { "dirty" } else { "cached" },
+                if dirty { ", style=dashed" } else { "" }
+            ),
+            None => {
+                // inputs just return stored value
+                let val = node.borrow_mut().compute();
+                let name = match names.get(&node_addr(&node)) {
+                    Some(name) => name.to_string(),
+                    None => node.borrow().label().unwrap_or("?").to_string(),
+                };
+                // custom nodes with operands, like subgraphs, are drawn collapsed
+                let composite = !node.borrow().operands().is_empty();
+                writeln!(
+                    out,
+                    "    n{} [label=\"{} = {}\", shape={}];",
+                    n,
+                    escape(&name),
+                    val,
+                    if composite { "box3d" } else { "box" }
+                )
+            }
+        };
+        let operands = node.borrow().operands();
+        for x in operands.iter() {
+            let _ = writeln!(out, "    n{} -> n{};", id(x), n);
+        }
+        stack.extend(operands.into_iter().rev());
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Returns operation name along with its constant parameters.
+pub(crate) fn operation_label<H, T: Float>(opp: &Operation<H, T>) -> String {
+    match opp {
+        Operation::Const(val) => format!("constant {}", val),
+        Operation::Pow(_, pow) => format!("pow {}", pow),
+        Operation::Log(_, base) => format!("log {}", base),
+        Operation::Eq(_, _, tolerance) => format!("eq ±{}", tolerance),
+        Operation::Softmax(_, i) => format!("softmax_component {}", i),
+        Operation::Piecewise(_, pieces, _) => {
+            let breakpoints: Vec<_> = pieces.iter().map(|(b, _)| b.to_string()).collect();
+            format!("piecewise {}", breakpoints.join(" "))
+        }
+        Operation::Polynomial(_, coeffs) => {
+            let coeffs: Vec<_> = coeffs.iter().map(|c| c.to_string()).collect();
+            format!("polynomial {}", coeffs.join(" "))
+        }
+        Operation::Quantize(_, step, rounding) => {
+            format!("quantize {} {}", step, rounding.name())
+        }
+        opp => opp.name().to_string(),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{Computable, Node};
+
+    #[test]
+    fn export() {
+        let x = Node::input(2.0f32);
+        let y = Node::input(3.0);
+        let shared = Node::mul(x.clone(), y.clone());
+        let root = Node::add(shared.clone(), Node::pow(shared, 2.0));
+        let names = HashMap::from([("x".to_string(), x), ("y\"".to_string(), y)]);
+        root.borrow_mut().compute();
+        let root: Handle = root;
+        let pow = root.borrow().operands()[1].clone();
+        pow.borrow_mut().reset_cache();
+
+        assert_eq!(
+            to_dot(&root, &names),
+            r#"digraph {
+    n0 [label="add\ndirty", style=dashed];
+    n1 -> n0;
+    n2 -> n0;
+    n1 [label="mul\ncached"];
+    n3 -> n1;
+    n4 -> n1;
+    n3 [label="x = 2", shape=box];
+    n4 [label="y\" = 3", shape=box];
+    n2 [label="pow 2\ndirty", style=dashed];
+    n1 -> n2;
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Errors of fallible graph evaluation.
+
+use std::{error::Error, fmt};
+
+use crate::{input::ValidationError, ops::DomainError, tensor::Shape, units::Unit};
+
+/// Error produced by `Computable::try_compute`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphError {
+    /// Operand value is outside of operation domain.
+    Domain(DomainError),
+    /// Operation produced `NaN` or infinite value from finite operands.
+    NonFinite {
+        /// Name of operation producing the value.
+        operation: &'static str,
+        /// Produced value.
+        value: f64,
+    },
+    /// Operand of operation with given name is already borrowed, usually due to a cycle.
+    BorrowConflict {
+        /// Name of operation whose operand couldn't be borrowed.
+        operation: &'static str,
+    },
+    /// Node is computed from itself, listing operations along the cycle.
+    Cycle {
+        /// Names of operations forming the cycle, `custom` for nodes without operation.
+        operations: Vec<&'static str>,
+    },
+    /// Evaluation budget ran out before computing node with given operation.
+    BudgetExceeded {
+        /// Name of operation evaluation stopped at.
+        operation: &'static str,
+        /// Amount of nodes computed before stopping.
+        evaluated: usize,
+    },
+    /// Evaluation was cancelled before computing node with given operation.
+    Cancelled {
+        /// Name of operation evaluation stopped at.
+        operation: &'static str,
+        /// Amount of nodes computed before stopping.
+        evaluated: usize,
+    },
+    /// Boolean operand is used where number is expected.
+    BooleanOperand {
+        /// Name of operation.
+        operation: &'static str,
+        /// Index of boolean operand.
+        operand: usize,
+    },
+    /// Name is already used by another node.
+    DuplicateName {
+        /// Conflicting name.
+        name: String,
+    },
+    /// No input is registered under given name.
+    UnknownInput {
+        /// Name of missing input.
+        name: String,
+    },
+    /// Operation is given amount of operands it doesn't accept.
+    Arity {
+        /// Name of operation.
+        operation: &'static str,
+        /// Minimal accepted amount of operands.
+        min: usize,
+        /// Maximal accepted amount of operands.
+        max: usize,
+        /// Given amount of operands.
+        actual: usize,
+    },
+    /// Graph is deeper than allowed.
+    TooDeep {
+        /// Depth of rejected graph.
+        depth: usize,
+        /// Maximal allowed depth.
+        limit: usize,
+    },
+    /// Operation has no symbolic derivative rule.
+    NotDifferentiable {
+        /// Name of operation.
+        operation: &'static str,
+    },
+    /// Operands of vector or matrix operation have incompatible shapes.
+    ShapeMismatch {
+        /// Name of operation.
+        operation: &'static str,
+        /// Shapes of operands.
+        shapes: Vec<Shape>,
+    },
+    /// Value asserted by `Node::assert_range` is out of range.
+    OutOfRange {
+        /// Label or expression of node computing the value.
+        node: String,
+        /// Offending value.
+        value: f64,
+        /// Lower bound of range.
+        lo: f64,
+        /// Upper bound of range.
+        hi: f64,
+    },
+    /// Operands of operation have units it doesn't accept, see `units`.
+    UnitMismatch {
+        /// Name of operation.
+        operation: &'static str,
+        /// Units of operands.
+        units: Vec<Unit>,
+    },
+    /// Value set to input violates one of its rules.
+    Validation(ValidationError),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::Domain(err) => err.fmt(f),
+            GraphError::NonFinite { operation, value } => {
+                write!(f, "{} produced non-finite value {}", operation, value)
+            }
+            GraphError::BorrowConflict { operation } => {
+                write!(f, "operand of {} is already borrowed", operation)
+            }
+            GraphError::Cycle { operations } => {
+                write!(f, "cycle through {}", operations.join(" -> "))
+            }
+            GraphError::BudgetExceeded {
+                operation,
+                evaluated,
+            } => write!(
+                f,
+                "evaluation budget exceeded at {} after {} nodes",
+                operation, evaluated
+            ),
+            GraphError::Cancelled {
+                operation,
+                evaluated,
+            } => write!(
+                f,
+                "evaluation cancelled at {} after {} nodes",
+                operation, evaluated
+            ),
+            GraphError::BooleanOperand { operation, operand } => {
+                write!(f, "boolean operand {} of {}", operand, operation)
+            }
+            GraphError::Arity {
+                operation,
+                min,
+                max,
+                actual,
+            } => {
+                write!(f, "{} expects ", operation)?;
+                match (min, max) {
+                    (min, max) if min == max => write!(f, "{}", min)?,
+                    (min, &usize::MAX) => write!(f, "at least {}", min)?,
+                    (min, max) => write!(f, "{} to {}", min, max)?,
+                }
+                write!(f, " operands, got {}", actual)
+            }
+            GraphError::DuplicateName { name } => write!(f, "name {} is already used", name),
+            GraphError::UnknownInput { name } => write!(f, "input {} isn't registered", name),
+            GraphError::TooDeep { depth, limit } => {
+                write!(f, "graph depth {} exceeds limit {}", depth, limit)
+            }
+            GraphError::NotDifferentiable { operation } => {
+                write!(f, "{} has no symbolic derivative", operation)
+            }
+            GraphError::ShapeMismatch { operation, shapes } => {
+                write!(f, "operands of {} have incompatible shapes", operation)?;
+                for (i, shape) in shapes.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { " " } else { ", " }, shape)?;
+                }
+                Ok(())
+            }
+            GraphError::OutOfRange {
+                node,
+                value,
+                lo,
+                hi,
+            } => {
+                write!(f, "{} of {} is outside of [{}, {}]", value, node, lo, hi)
+            }
+            GraphError::UnitMismatch { operation, units } => {
+                write!(f, "operands of {} have incompatible units", operation)?;
+                for (i, unit) in units.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { " " } else { ", " }, unit)?;
+                }
+                Ok(())
+            }
+            GraphError::Validation(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for GraphError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GraphError::Domain(err) => Some(err),
+            GraphError::Validation(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DomainError> for GraphError {
+    fn from(err: DomainError) -> Self {
+        GraphError::Domain(err)
+    }
+}
+
+impl From<ValidationError> for GraphError {
+    fn from(err: ValidationError) -> Self {
+        GraphError::Validation(err)
+    }
+}
@@ -0,0 +1,428 @@
+//! Limits and context of fallible graph evaluation.
+
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{error::GraphError, float::Float, ops::Operation};
+
+/// Flag for aborting evaluation, possibly from another thread.
+/// Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates token that isn't cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation of evaluations holding this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Deterministic pseudo-random generator, SplitMix64.
+/// Same seed always produces the same sequence, on any platform.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates generator with given seed.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns next uniformly distributed integer.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.state)
+    }
+
+    /// Returns next uniformly distributed value in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns independent generator for given key, without advancing this one.
+    pub fn fork(&self, key: u64) -> Self {
+        Self::new(mix(
+            self.state ^ mix(key.wrapping_add(0x9E37_79B9_7F4A_7C15))
+        ))
+    }
+}
+
+/// Scrambles bits of value, finalizer of SplitMix64.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Observer of node computations, e.g. for logging, progress reporting or metrics.
+/// Nodes are identified by their operation name, as in `GraphError`.
+pub trait ComputeObserver {
+    /// Called before computing node.
+    fn on_node_start(&mut self, _operation: &'static str) {}
+    /// Called after node was computed successfully, with its value.
+    fn on_node_end(&mut self, _operation: &'static str, _value: f64) {}
+    /// Called when cached result of node is used instead of computing it.
+    fn on_cache_hit(&mut self, _operation: &'static str) {}
+}
+
+/// Shared observer, so its state can be read after evaluation.
+impl<O: ComputeObserver + ?Sized> ComputeObserver for Rc<RefCell<O>> {
+    fn on_node_start(&mut self, operation: &'static str) {
+        self.borrow_mut().on_node_start(operation);
+    }
+
+    fn on_node_end(&mut self, operation: &'static str, value: f64) {
+        self.borrow_mut().on_node_end(operation, value);
+    }
+
+    fn on_cache_hit(&mut self, operation: &'static str) {
+        self.borrow_mut().on_cache_hit(operation);
+    }
+}
+
+/// Limits, progress and context of evaluation started with `Computable::try_compute`.
+/// Nodes taken from cache don't count towards the budget.
+/// Custom nodes can reach user data, clock and hooks of evaluation in their `try_compute`.
+#[derive(Default)]
+pub struct Evaluation {
+    /// Maximum amount of nodes to compute.
+    max_nodes: Option<usize>,
+    /// Moment after which evaluation is aborted.
+    deadline: Option<Instant>,
+    /// Token checked before computing each node.
+    cancellation: Option<CancellationToken>,
+    /// Amount of nodes computed so far.
+    evaluated: usize,
+    /// User data by type.
+    data: HashMap<TypeId, Box<dyn Any>>,
+    /// Source of current time, `Instant::now` if not set.
+    clock: Option<Rc<dyn Fn() -> Instant>>,
+    /// Callbacks invoked with operation name before computing each node.
+    hooks: Vec<Box<dyn FnMut(&'static str)>>,
+    /// Observers of node computations.
+    observers: Vec<Box<dyn ComputeObserver>>,
+    /// Generator seeded by evaluation seed.
+    seeded: Rng,
+    /// Index of simulation step.
+    step: u64,
+    /// Simulation time.
+    time: f64,
+    /// Callbacks resetting time dependent nodes, by node address.
+    on_step: HashMap<usize, Box<dyn Fn()>>,
+    /// Generator of current step, created on first use.
+    rng: Option<Rng>,
+}
+
+impl fmt::Debug for Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Evaluation")
+            .field("max_nodes", &self.max_nodes)
+            .field("deadline", &self.deadline)
+            .field("cancellation", &self.cancellation)
+            .field("evaluated", &self.evaluated)
+            .field("data", &self.data.len())
+            .field("hooks", &self.hooks.len())
+            .field("observers", &self.observers.len())
+            .field("seeded", &self.seeded)
+            .field("step", &self.step)
+            .field("time", &self.time)
+            .finish()
+    }
+}
+
+impl Evaluation {
+    /// Creates evaluation without limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits amount of nodes computed.
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// Limits wall-clock time of evaluation, counting from this call.
+    /// Time is taken from clock set before this call.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(self.now() + timeout);
+        self
+    }
+
+    /// Replaces source of current time, for example to make evaluation deterministic.
+    pub fn clock(mut self, clock: impl Fn() -> Instant + 'static) -> Self {
+        self.clock = Some(Rc::new(clock));
+        self
+    }
+
+    /// Returns current time according to clock of evaluation.
+    pub fn now(&self) -> Instant {
+        self.clock
+            .as_ref()
+            .map_or_else(Instant::now, |clock| clock())
+    }
+
+    /// Attaches user data, replacing previously attached data of the same type.
+    pub fn with_data<D: Any>(mut self, data: D) -> Self {
+        self.data.insert(TypeId::of::<D>(), Box::new(data));
+        self
+    }
+
+    /// Returns attached user data of given type.
+    pub fn data<D: Any>(&self) -> Option<&D> {
+        self.data.get(&TypeId::of::<D>())?.downcast_ref()
+    }
+
+    /// Returns mutable reference to attached user data of given type.
+    pub fn data_mut<D: Any>(&mut self) -> Option<&mut D> {
+        self.data.get_mut(&TypeId::of::<D>())?.downcast_mut()
+    }
+
+    /// Seeds random generators of evaluation.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seeded = Rng::new(seed);
+        self.rng = None;
+        self
+    }
+
+    /// Sets index of simulation step, each step has its own random sequence.
+    pub fn at_step(mut self, step: u64) -> Self {
+        self.step = step;
+        self.rng = None;
+        self
+    }
+
+    /// Sets simulation time.
+    pub fn at_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Returns simulation time, which is independent of wall clock.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Returns index of simulation step.
+    pub fn step_index(&self) -> u64 {
+        self.step
+    }
+
+    /// Advances simulation time by `dt` and moves to the next step,
+    /// invalidating time nodes computed in this evaluation.
+    pub fn step(&mut self, dt: f64) {
+        self.time += dt;
+        self.step += 1;
+        self.rng = None;
+        self.on_step.values().for_each(|reset| reset());
+    }
+
+    /// Registers callback invoked on each step, once per key.
+    pub(crate) fn on_step(&mut self, key: usize, reset: impl Fn() + 'static) {
+        self.on_step.entry(key).or_insert_with(|| Box::new(reset));
+    }
+
+    /// Returns random generator of current step, shared by all nodes.
+    /// Values depend on order of draws, use `rng_for` when order may vary.
+    pub fn rng(&mut self) -> &mut Rng {
+        let (seeded, step) = (&self.seeded, self.step);
+        self.rng.get_or_insert_with(|| seeded.fork(step))
+    }
+
+    /// Returns random generator of current step for given key, such as node or thread index.
+    /// Its sequence depends only on seed, step and key, not on other draws.
+    pub fn rng_for(&self, key: u64) -> Rng {
+        self.seeded.fork(self.step).fork(key)
+    }
+
+    /// Registers callback invoked with operation name before computing each node.
+    pub fn on_node(mut self, hook: impl FnMut(&'static str) + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Registers observer of node computations.
+    pub fn observer(mut self, observer: impl ComputeObserver + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Aborts evaluation once given token is cancelled.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Returns amount of nodes computed so far.
+    pub fn evaluated(&self) -> usize {
+        self.evaluated
+    }
+
+    /// Accounts computation of node with given operation,
+    /// failing if budget is exhausted or evaluation is cancelled.
+    pub fn tick<H, T: Float>(&mut self, opp: &Operation<H, T>) -> Result<(), GraphError> {
+        if self
+            .cancellation
+            .as_ref()
+            .map_or(false, |token| token.is_cancelled())
+        {
+            return Err(GraphError::Cancelled {
+                operation: opp.name(),
+                evaluated: self.evaluated,
+            });
+        }
+        let exceeded = self.max_nodes.map_or(false, |max| self.evaluated >= max)
+            || self
+                .deadline
+                .map_or(false, |deadline| self.now() >= deadline);
+        if exceeded {
+            return Err(GraphError::BudgetExceeded {
+                operation: opp.name(),
+                evaluated: self.evaluated,
+            });
+        }
+        self.evaluated += 1;
+        self.hooks.iter_mut().for_each(|hook| hook(opp.name()));
+        self.observers
+            .iter_mut()
+            .for_each(|observer| observer.on_node_start(opp.name()));
+        Ok(())
+    }
+
+    /// Notifies observers that node with given operation was computed.
+    pub fn computed<T: Float>(&mut self, operation: &'static str, val: T) {
+        self.observers
+            .iter_mut()
+            .for_each(|observer| observer.on_node_end(operation, val.to_f64()));
+    }
+
+    /// Notifies observers that cached result of node with given operation was used.
+    pub fn cache_hit(&mut self, operation: &'static str) {
+        self.observers
+            .iter_mut()
+            .for_each(|observer| observer.on_cache_hit(operation));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_budget() {
+        let mut eval = Evaluation::new().max_nodes(2);
+        let opp = Operation::<()>::Const(1.0);
+        assert_eq!(eval.tick(&opp), Ok(()));
+        assert_eq!(eval.tick(&opp), Ok(()));
+        assert_eq!(
+            eval.tick(&opp),
+            Err(GraphError::BudgetExceeded {
+                operation: "constant",
+                evaluated: 2
+            })
+        );
+        assert_eq!(eval.evaluated(), 2);
+    }
+
+    #[test]
+    fn cancellation() {
+        let token = CancellationToken::new();
+        let mut eval = Evaluation::new().cancellation(token.clone());
+        let opp = Operation::<()>::Const(1.0);
+        assert_eq!(eval.tick(&opp), Ok(()));
+
+        std::thread::spawn(move || token.cancel()).join().unwrap();
+        assert_eq!(
+            eval.tick(&opp),
+            Err(GraphError::Cancelled {
+                operation: "constant",
+                evaluated: 1
+            })
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        let mut eval = Evaluation::new().timeout(Duration::ZERO);
+        assert!(eval.tick(&Operation::<()>::Const(1.0)).is_err());
+
+        let mut eval = Evaluation::new().timeout(Duration::from_secs(3600));
+        assert!(eval.tick(&Operation::<()>::Const(1.0)).is_ok());
+    }
+
+    #[test]
+    fn context() {
+        use std::cell::{Cell, RefCell};
+
+        let start = Instant::now();
+        let time = Rc::new(Cell::new(start));
+        let clock = time.clone();
+        let names = Rc::new(RefCell::new(Vec::new()));
+        let sink = names.clone();
+        let mut eval = Evaluation::new()
+            .clock(move || clock.get())
+            .timeout(Duration::from_secs(1))
+            .with_data(7u64)
+            .on_node(move |name| sink.borrow_mut().push(name));
+
+        *eval.data_mut::<u64>().unwrap() += 1;
+        assert_eq!(eval.data::<u64>(), Some(&8));
+        assert_eq!(eval.data::<i32>(), None);
+
+        assert!(eval.tick(&Operation::<()>::Const(1.0)).is_ok());
+        time.set(start + Duration::from_secs(2));
+        assert_eq!(eval.now(), start + Duration::from_secs(2));
+        assert!(eval.tick(&Operation::<()>::Sin(())).is_err());
+        assert_eq!(*names.borrow(), vec!["constant"]);
+    }
+
+    #[test]
+    fn random() {
+        let draw =
+            |eval: &mut Evaluation| (0..3).map(|_| eval.rng().next_u64()).collect::<Vec<_>>();
+        let mut eval = Evaluation::new().seed(42).at_step(3);
+        let first = draw(&mut eval);
+        assert_eq!(draw(&mut Evaluation::new().at_step(3).seed(42)), first);
+        assert_ne!(draw(&mut Evaluation::new().seed(42).at_step(4)), first);
+        assert_ne!(draw(&mut Evaluation::new().seed(43).at_step(3)), first);
+        assert_eq!(
+            draw(&mut Evaluation::new()),
+            draw(&mut Evaluation::new().seed(0))
+        );
+
+        // keyed generators don't depend on other draws
+        let keyed = eval.rng_for(1).next_u64();
+        assert_eq!(
+            Evaluation::new().seed(42).at_step(3).rng_for(1).next_u64(),
+            keyed
+        );
+        assert_ne!(eval.rng_for(2).next_u64(), keyed);
+
+        let mut rng = Rng::new(0);
+        assert!((0..1000)
+            .map(|_| rng.next_f64())
+            .all(|x| (0.0..1.0).contains(&x)));
+    }
+}
@@ -0,0 +1,962 @@
+//! Importer for a subset of Excel formula syntax.
+//!
+//! Cell references become named input nodes, arithmetic and supported functions
+//! are mapped to graph operations.
+//! Supported syntax: numbers, cell references (`A1`, `$B$2`) up to `XFD1048576`,
+//! ranges inside functions, operators `+`, `-`, `*`, `/`, `^` (with constant exponent),
+//! comparisons `=`, `<>`, `<`, `<=`, `>`, `>=`, and functions `SUM`, `PRODUCT`, `AVERAGE`,
+//! `SIN`, `ASIN`, `ACOS`, `ATAN`, `COS`, `TAN`, `TANH`, `EXP`, `LN`, `LOG`, `SQRT`, `ABS`,
+//! `POWER`, `MIN`, `MAX`, `IF`.
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+    input::InputNode,
+    node::{Computable, Kind, Node},
+    ops::{DivPolicy, Reduction},
+    parse::DEFAULT_MAX_DEPTH,
+};
+
+/// Number of columns of a worksheet, the last one being `XFD`.
+const MAX_COLUMNS: u32 = 16_384;
+/// Number of rows of a worksheet.
+const MAX_ROWS: u32 = 1_048_576;
+/// Largest amount of cells a range may expand into.
+const MAX_RANGE_CELLS: u64 = 65_536;
+
+/// Error produced when formula can't be imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExcelError {
+    /// Byte offset in formula where error was detected.
+    pub position: usize,
+    /// Error description.
+    pub kind: ExcelErrorKind,
+}
+
+/// Kinds of import errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExcelErrorKind {
+    /// Character which can't start any token.
+    UnexpectedChar(char),
+    /// Token which doesn't fit grammar at its position.
+    UnexpectedToken(String),
+    /// Formula ended unexpectedly.
+    UnexpectedEnd,
+    /// Syntax is valid Excel, but has no counterpart among graph operations.
+    Unsupported(String),
+    /// Cell reference beyond the last column `XFD` or the last row `1048576`.
+    InvalidReference(String),
+    /// Formula is nested deeper than given limit.
+    TooDeep(usize),
+}
+
+impl fmt::Display for ExcelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ExcelErrorKind::*;
+
+        match &self.kind {
+            UnexpectedChar(c) => write!(f, "unexpected character '{}'", c)?,
+            UnexpectedToken(t) => write!(f, "unexpected token '{}'", t)?,
+            UnexpectedEnd => write!(f, "unexpected end of formula")?,
+            Unsupported(what) => write!(f, "unsupported {}", what)?,
+            InvalidReference(cell) => write!(f, "invalid cell reference {}", cell)?,
+            TooDeep(limit) => write!(f, "formula nested deeper than {}", limit)?,
+        }
+        write!(f, " at position {}", self.position)
+    }
+}
+
+impl std::error::Error for ExcelError {}
+
+/// Formula token.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    /// Cell reference or function name, `$` markers are stripped.
+    Ident(String),
+    Op(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Ident(s) => write!(f, "{}", s),
+            Token::Op(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+/// Splits formula into tokens paired with their byte offsets.
+fn tokenize(formula: &str) -> Result<Vec<(usize, Token)>, ExcelError> {
+    let mut tokens = Vec::new();
+    let mut chars = formula.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = pos;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let num = formula[pos..end].parse().map_err(|_| ExcelError {
+                position: pos,
+                kind: ExcelErrorKind::UnexpectedToken(formula[pos..end].to_string()),
+            })?;
+            tokens.push((pos, Token::Number(num)));
+        } else if c.is_ascii_alphabetic() || c == '$' {
+            let mut ident = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '$' || c == '_') {
+                    break;
+                }
+                if c != '$' {
+                    ident.push(c.to_ascii_uppercase());
+                }
+                chars.next();
+            }
+            tokens.push((pos, Token::Ident(ident)));
+        } else if "+-*/^(),:=<>&%".contains(c) {
+            tokens.push((pos, Token::Op(c)));
+            chars.next();
+        } else {
+            return Err(ExcelError {
+                position: pos,
+                kind: ExcelErrorKind::UnexpectedChar(c),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Splits cell reference like `AB12` into zero-based column and one-based row,
+/// returning `None` for references outside of worksheet, see `is_reference`.
+fn parse_cell(name: &str) -> Option<(u32, u32)> {
+    if !is_reference(name) {
+        return None;
+    }
+    let split = name.find(|c: char| c.is_ascii_digit())?;
+    let (col, row) = name.split_at(split);
+    let row = row.parse().ok().filter(|r| (1..=MAX_ROWS).contains(r))?;
+    let col = col.chars().try_fold(0u32, |acc, c| {
+        acc.checked_mul(26)?
+            .checked_add(c as u32 - 'A' as u32 + 1)
+            .filter(|col| *col <= MAX_COLUMNS)
+    })?;
+    Some((col - 1, row))
+}
+
+/// Returns true if name has form of cell reference, letters followed by digits,
+/// regardless of whether it fits into worksheet.
+fn is_reference(name: &str) -> bool {
+    let split = match name.find(|c: char| c.is_ascii_digit()) {
+        Some(split) => split,
+        None => return false,
+    };
+    let (col, row) = name.split_at(split);
+    !col.is_empty()
+        && col.chars().all(|c| c.is_ascii_uppercase())
+        && row.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Builds cell name from zero-based column and one-based row.
+fn cell_name(col: u32, row: u32) -> String {
+    let mut letters = Vec::new();
+    let mut col = col + 1;
+    while col > 0 {
+        letters.push((b'A' + ((col - 1) % 26) as u8) as char);
+        col = (col - 1) / 26;
+    }
+    letters.iter().rev().collect::<String>() + &row.to_string()
+}
+
+/// Intermediate parse result, keeps literals unfolded so they can serve as exponents.
+enum Value {
+    Number(f32),
+    Node(Rc<RefCell<dyn Computable>>),
+}
+
+impl Value {
+    fn into_node(self) -> Rc<RefCell<dyn Computable>> {
+        match self {
+            Value::Number(n) => Node::constant(n),
+            Value::Node(node) => node,
+        }
+    }
+}
+
+/// Imports formulas, sharing input nodes for cells referenced by multiple formulas.
+#[derive(Default)]
+pub struct Importer {
+    cells: HashMap<String, Rc<RefCell<InputNode>>>,
+}
+
+impl Importer {
+    /// Creates importer without known cells.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns input node for given cell, if any imported formula referenced it.
+    pub fn cell(&self, name: &str) -> Option<Rc<RefCell<InputNode>>> {
+        self.cells
+            .get(&name.replace('$', "").to_ascii_uppercase())
+            .cloned()
+    }
+
+    /// Returns all referenced cells.
+    pub fn cells(&self) -> &HashMap<String, Rc<RefCell<InputNode>>> {
+        &self.cells
+    }
+
+    /// Consumes importer, returning all referenced cells.
+    pub fn into_cells(self) -> HashMap<String, Rc<RefCell<InputNode>>> {
+        self.cells
+    }
+
+    /// Imports single formula, optionally starting with `=`.
+    /// New cells are created as input nodes with value `0.0`.
+    /// Formulas producing graphs deeper than `DEFAULT_MAX_DEPTH` are rejected.
+    pub fn import(&mut self, formula: &str) -> Result<Rc<RefCell<dyn Computable>>, ExcelError> {
+        self.import_with_limit(formula, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Imports formula like `import`, rejecting formulas producing graphs deeper than
+    /// `max_depth`, so untrusted formulas can't overflow the stack.
+    pub fn import_with_limit(
+        &mut self,
+        formula: &str,
+        max_depth: usize,
+    ) -> Result<Rc<RefCell<dyn Computable>>, ExcelError> {
+        let mut tokens = tokenize(formula)?;
+        if let Some((_, Token::Op('='))) = tokens.first() {
+            tokens.remove(0);
+        }
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: formula.len(),
+            cells: &mut self.cells,
+            nesting: 0,
+            max_depth,
+        };
+        let value = parser.comparison()?;
+        match parser.tokens.get(parser.pos) {
+            Some((pos, token)) => Err(ExcelError {
+                position: *pos,
+                kind: ExcelErrorKind::UnexpectedToken(token.to_string()),
+            }),
+            None => Ok(value.into_node()),
+        }
+    }
+}
+
+/// Imports single formula, returning its root and referenced cells.
+/// Formulas producing graphs deeper than `DEFAULT_MAX_DEPTH` are rejected.
+#[allow(clippy::type_complexity)]
+pub fn import(
+    formula: &str,
+) -> Result<
+    (
+        Rc<RefCell<dyn Computable>>,
+        HashMap<String, Rc<RefCell<InputNode>>>,
+    ),
+    ExcelError,
+> {
+    import_with_limit(formula, DEFAULT_MAX_DEPTH)
+}
+
+/// Imports formula like `import`, rejecting formulas producing graphs deeper than `max_depth`.
+#[allow(clippy::type_complexity)]
+pub fn import_with_limit(
+    formula: &str,
+    max_depth: usize,
+) -> Result<
+    (
+        Rc<RefCell<dyn Computable>>,
+        HashMap<String, Rc<RefCell<InputNode>>>,
+    ),
+    ExcelError,
+> {
+    let mut importer = Importer::new();
+    let root = importer.import_with_limit(formula, max_depth)?;
+    Ok((root, importer.into_cells()))
+}
+
+/// Recursive descent parser over formula tokens.
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Formula length, reported as position of unexpected end.
+    end: usize,
+    cells: &'a mut HashMap<String, Rc<RefCell<InputNode>>>,
+    /// Current nesting of unary expressions.
+    nesting: usize,
+    max_depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(p, _)| *p)
+    }
+
+    fn error(&self, kind: ExcelErrorKind) -> ExcelError {
+        ExcelError {
+            position: self.position(),
+            kind,
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, ExcelError> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error(ExcelErrorKind::UnexpectedEnd))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, op: char) -> Result<(), ExcelError> {
+        match self.peek() {
+            Some(Token::Op(c)) if *c == op => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(t) => Err(self.error(ExcelErrorKind::UnexpectedToken(t.to_string()))),
+            None => Err(self.error(ExcelErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    fn cell(&mut self, name: String) -> Rc<RefCell<dyn Computable>> {
+        self.cells
+            .entry(name.clone())
+            .or_insert_with(|| InputNode::with_label(0.0, name))
+            .clone()
+    }
+
+    /// Fails if value is a node deeper than allowed.
+    fn limit(&self, value: Value) -> Result<Value, ExcelError> {
+        match &value {
+            Value::Node(node) if node.borrow().depth() > self.max_depth => {
+                Err(self.error(ExcelErrorKind::TooDeep(self.max_depth)))
+            }
+            _ => Ok(value),
+        }
+    }
+
+    /// Returns true if current and next tokens are given operator characters.
+    fn peek_pair(&self, first: char, second: char) -> bool {
+        self.peek() == Some(&Token::Op(first))
+            && self.tokens.get(self.pos + 1).map(|(_, t)| t) == Some(&Token::Op(second))
+    }
+
+    /// comparison := expr (('=' | '<>' | '<' | '<=' | '>' | '>=') expr)?
+    fn comparison(&mut self) -> Result<Value, ExcelError> {
+        let lhs = self.expr()?;
+        let op = if self.peek_pair('<', '>') {
+            "<>"
+        } else if self.peek_pair('<', '=') {
+            "<="
+        } else if self.peek_pair('>', '=') {
+            ">="
+        } else {
+            match self.peek() {
+                Some(Token::Op('=')) => "=",
+                Some(Token::Op('<')) => "<",
+                Some(Token::Op('>')) => ">",
+                _ => return Ok(lhs),
+            }
+        };
+        self.pos += op.len();
+        let rhs = self.expr()?;
+        if let (Value::Number(x), Value::Number(y)) = (&lhs, &rhs) {
+            let holds = match op {
+                "=" => x == y,
+                "<>" => x != y,
+                "<" => x < y,
+                "<=" => x <= y,
+                ">" => x > y,
+                _ => x >= y,
+            };
+            return Ok(Value::Number(if holds { 1.0 } else { 0.0 }));
+        }
+        let (x, y) = (lhs.into_node(), rhs.into_node());
+        self.limit(Value::Node(match op {
+            "=" => Node::eq(x, y, 0.0),
+            "<>" => Node::or(Node::lt(x.clone(), y.clone()), Node::gt(x, y)),
+            "<" => Node::lt(x, y),
+            "<=" => Node::le(x, y),
+            ">" => Node::gt(x, y),
+            _ => Node::ge(x, y),
+        }))
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Value, ExcelError> {
+        let mut lhs = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = match (lhs, rhs) {
+                (Value::Number(x), Value::Number(y)) if op == '+' => Value::Number(x + y),
+                (Value::Number(x), Value::Number(y)) => Value::Number(x - y),
+                (x, y) if op == '+' => Value::Node(Node::add(x.into_node(), y.into_node())),
+                (x, y) => Value::Node(Node::sub(x.into_node(), y.into_node())),
+            };
+            lhs = self.limit(lhs)?;
+        }
+        Ok(lhs)
+    }
+
+    /// term := power (('*' | '/') power)*
+    fn term(&mut self) -> Result<Value, ExcelError> {
+        let mut lhs = self.power()?;
+        loop {
+            match self.peek() {
+                Some(Token::Op('*')) => {
+                    self.pos += 1;
+                    let rhs = self.power()?;
+                    lhs = match (lhs, rhs) {
+                        (Value::Number(x), Value::Number(y)) => Value::Number(x * y),
+                        (x, y) => Value::Node(Node::mul(x.into_node(), y.into_node())),
+                    };
+                    lhs = self.limit(lhs)?;
+                }
+                Some(Token::Op('/')) => {
+                    self.pos += 1;
+                    let rhs = self.power()?;
+                    lhs = match (lhs, rhs) {
+                        (Value::Number(x), Value::Number(y)) if y != 0.0 => Value::Number(x / y),
+                        // division by zero fails checked evaluation like `#DIV/0!`
+                        (x, y) => Value::Node(Node::div_with_policy(
+                            x.into_node(),
+                            y.into_node(),
+                            DivPolicy::Error,
+                        )),
+                    };
+                    lhs = self.limit(lhs)?;
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    /// power := unary ('^' unary)*
+    fn power(&mut self) -> Result<Value, ExcelError> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op('^')) = self.peek() {
+            self.pos += 1;
+            let pos = self.position();
+            lhs = match (lhs, self.unary()?) {
+                (Value::Number(x), Value::Number(y)) => Value::Number(x.powf(y)),
+                (x, Value::Number(y)) => Value::Node(Node::pow(x.into_node(), y)),
+                (_, Value::Node(_)) => {
+                    return Err(ExcelError {
+                        position: pos,
+                        kind: ExcelErrorKind::Unsupported("non-constant exponent".into()),
+                    })
+                }
+            };
+            lhs = self.limit(lhs)?;
+        }
+        Ok(lhs)
+    }
+
+    /// unary := ('-' | '+') unary | primary
+    fn unary(&mut self) -> Result<Value, ExcelError> {
+        // every nesting level passes here, so limiting it bounds parser recursion
+        self.nesting += 1;
+        if self.nesting > self.max_depth {
+            return Err(self.error(ExcelErrorKind::TooDeep(self.max_depth)));
+        }
+        let value = match self.peek() {
+            Some(Token::Op('-')) => {
+                self.pos += 1;
+                match self.unary()? {
+                    Value::Number(n) => Value::Number(-n),
+                    Value::Node(node) => Value::Node(Node::neg(node)),
+                }
+            }
+            Some(Token::Op('+')) => {
+                self.pos += 1;
+                self.unary()?
+            }
+            _ => self.primary()?,
+        };
+        self.nesting -= 1;
+        self.limit(value)
+    }
+
+    /// primary := number | cell | function '(' args ')' | '(' expr ')'
+    fn primary(&mut self) -> Result<Value, ExcelError> {
+        let pos = self.position();
+        match self.next()? {
+            Token::Number(n) => Ok(Value::Number(n)),
+            Token::Op('(') => {
+                let value = self.comparison()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::Op('(')) => {
+                self.pos += 1;
+                self.function(name, pos)
+            }
+            Token::Ident(name) if parse_cell(&name).is_some() => {
+                if self.peek() == Some(&Token::Op(':')) {
+                    return Err(ExcelError {
+                        position: pos,
+                        kind: ExcelErrorKind::Unsupported("range outside of SUM".into()),
+                    });
+                }
+                Ok(Value::Node(self.cell(name)))
+            }
+            Token::Ident(name) if is_reference(&name) => Err(ExcelError {
+                position: pos,
+                kind: ExcelErrorKind::InvalidReference(name),
+            }),
+            t => Err(ExcelError {
+                position: pos,
+                kind: ExcelErrorKind::UnexpectedToken(t.to_string()),
+            }),
+        }
+    }
+
+    /// Parses comma separated function arguments up to closing parenthesis.
+    /// Cell ranges are expanded into separate arguments.
+    fn args(&mut self) -> Result<Vec<Value>, ExcelError> {
+        let mut args = Vec::new();
+        if self.peek() == Some(&Token::Op(')')) {
+            self.pos += 1;
+            return Ok(args);
+        }
+        loop {
+            let is_range = matches!(
+                (self.peek(), self.tokens.get(self.pos + 1).map(|(_, t)| t)),
+                (Some(Token::Ident(_)), Some(Token::Op(':')))
+            );
+            if is_range {
+                args.extend(self.range()?);
+            } else {
+                args.push(self.comparison()?);
+            }
+            match self.next()? {
+                Token::Op(',') => continue,
+                Token::Op(')') => return Ok(args),
+                t => {
+                    self.pos -= 1;
+                    return Err(self.error(ExcelErrorKind::UnexpectedToken(t.to_string())));
+                }
+            }
+        }
+    }
+
+    /// Parses cell reference used as a range corner.
+    fn corner(&mut self) -> Result<(u32, u32), ExcelError> {
+        let pos = self.position();
+        let token = self.next()?;
+        let kind = match token {
+            Token::Ident(name) => match parse_cell(&name) {
+                Some(cell) => return Ok(cell),
+                None if is_reference(&name) => ExcelErrorKind::InvalidReference(name),
+                None => ExcelErrorKind::UnexpectedToken(name),
+            },
+            token => ExcelErrorKind::UnexpectedToken(token.to_string()),
+        };
+        Err(ExcelError {
+            position: pos,
+            kind,
+        })
+    }
+
+    /// Parses `A1:B2` range into its cells, row by row.
+    fn range(&mut self) -> Result<Vec<Value>, ExcelError> {
+        let pos = self.position();
+        let (c1, r1) = self.corner()?;
+        self.expect(':')?;
+        let (c2, r2) = self.corner()?;
+        let size = (c1.max(c2) - c1.min(c2) + 1) as u64 * (r1.max(r2) - r1.min(r2) + 1) as u64;
+        if size > MAX_RANGE_CELLS {
+            return Err(ExcelError {
+                position: pos,
+                kind: ExcelErrorKind::Unsupported(format!("range of {} cells", size)),
+            });
+        }
+        let mut cells = Vec::new();
+        for row in r1.min(r2)..=r1.max(r2) {
+            for col in c1.min(c2)..=c1.max(c2) {
+                cells.push(Value::Node(self.cell(cell_name(col, row))));
+            }
+        }
+        Ok(cells)
+    }
+
+    fn function(&mut self, name: String, pos: usize) -> Result<Value, ExcelError> {
+        let arity_error = |name: &str| ExcelError {
+            position: pos,
+            kind: ExcelErrorKind::UnexpectedToken(format!("{} arguments", name)),
+        };
+        let mut args = self.args()?;
+        match name.as_str() {
+            "SUM" => {
+                if args.iter().all(|a| matches!(a, Value::Number(_))) {
+                    return Ok(Value::Number(args.iter().fold(0.0, |acc, a| match a {
+                        Value::Number(n) => acc + n,
+                        Value::Node(_) => acc,
+                    })));
+                }
+                Ok(Value::Node(Node::add_var(
+                    args.into_iter().map(Value::into_node).collect(),
+                )))
+            }
+            "PRODUCT" | "AVERAGE" | "MIN" | "MAX" if !args.is_empty() => {
+                let reduction = match name.as_str() {
+                    "PRODUCT" => Reduction::Prod,
+                    "AVERAGE" => Reduction::Mean,
+                    "MIN" => Reduction::Min,
+                    _ => Reduction::Max,
+                };
+                if args.iter().all(|a| matches!(a, Value::Number(_))) {
+                    let vals = args.iter().filter_map(|a| match a {
+                        Value::Number(n) => Some(*n),
+                        Value::Node(_) => None,
+                    });
+                    return Ok(Value::Number(reduction.apply(vals)));
+                }
+                let mut args: Vec<_> = args.into_iter().map(Value::into_node).collect();
+                Ok(Value::Node(match (reduction, args.len()) {
+                    (Reduction::Min, 2) => Node::min(args.remove(0), args.remove(0)),
+                    (Reduction::Max, 2) => Node::max(args.remove(0), args.remove(0)),
+                    _ => Node::reduce(args, reduction),
+                }))
+            }
+            "SIN" if args.len() == 1 => Ok(match args.remove(0) {
+                Value::Number(n) => Value::Number(n.sin()),
+                Value::Node(x) => Value::Node(Node::sin(x)),
+            }),
+            "ASIN" if args.len() == 1 => Ok(match args.remove(0) {
+                Value::Number(n) => Value::Number(n.asin()),
+                Value::Node(x) => Value::Node(Node::asin(x)),
+            }),
+            "ACOS" if args.len() == 1 => Ok(match args.remove(0) {
+                Value::Number(n) => Value::Number(n.acos()),
+                Value::Node(x) => Value::Node(Node::acos(x)),
+            }),
+            "ATAN" if args.len() == 1 => Ok(match args.remove(0) {
+                Value::Number(n) => Value::Number(n.atan()),
+                Value::Node(x) => Value::Node(Node::atan(x)),
+            }),
+            "POWER" if args.len() == 2 => match (args.remove(0), args.remove(0)) {
+                (Value::Number(x), Value::Number(y)) => Ok(Value::Number(x.powf(y))),
+                (x, Value::Number(y)) => Ok(Value::Node(Node::pow(x.into_node(), y))),
+                (_, Value::Node(_)) => Err(ExcelError {
+                    position: pos,
+                    kind: ExcelErrorKind::Unsupported("non-constant exponent".into()),
+                }),
+            },
+            "COS" | "TAN" | "TANH" | "EXP" | "LN" | "SQRT" | "ABS" if args.len() == 1 => {
+                Ok(match args.remove(0) {
+                    Value::Number(n) => Value::Number(match name.as_str() {
+                        "COS" => n.cos(),
+                        "TAN" => n.tan(),
+                        "TANH" => n.tanh(),
+                        "EXP" => n.exp(),
+                        "LN" => n.ln(),
+                        "SQRT" => n.sqrt(),
+                        _ => n.abs(),
+                    }),
+                    Value::Node(x) => Value::Node(match name.as_str() {
+                        "COS" => Node::cos(x),
+                        "TAN" => Node::tan(x),
+                        "TANH" => Node::tanh(x),
+                        "EXP" => Node::exp(x),
+                        "LN" => Node::ln(x),
+                        "SQRT" => Node::sqrt(x),
+                        _ => Node::abs(x),
+                    }),
+                })
+            }
+            // base defaults to 10
+            "LOG" if args.len() == 1 || args.len() == 2 => {
+                let base = match args.get(1) {
+                    Some(Value::Number(base)) => *base,
+                    Some(Value::Node(_)) => {
+                        return Err(ExcelError {
+                            position: pos,
+                            kind: ExcelErrorKind::Unsupported("non-constant base".into()),
+                        })
+                    }
+                    None => 10.0,
+                };
+                Ok(match args.remove(0) {
+                    Value::Number(n) => Value::Number(n.log(base)),
+                    Value::Node(x) => Value::Node(Node::log(x, base)),
+                })
+            }
+            // missing else branch gives `FALSE`, any nonzero condition is true
+            "IF" if args.len() == 2 || args.len() == 3 => {
+                let if_false = match args.len() {
+                    3 => args.pop().unwrap(),
+                    _ => Value::Number(0.0),
+                };
+                let if_true = args.pop().unwrap();
+                match args.remove(0) {
+                    Value::Number(cond) if cond != 0.0 => Ok(if_true),
+                    Value::Number(_) => Ok(if_false),
+                    Value::Node(cond) => {
+                        let (if_true, if_false) = (if_true.into_node(), if_false.into_node());
+                        Ok(Value::Node(if cond.borrow().kind() == Kind::Boolean {
+                            Node::select(cond, if_true, if_false)
+                        } else {
+                            Node::select(
+                                Node::eq(cond, Node::constant(0.0), 0.0),
+                                if_false,
+                                if_true,
+                            )
+                        }))
+                    }
+                }
+            }
+            "SIN" | "ASIN" | "ACOS" | "ATAN" | "POWER" | "MIN" | "MAX" | "IF" => {
+                Err(arity_error(&name))
+            }
+            "PRODUCT" | "AVERAGE" => Err(arity_error(&name)),
+            "COS" | "TAN" | "TANH" | "EXP" | "LN" | "SQRT" | "ABS" | "LOG" => {
+                Err(arity_error(&name))
+            }
+            _ => Err(ExcelError {
+                position: pos,
+                kind: ExcelErrorKind::Unsupported(format!("function {}", name)),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::GraphError, eval::Evaluation, input::Input, ops::DomainError};
+
+    fn round(x: f32, precision: u32) -> f32 {
+        let m = 10i32.pow(precision) as f32;
+        (x * m).round() / m
+    }
+
+    #[test]
+    fn arithmetic() {
+        let (root, cells) = import("=A1 + $B$1 * C1^2 - 1").unwrap();
+        assert_eq!(cells.len(), 3);
+        cells["A1"].borrow_mut().set(1.0);
+        cells["B1"].borrow_mut().set(2.0);
+        cells["C1"].borrow_mut().set(3.0);
+        assert_eq!(root.borrow_mut().compute(), 18.0);
+
+        cells["C1"].borrow_mut().set(4.0);
+        assert_eq!(root.borrow_mut().compute(), 32.0);
+    }
+
+    #[test]
+    fn division() {
+        let (root, cells) = import("=A1 / B1 * 2").unwrap();
+        cells["A1"].borrow_mut().set(3.0);
+        cells["B1"].borrow_mut().set(4.0);
+        assert_eq!(root.borrow_mut().compute(), 1.5);
+
+        cells["B1"].borrow_mut().set(0.0);
+        assert_eq!(
+            root.borrow_mut().try_compute(&mut Evaluation::new()),
+            Err(GraphError::Domain(DomainError {
+                operation: "div",
+                value: 0.0
+            }))
+        );
+
+        let (root, _) = import("=6 / 4").unwrap();
+        assert_eq!(root.borrow_mut().compute(), 1.5);
+        let (root, _) = import("=1 / 0").unwrap();
+        assert!(root
+            .borrow_mut()
+            .try_compute(&mut Evaluation::new())
+            .is_err());
+    }
+
+    #[test]
+    fn unary_minus_binds_before_power() {
+        let (root, _) = import("-2^2").unwrap();
+        assert_eq!(root.borrow_mut().compute(), 4.0);
+
+        let (root, cells) = import("-a1").unwrap();
+        cells["A1"].borrow_mut().set(3.0);
+        assert_eq!(root.borrow_mut().compute(), -3.0);
+    }
+
+    #[test]
+    fn functions() {
+        let (root, cells) = import("=SUM(A1:B2, 10) + sin(C1) + POWER(A1, 3)").unwrap();
+        assert_eq!(cells.len(), 5);
+        for (name, val) in [("A1", 1.0), ("A2", 2.0), ("B1", 3.0), ("B2", 4.0)] {
+            cells[name].borrow_mut().set(val);
+        }
+        cells["C1"].borrow_mut().set(std::f32::consts::FRAC_PI_2);
+        assert_eq!(round(root.borrow_mut().compute(), 5), 22.0);
+    }
+
+    #[test]
+    fn inverse_trig() {
+        let (root, cells) = import("=ASIN(A1) + ACOS(A1) + ATAN(1)").unwrap();
+        cells["A1"].borrow_mut().set(0.3);
+        assert_eq!(
+            round(root.borrow_mut().compute(), 5),
+            round(3.0 * std::f32::consts::FRAC_PI_4, 5)
+        );
+    }
+
+    #[test]
+    fn elementary() {
+        let (root, cells) = import("=SQRT(ABS(A1)) + LOG(100) + LOG(A2, 2) + LN(EXP(A1))").unwrap();
+        cells["A1"].borrow_mut().set(-4.0);
+        cells["A2"].borrow_mut().set(8.0);
+        assert_eq!(round(root.borrow_mut().compute(), 5), 2.0 + 2.0 + 3.0 - 4.0);
+
+        let (root, cells) = import("=MAX(A1:A3, 0) - MIN(A1, B1, 2)").unwrap();
+        for (name, val) in [("A1", -1.0), ("A2", 4.0), ("A3", 2.0), ("B1", -3.0)] {
+            cells[name].borrow_mut().set(val);
+        }
+        assert_eq!(root.borrow_mut().compute(), 7.0);
+        let (root, _) = import("=MAX(1, 3, 2)").unwrap();
+        assert_eq!(root.borrow_mut().compute(), 3.0);
+        assert!(import("=MIN()").is_err());
+
+        let (root, cells) = import("=PRODUCT(A1:A3) + AVERAGE(A1:B2)").unwrap();
+        for (name, val) in [
+            ("A1", 2.0),
+            ("A2", 3.0),
+            ("A3", 4.0),
+            ("B1", 1.0),
+            ("B2", 2.0),
+        ] {
+            cells[name].borrow_mut().set(val);
+        }
+        assert_eq!(root.borrow_mut().compute(), 24.0 + 2.0);
+        let (root, _) = import("=AVERAGE(1, 2) * PRODUCT(2, 3)").unwrap();
+        assert_eq!(root.borrow_mut().compute(), 9.0);
+        assert!(import("=PRODUCT()").is_err());
+
+        let (root, cells) = import("=COS(A1) + TAN(A1) + TANH(A1)").unwrap();
+        cells["A1"].borrow_mut().set(0.0);
+        assert_eq!(root.borrow_mut().compute(), 1.0);
+        assert!(import("=LOG(A1, A2)").is_err());
+    }
+
+    #[test]
+    fn conditions() {
+        let (root, cells) = import("=IF(A1 >= B1, A1 - B1, 0) + IF(A2, 10) + (A1 <> 3)").unwrap();
+        cells["A1"].borrow_mut().set(5.0);
+        cells["B1"].borrow_mut().set(2.0);
+        cells["A2"].borrow_mut().set(-1.0);
+        assert_eq!(root.borrow_mut().compute(), 3.0 + 10.0 + 1.0);
+
+        cells["A1"].borrow_mut().set(1.0);
+        cells["A2"].borrow_mut().set(0.0);
+        assert_eq!(root.borrow_mut().compute(), 1.0);
+        let (root, _) = import("=IF(1 = 2, 3, 4) + (1 < 2)").unwrap();
+        assert_eq!(root.borrow_mut().compute(), 5.0);
+        assert!(import("=IF(A1)").is_err());
+    }
+
+    #[test]
+    fn shared_cells() {
+        let mut importer = Importer::new();
+        let x = importer.import("=A1 * 2").unwrap();
+        let y = importer.import("=A1 + B1").unwrap();
+        assert_eq!(importer.cells().len(), 2);
+
+        importer.cell("$a$1").unwrap().borrow_mut().set(5.0);
+        assert_eq!(x.borrow_mut().compute(), 10.0);
+        assert_eq!(y.borrow_mut().compute(), 5.0);
+    }
+
+    #[test]
+    fn cell_names() {
+        assert_eq!(parse_cell("A1"), Some((0, 1)));
+        assert_eq!(parse_cell("AB12"), Some((27, 12)));
+        assert_eq!(parse_cell("A0"), None);
+        assert_eq!(parse_cell("SUM"), None);
+        assert_eq!(parse_cell("XFD1048576"), Some((16_383, 1_048_576)));
+        assert_eq!(parse_cell("XFE1"), None);
+        assert_eq!(parse_cell("A1048577"), None);
+        assert_eq!(parse_cell("ABCDEFGHIJKLMNOP1"), None);
+        assert_eq!(cell_name(27, 12), "AB12");
+        assert_eq!(cell_name(25, 3), "Z3");
+    }
+
+    #[test]
+    fn errors() {
+        let err = |f: &str| import(f).err().unwrap();
+        assert_eq!(
+            err("VLOOKUP(A1, B1:C2, 2)").kind,
+            ExcelErrorKind::Unsupported("function VLOOKUP".into())
+        );
+        assert_eq!(
+            err("=ABCDEFGH1 + 1").kind,
+            ExcelErrorKind::InvalidReference("ABCDEFGH1".into())
+        );
+        assert_eq!(
+            err("=SUM(A1:XFE1)").kind,
+            ExcelErrorKind::InvalidReference("XFE1".into())
+        );
+        assert_eq!(
+            err("=SUM(A1:ZZZZ999999)").kind,
+            ExcelErrorKind::InvalidReference("ZZZZ999999".into())
+        );
+        assert_eq!(
+            err("=SUM(A1:XFD1048576)").kind,
+            ExcelErrorKind::Unsupported("range of 17179869184 cells".into())
+        );
+        assert_eq!(
+            err("A1 ^ B1").kind,
+            ExcelErrorKind::Unsupported("non-constant exponent".into())
+        );
+        assert_eq!(err("A1 +").kind, ExcelErrorKind::UnexpectedEnd);
+        assert_eq!(
+            err("A1 B1").kind,
+            ExcelErrorKind::UnexpectedToken("B1".into())
+        );
+        assert_eq!(err("A1 # 2").kind, ExcelErrorKind::UnexpectedChar('#'));
+        assert_eq!(
+            err("(A1").to_string(),
+            "unexpected end of formula at position 3"
+        );
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nested = format!("={}1{}", "(".repeat(2000), ")".repeat(2000));
+        assert_eq!(
+            import(&nested).err().unwrap().kind,
+            ExcelErrorKind::TooDeep(DEFAULT_MAX_DEPTH)
+        );
+        let nested = format!("=-{}A1{}", "SIN(".repeat(10), ")".repeat(10));
+        let (root, _) = import_with_limit(&nested, 12).unwrap();
+        assert_eq!(root.borrow().depth(), 12);
+        assert_eq!(
+            import_with_limit(&nested, 11).err().unwrap().kind,
+            ExcelErrorKind::TooDeep(11)
+        );
+
+        // long chains don't nest parser, but build deep graphs
+        let chain = vec!["A1"; 300].join(" + ");
+        assert!(import_with_limit(&chain, 300).is_ok());
+        assert_eq!(
+            import(&chain).err().unwrap().kind,
+            ExcelErrorKind::TooDeep(DEFAULT_MAX_DEPTH)
+        );
+    }
+}
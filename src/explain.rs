@@ -0,0 +1,364 @@
+//! Annotated evaluation traces showing how results are produced.
+//!
+//! Explaining a node evaluates it once more, recording value of each node along with
+//! operands it was computed from. Lazy operations record only taken branches, and each
+//! node shared by several operations is evaluated and recorded once. Explanations
+//! of large graphs can be restricted to labelled subtrees and limited depth, see `Filter`.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    dot::operation_label,
+    float::Float,
+    node::{node_addr, Handle, Node},
+};
+
+/// Recorded evaluation of single node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step<T: Float = f32> {
+    /// Operation with its constant parameters, `input` for leaves without operation.
+    pub description: String,
+    /// Label of node, such as input name.
+    pub label: Option<String>,
+    /// Computed value.
+    pub value: T,
+    /// Indices of steps of operands, in evaluation order.
+    pub operands: Vec<usize>,
+}
+
+/// Evaluation trace, steps of operands come before steps using them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation<T: Float = f32> {
+    steps: Vec<Step<T>>,
+    /// Steps of roots of recorded subtrees.
+    roots: Vec<usize>,
+    /// Step of explained root, unless it was filtered out.
+    root: Option<usize>,
+    value: T,
+}
+
+impl<T: Float> Explanation<T> {
+    /// Returns recorded steps, root of the last subtree being the last one.
+    pub fn steps(&self) -> &[Step<T>] {
+        &self.steps
+    }
+
+    /// Returns step of explained root, unless it was filtered out.
+    pub fn root(&self) -> Option<&Step<T>> {
+        self.root.map(|i| &self.steps[i])
+    }
+
+    /// Returns steps of roots of recorded subtrees, in evaluation order.
+    pub fn roots(&self) -> impl Iterator<Item = &Step<T>> + '_ {
+        self.roots.iter().map(move |i| &self.steps[*i])
+    }
+
+    /// Returns explained value.
+    pub fn value(&self) -> T {
+        self.value
+    }
+}
+
+/// Restriction of explanation to parts of graph, so explanations of large graphs
+/// stay readable. Nodes outside of recorded subtrees are evaluated, but not recorded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    pattern: Option<String>,
+    max_depth: Option<usize>,
+}
+
+impl Filter {
+    /// Creates filter recording the whole graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records only subtrees rooted at nodes with labels matching pattern,
+    /// in which `*` matches any sequence of characters, e.g. `pricing/*`.
+    pub fn labels(mut self, pattern: &str) -> Self {
+        self.pattern = Some(pattern.to_string());
+        self
+    }
+
+    /// Records operands only up to given depth below root of each recorded subtree.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Returns true if subtree of node with given label is recorded.
+    fn focuses(&self, label: Option<&str>) -> bool {
+        match (&self.pattern, label) {
+            (None, _) => true,
+            (Some(pattern), Some(label)) => matches(pattern, label),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Returns true if text matches pattern, in which `*` matches any sequence of characters.
+fn matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    let (last, middle) = match parts.split_last() {
+        Some(split) => split,
+        // no wildcard
+        None => return rest.is_empty(),
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Position of node relative to recorded subtrees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position {
+    /// Outside of recorded subtrees, so its subtree is recorded if filter focuses it.
+    Outside,
+    /// Within recorded subtree at given depth below its root.
+    Within(usize),
+    /// Within recorded subtree below depth limit, so it's not recorded.
+    Truncated,
+}
+
+/// Explanation in progress.
+struct Recorder<'a, T: Float> {
+    filter: &'a Filter,
+    steps: Vec<Step<T>>,
+    roots: Vec<usize>,
+    /// Values of evaluated nodes by address.
+    values: HashMap<usize, T>,
+    /// Steps of recorded nodes by address.
+    recorded: HashMap<usize, usize>,
+}
+
+impl<'a, T: Float> Recorder<'a, T> {
+    /// Evaluates node at given position, recording its step after steps of its operands
+    /// if it's within recorded subtree. Returns its value and index of its step, if recorded.
+    fn record(&mut self, node: &Handle<T>, position: Position) -> (T, Option<usize>) {
+        let addr = node_addr(node);
+        let (opp, label, operands) = {
+            let node = node.borrow();
+            (
+                node.operation().cloned(),
+                node.label().map(String::from),
+                node.operands(),
+            )
+        };
+        let depth = match position {
+            Position::Within(depth) => Some(depth),
+            Position::Outside if self.filter.focuses(label.as_deref()) => Some(0),
+            _ => None,
+        };
+        if let Some(i) = self.recorded.get(&addr) {
+            return (self.steps[*i].value, Some(*i));
+        }
+        if let (Some(val), None) = (self.values.get(&addr), depth) {
+            return (*val, None);
+        }
+        let inner = match (depth, self.filter.max_depth) {
+            (Some(depth), Some(max)) if depth >= max => Position::Truncated,
+            (Some(depth), _) => Position::Within(depth + 1),
+            (None, _) => position,
+        };
+        let mut indices = Vec::new();
+        let (description, value) = match opp {
+            // values of operands are taken from their steps, so the trace is consistent
+            // even with volatile operands
+            Some(opp) => {
+                let value = opp.eval(|x| {
+                    let (val, i) = self.record(x, inner);
+                    indices.extend(i);
+                    val
+                });
+                (operation_label(&opp), value)
+            }
+            None => {
+                for x in &operands {
+                    indices.extend(self.record(x, inner).1);
+                }
+                let description = if operands.is_empty() {
+                    "input"
+                } else {
+                    "custom"
+                };
+                (description.to_string(), node.borrow_mut().compute())
+            }
+        };
+        self.values.insert(addr, value);
+        if depth.is_none() {
+            return (value, None);
+        }
+        let i = self.steps.len();
+        self.steps.push(Step {
+            description,
+            label,
+            value,
+            operands: indices,
+        });
+        self.recorded.insert(addr, i);
+        if depth == Some(0) {
+            self.roots.push(i);
+        }
+        (value, Some(i))
+    }
+}
+
+impl<T: Float> Node<T> {
+    /// Evaluates root, recording how each value was computed, see `explain` module.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn explain(root: &Handle<T>) -> Explanation<T> {
+        Node::explain_filtered(root, &Filter::new())
+    }
+
+    /// Evaluates root, recording only parts of graph selected by filter.
+    /// Nodes shared by recorded subtrees are recorded at their first occurrence.
+    pub fn explain_filtered(root: &Handle<T>, filter: &Filter) -> Explanation<T> {
+        let mut recorder = Recorder {
+            filter,
+            steps: Vec::new(),
+            roots: Vec::new(),
+            values: HashMap::new(),
+            recorded: HashMap::new(),
+        };
+        let (value, root) = recorder.record(root, Position::Outside);
+        Explanation {
+            steps: recorder.steps,
+            roots: recorder.roots,
+            root,
+            value,
+        }
+    }
+}
+
+impl<T: Float> fmt::Display for Explanation<T> {
+    /// Renders trace as indented trees of recorded subtrees. Steps used more than once
+    /// are numbered and expanded only at their first occurrence.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut uses = vec![0; self.steps.len()];
+        for step in &self.steps {
+            for i in &step.operands {
+                uses[*i] += 1;
+            }
+        }
+        let mut expanded = vec![false; self.steps.len()];
+        // steps along with prefix of their line and of lines of their operands
+        let mut stack: Vec<_> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|i| (*i, String::new(), String::new()))
+            .collect();
+        while let Some((i, prefix, indent)) = stack.pop() {
+            let step = &self.steps[i];
+            f.write_str(&prefix)?;
+            if uses[i] > 1 {
+                write!(f, "#{} ", i)?;
+            }
+            if let Some(label) = &step.label {
+                write!(f, "{}: ", label)?;
+            }
+            if expanded[i] {
+                writeln!(f, "{} (see above)", step.value)?;
+                continue;
+            }
+            writeln!(f, "{} = {}", step.description, step.value)?;
+            expanded[i] = true;
+            // pushed reversed, so the first operand is rendered first
+            for (n, x) in step.operands.iter().enumerate().rev() {
+                let last = n + 1 == step.operands.len();
+                let (branch, next) = if last {
+                    ("└─ ", "   ")
+                } else {
+                    ("├─ ", "│  ")
+                };
+                stack.push((
+                    *x,
+                    format!("{}{}", indent, branch),
+                    format!("{}{}", indent, next),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputNode;
+
+    #[test]
+    fn trace() {
+        let price = InputNode::with_label(10.0f32, "price");
+        let qty = InputNode::with_label(3.0, "qty");
+        let net = Node::mul(price, qty);
+        net.borrow_mut().set_label("net");
+        let total: Handle = Node::add(net.clone(), Node::mul(net, Node::constant(0.2)));
+        let explanation = Node::explain(&total);
+        assert_eq!(explanation.value(), 36.0);
+        assert_eq!(explanation.steps().len(), 6);
+        assert_eq!(explanation.root().unwrap().operands, vec![2, 4]);
+        assert_eq!(
+            explanation.to_string(),
+            "add = 36\n\
+             ├─ #2 net: mul = 30\n\
+             │  ├─ price: input = 10\n\
+             │  └─ qty: input = 3\n\
+             └─ mul = 6\n\
+             \x20  ├─ #2 net: 30 (see above)\n\
+             \x20  └─ constant 0.2 = 0.2\n"
+        );
+    }
+
+    #[test]
+    fn lazy_branches() {
+        let x = InputNode::from_val(-1.0f32);
+        let root: Handle = Node::lazy_select(x.clone(), Node::sqrt(x.clone()), Node::neg(x));
+        let explanation = Node::explain(&root);
+        assert_eq!(explanation.value(), 1.0);
+        let described: Vec<_> = explanation
+            .steps()
+            .iter()
+            .map(|step| step.description.as_str())
+            .collect();
+        assert_eq!(described, vec!["input", "neg", "lazy_select"]);
+    }
+
+    #[test]
+    fn filters() {
+        assert!(matches("pricing/*", "pricing/net"));
+        assert!(matches("*net*", "pricing/net/sum"));
+        assert!(matches("a*b*c", "abbc"));
+        assert!(!matches("a*b*c", "acb"));
+        assert!(!matches("net", "net2"));
+
+        let x = InputNode::with_label(2.0f32, "x");
+        let inner = Node::mul(x.clone(), Node::sin(x));
+        inner.borrow_mut().set_label("pricing/inner");
+        let root: Handle = Node::add(Node::exp(inner), Node::constant(1.0));
+
+        let explanation = Node::explain_filtered(&root, &Filter::new().labels("pricing/*"));
+        assert_eq!(explanation.value(), (2.0f32 * 2.0f32.sin()).exp() + 1.0);
+        assert!(explanation.root().is_none());
+        let roots: Vec<_> = explanation.roots().map(|step| step.label.clone()).collect();
+        assert_eq!(roots, vec![Some("pricing/inner".to_string())]);
+        assert_eq!(explanation.steps().len(), 3);
+
+        let explanation = Node::explain_filtered(&root, &Filter::new().max_depth(1));
+        assert_eq!(explanation.steps().len(), 3);
+        assert_eq!(
+            explanation.to_string(),
+            "add = 7.163192\n├─ exp = 6.163192\n└─ constant 1 = 1\n"
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Expression handles building nodes with arithmetic operators.
+
+use std::{
+    cell::RefCell,
+    ops::{Add, Div, Mul, Neg, Sub},
+    rc::Rc,
+};
+
+use crate::{
+    float::Float,
+    input::InputNode,
+    node::{Handle, Node},
+};
+
+/// Handle to node, building new nodes with arithmetic operators.
+///
+/// ```
+/// use teza::{expr::Expr, input::Input, node::Node};
+///
+/// let x1 = Node::input(1.0f32);
+/// let x2 = Node::input(2.0);
+/// let (e1, e2) = (Expr::from(x1.clone()), Expr::from(x2));
+/// let y = e1 + e2.clone() * e2.pow(2.0) - 3.0;
+/// assert_eq!(y.compute(), 6.0);
+///
+/// x1.borrow_mut().set(2.0);
+/// assert_eq!(y.compute(), 7.0);
+/// ```
+#[derive(Clone)]
+pub struct Expr<T: Float = f32>(Handle<T>);
+
+impl<T: Float> Expr<T> {
+    /// Returns handle to underlying node.
+    pub fn node(&self) -> Handle<T> {
+        self.0.clone()
+    }
+
+    /// Computes value of expression.
+    pub fn compute(&self) -> T {
+        self.0.borrow_mut().compute()
+    }
+
+    /// Builds constant expression, converting value to scalar type.
+    pub fn from_f64(val: f64) -> Self {
+        Self(Node::constant(T::from_f64(val)))
+    }
+
+    /// Raises expression to constant power.
+    pub fn pow(self, pow: T) -> Self {
+        Self(Node::pow(self.0, pow))
+    }
+
+    /// Computes sine of expression.
+    pub fn sin(self) -> Self {
+        Self(Node::sin(self.0))
+    }
+
+    /// Computes cosine of expression.
+    pub fn cos(self) -> Self {
+        Self(Node::cos(self.0))
+    }
+
+    /// Computes exponent of expression.
+    pub fn exp(self) -> Self {
+        Self(Node::exp(self.0))
+    }
+
+    /// Computes natural logarithm of expression.
+    pub fn ln(self) -> Self {
+        Self(Node::ln(self.0))
+    }
+
+    /// Computes square root of expression.
+    pub fn sqrt(self) -> Self {
+        Self(Node::sqrt(self.0))
+    }
+
+    /// Computes absolute value of expression.
+    pub fn abs(self) -> Self {
+        Self(Node::abs(self.0))
+    }
+
+    /// Computes hyperbolic tangent of expression.
+    pub fn tanh(self) -> Self {
+        Self(Node::tanh(self.0))
+    }
+
+    /// Computes logistic function of expression.
+    pub fn sigmoid(self) -> Self {
+        Self(Node::sigmoid(self.0))
+    }
+
+    /// Computes rectified linear unit of expression.
+    pub fn relu(self) -> Self {
+        Self(Node::relu(self.0))
+    }
+
+    /// Passes expression through, blocking its gradient.
+    pub fn detach(self) -> Self {
+        Self(Node::detach(self.0))
+    }
+}
+
+impl<T: Float> From<T> for Expr<T> {
+    fn from(val: T) -> Self {
+        Self(Node::constant(val))
+    }
+}
+
+impl<T: Float> From<Rc<RefCell<Node<T>>>> for Expr<T> {
+    fn from(node: Rc<RefCell<Node<T>>>) -> Self {
+        Self(node)
+    }
+}
+
+impl<T: Float> From<Rc<RefCell<InputNode<T>>>> for Expr<T> {
+    fn from(node: Rc<RefCell<InputNode<T>>>) -> Self {
+        Self(node)
+    }
+}
+
+impl<T: Float> From<Handle<T>> for Expr<T> {
+    fn from(node: Handle<T>) -> Self {
+        Self(node)
+    }
+}
+
+impl<T: Float> Add for Expr<T> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(Node::add(self.0, rhs.0))
+    }
+}
+
+impl<T: Float> Sub for Expr<T> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(Node::sub(self.0, rhs.0))
+    }
+}
+
+impl<T: Float> Mul for Expr<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self(Node::mul(self.0, rhs.0))
+    }
+}
+
+impl<T: Float> Div for Expr<T> {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Self(Node::div(self.0, rhs.0))
+    }
+}
+
+impl<T: Float> Neg for Expr<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(Node::neg(self.0))
+    }
+}
+
+/// Implements operators between expressions and scalars on either side.
+macro_rules! impl_scalar_ops {
+    ($t:ident, $($op:ident::$f:ident),*) => {
+        $(
+            impl $op<$t> for Expr<$t> {
+                type Output = Self;
+
+                fn $f(self, rhs: $t) -> Self {
+                    self.$f(Self::from(rhs))
+                }
+            }
+
+            impl $op<Expr<$t>> for $t {
+                type Output = Expr<$t>;
+
+                fn $f(self, rhs: Expr<$t>) -> Expr<$t> {
+                    Expr::from(self).$f(rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar_ops!(f32, Add::add, Sub::sub, Mul::mul, Div::div);
+impl_scalar_ops!(f64, Add::add, Sub::sub, Mul::mul, Div::div);
+
+/// Builds graph from formula written in Rust tokens, returning handle to its root.
+///
+/// Supports `+`, `-`, `*`, `/`, unary `-`, parentheses and `^` with constant exponent,
+/// which binds tighter than unary minus and is given as literal or parenthesized Rust
+/// expression. Identifiers refer to node handles in scope, which are cloned, numeric
+/// literals become constants and calls such as `sin(x)` or `max(x, y)` use `Node`
+/// constructors of the same name, taking node operands only.
+///
+/// ```
+/// use teza::{expr, input::Input, node::Node};
+///
+/// let (x1, x2, x3) = (Node::input(1.0f32), Node::input(2.0), Node::input(0.0));
+/// let y = expr!(x1 + x2 * sin(x3 ^ 2) + -x2 ^ 2 / 4);
+/// assert_eq!(y.borrow_mut().compute(), 0.0);
+///
+/// x3.borrow_mut().set(1.0);
+/// assert_eq!(y.borrow_mut().compute(), 1.0 + 2.0 * 1f32.sin() - 1.0);
+/// ```
+#[macro_export]
+macro_rules! expr {
+    // splits sum into terms, tracking whether operand is expected so `-` after operator
+    // or at the start is kept as unary minus
+    (@sum [$($acc:tt)*] [$($term:tt)+] val + $($rest:tt)*) => {
+        $crate::expr!(@sum [$($acc)* ($crate::expr!(@prod [] [] $($term)+)) +] [] op $($rest)*)
+    };
+    (@sum [$($acc:tt)*] [$($term:tt)+] val - $($rest:tt)*) => {
+        $crate::expr!(@sum [$($acc)* ($crate::expr!(@prod [] [] $($term)+)) -] [] op $($rest)*)
+    };
+    (@sum $acc:tt [$($term:tt)*] $state:ident * $($rest:tt)*) => {
+        $crate::expr!(@sum $acc [$($term)* *] op $($rest)*)
+    };
+    (@sum $acc:tt [$($term:tt)*] $state:ident / $($rest:tt)*) => {
+        $crate::expr!(@sum $acc [$($term)* /] op $($rest)*)
+    };
+    (@sum $acc:tt [$($term:tt)*] $state:ident ^ $($rest:tt)*) => {
+        $crate::expr!(@sum $acc [$($term)* ^] op $($rest)*)
+    };
+    (@sum $acc:tt [$($term:tt)*] op - $($rest:tt)*) => {
+        $crate::expr!(@sum $acc [$($term)* -] op $($rest)*)
+    };
+    (@sum $acc:tt [$($term:tt)*] $state:ident $t:tt $($rest:tt)*) => {
+        $crate::expr!(@sum $acc [$($term)* $t] val $($rest)*)
+    };
+    (@sum [$($acc:tt)*] [$($term:tt)+] val) => {
+        $($acc)* ($crate::expr!(@prod [] [] $($term)+))
+    };
+
+    // splits product into factors
+    (@prod [$($acc:tt)*] [$($factor:tt)+] * $($rest:tt)*) => {
+        $crate::expr!(@prod [$($acc)* ($crate::expr!(@pow $($factor)+)) *] [] $($rest)*)
+    };
+    (@prod [$($acc:tt)*] [$($factor:tt)+] / $($rest:tt)*) => {
+        $crate::expr!(@prod [$($acc)* ($crate::expr!(@pow $($factor)+)) /] [] $($rest)*)
+    };
+    (@prod $acc:tt [$($factor:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::expr!(@prod $acc [$($factor)* $t] $($rest)*)
+    };
+    (@prod [$($acc:tt)*] [$($factor:tt)+]) => {
+        $($acc)* ($crate::expr!(@pow $($factor)+))
+    };
+
+    (@pow - $($rest:tt)+) => {
+        -($crate::expr!(@pow $($rest)+))
+    };
+    (@pow $f:ident ($($args:tt)*) ^ $($exp:tt)+) => {
+        $crate::expr!(@atom $f ($($args)*)).pow($crate::float::Float::from_f64(($($exp)+) as f64))
+    };
+    (@pow $x:tt ^ $($exp:tt)+) => {
+        $crate::expr!(@atom $x).pow($crate::float::Float::from_f64(($($exp)+) as f64))
+    };
+    (@pow $($atom:tt)+) => {
+        $crate::expr!(@atom $($atom)+)
+    };
+
+    (@atom ($($inner:tt)+)) => {
+        $crate::expr!(@sum [] [] op $($inner)+)
+    };
+    (@atom $f:ident ($($args:tt)+)) => {
+        $crate::expr!(@call $f [] [] $($args)+)
+    };
+    (@atom $x:ident) => {
+        $crate::expr::Expr::from($x.clone())
+    };
+    (@atom $val:literal) => {
+        $crate::expr::Expr::from_f64($val as f64)
+    };
+
+    // splits call arguments on commas
+    (@call $f:ident [$($args:tt)*] [$($arg:tt)+] , $($rest:tt)*) => {
+        $crate::expr!(@call $f [$($args)* ($crate::expr!(@sum [] [] op $($arg)+).node())] [] $($rest)*)
+    };
+    (@call $f:ident $args:tt [$($arg:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::expr!(@call $f $args [$($arg)* $t] $($rest)*)
+    };
+    (@call $f:ident [$($args:tt)*] [$($arg:tt)+]) => {
+        $crate::expr::Expr::from($crate::node::Node::$f(
+            $($args,)* $crate::expr!(@sum [] [] op $($arg)+).node()
+        ))
+    };
+
+    ($($t:tt)+) => {
+        $crate::expr!(@sum [] [] op $($t)+).node()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    #[test]
+    fn operators() {
+        let x1 = Node::input(4.0f32);
+        let x2 = Node::input(2.0);
+        let (e1, e2) = (Expr::from(x1), Expr::from(x2.clone()));
+        let y = -(e1.clone() / e2.clone()) + 2.0 * e1.pow(0.5) - e2 * 1.5;
+        assert_eq!(y.compute(), -1.0);
+
+        x2.borrow_mut().set(4.0);
+        assert_eq!(y.compute(), -3.0);
+        assert_eq!(y.node().borrow().operation().unwrap().name(), "sub");
+    }
+
+    #[test]
+    fn f64_scalars() {
+        let x = Expr::from(Node::input(1e-12f64));
+        let y = (1.0 + x) - 1.0;
+        assert!((y.compute() - 1e-12).abs() < 1e-15);
+        assert_eq!((1.0 / Expr::from(4.0f64)).sin().compute(), 0.25f64.sin());
+    }
+
+    #[test]
+    fn macro_precedence() {
+        let x = Node::input(3.0f32);
+        let y = Node::input(2.0);
+        let z: Handle = expr!(x - y - 1 + x * y / 2 ^ 2);
+        assert_eq!(z.borrow_mut().compute(), 1.5);
+        let z: Handle = expr!(-x ^ 2 + (x - y) * -max(x, y * 2) + 2 ^ (0.5 * 2.0));
+        assert_eq!(z.borrow_mut().compute(), -11.0);
+
+        // shared operands are captured, not copied
+        let z: Handle = expr!(sqrt(x * x + hypot(x, y)));
+        x.borrow_mut().set(0.0);
+        assert_eq!(z.borrow_mut().compute(), 2f32.sqrt());
+    }
+}
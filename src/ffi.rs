@@ -0,0 +1,302 @@
+//! C interface for embedding graphs in non-Rust hosts. Available with `ffi` feature.
+//!
+//! Graphs are opaque pointers created by `teza_graph_new` and released by `teza_graph_free`.
+//! Nodes are referred to by `u32` handles local to their graph. Functions building nodes
+//! return `TEZA_INVALID` when given unknown handles, and `teza_compute` returns `NaN`.
+//! Panics never cross the interface, functions return the same values instead.
+//! Declarations for C and C++ hosts are in `include/teza.h`. The crate builds as Rust
+//! library only, shared library for C hosts is built on demand with
+//! `cargo rustc --release --lib --features ffi -- --crate-type cdylib`, or static one
+//! with `--crate-type staticlib`, and placed in `target/release/deps`.
+//!
+//! Covers elementwise arithmetic and math functions over `f64` inputs and constants.
+//! Aggregations, rules, labels and serialization are available from Rust only.
+
+use std::{
+    cell::RefCell,
+    panic::{catch_unwind, AssertUnwindSafe},
+    rc::Rc,
+};
+
+use crate::{
+    input::{Input, InputNode},
+    node::{Handle, Node},
+};
+
+/// Handle returned in place of a node when arguments are invalid.
+pub const TEZA_INVALID: u32 = u32::MAX;
+
+/// Graph owning nodes referred to by handles.
+#[derive(Default)]
+pub struct TezaGraph {
+    nodes: Vec<Handle<f64>>,
+    /// Input nodes by handle, `None` for operation nodes.
+    inputs: Vec<Option<Rc<RefCell<InputNode<f64>>>>>,
+}
+
+impl TezaGraph {
+    /// Stores node, returning its handle, or `TEZA_INVALID` once handles run out.
+    fn push(&mut self, node: Handle<f64>, input: Option<Rc<RefCell<InputNode<f64>>>>) -> u32 {
+        if self.nodes.len() >= TEZA_INVALID as usize {
+            return TEZA_INVALID;
+        }
+        self.nodes.push(node);
+        self.inputs.push(input);
+        (self.nodes.len() - 1) as u32
+    }
+
+    /// Stores node built from existing one, or returns `TEZA_INVALID`.
+    fn unary(&mut self, x: u32, build: impl FnOnce(Handle<f64>) -> Handle<f64>) -> u32 {
+        match self.nodes.get(x as usize) {
+            Some(x) => {
+                let node = build(x.clone());
+                self.push(node, None)
+            }
+            None => TEZA_INVALID,
+        }
+    }
+
+    /// Stores node built from two existing ones, or returns `TEZA_INVALID`.
+    fn binary(
+        &mut self,
+        x: u32,
+        y: u32,
+        build: fn(Handle<f64>, Handle<f64>) -> Handle<f64>,
+    ) -> u32 {
+        match (self.nodes.get(x as usize), self.nodes.get(y as usize)) {
+            (Some(x), Some(y)) => {
+                let node = build(x.clone(), y.clone());
+                self.push(node, None)
+            }
+            _ => TEZA_INVALID,
+        }
+    }
+}
+
+/// Runs `f` over graph, returning `fallback` if graph is null or `f` panics.
+///
+/// # Safety
+///
+/// `graph` must be null or a live graph returned by `teza_graph_new`.
+unsafe fn with_graph<R>(
+    graph: *mut TezaGraph,
+    fallback: R,
+    f: impl FnOnce(&mut TezaGraph) -> R,
+) -> R {
+    match graph.as_mut() {
+        Some(graph) => catch_unwind(AssertUnwindSafe(|| f(graph))).unwrap_or(fallback),
+        None => fallback,
+    }
+}
+
+/// Creates empty graph, to be released with `teza_graph_free`.
+#[no_mangle]
+pub extern "C" fn teza_graph_new() -> *mut TezaGraph {
+    Box::into_raw(Box::new(TezaGraph::default()))
+}
+
+/// Releases graph along with all its nodes.
+///
+/// # Safety
+///
+/// `graph` must be null or returned by `teza_graph_new` and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn teza_graph_free(graph: *mut TezaGraph) {
+    if !graph.is_null() {
+        let graph = Box::from_raw(graph);
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(graph)));
+    }
+}
+
+/// Adds input node with given value, returning its handle.
+///
+/// # Safety
+///
+/// `graph` must be null or a live graph returned by `teza_graph_new`.
+#[no_mangle]
+pub unsafe extern "C" fn teza_input(graph: *mut TezaGraph, val: f64) -> u32 {
+    with_graph(graph, TEZA_INVALID, |graph| {
+        let input = InputNode::from_val(val);
+        graph.push(input.clone(), Some(input))
+    })
+}
+
+/// Adds node holding constant value, returning its handle.
+///
+/// # Safety
+///
+/// `graph` must be null or a live graph returned by `teza_graph_new`.
+#[no_mangle]
+pub unsafe extern "C" fn teza_constant(graph: *mut TezaGraph, val: f64) -> u32 {
+    with_graph(graph, TEZA_INVALID, |graph| {
+        graph.push(Node::constant(val), None)
+    })
+}
+
+/// Defines functions adding node for operation over one node.
+macro_rules! unary_fns {
+    ($($(#[$doc:meta])* $name:ident => $build:path;)*) => {$(
+        $(#[$doc])*
+        ///
+        /// # Safety
+        ///
+        /// `graph` must be null or a live graph returned by `teza_graph_new`.
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(graph: *mut TezaGraph, x: u32) -> u32 {
+            with_graph(graph, TEZA_INVALID, |graph| graph.unary(x, |x| $build(x)))
+        }
+    )*};
+}
+
+/// Defines functions adding node for operation over two nodes.
+macro_rules! binary_fns {
+    ($($(#[$doc:meta])* $name:ident => $build:path;)*) => {$(
+        $(#[$doc])*
+        ///
+        /// # Safety
+        ///
+        /// `graph` must be null or a live graph returned by `teza_graph_new`.
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(graph: *mut TezaGraph, x: u32, y: u32) -> u32 {
+            with_graph(graph, TEZA_INVALID, |graph| graph.binary(x, y, |x, y| $build(x, y)))
+        }
+    )*};
+}
+
+binary_fns! {
+    /// Adds node for sum of two nodes.
+    teza_add => Node::add;
+    /// Adds node for subtraction of two nodes.
+    teza_sub => Node::sub;
+    /// Adds node for multiplication of two nodes.
+    teza_mul => Node::mul;
+    /// Adds node for division of two nodes.
+    teza_div => Node::div;
+    /// Adds node for minimum of two nodes.
+    teza_min => Node::min;
+    /// Adds node for maximum of two nodes.
+    teza_max => Node::max;
+    /// Adds node for `sqrt(x² + y²)`.
+    teza_hypot => Node::hypot;
+    /// Adds node for four-quadrant arctangent, `atan2(x, y)` as in C.
+    teza_atan2 => Node::atan2;
+    /// Adds node for remainder of division truncated toward zero.
+    teza_rem => Node::rem;
+}
+
+unary_fns! {
+    /// Adds node for negation of given node.
+    teza_neg => Node::neg;
+    /// Adds node for absolute value of given node.
+    teza_abs => Node::abs;
+    /// Adds node for square root of given node.
+    teza_sqrt => Node::sqrt;
+    /// Adds node for exponent of given node.
+    teza_exp => Node::exp;
+    /// Adds node for natural logarithm of given node.
+    teza_ln => Node::ln;
+    /// Adds node for sine of given node.
+    teza_sin => Node::sin;
+    /// Adds node for cosine of given node.
+    teza_cos => Node::cos;
+    /// Adds node for tangent of given node.
+    teza_tan => Node::tan;
+    /// Adds node for arcsine of given node.
+    teza_asin => Node::asin;
+    /// Adds node for arccosine of given node.
+    teza_acos => Node::acos;
+    /// Adds node for arctangent of given node.
+    teza_atan => Node::atan;
+    /// Adds node for hyperbolic tangent of given node.
+    teza_tanh => Node::tanh;
+    /// Adds node for logistic function of given node.
+    teza_sigmoid => Node::sigmoid;
+}
+
+/// Adds node for given node raised to constant power.
+///
+/// # Safety
+///
+/// `graph` must be null or a live graph returned by `teza_graph_new`.
+#[no_mangle]
+pub unsafe extern "C" fn teza_pow(graph: *mut TezaGraph, x: u32, pow: f64) -> u32 {
+    with_graph(graph, TEZA_INVALID, |graph| {
+        graph.unary(x, |x| Node::pow(x, pow))
+    })
+}
+
+/// Sets new value of input node, resetting caches of its dependents.
+/// Returns `false` if handle doesn't refer to an input.
+///
+/// # Safety
+///
+/// `graph` must be null or a live graph returned by `teza_graph_new`.
+#[no_mangle]
+pub unsafe extern "C" fn teza_set(graph: *mut TezaGraph, input: u32, val: f64) -> bool {
+    with_graph(graph, false, |graph| {
+        match graph.inputs.get(input as usize) {
+            Some(Some(input)) => {
+                input.borrow_mut().set(val);
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Computes node, recomputing only what changed since last time.
+/// Returns `NaN` for unknown handles.
+///
+/// # Safety
+///
+/// `graph` must be null or a live graph returned by `teza_graph_new`.
+#[no_mangle]
+pub unsafe extern "C" fn teza_compute(graph: *mut TezaGraph, node: u32) -> f64 {
+    with_graph(graph, f64::NAN, |graph| {
+        match graph.nodes.get(node as usize) {
+            Some(node) => node.borrow_mut().compute(),
+            None => f64::NAN,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn c_interface() {
+        unsafe {
+            let g = teza_graph_new();
+            let x = teza_input(g, 2.0);
+            let y = teza_input(g, 3.0);
+            let half = teza_constant(g, 0.5);
+            let prod = teza_mul(g, x, y);
+            let root = teza_add(g, prod, half);
+            assert_eq!(teza_compute(g, root), 6.5);
+
+            assert!(teza_set(g, y, 4.0));
+            assert_eq!(teza_compute(g, root), 8.5);
+            assert!(!teza_set(g, root, 1.0));
+            assert_eq!(teza_div(g, root, 42), TEZA_INVALID);
+            assert!(teza_compute(g, TEZA_INVALID).is_nan());
+
+            let angle = teza_atan2(g, x, teza_neg(g, x));
+            assert!((teza_compute(g, angle) - 0.75 * std::f64::consts::PI).abs() < 1e-12);
+            let square = teza_pow(g, teza_sqrt(g, y), 2.0);
+            assert!((teza_compute(g, square) - 4.0).abs() < 1e-12);
+            assert_eq!(teza_sin(g, TEZA_INVALID), TEZA_INVALID);
+
+            // panics are caught at the boundary
+            let held = (*g).nodes[x as usize].clone();
+            let borrow = held.borrow_mut();
+            assert!(teza_compute(g, x).is_nan());
+            drop(borrow);
+            assert_eq!(teza_compute(g, x), 2.0);
+
+            assert_eq!(teza_input(std::ptr::null_mut(), 1.0), TEZA_INVALID);
+            teza_graph_free(g);
+            teza_graph_free(std::ptr::null_mut());
+        }
+    }
+}
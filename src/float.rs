@@ -0,0 +1,182 @@
+//! Scalar types graph values can be computed in.
+//!
+//! Graph operations do all their math through `Float`, so graphs can be computed over
+//! a scalar supplying its own math, e.g. `f32` wrapper implementing the functions with
+//! `libm`. The crate itself still requires `std`, it doesn't build under `no_std`.
+
+use std::{
+    fmt::{Debug, Display},
+    iter::Sum,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+/// Floating point scalar used for graph values.
+/// Implemented for `f32`, `f64` and fixed-point `Decimal`, other numeric types can be used
+/// by implementing it.
+pub trait Float:
+    Copy
+    + PartialOrd
+    + Debug
+    + Display
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + Sum
+    + Send
+    + Sync
+    + 'static
+{
+    /// Zero value.
+    const ZERO: Self;
+    /// One value.
+    const ONE: Self;
+    /// Smallest finite value.
+    const MIN: Self;
+    /// Largest finite value.
+    const MAX: Self;
+    /// Negative infinity.
+    const NEG_INFINITY: Self;
+
+    /// Converts from `f64`, rounding if needed.
+    fn from_f64(val: f64) -> Self;
+    /// Converts to `f64`, rounding if needed.
+    fn to_f64(self) -> f64;
+
+    // mirrors of inherent methods of primitive floats
+    fn abs(self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn sqrt(self) -> Self;
+    fn powf(self, pow: Self) -> Self;
+    fn fract(self) -> Self;
+    fn exp(self) -> Self;
+    fn exp_m1(self) -> Self;
+    fn ln(self) -> Self;
+    fn ln_1p(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn asin(self) -> Self;
+    fn acos(self) -> Self;
+    fn atan(self) -> Self;
+    fn tanh(self) -> Self;
+    fn hypot(self, other: Self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+    /// Remainder of division truncated toward zero, like `%` of primitive floats.
+    fn rem(self, other: Self) -> Self;
+    fn is_finite(self) -> bool;
+    fn is_infinite(self) -> bool;
+}
+
+macro_rules! impl_float {
+    ($t:ident) => {
+        impl Float for $t {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+            const MIN: Self = $t::MIN;
+            const MAX: Self = $t::MAX;
+            const NEG_INFINITY: Self = $t::NEG_INFINITY;
+
+            fn from_f64(val: f64) -> Self {
+                val as $t
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn abs(self) -> Self {
+                $t::abs(self)
+            }
+
+            fn min(self, other: Self) -> Self {
+                $t::min(self, other)
+            }
+
+            fn max(self, other: Self) -> Self {
+                $t::max(self, other)
+            }
+
+            fn sqrt(self) -> Self {
+                $t::sqrt(self)
+            }
+
+            fn powf(self, pow: Self) -> Self {
+                $t::powf(self, pow)
+            }
+
+            fn fract(self) -> Self {
+                $t::fract(self)
+            }
+
+            fn exp(self) -> Self {
+                $t::exp(self)
+            }
+
+            fn exp_m1(self) -> Self {
+                $t::exp_m1(self)
+            }
+
+            fn ln(self) -> Self {
+                $t::ln(self)
+            }
+
+            fn ln_1p(self) -> Self {
+                $t::ln_1p(self)
+            }
+
+            fn sin(self) -> Self {
+                $t::sin(self)
+            }
+
+            fn cos(self) -> Self {
+                $t::cos(self)
+            }
+
+            fn tan(self) -> Self {
+                $t::tan(self)
+            }
+
+            fn asin(self) -> Self {
+                $t::asin(self)
+            }
+
+            fn acos(self) -> Self {
+                $t::acos(self)
+            }
+
+            fn atan(self) -> Self {
+                $t::atan(self)
+            }
+
+            fn tanh(self) -> Self {
+                $t::tanh(self)
+            }
+
+            fn hypot(self, other: Self) -> Self {
+                $t::hypot(self, other)
+            }
+
+            fn atan2(self, other: Self) -> Self {
+                $t::atan2(self, other)
+            }
+
+            fn rem(self, other: Self) -> Self {
+                self % other
+            }
+
+            fn is_finite(self) -> bool {
+                $t::is_finite(self)
+            }
+
+            fn is_infinite(self) -> bool {
+                $t::is_infinite(self)
+            }
+        }
+    };
+}
+
+impl_float!(f32);
+impl_float!(f64);
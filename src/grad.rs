@@ -0,0 +1,691 @@
+//! Reverse-mode automatic differentiation of graph outputs.
+//!
+//! Node values are taken with `Computable::compute`, so cached results are reused
+//! and only derivative propagation is performed on top of them.
+//!
+//! Training loops use `Gradient::backward`, adding derivatives to gradients accumulated
+//! on input nodes, which sum up over backward passes until cleared with `zero_grad`.
+//! Jacobians of many outputs are stored sparsely, skipping inputs outputs don't depend on.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::{
+    input::InputNode,
+    node::{node_addr, post_order, Computable},
+    ops::{self, derivative_coeffs, horner, piece, Operation, Reduction},
+};
+
+/// Returns operands of operation together with partial derivatives of operation result
+/// with respect to them, obtaining operand values with `f`.
+/// Operands not affecting result, like untaken selection branch, are omitted.
+pub(crate) fn partials<H>(opp: &Operation<H>, mut f: impl FnMut(&H) -> f32) -> Vec<(&H, f32)> {
+    use Operation::*;
+
+    let frac_2_sqrt_pi = std::f32::consts::FRAC_2_SQRT_PI;
+    match opp {
+        Const(_) | Detach(_) => vec![],
+        Add(x, y) => vec![(x, 1.0), (y, 1.0)],
+        AddVar(args) => args.iter().map(|x| (x, 1.0)).collect(),
+        Sub(x, y) => vec![(x, 1.0), (y, -1.0)],
+        Mul(x, y) => {
+            let x_res = f(x);
+            let y_res = f(y);
+            vec![(x, y_res), (y, x_res)]
+        }
+        MulVar(args) | Reduce(args, Reduction::Prod) => {
+            // products of values before and after each operand, so zeros need no division
+            let vals: Vec<f32> = args.iter().map(f).collect();
+            let mut after = vec![1.0; vals.len() + 1];
+            for i in (0..vals.len()).rev() {
+                after[i] = after[i + 1] * vals[i];
+            }
+            let mut before = 1.0;
+            args.iter()
+                .zip(vals)
+                .zip(&after[1..])
+                .map(|((x, val), after)| {
+                    let d = before * after;
+                    before *= val;
+                    (x, d)
+                })
+                .collect()
+        }
+        Reduce(args, Reduction::Sum) => args.iter().map(|x| (x, 1.0)).collect(),
+        Reduce(args, Reduction::Mean) => {
+            let d = 1.0 / args.len() as f32;
+            args.iter().map(|x| (x, d)).collect()
+        }
+        // whole derivative goes to the first operand passed through
+        Reduce(args, Reduction::Min | Reduction::Max) => {
+            let res = opp.eval(&mut f);
+            args.iter()
+                .find(|x| f(x) == res)
+                .map(|x| vec![(x, 1.0)])
+                .unwrap_or_default()
+        }
+        Div(x, y, _) => {
+            let x_res = f(x);
+            let y_res = f(y);
+            vec![(x, 1.0 / y_res), (y, -x_res / (y_res * y_res))]
+        }
+        Neg(x) => vec![(x, -1.0)],
+        Recip(x) => vec![(x, -1.0 / f(x).powi(2))],
+        Pow(x, pow) => {
+            let d = if *pow == 0.0 {
+                0.0
+            } else {
+                pow * f(x).powf(pow - 1.0)
+            };
+            vec![(x, d)]
+        }
+        Sin(x) => vec![(x, f(x).cos())],
+        Asin(x) => vec![(x, 1.0 / (1.0 - f(x).powi(2)).sqrt())],
+        Acos(x) => vec![(x, -1.0 / (1.0 - f(x).powi(2)).sqrt())],
+        Atan(x) => vec![(x, 1.0 / (1.0 + f(x).powi(2)))],
+        Cos(x) => vec![(x, -f(x).sin())],
+        Tan(x) => vec![(x, 1.0 / f(x).cos().powi(2))],
+        Exp(x) => vec![(x, f(x).exp())],
+        Ln(x) => vec![(x, 1.0 / f(x))],
+        Log(x, base) => vec![(x, 1.0 / (f(x) * base.ln()))],
+        Sqrt(x) => vec![(x, 0.5 / f(x).sqrt())],
+        // piecewise constant
+        Sign(x) => vec![(x, 0.0)],
+        // jumps at integers
+        Fract(x) => vec![(x, 1.0)],
+        Abs(x) => {
+            let x_res = f(x);
+            vec![(x, if x_res == 0.0 { 0.0 } else { x_res.signum() })]
+        }
+        Tanh(x) => vec![(x, 1.0 - f(x).tanh().powi(2))],
+        Sigmoid(x) => {
+            let s = ops::sigmoid(f(x));
+            vec![(x, s * (1.0 - s))]
+        }
+        Relu(x) => vec![(x, if f(x) > 0.0 { 1.0 } else { 0.0 })],
+        Softplus(x) => vec![(x, ops::sigmoid(f(x)))],
+        ExpM1(x) => vec![(x, f(x).exp())],
+        Ln1p(x) => vec![(x, 1.0 / (1.0 + f(x)))],
+        Hypot(..) | Norm(_) => {
+            let vals: Vec<f32> = opp.operands().into_iter().map(&mut f).collect();
+            let norm = opp.eval(&mut f);
+            opp.operands()
+                .into_iter()
+                .zip(vals)
+                .map(|(x, val)| (x, if norm == 0.0 { 0.0 } else { val / norm }))
+                .collect()
+        }
+        Atan2(y, x) => {
+            let (y_res, x_res) = (f(y), f(x));
+            let r2 = x_res * x_res + y_res * y_res;
+            // zero at the origin, like `Hypot`
+            if r2 == 0.0 {
+                vec![(y, 0.0), (x, 0.0)]
+            } else {
+                vec![(y, x_res / r2), (x, -y_res / r2)]
+            }
+        }
+        Rem(x, y) => {
+            let (x_res, y_res) = (f(x), f(y));
+            vec![(x, 1.0), (y, -(x_res / y_res).trunc())]
+        }
+        Erf(x) => vec![(x, frac_2_sqrt_pi * (-f(x).powi(2)).exp())],
+        Erfc(x) => vec![(x, -frac_2_sqrt_pi * (-f(x).powi(2)).exp())],
+        NormalCdf(x) => vec![(x, normal_pdf(f(x)))],
+        NormalPdf(x) => {
+            let x_res = f(x);
+            vec![(x, -x_res * normal_pdf(x_res))]
+        }
+        LogSumExp(args) | Softmax(args, _) => {
+            let vals: Vec<f32> = args.iter().map(f).collect();
+            let max = vals.iter().fold(f32::NEG_INFINITY, |acc, x| acc.max(*x));
+            let exps: Vec<f32> = vals.iter().map(|x| (x - max).exp()).collect();
+            let sum: f32 = exps.iter().sum();
+            let softmax = exps.iter().map(|e| e / sum);
+            match opp {
+                Softmax(_, i) => {
+                    let s_i = exps[*i] / sum;
+                    args.iter()
+                        .zip(softmax)
+                        .enumerate()
+                        .map(|(j, (x, s_j))| {
+                            (
+                                x,
+                                if j == *i {
+                                    s_i * (1.0 - s_i)
+                                } else {
+                                    -s_i * s_j
+                                },
+                            )
+                        })
+                        .collect()
+                }
+                _ => args.iter().zip(softmax).collect(),
+            }
+        }
+        Select(cond, x, y) => vec![(if f(cond) > 0.0 { x } else { y }, 1.0)],
+        Piecewise(x, pieces, default) => vec![(piece(f(x), pieces, default), 1.0)],
+        // piecewise constant
+        Lt(x, y) | Le(x, y) | Gt(x, y) | Ge(x, y) | Eq(x, y, _) => vec![(x, 0.0), (y, 0.0)],
+        Quantize(x, ..) => vec![(x, 0.0)],
+        Polynomial(x, coeffs) => vec![(x, horner(f(x), &derivative_coeffs(coeffs)))],
+        // whole derivative goes to the operand passed through
+        Min(x, y) => vec![(if f(y) < f(x) { y } else { x }, 1.0)],
+        Max(x, y) => vec![(if f(y) > f(x) { y } else { x }, 1.0)],
+        Clamp(x, lo, hi) => {
+            let (x_res, lo_res) = (f(x), f(lo));
+            let (passed, val) = if x_res < lo_res {
+                (lo, lo_res)
+            } else {
+                (x, x_res)
+            };
+            vec![(if val > f(hi) { hi } else { passed }, 1.0)]
+        }
+        Custom(op, args) => {
+            let vals: Vec<f32> = args.iter().map(f).collect();
+            args.iter().zip(op.0.partials(&vals)).collect()
+        }
+    }
+}
+
+/// Clears gradients accumulated on given inputs.
+pub fn zero_grad(inputs: &[Rc<RefCell<InputNode>>]) {
+    inputs
+        .iter()
+        .for_each(|input| input.borrow_mut().zero_grad());
+}
+
+/// Shared handle of graph node.
+type Handle = Rc<RefCell<dyn Computable>>;
+
+/// Operands of node with partial derivatives with respect to them.
+type Partials = Vec<(Handle, f32)>;
+
+/// Computes standard normal probability density.
+fn normal_pdf(x: f32) -> f32 {
+    (-0.5 * x * x).exp() / (2.0 * std::f32::consts::PI).sqrt()
+}
+
+/// Partial derivatives of graph output with respect to its leaf nodes.
+#[derive(Debug, Clone, Default)]
+pub struct Gradient {
+    /// Derivatives keyed by leaf node address.
+    grads: HashMap<usize, f32>,
+}
+
+impl Gradient {
+    /// Computes derivatives of `root` output with respect to every input it depends on.
+    pub fn of<C: Computable + ?Sized>(root: &Rc<RefCell<C>>) -> Self {
+        let mut grad = Self::default();
+        let opp = match root.borrow().operation() {
+            Some(opp) => opp.clone(),
+            None => {
+                grad.grads.insert(node_addr(root), 1.0);
+                return grad;
+            }
+        };
+
+        // nodes in topological order with local derivatives of their operands
+        let mut order: Vec<(Handle, Partials)> = Vec::new();
+        let mut visited = HashSet::new();
+        let local = |opp: &Operation| -> Partials {
+            partials(opp, |x| x.borrow_mut().compute())
+                .into_iter()
+                .map(|(x, d)| (x.clone(), d))
+                .collect()
+        };
+
+        let root_partials = local(&opp);
+        let mut stack: Vec<(Handle, Option<Partials>)> = root_partials
+            .iter()
+            .rev()
+            .map(|(x, _)| (x.clone(), None))
+            .collect();
+        while let Some((node, children)) = stack.pop() {
+            if let Some(children) = children {
+                order.push((node, children));
+                continue;
+            }
+            if !visited.insert(node_addr(&node)) {
+                continue;
+            }
+            let opp = node.borrow().operation().cloned();
+            let children = opp.as_ref().map(local).unwrap_or_default();
+            let pending: Vec<_> = children
+                .iter()
+                .filter(|(x, _)| !visited.contains(&node_addr(x)))
+                .map(|(x, _)| (x.clone(), None))
+                .collect();
+            stack.push((node, Some(children)));
+            stack.extend(pending);
+        }
+
+        let mut adjoints: HashMap<usize, f32> = HashMap::new();
+        for (x, d) in &root_partials {
+            *adjoints.entry(node_addr(x)).or_default() += d;
+        }
+        for (node, children) in order.iter().rev() {
+            let addr = node_addr(node);
+            let adjoint = adjoints.get(&addr).copied().unwrap_or_default();
+            if node.borrow().operation().is_none() {
+                grad.grads.insert(addr, adjoint);
+                continue;
+            }
+            for (x, d) in children {
+                *adjoints.entry(node_addr(x)).or_default() += adjoint * d;
+            }
+        }
+        grad
+    }
+
+    /// Computes derivatives of `root` and adds them to gradients accumulated on given inputs,
+    /// so gradients of several outputs or batches sum up until `zero_grad`.
+    pub fn backward<C: Computable + ?Sized>(
+        root: &Rc<RefCell<C>>,
+        inputs: &[Rc<RefCell<InputNode>>],
+    ) -> Self {
+        let grad = Self::of(root);
+        grad.accumulate(inputs);
+        grad
+    }
+
+    /// Adds derivatives with respect to given inputs to gradients accumulated on them.
+    pub fn accumulate(&self, inputs: &[Rc<RefCell<InputNode>>]) {
+        for input in inputs {
+            let d = self.wrt(input);
+            input.borrow_mut().accumulate_grad(d);
+        }
+    }
+
+    /// Returns derivative with respect to given input, zero if output doesn't depend on it.
+    pub fn wrt<C: ?Sized>(&self, input: &Rc<RefCell<C>>) -> f32 {
+        self.grads
+            .get(&node_addr(input))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns amount of inputs output depends on.
+    pub fn len(&self) -> usize {
+        self.grads.len()
+    }
+
+    /// Returns true if output doesn't depend on any input.
+    pub fn is_empty(&self) -> bool {
+        self.grads.is_empty()
+    }
+}
+
+/// Structurally nonzero entries of Jacobian, i.e. inputs each output depends on,
+/// derived from graph structure without computing anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sparsity {
+    /// Sorted indices of inputs, per output.
+    rows: Vec<Vec<usize>>,
+    /// Amount of inputs.
+    cols: usize,
+}
+
+impl Sparsity {
+    /// Finds inputs, given by their indices, each output depends on.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn of<C: ?Sized>(outputs: &[Handle], inputs: &[Rc<RefCell<C>>]) -> Self {
+        let cols: HashMap<usize, usize> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| (node_addr(input), i))
+            .collect();
+        // inputs reachable from each visited node, shared subgraphs are visited once
+        let mut reach: HashMap<usize, Rc<Vec<usize>>> = HashMap::new();
+        for output in outputs {
+            for node in post_order(output) {
+                let addr = node_addr(&node);
+                if reach.contains_key(&addr) {
+                    continue;
+                }
+                let mut deps: Vec<usize> = cols.get(&addr).copied().into_iter().collect();
+                for x in node.borrow().operands() {
+                    deps.extend(reach[&node_addr(&x)].iter().copied());
+                }
+                deps.sort_unstable();
+                deps.dedup();
+                reach.insert(addr, Rc::new(deps));
+            }
+        }
+        let rows = outputs
+            .iter()
+            .map(|output| reach[&node_addr(output)].to_vec())
+            .collect();
+        Self {
+            rows,
+            cols: inputs.len(),
+        }
+    }
+
+    /// Returns sorted indices of inputs output with given index depends on.
+    pub fn row(&self, output: usize) -> &[usize] {
+        &self.rows[output]
+    }
+
+    /// Returns amount of structurally nonzero entries.
+    pub fn nnz(&self) -> usize {
+        self.rows.iter().map(Vec::len).sum()
+    }
+
+    /// Returns fraction of structurally nonzero entries.
+    pub fn density(&self) -> f64 {
+        let size = self.rows.len() * self.cols;
+        if size == 0 {
+            return 0.0;
+        }
+        self.nnz() as f64 / size as f64
+    }
+}
+
+/// Derivatives of several outputs with respect to several inputs,
+/// storing only structurally nonzero entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Jacobian {
+    /// Pairs of input index and derivative, sorted by input index, per output.
+    rows: Vec<Vec<(usize, f32)>>,
+    /// Amount of inputs.
+    cols: usize,
+}
+
+impl Jacobian {
+    /// Computes Jacobian of outputs with respect to inputs, skipping outputs
+    /// which don't depend on any of them.
+    pub fn of<C: ?Sized>(outputs: &[Handle], inputs: &[Rc<RefCell<C>>]) -> Self {
+        Self::with_sparsity(outputs, inputs, &Sparsity::of(outputs, inputs))
+    }
+
+    /// Computes Jacobian with sparsity found before, which stays valid until graph
+    /// structure changes, so it can be reused across input values.
+    pub fn with_sparsity<C: ?Sized>(
+        outputs: &[Handle],
+        inputs: &[Rc<RefCell<C>>],
+        sparsity: &Sparsity,
+    ) -> Self {
+        let rows = outputs
+            .iter()
+            .zip(&sparsity.rows)
+            .map(|(output, cols)| {
+                if cols.is_empty() {
+                    return Vec::new();
+                }
+                let grad = Gradient::of(output);
+                cols.iter().map(|&j| (j, grad.wrt(&inputs[j]))).collect()
+            })
+            .collect();
+        Self {
+            rows,
+            cols: inputs.len(),
+        }
+    }
+
+    /// Returns amount of outputs and inputs.
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows.len(), self.cols)
+    }
+
+    /// Returns derivative of output with respect to input, zero outside of sparsity.
+    pub fn get(&self, output: usize, input: usize) -> f32 {
+        let row = &self.rows[output];
+        row.binary_search_by_key(&input, |(j, _)| *j)
+            .map_or(0.0, |k| row[k].1)
+    }
+
+    /// Returns pairs of input index and derivative of output with given index.
+    pub fn row(&self, output: usize) -> &[(usize, f32)] {
+        &self.rows[output]
+    }
+
+    /// Returns amount of stored entries.
+    pub fn nnz(&self) -> usize {
+        self.rows.iter().map(Vec::len).sum()
+    }
+
+    /// Returns all entries as dense matrix, by output.
+    pub fn to_dense(&self) -> Vec<Vec<f32>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let mut dense = vec![0.0; self.cols];
+                row.iter().for_each(|(j, d)| dense[*j] = *d);
+                dense
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::Input, node::Node};
+
+    /// Checks gradient against central finite difference.
+    fn check_numeric(root: &Rc<RefCell<Node>>, inputs: &[Rc<RefCell<InputNode>>], point: &[f32]) {
+        for (input, val) in inputs.iter().zip(point) {
+            input.borrow_mut().set(*val);
+        }
+        let grad = Gradient::of(root);
+        for (i, input) in inputs.iter().enumerate() {
+            let h = 1e-2;
+            input.borrow_mut().set(point[i] + h);
+            let hi = root.borrow_mut().compute();
+            input.borrow_mut().set(point[i] - h);
+            let lo = root.borrow_mut().compute();
+            input.borrow_mut().set(point[i]);
+            let numeric = (hi - lo) / (2.0 * h);
+            let analytic = grad.wrt(input);
+            assert!(
+                (numeric - analytic).abs() < 1e-2 * (1.0 + numeric.abs()),
+                "input {}: {} != {}",
+                i,
+                analytic,
+                numeric
+            );
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let x = InputNode::from_val(3.0);
+        let y = InputNode::from_val(2.0);
+        // x * y + x^2 - y
+        let root = Node::sub(
+            Node::add(Node::mul(x.clone(), y.clone()), Node::pow(x.clone(), 2.0)),
+            y.clone(),
+        );
+        let grad = Gradient::of(&root);
+        assert_eq!(grad.len(), 2);
+        assert_eq!(grad.wrt(&x), 8.0);
+        assert_eq!(grad.wrt(&y), 2.0);
+
+        // derivatives of product stay defined for zero operands
+        let z = InputNode::from_val(0.0);
+        let grad = Gradient::of(&Node::mul_var(vec![x.clone(), y.clone(), z.clone()]));
+        assert_eq!((grad.wrt(&x), grad.wrt(&y), grad.wrt(&z)), (0.0, 0.0, 6.0));
+    }
+
+    #[test]
+    fn all_operations() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let z = InputNode::from_val(0.0);
+        let args = || -> Vec<Rc<RefCell<dyn Computable>>> { vec![x.clone(), y.clone(), z.clone()] };
+        let roots = [
+            Node::add_var(args()),
+            Node::div(Node::sub(x.clone(), z.clone()), y.clone()),
+            Node::mul(Node::sin(x.clone()), Node::pow(y.clone(), 3.0)),
+            Node::add(
+                Node::asin(x.clone()),
+                Node::acos(Node::mul(x.clone(), y.clone())),
+            ),
+            Node::atan(Node::sub(z.clone(), y.clone())),
+            Node::mul(Node::expm1(x.clone()), Node::ln_1p(z.clone())),
+            Node::add(Node::hypot(x.clone(), y.clone()), Node::norm(args())),
+            Node::add(
+                Node::atan2(y.clone(), x.clone()),
+                Node::rem(x.clone(), z.clone()),
+            ),
+            Node::add(Node::erf(x.clone()), Node::erfc(y.clone())),
+            Node::mul(Node::normal_cdf(x.clone()), Node::normal_pdf(z.clone())),
+            Node::add(Node::logsumexp(args()), Node::softmax_component(args(), 1)),
+            Node::mul(Node::cos(x.clone()), Node::tan(y.clone())),
+            Node::add(Node::exp(x.clone()), Node::ln(z.clone())),
+            Node::mul(Node::log(z.clone(), 2.0), Node::sqrt(z.clone())),
+            Node::abs(Node::sub(x.clone(), y.clone())),
+            Node::add(Node::fract(x.clone()), Node::sign(y.clone())),
+            Node::mul(Node::neg(x.clone()), Node::recip(z.clone())),
+            Node::mul(Node::tanh(x.clone()), Node::sigmoid(y.clone())),
+            Node::add(Node::relu(z.clone()), Node::softplus(x.clone())),
+            Node::mul(
+                Node::min(x.clone(), y.clone()),
+                Node::max(y.clone(), z.clone()),
+            ),
+            Node::clamp(Node::mul(x.clone(), z.clone()), y.clone(), z.clone()),
+            Node::add(Node::mul_var(args()), Node::reduce(args(), Reduction::Mean)),
+            Node::mul(
+                Node::reduce(args(), Reduction::Min),
+                Node::reduce(args(), Reduction::Max),
+            ),
+        ];
+        for root in &roots {
+            check_numeric(root, &[x.clone(), y.clone(), z.clone()], &[0.3, 0.7, 1.2]);
+            check_numeric(root, &[x.clone(), y.clone(), z.clone()], &[-0.4, 0.2, 0.5]);
+        }
+    }
+
+    #[test]
+    fn shared_subgraph() {
+        let x = InputNode::from_val(2.0);
+        let s = Node::pow(x.clone(), 2.0);
+        let root = Node::mul(s.clone(), Node::add(s, x.clone()));
+        // (x^2)^2 + x^3
+        assert_eq!(Gradient::of(&root).wrt(&x), 4.0 * 8.0 + 3.0 * 4.0);
+    }
+
+    #[test]
+    fn select_taken_branch() {
+        let cond = InputNode::from_val(1.0);
+        let x = InputNode::from_val(3.0);
+        let y = InputNode::from_val(4.0);
+        let root = Node::lazy_select(
+            cond.clone(),
+            Node::pow(x.clone(), 2.0),
+            Node::mul(y.clone(), x.clone()),
+        );
+        let grad = Gradient::of(&root);
+        assert_eq!(grad.wrt(&x), 6.0);
+        assert_eq!(grad.wrt(&y), 0.0);
+        assert_eq!(grad.wrt(&cond), 0.0);
+
+        cond.borrow_mut().set(0.0);
+        let grad = Gradient::of(&root);
+        assert_eq!(grad.wrt(&x), 4.0);
+        assert_eq!(grad.wrt(&y), 3.0);
+    }
+
+    #[test]
+    fn input_root() {
+        let x = InputNode::from_val(2.0);
+        let y = InputNode::from_val(2.0);
+        let grad = Gradient::of(&x);
+        assert_eq!(grad.wrt(&x), 1.0);
+        assert_eq!(grad.wrt(&y), 0.0);
+        assert!(Gradient::of(&Node::constant(1.0)).is_empty());
+    }
+
+    #[test]
+    fn closure_derivatives() {
+        let x = InputNode::from_val(1.25);
+        let y = InputNode::from_val(2.0);
+        // rounding has zero derivative almost everywhere, straight-through estimate passes it
+        let rounded = Node::unary_fn_with_derivative(x.clone(), f32::round, |_| 1.0);
+        let root = Node::add(
+            Node::mul(rounded, Node::unary_fn(y.clone(), f32::round)),
+            Node::binary_fn_with_partials(x.clone(), y.clone(), |a, b| a * b, |a, b| (b, a)),
+        );
+        assert_eq!(root.borrow_mut().compute(), 1.0 * 2.0 + 2.5);
+        let grad = Gradient::of(&root);
+        assert_eq!(grad.wrt(&x), 2.0 + 2.0);
+        assert_eq!(grad.wrt(&y), 1.25);
+
+        let root = Node::nary_fn_with_partials(
+            vec![x.clone(), y.clone()],
+            |vals| vals[0] - vals[1],
+            |_| vec![1.0, -1.0],
+        );
+        check_numeric(&root, &[x, y], &[0.3, 0.7]);
+    }
+
+    #[test]
+    fn detach() {
+        let x = InputNode::from_val(3.0);
+        // gradient of x * stop(x) treats the second factor as constant
+        let root = Node::mul(x.clone(), Node::detach(x.clone()));
+        assert_eq!(root.borrow_mut().compute(), 9.0);
+        assert_eq!(Gradient::of(&root).wrt(&x), 3.0);
+        assert_eq!(Gradient::of(&Node::detach(x.clone())).wrt(&x), 0.0);
+    }
+
+    #[test]
+    fn accumulation() {
+        let x = InputNode::from_val(2.0);
+        let y = InputNode::from_val(3.0);
+        let params = [x.clone(), y.clone()];
+        let loss = Node::mul(x.clone(), y.clone());
+        Gradient::backward(&loss, &params);
+        assert_eq!((x.borrow().grad(), y.borrow().grad()), (3.0, 2.0));
+
+        // second batch adds to the first
+        x.borrow_mut().set(1.0);
+        let grad = Gradient::backward(&Node::add(loss, x.clone()), &params);
+        assert_eq!(grad.wrt(&x), 4.0);
+        assert_eq!((x.borrow().grad(), y.borrow().grad()), (7.0, 3.0));
+
+        zero_grad(&params);
+        assert_eq!((x.borrow().grad(), y.borrow().grad()), (0.0, 0.0));
+        assert_eq!(x.borrow_mut().compute(), 1.0);
+    }
+
+    #[test]
+    fn sparse_jacobian() {
+        let x = InputNode::from_val(2.0);
+        let y = InputNode::from_val(3.0);
+        let z = InputNode::from_val(0.0);
+        let shared = Node::mul(x.clone(), y.clone());
+        let outputs: Vec<Handle> = vec![
+            Node::add(shared.clone(), Node::constant(1.0)),
+            Node::sin(z.clone()),
+            Node::constant(4.0),
+            Node::mul(shared, x.clone()),
+        ];
+        let inputs = [x, y, z];
+        let sparsity = Sparsity::of(&outputs, &inputs);
+        assert_eq!(sparsity.row(0), &[0, 1]);
+        assert_eq!(sparsity.row(1), &[2]);
+        assert!(sparsity.row(2).is_empty());
+        assert_eq!(sparsity.nnz(), 5);
+        assert_eq!(sparsity.density(), 5.0 / 12.0);
+
+        let jacobian = Jacobian::of(&outputs, &inputs);
+        assert_eq!(jacobian.shape(), (4, 3));
+        assert_eq!(jacobian.nnz(), 5);
+        assert_eq!(
+            jacobian.to_dense(),
+            vec![
+                vec![3.0, 2.0, 0.0],
+                vec![0.0, 0.0, 1.0],
+                vec![0.0; 3],
+                vec![12.0, 4.0, 0.0],
+            ]
+        );
+        assert_eq!(jacobian.get(1, 2), 1.0);
+        assert_eq!(jacobian.get(1, 0), 0.0);
+    }
+}
@@ -0,0 +1,1000 @@
+//! Container keeping named inputs and outputs of a graph.
+//!
+//! Names may be qualified with namespaces separated by `::`, like `fx::spot`,
+//! so graphs from different sources can be merged without their names colliding.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::{
+    error::GraphError,
+    eval::{ComputeObserver, Evaluation},
+    float::Float,
+    input::{InputNode, InputSource},
+    node::{evaluate, node_addr, operand_value, post_order, Computable, Handle, Node},
+    reactive,
+};
+
+/// Separator of namespaces in qualified names.
+pub const NAMESPACE_SEPARATOR: &str = "::";
+
+/// Graph with inputs and outputs registered under names.
+/// Both are kept in registration order, so iteration order doesn't depend on
+/// how the nodes were constructed.
+#[derive(Clone)]
+pub struct Graph<T: Float = f32> {
+    inputs: Vec<(String, Rc<RefCell<InputNode<T>>>)>,
+    outputs: Vec<(String, Output<T>)>,
+    /// Observers of computations by `try_outputs`.
+    observers: Vec<Rc<RefCell<dyn ComputeObserver>>>,
+    /// Sources of input values polled by `refresh`.
+    sources: Vec<Binding<T>>,
+}
+
+/// Input along with source of its values.
+type Binding<T> = (Rc<RefCell<InputNode<T>>>, Rc<RefCell<dyn InputSource<T>>>);
+
+/// Registered output along with its recomputation settings.
+#[derive(Clone)]
+struct Output<T: Float> {
+    node: Handle<T>,
+    /// Outputs with higher priority are recomputed first.
+    priority: i32,
+    /// Callbacks receiving new value on each recomputation.
+    observers: Vec<Rc<dyn Fn(T)>>,
+}
+
+impl<T: Float> Default for Graph<T> {
+    fn default() -> Self {
+        Self {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            observers: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl<T: Float> Graph<T> {
+    /// Creates empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates input node with given value and registers it under given name, used as its label.
+    /// Registering existing name replaces the input, keeping its position.
+    pub fn add_input(&mut self, name: impl Into<String>, val: T) -> Rc<RefCell<InputNode<T>>> {
+        let name = name.into();
+        let input = InputNode::with_label(val, name.clone());
+        insert(&mut self.inputs, name, input.clone());
+        input
+    }
+
+    /// Registers existing input node under given name, which also becomes its label
+    /// unless it has one. Registering existing name replaces the input, keeping its position.
+    pub fn register_input(&mut self, name: impl Into<String>, input: Rc<RefCell<InputNode<T>>>) {
+        let name = name.into();
+        if input.borrow().label().is_none() {
+            input.borrow_mut().set_label(name.clone());
+        }
+        insert(&mut self.inputs, name, input);
+    }
+
+    /// Returns input registered under given name.
+    pub fn input(&self, name: &str) -> Option<&Rc<RefCell<InputNode<T>>>> {
+        find(&self.inputs, name)
+    }
+
+    /// Returns registered inputs in registration order.
+    pub fn inputs(&self) -> impl Iterator<Item = (&str, &Rc<RefCell<InputNode<T>>>)> {
+        self.inputs
+            .iter()
+            .map(|(name, input)| (name.as_str(), input))
+    }
+
+    /// Restores default values of all inputs.
+    /// Each dependent node is invalidated at most once, however many of its inputs change.
+    pub fn reset_inputs(&self) {
+        self.inputs
+            .iter()
+            .for_each(|(_, input)| input.borrow_mut().reset());
+    }
+
+    /// Registers node as output under given name, with zero priority.
+    /// Registering existing name replaces the output, keeping its position.
+    pub fn add_output(&mut self, name: impl Into<String>, node: Rc<RefCell<dyn Computable<T>>>) {
+        let output = Output {
+            node,
+            priority: 0,
+            observers: Vec::new(),
+        };
+        insert(&mut self.outputs, name.into(), output);
+    }
+
+    /// Returns output registered under given name.
+    pub fn output(&self, name: &str) -> Option<&Rc<RefCell<dyn Computable<T>>>> {
+        find(&self.outputs, name).map(|output| &output.node)
+    }
+
+    /// Returns registered output nodes in registration order, without computing them.
+    pub fn output_nodes(&self) -> impl Iterator<Item = (&str, &Rc<RefCell<dyn Computable<T>>>)> {
+        self.outputs
+            .iter()
+            .map(|(name, output)| (name.as_str(), &output.node))
+    }
+
+    /// Sets recomputation priority of output, returning `false` if it isn't registered.
+    pub fn set_priority(&mut self, name: &str, priority: i32) -> bool {
+        find_mut(&mut self.outputs, name).map_or(false, |output| {
+            output.priority = priority;
+            true
+        })
+    }
+
+    /// Registers callback called with new value each time output is recomputed
+    /// by `recompute`, returning `false` if output isn't registered.
+    pub fn observe(&mut self, name: &str, callback: impl Fn(T) + 'static) -> bool {
+        find_mut(&mut self.outputs, name).map_or(false, |output| {
+            output.observers.push(Rc::new(callback));
+            true
+        })
+    }
+
+    /// Recomputes dirty outputs from highest priority to lowest, notifying their observers,
+    /// and returns their names in recomputation order.
+    pub fn recompute(&self) -> Vec<&str> {
+        self.recompute_from(i32::MIN)
+    }
+
+    /// Same as `recompute`, but only for outputs with at least given priority.
+    /// Lower priority outputs stay dirty, so they can be recomputed later, e.g. when idle.
+    pub fn recompute_from(&self, min_priority: i32) -> Vec<&str> {
+        let mut dirty: Vec<_> = self
+            .outputs
+            .iter()
+            .filter(|(_, output)| {
+                output.priority >= min_priority && output.node.borrow().is_dirty()
+            })
+            .collect();
+        // stable, so outputs of equal priority keep registration order
+        dirty.sort_by_key(|(_, output)| std::cmp::Reverse(output.priority));
+        dirty
+            .into_iter()
+            .map(|(name, output)| {
+                let val = output.node.borrow_mut().compute();
+                output.observers.iter().for_each(|o| o(val));
+                name.as_str()
+            })
+            .collect()
+    }
+
+    /// Sets inputs with given names to given values and computes all outputs,
+    /// returning them by name. Subexpressions shared by outputs are computed once.
+    /// Fails like `set_many`, in which case no input is changed.
+    pub fn eval(&self, inputs: &[(&str, T)]) -> Result<HashMap<String, T>, GraphError> {
+        self.set_many(inputs)?;
+        Ok(self
+            .outputs()
+            .into_iter()
+            .map(|(name, val)| (name.to_string(), val))
+            .collect())
+    }
+
+    /// Sets inputs with given names to given values, resetting caches of their dependents
+    /// in a single pass once all values are stored, so nodes depending on several of them
+    /// are reset once. If some input isn't registered or some value violates rules of its
+    /// input, no input is changed. Subscribed nodes are recomputed afterwards, see `reactive`.
+    pub fn set_many(&self, inputs: &[(&str, T)]) -> Result<(), GraphError> {
+        self.replace_many(inputs)?;
+        reactive::flush();
+        Ok(())
+    }
+
+    /// Sets inputs like `set_many`, without recomputing subscribed nodes.
+    pub(crate) fn replace_many(&self, inputs: &[(&str, T)]) -> Result<(), GraphError> {
+        let inputs = inputs
+            .iter()
+            .map(|(name, val)| match self.input(name) {
+                Some(input) => Ok((input, *val)),
+                None => Err(GraphError::UnknownInput {
+                    name: name.to_string(),
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (input, val) in &inputs {
+            input.borrow().validate(*val)?;
+        }
+        let old: Vec<_> = inputs
+            .iter()
+            .map(|(input, val)| input.borrow_mut().replace(*val))
+            .collect();
+        // caches already reset by preceding inputs stop the walk of following ones,
+        // inputs keeping their value don't reset any
+        for ((input, _), old) in inputs.iter().zip(old) {
+            // listeners are called once input is released, so they can compute dependents
+            let calls = input.borrow_mut().invalidate(old);
+            calls.into_iter().for_each(|call| call());
+        }
+        Ok(())
+    }
+
+    /// Binds source of values to input, replacing source bound to it before.
+    /// Sources of inputs not registered in graph are ignored by `refresh`, e.g. of inputs
+    /// of `b` merged into inputs of `a` by `merge`.
+    pub fn bind_input(
+        &mut self,
+        input: &Rc<RefCell<InputNode<T>>>,
+        source: impl InputSource<T> + 'static,
+    ) {
+        let source: Rc<RefCell<dyn InputSource<T>>> = Rc::new(RefCell::new(source));
+        match self
+            .sources
+            .iter_mut()
+            .find(|(x, _)| node_addr(x) == node_addr(input))
+        {
+            Some((_, existing)) => *existing = source,
+            None => self.sources.push((input.clone(), source)),
+        }
+    }
+
+    /// Polls sources bound by `bind_input` and sets inputs to new values like `set_many`,
+    /// then recomputes dirty outputs like `recompute`, returning names of outputs whose
+    /// values changed, including outputs not computed before, in registration order.
+    /// If some new value violates rules of its input, no input is changed
+    /// and polled values are dropped.
+    pub fn refresh(&self) -> Result<Vec<&str>, GraphError> {
+        let mut values = Vec::new();
+        for (input, source) in &self.sources {
+            let val = source.borrow_mut().poll();
+            let name = self
+                .inputs
+                .iter()
+                .find(|(_, x)| node_addr(x) == node_addr(input))
+                .map(|(name, _)| name.as_str());
+            if let (Some(name), Some(val)) = (name, val) {
+                values.push((name, val));
+            }
+        }
+        let previous: HashMap<&str, T> = self
+            .outputs
+            .iter()
+            .filter(|(_, output)| !output.node.borrow().is_dirty())
+            .map(|(name, output)| (name.as_str(), output.node.borrow_mut().compute()))
+            .collect();
+        self.set_many(&values)?;
+        self.recompute();
+        let changed = self
+            .outputs
+            .iter()
+            .filter(|(name, output)| {
+                let val = output.node.borrow_mut().compute();
+                previous.get(name.as_str()).map_or(true, |old| {
+                    *old != val && !(old.to_f64().is_nan() && val.to_f64().is_nan())
+                })
+            })
+            .map(|(name, _)| name.as_str())
+            .collect();
+        Ok(changed)
+    }
+
+    /// Computes only outputs with given names, returning their values in given order,
+    /// or `None` if some name isn't registered.
+    /// Other outputs are left uncomputed and keep being reported by `dirty_outputs`.
+    pub fn compute_only(&self, names: &[&str]) -> Option<Vec<T>> {
+        let outputs = names
+            .iter()
+            .map(|name| self.output(name))
+            .collect::<Option<Vec<_>>>()?;
+        Some(
+            outputs
+                .into_iter()
+                .map(|node| node.borrow_mut().compute())
+                .collect(),
+        )
+    }
+
+    /// Returns names of outputs whose values may have changed since they were last computed,
+    /// in registration order, without computing them.
+    pub fn dirty_outputs(&self) -> Vec<&str> {
+        self.outputs
+            .iter()
+            .filter(|(_, output)| output.node.borrow().is_dirty())
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Checks that no node reachable from outputs is computed from itself,
+    /// which would otherwise panic on evaluation.
+    pub fn validate(&self) -> Result<(), GraphError> {
+        let mut done = HashSet::new();
+        self.outputs
+            .iter()
+            .try_for_each(|(_, output)| check_cycles(output.node.clone(), &mut done))
+    }
+
+    /// Builds graph containing inputs and outputs of both graphs.
+    /// Inputs with the same name are merged into input of `a`, so nodes of `b` using them
+    /// are rebuilt over it, while other nodes of `b` are shared with `b`.
+    /// Custom nodes without operation aren't rebuilt, so they keep using inputs of `b`.
+    /// Fails if both graphs have output with the same name.
+    pub fn merge(a: &Graph<T>, b: &Graph<T>) -> Result<Graph<T>, GraphError> {
+        let mut merged = a.clone();
+        let mut done: HashMap<usize, Handle<T>> = HashMap::new();
+        for (name, input) in &b.inputs {
+            match a.input(name) {
+                Some(existing) => {
+                    done.insert(node_addr(input), existing.clone());
+                }
+                None => merged.inputs.push((name.clone(), input.clone())),
+            }
+        }
+        for (name, output) in &b.outputs {
+            if a.output(name).is_some() {
+                return Err(GraphError::DuplicateName { name: name.clone() });
+            }
+            let node = substitute(&output.node, &mut done);
+            merged.outputs.push((
+                name.clone(),
+                Output {
+                    node,
+                    ..output.clone()
+                },
+            ));
+        }
+        merged.sources.extend(b.sources.iter().cloned());
+        Ok(merged)
+    }
+
+    /// Replaces uses of `target` by nodes reachable from outputs with `replacement`,
+    /// returning amount of outputs which changed. Nodes depending on target are rebuilt,
+    /// like by `merge`, so they start without cache, while original nodes stay intact
+    /// for other users. Output being target itself is replaced too.
+    pub fn substitute(&mut self, target: &Handle<T>, replacement: Handle<T>) -> usize {
+        let mut done = HashMap::new();
+        done.insert(node_addr(target), replacement);
+        let mut changed = 0;
+        for (_, output) in &mut self.outputs {
+            let node = substitute(&output.node, &mut done);
+            if node_addr(&node) != node_addr(&output.node) {
+                output.node = node;
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Same as `merge`, but names of `b` are put into given namespace first,
+    /// so its inputs are only merged with inputs of `a` from that namespace.
+    pub fn merge_into(a: &Graph<T>, namespace: &str, b: &Graph<T>) -> Result<Graph<T>, GraphError> {
+        Self::merge(a, &b.with_namespace(namespace))
+    }
+
+    /// Returns graph sharing nodes with this one, with names of inputs and outputs
+    /// put into given namespace, e.g. `spot` becomes `fx::spot` in namespace `fx`.
+    /// Namespace may be nested itself, like `book::fx`.
+    /// Panics if namespace is empty.
+    pub fn with_namespace(&self, namespace: &str) -> Graph<T> {
+        assert!(!namespace.is_empty(), "namespace can't be empty");
+        let qualify = |name: &str| format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, name);
+        Graph {
+            inputs: self
+                .inputs
+                .iter()
+                .map(|(name, input)| (qualify(name), input.clone()))
+                .collect(),
+            outputs: self
+                .outputs
+                .iter()
+                .map(|(name, output)| (qualify(name), output.clone()))
+                .collect(),
+            observers: self.observers.clone(),
+            sources: self.sources.clone(),
+        }
+    }
+
+    /// Returns graph sharing nodes with this one, containing only inputs and outputs
+    /// from given namespace, with the namespace stripped from their names.
+    /// Names of nested namespaces keep the rest of their qualification.
+    pub fn namespace(&self, namespace: &str) -> Graph<T> {
+        let prefix = format!("{}{}", namespace, NAMESPACE_SEPARATOR);
+        let local = |name: &String| name.strip_prefix(&prefix).map(str::to_string);
+        Graph {
+            inputs: self
+                .inputs
+                .iter()
+                .filter_map(|(name, input)| Some((local(name)?, input.clone())))
+                .collect(),
+            outputs: self
+                .outputs
+                .iter()
+                .filter_map(|(name, output)| Some((local(name)?, output.clone())))
+                .collect(),
+            observers: self.observers.clone(),
+            sources: self.sources.clone(),
+        }
+    }
+
+    /// Returns top level namespaces used by names of inputs and outputs,
+    /// in order of their first appearance.
+    pub fn namespaces(&self) -> Vec<&str> {
+        let names = self.inputs.iter().map(|(name, _)| name);
+        let names = names.chain(self.outputs.iter().map(|(name, _)| name));
+        let mut namespaces = Vec::new();
+        for name in names {
+            if let Some((namespace, _)) = name.split_once(NAMESPACE_SEPARATOR) {
+                if !namespaces.contains(&namespace) {
+                    namespaces.push(namespace);
+                }
+            }
+        }
+        namespaces
+    }
+
+    /// Computes outputs and returns their names with values in registration order.
+    /// Outputs are computed together, see `Graph::compute_all`.
+    pub fn outputs(&self) -> Vec<(&str, T)> {
+        let nodes: Vec<Handle<T>> = self.outputs.iter().map(|(_, o)| o.node.clone()).collect();
+        let names = self.outputs.iter().map(|(name, _)| name.as_str());
+        names.zip(Self::compute_all(&nodes)).collect()
+    }
+
+    /// Computes given nodes as a single evaluation, returning their values in given order.
+    /// Intermediate results shared by several roots are computed once, so even volatile
+    /// nodes such as `RandNode` contribute the same value to all of them.
+    pub fn compute_all(roots: &[Handle<T>]) -> Vec<T> {
+        let values = evaluate(&roots.iter().collect::<Vec<_>>());
+        roots.iter().map(|x| operand_value(&values, x)).collect()
+    }
+
+    /// Attaches observer notified of computations by `try_outputs`.
+    /// Observer is shared, so its state can be read after evaluation.
+    pub fn attach_observer(&mut self, observer: Rc<RefCell<dyn ComputeObserver>>) {
+        self.observers.push(observer);
+    }
+
+    /// Computes outputs within limits of given evaluation, notifying attached observers
+    /// along with observers of evaluation, and returns their names with values
+    /// in registration order. Fails on the first output failing to compute.
+    pub fn try_outputs(&self, eval: Evaluation) -> Result<Vec<(&str, T)>, GraphError> {
+        let mut eval = self
+            .observers
+            .iter()
+            .fold(eval, |eval, observer| eval.observer(observer.clone()));
+        self.outputs
+            .iter()
+            .map(|(name, output)| {
+                Ok((
+                    name.as_str(),
+                    output.node.borrow_mut().try_compute(&mut eval)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Returns nodes reachable from outputs, each once, operands first.
+    pub(crate) fn reachable(&self) -> Vec<Handle<T>> {
+        let mut seen = HashSet::new();
+        self.output_nodes()
+            .flat_map(|(_, output)| post_order(output))
+            .filter(|node| seen.insert(node_addr(node)))
+            .collect()
+    }
+}
+
+/// Rebuilds operation nodes reachable from root over replaced nodes,
+/// keeping nodes not depending on replaced ones.
+/// `done` maps addresses of nodes to their replacements, and is extended with rebuilt nodes.
+fn substitute<T: Float>(root: &Handle<T>, done: &mut HashMap<usize, Handle<T>>) -> Handle<T> {
+    for node in post_order(root) {
+        let addr = node_addr(&node);
+        if done.contains_key(&addr) {
+            continue;
+        }
+        let opp = node.borrow().operation().cloned();
+        let new = match opp {
+            Some(opp)
+                if opp
+                    .operands()
+                    .iter()
+                    .any(|x| done.contains_key(&node_addr(x))) =>
+            {
+                Node::new(opp.map_operands(|x| {
+                    done.get(&node_addr(x))
+                        .cloned()
+                        .unwrap_or_else(|| x.clone())
+                }))
+            }
+            _ => continue,
+        };
+        done.insert(addr, new);
+    }
+    done.get(&node_addr(root))
+        .cloned()
+        .unwrap_or_else(|| root.clone())
+}
+
+/// Searches for cycles among nodes reachable from root, skipping nodes already checked.
+fn check_cycles<T: Float>(root: Handle<T>, done: &mut HashSet<usize>) -> Result<(), GraphError> {
+    if done.contains(&node_addr(&root)) {
+        return Ok(());
+    }
+    // nodes on current path along with their operands left to visit
+    let operands = root.borrow().operands();
+    let mut path = vec![(root, operands)];
+    let mut on_path: HashSet<usize> = path.iter().map(|(node, _)| node_addr(node)).collect();
+    while let Some((_, operands)) = path.last_mut() {
+        match operands.pop() {
+            Some(x) => {
+                let addr = node_addr(&x);
+                if on_path.contains(&addr) {
+                    let start = path.iter().position(|(node, _)| node_addr(node) == addr);
+                    return Err(GraphError::Cycle {
+                        operations: path[start.unwrap()..]
+                            .iter()
+                            .map(|(node, _)| {
+                                node.borrow().operation().map_or("custom", |opp| opp.name())
+                            })
+                            .collect(),
+                    });
+                }
+                if !done.contains(&addr) {
+                    let operands = x.borrow().operands();
+                    on_path.insert(addr);
+                    path.push((x, operands));
+                }
+            }
+            None => {
+                let (node, _) = path.pop().unwrap();
+                on_path.remove(&node_addr(&node));
+                done.insert(node_addr(&node));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Inserts named entry, replacing existing entry with the same name in place.
+fn insert<V>(entries: &mut Vec<(String, V)>, name: String, val: V) {
+    match entries.iter_mut().find(|(n, _)| *n == name) {
+        Some(entry) => entry.1 = val,
+        None => entries.push((name, val)),
+    }
+}
+
+fn find<'a, V>(entries: &'a [(String, V)], name: &str) -> Option<&'a V> {
+    entries.iter().find(|(n, _)| n == name).map(|(_, val)| val)
+}
+
+fn find_mut<'a, V>(entries: &'a mut [(String, V)], name: &str) -> Option<&'a mut V> {
+    entries
+        .iter_mut()
+        .find(|(n, _)| n == name)
+        .map(|(_, val)| val)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::Distribution,
+        input::{Input, InputHandle, RandNode, Rule},
+        node::Node,
+    };
+
+    #[test]
+    fn named_outputs() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 2.0);
+        let y = g.add_input("y", 3.0);
+        g.add_output("sum", Node::add(x.clone(), y.clone()));
+        g.add_output("product", Node::mul(x, y.clone()));
+        g.add_output("a", Node::constant(1.0));
+        assert_eq!(
+            g.outputs(),
+            vec![("sum", 5.0), ("product", 6.0), ("a", 1.0)]
+        );
+
+        g.input("y").unwrap().borrow_mut().set(4.0);
+        assert_eq!(g.output("product").unwrap().borrow_mut().compute(), 8.0);
+        assert!(g.output("missing").is_none());
+
+        g.add_output("sum", Node::sub(y, Node::constant(1.0)));
+        assert_eq!(
+            g.outputs(),
+            vec![("sum", 3.0), ("product", 8.0), ("a", 1.0)]
+        );
+    }
+
+    #[test]
+    fn compute_all() {
+        let r = RandNode::new(
+            Distribution::Uniform {
+                low: 0.0,
+                high: 1.0,
+            },
+            7,
+        );
+        let x = Node::input(0.0f32);
+        let a: Handle = Node::add(r.clone(), x.clone());
+        let b: Handle = Node::sub(r.clone(), x.clone());
+        let vals = Graph::compute_all(&[a.clone(), b.clone(), r]);
+        // roots share single draw
+        assert_eq!(vals[0], vals[2]);
+        assert_eq!(vals[1], vals[2]);
+        let again = Graph::compute_all(&[a, b]);
+        assert_ne!(again[0], vals[0]);
+
+        let y: Handle = Node::mul(x.clone(), x.clone());
+        x.set(3.0).unwrap();
+        assert_eq!(Graph::compute_all(&[y.clone(), x]), vec![9.0, 3.0]);
+        assert!(!y.borrow().is_dirty());
+    }
+
+    #[test]
+    fn input_order() {
+        let mut g = Graph::new();
+        g.add_input("b", 1.0);
+        g.add_input("a", 2.0);
+        g.add_input("b", 3.0);
+        let inputs: Vec<_> = g
+            .inputs()
+            .map(|(name, input)| (name, input.borrow_mut().compute()))
+            .collect();
+        assert_eq!(inputs, vec![("b", 3.0), ("a", 2.0)]);
+    }
+
+    #[test]
+    fn reset_inputs() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 2.0);
+        let y = g.add_input("y", 3.0);
+        g.add_output("sum", Node::add(x.clone(), y.clone()));
+        x.borrow_mut().set(5.0);
+        y.borrow_mut().set(7.0);
+        assert_eq!(g.outputs(), vec![("sum", 12.0)]);
+
+        g.reset_inputs();
+        assert_eq!(g.outputs(), vec![("sum", 5.0)]);
+    }
+
+    #[test]
+    fn cycles() {
+        struct Custom {
+            source: Option<Handle>,
+        }
+
+        impl Computable for Custom {
+            fn compute(&mut self) -> f32 {
+                self.source.as_ref().unwrap().borrow_mut().compute()
+            }
+
+            fn add_dependency(&mut self, _dependency: Handle) {}
+
+            fn reset_cache(&mut self) {}
+
+            fn operands(&self) -> Vec<Handle> {
+                self.source.iter().cloned().collect()
+            }
+        }
+
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0);
+        let custom = Rc::new(RefCell::new(Custom { source: None }));
+        let shared = Node::sin(x.clone());
+        let y = Node::add(Node::mul(custom.clone(), shared.clone()), shared);
+        g.add_output("x", x);
+        g.add_output("y", y.clone());
+        assert_eq!(g.validate(), Ok(()));
+
+        custom.borrow_mut().source = Some(y);
+        let err = g.validate().unwrap_err();
+        assert_eq!(
+            err,
+            GraphError::Cycle {
+                operations: vec!["add", "mul", "custom"]
+            }
+        );
+        assert_eq!(err.to_string(), "cycle through add -> mul -> custom");
+        custom.borrow_mut().source = None;
+    }
+
+    #[test]
+    fn dirty_outputs() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0);
+        let y = g.add_input("y", 2.0);
+        g.add_output("a", Node::sin(x.clone()));
+        g.add_output("b", Node::mul(x, y.clone()));
+        g.add_output("c", Node::expm1(y.clone()));
+        assert_eq!(g.dirty_outputs(), vec!["a", "b", "c"]);
+
+        g.outputs();
+        assert!(g.dirty_outputs().is_empty());
+
+        y.borrow_mut().set(3.0);
+        assert_eq!(g.dirty_outputs(), vec!["b", "c"]);
+        g.output("b").unwrap().borrow_mut().compute();
+        assert_eq!(g.dirty_outputs(), vec!["c"]);
+    }
+
+    #[test]
+    fn compute_only() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 2.0);
+        g.add_output("a", Node::mul(x.clone(), x.clone()));
+        g.add_output("b", Node::add(x.clone(), Node::constant(1.0)));
+        g.add_output("c", Node::sin(x.clone()));
+
+        assert_eq!(g.compute_only(&["c", "a"]), Some(vec![2f32.sin(), 4.0]));
+        assert_eq!(g.dirty_outputs(), vec!["b"]);
+
+        x.borrow_mut().set(3.0);
+        assert_eq!(g.compute_only(&["a"]), Some(vec![9.0]));
+        assert_eq!(g.dirty_outputs(), vec!["b", "c"]);
+        assert_eq!(g.compute_only(&["a", "missing"]), None);
+    }
+
+    #[test]
+    fn eval() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0);
+        let y = g.add_input("y", 1.0);
+        let shared = Node::mul(x, y);
+        g.add_output("sum", Node::add(shared.clone(), Node::constant(1.0)));
+        g.add_output("square", Node::mul(shared.clone(), shared));
+
+        let res = g.eval(&[("x", 2.0), ("y", 3.0)]).unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!((res["sum"], res["square"]), (7.0, 36.0));
+        assert_eq!(g.eval(&[("y", 1.0)]).unwrap()["sum"], 3.0);
+
+        g.input("y")
+            .unwrap()
+            .borrow_mut()
+            .add_rule(Rule::Range(0.0, 10.0));
+        assert!(matches!(
+            g.eval(&[("x", 1.0), ("y", 20.0)]),
+            Err(GraphError::Validation(err)) if err.value == 20.0
+        ));
+        assert_eq!(g.eval(&[]).unwrap()["sum"], 3.0);
+    }
+
+    #[test]
+    fn set_many() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0);
+        let y = g.add_input("y", 1.0);
+        y.borrow_mut().add_rule(Rule::Range(0.0, 10.0));
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let log = changes.clone();
+        x.borrow_mut()
+            .on_set(move |old, new| log.borrow_mut().push((old, new)));
+        g.add_output("sum", Node::add(x.clone(), y));
+        assert_eq!(g.outputs(), vec![("sum", 2.0)]);
+
+        assert!(matches!(
+            g.set_many(&[("x", 2.0), ("y", 20.0)]),
+            Err(GraphError::Validation(err)) if err.value == 20.0
+        ));
+        assert!(g.dirty_outputs().is_empty());
+        assert_eq!(x.borrow_mut().compute(), 1.0);
+
+        g.set_many(&[("x", 2.0), ("y", 3.0)]).unwrap();
+        assert_eq!(g.dirty_outputs(), vec!["sum"]);
+        assert_eq!(g.outputs(), vec![("sum", 5.0)]);
+        assert_eq!(*changes.borrow(), vec![(1.0, 2.0)]);
+    }
+
+    #[test]
+    fn refresh() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0);
+        let y = g.add_input("y", 1.0);
+        y.borrow_mut().add_rule(Rule::Range(0.0, 10.0));
+        g.add_output("sum", Node::add(x.clone(), y.clone()));
+        g.add_output("sign", Node::sign(x.clone()));
+        g.add_output("y", y.clone());
+
+        let mut feed = vec![Some(2.0), None, Some(3.0)];
+        g.bind_input(&x, move || feed.pop().flatten());
+        let readings = Rc::new(RefCell::new(vec![Some(20.0), None]));
+        let sensor = readings.clone();
+        g.bind_input(&y, move || sensor.borrow_mut().pop().flatten());
+        assert_eq!(g.refresh(), Ok(vec!["sum", "sign"]));
+        assert_eq!(g.outputs(), vec![("sum", 4.0), ("sign", 1.0), ("y", 1.0)]);
+
+        assert!(matches!(
+            g.refresh(),
+            Err(GraphError::Validation(err)) if err.value == 20.0
+        ));
+        assert_eq!(x.borrow().value(), 3.0);
+        // sign keeps its value
+        assert_eq!(g.refresh(), Ok(vec!["sum"]));
+        readings.borrow_mut().push(Some(5.0));
+        assert_eq!(g.refresh(), Ok(vec!["sum", "y"]));
+        assert_eq!(g.refresh(), Ok(vec![]));
+        assert_eq!(g.outputs()[0], ("sum", 7.0));
+    }
+
+    #[test]
+    fn unknown_input() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        let unknown = GraphError::UnknownInput { name: "z".into() };
+        assert_eq!(g.eval(&[("x", 2.0), ("z", 1.0)]), Err(unknown.clone()));
+        assert_eq!(g.set_many(&[("z", 1.0)]), Err(unknown.clone()));
+        assert_eq!(unknown.to_string(), "input z isn't registered");
+        assert_eq!(x.borrow().value(), 1.0);
+    }
+
+    #[test]
+    fn priorities() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0);
+        g.add_output("analytics", Node::mul(x.clone(), Node::constant(3.0)));
+        g.add_output("display", Node::add(x.clone(), Node::constant(1.0)));
+        g.add_output("status", Node::sin(x.clone()));
+        assert!(g.set_priority("display", 10));
+        assert!(g.set_priority("analytics", -1));
+        assert!(!g.set_priority("missing", 1));
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let sink = log.clone();
+        assert!(g.observe("display", move |v| sink.borrow_mut().push(v)));
+        assert_eq!(g.recompute(), vec!["display", "status", "analytics"]);
+        assert_eq!(*log.borrow(), vec![2.0]);
+
+        x.borrow_mut().set(2.0);
+        assert_eq!(g.recompute_from(0), vec!["display", "status"]);
+        assert_eq!(g.dirty_outputs(), vec!["analytics"]);
+        assert_eq!(g.recompute(), vec!["analytics"]);
+        assert!(g.recompute().is_empty());
+        assert_eq!(*log.borrow(), vec![2.0, 3.0]);
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = Graph::new();
+        let x = a.add_input("x", 2.0f32);
+        a.add_output("double", Node::mul(x.clone(), Node::constant(2.0)));
+
+        let mut b = Graph::new();
+        let bx = b.add_input("x", 5.0);
+        let y = b.add_input("y", 3.0);
+        let shared = Node::add(bx, y.clone());
+        let y_only = Node::sin(y);
+        b.add_output("sum", shared.clone());
+        b.add_output("square", Node::mul(shared.clone(), shared));
+        b.add_output("sin", y_only.clone());
+
+        let merged = Graph::merge(&a, &b).unwrap();
+        let names: Vec<_> = merged.inputs().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["x", "y"]);
+        assert_eq!(
+            merged.outputs(),
+            vec![
+                ("double", 4.0),
+                ("sum", 5.0),
+                ("square", 25.0),
+                ("sin", 3.0f32.sin())
+            ]
+        );
+        // nodes not using merged inputs are reused, shared nodes stay shared
+        assert_eq!(node_addr(merged.output("sin").unwrap()), node_addr(&y_only));
+        let square = merged.output("square").unwrap().borrow().operands();
+        assert_eq!(
+            node_addr(&square[0]),
+            node_addr(merged.output("sum").unwrap())
+        );
+
+        x.borrow_mut().set(1.0);
+        assert_eq!(merged.compute_only(&["square"]), Some(vec![16.0]));
+        // original graph is untouched
+        assert_eq!(b.compute_only(&["sum"]), Some(vec![8.0]));
+
+        assert_eq!(
+            Graph::merge(&a, &a).err(),
+            Some(GraphError::DuplicateName {
+                name: "double".into()
+            })
+        );
+    }
+
+    #[test]
+    fn namespaces() {
+        let mut fx = Graph::new();
+        let spot = fx.add_input("spot", 1.5f32);
+        fx.add_output("value", Node::mul(spot, Node::constant(2.0)));
+
+        let mut ir = Graph::new();
+        let spot = ir.add_input("spot", 0.1);
+        ir.add_output("value", Node::add(spot, Node::constant(1.0)));
+
+        // same names would collide without namespaces
+        assert!(Graph::merge(&fx, &ir).is_err());
+        let book = Graph::merge(&fx.with_namespace("fx"), &ir.with_namespace("ir")).unwrap();
+        let names: Vec<_> = book.inputs().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["fx::spot", "ir::spot"]);
+        assert_eq!(book.namespaces(), vec!["fx", "ir"]);
+        assert_eq!(book.eval(&[("ir::spot", 0.5)]).unwrap()["ir::value"], 1.5);
+
+        let local = book.namespace("fx");
+        assert_eq!(local.outputs(), vec![("value", 3.0)]);
+        local.input("spot").unwrap().borrow_mut().set(2.0);
+        assert_eq!(book.compute_only(&["fx::value"]), Some(vec![4.0]));
+        assert!(book.namespace("eq").inputs().next().is_none());
+
+        // inputs are shared only within the namespace
+        let mut risk = Graph::new();
+        let spot = risk.add_input("spot", 0.0);
+        risk.add_output("delta", Node::sin(spot));
+        let nested = Graph::merge_into(&book, "fx", &risk).unwrap();
+        assert_eq!(nested.inputs().count(), 2);
+        assert_eq!(nested.compute_only(&["fx::delta"]), Some(vec![2f32.sin()]));
+
+        let nested = book.with_namespace("desk");
+        assert_eq!(nested.namespaces(), vec!["desk"]);
+        assert!(nested.namespace("desk::fx").input("spot").is_some());
+    }
+
+    #[test]
+    fn substitute() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 2.0);
+        let placeholder: Handle = Node::constant(0.0);
+        let scaled = Node::mul(placeholder.clone(), x.clone());
+        g.add_output("scaled", scaled.clone());
+        g.add_output("same", placeholder.clone());
+        g.add_output("other", Node::sin(x.clone()));
+        assert_eq!(
+            g.outputs(),
+            vec![("scaled", 0.0), ("same", 0.0), ("other", 2f32.sin())]
+        );
+
+        let rate = Node::add(x.clone(), Node::constant(1.0));
+        assert_eq!(g.substitute(&placeholder, rate), 2);
+        assert_eq!(
+            g.outputs(),
+            vec![("scaled", 6.0), ("same", 3.0), ("other", 2f32.sin())]
+        );
+        // original nodes are kept
+        assert_eq!(scaled.borrow_mut().compute(), 0.0);
+        x.borrow_mut().set(1.0);
+        assert_eq!(g.dirty_outputs(), vec!["scaled", "same", "other"]);
+    }
+
+    #[test]
+    fn observers() {
+        #[derive(Default)]
+        struct Log(Vec<String>);
+        impl ComputeObserver for Log {
+            fn on_node_end(&mut self, operation: &'static str, value: f64) {
+                self.0.push(format!("{} = {}", operation, value));
+            }
+            fn on_cache_hit(&mut self, operation: &'static str) {
+                self.0.push(format!("{} cached", operation));
+            }
+        }
+
+        let mut g = Graph::new();
+        let x = g.add_input("x", 2.0f32);
+        let square = Node::mul(x.clone(), x);
+        g.add_output("square", square.clone());
+        g.add_output("sum", Node::add(square, Node::constant(1.0)));
+        let log = Rc::new(RefCell::new(Log::default()));
+        g.attach_observer(log.clone());
+        assert_eq!(
+            g.try_outputs(Evaluation::new()).unwrap(),
+            vec![("square", 4.0), ("sum", 5.0)]
+        );
+        assert_eq!(log.borrow().0, vec!["mul = 4", "mul cached", "add = 5"]);
+        assert!(matches!(
+            g.try_outputs(Evaluation::new()),
+            Ok(outputs) if outputs.len() == 2
+        ));
+        assert_eq!(log.borrow().0[3..], ["mul cached", "add cached"]);
+    }
+}
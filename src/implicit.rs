@@ -0,0 +1,147 @@
+//! Nodes defined implicitly as roots of residual subgraphs, e.g. calibrated parameters.
+//!
+//! Residual is compiled like a checkpoint and solved by Newton's method on each evaluation,
+//! starting from the previous solution. Derivatives with respect to the remaining inputs
+//! of the residual follow from the implicit function theorem, `dy/dp = -(df/dp) / (df/dy)`.
+
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    checkpoint::Segment,
+    input::InputNode,
+    node::{node_addr, Handle, Node},
+    ops::{CustomOp, Op, Operation},
+};
+
+/// Residual `f(y, p)` along with position of unknown `y` among its leaves.
+struct Implicit {
+    residual: Segment,
+    unknown: usize,
+    /// Bits of last solution, used as initial guess of the next one.
+    guess: AtomicU32,
+    tolerance: f32,
+    max_iterations: usize,
+}
+
+impl Implicit {
+    /// Returns values of residual leaves, with given value of unknown.
+    fn leaves(&self, params: &[f32], y: f32) -> Vec<f32> {
+        let mut leaves = params.to_vec();
+        leaves.insert(self.unknown, y);
+        leaves
+    }
+
+    /// Finds root of residual for given parameters, `NaN` if Newton's method doesn't converge.
+    fn solve(&self, params: &[f32]) -> f32 {
+        let mut y = f32::from_bits(self.guess.load(Ordering::Relaxed));
+        for _ in 0..self.max_iterations {
+            let (r, partials) = self.residual.gradient(&self.leaves(params, y));
+            if r.abs() <= self.tolerance {
+                self.guess.store(y.to_bits(), Ordering::Relaxed);
+                return y;
+            }
+            y -= r / partials[self.unknown];
+            if !y.is_finite() {
+                break;
+            }
+        }
+        f32::NAN
+    }
+}
+
+impl Op for Implicit {
+    fn name(&self) -> &'static str {
+        "implicit"
+    }
+
+    fn eval(&self, operands: &[f32]) -> f32 {
+        self.solve(operands)
+    }
+
+    fn partials(&self, operands: &[f32]) -> Vec<f32> {
+        let y = self.solve(operands);
+        let (_, mut partials) = self.residual.gradient(&self.leaves(operands, y));
+        let dy = partials.remove(self.unknown);
+        partials.iter().map(|d| -d / dy).collect()
+    }
+}
+
+impl Node {
+    /// Builds node computing value of `unknown` for which `residual` is zero, with other
+    /// nodes without operation of residual, such as inputs, as its operands. Solving starts
+    /// from current value of `unknown`, which itself isn't changed, and stops once residual
+    /// is within `tolerance` of zero, producing `NaN` if that doesn't happen within
+    /// `max_iterations` Newton steps. Residual must be acyclic, see `Graph::validate`.
+    pub fn implicit(
+        residual: &Handle,
+        unknown: &Rc<RefCell<InputNode>>,
+        tolerance: f32,
+        max_iterations: usize,
+    ) -> Rc<RefCell<Node>> {
+        let (residual, mut leaves) = Segment::compile(residual);
+        let guess = unknown.borrow().value();
+        // residual not depending on unknown can't be solved, it's kept last to produce `NaN`
+        let unknown = match leaves
+            .iter()
+            .position(|x| node_addr(x) == node_addr(unknown))
+        {
+            Some(i) => {
+                leaves.remove(i);
+                i
+            }
+            None => leaves.len(),
+        };
+        let op = Implicit {
+            residual,
+            unknown,
+            guess: AtomicU32::new(guess.to_bits()),
+            tolerance,
+            max_iterations,
+        };
+        Node::new(Operation::Custom(CustomOp(Arc::new(op)), leaves))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grad::Gradient, input::Input, node::Computable};
+
+    #[test]
+    fn solves_and_differentiates() {
+        // y^3 + a * y - b = 0
+        let y = InputNode::from_val(1.0f32);
+        let a = InputNode::from_val(2.0);
+        let b = InputNode::from_val(3.0);
+        let residual: Handle = Node::sub(
+            Node::add(Node::pow(y.clone(), 3.0), Node::mul(a.clone(), y.clone())),
+            b.clone(),
+        );
+        let root = Node::implicit(&residual, &y, 1e-5, 50);
+        assert_eq!(root.borrow().operands().len(), 2);
+        let solution = root.borrow_mut().compute();
+        assert!((solution - 1.0).abs() < 1e-5);
+
+        // dy/da = -y / (3y^2 + a), dy/db = 1 / (3y^2 + a)
+        let grad = Gradient::of(&(root.clone() as Handle));
+        assert!((grad.wrt(&a) + 0.2).abs() < 1e-4);
+        assert!((grad.wrt(&b) - 0.2).abs() < 1e-4);
+
+        b.borrow_mut().set(12.0);
+        assert!(root.borrow().is_dirty());
+        let solution = root.borrow_mut().compute();
+        assert!((solution - 2.0).abs() < 1e-4, "{}", solution);
+        assert_eq!(y.borrow().value(), 1.0);
+
+        let unsolvable: Handle = Node::add(Node::pow(y.clone(), 2.0), Node::constant(1.0));
+        let root = Node::implicit(&unsolvable, &y, 1e-6, 20);
+        assert!(root.borrow_mut().compute().is_nan());
+    }
+}
@@ -0,0 +1,233 @@
+//! Rendering of graphs as infix formulas, e.g. `x1 + x2 * sin(x2 + x3^3)`.
+//!
+//! Output follows syntax of `parse` module, so graphs without custom operations can be parsed
+//! back. Labeled operands are shown by their labels, see `Node::set_label`, unlabeled inputs
+//! by their current values and other unlabeled nodes without operation as `?`.
+
+use std::fmt;
+
+use crate::{
+    float::Float,
+    input::InputNode,
+    node::{Computable, Handle, Node},
+    ops::{Operation, Reduction},
+};
+
+/// Binding strength of rendered expressions, following grammar of `parse`.
+/// Operands binding weaker than their position requires are parenthesized.
+const SUM: u8 = 1;
+const PRODUCT: u8 = 2;
+const POWER: u8 = 3;
+const UNARY: u8 = 4;
+const ATOM: u8 = 5;
+
+/// Renders graph with given root as infix formula. Root is expanded even if labeled,
+/// root without operation is rendered like an operand.
+pub fn to_string_expr<T: Float>(root: &Handle<T>) -> String {
+    if root.borrow().operation().is_none() {
+        return operand(root).0;
+    }
+    expression(&*root.borrow()).0
+}
+
+impl<T: Float> fmt::Display for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&expression(self).0)
+    }
+}
+
+impl<T: Float> fmt::Display for InputNode<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.label() {
+            Some(label) => f.write_str(label),
+            None => f.write_str(&number(self.value()).0),
+        }
+    }
+}
+
+/// Renders node expanding its operation, returning text with its binding strength.
+fn expression<T: Float>(node: &dyn Computable<T>) -> (String, u8) {
+    let opp = match node.operation() {
+        Some(opp) => opp,
+        None => return ("?".into(), ATOM),
+    };
+    match opp {
+        Operation::Const(val) => number(*val),
+        Operation::Add(x, y) => infix(x, " + ", y, SUM),
+        Operation::AddVar(args) if args.len() > 1 => chain(args, " + ", SUM),
+        Operation::Sub(x, y) => infix(x, " - ", y, SUM),
+        Operation::Mul(x, y) => infix(x, " * ", y, PRODUCT),
+        Operation::MulVar(args) if args.len() > 1 => chain(args, " * ", PRODUCT),
+        Operation::Div(x, y, _) => infix(x, " / ", y, PRODUCT),
+        Operation::Neg(x) => (format!("-{}", wrap(operand(x), UNARY)), UNARY),
+        Operation::Pow(x, p) => (
+            format!("{}^{}", wrap(operand(x), POWER), number(*p).0),
+            POWER,
+        ),
+        Operation::AddVar(args) => call("sum", args, &[]),
+        Operation::MulVar(args) => call("prod", args, &[]),
+        Operation::Log(x, base) => call("log", [x], &[*base]),
+        Operation::Quantize(x, step, _) => call("quantize", [x], &[*step]),
+        Operation::Polynomial(x, coeffs) => call("polynomial", [x], coeffs),
+        Operation::Eq(x, y, tol) if *tol != T::ZERO => call("eq", [x, y], &[*tol]),
+        Operation::Softmax(args, index) => (
+            format!(
+                "softmax_component({}, {})",
+                index,
+                args.iter()
+                    .map(|x| operand(x).0)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ATOM,
+        ),
+        Operation::Piecewise(x, pieces, default) => {
+            let mut args = vec![operand(x).0];
+            for (breakpoint, piece) in pieces {
+                args.push(breakpoint.to_string());
+                args.push(operand(piece).0);
+            }
+            args.push(operand(default).0);
+            (format!("piecewise({})", args.join(", ")), ATOM)
+        }
+        Operation::Reduce(args, reduction) => {
+            let name = match reduction {
+                Reduction::Sum => "sum",
+                Reduction::Prod => "prod",
+                Reduction::Min => "min",
+                Reduction::Max => "max",
+                Reduction::Mean => "mean",
+            };
+            call(name, args, &[])
+        }
+        opp => call(opp.name(), opp.operands(), &[]),
+    }
+}
+
+/// Renders operand, shown by its label if it has one.
+fn operand<T: Float>(x: &Handle<T>) -> (String, u8) {
+    let node = match x.try_borrow() {
+        Ok(node) => node,
+        Err(_) => return ("?".into(), ATOM),
+    };
+    if let Some(label) = node.label() {
+        return (label.into(), ATOM);
+    }
+    if node.operation().is_none() && node.operands().is_empty() && !node.is_dirty() {
+        drop(node);
+        // leaves which aren't dirty are inputs returning stored value
+        if let Ok(mut leaf) = x.try_borrow_mut() {
+            return number(leaf.compute());
+        }
+        return ("?".into(), ATOM);
+    }
+    expression(&*node)
+}
+
+fn number<T: Float>(val: T) -> (String, u8) {
+    let prec = if val < T::ZERO { UNARY } else { ATOM };
+    (val.to_string(), prec)
+}
+
+/// Parenthesizes expression binding weaker than `min`.
+fn wrap((text, prec): (String, u8), min: u8) -> String {
+    if prec < min {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+/// Renders left-associative binary operator, right operand must bind stronger,
+/// so parsed result keeps the same structure.
+fn infix<T: Float>(x: &Handle<T>, op: &str, y: &Handle<T>, prec: u8) -> (String, u8) {
+    let x = wrap(operand(x), prec);
+    let y = wrap(operand(y), prec + 1);
+    (format!("{}{}{}", x, op, y), prec)
+}
+
+/// Renders variadic operation as chain of left-associative operators.
+fn chain<T: Float>(args: &[Handle<T>], op: &str, prec: u8) -> (String, u8) {
+    let terms: Vec<_> = args
+        .iter()
+        .enumerate()
+        .map(|(i, x)| wrap(operand(x), if i == 0 { prec } else { prec + 1 }))
+        .collect();
+    (terms.join(op), prec)
+}
+
+/// Renders function call with given operands followed by constant parameters.
+fn call<'a, T: Float>(
+    name: &str,
+    args: impl IntoIterator<Item = &'a Handle<T>>,
+    params: &[T],
+) -> (String, u8) {
+    let args: Vec<_> = args
+        .into_iter()
+        .map(|x| operand(x).0)
+        .chain(params.iter().map(|p| p.to_string()))
+        .collect();
+    (format!("{}({})", name, args.join(", ")), ATOM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graph::Graph, input::Input, parse::parse};
+
+    #[test]
+    fn formulas() {
+        let mut g = Graph::new();
+        let x1 = g.add_input("x1", 1.0f32);
+        let x2 = g.add_input("x2", 2.0);
+        let x3 = g.add_input("x3", 3.0);
+        let root: Handle = Node::add(
+            x1.clone(),
+            Node::mul(
+                x2.clone(),
+                Node::sin(Node::add(x2.clone(), Node::pow(x3.clone(), 3.0))),
+            ),
+        );
+        assert_eq!(to_string_expr(&root), "x1 + x2 * sin(x2 + x3^3)");
+
+        let nested: Handle = Node::sub(
+            x1.clone(),
+            Node::div(
+                Node::neg(Node::pow(x2.clone(), 2.0)),
+                Node::mul(x3.clone(), Node::constant(-0.5)),
+            ),
+        );
+        let text = to_string_expr(&nested);
+        assert_eq!(text, "x1 - -(x2^2) / (x3 * -0.5)");
+        let (parsed, inputs) = parse(&text).unwrap();
+        for (name, input) in [("x1", &x1), ("x2", &x2), ("x3", &x3)] {
+            inputs[name].borrow_mut().set(input.borrow().value());
+        }
+        assert_eq!(to_string_expr(&parsed), text);
+        assert_eq!(parsed.borrow_mut().compute(), nested.borrow_mut().compute());
+
+        let other: Handle = Node::add(
+            Node::reduce(vec![x1.clone(), x2.clone(), x3], Reduction::Max),
+            Node::mul(Node::log(x1, 2.0), Node::eq(x2, Node::input(4.0), 0.5)),
+        );
+        assert_eq!(
+            to_string_expr(&other),
+            "max(x1, x2, x3) + log(x1, 2) * eq(x2, 4, 0.5)"
+        );
+    }
+
+    #[test]
+    fn labels() {
+        let x = Node::input(2.0f32);
+        let inner = Node::mul(x.clone(), x.clone());
+        assert_eq!(inner.borrow().to_string(), "2 * 2");
+        x.borrow_mut().set_label("x");
+        assert_eq!(x.borrow().to_string(), "x");
+        inner.borrow_mut().set_label("square");
+        let outer = Node::add(inner.clone(), Node::constant(1.0));
+        assert_eq!(outer.borrow().to_string(), "square + 1");
+        // labeled root is still expanded
+        assert_eq!(inner.borrow().to_string(), "x * x");
+        assert_eq!(inner.borrow().label(), Some("square"));
+    }
+}
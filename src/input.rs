@@ -1,66 +1,544 @@
 //! Graph input node implementation.
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, error::Error, fmt, rc::Rc, time::Instant};
 
-use crate::node::Computable;
+use crate::{
+    analysis::Distribution,
+    error::GraphError,
+    eval::{Evaluation, Rng},
+    float::Float,
+    node::{
+        dependents, node_addr, remove_dependency, reset_dependencies, Computable, Dependencies,
+    },
+    reactive,
+    units::Unit,
+};
+
+/// Identifier of input node, valid while the node is alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputId(pub(crate) usize);
+
+impl InputId {
+    /// Returns identifier of given input node.
+    pub fn of<C: ?Sized>(node: &Rc<RefCell<C>>) -> Self {
+        Self(node_addr(node))
+    }
+}
 
 /// Trait definition for inputable types.
-pub trait Input: Computable {
+pub trait Input<T: Float = f32>: Computable<T> {
     /// Sets new input value.
-    fn set(&mut self, val: f32);
+    fn set(&mut self, val: T);
+
+    /// Sets new input value, failing if it violates rules of this input.
+    /// Same as `set` unless overridden.
+    fn try_set(&mut self, val: T) -> Result<(), ValidationError> {
+        self.set(val);
+        Ok(())
+    }
+
+    /// Sets new input value like `try_set`, returning calls of listeners instead of making
+    /// them, so they can be made once input is released and can compute its dependents.
+    /// Same as `try_set` unless overridden.
+    fn try_set_deferred(&mut self, val: T) -> Result<ListenerCalls, ValidationError> {
+        self.try_set(val)?;
+        Ok(Vec::new())
+    }
+
+    /// Sets value temporarily while probing the graph, e.g. by finite differences,
+    /// resetting dependent caches but bypassing rules, tolerance and listeners.
+    /// Same as `set` unless overridden.
+    fn probe(&mut self, val: T) {
+        self.set(val);
+    }
+
+    /// Returns false if value violates rules of this input.
+    fn accepts(&self, _val: T) -> bool {
+        true
+    }
+}
+
+/// Calls of input listeners postponed until input is released, see `Input::try_set_deferred`.
+pub type ListenerCalls = Vec<Box<dyn FnOnce()>>;
+
+/// Ends probing started at `original` by setting `val` through `Input::set`,
+/// so listeners see a single change. Value violating rules is probed instead.
+pub(crate) fn settle<T: Float, I: Input<T> + ?Sized>(input: &mut I, original: T, val: T) {
+    input.probe(original);
+    if input.accepts(val) {
+        input.set(val);
+    } else {
+        input.probe(val);
+    }
+}
+
+/// Setting values of shared inputs without touching `RefCell` directly, see `NodeHandle`.
+/// Implemented for inputs only, so derived nodes can't be set by mistake:
+///
+/// ```compile_fail
+/// use teza::{input::InputHandle, node::Node};
+///
+/// let x = Node::input(1.0f32);
+/// let y = Node::sin(x);
+/// y.set(2.0).unwrap();
+/// ```
+pub trait InputHandle<T: Float = f32> {
+    /// Sets new input value, failing if input is borrowed or value violates its rules,
+    /// see `Input::try_set`. Listeners are called once input is released, so they can
+    /// compute its dependents. Subscribed nodes are recomputed afterwards, see `reactive`.
+    fn set(&self, val: T) -> Result<(), GraphError>;
+}
+
+impl<T: Float, C: Input<T> + ?Sized> InputHandle<T> for Rc<RefCell<C>> {
+    fn set(&self, val: T) -> Result<(), GraphError> {
+        let mut input = self
+            .try_borrow_mut()
+            .map_err(|_| GraphError::BorrowConflict { operation: "input" })?;
+        let calls = input.try_set_deferred(val)?;
+        drop(input);
+        calls.into_iter().for_each(|call| call());
+        reactive::flush();
+        Ok(())
+    }
+}
+
+/// External source of input values, such as a sensor or a feed, see `Graph::bind_input`.
+pub trait InputSource<T: Float = f32> {
+    /// Returns new value if one arrived since the last poll.
+    fn poll(&mut self) -> Option<T>;
+}
+
+impl<T: Float, F: FnMut() -> Option<T>> InputSource<T> for F {
+    fn poll(&mut self) -> Option<T> {
+        self()
+    }
+}
+
+/// Rule values accepted by input must satisfy.
+#[derive(Clone)]
+pub enum Rule<T: Float = f32> {
+    /// Value must be within inclusive range.
+    Range(T, T),
+    /// Value must be neither `NaN` nor infinite.
+    Finite,
+    /// Value must satisfy predicate, described by given name.
+    Custom(&'static str, Rc<dyn Fn(T) -> bool>),
 }
 
+impl<T: Float> Rule<T> {
+    /// Returns true if value satisfies this rule.
+    pub fn accepts(&self, val: T) -> bool {
+        match self {
+            Rule::Range(min, max) => *min <= val && val <= *max,
+            Rule::Finite => val.is_finite(),
+            Rule::Custom(_, predicate) => predicate(val),
+        }
+    }
+}
+
+impl<T: Float> fmt::Display for Rule<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::Range(min, max) => write!(f, "range [{}, {}]", min, max),
+            Rule::Finite => write!(f, "finiteness"),
+            Rule::Custom(name, _) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Error of setting input to value violating one of its rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// Rejected value.
+    pub value: f64,
+    /// Description of violated rule.
+    pub rule: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} violates {} of input", self.value, self.rule)
+    }
+}
+
+impl Error for ValidationError {}
+
 /// Graph input node implementation.
 #[derive(Clone)]
-pub struct InputNode {
-    val: f32,
+pub struct InputNode<T: Float = f32> {
+    val: T,
+    /// Value input is restored to by `reset`.
+    default: T,
     /// Holds references to nodes that depend from this node.
-    dependencies: Vec<Rc<RefCell<dyn Computable>>>,
+    dependencies: Dependencies<T>,
+    /// Rules new values are checked against.
+    rules: Vec<Rule<T>>,
+    /// Callbacks receiving previous and new value on each accepted set.
+    listeners: Vec<Rc<dyn Fn(T, T)>>,
+    /// Name shown when input is displayed.
+    label: Option<String>,
+    /// Derivatives accumulated by `Gradient::accumulate` since the last `zero_grad`.
+    grad: T,
+    /// Maximal difference from current value of new values which are ignored,
+    /// `None` if every set counts.
+    tolerance: Option<T>,
+    /// Physical unit of value, if tagged.
+    unit: Option<Unit>,
 }
 
-impl InputNode {
-    pub fn from_val(val: f32) -> Rc<RefCell<Self>> {
+impl<T: Float> InputNode<T> {
+    pub fn from_val(val: T) -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(Self {
             val,
-            dependencies: Vec::default(),
+            default: val,
+            dependencies: Dependencies::default(),
+            rules: Vec::new(),
+            listeners: Vec::new(),
+            label: None,
+            grad: T::ZERO,
+            tolerance: Some(T::ZERO),
+            unit: None,
         }))
     }
+
+    /// Returns current value.
+    pub fn value(&self) -> T {
+        self.val
+    }
+
+    /// Returns sum of derivatives accumulated since the last `zero_grad`.
+    pub fn grad(&self) -> T {
+        self.grad
+    }
+
+    /// Adds derivative to accumulated gradient.
+    pub fn accumulate_grad(&mut self, d: T) {
+        self.grad = self.grad + d;
+    }
+
+    /// Clears accumulated gradient, value of input is kept.
+    pub fn zero_grad(&mut self) {
+        self.grad = T::ZERO;
+    }
+
+    /// Returns label of this input.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Sets label shown when input is displayed.
+    pub fn set_label(&mut self, label: impl Into<String>) {
+        self.label = Some(label.into());
+    }
+
+    /// Tags input with physical unit, checked by operations over it, see `units`.
+    pub fn set_unit(&mut self, unit: Unit) {
+        self.unit = Some(unit);
+    }
+
+    /// Creates input with given value, labeled with given name.
+    pub fn with_label(val: T, label: impl Into<String>) -> Rc<RefCell<Self>> {
+        let input = Self::from_val(val);
+        input.borrow_mut().label = Some(label.into());
+        input
+    }
+
+    /// Returns value input is restored to by `reset`, initial value unless changed.
+    pub fn default_val(&self) -> T {
+        self.default
+    }
+
+    /// Changes value input is restored to by `reset`, keeping current value.
+    pub fn set_default(&mut self, val: T) {
+        self.default = val;
+    }
+
+    /// Skips new values within given difference from current value, keeping current
+    /// value and dependent caches. By default, only setting exactly the same value is skipped,
+    /// unless input counts every set, see `Computable::count_every_set`.
+    pub fn set_tolerance(&mut self, tolerance: T) {
+        self.tolerance = Some(tolerance);
+    }
+
+    /// Restores default value, resetting dependent caches only if value changes.
+    /// Default is restored exactly, regardless of tolerance.
+    pub fn reset(&mut self) {
+        if self.val != self.default {
+            if let Err(err) = self.validate(self.default) {
+                panic!("{}", err);
+            }
+            let old = std::mem::replace(&mut self.val, self.default);
+            self.notify(Some(old));
+        }
+    }
+
+    /// Adds rule checked by subsequent `set` and `try_set` calls.
+    pub fn add_rule(&mut self, rule: Rule<T>) {
+        self.rules.push(rule);
+    }
+
+    /// Registers callback called with previous and new value on each accepted set.
+    pub fn on_set(&mut self, callback: impl Fn(T, T) + 'static) {
+        self.listeners.push(Rc::new(callback));
+    }
+
+    /// Checks value against rules of this input.
+    pub fn validate(&self, val: T) -> Result<(), ValidationError> {
+        match self.rules.iter().find(|rule| !rule.accepts(val)) {
+            Some(rule) => Err(ValidationError {
+                value: val.to_f64(),
+                rule: rule.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Sets new value if it satisfies all rules, leaving input and its dependents
+    /// untouched otherwise. Value within tolerance of the current one, see `set_tolerance`,
+    /// is ignored, so dependent caches are kept.
+    pub fn try_set(&mut self, val: T) -> Result<(), ValidationError> {
+        self.validate(val)?;
+        let old = self.replace(val);
+        self.notify(old);
+        Ok(())
+    }
+
+    /// Stores new value without resetting dependent caches, returning the previous one,
+    /// or `None` if value is within tolerance of current one and is ignored.
+    pub(crate) fn replace(&mut self, val: T) -> Option<T> {
+        if let Some(tolerance) = self.tolerance {
+            if val == self.val || (val - self.val).abs() <= tolerance {
+                return None;
+            }
+        }
+        Some(std::mem::replace(&mut self.val, val))
+    }
+
+    /// Calls listeners after set, resetting dependent caches if value changed from `old`.
+    pub(crate) fn notify(&mut self, old: Option<T>) {
+        self.invalidate(old).into_iter().for_each(|call| call());
+    }
+
+    /// Resets dependent caches if value changed from `old`, returning calls of listeners
+    /// to make after set.
+    pub(crate) fn invalidate(&mut self, old: Option<T>) -> ListenerCalls {
+        let val = self.val;
+        if old.is_some() {
+            reset_dependencies(&mut self.dependencies);
+        }
+        let old = old.unwrap_or(val);
+        self.listeners
+            .iter()
+            .map(|l| {
+                let l = l.clone();
+                Box::new(move || l(old, val)) as Box<dyn FnOnce()>
+            })
+            .collect()
+    }
 }
 
-impl Computable for InputNode {
+impl<T: Float> Computable<T> for InputNode<T> {
     /// Just returns stored value.
-    fn compute(&mut self) -> f32 {
+    fn compute(&mut self) -> T {
         self.val
     }
 
     /// Adds dependency from another `Computable` object.
-    fn add_dependency(&mut self, dependency: Rc<RefCell<dyn Computable>>) {
-        self.dependencies.push(dependency)
+    fn add_dependency(&mut self, dependency: Rc<RefCell<dyn Computable<T>>>) {
+        self.dependencies.push(Rc::downgrade(&dependency))
+    }
+
+    fn remove_dependency(&mut self, dependency: &Rc<RefCell<dyn Computable<T>>>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Rc<RefCell<dyn Computable<T>>>> {
+        dependents(&self.dependencies)
     }
 
     /// Doesn't have cache, so doing nothing.
     fn reset_cache(&mut self) {}
+
+    fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    fn unit(&self) -> Option<Unit> {
+        self.unit
+    }
+
+    /// Makes every set reset dependent caches, even of unchanged value, for inputs feeding
+    /// nodes which count recomputations, like `StatsNode`.
+    fn count_every_set(&mut self) {
+        self.tolerance = None;
+    }
 }
 
-impl Input for InputNode {
+impl<T: Float> Input<T> for InputNode<T> {
     /// Sets new input value to node and resets cache for all dependable nodes.
-    fn set(&mut self, val: f32) {
-        self.val = val;
-        self.dependencies
-            .iter()
-            .for_each(|d| d.borrow_mut().reset_cache())
+    /// Panics if value violates rules of this input, see `try_set`.
+    fn set(&mut self, val: T) {
+        if let Err(err) = self.try_set(val) {
+            panic!("{}", err);
+        }
+    }
+
+    fn try_set(&mut self, val: T) -> Result<(), ValidationError> {
+        InputNode::try_set(self, val)
+    }
+
+    fn try_set_deferred(&mut self, val: T) -> Result<ListenerCalls, ValidationError> {
+        self.validate(val)?;
+        let old = self.replace(val);
+        Ok(self.invalidate(old))
+    }
+
+    fn probe(&mut self, val: T) {
+        if self.val != val {
+            self.val = val;
+            reset_dependencies(&mut self.dependencies);
+        }
+    }
+
+    fn accepts(&self, val: T) -> bool {
+        self.validate(val).is_ok()
+    }
+}
+
+/// Volatile source of random values drawn from distribution on each computation.
+/// Node stays dirty, so dependents recompute it instead of caching, see `CachePolicy`.
+pub struct RandNode<T: Float = f32> {
+    distribution: Distribution,
+    rng: Rng,
+    /// Holds references to nodes that depend from this node.
+    dependencies: Dependencies<T>,
+}
+
+impl<T: Float> RandNode<T> {
+    /// Creates source drawing from given distribution with generator seeded by `seed`.
+    pub fn new(distribution: Distribution, seed: u64) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            distribution,
+            rng: Rng::new(seed),
+            dependencies: Dependencies::default(),
+        }))
+    }
+
+    /// Restarts sequence of drawn values from given seed, resetting dependents.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+        reset_dependencies(&mut self.dependencies);
+    }
+}
+
+impl<T: Float> Computable<T> for RandNode<T> {
+    /// Draws next value.
+    fn compute(&mut self) -> T {
+        // shifted by half of the resolution into `(0, 1)`, where quantiles are finite
+        let u = ((self.rng.next_u64() >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+        T::from_f64(self.distribution.quantile(u) as f64)
+    }
+
+    fn add_dependency(&mut self, dependency: Rc<RefCell<dyn Computable<T>>>) {
+        self.dependencies.push(Rc::downgrade(&dependency))
+    }
+
+    fn remove_dependency(&mut self, dependency: &Rc<RefCell<dyn Computable<T>>>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Rc<RefCell<dyn Computable<T>>>> {
+        dependents(&self.dependencies)
+    }
+
+    /// Doesn't have cache, so doing nothing.
+    fn reset_cache(&mut self) {}
+
+    fn is_dirty(&self) -> bool {
+        true
+    }
+}
+
+/// Volatile source of seconds elapsed since its origin, read on each computation.
+/// Checked evaluation reads clock of evaluation, see `Evaluation::clock`, so tests can
+/// control it. Unlike simulation time of `TimeNode`, it follows real time.
+pub struct ClockNode<T: Float = f32> {
+    origin: Instant,
+    /// Holds references to nodes that depend from this node.
+    dependencies: Dependencies<T>,
+}
+
+impl<T: Float> ClockNode<T> {
+    /// Creates clock counting from now.
+    pub fn new() -> Rc<RefCell<Self>> {
+        Self::since(Instant::now())
+    }
+
+    /// Creates clock counting from given origin.
+    pub fn since(origin: Instant) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            origin,
+            dependencies: Dependencies::default(),
+        }))
+    }
+
+    /// Returns signed amount of seconds from origin to given moment.
+    fn elapsed(&self, now: Instant) -> T {
+        match now.checked_duration_since(self.origin) {
+            Some(elapsed) => T::from_f64(elapsed.as_secs_f64()),
+            None => -T::from_f64(self.origin.duration_since(now).as_secs_f64()),
+        }
+    }
+}
+
+impl<T: Float> Computable<T> for ClockNode<T> {
+    fn compute(&mut self) -> T {
+        self.elapsed(Instant::now())
+    }
+
+    fn add_dependency(&mut self, dependency: Rc<RefCell<dyn Computable<T>>>) {
+        self.dependencies.push(Rc::downgrade(&dependency))
+    }
+
+    fn remove_dependency(&mut self, dependency: &Rc<RefCell<dyn Computable<T>>>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Rc<RefCell<dyn Computable<T>>>> {
+        dependents(&self.dependencies)
+    }
+
+    /// Doesn't have cache, so doing nothing.
+    fn reset_cache(&mut self) {}
+
+    fn is_dirty(&self) -> bool {
+        true
+    }
+
+    fn try_compute(&mut self, eval: &mut Evaluation) -> Result<T, GraphError> {
+        Ok(self.elapsed(eval.now()))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use super::*;
+    use crate::node::Node;
 
     #[test]
     fn set_compute() {
         let mut x = InputNode {
             val: 42.0,
-            dependencies: Vec::default(),
+            default: 42.0,
+            dependencies: Dependencies::default(),
+            rules: Vec::new(),
+            listeners: Vec::new(),
+            label: None,
+            grad: 0.0,
+            tolerance: None,
+            unit: None,
         };
         assert_eq!(x.compute(), 42.0);
 
@@ -89,7 +567,14 @@ mod tests {
         let cached = Rc::new(RefCell::new(S { cache: Some(1.0) }));
         let mut x = InputNode {
             val: 42.0,
-            dependencies: Vec::default(),
+            default: 42.0,
+            dependencies: Dependencies::default(),
+            rules: Vec::new(),
+            listeners: Vec::new(),
+            label: None,
+            grad: 0.0,
+            tolerance: None,
+            unit: None,
         };
 
         x.add_dependency(cached.clone());
@@ -100,4 +585,168 @@ mod tests {
         assert!(!x.dependencies.is_empty());
         assert_eq!(cached.borrow().cache, None);
     }
+
+    #[test]
+    fn validation() {
+        let x = InputNode::from_val(0.5);
+        let y = Node::add(x.clone(), Node::constant(1.0));
+        assert_eq!(y.borrow_mut().compute(), 1.5);
+
+        x.borrow_mut().add_rule(Rule::Finite);
+        x.borrow_mut().add_rule(Rule::Range(0.0, 1.0));
+        x.borrow_mut()
+            .add_rule(Rule::Custom("non-zero", Rc::new(|v| v != 0.0)));
+
+        assert_eq!(
+            x.borrow_mut().try_set(2.0),
+            Err(ValidationError {
+                value: 2.0,
+                rule: "range [0, 1]".to_string()
+            })
+        );
+        assert_eq!(
+            x.borrow_mut().try_set(f32::NAN).unwrap_err().rule,
+            "finiteness"
+        );
+        assert_eq!(
+            x.borrow_mut().try_set(0.0).unwrap_err().to_string(),
+            "0 violates non-zero of input"
+        );
+        assert_eq!(y.borrow_mut().compute(), 1.5);
+
+        assert_eq!(x.borrow_mut().try_set(1.0), Ok(()));
+        assert_eq!(y.borrow_mut().compute(), 2.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "violates range")]
+    fn set_invalid() {
+        let x = InputNode::from_val(0.5);
+        x.borrow_mut().add_rule(Rule::Range(0.0, 1.0));
+        x.borrow_mut().set(-1.0);
+    }
+
+    #[test]
+    fn reset_default() {
+        let x = InputNode::from_val(1.0);
+        let y = Node::mul(x.clone(), Node::constant(2.0));
+        x.borrow_mut().set(3.0);
+        assert_eq!(y.borrow_mut().compute(), 6.0);
+
+        x.borrow_mut().reset();
+        assert_eq!(y.borrow_mut().compute(), 2.0);
+
+        x.borrow_mut().set_default(5.0);
+        assert_eq!(x.borrow().default_val(), 5.0);
+        assert_eq!(y.borrow_mut().compute(), 2.0);
+        x.borrow_mut().reset();
+        assert_eq!(y.borrow_mut().compute(), 10.0);
+    }
+
+    #[test]
+    fn unchanged_value() {
+        let x = InputNode::from_val(1.0);
+        let y = Node::mul(x.clone(), Node::constant(2.0));
+        assert_eq!(y.borrow_mut().compute(), 2.0);
+        x.borrow_mut().set(1.0);
+        assert!(!y.borrow().is_dirty());
+
+        x.borrow_mut().count_every_set();
+        x.borrow_mut().set(1.0);
+        assert!(y.borrow().is_dirty());
+        assert_eq!(y.borrow_mut().compute(), 2.0);
+        x.borrow_mut().set_tolerance(0.1);
+        x.borrow_mut().set(1.05);
+        assert!(!y.borrow().is_dirty());
+        assert_eq!(x.borrow().value(), 1.0);
+        x.borrow_mut().set(1.2);
+        assert_eq!(y.borrow_mut().compute(), 2.4);
+
+        x.borrow_mut().set_default(1.25);
+        x.borrow_mut().reset();
+        assert_eq!(y.borrow_mut().compute(), 2.5);
+    }
+
+    #[test]
+    fn on_set() {
+        let x = InputNode::from_val(1.0);
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let sink = log.clone();
+        x.borrow_mut()
+            .on_set(move |old, new| sink.borrow_mut().push((old, new)));
+        x.borrow_mut().add_rule(Rule::Finite);
+
+        x.borrow_mut().set(2.0);
+        assert!(x.borrow_mut().try_set(f32::INFINITY).is_err());
+        x.borrow_mut().set(2.0);
+        x.borrow_mut().reset();
+        assert_eq!(*log.borrow(), vec![(1.0, 2.0), (2.0, 2.0), (2.0, 1.0)]);
+    }
+
+    #[test]
+    fn listener_computes_dependent() {
+        use crate::node::NodeHandle;
+
+        let x = InputNode::from_val(1.0f32);
+        let y = Node::mul(x.clone(), Node::constant(3.0));
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let (sink, dependent) = (seen.clone(), y.clone());
+        x.borrow_mut()
+            .on_set(move |_, _| sink.borrow_mut().push(dependent.compute().unwrap()));
+        InputHandle::set(&x, 4.0).unwrap();
+
+        let mut g = crate::graph::Graph::new();
+        g.add_input("x", 0.0);
+        let x = g.input("x").unwrap().clone();
+        let (sink, dependent) = (seen.clone(), Node::neg(x.clone()));
+        x.borrow_mut()
+            .on_set(move |_, _| sink.borrow_mut().push(dependent.compute().unwrap()));
+        g.set_many(&[("x", 2.0)]).unwrap();
+        assert_eq!(*seen.borrow(), vec![12.0, -2.0]);
+        assert_eq!(y.borrow_mut().compute(), 12.0);
+    }
+
+    #[test]
+    fn random_source() {
+        let r = RandNode::new(
+            Distribution::Uniform {
+                low: 2.0,
+                high: 4.0,
+            },
+            7,
+        );
+        let y = Node::mul(r.clone(), Node::constant(10.0f32));
+        let first: Vec<f32> = (0..100).map(|_| y.borrow_mut().compute()).collect();
+        assert!(first.iter().all(|v| (20.0..40.0).contains(v)));
+        assert_ne!(first[0], first[1]);
+        assert!(y.borrow().is_dirty());
+
+        r.borrow_mut().reseed(7);
+        let again: Vec<f32> = (0..100).map(|_| y.borrow_mut().compute()).collect();
+        assert_eq!(again, first);
+
+        let normal = RandNode::<f64>::new(
+            Distribution::Normal {
+                mean: 1.0,
+                std_dev: 2.0,
+            },
+            1,
+        );
+        let mean = (0..10000)
+            .map(|_| normal.borrow_mut().compute())
+            .sum::<f64>()
+            / 10000.0;
+        assert!((mean - 1.0).abs() < 0.1, "{}", mean);
+    }
+
+    #[test]
+    fn clock_source() {
+        let origin = Instant::now();
+        let t = ClockNode::since(origin);
+        let y = Node::add(t, Node::constant(1.0f64));
+        let mut eval = Evaluation::new().clock(move || origin + Duration::from_millis(1500));
+        assert_eq!(y.borrow_mut().try_compute(&mut eval), Ok(2.5));
+        assert!(y.borrow().is_dirty());
+        assert!(y.borrow_mut().compute() >= 1.0);
+    }
 }
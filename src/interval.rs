@@ -0,0 +1,517 @@
+//! Interval evaluation, propagating ranges of inputs to bounds of outputs.
+//!
+//! Each operation maps ranges of its operands to a range containing all of its results,
+//! so the output range bounds the output for any input values within their ranges.
+//! Bounds are exact up to rounding of their endpoints. Operations without known interval
+//! extension, like user-defined ones, and operands outside of domains, like logarithm
+//! of a range reaching below zero, give the entire real line.
+
+use std::{
+    collections::HashMap,
+    f64::consts::PI,
+    fmt,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+use crate::{
+    float::Float,
+    input::InputId,
+    node::{node_addr, post_order, Handle, Node},
+    ops::{Operation, Reduction},
+};
+
+/// Closed range of values `[lo, hi]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval<T: Float = f32> {
+    /// Lower bound.
+    pub lo: T,
+    /// Upper bound.
+    pub hi: T,
+}
+
+impl<T: Float> Interval<T> {
+    /// Creates interval between given bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lo` is greater than `hi` or bound is `NaN`.
+    pub fn new(lo: T, hi: T) -> Self {
+        assert!(lo <= hi, "interval bounds aren't ordered");
+        Self { lo, hi }
+    }
+
+    /// Creates interval containing single value.
+    pub fn point(val: T) -> Self {
+        Self { lo: val, hi: val }
+    }
+
+    /// Returns interval of all values.
+    pub fn entire() -> Self {
+        Self {
+            lo: T::NEG_INFINITY,
+            hi: -T::NEG_INFINITY,
+        }
+    }
+
+    /// Returns true if value lies within interval.
+    pub fn contains(&self, val: T) -> bool {
+        self.lo <= val && val <= self.hi
+    }
+
+    /// Returns `hi - lo`.
+    pub fn width(&self) -> T {
+        self.hi - self.lo
+    }
+
+    /// Returns smallest interval containing both intervals.
+    pub fn hull(&self, other: &Self) -> Self {
+        Self {
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        }
+    }
+
+    /// Returns interval of smallest and largest of given values, entire one if some is `NaN`.
+    fn span(vals: &[T]) -> Self {
+        if vals.iter().any(|x| x.to_f64().is_nan()) {
+            return Self::entire();
+        }
+        let lo = vals.iter().fold(-T::NEG_INFINITY, |acc, x| acc.min(*x));
+        let hi = vals.iter().fold(T::NEG_INFINITY, |acc, x| acc.max(*x));
+        Self { lo, hi }
+    }
+
+    /// Applies non-decreasing function to bounds.
+    fn increasing(&self, f: impl Fn(T) -> T) -> Self {
+        Self::span(&[f(self.lo), f(self.hi)])
+    }
+
+    /// Applies non-increasing function to bounds.
+    fn decreasing(&self, f: impl Fn(T) -> T) -> Self {
+        Self::span(&[f(self.hi), f(self.lo)])
+    }
+
+    /// Returns interval of `x^pow`.
+    fn pow(&self, pow: T) -> Self {
+        let f = |x: T| x.powf(pow);
+        let integer = pow.fract() == T::ZERO;
+        let even = integer && (pow / T::from_f64(2.0)).fract() == T::ZERO;
+        if self.lo < T::ZERO && !integer {
+            return Self::entire();
+        }
+        if pow < T::ZERO && self.contains(T::ZERO) {
+            return Self::entire();
+        }
+        if even && self.contains(T::ZERO) {
+            // even power has its minimum at zero
+            return Self::span(&[f(T::ZERO), f(self.lo), f(self.hi)]);
+        }
+        Self::span(&[f(self.lo), f(self.hi)])
+    }
+
+    /// Returns interval of `|x|`.
+    fn abs(&self) -> Self {
+        if self.contains(T::ZERO) {
+            Self::span(&[T::ZERO, self.lo.abs(), self.hi.abs()])
+        } else {
+            Self::span(&[self.lo.abs(), self.hi.abs()])
+        }
+    }
+
+    /// Returns interval of `sin(x + shift)`.
+    fn sin(&self, shift: f64) -> Self {
+        let (lo, hi) = (self.lo.to_f64() + shift, self.hi.to_f64() + shift);
+        if hi - lo >= 2.0 * PI {
+            return Self::new(-T::ONE, T::ONE);
+        }
+        // whether some `offset + 2kπ` lies within the range
+        let reaches = |offset: f64| ((lo - offset) / (2.0 * PI)).ceil() * 2.0 * PI + offset <= hi;
+        let ends = Self::span(&[T::from_f64(lo.sin()), T::from_f64(hi.sin())]);
+        Self {
+            lo: if reaches(-PI / 2.0) { -T::ONE } else { ends.lo },
+            hi: if reaches(PI / 2.0) { T::ONE } else { ends.hi },
+        }
+    }
+
+    /// Returns interval of truth value of `x < y`, or `x <= y` if `or_equal`.
+    fn less(&self, other: &Self, or_equal: bool) -> Self {
+        let always = if or_equal {
+            self.hi <= other.lo
+        } else {
+            self.hi < other.lo
+        };
+        let never = if or_equal {
+            self.lo > other.hi
+        } else {
+            self.lo >= other.hi
+        };
+        match (always, never) {
+            (true, _) => Self::point(T::ONE),
+            (_, true) => Self::point(T::ZERO),
+            _ => Self::new(T::ZERO, T::ONE),
+        }
+    }
+}
+
+impl<T: Float> Add for Interval<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::span(&[self.lo + other.lo, self.hi + other.hi])
+    }
+}
+
+impl<T: Float> Sub for Interval<T> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::span(&[self.lo - other.hi, self.hi - other.lo])
+    }
+}
+
+impl<T: Float> Mul for Interval<T> {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::span(&[
+            self.lo * other.lo,
+            self.lo * other.hi,
+            self.hi * other.lo,
+            self.hi * other.hi,
+        ])
+    }
+}
+
+impl<T: Float> Div for Interval<T> {
+    type Output = Self;
+
+    /// Divides intervals, giving the entire real line if divisor contains zero.
+    fn div(self, other: Self) -> Self {
+        if other.contains(T::ZERO) {
+            return Self::entire();
+        }
+        self * Self::span(&[T::ONE / other.lo, T::ONE / other.hi])
+    }
+}
+
+impl<T: Float> Neg for Interval<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            lo: -self.hi,
+            hi: -self.lo,
+        }
+    }
+}
+
+impl<T: Float> fmt::Display for Interval<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+/// Returns interval of operation result from intervals of its operands.
+fn extend<T: Float>(
+    opp: &Operation<Handle<T>, T>,
+    get: impl Fn(&Handle<T>) -> Interval<T>,
+) -> Interval<T> {
+    use Operation::*;
+
+    // result of unary operation for given operand value
+    let at = |v: T| opp.eval(|_| v);
+    let all = |args: &[Handle<T>]| -> Vec<Interval<T>> { args.iter().map(&get).collect() };
+    let sum = |args: Vec<Interval<T>>| {
+        args.into_iter()
+            .fold(Interval::point(T::ZERO), |acc, x| acc + x)
+    };
+    let product = |args: Vec<Interval<T>>| {
+        args.into_iter()
+            .fold(Interval::point(T::ONE), |acc, x| acc * x)
+    };
+    let min = |x: Interval<T>, y: Interval<T>| Interval::span(&[x.lo.min(y.lo), x.hi.min(y.hi)]);
+    let max = |x: Interval<T>, y: Interval<T>| Interval::span(&[x.lo.max(y.lo), x.hi.max(y.hi)]);
+    match opp {
+        Const(val) => Interval::point(*val),
+        Add(x, y) => get(x) + get(y),
+        AddVar(args) => sum(all(args)),
+        Sub(x, y) => get(x) - get(y),
+        Mul(x, y) => get(x) * get(y),
+        MulVar(args) => product(all(args)),
+        Div(x, y, _) => get(x) / get(y),
+        Neg(x) => -get(x),
+        Recip(x) => Interval::point(T::ONE) / get(x),
+        Pow(x, pow) => get(x).pow(*pow),
+        Polynomial(x, coeffs) => {
+            let x = get(x);
+            coeffs
+                .iter()
+                .rev()
+                .fold(Interval::point(T::ZERO), |acc, c| {
+                    acc * x + Interval::point(*c)
+                })
+        }
+        Sin(x) => get(x).sin(0.0),
+        Cos(x) => get(x).sin(PI / 2.0),
+        Tan(x) => {
+            let x = get(x);
+            // poles at `π/2 + kπ`
+            let (lo, hi) = (x.lo.to_f64(), x.hi.to_f64());
+            if ((lo - PI / 2.0) / PI).ceil() * PI + PI / 2.0 <= hi || hi - lo >= PI {
+                Interval::entire()
+            } else {
+                x.increasing(at)
+            }
+        }
+        Asin(x) | Acos(x) if get(x).lo < -T::ONE || get(x).hi > T::ONE => Interval::entire(),
+        Ln(x) | Log(x, _) | Sqrt(x) if get(x).lo < T::ZERO => Interval::entire(),
+        Ln1p(x) if get(x).lo < -T::ONE => Interval::entire(),
+        Log(x, base) if *base < T::ONE => get(x).decreasing(at),
+        Detach(x)
+        | Asin(x)
+        | Atan(x)
+        | Exp(x)
+        | Ln(x)
+        | Log(x, _)
+        | Sqrt(x)
+        | Tanh(x)
+        | Sigmoid(x)
+        | Relu(x)
+        | Softplus(x)
+        | ExpM1(x)
+        | Ln1p(x)
+        | Erf(x)
+        | NormalCdf(x)
+        | Sign(x)
+        | Quantize(x, ..) => get(x).increasing(at),
+        Acos(x) | Erfc(x) => get(x).decreasing(at),
+        NormalPdf(x) => get(x).abs().decreasing(at),
+        Abs(x) => get(x).abs(),
+        // increasing between integers, anywhere within `(-1, 1)` with sign of value otherwise
+        Fract(x) => {
+            let x = get(x);
+            let trunc = |v: T| v - v.fract();
+            if trunc(x.lo) == trunc(x.hi) && (x.lo >= T::ZERO || x.hi <= T::ZERO) {
+                x.increasing(at)
+            } else {
+                Interval::new(
+                    x.lo.max(-T::ONE).min(T::ZERO),
+                    x.hi.min(T::ONE).max(T::ZERO),
+                )
+            }
+        }
+        Min(x, y) => min(get(x), get(y)),
+        Max(x, y) => max(get(x), get(y)),
+        Clamp(x, lo, hi) => min(max(get(x), get(lo)), get(hi)),
+        Lt(x, y) => get(x).less(&get(y), false),
+        Le(x, y) => get(x).less(&get(y), true),
+        Gt(x, y) => get(y).less(&get(x), false),
+        Ge(x, y) => get(y).less(&get(x), true),
+        Eq(x, y, tolerance) => {
+            let diff = (get(x) - get(y)).abs();
+            if diff.lo > *tolerance {
+                Interval::point(T::ZERO)
+            } else if diff.hi <= *tolerance {
+                Interval::point(T::ONE)
+            } else {
+                Interval::new(T::ZERO, T::ONE)
+            }
+        }
+        Hypot(x, y) => {
+            (get(x).pow(T::from_f64(2.0)) + get(y).pow(T::from_f64(2.0))).increasing(|v| v.sqrt())
+        }
+        // whole range unless signs of coordinates pin the quadrant
+        Atan2(y, x) => {
+            let (y, x) = (get(y), get(x));
+            let pi = T::from_f64(std::f64::consts::PI);
+            if x.lo > T::ZERO || y.lo > T::ZERO || y.hi < T::ZERO {
+                let angle = |y: T, x: T| y.atan2(x);
+                Interval::span(&[
+                    angle(y.lo, x.lo),
+                    angle(y.lo, x.hi),
+                    angle(y.hi, x.lo),
+                    angle(y.hi, x.hi),
+                ])
+            } else {
+                Interval::new(-pi, pi)
+            }
+        }
+        // smaller than divisor in magnitude, with sign of dividend
+        Rem(x, y) => {
+            let (x, y) = (get(x), get(y));
+            if y.contains(T::ZERO) {
+                Interval::entire()
+            } else {
+                let limit = y.lo.abs().max(y.hi.abs());
+                Interval::new(x.lo.max(-limit).min(T::ZERO), x.hi.min(limit).max(T::ZERO))
+            }
+        }
+        Norm(args) => sum(all(args).iter().map(|x| x.pow(T::from_f64(2.0))).collect())
+            .increasing(|v| v.sqrt()),
+        LogSumExp(args) => {
+            let args = all(args);
+            let lse = |vals: Vec<T>| opp.eval(|x| vals[args_index(opp, x)]);
+            Interval::span(&[
+                lse(args.iter().map(|x| x.lo).collect()),
+                lse(args.iter().map(|x| x.hi).collect()),
+            ])
+        }
+        Softmax(args, i) => {
+            // component grows with its own value and shrinks with the others
+            let args = all(args);
+            let bound = |own: bool| {
+                let vals: Vec<T> = args
+                    .iter()
+                    .enumerate()
+                    .map(|(j, x)| if (j == *i) == own { x.hi } else { x.lo })
+                    .collect();
+                opp.eval(|x| vals[args_index(opp, x)])
+            };
+            Interval::span(&[bound(false), bound(true)])
+        }
+        Reduce(args, reduction) => {
+            let args = all(args);
+            let n = T::from_f64(args.len() as f64);
+            match reduction {
+                Reduction::Sum => sum(args),
+                Reduction::Mean => sum(args) / Interval::point(n),
+                Reduction::Prod => product(args),
+                Reduction::Min => args
+                    .into_iter()
+                    .reduce(min)
+                    .unwrap_or_else(Interval::entire),
+                Reduction::Max => args
+                    .into_iter()
+                    .reduce(max)
+                    .unwrap_or_else(Interval::entire),
+            }
+        }
+        Select(cond, x, y) => {
+            let cond = get(cond);
+            if cond.lo > T::ZERO {
+                get(x)
+            } else if cond.hi <= T::ZERO {
+                get(y)
+            } else {
+                get(x).hull(&get(y))
+            }
+        }
+        Piecewise(x, pieces, last) => {
+            // pieces whose regions `[previous breakpoint, breakpoint)` overlap the range
+            let x = get(x);
+            let mut res: Option<Interval<T>> = None;
+            let mut start = T::NEG_INFINITY;
+            for (breakpoint, piece) in pieces
+                .iter()
+                .chain(std::iter::once(&(-T::NEG_INFINITY, last.clone())))
+            {
+                if x.lo < *breakpoint && x.hi >= start {
+                    let val = get(piece);
+                    res = Some(res.map_or(val, |res| res.hull(&val)));
+                }
+                start = start.max(*breakpoint);
+            }
+            res.unwrap_or_else(|| get(last))
+        }
+        Custom(..) => Interval::entire(),
+    }
+}
+
+/// Returns position of operand among operands of operation.
+fn args_index<T: Float>(opp: &Operation<Handle<T>, T>, x: &Handle<T>) -> usize {
+    opp.operands()
+        .iter()
+        .position(|y| std::ptr::eq(*y, x))
+        .expect("operand of operation")
+}
+
+impl<T: Float> Node<T> {
+    /// Computes bounds of root value for inputs ranging over given intervals,
+    /// see `interval` module. Other nodes without operation keep their current value.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn compute_interval(root: &Handle<T>, ranges: &[(InputId, Interval<T>)]) -> Interval<T> {
+        let ranges: HashMap<usize, Interval<T>> =
+            ranges.iter().map(|(id, range)| (id.0, *range)).collect();
+        let mut done: HashMap<usize, Interval<T>> = HashMap::new();
+        for node in post_order(root) {
+            let addr = node_addr(&node);
+            let opp = node.borrow().operation().cloned();
+            let range = match (ranges.get(&addr), opp) {
+                (Some(range), _) => *range,
+                (None, Some(opp)) => extend(&opp, |x| done[&node_addr(x)]),
+                (None, None) => {
+                    let operands = node.borrow().operands();
+                    // custom nodes depending on ranges can take any value
+                    if operands
+                        .iter()
+                        .all(|x| done[&node_addr(x)].width() == T::ZERO)
+                    {
+                        Interval::point(node.borrow_mut().compute())
+                    } else {
+                        Interval::entire()
+                    }
+                }
+            };
+            done.insert(addr, range);
+        }
+        done[&node_addr(root)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputNode;
+
+    #[test]
+    fn arithmetic() {
+        let a = Interval::new(-1.0f32, 2.0);
+        let b = Interval::new(3.0, 4.0);
+        assert_eq!(a + b, Interval::new(2.0, 6.0));
+        assert_eq!(a - b, Interval::new(-5.0, -1.0));
+        assert_eq!(a * b, Interval::new(-4.0, 8.0));
+        assert_eq!(a / b, Interval::new(-1.0 / 3.0, 2.0 / 3.0));
+        assert_eq!(b / a, Interval::entire());
+        assert_eq!(a.pow(2.0), Interval::new(0.0, 4.0));
+        assert_eq!(a.pow(3.0), Interval::new(-1.0, 8.0));
+        assert_eq!(a.pow(0.5), Interval::entire());
+        assert_eq!(Interval::new(0.0f32, 3.0).sin(0.0), Interval::new(0.0, 1.0));
+        assert_eq!(Interval::new(3.0f32, 7.0).sin(0.0).lo, -1.0);
+        assert_eq!(
+            Interval::new(0.5f32, 1.0).sin(PI / 2.0),
+            Interval::new(1.0f32.cos(), 0.5f32.cos())
+        );
+    }
+
+    #[test]
+    fn bounds_graph() {
+        let x = InputNode::from_val(0.0f32);
+        let y = InputNode::from_val(1.0);
+        // x^2 - 2xy + exp(y), with lazy branch chosen by sign of y
+        let expr = Node::add(
+            Node::sub(
+                Node::pow(x.clone(), 2.0),
+                Node::mul(Node::constant(2.0), Node::mul(x.clone(), y.clone())),
+            ),
+            Node::exp(y.clone()),
+        );
+        let root: Handle = Node::lazy_select(y.clone(), expr, Node::constant(-100.0));
+        let ranges = [
+            (InputId::of(&x), Interval::new(-1.0, 1.0)),
+            (InputId::of(&y), Interval::new(0.5, 1.0)),
+        ];
+        let bounds = Node::compute_interval(&root, &ranges);
+        // sampled values lie within bounds
+        for i in 0..=10 {
+            for j in 0..=10 {
+                let (vx, vy) = (-1.0 + 0.2 * i as f32, 0.5 + 0.05 * j as f32);
+                let val = vx * vx - 2.0 * vx * vy + vy.exp();
+                assert!(bounds.contains(val), "{} outside of {}", val, bounds);
+            }
+        }
+        assert!(bounds.lo > -2.0 && bounds.hi < 6.0);
+
+        let ranges = [(InputId::of(&y), Interval::new(-1.0, 1.0))];
+        assert_eq!(Node::compute_interval(&root, &ranges).lo, -100.0);
+    }
+}
@@ -0,0 +1,303 @@
+//! Rendering of graphs as LaTeX math, e.g. `\frac{x}{2} + \sin\left(y^{3}\right)`.
+//!
+//! Operands are shown the same way as by `infix` rendering: labeled ones by labels,
+//! unlabeled inputs by current values. Functions without standard notation are written
+//! with `\operatorname`.
+
+use crate::{
+    float::Float,
+    node::{Computable, Handle, Node},
+    ops::{Operation, Reduction},
+};
+
+/// Binding strength of rendered expressions, operands binding weaker than their
+/// position requires are parenthesized.
+const RELATION: u8 = 0;
+const SUM: u8 = 1;
+const PRODUCT: u8 = 2;
+const FUNCTION: u8 = 3;
+const ATOM: u8 = 4;
+
+/// Renders graph with given root as LaTeX math. Root is expanded even if labeled.
+pub fn to_latex<T: Float>(root: &Handle<T>) -> String {
+    expression(&*root.borrow()).0
+}
+
+impl<T: Float> Node<T> {
+    /// Renders expression of this node as LaTeX math, see `latex` module.
+    pub fn to_latex(&self) -> String {
+        expression(self).0
+    }
+}
+
+/// Renders node expanding its operation, returning text with its binding strength.
+fn expression<T: Float>(node: &dyn Computable<T>) -> (String, u8) {
+    use Operation::*;
+
+    let opp = match node.operation() {
+        Some(opp) => opp,
+        None => return ("?".into(), ATOM),
+    };
+    let arg = |x: &Handle<T>| operand(x).0;
+    match opp {
+        Const(val) => number(*val),
+        Add(x, y) => binary(x, " + ", y, SUM),
+        Sub(x, y) => binary(x, " - ", y, SUM),
+        Mul(x, y) => binary(x, " \\cdot ", y, PRODUCT),
+        AddVar(xs) | Reduce(xs, Reduction::Sum) if !xs.is_empty() => chain(xs, " + ", SUM),
+        MulVar(xs) | Reduce(xs, Reduction::Prod) if !xs.is_empty() => {
+            chain(xs, " \\cdot ", PRODUCT)
+        }
+        AddVar(_) | Reduce(_, Reduction::Sum) => ("0".into(), ATOM),
+        MulVar(_) | Reduce(_, Reduction::Prod) => ("1".into(), ATOM),
+        // fractions and superscripts are parenthesized as bases of powers
+        Div(x, y, _) => (format!("\\frac{{{}}}{{{}}}", arg(x), arg(y)), FUNCTION),
+        Recip(x) => (format!("\\frac{{1}}{{{}}}", arg(x)), FUNCTION),
+        Neg(x) => (format!("-{}", wrap(operand(x), PRODUCT)), SUM),
+        Pow(x, p) => (
+            format!("{}^{{{}}}", wrap(operand(x), ATOM), number(*p).0),
+            FUNCTION,
+        ),
+        Sqrt(x) => (format!("\\sqrt{{{}}}", arg(x)), ATOM),
+        Exp(x) => (format!("e^{{{}}}", arg(x)), FUNCTION),
+        ExpM1(x) => (format!("e^{{{}}} - 1", arg(x)), SUM),
+        Ln1p(x) => (format!("\\ln\\left(1 + {}\\right)", arg(x)), FUNCTION),
+        Abs(x) => (format!("\\left|{}\\right|", arg(x)), ATOM),
+        Log(x, base) => (
+            format!("\\log_{{{}}}\\left({}\\right)", number(*base).0, arg(x)),
+            FUNCTION,
+        ),
+        Sin(x) => call("\\sin", [x]),
+        Asin(x) => call("\\arcsin", [x]),
+        Acos(x) => call("\\arccos", [x]),
+        Atan(x) => call("\\arctan", [x]),
+        Cos(x) => call("\\cos", [x]),
+        Tan(x) => call("\\tan", [x]),
+        Ln(x) => call("\\ln", [x]),
+        Tanh(x) => call("\\tanh", [x]),
+        Sigmoid(x) => call("\\sigma", [x]),
+        NormalCdf(x) => call("\\Phi", [x]),
+        NormalPdf(x) => call("\\varphi", [x]),
+        Min(x, y) => call("\\min", [x, y]),
+        Max(x, y) => call("\\max", [x, y]),
+        Reduce(xs, Reduction::Min) => call("\\min", xs),
+        Reduce(xs, Reduction::Max) => call("\\max", xs),
+        Reduce(xs, Reduction::Mean) => call("\\operatorname{mean}", xs),
+        Hypot(..) | Norm(_) => {
+            let squares: Vec<_> = opp
+                .operands()
+                .into_iter()
+                .map(|x| format!("{}^{{2}}", wrap(operand(x), ATOM)))
+                .collect();
+            (format!("\\sqrt{{{}}}", squares.join(" + ")), ATOM)
+        }
+        Lt(x, y) => binary(x, " < ", y, RELATION),
+        Le(x, y) => binary(x, " \\le ", y, RELATION),
+        Gt(x, y) => binary(x, " > ", y, RELATION),
+        Ge(x, y) => binary(x, " \\ge ", y, RELATION),
+        Eq(x, y, tol) if *tol == T::ZERO => binary(x, " = ", y, RELATION),
+        Eq(x, y, tol) => (
+            format!(
+                "\\left|{} - {}\\right| \\le {}",
+                wrap(operand(x), SUM),
+                wrap(operand(y), PRODUCT),
+                number(*tol).0
+            ),
+            RELATION,
+        ),
+        Softmax(xs, i) => (
+            format!(
+                "\\operatorname{{softmax}}_{{{}}}\\left({}\\right)",
+                i,
+                xs.iter().map(arg).collect::<Vec<_>>().join(", ")
+            ),
+            FUNCTION,
+        ),
+        Quantize(x, step, _) => (
+            format!(
+                "\\operatorname{{quantize}}_{{{}}}\\left({}\\right)",
+                number(*step).0,
+                arg(x)
+            ),
+            FUNCTION,
+        ),
+        Select(cond, x, y) => (
+            format!(
+                "\\begin{{cases}} {} & {} > 0 \\\\ {} & \\text{{otherwise}} \\end{{cases}}",
+                arg(x),
+                wrap(operand(cond), SUM),
+                arg(y)
+            ),
+            ATOM,
+        ),
+        Piecewise(x, pieces, default) => {
+            let x = wrap(operand(x), SUM);
+            let cases: Vec<_> = pieces
+                .iter()
+                .map(|(breakpoint, piece)| {
+                    format!("{} & {} < {}", arg(piece), x, number(*breakpoint).0)
+                })
+                .collect();
+            (
+                format!(
+                    "\\begin{{cases}} {} \\\\ {} & \\text{{otherwise}} \\end{{cases}}",
+                    cases.join(" \\\\ "),
+                    arg(default)
+                ),
+                ATOM,
+            )
+        }
+        Polynomial(x, coeffs) if !coeffs.is_empty() => {
+            let terms: Vec<_> = coeffs
+                .iter()
+                .enumerate()
+                .map(|(i, c)| match i {
+                    0 => number(*c).0,
+                    1 => format!(
+                        "{} \\cdot {}",
+                        wrap(number(*c), PRODUCT),
+                        wrap(operand(x), PRODUCT)
+                    ),
+                    _ => format!(
+                        "{} \\cdot {}^{{{}}}",
+                        wrap(number(*c), PRODUCT),
+                        wrap(operand(x), ATOM),
+                        i
+                    ),
+                })
+                .collect();
+            (terms.join(" + "), SUM)
+        }
+        // gradients have no notation, value is the same as of operand
+        Detach(x) => operand(x),
+        opp => call(
+            &format!("\\operatorname{{{}}}", escape(opp.name())),
+            opp.operands(),
+        ),
+    }
+}
+
+/// Renders operand, shown by its label if it has one.
+fn operand<T: Float>(x: &Handle<T>) -> (String, u8) {
+    let node = match x.try_borrow() {
+        Ok(node) => node,
+        Err(_) => return ("?".into(), ATOM),
+    };
+    if let Some(label) = node.label() {
+        return (identifier(label), ATOM);
+    }
+    if node.operation().is_none() && node.operands().is_empty() && !node.is_dirty() {
+        drop(node);
+        // leaves which aren't dirty are inputs returning stored value
+        if let Ok(mut leaf) = x.try_borrow_mut() {
+            return number(leaf.compute());
+        }
+        return ("?".into(), ATOM);
+    }
+    expression(&*node)
+}
+
+/// Single letters are written as math italic, longer names upright.
+fn identifier(label: &str) -> String {
+    if label.chars().count() == 1 && label.chars().all(char::is_alphabetic) {
+        label.into()
+    } else {
+        format!("\\mathrm{{{}}}", escape(label))
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '_' | '#' | '$' | '%' | '&' | '{' | '}' => format!("\\{}", c),
+            '\\' => "\\backslash ".into(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn number<T: Float>(val: T) -> (String, u8) {
+    let text = if val.to_f64().is_nan() {
+        "\\mathrm{NaN}".into()
+    } else if val.is_infinite() {
+        if val > T::ZERO { "\\infty" } else { "-\\infty" }.into()
+    } else {
+        val.to_string()
+    };
+    (text, if val < T::ZERO { SUM } else { ATOM })
+}
+
+/// Parenthesizes expression binding weaker than `min`.
+fn wrap((text, prec): (String, u8), min: u8) -> String {
+    if prec < min {
+        format!("\\left({}\\right)", text)
+    } else {
+        text
+    }
+}
+
+/// Renders left-associative binary operator, right operand must bind stronger.
+fn binary<T: Float>(x: &Handle<T>, op: &str, y: &Handle<T>, prec: u8) -> (String, u8) {
+    let x = wrap(operand(x), prec);
+    let y = wrap(operand(y), prec + 1);
+    (format!("{}{}{}", x, op, y), prec)
+}
+
+/// Renders variadic operation as chain of left-associative operators.
+fn chain<T: Float>(args: &[Handle<T>], op: &str, prec: u8) -> (String, u8) {
+    let terms: Vec<_> = args
+        .iter()
+        .enumerate()
+        .map(|(i, x)| wrap(operand(x), if i == 0 { prec } else { prec + 1 }))
+        .collect();
+    (terms.join(op), prec)
+}
+
+/// Renders function applied to given operands.
+fn call<'a, T: Float>(name: &str, args: impl IntoIterator<Item = &'a Handle<T>>) -> (String, u8) {
+    let args: Vec<_> = args.into_iter().map(|x| operand(x).0).collect();
+    (
+        format!("{}\\left({}\\right)", name, args.join(", ")),
+        FUNCTION,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Graph;
+
+    #[test]
+    fn formulas() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        let y = g.add_input("y", 2.0);
+        let rate = g.add_input("rate_1", 0.5);
+        let root = Node::add(
+            Node::div(x.clone(), Node::constant(2.0)),
+            Node::sin(Node::pow(y.clone(), 3.0)),
+        );
+        assert_eq!(
+            root.borrow().to_latex(),
+            "\\frac{x}{2} + \\sin\\left(y^{3}\\right)"
+        );
+
+        let root: Handle = Node::mul(
+            Node::neg(Node::add(x.clone(), y.clone())),
+            Node::pow(Node::exp(Node::mul(rate, Node::constant(-1.0))), 2.0),
+        );
+        assert_eq!(
+            to_latex(&root),
+            "\\left(-\\left(x + y\\right)\\right) \\cdot \\left(e^{\\mathrm{rate\\_1} \\cdot \\left(-1\\right)}\\right)^{2}"
+        );
+
+        let root = Node::sub(
+            Node::sqrt(Node::hypot(x.clone(), y.clone())),
+            Node::sub(Node::abs(x), Node::recip(y)),
+        );
+        assert_eq!(
+            root.borrow().to_latex(),
+            "\\sqrt{\\sqrt{x^{2} + y^{2}}} - \\left(\\left|x\\right| - \\frac{1}{y}\\right)"
+        );
+    }
+}
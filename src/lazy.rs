@@ -0,0 +1,171 @@
+//! Nodes materializing their subgraph on first computation.
+//!
+//! Large models can be split into parts, e.g. separate snapshots, each loaded only
+//! when an output needing it is computed, so memory stays proportional to what's used.
+
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    error::GraphError,
+    eval::Evaluation,
+    float::Float,
+    node::{
+        dependents, remove_dependency, reset_dependencies, Computable, Dependencies, Handle, Kind,
+        Node,
+    },
+};
+
+/// Builds root of subgraph when it's first needed.
+type Loader<T> = Box<dyn FnOnce() -> Handle<T>>;
+
+/// Node passing value of subgraph through, building the subgraph on first computation.
+/// Before loading it has no operands, so traversals don't force loading.
+pub struct LazyNode<T: Float = f32> {
+    /// Reference to itself, so loaded subgraph can invalidate the node.
+    this: Weak<RefCell<LazyNode<T>>>,
+    loader: Option<Loader<T>>,
+    /// Root of loaded subgraph.
+    root: Option<Handle<T>>,
+    /// Cached result.
+    cache: Option<T>,
+    /// Holds references to nodes that depend from this node.
+    dependencies: Dependencies<T>,
+}
+
+impl<T: Float> Node<T> {
+    /// Builds node computing subgraph returned by `loader`, which is called on first computation.
+    pub fn lazy(loader: impl FnOnce() -> Handle<T> + 'static) -> Rc<RefCell<LazyNode<T>>> {
+        Rc::new_cyclic(|this| {
+            RefCell::new(LazyNode {
+                this: this.clone(),
+                loader: Some(Box::new(loader)),
+                root: None,
+                cache: None,
+                dependencies: Dependencies::default(),
+            })
+        })
+    }
+}
+
+impl<T: Float> LazyNode<T> {
+    /// Returns `true` if subgraph was already loaded.
+    pub fn is_loaded(&self) -> bool {
+        self.root.is_some()
+    }
+
+    /// Loads subgraph if it isn't loaded yet, returning its root.
+    pub fn load(&mut self) -> Handle<T> {
+        if let Some(root) = &self.root {
+            return root.clone();
+        }
+        let root = (self.loader.take().expect("loader panicked before"))();
+        if let Some(this) = self.this.upgrade() {
+            root.borrow_mut().add_dependency(this);
+        }
+        self.root = Some(root.clone());
+        root
+    }
+}
+
+impl<T: Float> Computable<T> for LazyNode<T> {
+    fn compute(&mut self) -> T {
+        if let Some(val) = self.cache {
+            return val;
+        }
+        let val = self.load().borrow_mut().compute();
+        self.cache = Some(val);
+        val
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependencies.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Handle<T>> {
+        dependents(&self.dependencies)
+    }
+
+    fn reset_cache(&mut self) {
+        if self.cache.take().is_none() {
+            return;
+        }
+        reset_dependencies(&mut self.dependencies);
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.cache.is_none()
+    }
+
+    fn operands(&self) -> Vec<Handle<T>> {
+        self.root.iter().cloned().collect()
+    }
+
+    fn kind(&self) -> Kind {
+        self.root
+            .as_ref()
+            .map_or(Kind::Numeric, |root| root.borrow().kind())
+    }
+
+    fn try_compute(&mut self, eval: &mut Evaluation) -> Result<T, GraphError> {
+        if let Some(val) = self.cache {
+            return Ok(val);
+        }
+        let val = self.load().borrow_mut().try_compute(eval)?;
+        self.cache = Some(val);
+        Ok(val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::{graph::Graph, input::Input, snapshot::Snapshot};
+
+    #[test]
+    fn loads_on_demand() {
+        let loads = Rc::new(Cell::new(0));
+        let x = Node::input(2.0f32);
+        let part = {
+            let (x, loads) = (x.clone(), loads.clone());
+            Node::lazy(move || -> Handle {
+                loads.set(loads.get() + 1);
+                Node::mul(x, Node::constant(3.0))
+            })
+        };
+        let root = Node::add(part.clone(), Node::constant(1.0));
+        assert!(!part.borrow().is_loaded());
+        assert!(part.borrow().operands().is_empty());
+
+        assert_eq!(root.borrow_mut().compute(), 7.0);
+        assert!(part.borrow().is_loaded());
+        x.borrow_mut().set(1.0);
+        assert!(root.borrow().is_dirty());
+        assert_eq!(root.borrow_mut().compute(), 4.0);
+        assert_eq!(loads.get(), 1);
+    }
+
+    #[test]
+    fn snapshot_parts() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 3.0f32);
+        g.add_output("curve", Node::sin(x));
+        let part = Snapshot::of(&g);
+
+        let unused = {
+            let part = part.clone();
+            Node::lazy(move || part.restore().output("curve").unwrap().clone())
+        };
+        let used = Node::lazy(move || part.restore().output("curve").unwrap().clone());
+        assert_eq!(used.borrow_mut().compute(), 3f32.sin());
+        assert!(!unused.borrow().is_loaded());
+    }
+}
@@ -1,3 +1,70 @@
+pub mod alert;
+pub mod analysis;
+pub mod arena;
+pub mod bank;
+pub mod binary;
+pub mod bounds;
+pub mod checkpoint;
+pub mod compare;
+pub mod compile;
+pub mod complex;
+pub mod constant;
+pub mod cost;
+#[cfg(feature = "counters")]
+pub mod counters;
+pub mod decimal;
+pub mod diff;
+pub mod differential;
+pub mod dot;
+pub mod error;
+pub mod eval;
+pub mod excel;
+pub mod explain;
+pub mod expr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod float;
+pub mod grad;
+pub mod graph;
+pub mod implicit;
+pub mod infix;
 pub mod input;
+pub mod interval;
+pub mod latex;
+pub mod lazy;
+pub mod library;
+pub mod lint;
+pub mod lut;
+pub mod mathml;
 pub mod node;
-mod ops;
+pub mod noderef;
+pub mod ops;
+pub mod optim;
+pub mod owner;
+pub mod parse;
+pub mod profile;
+pub mod prune;
+pub mod reactive;
+pub mod reload;
+pub mod scenario;
+pub mod schedule;
+pub mod simplify;
+mod smallvec;
+pub mod snapshot;
+pub mod solve;
+pub mod stats;
+pub mod store;
+pub mod strategy;
+pub mod stress;
+pub mod subgraph;
+pub mod sympy;
+pub mod sync;
+pub mod temporal;
+pub mod tensor;
+pub mod testing;
+pub mod time;
+pub mod typed;
+pub mod units;
+pub mod visit;
+#[cfg(feature = "wasm")]
+pub mod wasm;
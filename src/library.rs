@@ -0,0 +1,262 @@
+//! Named formulas with declared parameters, instantiated into larger graphs.
+//!
+//! Each formula is kept as a template graph over input nodes of its parameters.
+//! Instantiation copies operation nodes of the template over given argument nodes,
+//! so instances cache independently, while arguments may be shared between them.
+
+use std::{cell::RefCell, collections::HashMap, error::Error, fmt, rc::Rc};
+
+use crate::{
+    float::Float,
+    input::InputNode,
+    node::{node_addr, post_order, Handle, Node},
+    parse::{parse, ParseError},
+};
+
+/// Error of defining or instantiating formula.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LibraryError {
+    /// Formula with given name isn't defined.
+    UnknownFormula(String),
+    /// Formula with given name is already defined.
+    DuplicateFormula(String),
+    /// Formula uses variable which isn't among its parameters.
+    UndeclaredVariable(String),
+    /// Formula is instantiated with wrong amount of arguments.
+    Arity {
+        /// Name of formula.
+        name: String,
+        /// Amount of parameters.
+        expected: usize,
+        /// Amount of given arguments.
+        actual: usize,
+    },
+    /// Formula can't be parsed.
+    Parse(ParseError),
+}
+
+impl fmt::Display for LibraryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LibraryError::UnknownFormula(name) => write!(f, "formula {} isn't defined", name),
+            LibraryError::DuplicateFormula(name) => {
+                write!(f, "formula {} is already defined", name)
+            }
+            LibraryError::UndeclaredVariable(name) => {
+                write!(f, "variable {} isn't a parameter", name)
+            }
+            LibraryError::Arity {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "formula {} expects {} arguments, got {}",
+                name, expected, actual
+            ),
+            LibraryError::Parse(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for LibraryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LibraryError::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for LibraryError {
+    fn from(err: ParseError) -> Self {
+        LibraryError::Parse(err)
+    }
+}
+
+/// Template graph of formula.
+struct Formula<T: Float> {
+    /// Parameters by name along with their template inputs, in declaration order.
+    params: Vec<(String, Rc<RefCell<InputNode<T>>>)>,
+    root: Handle<T>,
+}
+
+/// Collection of formulas by name.
+pub struct FormulaLibrary<T: Float = f32> {
+    formulas: HashMap<String, Formula<T>>,
+}
+
+impl<T: Float> Default for FormulaLibrary<T> {
+    fn default() -> Self {
+        Self {
+            formulas: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Float> FormulaLibrary<T> {
+    /// Creates empty library.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines formula computing graph with given root from given parameter inputs.
+    /// Other leaves of the graph are shared by all instances.
+    pub fn define_graph(
+        &mut self,
+        name: &str,
+        params: &[(&str, Rc<RefCell<InputNode<T>>>)],
+        root: Handle<T>,
+    ) -> Result<(), LibraryError> {
+        if self.formulas.contains_key(name) {
+            return Err(LibraryError::DuplicateFormula(name.to_string()));
+        }
+        let params = params
+            .iter()
+            .map(|(name, input)| (name.to_string(), input.clone()))
+            .collect();
+        self.formulas
+            .insert(name.to_string(), Formula { params, root });
+        Ok(())
+    }
+
+    /// Returns names of parameters of formula, in declaration order.
+    pub fn params(&self, name: &str) -> Option<Vec<&str>> {
+        let formula = self.formulas.get(name)?;
+        Some(formula.params.iter().map(|(p, _)| p.as_str()).collect())
+    }
+
+    /// Builds graph of formula over given argument nodes, one per parameter.
+    pub fn instantiate(&self, name: &str, args: &[Handle<T>]) -> Result<Handle<T>, LibraryError> {
+        let formula = self
+            .formulas
+            .get(name)
+            .ok_or_else(|| LibraryError::UnknownFormula(name.to_string()))?;
+        if args.len() != formula.params.len() {
+            return Err(LibraryError::Arity {
+                name: name.to_string(),
+                expected: formula.params.len(),
+                actual: args.len(),
+            });
+        }
+        let mut copies: HashMap<usize, Handle<T>> = formula
+            .params
+            .iter()
+            .zip(args)
+            .map(|((_, input), arg)| (node_addr(input), arg.clone()))
+            .collect();
+        for node in post_order(&formula.root) {
+            if copies.contains_key(&node_addr(&node)) {
+                continue;
+            }
+            let opp = node
+                .borrow()
+                .operation()
+                .map(|opp| opp.map_operands(|x| copies[&node_addr(x)].clone()));
+            let copy: Handle<T> = match opp {
+                Some(opp) => {
+                    let copy = Node::new(opp);
+                    if let Some(label) = node.borrow().label() {
+                        copy.borrow_mut().set_label(label);
+                    }
+                    copy
+                }
+                None => node.clone(),
+            };
+            copies.insert(node_addr(&node), copy);
+        }
+        Ok(copies[&node_addr(&formula.root)].clone())
+    }
+
+    /// Builds graph of formula over fresh inputs, returned by parameter name.
+    /// Inputs are labeled by their parameters and start at zero.
+    #[allow(clippy::type_complexity)]
+    pub fn instantiate_fresh(
+        &self,
+        name: &str,
+    ) -> Result<(Handle<T>, HashMap<String, Rc<RefCell<InputNode<T>>>>), LibraryError> {
+        let params = self
+            .params(name)
+            .ok_or_else(|| LibraryError::UnknownFormula(name.to_string()))?;
+        let inputs: Vec<(String, Rc<RefCell<InputNode<T>>>)> = params
+            .iter()
+            .map(|p| (p.to_string(), InputNode::with_label(T::ZERO, *p)))
+            .collect();
+        let args: Vec<Handle<T>> = inputs.iter().map(|(_, input)| input.clone() as _).collect();
+        let root = self.instantiate(name, &args)?;
+        Ok((root, inputs.into_iter().collect()))
+    }
+}
+
+impl FormulaLibrary<f32> {
+    /// Defines formula parsed from string, whose variables must be among given parameters,
+    /// see `parse` module for supported syntax.
+    pub fn define(
+        &mut self,
+        name: &str,
+        params: &[&str],
+        formula: &str,
+    ) -> Result<(), LibraryError> {
+        let (root, mut vars) = parse(formula)?;
+        if let Some(var) = vars.keys().find(|var| !params.contains(&var.as_str())) {
+            return Err(LibraryError::UndeclaredVariable(var.clone()));
+        }
+        // parameters unused by formula still take their arguments
+        let params: Vec<(&str, Rc<RefCell<InputNode>>)> = params
+            .iter()
+            .map(|p| {
+                (
+                    *p,
+                    vars.remove(*p).unwrap_or_else(|| InputNode::from_val(0.0)),
+                )
+            })
+            .collect();
+        self.define_graph(name, &params, root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    #[test]
+    fn formulas() {
+        let mut lib = FormulaLibrary::new();
+        lib.define("area", &["r"], "3.14159265 * r^2").unwrap();
+        lib.define("ring", &["outer", "inner"], "outer - inner")
+            .unwrap();
+        assert_eq!(lib.params("ring"), Some(vec!["outer", "inner"]));
+
+        // shared radius feeds both instances
+        let r = Node::input(2.0f32);
+        let outer = lib.instantiate("area", &[r.clone()]).unwrap();
+        let doubled: Handle = Node::mul(r.clone(), Node::constant(0.5));
+        let inner = lib.instantiate("area", &[doubled]).unwrap();
+        let ring = lib.instantiate("ring", &[outer, inner]).unwrap();
+        assert!((ring.borrow_mut().compute() - 3.0 * std::f32::consts::PI).abs() < 1e-4);
+        r.borrow_mut().set(4.0);
+        assert!((ring.borrow_mut().compute() - 12.0 * std::f32::consts::PI).abs() < 1e-4);
+
+        let (area, inputs) = lib.instantiate_fresh("area").unwrap();
+        inputs["r"].borrow_mut().set(1.0);
+        assert!((area.borrow_mut().compute() - std::f32::consts::PI).abs() < 1e-6);
+
+        assert_eq!(
+            lib.define("volume", &["r"], "r * h"),
+            Err(LibraryError::UndeclaredVariable("h".to_string()))
+        );
+        assert!(matches!(
+            lib.define("area", &["r"], "r"),
+            Err(LibraryError::DuplicateFormula(_))
+        ));
+        assert_eq!(
+            lib.instantiate("ring", &[r]).err(),
+            Some(LibraryError::Arity {
+                name: "ring".to_string(),
+                expected: 2,
+                actual: 1
+            })
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! Structural checks of graphs, reported with configurable severities,
+//! so model changes can be gated on them.
+//!
+//! Checks cover nodes reachable from graph outputs, see `Lint` for the list.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    float::Float,
+    graph::Graph,
+    node::{node_addr, Handle},
+    ops::Operation,
+};
+
+/// Structural check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// Registered input which no output depends on.
+    UnusedInput,
+    /// Subtree computed from constants only, which could be folded, see `simplify`.
+    ConstantFoldable,
+    /// Structurally identical subexpressions built as separate nodes, see `dedup`.
+    DuplicateSubexpression,
+    /// Operation likely producing `NaN`, like `pow` with negative base and fractional
+    /// exponent at current input values, or division by constant zero.
+    SuspiciousOperation,
+}
+
+impl Lint {
+    /// All checks.
+    pub const ALL: [Lint; 4] = [
+        Lint::UnusedInput,
+        Lint::ConstantFoldable,
+        Lint::DuplicateSubexpression,
+        Lint::SuspiciousOperation,
+    ];
+
+    /// Returns name of check, as shown in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedInput => "unused_input",
+            Lint::ConstantFoldable => "constant_foldable",
+            Lint::DuplicateSubexpression => "duplicate_subexpression",
+            Lint::SuspiciousOperation => "suspicious_operation",
+        }
+    }
+}
+
+/// Severity of findings of check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Check isn't run.
+    Allow,
+    /// Findings are reported as warnings.
+    Warn,
+    /// Findings are reported as errors, see `Report::is_denied`.
+    Deny,
+}
+
+/// Severities of checks, all checks warn unless configured otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    levels: HashMap<Lint, Level>,
+}
+
+impl Config {
+    /// Creates config with all checks warning.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets severity of check.
+    pub fn level(mut self, lint: Lint, level: Level) -> Self {
+        self.levels.insert(lint, level);
+        self
+    }
+
+    /// Sets severity of all checks.
+    pub fn all(mut self, level: Level) -> Self {
+        for lint in Lint::ALL {
+            self.levels.insert(lint, level);
+        }
+        self
+    }
+
+    /// Returns severity of check.
+    pub fn level_of(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).copied().unwrap_or(Level::Warn)
+    }
+}
+
+/// Single finding of check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// Check which produced the finding.
+    pub lint: Lint,
+    /// Severity of the check.
+    pub level: Level,
+    /// Description of the finding.
+    pub message: String,
+}
+
+/// Findings of checks, in order of checks in `Lint::ALL`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    findings: Vec<Finding>,
+}
+
+impl Report {
+    /// Returns all findings.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Returns findings of given check.
+    pub fn of(&self, lint: Lint) -> impl Iterator<Item = &Finding> + '_ {
+        self.findings.iter().filter(move |x| x.lint == lint)
+    }
+
+    /// Returns true if some finding is denied.
+    pub fn is_denied(&self) -> bool {
+        self.findings.iter().any(|x| x.level == Level::Deny)
+    }
+
+    /// Returns true if there are no findings.
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    /// Renders finding per line, like `warning[unused_input]: input x isn't used`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for x in &self.findings {
+            let level = if x.level == Level::Deny {
+                "error"
+            } else {
+                "warning"
+            };
+            writeln!(f, "{}[{}]: {}", level, x.lint.name(), x.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs checks enabled by config over graph.
+pub fn run<T: Float>(graph: &Graph<T>, config: &Config) -> Report {
+    let nodes = graph.reachable();
+    let mut report = Report::default();
+    for lint in Lint::ALL {
+        let level = config.level_of(lint);
+        if level == Level::Allow {
+            continue;
+        }
+        let messages = match lint {
+            Lint::UnusedInput => unused_inputs(graph, &nodes),
+            Lint::ConstantFoldable => constant_foldable(&nodes),
+            Lint::DuplicateSubexpression => duplicates(&nodes),
+            Lint::SuspiciousOperation => suspicious(&nodes),
+        };
+        report
+            .findings
+            .extend(messages.into_iter().map(|message| Finding {
+                lint,
+                level,
+                message,
+            }));
+    }
+    report
+}
+
+fn unused_inputs<T: Float>(graph: &Graph<T>, nodes: &[Handle<T>]) -> Vec<String> {
+    let used: HashSet<usize> = nodes.iter().map(node_addr).collect();
+    graph
+        .inputs()
+        .filter(|(_, input)| !used.contains(&node_addr(input)))
+        .map(|(name, _)| format!("input {} isn't used by any output", name))
+        .collect()
+}
+
+/// Reports the largest subtrees of operations over constants.
+fn constant_foldable<T: Float>(nodes: &[Handle<T>]) -> Vec<String> {
+    let mut constant = HashSet::new();
+    // foldable nodes which are operands of other foldable nodes
+    let mut inner = HashSet::new();
+    let mut foldable = Vec::new();
+    for node in nodes {
+        let node_ref = node.borrow();
+        let opp = match node_ref.operation() {
+            Some(Operation::Const(_)) => {
+                constant.insert(node_addr(node));
+                continue;
+            }
+            Some(opp) => opp,
+            None => continue,
+        };
+        let operands = opp.operands();
+        if !operands.is_empty() && operands.iter().all(|x| constant.contains(&node_addr(x))) {
+            constant.insert(node_addr(node));
+            inner.extend(operands.iter().map(|x| node_addr(x)));
+            foldable.push((node_addr(node), opp.name()));
+        }
+    }
+    foldable
+        .into_iter()
+        .filter(|(addr, _)| !inner.contains(addr))
+        .map(|(_, name)| format!("{} is computed from constants only", name))
+        .collect()
+}
+
+/// Reports groups of identical operations over identical operands.
+fn duplicates<T: Float>(nodes: &[Handle<T>]) -> Vec<String> {
+    // id of canonical node for each node
+    let mut ids: HashMap<usize, usize> = HashMap::new();
+    // id of canonical node, operation name and amount of nodes by key of operation
+    let mut groups: HashMap<String, (usize, &'static str, usize)> = HashMap::new();
+    let mut order = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let id = match node.borrow().operation() {
+            Some(opp) => {
+                // float parameters have no `Hash`, their debug form tells them apart
+                let key = format!("{:?}", opp.map_operands(|x| ids[&node_addr(x)]));
+                let group = groups.entry(key.clone()).or_insert_with(|| {
+                    order.push(key);
+                    (i, opp.name(), 0)
+                });
+                group.2 += 1;
+                group.0
+            }
+            None => i,
+        };
+        ids.insert(node_addr(node), id);
+    }
+    order
+        .into_iter()
+        .map(|key| groups[&key])
+        .filter(|(_, name, count)| *count > 1 && *name != "constant")
+        .map(|(_, name, count)| format!("{} nodes compute identical {}", count, name))
+        .collect()
+}
+
+fn suspicious<T: Float>(nodes: &[Handle<T>]) -> Vec<String> {
+    let mut messages = Vec::new();
+    for node in nodes {
+        let opp = match node.borrow().operation() {
+            Some(opp) => opp.clone(),
+            None => continue,
+        };
+        match opp {
+            Operation::Pow(x, pow) if pow.fract() != T::ZERO => {
+                let base = x.borrow_mut().compute();
+                if base < T::ZERO {
+                    messages.push(format!(
+                        "pow {} has negative base {}, producing NaN",
+                        pow, base
+                    ));
+                }
+            }
+            Operation::Div(_, y, _) => {
+                if let Some(Operation::Const(val)) = y.borrow().operation() {
+                    if *val == T::ZERO {
+                        messages.push("div has constant zero divisor".to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Node;
+
+    #[test]
+    fn findings() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", -2.0f32);
+        g.add_input("unused", 0.0);
+        let folded = Node::mul(
+            Node::constant(2.0),
+            Node::add(Node::constant(1.0), Node::constant(3.0)),
+        );
+        let a = Node::mul(x.clone(), folded);
+        let b = Node::pow(x.clone(), 0.5);
+        let c = Node::div(Node::sin(x.clone()), Node::constant(0.0));
+        g.add_output("y", Node::add(Node::add(Node::sin(x), a), Node::add(b, c)));
+
+        let report = run(
+            &g,
+            &Config::new().level(Lint::SuspiciousOperation, Level::Deny),
+        );
+        let messages = |lint| {
+            report
+                .of(lint)
+                .map(|x: &Finding| x.message.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            messages(Lint::UnusedInput),
+            vec!["input unused isn't used by any output"]
+        );
+        assert_eq!(
+            messages(Lint::ConstantFoldable),
+            vec!["mul is computed from constants only"]
+        );
+        assert_eq!(
+            messages(Lint::DuplicateSubexpression),
+            vec!["2 nodes compute identical sin"]
+        );
+        assert_eq!(messages(Lint::SuspiciousOperation).len(), 2);
+        assert!(report.is_denied());
+        assert!(report
+            .to_string()
+            .starts_with("warning[unused_input]: input unused isn't used by any output\n"));
+
+        let report = run(
+            &g,
+            &Config::new()
+                .all(Level::Allow)
+                .level(Lint::UnusedInput, Level::Warn),
+        );
+        assert_eq!(report.findings().len(), 1);
+        assert!(!report.is_denied());
+    }
+}
@@ -0,0 +1,263 @@
+//! Lookup tables interpolating values over grids, e.g. calibration curves
+//! or volatility surfaces.
+
+use std::{cell::RefCell, ops::RangeInclusive, rc::Rc, sync::Arc};
+
+use crate::{
+    float::Float,
+    node::{Handle, Node},
+    ops::{CustomOp, Op, Operation},
+};
+
+/// Handling of coordinates outside of table grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bounds {
+    /// Coordinates are clamped to grid, so values at edges extend outwards.
+    Clamp,
+    /// Values are extrapolated linearly from the closest grid cell.
+    Extrapolate,
+    /// Coordinates outside of grid give `NaN`, failing checked evaluation.
+    Error,
+}
+
+/// Returns index of axis cell containing coordinate, along with position within the
+/// cell, from 0 at its start to 1 at its end, and derivative of position by coordinate.
+fn locate<T: Float>(axis: &[T], x: T, bounds: Bounds) -> (usize, T, T) {
+    let i = axis[1..axis.len() - 1].partition_point(|b| *b <= x);
+    let width = axis[i + 1] - axis[i];
+    let t = (x - axis[i]) / width;
+    let outside = t < T::ZERO || t > T::ONE;
+    match bounds {
+        Bounds::Clamp if t < T::ZERO => (i, T::ZERO, T::ZERO),
+        Bounds::Clamp if t > T::ONE => (i, T::ONE, T::ZERO),
+        Bounds::Error if outside => (i, T::from_f64(f64::NAN), T::from_f64(f64::NAN)),
+        _ => (i, t, T::ONE / width),
+    }
+}
+
+/// Checks that axis has at least two points and is increasing.
+fn check_axis<T: Float>(axis: &[T]) {
+    assert!(axis.len() >= 2, "table axis has less than two points");
+    assert!(
+        axis.windows(2).all(|w| w[0] < w[1]),
+        "table axis isn't increasing"
+    );
+}
+
+/// Table of values at increasing points, interpolated linearly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table1d<T: Float = f32> {
+    xs: Vec<T>,
+    ys: Vec<T>,
+    bounds: Bounds,
+}
+
+impl<T: Float> Table1d<T> {
+    /// Creates table from points given as `(x, value)` pairs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are less than two points or they aren't increasing.
+    pub fn new(points: Vec<(T, T)>, bounds: Bounds) -> Self {
+        let (xs, ys): (Vec<T>, Vec<T>) = points.into_iter().unzip();
+        check_axis(&xs);
+        Self { xs, ys, bounds }
+    }
+
+    /// Interpolates value at given coordinate, along with its derivative.
+    fn interpolate(&self, x: T) -> (T, T) {
+        let (i, t, dt) = locate(&self.xs, x, self.bounds);
+        let (lo, hi) = (self.ys[i], self.ys[i + 1]);
+        (lo + (hi - lo) * t, (hi - lo) * dt)
+    }
+
+    /// Returns value interpolated at given coordinate.
+    pub fn get(&self, x: T) -> T {
+        self.interpolate(x).0
+    }
+}
+
+impl<T: Float> Op<T> for Table1d<T> {
+    fn name(&self) -> &'static str {
+        "lookup"
+    }
+
+    fn eval(&self, operands: &[T]) -> T {
+        self.get(operands[0])
+    }
+
+    fn arity(&self) -> RangeInclusive<usize> {
+        1..=1
+    }
+
+    fn partials(&self, operands: &[T]) -> Vec<T> {
+        vec![self.interpolate(operands[0]).1]
+    }
+}
+
+/// Table of values over grid of two increasing axes, interpolated bilinearly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table2d<T: Float = f32> {
+    xs: Vec<T>,
+    ys: Vec<T>,
+    /// Values by rows of `xs`, value at `(xs[i], ys[j])` is at `i * ys.len() + j`.
+    values: Vec<T>,
+    bounds: Bounds,
+}
+
+impl<T: Float> Table2d<T> {
+    /// Creates table with values given by rows, one row per point of `xs`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if axis has less than two points or isn't increasing, or if amount
+    /// of values doesn't match the grid.
+    pub fn new(xs: Vec<T>, ys: Vec<T>, values: Vec<T>, bounds: Bounds) -> Self {
+        check_axis(&xs);
+        check_axis(&ys);
+        assert_eq!(
+            values.len(),
+            xs.len() * ys.len(),
+            "amount of table values doesn't match grid"
+        );
+        Self {
+            xs,
+            ys,
+            values,
+            bounds,
+        }
+    }
+
+    /// Returns value at grid point with given indices.
+    fn at(&self, i: usize, j: usize) -> T {
+        self.values[i * self.ys.len() + j]
+    }
+
+    /// Interpolates value at given coordinates, along with its partial derivatives.
+    fn interpolate(&self, x: T, y: T) -> (T, T, T) {
+        let (i, tx, dtx) = locate(&self.xs, x, self.bounds);
+        let (j, ty, dty) = locate(&self.ys, y, self.bounds);
+        let (v00, v01) = (self.at(i, j), self.at(i, j + 1));
+        let (v10, v11) = (self.at(i + 1, j), self.at(i + 1, j + 1));
+        let lo = v00 + (v10 - v00) * tx;
+        let hi = v01 + (v11 - v01) * tx;
+        let dx = ((v10 - v00) * (T::ONE - ty) + (v11 - v01) * ty) * dtx;
+        (lo + (hi - lo) * ty, dx, (hi - lo) * dty)
+    }
+
+    /// Returns value interpolated at given coordinates.
+    pub fn get(&self, x: T, y: T) -> T {
+        self.interpolate(x, y).0
+    }
+}
+
+impl<T: Float> Op<T> for Table2d<T> {
+    fn name(&self) -> &'static str {
+        "lut2d"
+    }
+
+    fn eval(&self, operands: &[T]) -> T {
+        self.get(operands[0], operands[1])
+    }
+
+    fn arity(&self) -> RangeInclusive<usize> {
+        2..=2
+    }
+
+    fn partials(&self, operands: &[T]) -> Vec<T> {
+        let (_, dx, dy) = self.interpolate(operands[0], operands[1]);
+        vec![dx, dy]
+    }
+}
+
+impl<T: Float> Node<T> {
+    /// Builds `Node` interpolating linearly between points of table at coordinate `x`.
+    /// Points are given as `(x, value)` pairs with increasing `x`, see `Table1d::new`.
+    pub fn lookup(x: Handle<T>, points: Vec<(T, T)>, bounds: Bounds) -> Rc<RefCell<Node<T>>> {
+        Node::new(Operation::Custom(
+            CustomOp(Arc::new(Table1d::new(points, bounds))),
+            vec![x],
+        ))
+    }
+
+    /// Builds `Node` looking up value of table at coordinates given by `x` and `y`.
+    pub fn lut2d(x: Handle<T>, y: Handle<T>, table: Table2d<T>) -> Rc<RefCell<Node<T>>> {
+        Node::new(Operation::Custom(CustomOp(Arc::new(table)), vec![x, y]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grad::Gradient,
+        input::{Input, InputNode},
+        node::Computable,
+    };
+
+    fn table(bounds: Bounds) -> Table2d {
+        // value is x + 10 * y over grid of x in {0, 1, 3} and y in {0, 2}
+        Table2d::new(
+            vec![0.0, 1.0, 3.0],
+            vec![0.0, 2.0],
+            vec![0.0, 20.0, 1.0, 21.0, 3.0, 23.0],
+            bounds,
+        )
+    }
+
+    #[test]
+    fn interpolation() {
+        let t = table(Bounds::Clamp);
+        assert_eq!(t.get(1.0, 2.0), 21.0);
+        assert_eq!(t.get(2.0, 1.0), 12.0);
+        assert_eq!(t.get(-1.0, 3.0), 20.0);
+        assert_eq!(t.get(4.0, -1.0), 3.0);
+        let t = table(Bounds::Extrapolate);
+        assert_eq!(t.get(-1.0, 3.0), 29.0);
+        assert_eq!(t.get(4.0, -1.0), -6.0);
+    }
+
+    #[test]
+    fn node() {
+        let x = InputNode::from_val(2.0);
+        let y = InputNode::from_val(0.5);
+        let lut = Node::lut2d(x.clone(), y.clone(), table(Bounds::Clamp));
+        assert_eq!(lut.borrow_mut().compute(), 7.0);
+        let grad = Gradient::of(&lut);
+        assert_eq!((grad.wrt(&x), grad.wrt(&y)), (1.0, 10.0));
+
+        x.borrow_mut().set(5.0);
+        assert_eq!(lut.borrow_mut().compute(), 8.0);
+        assert_eq!(Gradient::of(&lut).wrt(&x), 0.0);
+    }
+
+    #[test]
+    fn lookup() {
+        let points = vec![(0.0, 1.0), (1.0, 3.0), (3.0, 2.0)];
+        let x = InputNode::from_val(0.5f32);
+        let clamp = Node::lookup(x.clone(), points.clone(), Bounds::Clamp);
+        let linear = Node::lookup(x.clone(), points.clone(), Bounds::Extrapolate);
+        let strict = Node::lookup(x.clone(), points, Bounds::Error);
+        assert_eq!(clamp.borrow_mut().compute(), 2.0);
+        assert_eq!(Gradient::of(&clamp).wrt(&x), 2.0);
+
+        x.borrow_mut().set(5.0);
+        assert_eq!(clamp.borrow_mut().compute(), 2.0);
+        assert_eq!(Gradient::of(&clamp).wrt(&x), 0.0);
+        assert_eq!(linear.borrow_mut().compute(), 1.0);
+        assert_eq!(Gradient::of(&linear).wrt(&x), -0.5);
+        assert!(strict.borrow_mut().compute().is_nan());
+        assert!(strict
+            .borrow_mut()
+            .try_compute(&mut crate::eval::Evaluation::new())
+            .is_err());
+        x.borrow_mut().set(3.0);
+        assert_eq!(strict.borrow_mut().compute(), 2.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "table axis isn't increasing")]
+    fn unordered_axis() {
+        Table2d::new(vec![1.0, 0.0], vec![0.0, 1.0], vec![0.0; 4], Bounds::Clamp);
+    }
+}
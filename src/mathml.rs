@@ -0,0 +1,1004 @@
+//! Conversion of graphs to and from Content MathML.
+//!
+//! Input nodes are exported as `<ci>` identifiers using caller-provided names,
+//! constants as `<cn>` numbers and operations as `<apply>` elements.
+//! Functions without a Content MathML element are applied as `<csymbol>`.
+//! Shared subgraphs are written out at every place they are used.
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+    input::InputNode,
+    node::{node_addr, Computable, Node},
+    ops::{Operation, Reduction, Rounding},
+    parse::DEFAULT_MAX_DEPTH,
+};
+
+/// Error produced by MathML conversion.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathMlError {
+    /// Node is neither a named input nor an operation node.
+    UnknownNode,
+    /// Document isn't well-formed.
+    Syntax { position: usize, message: String },
+    /// Element has no counterpart among graph operations.
+    Unsupported(String),
+    /// Document is nested deeper than given limit.
+    TooDeep(usize),
+}
+
+impl fmt::Display for MathMlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MathMlError::UnknownNode => write!(f, "node is neither a named input nor an operation"),
+            MathMlError::Syntax { position, message } => {
+                write!(f, "{} at position {}", message, position)
+            }
+            MathMlError::Unsupported(what) => write!(f, "unsupported {}", what),
+            MathMlError::TooDeep(limit) => write!(f, "document nested deeper than {}", limit),
+        }
+    }
+}
+
+impl std::error::Error for MathMlError {}
+
+/// Exports graph with given root as Content MathML document.
+/// `names` maps variable names to input nodes that appear in the graph.
+pub fn to_mathml<C: Computable + ?Sized>(
+    root: &Rc<RefCell<C>>,
+    names: &HashMap<String, Rc<RefCell<InputNode>>>,
+) -> Result<String, MathMlError> {
+    let names: HashMap<usize, &str> = names
+        .iter()
+        .map(|(name, input)| (node_addr(input), name.as_str()))
+        .collect();
+    let mut out = String::from(r#"<math xmlns="http://www.w3.org/1998/Math/MathML">"#);
+    if let Some(name) = names.get(&node_addr(root)) {
+        write_ci(&mut out, name);
+    } else {
+        let opp = root
+            .borrow()
+            .operation()
+            .cloned()
+            .ok_or(MathMlError::UnknownNode)?;
+        write_operation(&mut out, &opp, &names)?;
+    }
+    out.push_str("</math>");
+    Ok(out)
+}
+
+fn write_ci(out: &mut String, name: &str) {
+    out.push_str("<ci>");
+    out.push_str(&escape(name));
+    out.push_str("</ci>");
+}
+
+fn write_cn(out: &mut String, val: f32) {
+    out.push_str("<cn>");
+    out.push_str(&val.to_string());
+    out.push_str("</cn>");
+}
+
+fn write_node(
+    out: &mut String,
+    node: &Rc<RefCell<dyn Computable>>,
+    names: &HashMap<usize, &str>,
+) -> Result<(), MathMlError> {
+    if let Some(name) = names.get(&node_addr(node)) {
+        write_ci(out, name);
+        return Ok(());
+    }
+    let opp = node
+        .borrow()
+        .operation()
+        .cloned()
+        .ok_or(MathMlError::UnknownNode)?;
+    write_operation(out, &opp, names)
+}
+
+fn write_operation(
+    out: &mut String,
+    opp: &Operation,
+    names: &HashMap<usize, &str>,
+) -> Result<(), MathMlError> {
+    use Operation::*;
+
+    let op = match opp {
+        Const(val) => {
+            write_cn(out, *val);
+            return Ok(());
+        }
+        Add(..) | AddVar(_) | Reduce(_, Reduction::Sum) => "plus",
+        Sub(..) | Neg(_) => "minus",
+        Mul(..) | MulVar(_) | Reduce(_, Reduction::Prod) => "times",
+        Div(..) => "divide",
+        Pow(..) => "power",
+        Sin(_) => "sin",
+        Asin(_) => "arcsin",
+        Acos(_) => "arccos",
+        Atan(_) => "arctan",
+        Cos(_) => "cos",
+        Tan(_) => "tan",
+        Exp(_) => "exp",
+        Ln(_) => "ln",
+        Sqrt(_) => "root",
+        Abs(_) => "abs",
+        Tanh(_) => "tanh",
+        Lt(..) => "lt",
+        Le(..) => "leq",
+        Gt(..) => "gt",
+        Ge(..) => "geq",
+        Eq(_, _, tolerance) if *tolerance == 0.0 => "eq",
+        Eq(x, y, tolerance) => {
+            out.push_str("<apply><leq/><apply><abs/><apply><minus/>");
+            write_node(out, x, names)?;
+            write_node(out, y, names)?;
+            out.push_str("</apply></apply>");
+            write_cn(out, *tolerance);
+            out.push_str("</apply>");
+            return Ok(());
+        }
+        Rem(..) => "rem",
+        Min(..) | Reduce(_, Reduction::Min) => "min",
+        Max(..) | Reduce(_, Reduction::Max) => "max",
+        Reduce(_, Reduction::Mean) => "mean",
+        Clamp(x, lo, hi) => {
+            out.push_str("<apply><min/><apply><max/>");
+            write_node(out, x, names)?;
+            write_node(out, lo, names)?;
+            out.push_str("</apply>");
+            write_node(out, hi, names)?;
+            out.push_str("</apply>");
+            return Ok(());
+        }
+        // gradients have no notation, value is the same as of operand
+        Detach(x) => return write_node(out, x, names),
+        Recip(x) => {
+            out.push_str("<apply><divide/><cn>1</cn>");
+            write_node(out, x, names)?;
+            out.push_str("</apply>");
+            return Ok(());
+        }
+        Log(x, base) => {
+            out.push_str("<apply><log/><logbase>");
+            write_cn(out, *base);
+            out.push_str("</logbase>");
+            write_node(out, x, names)?;
+            out.push_str("</apply>");
+            return Ok(());
+        }
+        // no dedicated elements exist, so these are written in expanded form
+        ExpM1(x) => {
+            out.push_str("<apply><minus/><apply><exp/>");
+            write_node(out, x, names)?;
+            out.push_str("</apply><cn>1</cn></apply>");
+            return Ok(());
+        }
+        Ln1p(x) => {
+            out.push_str("<apply><ln/><apply><plus/><cn>1</cn>");
+            write_node(out, x, names)?;
+            out.push_str("</apply></apply>");
+            return Ok(());
+        }
+        Sigmoid(x) => {
+            out.push_str("<apply><divide/><cn>1</cn><apply><plus/><cn>1</cn>");
+            out.push_str("<apply><exp/><apply><minus/>");
+            write_node(out, x, names)?;
+            out.push_str("</apply></apply></apply></apply>");
+            return Ok(());
+        }
+        Relu(x) => {
+            out.push_str("<apply><max/>");
+            write_node(out, x, names)?;
+            out.push_str("<cn>0</cn></apply>");
+            return Ok(());
+        }
+        Softplus(x) => {
+            out.push_str("<apply><ln/><apply><plus/><cn>1</cn><apply><exp/>");
+            write_node(out, x, names)?;
+            out.push_str("</apply></apply></apply>");
+            return Ok(());
+        }
+        Hypot(..) | Norm(_) => {
+            out.push_str("<apply><root/><apply><plus/>");
+            for x in opp.operands() {
+                out.push_str("<apply><power/>");
+                write_node(out, x, names)?;
+                out.push_str("<cn>2</cn></apply>");
+            }
+            out.push_str("</apply></apply>");
+            return Ok(());
+        }
+        Select(cond, x, y) => {
+            out.push_str("<piecewise><piece>");
+            write_node(out, x, names)?;
+            out.push_str("<apply><gt/>");
+            write_node(out, cond, names)?;
+            out.push_str("<cn>0</cn></apply></piece><otherwise>");
+            write_node(out, y, names)?;
+            out.push_str("</otherwise></piecewise>");
+            return Ok(());
+        }
+        // pieces are ordered, the first one whose condition holds is taken
+        Piecewise(x, pieces, default) => {
+            out.push_str("<piecewise>");
+            for (breakpoint, piece) in pieces {
+                out.push_str("<piece>");
+                write_node(out, piece, names)?;
+                out.push_str("<apply><lt/>");
+                write_node(out, x, names)?;
+                write_cn(out, *breakpoint);
+                out.push_str("</apply></piece>");
+            }
+            out.push_str("<otherwise>");
+            write_node(out, default, names)?;
+            out.push_str("</otherwise></piecewise>");
+            return Ok(());
+        }
+        Polynomial(x, coeffs) => {
+            out.push_str("<apply><plus/>");
+            for (i, c) in coeffs.iter().enumerate() {
+                if i == 0 {
+                    write_cn(out, *c);
+                    continue;
+                }
+                out.push_str("<apply><times/>");
+                write_cn(out, *c);
+                out.push_str("<apply><power/>");
+                write_node(out, x, names)?;
+                write_cn(out, i as f32);
+                out.push_str("</apply></apply>");
+            }
+            out.push_str("</apply>");
+            return Ok(());
+        }
+        // `step * round(x / step)`, roundings without dedicated elements are named symbols
+        Quantize(x, step, rounding) => {
+            out.push_str("<apply><times/>");
+            write_cn(out, *step);
+            match rounding {
+                Rounding::Floor => out.push_str("<apply><floor/>"),
+                Rounding::Ceiling => out.push_str("<apply><ceiling/>"),
+                rounding => {
+                    out.push_str("<apply><csymbol>round_");
+                    out.push_str(rounding.name());
+                    out.push_str("</csymbol>");
+                }
+            }
+            out.push_str("<apply><divide/>");
+            write_node(out, x, names)?;
+            write_cn(out, *step);
+            out.push_str("</apply></apply></apply>");
+            return Ok(());
+        }
+        Erf(_) | Erfc(_) | NormalCdf(_) | NormalPdf(_) | LogSumExp(_) | Softmax(..) | Atan2(..)
+        | Sign(_) | Fract(_) | Custom(..) => {
+            let name = match opp {
+                Custom(op, _) => op.0.name(),
+                Sign(_) => "sign",
+                Fract(_) => "fract",
+                Atan2(..) => "atan2",
+                Erf(_) => "erf",
+                Erfc(_) => "erfc",
+                NormalCdf(_) => "normal_cdf",
+                NormalPdf(_) => "normal_pdf",
+                LogSumExp(_) => "logsumexp",
+                _ => "softmax",
+            };
+            out.push_str("<apply><csymbol>");
+            out.push_str(&escape(name));
+            out.push_str("</csymbol>");
+            // softmax component index goes first
+            if let Softmax(_, i) = opp {
+                write_cn(out, *i as f32);
+            }
+            for x in opp.operands() {
+                write_node(out, x, names)?;
+            }
+            out.push_str("</apply>");
+            return Ok(());
+        }
+    };
+    out.push_str("<apply><");
+    out.push_str(op);
+    out.push_str("/>");
+    for x in opp.operands() {
+        write_node(out, x, names)?;
+    }
+    if let Pow(_, pow) = opp {
+        write_cn(out, *pow);
+    }
+    out.push_str("</apply>");
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Parsed XML element.
+#[derive(Debug)]
+struct Element {
+    /// Local name without namespace prefix.
+    name: String,
+    children: Vec<Element>,
+    text: String,
+    /// Offset of element start in document.
+    position: usize,
+}
+
+/// Minimal XML reader supporting elements, text, comments and declarations.
+struct Reader<'a> {
+    doc: &'a str,
+    pos: usize,
+    /// Amount of elements currently open.
+    nesting: usize,
+    max_nesting: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, message: &str) -> MathMlError {
+        MathMlError::Syntax {
+            position: self.pos,
+            message: message.into(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.doc[self.pos..]
+    }
+
+    /// Skips whitespace, comments and processing instructions.
+    fn skip_misc(&mut self) -> Result<(), MathMlError> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            let end = if trimmed.starts_with("<!--") {
+                "-->"
+            } else if trimmed.starts_with("<?") {
+                "?>"
+            } else {
+                return Ok(());
+            };
+            let len = trimmed
+                .find(end)
+                .ok_or_else(|| self.error("unterminated markup"))?;
+            self.pos += len + end.len();
+        }
+    }
+
+    fn element(&mut self) -> Result<Element, MathMlError> {
+        self.nesting += 1;
+        if self.nesting > self.max_nesting {
+            return Err(MathMlError::TooDeep(self.max_nesting - 1));
+        }
+        self.skip_misc()?;
+        let position = self.pos;
+        if !self.rest().starts_with('<') {
+            return Err(self.error("expected element"));
+        }
+        let tag_len = self
+            .rest()
+            .find('>')
+            .ok_or_else(|| self.error("unterminated tag"))?;
+        let tag = &self.rest()[1..tag_len];
+        self.pos += tag_len + 1;
+        let self_closing = tag.ends_with('/');
+        let qname = tag
+            .trim_end_matches('/')
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| self.error("expected element name"))?;
+        let mut element = Element {
+            name: qname.rsplit(':').next().unwrap_or(qname).to_string(),
+            children: Vec::new(),
+            text: String::new(),
+            position,
+        };
+        if self_closing {
+            self.nesting -= 1;
+            return Ok(element);
+        }
+        let close = format!("</{}", qname);
+        loop {
+            let rest = self.rest();
+            let text_len = rest
+                .find('<')
+                .ok_or_else(|| self.error("unclosed element"))?;
+            element.text.push_str(&unescape(&rest[..text_len]));
+            self.pos += text_len;
+            if self.rest().starts_with(&close) {
+                let len = self
+                    .rest()
+                    .find('>')
+                    .ok_or_else(|| self.error("unterminated tag"))?;
+                self.pos += len + 1;
+                element.text = element.text.trim().to_string();
+                self.nesting -= 1;
+                return Ok(element);
+            }
+            if self.rest().starts_with("<!--") || self.rest().starts_with("<?") {
+                self.skip_misc()?;
+                continue;
+            }
+            if self.rest().starts_with("</") {
+                return Err(self.error("mismatched closing tag"));
+            }
+            element.children.push(self.element()?);
+        }
+    }
+}
+
+/// Imports Content MathML document, returning graph root and inputs created for identifiers.
+/// Input nodes are created with value `0.0`.
+/// Documents producing graphs deeper than `DEFAULT_MAX_DEPTH` are rejected.
+#[allow(clippy::type_complexity)]
+pub fn from_mathml(
+    doc: &str,
+) -> Result<
+    (
+        Rc<RefCell<dyn Computable>>,
+        HashMap<String, Rc<RefCell<InputNode>>>,
+    ),
+    MathMlError,
+> {
+    from_mathml_with_limit(doc, DEFAULT_MAX_DEPTH)
+}
+
+/// Imports document like `from_mathml`, rejecting documents producing graphs deeper than
+/// `max_depth` or nesting more elements below the root, so untrusted documents can't
+/// overflow the stack.
+#[allow(clippy::type_complexity)]
+pub fn from_mathml_with_limit(
+    doc: &str,
+    max_depth: usize,
+) -> Result<
+    (
+        Rc<RefCell<dyn Computable>>,
+        HashMap<String, Rc<RefCell<InputNode>>>,
+    ),
+    MathMlError,
+> {
+    // root `math` element wraps expression
+    let mut reader = Reader {
+        doc,
+        pos: 0,
+        nesting: 0,
+        max_nesting: max_depth.saturating_add(1),
+    };
+    let mut root = reader.element()?;
+    reader.skip_misc()?;
+    if reader.pos != doc.len() {
+        return Err(reader.error("unexpected content after root element"));
+    }
+    if root.name == "math" {
+        if root.children.len() != 1 {
+            return Err(MathMlError::Syntax {
+                position: root.position,
+                message: "math element must contain single expression".into(),
+            });
+        }
+        root = root.children.remove(0);
+    }
+    let mut inputs = HashMap::new();
+    let node = build(&root, &mut inputs)?;
+    if node.borrow().depth() > max_depth {
+        return Err(MathMlError::TooDeep(max_depth));
+    }
+    Ok((node, inputs))
+}
+
+fn number(element: &Element) -> Result<f32, MathMlError> {
+    element.text.parse().map_err(|_| MathMlError::Syntax {
+        position: element.position,
+        message: format!("invalid number '{}'", element.text),
+    })
+}
+
+fn build(
+    element: &Element,
+    inputs: &mut HashMap<String, Rc<RefCell<InputNode>>>,
+) -> Result<Rc<RefCell<dyn Computable>>, MathMlError> {
+    match element.name.as_str() {
+        "cn" => Ok(Node::constant(number(element)?)),
+        "piecewise" => {
+            let (x, cond, y) = match element.children.as_slice() {
+                [piece, otherwise] if piece.name == "piece" && otherwise.name == "otherwise" => {
+                    match (piece.children.as_slice(), otherwise.children.as_slice()) {
+                        ([x, cond], [y]) => (x, cond, y),
+                        _ => {
+                            return Err(MathMlError::Syntax {
+                                position: element.position,
+                                message: "malformed piecewise".into(),
+                            })
+                        }
+                    }
+                }
+                _ => {
+                    return Err(MathMlError::Unsupported(
+                        "piecewise with many pieces".into(),
+                    ))
+                }
+            };
+            // only `cond > 0` conditions map onto selection
+            let cond = match cond.children.as_slice() {
+                [gt, cond, zero]
+                    if cond.name != "cn"
+                        && gt.name == "gt"
+                        && zero.name == "cn"
+                        && number(zero)? == 0.0 =>
+                {
+                    cond
+                }
+                _ => return Err(MathMlError::Unsupported("piecewise condition".into())),
+            };
+            Ok(Node::lazy_select(
+                build(cond, inputs)?,
+                build(x, inputs)?,
+                build(y, inputs)?,
+            ))
+        }
+        "ci" => Ok(inputs
+            .entry(element.text.clone())
+            .or_insert_with(|| InputNode::with_label(0.0, element.text.clone()))
+            .clone()),
+        "apply" => {
+            let (op, args) = element.children.split_first().ok_or(MathMlError::Syntax {
+                position: element.position,
+                message: "empty apply".into(),
+            })?;
+            let arity_error = || MathMlError::Syntax {
+                position: element.position,
+                message: format!("wrong amount of arguments for {}", op.name),
+            };
+            let name = if op.name == "csymbol" {
+                op.text.as_str()
+            } else {
+                op.name.as_str()
+            };
+            if op.name == "power" {
+                return match args {
+                    [x, pow] if pow.name == "cn" => Ok(Node::pow(build(x, inputs)?, number(pow)?)),
+                    [_, _] => Err(MathMlError::Unsupported("non-constant exponent".into())),
+                    _ => Err(arity_error()),
+                };
+            }
+            if name == "log" {
+                // base defaults to 10
+                return match args {
+                    [base, x] if base.name == "logbase" => match base.children.as_slice() {
+                        [base] if base.name == "cn" => {
+                            Ok(Node::log(build(x, inputs)?, number(base)?))
+                        }
+                        _ => Err(MathMlError::Unsupported("non-constant base".into())),
+                    },
+                    [x] => Ok(Node::log(build(x, inputs)?, 10.0)),
+                    _ => Err(arity_error()),
+                };
+            }
+            if name == "softmax" {
+                return match args {
+                    [i, _, ..] if i.name == "cn" => {
+                        let i = number(i)?;
+                        if i < 0.0 || i.fract() != 0.0 || i as usize >= args.len() - 1 {
+                            return Err(MathMlError::Syntax {
+                                position: element.position,
+                                message: "invalid softmax component index".into(),
+                            });
+                        }
+                        let args = args[1..]
+                            .iter()
+                            .map(|arg| build(arg, inputs))
+                            .collect::<Result<_, _>>()?;
+                        Ok(Node::softmax_component(args, i as usize))
+                    }
+                    _ => Err(arity_error()),
+                };
+            }
+            let mut args = args
+                .iter()
+                .map(|arg| build(arg, inputs))
+                .collect::<Result<Vec<_>, _>>()?;
+            match (name, args.len()) {
+                ("plus" | "times", 1) => Ok(args.remove(0)),
+                ("plus", 2) => Ok(Node::add(args.remove(0), args.remove(0))),
+                ("plus", n) if n > 2 => Ok(Node::add_var(args)),
+                ("times", 2) => Ok(Node::mul(args.remove(0), args.remove(0))),
+                ("times", n) if n > 2 => Ok(Node::mul_var(args)),
+                ("minus", 1) => Ok(Node::neg(args.remove(0))),
+                ("minus", 2) => Ok(Node::sub(args.remove(0), args.remove(0))),
+                ("divide", 2) => Ok(Node::div(args.remove(0), args.remove(0))),
+                ("sin", 1) => Ok(Node::sin(args.remove(0))),
+                ("arcsin", 1) => Ok(Node::asin(args.remove(0))),
+                ("arccos", 1) => Ok(Node::acos(args.remove(0))),
+                ("arctan", 1) => Ok(Node::atan(args.remove(0))),
+                ("cos", 1) => Ok(Node::cos(args.remove(0))),
+                ("tan", 1) => Ok(Node::tan(args.remove(0))),
+                ("exp", 1) => Ok(Node::exp(args.remove(0))),
+                ("ln", 1) => Ok(Node::ln(args.remove(0))),
+                ("root", 1) => Ok(Node::sqrt(args.remove(0))),
+                ("abs", 1) => Ok(Node::abs(args.remove(0))),
+                ("tanh", 1) => Ok(Node::tanh(args.remove(0))),
+                ("min", 2) => Ok(Node::min(args.remove(0), args.remove(0))),
+                ("min", n) if n > 2 => Ok(Node::reduce(args, Reduction::Min)),
+                ("lt", 2) => Ok(Node::lt(args.remove(0), args.remove(0))),
+                ("leq", 2) => Ok(Node::le(args.remove(0), args.remove(0))),
+                ("gt", 2) => Ok(Node::gt(args.remove(0), args.remove(0))),
+                ("geq", 2) => Ok(Node::ge(args.remove(0), args.remove(0))),
+                ("eq", 2) => Ok(Node::eq(args.remove(0), args.remove(0), 0.0)),
+                ("max", 2) => Ok(Node::max(args.remove(0), args.remove(0))),
+                ("max", n) if n > 2 => Ok(Node::reduce(args, Reduction::Max)),
+                ("mean", n) if n > 0 => Ok(Node::reduce(args, Reduction::Mean)),
+                ("erf", 1) => Ok(Node::erf(args.remove(0))),
+                ("erfc", 1) => Ok(Node::erfc(args.remove(0))),
+                ("normal_cdf", 1) => Ok(Node::normal_cdf(args.remove(0))),
+                ("normal_pdf", 1) => Ok(Node::normal_pdf(args.remove(0))),
+                ("sign", 1) => Ok(Node::sign(args.remove(0))),
+                ("fract", 1) => Ok(Node::fract(args.remove(0))),
+                ("logsumexp", n) if n > 0 => Ok(Node::logsumexp(args)),
+                (
+                    "plus" | "times" | "minus" | "divide" | "sin" | "arcsin" | "arccos" | "arctan",
+                    _,
+                ) => Err(arity_error()),
+                (
+                    "erf" | "erfc" | "normal_cdf" | "normal_pdf" | "logsumexp" | "sign" | "fract",
+                    _,
+                ) => Err(arity_error()),
+                ("cos" | "tan" | "exp" | "ln" | "root" | "abs" | "tanh" | "min" | "max", _)
+                | ("lt" | "leq" | "gt" | "geq" | "eq" | "mean", _) => Err(arity_error()),
+                (name, _) => Err(MathMlError::Unsupported(format!("operator {}", name))),
+            }
+        }
+        name => Err(MathMlError::Unsupported(format!("element {}", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    #[test]
+    fn export() {
+        let x = InputNode::from_val(1.0);
+        let y = InputNode::from_val(2.0);
+        let root = Node::add(
+            x.clone(),
+            Node::mul(y.clone(), Node::pow(Node::sin(x.clone()), 2.0)),
+        );
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        assert_eq!(
+            to_mathml(&root, &names).unwrap(),
+            r#"<math xmlns="http://www.w3.org/1998/Math/MathML">"#.to_string()
+                + "<apply><plus/><ci>x</ci><apply><times/><ci>y</ci>"
+                + "<apply><power/><apply><sin/><ci>x</ci></apply><cn>2</cn></apply>"
+                + "</apply></apply></math>"
+        );
+    }
+
+    #[test]
+    fn export_unnamed_input() {
+        let x = InputNode::from_val(1.0);
+        let root = Node::sin(x);
+        assert_eq!(
+            to_mathml(&root, &HashMap::new()),
+            Err(MathMlError::UnknownNode)
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let root = Node::sub(
+            Node::add_var(vec![x.clone(), y.clone(), Node::constant(1.5)]),
+            Node::sin(y.clone()),
+        );
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        let doc = to_mathml(&root, &names).unwrap();
+
+        let (imported, inputs) = from_mathml(&doc).unwrap();
+        assert_eq!(to_mathml(&imported, &inputs).unwrap(), doc);
+        inputs["x"].borrow_mut().set(2.0);
+        assert_eq!(imported.borrow_mut().compute(), 3.5);
+    }
+
+    #[test]
+    fn inverse_trig_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::add_var(vec![
+            Node::asin(x.clone()),
+            Node::acos(x.clone()),
+            Node::atan(x.clone()),
+        ]);
+        let names = HashMap::from([("x".to_string(), x)]);
+        let doc = to_mathml(&root, &names).unwrap();
+        assert!(
+            doc.contains("<arcsin/>") && doc.contains("<arccos/>") && doc.contains("<arctan/>")
+        );
+
+        let (imported, inputs) = from_mathml(&doc).unwrap();
+        assert_eq!(to_mathml(&imported, &inputs).unwrap(), doc);
+    }
+
+    #[test]
+    fn elementary_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::add_var(vec![
+            Node::cos(x.clone()),
+            Node::tan(x.clone()),
+            Node::ln(Node::exp(x.clone())),
+            Node::log(Node::sqrt(Node::abs(x.clone())), 2.0),
+        ]);
+        let names = HashMap::from([("x".to_string(), x)]);
+        let doc = to_mathml(&root, &names).unwrap();
+        assert!(doc.contains("<apply><log/><logbase><cn>2</cn></logbase><apply><root/>"));
+
+        let (imported, inputs) = from_mathml(&doc).unwrap();
+        assert_eq!(to_mathml(&imported, &inputs).unwrap(), doc);
+
+        // base defaults to 10
+        let (imported, _) = from_mathml("<math><apply><log/><cn>100</cn></apply></math>").unwrap();
+        assert_eq!(imported.borrow_mut().compute(), 2.0);
+    }
+
+    #[test]
+    fn min_max_clamp_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let root = Node::add(
+            Node::min(x.clone(), y.clone()),
+            Node::clamp(x.clone(), y.clone(), Node::constant(1.0)),
+        );
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        let doc = to_mathml(&root, &names).unwrap();
+        assert!(doc.contains("<apply><min/><apply><max/><ci>x</ci><ci>y</ci></apply><cn>1</cn>"));
+
+        // clamp is imported in its expanded form
+        let (imported, inputs) = from_mathml(&doc).unwrap();
+        assert_eq!(to_mathml(&imported, &inputs).unwrap(), doc);
+        inputs["x"].borrow_mut().set(3.0);
+        inputs["y"].borrow_mut().set(-2.0);
+        assert_eq!(imported.borrow_mut().compute(), -1.0);
+    }
+
+    #[test]
+    fn reductions_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let args = || -> Vec<Rc<RefCell<dyn Computable>>> {
+            vec![x.clone(), y.clone(), Node::constant(4.0)]
+        };
+        let root = Node::add_var(vec![
+            Node::mul_var(args()),
+            Node::reduce(args(), Reduction::Max),
+            Node::reduce(args(), Reduction::Mean),
+        ]);
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        let doc = to_mathml(&root, &names).unwrap();
+        assert!(doc.contains("<apply><mean/><ci>x</ci><ci>y</ci><cn>4</cn></apply>"));
+
+        let (imported, inputs) = from_mathml(&doc).unwrap();
+        assert_eq!(to_mathml(&imported, &inputs).unwrap(), doc);
+        inputs["x"].borrow_mut().set(1.0);
+        inputs["y"].borrow_mut().set(-2.0);
+        assert_eq!(imported.borrow_mut().compute(), -8.0 + 4.0 + 1.0);
+    }
+
+    #[test]
+    fn comparisons_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let root = Node::add_var(vec![
+            Node::lt(x.clone(), y.clone()),
+            Node::le(x.clone(), y.clone()),
+            Node::gt(x.clone(), y.clone()),
+            Node::ge(x.clone(), y.clone()),
+            Node::eq(x.clone(), y.clone(), 0.0),
+        ]);
+        let names = HashMap::from([("x".to_string(), x.clone()), ("y".to_string(), y.clone())]);
+        let doc = to_mathml(&root, &names).unwrap();
+        assert!(doc.contains("<apply><leq/><ci>x</ci><ci>y</ci></apply>"));
+        let (imported, inputs) = from_mathml(&doc).unwrap();
+        assert_eq!(to_mathml(&imported, &inputs).unwrap(), doc);
+
+        let root = Node::eq(x, y, 0.5);
+        assert!(to_mathml(&root, &names)
+            .unwrap()
+            .contains("<apply><leq/><apply><abs/><apply><minus/><ci>x</ci><ci>y</ci></apply></apply><cn>0.5</cn></apply>"));
+    }
+
+    #[test]
+    fn export_expanded() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::add(Node::expm1(x.clone()), Node::ln_1p(x.clone()));
+        let names = HashMap::from([("x".to_string(), x)]);
+        assert_eq!(
+            to_mathml(&root, &names).unwrap(),
+            r#"<math xmlns="http://www.w3.org/1998/Math/MathML"><apply><plus/>"#.to_string()
+                + "<apply><minus/><apply><exp/><ci>x</ci></apply><cn>1</cn></apply>"
+                + "<apply><ln/><apply><plus/><cn>1</cn><ci>x</ci></apply></apply>"
+                + "</apply></math>"
+        );
+    }
+
+    #[test]
+    fn export_norm() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let root = Node::hypot(x.clone(), y.clone());
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        assert_eq!(
+            to_mathml(&root, &names).unwrap(),
+            r#"<math xmlns="http://www.w3.org/1998/Math/MathML"><apply><root/><apply><plus/>"#
+                .to_string()
+                + "<apply><power/><ci>x</ci><cn>2</cn></apply>"
+                + "<apply><power/><ci>y</ci><cn>2</cn></apply>"
+                + "</apply></apply></math>"
+        );
+    }
+
+    #[test]
+    fn csymbol_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::add_var(vec![
+            Node::erf(x.clone()),
+            Node::erfc(x.clone()),
+            Node::normal_cdf(x.clone()),
+            Node::normal_pdf(x.clone()),
+        ]);
+        let names = HashMap::from([("x".to_string(), x)]);
+        let doc = to_mathml(&root, &names).unwrap();
+        assert!(doc.contains("<apply><csymbol>erf</csymbol><ci>x</ci></apply>"));
+
+        let (imported, inputs) = from_mathml(&doc).unwrap();
+        assert_eq!(to_mathml(&imported, &inputs).unwrap(), doc);
+    }
+
+    #[test]
+    fn softmax_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let args: Vec<Rc<RefCell<dyn Computable>>> = vec![x.clone(), y.clone()];
+        let root = Node::add(
+            Node::logsumexp(args.clone()),
+            Node::softmax_component(args, 1),
+        );
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        let doc = to_mathml(&root, &names).unwrap();
+        assert!(doc.contains("<apply><csymbol>softmax</csymbol><cn>1</cn><ci>x</ci><ci>y</ci>"));
+
+        let (imported, inputs) = from_mathml(&doc).unwrap();
+        assert_eq!(to_mathml(&imported, &inputs).unwrap(), doc);
+        assert!(
+            from_mathml("<apply><csymbol>softmax</csymbol><cn>1</cn><ci>x</ci></apply>").is_err()
+        );
+    }
+
+    #[test]
+    fn piecewise_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::lazy_select(x.clone(), Node::sin(x.clone()), Node::constant(2.0));
+        let names = HashMap::from([("x".to_string(), x)]);
+        let doc = to_mathml(&root, &names).unwrap();
+        assert!(doc.contains("<piecewise><piece><apply><sin/><ci>x</ci></apply>"));
+
+        let (imported, inputs) = from_mathml(&doc).unwrap();
+        assert_eq!(to_mathml(&imported, &inputs).unwrap(), doc);
+        assert_eq!(imported.borrow_mut().compute(), 2.0);
+        inputs["x"].borrow_mut().set(1.0);
+        assert_eq!(imported.borrow_mut().compute(), 1f32.sin());
+
+        let doc = "<piecewise><piece><cn>1</cn><apply><lt/><ci>x</ci><cn>0</cn></apply></piece>\
+                   <otherwise><cn>2</cn></otherwise></piecewise>";
+        assert_eq!(
+            from_mathml(doc).err(),
+            Some(MathMlError::Unsupported("piecewise condition".into()))
+        );
+    }
+
+    #[test]
+    fn import() {
+        let doc = r#"<?xml version="1.0"?>
+            <m:math xmlns:m="http://www.w3.org/1998/Math/MathML">
+              <!-- a * b * c - (-a) -->
+              <m:apply>
+                <m:minus/>
+                <m:apply><m:times/><m:ci>a</m:ci><m:ci>b</m:ci><m:ci> c </m:ci></m:apply>
+                <m:apply><m:minus/><m:ci>a</m:ci></m:apply>
+              </m:apply>
+            </m:math>"#;
+        let (root, inputs) = from_mathml(doc).unwrap();
+        assert_eq!(inputs.len(), 3);
+        inputs["a"].borrow_mut().set(2.0);
+        inputs["b"].borrow_mut().set(3.0);
+        inputs["c"].borrow_mut().set(4.0);
+        assert_eq!(root.borrow_mut().compute(), 26.0);
+    }
+
+    #[test]
+    fn divide_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::div(Node::constant(1.0), x.clone());
+        let names = HashMap::from([("x".to_string(), x)]);
+        let doc = to_mathml(&root, &names).unwrap();
+        assert!(doc.contains("<apply><divide/><cn>1</cn><ci>x</ci></apply>"));
+
+        let (imported, inputs) = from_mathml(&doc).unwrap();
+        inputs["x"].borrow_mut().set(4.0);
+        assert_eq!(imported.borrow_mut().compute(), 0.25);
+
+        // reciprocal is written as division, negation as unary minus
+        let x = InputNode::from_val(0.0);
+        let root = Node::neg(Node::recip(x.clone()));
+        let names = HashMap::from([("x".to_string(), x)]);
+        let doc = to_mathml(&root, &names).unwrap();
+        assert!(doc.contains("<apply><minus/><apply><divide/><cn>1</cn><ci>x</ci></apply></apply>"));
+        let (imported, _) = from_mathml(&doc).unwrap();
+        assert_eq!(imported.borrow().operation().unwrap().name(), "neg");
+    }
+
+    #[test]
+    fn import_errors() {
+        assert_eq!(
+            from_mathml("<math><apply><factorial/><cn>3</cn></apply></math>").err(),
+            Some(MathMlError::Unsupported("operator factorial".into()))
+        );
+        assert_eq!(
+            from_mathml("<apply><power/><ci>x</ci><ci>y</ci></apply>").err(),
+            Some(MathMlError::Unsupported("non-constant exponent".into()))
+        );
+        assert!(matches!(
+            from_mathml("<apply><sin/><cn>1</cn><cn>2</cn></apply>"),
+            Err(MathMlError::Syntax { .. })
+        ));
+        assert!(matches!(
+            from_mathml("<apply><plus/><cn>1</cn>"),
+            Err(MathMlError::Syntax { .. })
+        ));
+        assert!(matches!(
+            from_mathml("<cn>x</cn>"),
+            Err(MathMlError::Syntax { .. })
+        ));
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nested = |n| {
+            format!(
+                "<math>{}<ci>x</ci>{}</math>",
+                "<apply><minus/>".repeat(n),
+                "</apply>".repeat(n)
+            )
+        };
+        assert_eq!(
+            from_mathml(&nested(2000)).err(),
+            Some(MathMlError::TooDeep(DEFAULT_MAX_DEPTH))
+        );
+        let (root, _) = from_mathml_with_limit(&nested(10), 11).unwrap();
+        assert_eq!(root.borrow().depth(), 11);
+        assert_eq!(
+            from_mathml_with_limit(&nested(10), 10).err(),
+            Some(MathMlError::TooDeep(10))
+        );
+
+        // without `math` element, root expression counts as well
+        let bare = "<apply><minus/><apply><minus/><ci>x</ci></apply></apply>";
+        assert!(from_mathml_with_limit(bare, 3).is_ok());
+        assert_eq!(
+            from_mathml_with_limit(bare, 2).err(),
+            Some(MathMlError::TooDeep(2))
+        );
+    }
+}
@@ -1,45 +1,788 @@
 //! Graph expression node implementation.
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ops::RangeInclusive,
+    rc::{Rc, Weak},
+};
 
-use crate::ops::Operation;
+#[cfg(feature = "counters")]
+use crate::counters::Counters;
+use crate::{
+    error::GraphError,
+    eval::Evaluation,
+    float::Float,
+    input::InputNode,
+    ops::{CustomOp, DivPolicy, Op, Operation, Reduction, Rounding},
+    smallvec::SmallVec,
+    units::{unit_of, Unit},
+};
 
 /// Trait definition ofr computable types.
-pub trait Computable {
+/// Results are of scalar type `T`, which is `f32` by default.
+pub trait Computable<T: Float = f32> {
     /// Computes result of this type.
-    fn compute(&mut self) -> f32;
+    fn compute(&mut self) -> T;
+    /// Computes result like `compute`, obtaining values of operands from `operand`,
+    /// which computes each of them at most once per evaluation, see `Node::compute`.
+    /// Nodes computing their operands themselves ignore it, which is the default.
+    fn compute_with(
+        &mut self,
+        _operand: &mut dyn FnMut(&Rc<RefCell<dyn Computable<T>>>) -> T,
+    ) -> T {
+        self.compute()
+    }
     /// Adds dependency from another `Computable` object.
-    fn add_dependency(&mut self, dependency: Rc<RefCell<dyn Computable>>);
-    /// Resets cache for this node.
+    fn add_dependency(&mut self, dependency: Rc<RefCell<dyn Computable<T>>>);
+    /// Computes result like `try_compute`, obtaining values of operands from `operand`,
+    /// see `compute_with`. Nodes computing their operands themselves ignore it, which is
+    /// the default.
+    fn try_compute_with(
+        &mut self,
+        eval: &mut Evaluation,
+        _operand: &mut TryOperand<'_, T>,
+    ) -> Result<T, GraphError> {
+        self.try_compute(eval)
+    }
+    /// Drops references to operands and returns them, so chains of nodes are freed
+    /// without recursion, see `Drop for Node`. Node can't be computed afterwards.
+    /// Nodes holding no operands, or freeing them otherwise, return none, which is the default.
+    fn take_operands(&mut self) -> Vec<Rc<RefCell<dyn Computable<T>>>> {
+        Vec::new()
+    }
+    /// Resets cache for this node, passing reset on to dependents.
+    /// Nodes which are already dirty should stop there, since their dependents were reset
+    /// along with them and weren't computed since, so repeated updates stay cheap.
     fn reset_cache(&mut self);
+    /// Returns operation computed by this node, if it is an operation node.
+    fn operation(&self) -> Option<&NodeOperation<T>> {
+        None
+    }
+    /// Returns true if node needs recomputation, since it wasn't computed
+    /// or some of its operands changed. Nodes without cache are never dirty.
+    /// Volatile nodes, whose value may change on each computation, should stay dirty
+    /// after computing, so dependents don't cache their values, see `CachePolicy`.
+    fn is_dirty(&self) -> bool {
+        false
+    }
+    /// Returns nodes this node is computed from, operands of its operation by default.
+    /// Custom nodes computed from other nodes should return them, so cycles can be detected.
+    fn operands(&self) -> Vec<Rc<RefCell<dyn Computable<T>>>> {
+        self.operation().map_or_else(Vec::new, |opp| {
+            opp.operands().into_iter().cloned().collect()
+        })
+    }
+    /// Returns amount of nodes along the longest path from this node to a leaf.
+    /// Computed recursively by default, operation nodes store it on construction.
+    fn depth(&self) -> usize {
+        1 + self
+            .operands()
+            .iter()
+            .map(|x| x.borrow().depth())
+            .max()
+            .unwrap_or(0)
+    }
+    /// Returns kind of values computed by this node, numeric by default.
+    fn kind(&self) -> Kind {
+        Kind::Numeric
+    }
+    /// Fills cache with previously computed result, returning `false` for nodes without cache.
+    /// Operands must be cached already, since resets don't pass through nodes without cache.
+    fn seed_cache(&mut self, _val: T) -> bool {
+        false
+    }
+    /// Computes result of this type within limits of given evaluation,
+    /// failing instead of producing invalid values.
+    fn try_compute(&mut self, _eval: &mut Evaluation) -> Result<T, GraphError> {
+        Ok(self.compute())
+    }
+    /// Returns name shown instead of the expression when node is displayed, see `infix`.
+    fn label(&self) -> Option<&str> {
+        None
+    }
+    /// Returns physical unit of values computed by this node, `None` if unknown, see `units`.
+    fn unit(&self) -> Option<Unit> {
+        None
+    }
+    /// Drops reference to dependent node which no longer uses this node as operand.
+    fn remove_dependency(&mut self, _dependency: &Rc<RefCell<dyn Computable<T>>>) {}
+    /// Returns dependent nodes which are still alive, none for nodes not tracking them.
+    fn dependents(&self) -> Vec<Rc<RefCell<dyn Computable<T>>>> {
+        Vec::new()
+    }
+    /// Updates depth stored on construction after depth of operand changed to given one,
+    /// since its operands were replaced. Nodes computing depth on demand do nothing.
+    fn refresh_depth(&mut self, _operand_depth: usize) {}
+    /// Moves stateful node to the next tick, see `temporal`. Other nodes do nothing.
+    fn advance(&mut self) {}
+    /// Makes input reset its dependents on every set, even of unchanged value, see
+    /// `InputNode::count_every_set`. Other nodes do nothing.
+    fn count_every_set(&mut self) {}
+    /// Returns counts of computations and cache hits, `None` for nodes not counting them.
+    #[cfg(feature = "counters")]
+    fn counters(&self) -> Option<Counters> {
+        None
+    }
+}
+
+/// Source of operand values for `Computable::try_compute_with`.
+pub type TryOperand<'a, T = f32> =
+    dyn FnMut(&Rc<RefCell<dyn Computable<T>>>, &mut Evaluation) -> Result<T, GraphError> + 'a;
+
+/// Kind of values computed by node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Arbitrary number.
+    Numeric,
+    /// Truth value, `1.0` for true and `0.0` for false.
+    Boolean,
+}
+
+/// Operation over shared handles to nodes computing values of type `T`.
+pub type NodeOperation<T = f32> = Operation<Handle<T>, T>;
+
+/// Shared handle to node computing values of type `T`.
+pub(crate) type Handle<T = f32> = Rc<RefCell<dyn Computable<T>>>;
+
+/// Access to shared nodes without touching `RefCell` directly.
+/// Conflicting borrows, e.g. from within computation of the same node, produce
+/// `GraphError::BorrowConflict` instead of panicking.
+pub trait NodeHandle<T: Float = f32> {
+    /// Computes result of node.
+    fn compute(&self) -> Result<T, GraphError>;
+    /// Returns cached result, `None` if node needs recomputation.
+    fn value(&self) -> Result<Option<T>, GraphError>;
+}
+
+impl<T: Float, C: Computable<T> + ?Sized> NodeHandle<T> for Rc<RefCell<C>> {
+    fn compute(&self) -> Result<T, GraphError> {
+        let mut node = self
+            .try_borrow_mut()
+            .map_err(|_| GraphError::BorrowConflict { operation: "node" })?;
+        check_borrows(&*node)?;
+        Ok(node.compute())
+    }
+
+    fn value(&self) -> Result<Option<T>, GraphError> {
+        let mut node = self
+            .try_borrow_mut()
+            .map_err(|_| GraphError::BorrowConflict { operation: "node" })?;
+        if node.is_dirty() {
+            return Ok(None);
+        }
+        // computing clean node only returns its cache, unless it computes operands itself
+        check_borrows(&*node)?;
+        Ok(Some(node.compute()))
+    }
+}
+
+/// Fails if some node which computing given one may borrow is already borrowed,
+/// as computation would panic then. Clean operation nodes return their cache, so
+/// their operands aren't checked, while other nodes may compute all their operands.
+fn check_borrows<T: Float, C: Computable<T> + ?Sized>(node: &C) -> Result<(), GraphError> {
+    if !node.is_dirty() && node.operation().is_some() {
+        return Ok(());
+    }
+    let operation = node.operation().map_or("custom", Operation::name);
+    let mut stack: Vec<_> = node
+        .operands()
+        .into_iter()
+        .map(|x| (x, operation))
+        .collect();
+    let mut visited = HashSet::new();
+    while let Some((x, operation)) = stack.pop() {
+        if !visited.insert(node_addr(&x)) {
+            continue;
+        }
+        let x = x
+            .try_borrow_mut()
+            .map_err(|_| GraphError::BorrowConflict { operation })?;
+        if x.is_dirty() || x.operation().is_none() {
+            let operation = x.operation().map_or("custom", Operation::name);
+            stack.extend(x.operands().into_iter().map(|y| (y, operation)));
+        }
+    }
+    Ok(())
+}
+
+/// Returns address identifying node behind shared pointer.
+pub(crate) fn node_addr<C: ?Sized>(node: &Rc<RefCell<C>>) -> usize {
+    Rc::as_ptr(node) as *const () as usize
+}
+
+/// Returns nodes reachable from root once each, with operands preceding nodes using them.
+pub(crate) fn post_order<T: Float>(root: &Handle<T>) -> Vec<Handle<T>> {
+    let mut order = Vec::new();
+    let mut visited = HashSet::new();
+    // nodes along with flag whether their operands were already pushed
+    let mut stack = vec![(root.clone(), false)];
+    while let Some((node, expanded)) = stack.pop() {
+        if expanded {
+            order.push(node);
+            continue;
+        }
+        if !visited.insert(node_addr(&node)) {
+            continue;
+        }
+        let operands = node.borrow().operands();
+        stack.push((node, true));
+        stack.extend(operands.into_iter().map(|x| (x, false)));
+    }
+    order
+}
+
+/// Computes dirty nodes required by given operands in topological order, operands
+/// preceding nodes using them and earlier operands preceding later ones, each once.
+/// Returns computed values by node address.
+pub(crate) fn evaluate<T: Float>(operands: &[&Handle<T>]) -> HashMap<usize, T> {
+    let order = dirty_order(operands);
+    let mut values = HashMap::with_capacity(order.len());
+    for node in order {
+        let val = node
+            .borrow_mut()
+            .compute_with(&mut |x| operand_value(&values, x));
+        values.insert(node_addr(&node), val);
+    }
+    values
+}
+
+/// Returns dirty nodes required by given operands in topological order, see `evaluate`.
+fn dirty_order<T: Float>(operands: &[&Handle<T>]) -> Vec<Handle<T>> {
+    let mut order = Vec::new();
+    let mut visited = HashSet::new();
+    // nodes along with flag whether their operands were already pushed
+    let mut stack: Vec<_> = operands
+        .iter()
+        .rev()
+        .map(|x| ((*x).clone(), false))
+        .collect();
+    while let Some((node, expanded)) = stack.pop() {
+        if expanded {
+            order.push(node);
+            continue;
+        }
+        if !visited.insert(node_addr(&node)) {
+            continue;
+        }
+        // clean nodes return their cache, nodes borrowed along a cycle are left to fail
+        let operands = match node.try_borrow() {
+            Ok(x) if x.is_dirty() => match x.operation() {
+                Some(opp) => opp.eager_operands().into_iter().cloned().collect(),
+                None => x.operands(),
+            },
+            _ => continue,
+        };
+        stack.push((node, true));
+        stack.extend(operands.into_iter().rev().map(|x| (x, false)));
+    }
+    order
+}
+
+/// Computes dirty nodes required by given operands like `evaluate`, but checked within
+/// limits of given evaluation, see `Computable::try_compute`. Fails at the first node
+/// failing to compute. Returns computed values by node address.
+pub(crate) fn try_evaluate<T: Float>(
+    operands: &[&Handle<T>],
+    eval: &mut Evaluation,
+) -> Result<HashMap<usize, T>, GraphError> {
+    let mut values = HashMap::new();
+    for node in dirty_order(operands) {
+        let val = {
+            let mut x = node.borrow_mut();
+            let operation = x.operation().map_or("custom", Operation::name);
+            x.try_compute_with(eval, &mut |y, eval| {
+                try_operand_value(&values, y, eval, operation)
+            })?
+        };
+        values.insert(node_addr(&node), val);
+    }
+    Ok(values)
+}
+
+/// Returns value of operand computed by `try_evaluate`, computing operands it skipped,
+/// failing if operand is borrowed by given operation, e.g. along a cycle.
+pub(crate) fn try_operand_value<T: Float>(
+    values: &HashMap<usize, T>,
+    x: &Handle<T>,
+    eval: &mut Evaluation,
+    operation: &'static str,
+) -> Result<T, GraphError> {
+    match values.get(&node_addr(x)) {
+        Some(val) => Ok(*val),
+        None => match x.try_borrow_mut() {
+            Ok(mut x) => x.try_compute(eval),
+            Err(_) => Err(GraphError::BorrowConflict { operation }),
+        },
+    }
+}
+
+/// Returns value of operand computed by `evaluate`, computing operands it skipped.
+pub(crate) fn operand_value<T: Float>(values: &HashMap<usize, T>, x: &Handle<T>) -> T {
+    match values.get(&node_addr(x)) {
+        Some(val) => *val,
+        None => x.borrow_mut().compute(),
+    }
+}
+
+/// Storage for references to dependent nodes.
+/// Most nodes have one or two dependents, so those are kept inline.
+/// References are weak, as dependents already hold strong references to their operands.
+pub(crate) type Dependencies<T = f32> = SmallVec<Weak<RefCell<dyn Computable<T>>>, 2>;
+
+/// Reset of dependent node, independent of scalar type.
+type Reset = Box<dyn FnOnce()>;
+
+thread_local! {
+    /// Resets of dependents queued by nested `reset_dependencies` calls for the outermost
+    /// one, `None` while no reset is in progress.
+    static RESETS: RefCell<Option<Vec<Reset>>> = RefCell::new(None);
+}
+
+/// Clears queue of resets once the outermost reset is done, even if it panicked.
+struct ResetGuard;
+
+impl Drop for ResetGuard {
+    fn drop(&mut self) {
+        RESETS.with(|resets| *resets.borrow_mut() = None);
+    }
+}
+
+/// Resets caches of dependent nodes, dropping references to already freed ones.
+/// Resets of their dependents are queued rather than nested, so invalidation of
+/// arbitrarily deep graphs doesn't overflow the stack.
+pub(crate) fn reset_dependencies<T: Float>(dependencies: &mut Dependencies<T>) {
+    let mut queued: Vec<Reset> = Vec::new();
+    dependencies.retain(|d| match d.upgrade() {
+        Some(d) => {
+            queued.push(Box::new(move || d.borrow_mut().reset_cache()));
+            true
+        }
+        None => false,
+    });
+    let outermost = RESETS.with(|resets| match &mut *resets.borrow_mut() {
+        Some(pending) => {
+            pending.extend(queued.drain(..).rev());
+            false
+        }
+        resets @ None => {
+            *resets = Some(queued.drain(..).rev().collect());
+            true
+        }
+    });
+    if !outermost {
+        return;
+    }
+    let _guard = ResetGuard;
+    while let Some(reset) = RESETS.with(|resets| resets.borrow_mut().as_mut().and_then(Vec::pop)) {
+        reset();
+    }
+}
+
+/// Returns dependent nodes which are still alive.
+pub(crate) fn dependents<T: Float>(dependencies: &Dependencies<T>) -> Vec<Handle<T>> {
+    dependencies.iter().filter_map(Weak::upgrade).collect()
+}
+
+/// Drops references to given dependent node, along with references to already freed ones.
+pub(crate) fn remove_dependency<T: Float>(
+    dependencies: &mut Dependencies<T>,
+    dependency: &Handle<T>,
+) {
+    let addr = node_addr(dependency);
+    dependencies.retain(|d| d.strong_count() > 0 && d.as_ptr() as *const () as usize != addr);
+}
+
+/// Operation computed by closure over given amount of operand values.
+pub(crate) struct FnOp<F>(pub(crate) F, pub(crate) usize);
+
+impl<T, F: Fn(&[T]) -> T> Op<T> for FnOp<F> {
+    fn name(&self) -> &'static str {
+        "fn"
+    }
+
+    fn eval(&self, operands: &[T]) -> T {
+        (self.0)(operands)
+    }
+
+    fn arity(&self) -> RangeInclusive<usize> {
+        self.1..=self.1
+    }
+}
+
+/// Operation computed by closure over given amount of operand values, with partial
+/// derivatives computed by another closure.
+struct DiffFnOp<F, D>(F, D, usize);
+
+impl<T: Float, F: Fn(&[T]) -> T, D: Fn(&[T]) -> Vec<T>> Op<T> for DiffFnOp<F, D> {
+    fn name(&self) -> &'static str {
+        "fn"
+    }
+
+    fn eval(&self, operands: &[T]) -> T {
+        (self.0)(operands)
+    }
+
+    fn arity(&self) -> RangeInclusive<usize> {
+        self.2..=self.2
+    }
+
+    fn derivative(&self, operands: &[T], i: usize) -> T {
+        (self.1)(operands)[i]
+    }
+
+    fn partials(&self, operands: &[T]) -> Vec<T> {
+        let partials = (self.1)(operands);
+        assert_eq!(
+            partials.len(),
+            operands.len(),
+            "partials closure returned wrong amount of derivatives"
+        );
+        partials
+    }
+}
+
+/// When node keeps its computed result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Result is cached until some operand changes. Results computed from volatile
+    /// operands, which stay dirty after computing, aren't cached.
+    Always,
+    /// Result is recomputed on each computation, for volatile sources like random numbers
+    /// or sensors. Node stays dirty, so dependents aren't cached either.
+    Never,
+    /// Result is cached until `Node::invalidate` is called, changes of operands are ignored.
+    Manual,
 }
 
 /// Graph expression node implementation.
 #[derive(Clone)]
-pub struct Node {
+pub struct Node<T: Float = f32> {
     /// Cached result.
-    cache: Option<f32>,
+    cache: Option<T>,
     /// Holds references to nodes that depend from this node.
-    dependencies: Vec<Rc<RefCell<dyn Computable>>>,
+    dependencies: Dependencies<T>,
     /// Holds operation for this node.
-    opp: Operation,
+    opp: NodeOperation<T>,
+    /// Amount of nodes along the longest path to a leaf.
+    depth: usize,
+    /// Name shown instead of the expression when node is displayed as operand.
+    label: Option<String>,
+    /// When result is cached.
+    policy: CachePolicy,
+    #[cfg(feature = "counters")]
+    counters: Counters,
 }
 
-impl Node {
+impl<T: Float> Node<T> {
+    /// Replaces operation with constant of the last result, dropping references to operands,
+    /// so reference cycles through this node are broken.
+    pub(crate) fn drop_operands(&mut self) {
+        let val = self.cache.unwrap_or(T::ZERO);
+        self.opp = Operation::Const(val);
+        self.cache = Some(val);
+        self.depth = 1;
+    }
+
+    /// Returns label of this node.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Sets label shown instead of the expression when node is displayed as operand.
+    pub fn set_label(&mut self, label: impl Into<String>) {
+        self.label = Some(label.into());
+    }
+
     /// Builds `Node` from given `Operation`.
-    fn from_opp(opp: Operation) -> Rc<RefCell<Node>> {
+    fn from_opp(opp: NodeOperation<T>) -> Rc<RefCell<Node<T>>> {
+        let depth = Self::depth_of(&opp);
         Rc::new(RefCell::new(Self {
             cache: None,
-            dependencies: Vec::default(),
+            dependencies: Dependencies::default(),
             opp,
+            depth,
+            label: None,
+            policy: CachePolicy::Always,
+            #[cfg(feature = "counters")]
+            counters: Counters::default(),
         }))
     }
 
+    /// Returns cache policy of this node.
+    pub fn cache_policy(&self) -> CachePolicy {
+        self.policy
+    }
+
+    /// Sets cache policy of this node, dropping cached result.
+    pub fn set_cache_policy(&mut self, policy: CachePolicy) {
+        self.policy = policy;
+        self.invalidate();
+    }
+
+    /// Drops cached result, resetting caches of dependents, regardless of cache policy.
+    pub fn invalidate(&mut self) {
+        if self.cache.take().is_some() {
+            reset_dependencies(&mut self.dependencies);
+        }
+    }
+
+    /// Replaces coefficients of polynomial node, lowest degree first, resetting
+    /// caches of the node and its dependents.
+    ///
+    /// # Panics
+    ///
+    /// Panics if node isn't built by `Node::polynomial`.
+    pub fn set_coeffs(&mut self, coeffs: Vec<T>) {
+        match &mut self.opp {
+            Operation::Polynomial(_, old) => *old = coeffs,
+            _ => panic!("{} node has no coefficients", self.opp.name()),
+        }
+        self.invalidate();
+    }
+
+    /// Stores computed result, if allowed by cache policy.
+    /// `fresh` tells whether all computed operands stayed cached.
+    fn store(&mut self, val: T, fresh: bool) {
+        match self.policy {
+            CachePolicy::Always if fresh => self.cache = Some(val),
+            CachePolicy::Manual => self.cache = Some(val),
+            _ => {}
+        }
+    }
+
+    /// Returns true if operand with given index may be boolean.
+    /// Booleans can be counted, masked and selected, but not passed to numeric functions.
+    fn accepts_boolean(opp: &NodeOperation<T>, operand: usize) -> bool {
+        match opp {
+            Operation::Add(..)
+            | Operation::AddVar(_)
+            | Operation::Sub(..)
+            | Operation::Mul(..)
+            | Operation::MulVar(_)
+            | Operation::Min(..)
+            | Operation::Max(..)
+            | Operation::Eq(..)
+            | Operation::Reduce(..)
+            | Operation::Detach(_)
+            | Operation::Select(..) => true,
+            Operation::Div(..) => operand == 0,
+            Operation::Piecewise(..) => operand > 0,
+            Operation::Custom(op, _) => op.0.accepts_boolean(operand),
+            _ => false,
+        }
+    }
+
+    /// Stores new depth, updating depths of dependents if it changed.
+    fn set_depth(&mut self, depth: usize) {
+        if depth == self.depth {
+            return;
+        }
+        self.depth = depth;
+        for d in self.dependencies.iter().filter_map(Weak::upgrade) {
+            d.borrow_mut().refresh_depth(depth);
+        }
+    }
+
+    /// Returns depth of node computing given operation.
+    fn depth_of(opp: &NodeOperation<T>) -> usize {
+        1 + opp
+            .operands()
+            .iter()
+            .map(|x| x.borrow().depth())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Builds `Node` computing given operation and registers it as dependency of its operands.
+    pub fn new(opp: NodeOperation<T>) -> Rc<RefCell<Node<T>>> {
+        let operands: Vec<_> = opp.operands().into_iter().cloned().collect();
+        let obj = Self::from_opp(opp);
+        operands
+            .iter()
+            .for_each(|x| x.borrow_mut().add_dependency(obj.clone()));
+        obj
+    }
+
+    /// Builds `Node` like `new`, failing if resulting graph would be deeper than `max_depth`,
+    /// if boolean operand is used where only numbers make sense, like `sin` of a boolean,
+    /// if operands have inconsistent units, see `units`, or if user-defined operation
+    /// doesn't accept amount of its operands, see `Op::arity`.
+    /// Computation, invalidation and dropping don't recurse through operation nodes,
+    /// but lazy branches and custom nodes may, so depth is still worth limiting.
+    pub fn try_new(
+        opp: NodeOperation<T>,
+        max_depth: usize,
+    ) -> Result<Rc<RefCell<Node<T>>>, GraphError> {
+        if let Operation::Custom(op, args) = &opp {
+            let arity = op.0.arity();
+            if !arity.contains(&args.len()) {
+                return Err(GraphError::Arity {
+                    operation: op.0.name(),
+                    min: *arity.start(),
+                    max: *arity.end(),
+                    actual: args.len(),
+                });
+            }
+        }
+        for (i, x) in opp.operands().into_iter().enumerate() {
+            if x.borrow().kind() == Kind::Boolean && !Self::accepts_boolean(&opp, i) {
+                return Err(GraphError::BooleanOperand {
+                    operation: opp.name(),
+                    operand: i,
+                });
+            }
+        }
+        unit_of(&opp, |x| x.borrow().unit())?;
+        let depth = Self::depth_of(&opp);
+        if depth > max_depth {
+            return Err(GraphError::TooDeep {
+                depth,
+                limit: max_depth,
+            });
+        }
+        Ok(Self::new(opp))
+    }
+
+    /// Replaces every use of `old` as operand of given node with `new`, returning amount
+    /// of replaced operands. Dependency lists of both operands are updated and caches
+    /// of the node and its dependents are reset. Fails without changes if `new`
+    /// is computed from the node, as that would form a cycle.
+    pub fn replace_operand(
+        node: &Rc<RefCell<Node<T>>>,
+        old: &Handle<T>,
+        new: Handle<T>,
+    ) -> Result<usize, GraphError> {
+        let this: Handle<T> = node.clone();
+        if post_order(&new)
+            .iter()
+            .any(|x| node_addr(x) == node_addr(&this))
+        {
+            return Err(GraphError::Cycle {
+                operations: vec![node.borrow().opp.name()],
+            });
+        }
+        let mut replaced = 0;
+        let opp = node.borrow().opp.map_operands(|x| {
+            if node_addr(x) == node_addr(old) {
+                replaced += 1;
+                new.clone()
+            } else {
+                x.clone()
+            }
+        });
+        if replaced == 0 {
+            return Ok(0);
+        }
+        let depth = Self::depth_of(&opp);
+        node.borrow_mut().opp = opp;
+        old.borrow_mut().remove_dependency(&this);
+        // registered once per use, like by `new`
+        for _ in 0..replaced {
+            new.borrow_mut().add_dependency(this.clone());
+        }
+        let mut node = node.borrow_mut();
+        node.set_depth(depth);
+        node.invalidate();
+        Ok(replaced)
+    }
+
+    /// Builds input node with given value, same as `InputNode::from_val`.
+    pub fn input(val: T) -> Rc<RefCell<InputNode<T>>> {
+        InputNode::from_val(val)
+    }
+
+    /// Builds `Node` computing user-defined operation over given nodes.
+    pub fn from_op(
+        op: Box<dyn Op<T> + Send + Sync>,
+        operands: Vec<Rc<RefCell<dyn Computable<T>>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::new(Operation::Custom(CustomOp(op.into()), operands))
+    }
+
+    /// Builds `Node` computing user-defined operation like `from_op`, failing if operation
+    /// doesn't accept amount or kinds of given operands, see `try_new`.
+    pub fn try_from_op(
+        op: Box<dyn Op<T> + Send + Sync>,
+        operands: Vec<Rc<RefCell<dyn Computable<T>>>>,
+    ) -> Result<Rc<RefCell<Node<T>>>, GraphError> {
+        Self::try_new(Operation::Custom(CustomOp(op.into()), operands), usize::MAX)
+    }
+
+    /// Builds `Node` applying closure to value of given node.
+    pub fn unary_fn(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        f: impl Fn(T) -> T + Send + Sync + 'static,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::nary_fn(vec![x], move |vals| f(vals[0]))
+    }
+
+    /// Builds `Node` applying closure to values of given nodes.
+    pub fn binary_fn(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+        f: impl Fn(T, T) -> T + Send + Sync + 'static,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::nary_fn(vec![x, y], move |vals| f(vals[0], vals[1]))
+    }
+
+    /// Builds `Node` applying closure to values of variable amount of nodes.
+    pub fn nary_fn(
+        args: Vec<Rc<RefCell<dyn Computable<T>>>>,
+        f: impl Fn(&[T]) -> T + Send + Sync + 'static,
+    ) -> Rc<RefCell<Node<T>>> {
+        let n = args.len();
+        Self::from_op(Box::new(FnOp(f, n)), args)
+    }
+
+    /// Builds `Node` applying closure `f` to value of given node, with derivative
+    /// given by closure `df`, so gradients don't rely on numeric estimation.
+    pub fn unary_fn_with_derivative(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        f: impl Fn(T) -> T + Send + Sync + 'static,
+        df: impl Fn(T) -> T + Send + Sync + 'static,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::nary_fn_with_partials(
+            vec![x],
+            move |vals| f(vals[0]),
+            move |vals| vec![df(vals[0])],
+        )
+    }
+
+    /// Builds `Node` applying closure `f` to values of given nodes, with partial derivatives
+    /// with respect to both given by closure `df`.
+    pub fn binary_fn_with_partials(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+        f: impl Fn(T, T) -> T + Send + Sync + 'static,
+        df: impl Fn(T, T) -> (T, T) + Send + Sync + 'static,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::nary_fn_with_partials(
+            vec![x, y],
+            move |vals| f(vals[0], vals[1]),
+            move |vals| {
+                let (dx, dy) = df(vals[0], vals[1]);
+                vec![dx, dy]
+            },
+        )
+    }
+
+    /// Builds `Node` applying closure `f` to values of variable amount of nodes,
+    /// with partial derivatives with respect to each of them given by closure `partials`.
+    pub fn nary_fn_with_partials(
+        args: Vec<Rc<RefCell<dyn Computable<T>>>>,
+        f: impl Fn(&[T]) -> T + Send + Sync + 'static,
+        partials: impl Fn(&[T]) -> Vec<T> + Send + Sync + 'static,
+    ) -> Rc<RefCell<Node<T>>> {
+        let n = args.len();
+        Self::from_op(Box::new(DiffFnOp(f, partials, n)), args)
+    }
+
     /// Builds `Node` for sum of two nodes.
     pub fn add(
-        x: Rc<RefCell<dyn Computable>>,
-        y: Rc<RefCell<dyn Computable>>,
-    ) -> Rc<RefCell<Node>> {
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
         let obj = Self::from_opp(Operation::Add(x.clone(), y.clone()));
         x.borrow_mut().add_dependency(obj.clone());
         y.borrow_mut().add_dependency(obj.clone());
@@ -47,18 +790,36 @@ impl Node {
     }
 
     /// Builds `Node` for sum of variable amount of nodes.
-    pub fn add_var(args: Vec<Rc<RefCell<dyn Computable>>>) -> Rc<RefCell<Node>> {
+    pub fn add_var(args: Vec<Rc<RefCell<dyn Computable<T>>>>) -> Rc<RefCell<Node<T>>> {
         let obj = Self::from_opp(Operation::AddVar(args.clone()));
         args.iter()
             .for_each(|arg| arg.borrow_mut().add_dependency(obj.clone()));
         obj
     }
 
+    /// Builds `Node` for product of variable amount of nodes.
+    pub fn mul_var(args: Vec<Rc<RefCell<dyn Computable<T>>>>) -> Rc<RefCell<Node<T>>> {
+        Self::new(Operation::MulVar(args))
+    }
+
+    /// Builds `Node` reducing variable amount of nodes to single value.
+    /// Sums and products are built as `add_var` and `mul_var`.
+    pub fn reduce(
+        args: Vec<Rc<RefCell<dyn Computable<T>>>>,
+        reduction: Reduction,
+    ) -> Rc<RefCell<Node<T>>> {
+        match reduction {
+            Reduction::Sum => Self::add_var(args),
+            Reduction::Prod => Self::mul_var(args),
+            _ => Self::new(Operation::Reduce(args, reduction)),
+        }
+    }
+
     /// Builds `Node` for subtraction node of two nodes.
     pub fn sub(
-        x: Rc<RefCell<dyn Computable>>,
-        y: Rc<RefCell<dyn Computable>>,
-    ) -> Rc<RefCell<Node>> {
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
         let obj = Self::from_opp(Operation::Sub(x.clone(), y.clone()));
         x.borrow_mut().add_dependency(obj.clone());
         y.borrow_mut().add_dependency(obj.clone());
@@ -67,57 +828,713 @@ impl Node {
 
     /// Builds `Node` for multiplication of two nodes.
     pub fn mul(
-        x: Rc<RefCell<dyn Computable>>,
-        y: Rc<RefCell<dyn Computable>>,
-    ) -> Rc<RefCell<Node>> {
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
         let obj = Self::from_opp(Operation::Mul(x.clone(), y.clone()));
         x.borrow_mut().add_dependency(obj.clone());
         y.borrow_mut().add_dependency(obj.clone());
         obj
     }
 
+    /// Builds `Node` for division of two nodes, producing `inf` or `NaN` on division by zero.
+    pub fn div(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::div_with_policy(x, y, DivPolicy::default())
+    }
+
+    /// Builds `Node` for division of two nodes with given division by zero policy.
+    pub fn div_with_policy(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+        policy: DivPolicy,
+    ) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Div(x.clone(), y.clone(), policy));
+        x.borrow_mut().add_dependency(obj.clone());
+        y.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` for smaller of two nodes.
+    pub fn min(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Min(x.clone(), y.clone()));
+        x.borrow_mut().add_dependency(obj.clone());
+        y.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` for larger of two nodes.
+    pub fn max(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Max(x.clone(), y.clone()));
+        x.borrow_mut().add_dependency(obj.clone());
+        y.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` limiting value of node to range between values of `lo` and `hi`.
+    /// Upper bound wins if bounds are swapped.
+    pub fn clamp(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        lo: Rc<RefCell<dyn Computable<T>>>,
+        hi: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Clamp(x.clone(), lo.clone(), hi.clone()));
+        x.borrow_mut().add_dependency(obj.clone());
+        lo.borrow_mut().add_dependency(obj.clone());
+        hi.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` for length of hypotenuse with legs of two nodes.
+    pub fn hypot(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Hypot(x.clone(), y.clone()));
+        x.borrow_mut().add_dependency(obj.clone());
+        y.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` for angle of point with coordinates computed by two nodes, `y` first.
+    pub fn atan2(
+        y: Rc<RefCell<dyn Computable<T>>>,
+        x: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Atan2(y.clone(), x.clone()));
+        y.borrow_mut().add_dependency(obj.clone());
+        x.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` for remainder of division of first node by second, with sign of the first.
+    pub fn rem(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Rem(x.clone(), y.clone()));
+        x.borrow_mut().add_dependency(obj.clone());
+        y.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` for Euclidean norm of variable amount of nodes.
+    pub fn norm(args: Vec<Rc<RefCell<dyn Computable<T>>>>) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Norm(args.clone()));
+        args.iter()
+            .for_each(|arg| arg.borrow_mut().add_dependency(obj.clone()));
+        obj
+    }
+
+    /// Builds `Node` for logarithm of sum of exponents of variable amount of nodes.
+    pub fn logsumexp(args: Vec<Rc<RefCell<dyn Computable<T>>>>) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::LogSumExp(args.clone()));
+        args.iter()
+            .for_each(|arg| arg.borrow_mut().add_dependency(obj.clone()));
+        obj
+    }
+
+    /// Builds `Node` for component `i` of softmax over variable amount of nodes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of `args` bounds.
+    pub fn softmax_component(
+        args: Vec<Rc<RefCell<dyn Computable<T>>>>,
+        i: usize,
+    ) -> Rc<RefCell<Node<T>>> {
+        assert!(i < args.len(), "softmax component index out of bounds");
+        let obj = Self::from_opp(Operation::Softmax(args.clone(), i));
+        args.iter()
+            .for_each(|arg| arg.borrow_mut().add_dependency(obj.clone()));
+        obj
+    }
+
+    /// Builds `Node` selecting `x` if `cond` is positive and `y` otherwise.
+    /// Only the taken branch is computed, cache of the other one stays untouched.
+    pub fn lazy_select(
+        cond: Rc<RefCell<dyn Computable<T>>>,
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Select(cond.clone(), x.clone(), y.clone()));
+        cond.borrow_mut().add_dependency(obj.clone());
+        x.borrow_mut().add_dependency(obj.clone());
+        y.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` taking value of the first piece whose breakpoint is greater than `x`,
+    /// or of `default` if there's no such piece. Only the taken piece is computed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if breakpoints aren't increasing.
+    pub fn piecewise(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        pieces: &[(T, Handle<T>)],
+        default: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        assert!(
+            pieces.windows(2).all(|w| w[0].0 < w[1].0),
+            "breakpoints of piecewise aren't increasing"
+        );
+        let obj = Self::from_opp(Operation::Piecewise(
+            x.clone(),
+            pieces.to_vec(),
+            default.clone(),
+        ));
+        x.borrow_mut().add_dependency(obj.clone());
+        for (_, piece) in pieces {
+            piece.borrow_mut().add_dependency(obj.clone());
+        }
+        default.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` selecting `if_true` if boolean `cond` is true and `if_false` otherwise,
+    /// same as `lazy_select`. Only the taken branch is computed.
+    pub fn select(
+        cond: Rc<RefCell<dyn Computable<T>>>,
+        if_true: Rc<RefCell<dyn Computable<T>>>,
+        if_false: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::lazy_select(cond, if_true, if_false)
+    }
+
+    /// Builds `Node` computing logical conjunction of booleans, short-circuiting:
+    /// `y` is computed only if `x` is true, otherwise `x` is returned.
+    pub fn and(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::lazy_select(x.clone(), y, x)
+    }
+
+    /// Builds `Node` computing logical disjunction of booleans, short-circuiting:
+    /// `y` is computed only if `x` is false, otherwise `x` is returned.
+    pub fn or(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::lazy_select(x.clone(), x, y)
+    }
+
+    /// Builds boolean `Node` checking whether `x` is less than `y`.
+    pub fn lt(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::comparison(Operation::Lt(x.clone(), y.clone()), x, y)
+    }
+
+    /// Builds boolean `Node` checking whether `x` is less than or equal to `y`.
+    pub fn le(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::comparison(Operation::Le(x.clone(), y.clone()), x, y)
+    }
+
+    /// Builds boolean `Node` checking whether `x` is greater than `y`.
+    pub fn gt(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::comparison(Operation::Gt(x.clone(), y.clone()), x, y)
+    }
+
+    /// Builds boolean `Node` checking whether `x` is greater than or equal to `y`.
+    pub fn ge(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::comparison(Operation::Ge(x.clone(), y.clone()), x, y)
+    }
+
+    /// Builds boolean `Node` checking whether `x` and `y` differ at most by `tolerance`.
+    pub fn eq(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+        tolerance: T,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::comparison(Operation::Eq(x.clone(), y.clone(), tolerance), x, y)
+    }
+
+    fn comparison(
+        opp: NodeOperation<T>,
+        x: Rc<RefCell<dyn Computable<T>>>,
+        y: Rc<RefCell<dyn Computable<T>>>,
+    ) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(opp);
+        x.borrow_mut().add_dependency(obj.clone());
+        y.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` returning `value` if `cond` is positive and `fallback` otherwise.
+    /// `value` isn't computed while `cond` isn't positive, which protects subgraphs
+    /// undefined for some inputs.
+    pub fn guard(
+        cond: Rc<RefCell<dyn Computable<T>>>,
+        value: Rc<RefCell<dyn Computable<T>>>,
+        fallback: T,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::lazy_select(cond, value, Self::constant(fallback))
+    }
+
     /// Builds `Node` for exponentiation of node to given exponent.
-    pub fn pow(x: Rc<RefCell<dyn Computable>>, pow: f32) -> Rc<RefCell<Node>> {
+    pub fn pow(x: Rc<RefCell<dyn Computable<T>>>, pow: T) -> Rc<RefCell<Node<T>>> {
         let obj = Self::from_opp(Operation::Pow(x.clone(), pow));
         x.borrow_mut().add_dependency(obj.clone());
         obj
     }
 
+    /// Builds `Node` for unary operation over given node.
+    fn unary(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        opp: fn(Handle<T>) -> NodeOperation<T>,
+    ) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(opp(x.clone()));
+        x.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` for negation of given node.
+    pub fn neg(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Neg)
+    }
+
+    /// Builds `Node` for reciprocal of given node.
+    pub fn recip(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Recip)
+    }
+
+    /// Builds `Node` passing value of given node through, but not its gradient,
+    /// so the node is treated as constant by `grad`.
+    pub fn detach(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Detach)
+    }
+
     /// Builds `Node` for sin value of given node.
-    pub fn sin(x: Rc<RefCell<dyn Computable>>) -> Rc<RefCell<Node>> {
-        let obj = Self::from_opp(Operation::Sin(x.clone()));
+    pub fn sin(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Sin)
+    }
+
+    /// Builds `Node` for arcsine of given node.
+    pub fn asin(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Asin)
+    }
+
+    /// Builds `Node` for arccosine of given node.
+    pub fn acos(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Acos)
+    }
+
+    /// Builds `Node` for arctangent of given node.
+    pub fn atan(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Atan)
+    }
+
+    /// Builds `Node` for cosine of given node.
+    pub fn cos(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Cos)
+    }
+
+    /// Builds `Node` for tangent of given node.
+    pub fn tan(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Tan)
+    }
+
+    /// Builds `Node` for exponent of given node.
+    pub fn exp(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Exp)
+    }
+
+    /// Builds `Node` for natural logarithm of given node.
+    pub fn ln(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Ln)
+    }
+
+    /// Builds `Node` for logarithm of given node to given base.
+    pub fn log(x: Rc<RefCell<dyn Computable<T>>>, base: T) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Log(x.clone(), base));
+        x.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` rounding given node to multiple of `step` with given rounding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if step isn't positive and finite.
+    pub fn quantize(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        step: T,
+        rounding: Rounding,
+    ) -> Rc<RefCell<Node<T>>> {
+        assert!(
+            step > T::ZERO && step.is_finite(),
+            "quantization step isn't positive"
+        );
+        let obj = Self::from_opp(Operation::Quantize(x.clone(), step, rounding));
+        x.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Builds `Node` computing polynomial of given node with given coefficients,
+    /// lowest degree first, as a single operation evaluated with Horner's method.
+    pub fn polynomial(x: Rc<RefCell<dyn Computable<T>>>, coeffs: Vec<T>) -> Rc<RefCell<Node<T>>> {
+        let obj = Self::from_opp(Operation::Polynomial(x.clone(), coeffs));
         x.borrow_mut().add_dependency(obj.clone());
         obj
     }
+
+    /// Builds `Node` rounding given node to given amount of decimal places with given
+    /// rounding, negative amounts round to tens, hundreds and so on.
+    pub fn round_dp(
+        x: Rc<RefCell<dyn Computable<T>>>,
+        decimals: i32,
+        rounding: Rounding,
+    ) -> Rc<RefCell<Node<T>>> {
+        Self::quantize(x, T::from_f64(10f64.powi(-decimals)), rounding)
+    }
+
+    /// Builds `Node` for square root of given node.
+    pub fn sqrt(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Sqrt)
+    }
+
+    /// Builds `Node` for absolute value of given node.
+    pub fn abs(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Abs)
+    }
+
+    /// Builds `Node` for sign of given node, `-1`, `0` or `1`.
+    /// Its derivative is zero, also at zero where it jumps.
+    pub fn sign(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Sign)
+    }
+
+    /// Builds `Node` for fractional part of given node, with sign of the node.
+    /// Its derivative is one, also at integers where it jumps.
+    pub fn fract(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Fract)
+    }
+
+    /// Builds `Node` for largest integer not greater than given node, see `quantize`.
+    /// Its derivative is zero, also at integers where it jumps.
+    pub fn floor(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::quantize(x, T::ONE, Rounding::Floor)
+    }
+
+    /// Builds `Node` for smallest integer not less than given node, see `quantize`.
+    /// Its derivative is zero, also at integers where it jumps.
+    pub fn ceil(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::quantize(x, T::ONE, Rounding::Ceiling)
+    }
+
+    /// Builds `Node` for given node rounded to nearest integer, ties away from zero,
+    /// see `quantize`. Its derivative is zero, also at half-integers where it jumps.
+    pub fn round(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::quantize(x, T::ONE, Rounding::HalfAwayFromZero)
+    }
+
+    /// Builds `Node` for hyperbolic tangent of given node.
+    pub fn tanh(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Tanh)
+    }
+
+    /// Builds `Node` for logistic function of given node.
+    pub fn sigmoid(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Sigmoid)
+    }
+
+    /// Builds `Node` for rectified linear unit `max(x, 0)` of given node.
+    pub fn relu(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Relu)
+    }
+
+    /// Builds `Node` for softplus `ln(1 + exp(x))` of given node.
+    pub fn softplus(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Softplus)
+    }
+
+    /// Builds `Node` for `exp(x) - 1` of given node.
+    pub fn expm1(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::ExpM1)
+    }
+
+    /// Builds `Node` for `ln(1 + x)` of given node.
+    pub fn ln_1p(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Ln1p)
+    }
+
+    /// Builds `Node` for error function of given node.
+    pub fn erf(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Erf)
+    }
+
+    /// Builds `Node` for complementary error function of given node.
+    pub fn erfc(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::Erfc)
+    }
+
+    /// Builds `Node` for standard normal cumulative distribution function of given node.
+    pub fn normal_cdf(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::NormalCdf)
+    }
+
+    /// Builds `Node` for standard normal probability density function of given node.
+    pub fn normal_pdf(x: Rc<RefCell<dyn Computable<T>>>) -> Rc<RefCell<Node<T>>> {
+        Self::unary(x, Operation::NormalPdf)
+    }
 }
 
-impl Computable for Node {
+impl<T: Float> Computable<T> for Node<T> {
     /// Returns computation result of this node.
     /// Takes cached value if available, otherwise computes the result and stores it in cache.
-    fn compute(&mut self) -> f32 {
-        self.cache.unwrap_or({
-            let val = self.opp.compute();
-            self.cache = Some(val);
+    /// Dirty nodes the result depends on are computed first, without recursion, in
+    /// topological order: operands before nodes using them, earlier operands first.
+    /// Each of them is computed exactly once, even if it doesn't cache its result.
+    /// Branches of lazy operations are computed only once selected.
+    fn compute(&mut self) -> T {
+        let values = match self.cache {
+            Some(_) => HashMap::new(),
+            None => evaluate(&self.opp.eager_operands()),
+        };
+        self.compute_with(&mut |x| operand_value(&values, x))
+    }
+
+    fn compute_with(&mut self, operand: &mut dyn FnMut(&Handle<T>) -> T) -> T {
+        if let Some(val) = self.cache {
+            #[cfg(feature = "counters")]
+            {
+                self.counters.cached += 1;
+            }
+            return val;
+        }
+        #[cfg(feature = "counters")]
+        {
+            self.counters.computed += 1;
+        }
+        let mut fresh = true;
+        let val = self.opp.eval(|x| {
+            let val = operand(x);
+            fresh &= !x.borrow().is_dirty();
             val
-        })
+        });
+        self.store(val, fresh);
+        val
     }
 
     /// Adds dependency from another `Computable` object.
-    fn add_dependency(&mut self, dependency: Rc<RefCell<dyn Computable>>) {
-        self.dependencies.push(dependency);
+    fn add_dependency(&mut self, dependency: Rc<RefCell<dyn Computable<T>>>) {
+        self.dependencies.push(Rc::downgrade(&dependency));
     }
 
     /// Resets cache for this node and all the dependable nodes.
+    /// Stops at nodes without cache, as their dependents can't be cached either.
+    /// Nodes with manual cache policy keep their cache, see `invalidate`.
     fn reset_cache(&mut self) {
-        self.cache = None;
-        self.dependencies
+        if self.policy != CachePolicy::Manual {
+            self.invalidate();
+        }
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Handle<T>> {
+        dependents(&self.dependencies)
+    }
+
+    /// Recomputes depth from operands, passing change on to dependents.
+    /// Operand which changed is borrowed during the call, so its depth is given.
+    fn refresh_depth(&mut self, operand_depth: usize) {
+        let depth = 1 + self
+            .opp
+            .operands()
             .iter()
-            .for_each(|d| d.borrow_mut().reset_cache());
+            .filter_map(|x| x.try_borrow().ok().map(|x| x.depth()))
+            .fold(operand_depth, usize::max);
+        self.set_depth(depth);
+    }
+
+    /// Returns true if result isn't cached.
+    fn is_dirty(&self) -> bool {
+        self.cache.is_none()
+    }
+
+    fn seed_cache(&mut self, val: T) -> bool {
+        if self.policy == CachePolicy::Never {
+            return false;
+        }
+        self.cache = Some(val);
+        true
+    }
+
+    /// Returns operation of this node.
+    fn operation(&self) -> Option<&NodeOperation<T>> {
+        Some(&self.opp)
+    }
+
+    /// Returns depth computed on construction.
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns unit combined from units of operands, `None` if they're inconsistent.
+    fn unit(&self) -> Option<Unit> {
+        unit_of(&self.opp, |x| x.borrow().unit()).ok().flatten()
+    }
+
+    /// Returns boolean for products and selections of booleans, numeric otherwise.
+    fn kind(&self) -> Kind {
+        let boolean = |x: &Handle<T>| x.borrow().kind() == Kind::Boolean;
+        match &self.opp {
+            Operation::Detach(x) => x.borrow().kind(),
+            Operation::Mul(x, y)
+            | Operation::Min(x, y)
+            | Operation::Max(x, y)
+            | Operation::Select(_, x, y)
+                if boolean(x) && boolean(y) =>
+            {
+                Kind::Boolean
+            }
+            Operation::MulVar(args)
+            | Operation::Reduce(args, Reduction::Prod | Reduction::Min | Reduction::Max)
+                if !args.is_empty() && args.iter().all(boolean) =>
+            {
+                Kind::Boolean
+            }
+            Operation::Piecewise(_, pieces, default)
+                if boolean(default) && pieces.iter().all(|(_, piece)| boolean(piece)) =>
+            {
+                Kind::Boolean
+            }
+            Operation::Lt(..)
+            | Operation::Le(..)
+            | Operation::Gt(..)
+            | Operation::Ge(..)
+            | Operation::Eq(..) => Kind::Boolean,
+            _ => Kind::Numeric,
+        }
+    }
+
+    /// Returns cached result or computes it with checked operands.
+    /// Fails at the first node producing `NaN` or infinity, nothing is cached for failed
+    /// nodes, so evaluation can be retried later. Dirty nodes are computed without
+    /// recursion, in the same order as by `compute`.
+    /// Non-finite values cached by `compute` are recomputed to find where they originate.
+    fn try_compute(&mut self, eval: &mut Evaluation) -> Result<T, GraphError> {
+        let values = match self.cache {
+            Some(val) if val.is_finite() => HashMap::new(),
+            _ => try_evaluate(&self.opp.eager_operands(), eval)?,
+        };
+        let operation = self.opp.name();
+        self.try_compute_with(eval, &mut |x, eval| {
+            try_operand_value(&values, x, eval, operation)
+        })
+    }
+
+    fn try_compute_with(
+        &mut self,
+        eval: &mut Evaluation,
+        operand: &mut TryOperand<'_, T>,
+    ) -> Result<T, GraphError> {
+        if let Some(val) = self.cache.filter(|val| val.is_finite()) {
+            #[cfg(feature = "counters")]
+            {
+                self.counters.cached += 1;
+            }
+            eval.cache_hit(self.opp.name());
+            return Ok(val);
+        }
+        eval.tick(&self.opp)?;
+        #[cfg(feature = "counters")]
+        {
+            self.counters.computed += 1;
+        }
+        let operation = self.opp.name();
+        let mut error = None;
+        let mut fresh = true;
+        let val = self.opp.try_eval(|x| {
+            if error.is_some() {
+                return T::ZERO;
+            }
+            match operand(x, eval) {
+                Ok(val) => {
+                    fresh &= x.try_borrow().map_or(false, |x| !x.is_dirty());
+                    val
+                }
+                Err(err) => {
+                    error = Some(err);
+                    T::ZERO
+                }
+            }
+        });
+        if let Some(err) = error {
+            return Err(err);
+        }
+        let val = val?;
+        if !val.is_finite() {
+            return Err(GraphError::NonFinite {
+                operation,
+                value: val.to_f64(),
+            });
+        }
+        self.store(val, fresh);
+        eval.computed(operation, val);
+        Ok(val)
+    }
+
+    fn take_operands(&mut self) -> Vec<Handle<T>> {
+        let opp = std::mem::replace(&mut self.opp, Operation::Const(T::ZERO));
+        opp.operands().into_iter().cloned().collect()
+    }
+
+    fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    #[cfg(feature = "counters")]
+    fn counters(&self) -> Option<Counters> {
+        Some(self.counters)
+    }
+}
+
+impl<T: Float> Drop for Node<T> {
+    /// Frees operands no longer used elsewhere along with their own operands iteratively,
+    /// so dropping long chains doesn't overflow the stack.
+    fn drop(&mut self) {
+        let mut stack = self.take_operands();
+        while let Some(x) = stack.pop() {
+            if Rc::strong_count(&x) == 1 {
+                if let Ok(mut x) = x.try_borrow_mut() {
+                    stack.extend(x.take_operands());
+                }
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
     use crate::input::{Input, InputNode};
 
     use super::*;
@@ -134,6 +1551,22 @@ mod tests {
         assert_eq!(node.borrow().cache.map(|v| round(v, 5)), Some(expected));
     }
 
+    #[test]
+    fn constant() {
+        let x = Node::new(Operation::Const(42.0));
+        check_node(x, 42.0);
+    }
+
+    #[test]
+    fn input() {
+        let x1 = Node::input(1.0);
+        let x = Node::add(x1.clone(), Node::constant(2.0));
+        check_node(x.clone(), 3.0);
+
+        x1.borrow_mut().set(2.0);
+        check_node(x, 4.0);
+    }
+
     #[test]
     fn add() {
         let x1 = InputNode::from_val(1.0);
@@ -161,6 +1594,49 @@ mod tests {
         check_node(x, 15.0);
     }
 
+    #[test]
+    fn reductions() {
+        let xs: Vec<_> = (1..=20)
+            .map(|i| InputNode::from_val(i as f32 / 10.0))
+            .collect();
+        let args = || -> Vec<Handle> { xs.iter().map(|x| x.clone() as Handle).collect() };
+        let prod = Node::mul_var(args());
+        assert_eq!(prod.borrow().depth(), 2);
+        let expected: f32 = (1..=20).map(|i| i as f32 / 10.0).product();
+        assert_eq!(prod.borrow_mut().compute(), expected);
+
+        let max = Node::reduce(args(), Reduction::Max);
+        let mean = Node::reduce(args(), Reduction::Mean);
+        assert_eq!(max.borrow_mut().compute(), 2.0);
+        assert!((mean.borrow_mut().compute() - 1.05).abs() < 1e-6);
+        assert_eq!(
+            Node::reduce(args(), Reduction::Sum)
+                .borrow()
+                .operation()
+                .unwrap()
+                .name(),
+            "add_var"
+        );
+
+        xs[19].borrow_mut().set(0.0);
+        assert!(max.borrow().is_dirty());
+        assert_eq!(prod.borrow_mut().compute(), 0.0);
+        assert_eq!(max.borrow_mut().compute(), 1.9);
+
+        let flags = vec![
+            Node::lt(xs[0].clone(), xs[1].clone()) as Handle,
+            Node::gt(xs[0].clone(), xs[1].clone()),
+        ];
+        assert_eq!(
+            Node::reduce(flags.clone(), Reduction::Min).borrow().kind(),
+            Kind::Boolean
+        );
+        assert_eq!(
+            Node::reduce(flags, Reduction::Mean).borrow().kind(),
+            Kind::Numeric
+        );
+    }
+
     #[test]
     fn sub() {
         let x1 = InputNode::from_val(1.0);
@@ -185,6 +1661,35 @@ mod tests {
         check_node(x, 20.0);
     }
 
+    #[test]
+    fn div() {
+        let x1 = InputNode::from_val(3.0);
+        let x2 = InputNode::from_val(2.0);
+        let x = Node::div(x1.clone(), x2.clone());
+        check_node(x.clone(), 1.5);
+
+        x2.borrow_mut().set(0.0);
+        assert_eq!(x.borrow_mut().compute(), f32::INFINITY);
+
+        assert!(matches!(
+            x.borrow_mut().try_compute(&mut Evaluation::new()),
+            Err(GraphError::NonFinite { .. })
+        ));
+
+        let x = Node::div_with_policy(x1.clone(), x2.clone(), DivPolicy::Error);
+        assert_eq!(
+            x.borrow_mut().try_compute(&mut Evaluation::new()),
+            Err(GraphError::Domain(crate::ops::DomainError {
+                operation: "div",
+                value: 0.0
+            }))
+        );
+        assert_eq!(x.borrow().cache, None);
+
+        let x = Node::div_with_policy(x1, x2, DivPolicy::Clamp);
+        assert_eq!(x.borrow_mut().compute(), f32::MAX);
+    }
+
     #[test]
     fn pow() {
         let x1 = InputNode::from_val(2.0);
@@ -205,6 +1710,509 @@ mod tests {
         check_node(x, 1.0);
     }
 
+    #[test]
+    fn inverse_trig() {
+        let x1 = InputNode::from_val(0.5);
+        let x = Node::sin(Node::asin(x1.clone()));
+        check_node(x.clone(), 0.5);
+
+        x1.borrow_mut().set(-0.25);
+        check_node(x, -0.25);
+
+        let x1 = InputNode::from_val(1.0);
+        let x = Node::add(
+            Node::acos(x1.clone()),
+            Node::atan(Node::sub(x1.clone(), Node::constant(1.0))),
+        );
+        check_node(x.clone(), 0.0);
+
+        x1.borrow_mut().set(0.5);
+        check_node(x, 0.58355);
+    }
+
+    #[test]
+    fn elementary() {
+        let x1 = InputNode::from_val(0.0);
+        let x = Node::add(Node::cos(x1.clone()), Node::tan(x1.clone()));
+        check_node(x.clone(), 1.0);
+
+        x1.borrow_mut().set(std::f32::consts::FRAC_PI_4);
+        check_node(x, 1.70711);
+
+        let x1 = InputNode::from_val(-9.0);
+        let x = Node::add(
+            Node::sqrt(Node::abs(x1.clone())),
+            Node::ln(Node::exp(Node::abs(x1.clone()))),
+        );
+        check_node(x, 12.0);
+
+        let x = Node::log(Node::abs(x1), 3.0);
+        check_node(x, 2.0);
+    }
+
+    #[test]
+    fn elementary_domain() {
+        let x1 = InputNode::from_val(-1.0f32);
+        for (x, operation) in [
+            (Node::ln(x1.clone()), "ln"),
+            (Node::log(x1.clone(), 10.0), "log"),
+            (Node::sqrt(x1), "sqrt"),
+        ] {
+            assert_eq!(
+                x.borrow_mut().try_compute(&mut Evaluation::new()),
+                Err(GraphError::Domain(crate::ops::DomainError {
+                    operation,
+                    value: -1.0
+                }))
+            );
+        }
+    }
+
+    #[test]
+    fn activations() {
+        // single neuron with two inputs
+        let x1 = InputNode::from_val(1.0);
+        let x2 = InputNode::from_val(2.0);
+        let z = Node::add_var(vec![
+            Node::mul(x1, Node::constant(0.5)),
+            Node::mul(x2.clone(), Node::constant(-0.25)),
+            Node::constant(0.1),
+        ]);
+        let x = Node::add_var(vec![
+            Node::tanh(z.clone()),
+            Node::sigmoid(z.clone()),
+            Node::relu(z.clone()),
+            Node::softplus(z),
+        ]);
+        check_node(x.clone(), 1.46904);
+
+        x2.borrow_mut().set(4.0);
+        check_node(x, 0.53438);
+    }
+
+    #[test]
+    fn min_max_clamp() {
+        let x1 = InputNode::from_val(5.0);
+        let lo = InputNode::from_val(0.0);
+        let hi = InputNode::from_val(2.0);
+        let x = Node::clamp(x1.clone(), lo.clone(), hi.clone());
+        check_node(x.clone(), 2.0);
+
+        x1.borrow_mut().set(-1.0);
+        check_node(x.clone(), 0.0);
+        lo.borrow_mut().set(-3.0);
+        check_node(x.clone(), -1.0);
+        // swapped bounds
+        hi.borrow_mut().set(-5.0);
+        check_node(x, -5.0);
+
+        let x = Node::sub(Node::max(x1.clone(), hi.clone()), Node::min(x1, hi.clone()));
+        check_node(x.clone(), 4.0);
+        hi.borrow_mut().set(3.0);
+        check_node(x, 4.0);
+    }
+
+    #[test]
+    fn exp_m1_ln_1p() {
+        let x1 = InputNode::from_val(0.0);
+        let x = Node::ln_1p(Node::expm1(x1.clone()));
+        check_node(x.clone(), 0.0);
+
+        x1.borrow_mut().set(2.5);
+        check_node(x, 2.5);
+
+        let x1 = InputNode::from_val(1e-9f32);
+        let x = Node::expm1(x1);
+        assert_eq!(x.borrow_mut().compute(), 1e-9);
+    }
+
+    #[test]
+    fn neg_recip() {
+        let x1 = InputNode::from_val(4.0);
+        let x = Node::neg(Node::recip(x1.clone()));
+        assert_eq!(x.borrow().operation().unwrap().name(), "neg");
+        check_node(x.clone(), -0.25);
+
+        x1.borrow_mut().set(0.0);
+        check_node(x, f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn hypot_norm() {
+        let x1 = InputNode::from_val(3.0);
+        let x2 = InputNode::from_val(4.0);
+        let x = Node::hypot(x1.clone(), x2.clone());
+        check_node(x.clone(), 5.0);
+
+        x1.borrow_mut().set(3e30);
+        x2.borrow_mut().set(4e30);
+        assert_eq!(x.borrow_mut().compute(), 5e30);
+
+        let x3 = InputNode::from_val(12.0);
+        let x = Node::norm(vec![x1.clone(), x2.clone(), x3]);
+        x1.borrow_mut().set(3.0);
+        x2.borrow_mut().set(4.0);
+        check_node(x, 13.0);
+    }
+
+    #[test]
+    fn normal_distribution() {
+        let x1 = InputNode::from_val(0.0);
+        let x = Node::add(Node::normal_cdf(x1.clone()), Node::erf(x1.clone()));
+        check_node(x.clone(), 0.5);
+
+        x1.borrow_mut().set(10.0);
+        check_node(x, 2.0);
+
+        let x1 = InputNode::from_val(0.0);
+        let x = Node::sub(Node::erfc(x1.clone()), Node::normal_pdf(x1.clone()));
+        check_node(x.clone(), 0.60106);
+
+        x1.borrow_mut().set(-10.0);
+        check_node(x, 2.0);
+    }
+
+    #[test]
+    fn softmax() {
+        let x1 = InputNode::from_val(0.0);
+        let x2 = InputNode::from_val(0.0);
+        let args: Vec<Rc<RefCell<dyn Computable>>> = vec![x1.clone(), x2.clone()];
+        let lse = Node::logsumexp(args.clone());
+        let x = Node::softmax_component(args, 1);
+        check_node(x.clone(), 0.5);
+
+        x1.borrow_mut().set(500.0);
+        x2.borrow_mut().set(500.0 + 3f32.ln());
+        check_node(x, 0.75);
+        assert_eq!(lse.borrow_mut().compute(), 500.0 + 4f32.ln());
+    }
+
+    #[test]
+    #[should_panic]
+    fn softmax_out_of_bounds() {
+        Node::softmax_component(vec![InputNode::from_val(0.0)], 1);
+    }
+
+    #[test]
+    fn lazy_select() {
+        let cond = InputNode::from_val(1.0);
+        let x1 = InputNode::from_val(4.0);
+        let a = Node::pow(x1.clone(), 0.5);
+        let b = Node::asin(x1.clone());
+        let x = Node::lazy_select(cond.clone(), a.clone(), b.clone());
+        check_node(x.clone(), 2.0);
+        assert_eq!(b.borrow().cache, None);
+
+        cond.borrow_mut().set(0.0);
+        x1.borrow_mut().set(0.0);
+        check_node(x, 0.0);
+        assert_eq!(a.borrow().cache, None);
+    }
+
+    #[test]
+    fn short_circuit() {
+        let x = InputNode::from_val(1.0f32);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let expensive = Node::unary_fn(x.clone(), move |v| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            v
+        });
+        let positive = Node::gt(x.clone(), Node::constant(0.0));
+        let big = Node::gt(expensive.clone(), Node::constant(2.0));
+        let and = Node::and(positive.clone(), big.clone());
+        let or = Node::or(positive, big);
+        assert_eq!(and.borrow().kind(), Kind::Boolean);
+        check_node(or.clone(), 1.0);
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+        check_node(and.clone(), 0.0);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        x.borrow_mut().set(-3.0);
+        check_node(and, 0.0);
+        assert_eq!(expensive.borrow().cache, None);
+        check_node(or, 0.0);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn piecewise_brackets() {
+        // tax of 10% up to 100, 20% up to 500 and 30% above
+        let income = InputNode::from_val(50.0);
+        let rate = |r| Node::mul(income.clone(), Node::constant(r));
+        let (low, mid, high) = (rate(0.1), rate(0.2), rate(0.3));
+        let x = Node::piecewise(
+            income.clone(),
+            &[(100.0, low.clone()), (500.0, mid.clone())],
+            high.clone(),
+        );
+        check_node(x.clone(), 5.0);
+        assert_eq!((mid.borrow().cache, high.borrow().cache), (None, None));
+
+        income.borrow_mut().set(100.0);
+        check_node(x.clone(), 20.0);
+        assert_eq!(low.borrow().cache, None);
+        income.borrow_mut().set(1000.0);
+        check_node(x, 300.0);
+    }
+
+    #[test]
+    fn quantize() {
+        let x = InputNode::from_val(2.125f32);
+        let cents = Node::round_dp(x.clone(), 2, Rounding::HalfEven);
+        let nickels = Node::quantize(x.clone(), 0.05, Rounding::Floor);
+        check_node(cents.clone(), 2.12);
+        check_node(nickels.clone(), 2.1);
+        assert_eq!(
+            crate::grad::Gradient::of(&(cents.clone() as Handle)).wrt(&x),
+            0.0
+        );
+
+        x.borrow_mut().set(-1.375);
+        check_node(cents, -1.38);
+        check_node(nickels, -1.4);
+    }
+
+    #[test]
+    fn polynomial() {
+        let x = InputNode::from_val(2.0f32);
+        let p = Node::polynomial(x.clone(), vec![1.0, -3.0, 0.5]);
+        let y = Node::neg(p.clone());
+        check_node(y.clone(), 3.0);
+        assert_eq!(crate::grad::Gradient::of(&p).wrt(&x), -1.0);
+        let dp = Node::differentiate(&(p.clone() as Handle), &x).unwrap();
+        assert_eq!(dp.borrow_mut().compute(), -1.0);
+        assert_eq!(
+            crate::infix::to_string_expr(&(p.clone() as Handle)),
+            "polynomial(2, 1, -3, 0.5)"
+        );
+
+        p.borrow_mut().set_coeffs(vec![0.0, 0.0, 0.0, 1.0]);
+        assert!(y.borrow().is_dirty());
+        check_node(y, -8.0);
+        x.borrow_mut().set(-1.0);
+        check_node(p, -1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "quantization step isn't positive")]
+    fn quantize_zero_step() {
+        Node::quantize(Node::constant(1.0f32), 0.0, Rounding::HalfEven);
+    }
+
+    #[test]
+    #[should_panic(expected = "breakpoints of piecewise aren't increasing")]
+    fn piecewise_unordered() {
+        let x = InputNode::from_val(0.0);
+        Node::piecewise(x.clone(), &[(1.0, x.clone()), (1.0, x.clone())], x);
+    }
+
+    /// Custom dependent counting resets reaching it.
+    struct ResetCounter(Rc<std::cell::Cell<usize>>);
+
+    impl Computable for ResetCounter {
+        fn compute(&mut self) -> f32 {
+            0.0
+        }
+        fn add_dependency(&mut self, _dependency: Handle) {}
+        fn reset_cache(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn invalidation_stops_at_dirty() {
+        let x = InputNode::from_val(1.0);
+        let a = Node::sin(x.clone());
+        let b = Node::mul(a.clone(), a);
+        let resets = Rc::new(std::cell::Cell::new(0));
+        let counter: Handle = Rc::new(RefCell::new(ResetCounter(resets.clone())));
+        b.borrow_mut().add_dependency(counter.clone());
+
+        b.borrow_mut().compute();
+        for val in [2.0, 3.0, 4.0] {
+            x.borrow_mut().set(val);
+        }
+        assert_eq!(resets.get(), 1);
+        b.borrow_mut().compute();
+        x.borrow_mut().set(5.0);
+        assert_eq!(resets.get(), 2);
+        drop(counter);
+    }
+
+    #[test]
+    fn diamond_invalidation() {
+        // each layer uses the previous one twice, so there are 2^40 paths to the top
+        let x = InputNode::from_val(1.0);
+        let y = InputNode::from_val(2.0);
+        let mut top: Handle = x.clone();
+        for _ in 0..40 {
+            let left = Node::mul(top.clone(), Node::constant(0.5));
+            let right = Node::mul(top, Node::constant(0.5));
+            top = Node::add(left, right);
+        }
+        let unrelated = Node::sin(y);
+        let resets = Rc::new(std::cell::Cell::new(0));
+        let counter: Handle = Rc::new(RefCell::new(ResetCounter(resets.clone())));
+        top.borrow_mut().add_dependency(counter.clone());
+
+        assert_eq!(top.borrow_mut().compute(), 1.0);
+        unrelated.borrow_mut().compute();
+        x.borrow_mut().set(3.0);
+        assert_eq!(resets.get(), 1);
+        assert!(!unrelated.borrow().is_dirty());
+        assert_eq!(top.borrow_mut().compute(), 3.0);
+        drop(counter);
+    }
+
+    #[test]
+    fn cache_policy() {
+        let counter = AtomicUsize::new(0);
+        let sensor = Node::nary_fn(vec![], move |_| {
+            counter.fetch_add(1, Ordering::Relaxed) as f32
+        });
+        sensor.borrow_mut().set_cache_policy(CachePolicy::Never);
+        let x = InputNode::from_val(10.0);
+        let stable = Node::sin(x.clone());
+        let reading = Node::add(sensor.clone(), stable.clone());
+        let root = Node::mul(reading, Node::constant(2.0));
+
+        assert_eq!(root.borrow_mut().compute(), 2.0 * 10f32.sin());
+        assert_eq!(root.borrow_mut().compute(), 2.0 * (1.0 + 10f32.sin()));
+        assert!(root.borrow().is_dirty());
+        assert!(!stable.borrow().is_dirty());
+        assert!(!sensor.borrow_mut().seed_cache(5.0));
+
+        let manual = Node::mul(x.clone(), Node::constant(2.0));
+        manual.borrow_mut().set_cache_policy(CachePolicy::Manual);
+        assert_eq!(manual.borrow_mut().compute(), 20.0);
+        x.borrow_mut().set(3.0);
+        assert!(!manual.borrow().is_dirty());
+        assert_eq!(manual.borrow_mut().compute(), 20.0);
+        manual.borrow_mut().invalidate();
+        assert_eq!(manual.borrow_mut().compute(), 6.0);
+        assert_eq!(manual.borrow().cache_policy(), CachePolicy::Manual);
+    }
+
+    #[test]
+    fn guard() {
+        let x1 = InputNode::from_val(-3.0);
+        let value = Node::ln_1p(x1.clone());
+        let x = Node::guard(
+            Node::add(x1.clone(), Node::constant(1.0)),
+            value.clone(),
+            0.0,
+        );
+        check_node(x.clone(), 0.0);
+        assert_eq!(value.borrow().cache, None);
+
+        x1.borrow_mut().set(0.0);
+        check_node(x, 0.0);
+        assert_eq!(value.borrow().cache, Some(0.0));
+    }
+
+    #[test]
+    fn try_compute() {
+        let x1 = InputNode::from_val(0.5);
+        let a = Node::asin(x1.clone());
+        let x = Node::add(Node::sin(x1.clone()), a.clone());
+        let mut eval = Evaluation::new();
+        assert_eq!(
+            x.borrow_mut().try_compute(&mut eval),
+            Ok(0.5f32.sin() + 0.5f32.asin())
+        );
+        assert_eq!(eval.evaluated(), 3);
+        assert_eq!(
+            x.borrow_mut().try_compute(&mut eval),
+            Ok(0.5f32.sin() + 0.5f32.asin())
+        );
+        assert_eq!(eval.evaluated(), 3);
+
+        x1.borrow_mut().set(2.0);
+        assert_eq!(
+            x.borrow_mut().try_compute(&mut Evaluation::new()),
+            Err(GraphError::Domain(crate::ops::DomainError {
+                operation: "asin",
+                value: 2.0
+            }))
+        );
+        assert_eq!(x.borrow().cache, None);
+        assert_eq!(a.borrow().cache, None);
+    }
+
+    #[test]
+    fn try_compute_errors() {
+        let x1 = InputNode::from_val(-8.0f32);
+        let x = Node::add(x1.clone(), Node::pow(x1.clone(), 0.5));
+        assert_eq!(
+            x.borrow_mut().try_compute(&mut Evaluation::new()),
+            Err(GraphError::Domain(crate::ops::DomainError {
+                operation: "pow",
+                value: -8.0
+            }))
+        );
+        // cached NaN is traced back to its origin
+        assert!(x.borrow_mut().compute().is_nan());
+        assert!(matches!(
+            x.borrow_mut().try_compute(&mut Evaluation::new()),
+            Err(GraphError::Domain(_))
+        ));
+
+        x1.borrow_mut().set(1e30);
+        let x = Node::mul(Node::sin(x1.clone()), Node::pow(x1.clone(), 2.0));
+        assert_eq!(
+            x.borrow_mut().try_compute(&mut Evaluation::new()),
+            Err(GraphError::NonFinite {
+                operation: "pow",
+                value: f64::INFINITY
+            })
+        );
+
+        let x = Node::sin(x1.clone());
+        let _guard = x1.borrow_mut();
+        assert_eq!(
+            x.borrow_mut().try_compute(&mut Evaluation::new()),
+            Err(GraphError::BorrowConflict { operation: "sin" })
+        );
+    }
+
+    #[test]
+    fn try_compute_budget() {
+        let x1 = InputNode::from_val(1.0);
+        let x = Node::mul(Node::sin(x1.clone()), Node::pow(x1, 2.0));
+        // operands are computed before nodes using them
+        let mut eval = Evaluation::new().max_nodes(2);
+        assert_eq!(
+            x.borrow_mut().try_compute(&mut eval),
+            Err(GraphError::BudgetExceeded {
+                operation: "mul",
+                evaluated: 2
+            })
+        );
+        assert_eq!(x.borrow().cache, None);
+
+        // computed part stays cached and isn't counted again
+        let mut eval = Evaluation::new().max_nodes(2);
+        assert_eq!(x.borrow_mut().try_compute(&mut eval), Ok(1f32.sin()));
+        assert_eq!(eval.evaluated(), 1);
+    }
+
+    #[test]
+    fn try_compute_cancelled() {
+        let x1 = InputNode::from_val(1.0);
+        let a = Node::sin(x1.clone());
+        let x = Node::add(a.clone(), x1);
+        let token = crate::eval::CancellationToken::new();
+        let mut eval = Evaluation::new().cancellation(token.clone());
+        token.cancel();
+        assert!(matches!(
+            x.borrow_mut().try_compute(&mut eval),
+            Err(GraphError::Cancelled { .. })
+        ));
+        assert_eq!(a.borrow().cache, None);
+        assert_eq!(x.borrow_mut().compute(), 1f32.sin() + 1.0);
+    }
+
     #[test]
     fn nested_expression() {
         let x1 = InputNode::from_val(1.0);
@@ -298,4 +2306,347 @@ mod tests {
         x1.borrow_mut().set(4.0);
         check_node(x, 7.0);
     }
+
+    #[test]
+    fn f64_graph() {
+        let x1 = InputNode::from_val(1e-12f64);
+        let x = Node::sub(
+            Node::add(x1.clone(), Node::constant(1.0)),
+            Node::constant(1.0),
+        );
+        assert!((x.borrow_mut().compute() - 1e-12).abs() < 1e-15);
+
+        x1.borrow_mut().set(1e100);
+        let x = Node::pow(x1, 2.0);
+        assert_eq!(
+            x.borrow_mut().try_compute(&mut Evaluation::new()),
+            Ok(1e200)
+        );
+    }
+
+    #[test]
+    fn dependents_freed() {
+        let x1 = InputNode::from_val(1.0);
+        let x = Node::add(x1.clone(), Node::sin(x1.clone()));
+        let weak = Rc::downgrade(&x);
+        assert_eq!(x.borrow_mut().compute(), 1.0 + 1f32.sin());
+
+        drop(x);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(Rc::strong_count(&x1), 1);
+        x1.borrow_mut().set(2.0);
+    }
+
+    #[test]
+    fn depth() {
+        let x = Node::input(1.0f32);
+        let sin = Node::sin(x.clone());
+        let root = Node::add(sin.clone(), Node::mul(sin, x.clone()));
+        assert_eq!(x.borrow().depth(), 1);
+        assert_eq!(root.borrow().depth(), 4);
+
+        let opp = Operation::Pow(root as Handle<f32>, 2.0);
+        assert!(Node::try_new(opp.clone(), 5).is_ok());
+        assert_eq!(
+            Node::try_new(opp, 4).err(),
+            Some(GraphError::TooDeep { depth: 5, limit: 4 })
+        );
+    }
+
+    #[test]
+    fn kinds() {
+        struct Flag;
+        impl Computable for Flag {
+            fn compute(&mut self) -> f32 {
+                1.0
+            }
+            fn add_dependency(&mut self, _: Handle) {}
+            fn reset_cache(&mut self) {}
+            fn kind(&self) -> Kind {
+                Kind::Boolean
+            }
+        }
+
+        let flag: Handle = Rc::new(RefCell::new(Flag));
+        let x: Handle = Node::input(2.0);
+        let both = Node::try_new(Operation::Mul(flag.clone(), flag.clone()), 10).unwrap();
+        assert_eq!(both.borrow().kind(), Kind::Boolean);
+        let masked = Node::try_new(Operation::Mul(x.clone(), flag.clone()), 10).unwrap();
+        assert_eq!(masked.borrow().kind(), Kind::Numeric);
+        assert!(
+            Node::try_new(Operation::Select(flag.clone(), x.clone(), flag.clone()), 10).is_ok()
+        );
+
+        let err = Node::try_new(Operation::Sin(both), 10).err().unwrap();
+        assert_eq!(err.to_string(), "boolean operand 0 of sin");
+        assert!(Node::try_new(Operation::Div(x, flag, DivPolicy::default()), 10).is_err());
+    }
+
+    #[test]
+    fn comparisons() {
+        let x1 = InputNode::from_val(1.0);
+        let x2 = InputNode::from_val(2.0);
+        let flags = [
+            Node::lt(x1.clone(), x2.clone()),
+            Node::le(x1.clone(), x2.clone()),
+            Node::gt(x1.clone(), x2.clone()),
+            Node::ge(x1.clone(), x2.clone()),
+            Node::eq(x1, x2.clone(), 0.5),
+        ];
+        let vals = |flags: &[Rc<RefCell<Node>>]| -> Vec<f32> {
+            flags.iter().map(|f| f.borrow_mut().compute()).collect()
+        };
+        assert_eq!(vals(&flags), vec![1.0, 1.0, 0.0, 0.0, 0.0]);
+        assert!(flags.iter().all(|f| f.borrow().kind() == Kind::Boolean));
+
+        x2.borrow_mut().set(1.25);
+        assert_eq!(vals(&flags), vec![1.0, 1.0, 0.0, 0.0, 1.0]);
+        x2.borrow_mut().set(1.0);
+        assert_eq!(vals(&flags), vec![0.0, 1.0, 0.0, 1.0, 1.0]);
+        assert!(Node::try_new(Operation::Sin(flags[0].clone()), 10).is_err());
+    }
+
+    #[test]
+    fn piecewise() {
+        // ln(x) for positive x, -1 otherwise
+        let x1 = InputNode::from_val(-1.0f32);
+        let log = Node::ln(x1.clone());
+        let x = Node::select(
+            Node::gt(x1.clone(), Node::constant(0.0)),
+            log.clone(),
+            Node::constant(-1.0),
+        );
+        assert_eq!(x.borrow_mut().try_compute(&mut Evaluation::new()), Ok(-1.0));
+        // branch not taken isn't computed
+        assert!(log.borrow().is_dirty());
+
+        x1.borrow_mut().set(1.0);
+        check_node(x, 0.0);
+    }
+
+    #[test]
+    fn custom_op() {
+        struct Clamp;
+        impl Op for Clamp {
+            fn name(&self) -> &'static str {
+                "clamp"
+            }
+            fn eval(&self, operands: &[f32]) -> f32 {
+                operands[0].max(operands[1]).min(operands[2])
+            }
+        }
+
+        let x = Node::input(5.0);
+        let clamp = Node::from_op(
+            Box::new(Clamp),
+            vec![x.clone(), Node::constant(0.0), Node::constant(2.0)],
+        );
+        let root = Node::mul(clamp.clone(), Node::constant(3.0));
+        assert_eq!(root.borrow_mut().compute(), 6.0);
+        assert_eq!(clamp.borrow().operation().unwrap().name(), "clamp");
+
+        x.borrow_mut().set(1.0);
+        assert!(root.borrow().is_dirty());
+        assert_eq!(root.borrow_mut().compute(), 3.0);
+        let grad = crate::grad::Gradient::of(&root);
+        assert!((grad.wrt(&x) - 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn custom_op_arity() {
+        struct Mask;
+        impl Op for Mask {
+            fn eval(&self, operands: &[f32]) -> f32 {
+                operands[0] * operands[1]
+            }
+            fn arity(&self) -> RangeInclusive<usize> {
+                2..=2
+            }
+            fn accepts_boolean(&self, operand: usize) -> bool {
+                operand == 1
+            }
+        }
+
+        let x = Node::input(2.0);
+        let flag = Node::gt(x.clone(), Node::constant(1.0));
+        let err = Node::try_from_op(Box::new(Mask), vec![x.clone()])
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "custom expects 2 operands, got 1");
+        assert_eq!(
+            Node::try_from_op(Box::new(Mask), vec![flag.clone(), x.clone()]).err(),
+            Some(GraphError::BooleanOperand {
+                operation: "custom",
+                operand: 0
+            })
+        );
+        let masked = Node::try_from_op(Box::new(Mask), vec![x.clone(), flag]).unwrap();
+        assert_eq!(masked.borrow_mut().compute(), 2.0);
+
+        // closures accept the amount of operands they were built with
+        let sum = Node::nary_fn(vec![x.clone(), x.clone()], |vals| vals[0] + vals[1]);
+        let opp = sum
+            .borrow()
+            .operation()
+            .unwrap()
+            .map_operands(|_| x.clone() as Handle);
+        let rewired = match opp {
+            Operation::Custom(op, mut args) => {
+                args.pop();
+                Operation::Custom(op, args)
+            }
+            _ => unreachable!(),
+        };
+        assert!(matches!(
+            Node::try_new(rewired, 10),
+            Err(GraphError::Arity {
+                min: 2,
+                max: 2,
+                actual: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn closures() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let x = Node::input(3.0);
+        let y = Node::input(4.0);
+        let clamped = Node::unary_fn(x.clone(), move |v: f32| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            v.min(2.0)
+        });
+        let root = Node::nary_fn(
+            vec![
+                clamped.clone(),
+                Node::binary_fn(x.clone(), y.clone(), |a, b| a * b),
+                y.clone(),
+            ],
+            |vals| vals.iter().sum(),
+        );
+        assert_eq!(root.borrow_mut().compute(), 2.0 + 12.0 + 4.0);
+        assert_eq!(root.borrow_mut().compute(), 18.0);
+        assert_eq!(clamped.borrow().operation().unwrap().name(), "fn");
+
+        // only dependents of changed input are recomputed
+        y.borrow_mut().set(1.0);
+        assert_eq!(root.borrow_mut().compute(), 2.0 + 3.0 + 1.0);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        x.borrow_mut().set(1.0);
+        assert_eq!(root.borrow_mut().compute(), 1.0 + 1.0 + 1.0);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn handles() {
+        use crate::input::InputHandle;
+
+        let x = InputNode::from_val(2.0f32);
+        let root = Node::mul(x.clone(), Node::constant(3.0));
+        assert_eq!(root.value(), Ok(None));
+        assert_eq!(root.compute(), Ok(6.0));
+        assert_eq!(root.value(), Ok(Some(6.0)));
+
+        x.set(1.0).unwrap();
+        assert_eq!(root.value(), Ok(None));
+        assert_eq!(root.compute(), Ok(3.0));
+
+        let held = x.borrow_mut();
+        let conflict = GraphError::BorrowConflict { operation: "input" };
+        assert_eq!(x.set(5.0), Err(conflict));
+        assert!(x.compute().is_err());
+        drop(held);
+        assert_eq!(x.compute(), Ok(1.0));
+
+        x.borrow_mut().add_rule(crate::input::Rule::Range(0.0, 2.0));
+        let err = x.set(5.0).unwrap_err();
+        assert_eq!(err.to_string(), "5 violates range [0, 2] of input");
+        assert_eq!(x.compute(), Ok(1.0));
+
+        // operands held borrowed fail computation instead of panicking
+        x.set(2.0).unwrap();
+        let held = x.borrow();
+        let conflict = GraphError::BorrowConflict { operation: "mul" };
+        assert_eq!(root.compute(), Err(conflict));
+        drop(held);
+        assert_eq!(root.compute(), Ok(6.0));
+        let held = x.borrow_mut();
+        assert_eq!(root.value(), Ok(Some(6.0)));
+        drop(held);
+    }
+
+    #[test]
+    fn replace_operand() {
+        let placeholder: Handle = Node::constant(0.0);
+        let x = Node::input(3.0f32);
+        let scaled = Node::mul(placeholder.clone(), Node::constant(2.0));
+        let root = Node::add(scaled.clone(), Node::constant(1.0));
+        assert_eq!(root.borrow_mut().compute(), 1.0);
+
+        let sin: Handle = Node::sin(x.clone());
+        assert_eq!(
+            Node::replace_operand(&scaled, &placeholder, sin.clone()),
+            Ok(1)
+        );
+        assert!(root.borrow().is_dirty());
+        assert_eq!(root.borrow_mut().compute(), 2.0 * 3f32.sin() + 1.0);
+        assert_eq!(root.borrow().depth(), 4);
+        // new operand invalidates the node
+        x.borrow_mut().set(1.0);
+        assert_eq!(root.borrow_mut().compute(), 2.0 * 1f32.sin() + 1.0);
+
+        assert_eq!(Node::replace_operand(&scaled, &placeholder, x), Ok(0));
+        assert!(matches!(
+            Node::replace_operand(&scaled, &sin, root.clone()),
+            Err(GraphError::Cycle { .. })
+        ));
+        assert_eq!(root.borrow_mut().compute(), 2.0 * 1f32.sin() + 1.0);
+    }
+
+    #[test]
+    fn topological_order() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let logged = |x: Handle, name: &'static str| {
+            let order = order.clone();
+            Node::unary_fn(x, move |v| {
+                order.lock().unwrap().push(name);
+                v
+            })
+        };
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let sensor = Node::nary_fn(vec![], move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            1.0
+        });
+        sensor.borrow_mut().set_cache_policy(CachePolicy::Never);
+        let x = InputNode::from_val(2.0f32);
+        let a = logged(Node::add(x.clone(), sensor.clone()), "a");
+        let b = logged(Node::mul(a.clone(), sensor), "b");
+        let c = logged(x.clone(), "c");
+        let root = Node::add_var(vec![b, c, a]);
+
+        assert_eq!(root.borrow_mut().compute(), 3.0 + 2.0 + 3.0);
+        assert_eq!(*order.lock().unwrap(), vec!["a", "b", "c"]);
+        // volatile node is computed once per evaluation
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        root.borrow_mut().compute();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        // deep graphs don't overflow the stack when computed, invalidated or dropped
+        let mut deep: Handle = x.clone();
+        for _ in 0..100_000 {
+            deep = Node::add(deep, Node::constant(1.0));
+        }
+        assert_eq!(deep.borrow_mut().compute(), 100_002.0);
+        x.borrow_mut().set(3.0);
+        assert_eq!(
+            deep.borrow_mut().try_compute(&mut Evaluation::new()),
+            Ok(100_003.0)
+        );
+        x.borrow_mut().set(4.0);
+        assert_eq!(deep.borrow_mut().compute(), 100_004.0);
+    }
 }
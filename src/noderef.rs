@@ -0,0 +1,322 @@
+//! Opaque handles to nodes, independent of how nodes are stored.
+//!
+//! `NodeRef` hides the shared `RefCell` behind it, so code built on it keeps working
+//! as node representation evolves. Inputs are created and set, and nodes are built
+//! with constructors and arithmetic operators, without touching the `RefCell`.
+//! Conversions to and from handles used by the rest of the API allow adopting it gradually.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    ops::{Add, Div, Mul, Neg, Sub},
+    rc::Rc,
+};
+
+use crate::{
+    error::GraphError,
+    eval::Evaluation,
+    expr::Expr,
+    float::Float,
+    input::{InputHandle, InputNode},
+    node::{node_addr, Handle, Kind, Node, NodeHandle},
+    ops::Operation,
+};
+
+/// Shared handle to node. Clones refer to the same node.
+///
+/// ```
+/// use teza::noderef::NodeRef;
+///
+/// let x = NodeRef::input(2.0f32);
+/// let y = x.clone() * x.clone() + 1.0;
+/// assert_eq!(y.compute(), Ok(5.0));
+///
+/// x.set(3.0).unwrap();
+/// assert_eq!(y.compute(), Ok(10.0));
+/// ```
+#[derive(Clone)]
+pub struct NodeRef<T: Float = f32> {
+    node: Handle<T>,
+    /// Input behind the node, if it's known to be one.
+    input: Option<Rc<RefCell<InputNode<T>>>>,
+}
+
+impl<T: Float> NodeRef<T> {
+    fn wrap(node: Handle<T>) -> Self {
+        Self { node, input: None }
+    }
+
+    /// Builds input node holding given value.
+    pub fn input(val: T) -> Self {
+        Self::from(InputNode::from_val(val))
+    }
+
+    /// Builds node computing given operation over nodes.
+    pub fn new(opp: Operation<NodeRef<T>, T>) -> Self {
+        Self::wrap(Node::new(opp.map_operands(|x| x.node.clone())))
+    }
+
+    /// Builds node like `new`, failing if operation doesn't accept its operands
+    /// or graph would be deeper than `max_depth`, see `Node::try_new`.
+    pub fn try_new(opp: Operation<NodeRef<T>, T>, max_depth: usize) -> Result<Self, GraphError> {
+        let node = Node::try_new(opp.map_operands(|x| x.node.clone()), max_depth)?;
+        Ok(Self::wrap(node))
+    }
+
+    /// Builds constant node.
+    pub fn constant(val: T) -> Self {
+        Self::wrap(Node::constant(val))
+    }
+
+    /// Returns true if node is an input which can be set. Handles converted from
+    /// `Handle` aren't known to be inputs.
+    pub fn is_input(&self) -> bool {
+        self.input.is_some()
+    }
+
+    /// Sets new value of input, resetting caches of its dependents, see `InputHandle::set`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if node isn't an input, see `is_input`.
+    pub fn set(&self, val: T) -> Result<(), GraphError> {
+        match &self.input {
+            Some(input) => InputHandle::set(input, val),
+            None => panic!("{} node isn't an input", self.name()),
+        }
+    }
+
+    /// Returns name of operation computed by node, `custom` for nodes without operation.
+    fn name(&self) -> &'static str {
+        self.node
+            .borrow()
+            .operation()
+            .map_or("custom", |opp| opp.name())
+    }
+
+    /// Computes result of node, failing if node or its operands are borrowed,
+    /// see `NodeHandle`.
+    pub fn compute(&self) -> Result<T, GraphError> {
+        NodeHandle::compute(&self.node)
+    }
+
+    /// Computes result of node within limits of given evaluation.
+    pub fn try_compute(&self, eval: &mut Evaluation) -> Result<T, GraphError> {
+        self.node
+            .try_borrow_mut()
+            .map_err(|_| GraphError::BorrowConflict { operation: "node" })?
+            .try_compute(eval)
+    }
+
+    /// Returns true if node needs recomputation.
+    pub fn is_dirty(&self) -> bool {
+        self.node.borrow().is_dirty()
+    }
+
+    /// Returns operation computed by node, if it has one, over its operands.
+    pub fn operation(&self) -> Option<Operation<NodeRef<T>, T>> {
+        self.node
+            .borrow()
+            .operation()
+            .map(|opp| opp.map_operands(|x| Self::wrap(x.clone())))
+    }
+
+    /// Returns nodes this node is computed from.
+    pub fn operands(&self) -> Vec<NodeRef<T>> {
+        self.node
+            .borrow()
+            .operands()
+            .into_iter()
+            .map(Self::wrap)
+            .collect()
+    }
+
+    /// Returns kind of values computed by node.
+    pub fn kind(&self) -> Kind {
+        self.node.borrow().kind()
+    }
+
+    /// Returns depth of node, see `Computable::depth`.
+    pub fn depth(&self) -> usize {
+        self.node.borrow().depth()
+    }
+
+    /// Returns label of node, if it has one.
+    pub fn label(&self) -> Option<String> {
+        self.node.borrow().label().map(String::from)
+    }
+
+    /// Returns true if both handles refer to the same node.
+    pub fn ptr_eq(&self, other: &NodeRef<T>) -> bool {
+        node_addr(&self.node) == node_addr(&other.node)
+    }
+
+    /// Returns handle for use with API taking shared nodes.
+    pub fn handle(&self) -> Handle<T> {
+        self.node.clone()
+    }
+}
+
+impl<T: Float> fmt::Debug for NodeRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeRef")
+            .field("operation", &self.name())
+            .field("label", &self.label())
+            .finish()
+    }
+}
+
+impl<T: Float> From<Rc<RefCell<Node<T>>>> for NodeRef<T> {
+    fn from(node: Rc<RefCell<Node<T>>>) -> Self {
+        Self::wrap(node)
+    }
+}
+
+impl<T: Float> From<Rc<RefCell<InputNode<T>>>> for NodeRef<T> {
+    fn from(node: Rc<RefCell<InputNode<T>>>) -> Self {
+        Self {
+            node: node.clone(),
+            input: Some(node),
+        }
+    }
+}
+
+impl<T: Float> From<Handle<T>> for NodeRef<T> {
+    fn from(node: Handle<T>) -> Self {
+        Self::wrap(node)
+    }
+}
+
+impl<T: Float> From<NodeRef<T>> for Handle<T> {
+    fn from(node: NodeRef<T>) -> Self {
+        node.node
+    }
+}
+
+impl<T: Float> From<Expr<T>> for NodeRef<T> {
+    fn from(expr: Expr<T>) -> Self {
+        Self::wrap(expr.node())
+    }
+}
+
+impl<T: Float> From<NodeRef<T>> for Expr<T> {
+    fn from(node: NodeRef<T>) -> Self {
+        Expr::from(node.node)
+    }
+}
+
+impl<T: Float> From<T> for NodeRef<T> {
+    fn from(val: T) -> Self {
+        Self::constant(val)
+    }
+}
+
+/// Implements binary operators between nodes, and with constants on the right.
+macro_rules! impl_ops {
+    ($($op:ident::$f:ident => $build:path),*) => {
+        $(
+            impl<T: Float> $op for NodeRef<T> {
+                type Output = Self;
+
+                fn $f(self, rhs: Self) -> Self {
+                    Self::wrap($build(self.node, rhs.node))
+                }
+            }
+
+            impl<T: Float> $op<T> for NodeRef<T> {
+                type Output = Self;
+
+                fn $f(self, rhs: T) -> Self {
+                    self.$f(Self::constant(rhs))
+                }
+            }
+        )*
+    };
+}
+
+impl_ops!(
+    Add::add => Node::add,
+    Sub::sub => Node::sub,
+    Mul::mul => Node::mul,
+    Div::div => Node::div
+);
+
+impl<T: Float> Neg for NodeRef<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::wrap(Node::neg(self.node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    #[test]
+    fn wraps_nodes() {
+        let input = Node::input(2.0f32);
+        let x = NodeRef::from(input.clone());
+        let square = NodeRef::new(Operation::Mul(x.clone(), x.clone()));
+        let y = NodeRef::new(Operation::Add(square.clone(), NodeRef::constant(1.0)));
+        assert_eq!(y.compute(), Ok(5.0));
+        assert!(!y.is_dirty());
+
+        input.borrow_mut().set(3.0);
+        assert!(y.is_dirty());
+        assert_eq!(y.compute(), Ok(10.0));
+
+        let operands = y.operands();
+        assert!(operands[0].ptr_eq(&square));
+        match square.operation() {
+            Some(Operation::Mul(a, b)) => assert!(a.ptr_eq(&x) && b.ptr_eq(&x)),
+            _ => panic!("mul expected"),
+        }
+        assert!(x.operation().is_none());
+        assert_eq!(y.depth(), square.depth() + 1);
+
+        // interoperates with expressions and handles
+        let z = NodeRef::from(Expr::from(y) * 2.0);
+        assert_eq!(z.compute(), Ok(20.0));
+        input.borrow_mut().set(1.0);
+        let held = input.borrow();
+        assert_eq!(
+            z.compute(),
+            Err(GraphError::BorrowConflict { operation: "mul" })
+        );
+        drop(held);
+        assert_eq!(z.compute(), Ok(4.0));
+        let handle: Handle = z.into();
+        assert_eq!(handle.borrow_mut().compute(), 4.0);
+        assert!(matches!(
+            NodeRef::try_new(
+                Operation::Sin(NodeRef::new(Operation::Gt(x.clone(), x))),
+                usize::MAX
+            ),
+            Err(GraphError::BooleanOperand { .. })
+        ));
+    }
+
+    #[test]
+    fn inputs_and_operators() {
+        let x = NodeRef::input(2.0f32);
+        let y = NodeRef::input(4.0);
+        let z = -(x.clone() * 3.0 - y.clone() / x.clone()) + NodeRef::from(1.0);
+        assert_eq!(z.compute(), Ok(-3.0));
+        assert!(x.is_input() && !z.is_input());
+        assert!(!NodeRef::from(x.handle()).is_input());
+
+        x.set(1.0).unwrap();
+        assert_eq!(z.compute(), Ok(2.0));
+        let held = y.handle();
+        let borrow = held.borrow_mut();
+        assert_eq!(
+            y.set(5.0),
+            Err(GraphError::BorrowConflict { operation: "input" })
+        );
+        drop(borrow);
+        y.set(6.0).unwrap();
+        assert_eq!(z.compute(), Ok(4.0));
+    }
+}
@@ -1,52 +1,862 @@
-use std::{cell::RefCell, rc::Rc};
+//! Operations available for graph nodes.
 
-use crate::node::Computable;
+use std::{cell::RefCell, error::Error, fmt, ops::RangeInclusive, rc::Rc, sync::Arc};
+
+use crate::{float::Float, node::Computable};
 
 /// Represents set of available operations for computational graph.
-#[derive(Clone)]
-pub enum Operation {
+/// Operands are referenced by handle `H`, which is a shared node pointer by default,
+/// and values are of scalar type `T`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation<H = Rc<RefCell<dyn Computable>>, T = f32> {
+    /// Constant value.
+    Const(T),
     /// Sum of two values.
-    Add(Rc<RefCell<dyn Computable>>, Rc<RefCell<dyn Computable>>),
+    Add(H, H),
     /// Sum of variable amount of values.
-    AddVar(Vec<Rc<RefCell<dyn Computable>>>),
+    AddVar(Vec<H>),
     /// Subtraction of two values.
-    Sub(Rc<RefCell<dyn Computable>>, Rc<RefCell<dyn Computable>>),
+    Sub(H, H),
     /// Multiplication of two values.
-    Mul(Rc<RefCell<dyn Computable>>, Rc<RefCell<dyn Computable>>),
+    Mul(H, H),
+    /// Product of variable amount of values.
+    MulVar(Vec<H>),
+    /// Division of two values, handling division by zero according to policy.
+    Div(H, H, DivPolicy),
+    /// Negation of value.
+    Neg(H),
+    /// Reciprocal `1 / x` of value, following IEEE rules for zero.
+    Recip(H),
+    /// Value passed through unchanged, blocking gradient flow to it.
+    Detach(H),
     /// Exponentiation of value to given exponent.
-    Pow(Rc<RefCell<dyn Computable>>, f32),
+    Pow(H, T),
     /// Sin result of given value.
-    Sin(Rc<RefCell<dyn Computable>>),
+    Sin(H),
+    /// Arcsine of given value, defined on `[-1, 1]`.
+    Asin(H),
+    /// Arccosine of given value, defined on `[-1, 1]`.
+    Acos(H),
+    /// Arctangent of given value.
+    Atan(H),
+    /// Cosine of given value.
+    Cos(H),
+    /// Tangent of given value.
+    Tan(H),
+    /// Exponent of given value.
+    Exp(H),
+    /// Natural logarithm of given value, defined above zero.
+    Ln(H),
+    /// Logarithm of given value to given base, defined above zero.
+    Log(H, T),
+    /// Square root of given value, defined from zero.
+    Sqrt(H),
+    /// Absolute value of given value.
+    Abs(H),
+    /// Sign of given value, `-1`, `0` or `1`, `NaN` for `NaN`.
+    Sign(H),
+    /// Fractional part of given value, `x - trunc(x)`, with sign of the value.
+    Fract(H),
+    /// Hyperbolic tangent of given value.
+    Tanh(H),
+    /// Logistic function `1 / (1 + exp(-x))` of given value.
+    Sigmoid(H),
+    /// Rectified linear unit `max(x, 0)` of given value.
+    Relu(H),
+    /// Smooth approximation of rectifier `ln(1 + exp(x))` of given value.
+    Softplus(H),
+    /// `exp(x) - 1`, accurate for values near zero.
+    ExpM1(H),
+    /// `ln(1 + x)`, accurate for values near zero, defined above `-1`.
+    Ln1p(H),
+    /// Smaller of two values.
+    Min(H, H),
+    /// Larger of two values.
+    Max(H, H),
+    /// First value limited to range between second and third, `min(max(x, lo), hi)`.
+    Clamp(H, H, H),
+    /// One if first value is less than second, zero otherwise.
+    Lt(H, H),
+    /// One if first value is less than or equal to second, zero otherwise.
+    Le(H, H),
+    /// One if first value is greater than second, zero otherwise.
+    Gt(H, H),
+    /// One if first value is greater than or equal to second, zero otherwise.
+    Ge(H, H),
+    /// One if values differ at most by given tolerance, zero otherwise.
+    Eq(H, H, T),
+    /// Length of hypotenuse for two legs.
+    Hypot(H, H),
+    /// Angle of point with given `y` and `x` coordinates, in range `[-pi, pi]`.
+    Atan2(H, H),
+    /// Remainder of division of first value by second, with sign of the first one.
+    Rem(H, H),
+    /// Euclidean norm of variable amount of values.
+    Norm(Vec<H>),
+    /// Error function of given value.
+    Erf(H),
+    /// Complementary error function `1 - erf(x)` of given value.
+    Erfc(H),
+    /// Cumulative distribution function of standard normal distribution.
+    NormalCdf(H),
+    /// Probability density function of standard normal distribution.
+    NormalPdf(H),
+    /// Logarithm of sum of exponents of variable amount of values.
+    LogSumExp(Vec<H>),
+    /// Component with given index of softmax over variable amount of values.
+    Softmax(Vec<H>, usize),
+    /// Reduction of variable amount of values to single one.
+    Reduce(Vec<H>, Reduction),
+    /// Second value if first is positive, third value otherwise.
+    /// Only the taken branch is evaluated.
+    Select(H, H, H),
+    /// Value of the first piece whose breakpoint is greater than first value, ordered by
+    /// increasing breakpoints, last value if there's no such piece.
+    /// Only the taken piece is evaluated.
+    Piecewise(H, Vec<(T, H)>, H),
+    /// Value rounded to multiple of given positive step with given rounding.
+    Quantize(H, T, Rounding),
+    /// Polynomial of value with given coefficients, lowest degree first,
+    /// evaluated with Horner's method.
+    Polynomial(H, Vec<T>),
+    /// User-defined operation over variable amount of values.
+    Custom(CustomOp<T>, Vec<H>),
 }
 
-impl Operation {
-    /// Computes operation result depending on its type.
-    pub fn compute(&self) -> f32 {
+/// User-defined operation, computed from values of its operands.
+pub trait Op<T = f32> {
+    /// Returns name of operation, used in errors and exports.
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    /// Computes result from operand values.
+    fn eval(&self, operands: &[T]) -> T;
+
+    /// Returns accepted amounts of operands, checked by `Node::try_new`. Any by default.
+    fn arity(&self) -> RangeInclusive<usize> {
+        0..=usize::MAX
+    }
+
+    /// Returns true if operand with given index may be boolean, checked by `Node::try_new`.
+    /// Booleans aren't accepted by default.
+    fn accepts_boolean(&self, _operand: usize) -> bool {
+        false
+    }
+
+    /// Returns partial derivative of result with respect to operand with given index.
+    /// Estimated with central difference by default.
+    fn derivative(&self, operands: &[T], i: usize) -> T
+    where
+        T: Float,
+    {
+        let h = T::from_f64(1e-3) * operands[i].abs().max(T::ONE);
+        let mut shifted = operands.to_vec();
+        shifted[i] = operands[i] + h;
+        let up = self.eval(&shifted);
+        shifted[i] = operands[i] - h;
+        let down = self.eval(&shifted);
+        (up - down) / (h + h)
+    }
+
+    /// Returns partial derivatives of result with respect to all operands.
+    /// Computed with `derivative` for each operand by default, operations computing
+    /// all of them at once more cheaply can override it.
+    fn partials(&self, operands: &[T]) -> Vec<T>
+    where
+        T: Float,
+    {
+        (0..operands.len())
+            .map(|i| self.derivative(operands, i))
+            .collect()
+    }
+}
+
+/// Shared user-defined operation, equal only to itself.
+#[derive(Clone)]
+pub struct CustomOp<T = f32>(pub Arc<dyn Op<T> + Send + Sync>);
+
+impl<T> fmt::Debug for CustomOp<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // address tells apart distinct operations with the same name
+        write!(
+            f,
+            "CustomOp({} at {:p})",
+            self.0.name(),
+            Arc::as_ptr(&self.0)
+        )
+    }
+}
+
+impl<T> PartialEq for CustomOp<T> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(
+            Arc::as_ptr(&self.0) as *const (),
+            Arc::as_ptr(&other.0) as *const (),
+        )
+    }
+}
+
+/// Ways of reducing variable amount of values to single one.
+/// Reduction of no values gives its identity, or `NaN` for `Mean`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduction {
+    /// Sum of values.
+    Sum,
+    /// Product of values.
+    Prod,
+    /// Smallest of values.
+    Min,
+    /// Largest of values.
+    Max,
+    /// Arithmetic mean of values.
+    Mean,
+}
+
+impl Reduction {
+    /// Reduces values to single one.
+    pub fn apply<T: Float>(self, vals: impl Iterator<Item = T>) -> T {
+        match self {
+            Reduction::Sum => vals.fold(T::ZERO, |acc, x| acc + x),
+            Reduction::Prod => vals.fold(T::ONE, |acc, x| acc * x),
+            Reduction::Min => vals.fold(-T::NEG_INFINITY, |acc, x| if x < acc { x } else { acc }),
+            Reduction::Max => vals.fold(T::NEG_INFINITY, |acc, x| if x > acc { x } else { acc }),
+            Reduction::Mean => {
+                let (sum, n) = vals.fold((T::ZERO, 0), |(sum, n), x| (sum + x, n + 1));
+                sum / T::from_f64(n as f64)
+            }
+        }
+    }
+}
+
+/// Rounding of values lying between two representable multiples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// To nearest, ties to even multiple, also known as banker's rounding.
+    HalfEven,
+    /// To nearest, ties away from zero.
+    HalfAwayFromZero,
+    /// Towards zero, truncating.
+    TowardZero,
+    /// Away from zero.
+    AwayFromZero,
+    /// Towards negative infinity.
+    Floor,
+    /// Towards positive infinity.
+    Ceiling,
+}
+
+impl Rounding {
+    /// Returns name of rounding, used in exports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rounding::HalfEven => "half_even",
+            Rounding::HalfAwayFromZero => "half_away_from_zero",
+            Rounding::TowardZero => "toward_zero",
+            Rounding::AwayFromZero => "away_from_zero",
+            Rounding::Floor => "floor",
+            Rounding::Ceiling => "ceiling",
+        }
+    }
+
+    /// Rounds value to integer, non-finite values are kept.
+    pub fn apply<T: Float>(self, x: T) -> T {
+        if !x.is_finite() {
+            return x;
+        }
+        let fract = x.fract();
+        let trunc = x - fract;
+        let away = if x < T::ZERO {
+            trunc - T::ONE
+        } else {
+            trunc + T::ONE
+        };
+        let half = T::from_f64(0.5);
+        let up = match self {
+            Rounding::HalfEven if fract.abs() == half => (trunc * half).fract() != T::ZERO,
+            Rounding::HalfEven => fract.abs() > half,
+            Rounding::HalfAwayFromZero => fract.abs() >= half,
+            Rounding::TowardZero => false,
+            Rounding::AwayFromZero => fract != T::ZERO,
+            Rounding::Floor => fract < T::ZERO,
+            Rounding::Ceiling => fract > T::ZERO,
+        };
+        if up {
+            away
+        } else {
+            trunc
+        }
+    }
+
+    /// Rounds value to multiple of given step. Steps whose reciprocal is integer, such as
+    /// powers of ten below one, scale by that integer so decimal places round exactly.
+    pub fn quantize<T: Float>(self, x: T, step: T) -> T {
+        let scale = T::ONE / step;
+        if scale.fract() == T::ZERO {
+            self.apply(x * scale) / scale
+        } else {
+            self.apply(x / step) * step
+        }
+    }
+}
+
+impl Default for Rounding {
+    fn default() -> Self {
+        Rounding::HalfEven
+    }
+}
+
+/// Handling of division by zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivPolicy {
+    /// Produces `inf` or `NaN` following IEEE rules.
+    Propagate,
+    /// Fails checked evaluation with domain error, unchecked one behaves like `Propagate`.
+    Error,
+    /// Clamps result into finite range, zero divided by zero gives zero.
+    Clamp,
+}
+
+impl Default for DivPolicy {
+    fn default() -> Self {
+        DivPolicy::Propagate
+    }
+}
+
+/// Error of operand value lying outside of operation domain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainError {
+    /// Name of operation.
+    pub operation: &'static str,
+    /// Offending operand value.
+    pub value: f64,
+}
+
+impl fmt::Display for DomainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is outside of {} domain", self.value, self.operation)
+    }
+}
+
+impl Error for DomainError {}
+
+impl<H, T: Float> Operation<H, T> {
+    /// Returns name of operation, matching its `Node` constructor.
+    pub fn name(&self) -> &'static str {
+        use Operation::*;
+
+        match self {
+            Const(_) => "constant",
+            Add(..) => "add",
+            AddVar(_) => "add_var",
+            Sub(..) => "sub",
+            Mul(..) => "mul",
+            MulVar(_) => "mul_var",
+            Div(..) => "div",
+            Neg(_) => "neg",
+            Recip(_) => "recip",
+            Detach(_) => "detach",
+            Pow(..) => "pow",
+            Sin(_) => "sin",
+            Asin(_) => "asin",
+            Acos(_) => "acos",
+            Atan(_) => "atan",
+            Cos(_) => "cos",
+            Tan(_) => "tan",
+            Exp(_) => "exp",
+            Ln(_) => "ln",
+            Log(..) => "log",
+            Sqrt(_) => "sqrt",
+            Abs(_) => "abs",
+            Sign(_) => "sign",
+            Fract(_) => "fract",
+            Tanh(_) => "tanh",
+            Sigmoid(_) => "sigmoid",
+            Relu(_) => "relu",
+            Softplus(_) => "softplus",
+            ExpM1(_) => "expm1",
+            Ln1p(_) => "ln_1p",
+            Min(..) => "min",
+            Max(..) => "max",
+            Clamp(..) => "clamp",
+            Lt(..) => "lt",
+            Le(..) => "le",
+            Gt(..) => "gt",
+            Ge(..) => "ge",
+            Eq(..) => "eq",
+            Hypot(..) => "hypot",
+            Atan2(..) => "atan2",
+            Rem(..) => "rem",
+            Norm(_) => "norm",
+            Erf(_) => "erf",
+            Erfc(_) => "erfc",
+            NormalCdf(_) => "normal_cdf",
+            NormalPdf(_) => "normal_pdf",
+            LogSumExp(_) => "logsumexp",
+            Softmax(..) => "softmax_component",
+            Reduce(_, reduction) => match reduction {
+                Reduction::Sum => "reduce_sum",
+                Reduction::Prod => "reduce_prod",
+                Reduction::Min => "reduce_min",
+                Reduction::Max => "reduce_max",
+                Reduction::Mean => "reduce_mean",
+            },
+            Select(..) => "lazy_select",
+            Piecewise(..) => "piecewise",
+            Quantize(..) => "quantize",
+            Polynomial(..) => "polynomial",
+            Custom(op, _) => op.0.name(),
+        }
+    }
+
+    /// Computes operation result, obtaining operand values with `f`.
+    pub fn eval(&self, mut f: impl FnMut(&H) -> T) -> T {
         use Operation::*;
 
         match self {
+            Const(val) => *val,
             Add(x, y) => {
-                let x_res = x.borrow_mut().compute();
-                let y_res = y.borrow_mut().compute();
+                let x_res = f(x);
+                let y_res = f(y);
                 x_res + y_res
             }
-            AddVar(args) => args
-                .iter()
-                .fold(0.0, |acc, arg| acc + arg.borrow_mut().compute()),
+            AddVar(args) => args.iter().fold(T::ZERO, |acc, arg| acc + f(arg)),
             Sub(x, y) => {
-                let x_res = x.borrow_mut().compute();
-                let y_res = y.borrow_mut().compute();
+                let x_res = f(x);
+                let y_res = f(y);
                 x_res - y_res
             }
             Mul(x, y) => {
-                let x_res = x.borrow_mut().compute();
-                let y_res = y.borrow_mut().compute();
+                let x_res = f(x);
+                let y_res = f(y);
                 x_res * y_res
             }
-            Pow(x, pow) => x.borrow_mut().compute().powf(*pow),
-            Sin(x) => x.borrow_mut().compute().sin(),
+            MulVar(args) => args.iter().fold(T::ONE, |acc, arg| acc * f(arg)),
+            Div(x, y, policy) => {
+                let x_res = f(x);
+                let y_res = f(y);
+                let res = x_res / y_res;
+                match policy {
+                    DivPolicy::Clamp if y_res == T::ZERO && x_res == T::ZERO => T::ZERO,
+                    DivPolicy::Clamp if res > T::MAX => T::MAX,
+                    DivPolicy::Clamp if res < T::MIN => T::MIN,
+                    _ => res,
+                }
+            }
+            Neg(x) => -f(x),
+            Recip(x) => T::ONE / f(x),
+            Detach(x) => f(x),
+            Pow(x, pow) => f(x).powf(*pow),
+            Sin(x) => f(x).sin(),
+            Asin(x) => f(x).asin(),
+            Acos(x) => f(x).acos(),
+            Atan(x) => f(x).atan(),
+            Cos(x) => f(x).cos(),
+            Tan(x) => f(x).tan(),
+            Exp(x) => f(x).exp(),
+            Ln(x) => f(x).ln(),
+            Log(x, base) => f(x).ln() / base.ln(),
+            Sqrt(x) => f(x).sqrt(),
+            Abs(x) => f(x).abs(),
+            Sign(x) => {
+                let x_res = f(x);
+                if x_res > T::ZERO {
+                    T::ONE
+                } else if x_res < T::ZERO {
+                    -T::ONE
+                } else {
+                    // zero or `NaN`
+                    x_res
+                }
+            }
+            Fract(x) => f(x).fract(),
+            Tanh(x) => f(x).tanh(),
+            Sigmoid(x) => sigmoid(f(x)),
+            Relu(x) => match f(x) {
+                x if x > T::ZERO => x,
+                _ => T::ZERO,
+            },
+            Softplus(x) => softplus(f(x)),
+            ExpM1(x) => f(x).exp_m1(),
+            Ln1p(x) => f(x).ln_1p(),
+            Min(x, y) => {
+                let x_res = f(x);
+                let y_res = f(y);
+                if y_res < x_res {
+                    y_res
+                } else {
+                    x_res
+                }
+            }
+            Max(x, y) => {
+                let x_res = f(x);
+                let y_res = f(y);
+                if y_res > x_res {
+                    y_res
+                } else {
+                    x_res
+                }
+            }
+            Clamp(x, lo, hi) => {
+                let x_res = f(x);
+                let lo_res = f(lo);
+                let hi_res = f(hi);
+                clamp(x_res, lo_res, hi_res)
+            }
+            Lt(x, y) | Le(x, y) | Gt(x, y) | Ge(x, y) | Eq(x, y, _) => {
+                let x_res = f(x);
+                let y_res = f(y);
+                let res = match self {
+                    Lt(..) => x_res < y_res,
+                    Le(..) => x_res <= y_res,
+                    Gt(..) => x_res > y_res,
+                    Ge(..) => x_res >= y_res,
+                    Eq(_, _, tolerance) => (x_res - y_res).abs() <= *tolerance,
+                    _ => unreachable!(),
+                };
+                if res {
+                    T::ONE
+                } else {
+                    T::ZERO
+                }
+            }
+            Hypot(x, y) => {
+                let x_res = f(x);
+                let y_res = f(y);
+                x_res.hypot(y_res)
+            }
+            Atan2(y, x) => {
+                let y_res = f(y);
+                let x_res = f(x);
+                y_res.atan2(x_res)
+            }
+            Rem(x, y) => {
+                let x_res = f(x);
+                let y_res = f(y);
+                x_res.rem(y_res)
+            }
+            Norm(args) => {
+                let vals: Vec<T> = args.iter().map(f).collect();
+                norm(|| vals.iter().copied())
+            }
+            Erf(x) => T::from_f64(erf(f(x).to_f64())),
+            Erfc(x) => T::from_f64(erfc(f(x).to_f64())),
+            NormalCdf(x) => T::from_f64(0.5 * erfc(-f(x).to_f64() / std::f64::consts::SQRT_2)),
+            NormalPdf(x) => {
+                let x = f(x).to_f64();
+                T::from_f64((-0.5 * x * x).exp() / (2.0 * std::f64::consts::PI).sqrt())
+            }
+            LogSumExp(args) => {
+                let vals: Vec<T> = args.iter().map(f).collect();
+                log_sum_exp(|| vals.iter().copied())
+            }
+            Softmax(args, i) => {
+                let vals: Vec<T> = args.iter().map(f).collect();
+                softmax(|| vals.iter().copied(), *i)
+            }
+            Reduce(args, reduction) => reduction.apply(args.iter().map(f)),
+            Select(cond, x, y) => {
+                if f(cond) > T::ZERO {
+                    f(x)
+                } else {
+                    f(y)
+                }
+            }
+            Piecewise(x, pieces, default) => {
+                let x = f(x);
+                f(piece(x, pieces, default))
+            }
+            Quantize(x, step, rounding) => rounding.quantize(f(x), *step),
+            Polynomial(x, coeffs) => horner(f(x), coeffs),
+            Custom(op, args) => {
+                let vals: Vec<T> = args.iter().map(f).collect();
+                op.0.eval(&vals)
+            }
         }
     }
+
+    /// Computes operation result like `eval`, but fails if operand is outside of operation domain
+    /// instead of producing `NaN`.
+    pub fn try_eval(&self, mut f: impl FnMut(&H) -> T) -> Result<T, DomainError> {
+        use Operation::*;
+
+        let error = |x: T| DomainError {
+            operation: self.name(),
+            value: x.to_f64(),
+        };
+        let unit = |x: T| {
+            if x >= -T::ONE && x <= T::ONE {
+                Ok(x)
+            } else {
+                Err(error(x))
+            }
+        };
+        match self {
+            Div(x, y, DivPolicy::Error) => {
+                let x_res = f(x);
+                match f(y) {
+                    y if y == T::ZERO => Err(error(y)),
+                    y => Ok(x_res / y),
+                }
+            }
+            Pow(x, pow) => match f(x) {
+                x if x < T::ZERO && pow.fract() != T::ZERO => Err(error(x)),
+                x => Ok(x.powf(*pow)),
+            },
+            Asin(x) => unit(f(x)).map(T::asin),
+            Acos(x) => unit(f(x)).map(T::acos),
+            Ln1p(x) => match f(x) {
+                x if x > -T::ONE => Ok(x.ln_1p()),
+                x => Err(error(x)),
+            },
+            Ln(x) => match f(x) {
+                x if x > T::ZERO => Ok(x.ln()),
+                x => Err(error(x)),
+            },
+            Log(x, base) => match f(x) {
+                x if x > T::ZERO => Ok(x.ln() / base.ln()),
+                x => Err(error(x)),
+            },
+            Sqrt(x) => match f(x) {
+                x if x >= T::ZERO => Ok(x.sqrt()),
+                x => Err(error(x)),
+            },
+            _ => Ok(self.eval(f)),
+        }
+    }
+
+    /// Returns operands of this operation in evaluation order.
+    pub fn operands(&self) -> Vec<&H> {
+        use Operation::*;
+
+        match self {
+            Const(_) => vec![],
+            Add(x, y) | Sub(x, y) | Mul(x, y) | Div(x, y, _) | Hypot(x, y) => vec![x, y],
+            Min(x, y) | Max(x, y) | Atan2(x, y) | Rem(x, y) => vec![x, y],
+            Lt(x, y) | Le(x, y) | Gt(x, y) | Ge(x, y) | Eq(x, y, _) => vec![x, y],
+            Clamp(x, lo, hi) => vec![x, lo, hi],
+            AddVar(args) | MulVar(args) | Norm(args) | Reduce(args, _) => args.iter().collect(),
+            LogSumExp(args) | Softmax(args, _) | Custom(_, args) => args.iter().collect(),
+            Pow(x, _) | Sin(x) | Asin(x) | Acos(x) | Atan(x) | ExpM1(x) | Ln1p(x) => {
+                vec![x]
+            }
+            Neg(x) | Recip(x) | Detach(x) => vec![x],
+            Cos(x) | Tan(x) | Exp(x) | Ln(x) | Log(x, _) | Sqrt(x) | Abs(x) => vec![x],
+            Sign(x) | Fract(x) | Quantize(x, ..) | Polynomial(x, _) => vec![x],
+            Tanh(x) | Sigmoid(x) | Relu(x) | Softplus(x) => vec![x],
+            Erf(x) | Erfc(x) | NormalCdf(x) | NormalPdf(x) => vec![x],
+            Select(cond, x, y) => vec![cond, x, y],
+            Piecewise(x, pieces, default) => std::iter::once(x)
+                .chain(pieces.iter().map(|(_, piece)| piece))
+                .chain(std::iter::once(default))
+                .collect(),
+        }
+    }
+
+    /// Returns operands computed whenever operation is, leaving out branches of lazy
+    /// operations, which are computed only once selected.
+    pub(crate) fn eager_operands(&self) -> Vec<&H> {
+        match self {
+            Operation::Select(cond, ..) => vec![cond],
+            Operation::Piecewise(x, ..) => vec![x],
+            _ => self.operands(),
+        }
+    }
+
+    /// Returns same operation with operands mapped to other handles.
+    pub fn map_operands<H2>(&self, mut f: impl FnMut(&H) -> H2) -> Operation<H2, T> {
+        use Operation::*;
+
+        let mut map_all = |args: &[H]| args.iter().map(&mut f).collect();
+        match self {
+            Const(val) => Const(*val),
+            Add(x, y) => Add(f(x), f(y)),
+            AddVar(args) => AddVar(map_all(args)),
+            Sub(x, y) => Sub(f(x), f(y)),
+            Mul(x, y) => Mul(f(x), f(y)),
+            MulVar(args) => MulVar(map_all(args)),
+            Div(x, y, policy) => Div(f(x), f(y), *policy),
+            Neg(x) => Neg(f(x)),
+            Recip(x) => Recip(f(x)),
+            Detach(x) => Detach(f(x)),
+            Pow(x, pow) => Pow(f(x), *pow),
+            Sin(x) => Sin(f(x)),
+            Asin(x) => Asin(f(x)),
+            Acos(x) => Acos(f(x)),
+            Atan(x) => Atan(f(x)),
+            Cos(x) => Cos(f(x)),
+            Tan(x) => Tan(f(x)),
+            Exp(x) => Exp(f(x)),
+            Ln(x) => Ln(f(x)),
+            Log(x, base) => Log(f(x), *base),
+            Sqrt(x) => Sqrt(f(x)),
+            Abs(x) => Abs(f(x)),
+            Sign(x) => Sign(f(x)),
+            Fract(x) => Fract(f(x)),
+            Tanh(x) => Tanh(f(x)),
+            Sigmoid(x) => Sigmoid(f(x)),
+            Relu(x) => Relu(f(x)),
+            Softplus(x) => Softplus(f(x)),
+            ExpM1(x) => ExpM1(f(x)),
+            Ln1p(x) => Ln1p(f(x)),
+            Min(x, y) => Min(f(x), f(y)),
+            Max(x, y) => Max(f(x), f(y)),
+            Clamp(x, lo, hi) => Clamp(f(x), f(lo), f(hi)),
+            Lt(x, y) => Lt(f(x), f(y)),
+            Le(x, y) => Le(f(x), f(y)),
+            Gt(x, y) => Gt(f(x), f(y)),
+            Ge(x, y) => Ge(f(x), f(y)),
+            Eq(x, y, tolerance) => Eq(f(x), f(y), *tolerance),
+            Hypot(x, y) => Hypot(f(x), f(y)),
+            Atan2(y, x) => Atan2(f(y), f(x)),
+            Rem(x, y) => Rem(f(x), f(y)),
+            Norm(args) => Norm(map_all(args)),
+            Erf(x) => Erf(f(x)),
+            Erfc(x) => Erfc(f(x)),
+            NormalCdf(x) => NormalCdf(f(x)),
+            NormalPdf(x) => NormalPdf(f(x)),
+            LogSumExp(args) => LogSumExp(map_all(args)),
+            Softmax(args, i) => Softmax(map_all(args), *i),
+            Reduce(args, reduction) => Reduce(map_all(args), *reduction),
+            Select(cond, x, y) => Select(f(cond), f(x), f(y)),
+            Piecewise(x, pieces, default) => Piecewise(
+                f(x),
+                pieces.iter().map(|(b, piece)| (*b, f(piece))).collect(),
+                f(default),
+            ),
+            Quantize(x, step, rounding) => Quantize(f(x), *step, *rounding),
+            Polynomial(x, coeffs) => Polynomial(f(x), coeffs.clone()),
+            Custom(op, args) => Custom(op.clone(), map_all(args)),
+        }
+    }
+}
+
+/// Evaluates polynomial with given coefficients, lowest degree first, with Horner's method.
+pub(crate) fn horner<T: Float>(x: T, coeffs: &[T]) -> T {
+    coeffs.iter().rev().fold(T::ZERO, |acc, c| acc * x + *c)
+}
+
+/// Returns coefficients of derivative of polynomial, lowest degree first.
+pub(crate) fn derivative_coeffs<T: Float>(coeffs: &[T]) -> Vec<T> {
+    (1..coeffs.len())
+        .map(|i| coeffs[i] * T::from_f64(i as f64))
+        .collect()
+}
+
+/// Computes Euclidean norm, scaling values by the largest magnitude so squares don't overflow.
+pub(crate) fn norm<T: Float, I: Iterator<Item = T>>(vals: impl Fn() -> I) -> T {
+    let scale = vals().fold(T::ZERO, |acc, x| acc.max(x.abs()));
+    if scale == T::ZERO || scale.is_infinite() {
+        return scale;
+    }
+    let sum: T = vals().map(|x| x / scale).map(|x| x * x).sum();
+    scale * sum.sqrt()
+}
+
+/// Computes logarithm of sum of exponents, shifting by maximum to avoid overflow.
+/// Values are iterated twice, so they aren't collected.
+pub(crate) fn log_sum_exp<T: Float, I: Iterator<Item = T>>(vals: impl Fn() -> I) -> T {
+    let max = vals().fold(T::NEG_INFINITY, |acc, x| acc.max(x));
+    if max.is_infinite() {
+        return max;
+    }
+    max + vals().map(|x| (x - max).exp()).sum::<T>().ln()
+}
+
+/// Returns piece taken for given value, see `Operation::Piecewise`.
+pub(crate) fn piece<'a, H, T: Float>(x: T, pieces: &'a [(T, H)], default: &'a H) -> &'a H {
+    pieces
+        .iter()
+        .find(|(breakpoint, _)| x < *breakpoint)
+        .map_or(default, |(_, piece)| piece)
+}
+
+/// Computes softmax component with given index, shifting by maximum to avoid overflow.
+/// Values are iterated several times, so they aren't collected.
+pub(crate) fn softmax<T: Float, I: Iterator<Item = T>>(vals: impl Fn() -> I, i: usize) -> T {
+    let max = vals().fold(T::NEG_INFINITY, |acc, x| acc.max(x));
+    let component = vals().nth(i).expect("softmax component index out of range");
+    (component - max).exp() / vals().map(|x| (x - max).exp()).sum::<T>()
+}
+
+/// Limits value to range, with upper bound winning if bounds are swapped.
+pub(crate) fn clamp<T: Float>(x: T, lo: T, hi: T) -> T {
+    let x = if x < lo { lo } else { x };
+    if x > hi {
+        hi
+    } else {
+        x
+    }
+}
+
+/// Computes logistic function without overflowing exponent for large magnitudes.
+pub(crate) fn sigmoid<T: Float>(x: T) -> T {
+    if x >= T::ZERO {
+        T::ONE / (T::ONE + (-x).exp())
+    } else {
+        let e = x.exp();
+        e / (T::ONE + e)
+    }
+}
+
+/// Computes `ln(1 + exp(x))` without overflowing exponent for large values.
+pub(crate) fn softplus<T: Float>(x: T) -> T {
+    x.max(T::ZERO) + (-x.abs()).exp().ln_1p()
+}
+
+/// Computes error function, using Taylor series near zero to keep relative precision.
+fn erf(x: f64) -> f64 {
+    if x.abs() >= 0.5 {
+        return 1.0 - erfc(x);
+    }
+    let x2 = x * x;
+    let mut term = x;
+    let mut sum = x;
+    for n in 1..12 {
+        term *= -x2 / n as f64;
+        sum += term / (2 * n + 1) as f64;
+    }
+    sum * std::f64::consts::FRAC_2_SQRT_PI
+}
+
+/// Computes complementary error function with Chebyshev approximation,
+/// fractional error is below `1.2e-7` everywhere, which also bounds precision for `f64`.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ]
+    .iter()
+    .rev()
+    .fold(0.0, |acc, c| c + t * acc);
+    let res = t * (-z * z + poly).exp();
+    if x >= 0.0 {
+        res
+    } else {
+        2.0 - res
+    }
+}
+
+impl<C: Computable<T> + ?Sized, T: Float> Operation<Rc<RefCell<C>>, T> {
+    /// Computes operation result depending on its type.
+    pub fn compute(&self) -> T {
+        self.eval(|x| x.borrow_mut().compute())
+    }
+
+    /// Computes operation result, checking operand domain.
+    pub fn try_compute(&self) -> Result<T, DomainError> {
+        self.try_eval(|x| x.borrow_mut().compute())
+    }
 }
 
 #[cfg(test)]
@@ -73,6 +883,12 @@ mod tests {
         fn reset_cache(&mut self) {}
     }
 
+    #[test]
+    fn constant() {
+        let opp = Operation::<Rc<RefCell<Const>>>::Const(42.0);
+        assert_eq!(opp.compute(), 42.0);
+    }
+
     #[test]
     fn add() {
         let x1 = Const::from_val(1.0);
@@ -92,6 +908,29 @@ mod tests {
         assert_eq!(opp.compute(), 6.0);
     }
 
+    #[test]
+    fn reductions() {
+        let args: Vec<Rc<RefCell<dyn Computable>>> = vec![
+            Const::from_val(2.0),
+            Const::from_val(-1.0),
+            Const::from_val(5.0),
+        ];
+        assert_eq!(Operation::MulVar(args.clone()).compute(), -10.0);
+        let reduce = |reduction| Operation::Reduce(args.clone(), reduction).compute();
+        assert_eq!(reduce(Reduction::Sum), 6.0);
+        assert_eq!(reduce(Reduction::Prod), -10.0);
+        assert_eq!(reduce(Reduction::Min), -1.0);
+        assert_eq!(reduce(Reduction::Max), 5.0);
+        assert_eq!(reduce(Reduction::Mean), 2.0);
+
+        assert_eq!(Reduction::Prod.apply(std::iter::empty::<f32>()), 1.0);
+        assert_eq!(
+            Reduction::Min.apply(std::iter::empty::<f32>()),
+            f32::INFINITY
+        );
+        assert!(Reduction::Mean.apply(std::iter::empty::<f32>()).is_nan());
+    }
+
     #[test]
     fn sub() {
         let x1 = Const::from_val(1.0);
@@ -108,6 +947,27 @@ mod tests {
         assert_eq!(opp.compute(), 6.0);
     }
 
+    #[test]
+    fn div() {
+        let div = |x, y, policy| Operation::Div(Const::from_val(x), Const::from_val(y), policy);
+        assert_eq!(div(3.0, 2.0, DivPolicy::Propagate).compute(), 1.5);
+        assert_eq!(div(1.0, 0.0, DivPolicy::Propagate).compute(), f32::INFINITY);
+        assert!(div(0.0, 0.0, DivPolicy::Propagate).compute().is_nan());
+        assert_eq!(div(-1.0, 0.0, DivPolicy::Clamp).compute(), f32::MIN);
+        assert_eq!(div(0.0, 0.0, DivPolicy::Clamp).compute(), 0.0);
+        assert_eq!(div(3.0, 2.0, DivPolicy::Clamp).compute(), 1.5);
+
+        assert_eq!(div(3.0, 2.0, DivPolicy::Error).try_compute(), Ok(1.5));
+        assert_eq!(
+            div(3.0, 0.0, DivPolicy::Error).try_compute(),
+            Err(DomainError {
+                operation: "div",
+                value: 0.0
+            })
+        );
+        assert!(div(1.0, 0.0, DivPolicy::Propagate).try_compute().is_ok());
+    }
+
     #[test]
     fn pow() {
         let x1 = Const::from_val(2.0);
@@ -121,4 +981,239 @@ mod tests {
         let opp = Operation::Sin(x1);
         assert_eq!(opp.compute(), 1.0);
     }
+
+    #[test]
+    fn inverse_trig() {
+        let opp = Operation::Asin(Const::from_val(1.0));
+        assert_eq!(opp.compute(), std::f32::consts::FRAC_PI_2);
+        let opp = Operation::Acos(Const::from_val(1.0));
+        assert_eq!(opp.compute(), 0.0);
+        let opp = Operation::Atan(Const::from_val(1.0));
+        assert_eq!(opp.compute(), std::f32::consts::FRAC_PI_4);
+    }
+
+    #[test]
+    fn activations() {
+        assert_eq!(sigmoid(0.0f32), 0.5);
+        assert_eq!(sigmoid(-1000.0f32), 0.0);
+        assert_eq!(sigmoid(1000.0f32), 1.0);
+        assert_eq!(softplus(1000.0f32), 1000.0);
+        assert!((softplus(0.0f64) - 2f64.ln()).abs() < 1e-15);
+        assert_eq!(Operation::Relu(Const::from_val(-2.0)).compute(), 0.0);
+        assert_eq!(Operation::Relu(Const::from_val(2.0)).compute(), 2.0);
+    }
+
+    #[test]
+    fn exp_m1_ln_1p() {
+        let opp = Operation::ExpM1(Const::from_val(1e-10));
+        assert_eq!(opp.compute(), 1e-10);
+        let opp = Operation::Ln1p(Const::from_val(1e-10));
+        assert_eq!(opp.compute(), 1e-10);
+        assert_eq!(
+            Operation::Ln1p(Const::from_val(-1.0)).try_compute(),
+            Err(DomainError {
+                operation: "ln_1p",
+                value: -1.0
+            })
+        );
+    }
+
+    #[test]
+    fn hypot_norm() {
+        let opp = Operation::Hypot(Const::from_val(3.0), Const::from_val(4.0));
+        assert_eq!(opp.compute(), 5.0);
+
+        let args: Vec<Rc<RefCell<dyn Computable>>> = vec![
+            Const::from_val(2.0),
+            Const::from_val(-4.0),
+            Const::from_val(4.0),
+        ];
+        assert_eq!(Operation::Norm(args).compute(), 6.0);
+
+        let big = Operation::Norm(vec![Const::from_val(3e30), Const::from_val(4e30)]);
+        assert_eq!(big.compute(), 5e30);
+        let opp = Operation::Norm(vec![Const::from_val(f32::INFINITY), Const::from_val(1.0)]);
+        assert_eq!(opp.compute(), f32::INFINITY);
+        assert_eq!(Operation::<Rc<RefCell<Const>>>::Norm(vec![]).compute(), 0.0);
+    }
+
+    #[test]
+    fn sign_fract() {
+        let sign = |val: f32| Operation::Sign(Const::from_val(val)).compute();
+        assert_eq!((sign(-2.5), sign(0.0), sign(3.0)), (-1.0, 0.0, 1.0));
+        assert!(sign(f32::NAN).is_nan());
+        let fract = |val: f32| Operation::Fract(Const::from_val(val)).compute();
+        assert_eq!((fract(2.25), fract(-2.25)), (0.25, -0.25));
+    }
+
+    #[test]
+    fn atan2_rem() {
+        let opp = Operation::Atan2(Const::from_val(1.0), Const::from_val(-1.0));
+        assert_eq!(opp.compute(), 0.75 * std::f32::consts::PI);
+        let opp = Operation::Rem(Const::from_val(-7.0), Const::from_val(3.0));
+        assert_eq!(opp.compute(), -1.0);
+        let opp = Operation::Rem(Const::from_val(1.0), Const::from_val(0.0));
+        assert!(opp.compute().is_nan());
+    }
+
+    #[test]
+    fn error_function() {
+        let close = |opp: Operation<Rc<RefCell<Const>>>, expected: f32| {
+            let res = opp.compute();
+            assert!(
+                ((res - expected) / expected).abs() < 1e-6,
+                "{} != {}",
+                res,
+                expected
+            );
+        };
+        close(Operation::Erf(Const::from_val(1e-10)), 1.1283792e-10);
+        close(Operation::Erf(Const::from_val(0.3)), 0.32862676);
+        close(Operation::Erf(Const::from_val(-1.0)), -0.8427008);
+        close(Operation::Erfc(Const::from_val(0.5)), 0.47950012);
+        close(Operation::Erfc(Const::from_val(3.0)), 2.2090497e-5);
+        close(Operation::Erfc(Const::from_val(-2.0)), 1.9953223);
+        close(Operation::NormalCdf(Const::from_val(1.96)), 0.9750021);
+        close(Operation::NormalCdf(Const::from_val(-4.0)), 3.1671242e-5);
+        close(Operation::NormalPdf(Const::from_val(0.0)), 0.3989423);
+        close(Operation::NormalPdf(Const::from_val(-2.0)), 0.05399097);
+    }
+
+    #[test]
+    fn log_sum_exp() {
+        let opp = Operation::LogSumExp(vec![Const::from_val(0.0), Const::from_val(0.0)]);
+        assert_eq!(opp.compute(), 2f32.ln());
+        let opp = Operation::LogSumExp(vec![Const::from_val(1000.0), Const::from_val(1000.0)]);
+        assert_eq!(opp.compute(), 1000.0 + 2f32.ln());
+        let opp = Operation::LogSumExp(vec![Const::from_val(f32::NEG_INFINITY)]);
+        assert_eq!(opp.compute(), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn softmax() {
+        let args = vec![
+            Const::from_val(1000.0),
+            Const::from_val(1000.0),
+            Const::from_val(-1000.0),
+        ];
+        assert_eq!(Operation::Softmax(args.clone(), 0).compute(), 0.5);
+        assert_eq!(Operation::Softmax(args, 2).compute(), 0.0);
+    }
+
+    #[test]
+    fn select_is_lazy() {
+        let opp = Operation::Select(1, 2, 3);
+        let mut evaluated = vec![];
+        let res = opp.eval(|x| {
+            evaluated.push(*x);
+            *x as f32 * 10.0
+        });
+        assert_eq!(res, 20.0);
+        assert_eq!(evaluated, vec![1, 2]);
+
+        let opp = Operation::Select(
+            Const::from_val(0.0),
+            Const::from_val(2.0),
+            Const::from_val(3.0),
+        );
+        assert_eq!(opp.compute(), 3.0);
+    }
+
+    #[test]
+    fn rounding() {
+        let round = |rounding: Rounding| -> Vec<f64> {
+            [2.5, 3.5, -2.5, 2.4, -2.6, 7.0]
+                .into_iter()
+                .map(|x| rounding.apply(x))
+                .collect()
+        };
+        assert_eq!(
+            round(Rounding::HalfEven),
+            vec![2.0, 4.0, -2.0, 2.0, -3.0, 7.0]
+        );
+        assert_eq!(
+            round(Rounding::HalfAwayFromZero),
+            vec![3.0, 4.0, -3.0, 2.0, -3.0, 7.0]
+        );
+        assert_eq!(
+            round(Rounding::TowardZero),
+            vec![2.0, 3.0, -2.0, 2.0, -2.0, 7.0]
+        );
+        assert_eq!(
+            round(Rounding::AwayFromZero),
+            vec![3.0, 4.0, -3.0, 3.0, -3.0, 7.0]
+        );
+        assert_eq!(round(Rounding::Floor), vec![2.0, 3.0, -3.0, 2.0, -3.0, 7.0]);
+        assert_eq!(
+            round(Rounding::Ceiling),
+            vec![3.0, 4.0, -2.0, 3.0, -2.0, 7.0]
+        );
+        assert!(Rounding::Floor.apply(f64::NAN).is_nan());
+
+        // hundredths are scaled by exact integer
+        assert_eq!(Rounding::HalfEven.quantize(0.125, 0.01), 0.12);
+        assert_eq!(Rounding::HalfAwayFromZero.quantize(0.125, 0.01), 0.13);
+        assert_eq!(Rounding::HalfEven.quantize(1.3f32, 0.25), 1.25);
+        assert_eq!(Rounding::Ceiling.quantize(101.0, 50.0), 150.0);
+    }
+
+    #[test]
+    fn piecewise_is_lazy() {
+        let opp = Operation::Piecewise(0, vec![(10.0, 1), (20.0, 2)], 3);
+        for (x, expected) in [(5.0, 1), (10.0, 2), (25.0, 3)] {
+            let mut evaluated = vec![];
+            opp.eval(|i| {
+                evaluated.push(*i);
+                if *i == 0 {
+                    x
+                } else {
+                    0.0
+                }
+            });
+            assert_eq!(evaluated, vec![0, expected]);
+        }
+    }
+
+    #[test]
+    fn domain_check() {
+        let opp = Operation::Acos(Const::from_val(-1.0));
+        assert_eq!(opp.try_compute(), Ok(std::f32::consts::PI));
+
+        let opp = Operation::Asin(Const::from_val(1.5));
+        assert!(opp.compute().is_nan());
+        assert_eq!(
+            opp.try_compute(),
+            Err(DomainError {
+                operation: "asin",
+                value: 1.5
+            })
+        );
+
+        let opp = Operation::Atan(Const::from_val(1e6));
+        assert!(opp.try_compute().is_ok());
+
+        let opp = Operation::Pow(Const::from_val(-8.0), 1.0 / 3.0);
+        assert_eq!(
+            opp.try_compute(),
+            Err(DomainError {
+                operation: "pow",
+                value: -8.0
+            })
+        );
+        assert_eq!(
+            Operation::Pow(Const::from_val(-2.0), 3.0).try_compute(),
+            Ok(-8.0)
+        );
+    }
+
+    #[test]
+    fn eval_with_handles() {
+        let opp = Operation::AddVar(vec![1, 2, 3]);
+        assert_eq!(opp.eval(|x| *x as f32 * 2.0), 12.0);
+        assert_eq!(opp.operands(), vec![&1, &2, &3]);
+
+        let opp = Operation::Pow(2, 3.0);
+        assert_eq!(opp.eval(|x| *x as f32), 8.0);
+        assert_eq!(opp.operands(), vec![&2]);
+    }
 }
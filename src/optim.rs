@@ -0,0 +1,460 @@
+//! Optimizers minimizing graph output over its inputs.
+//!
+//! Optimizers drive inputs only through `Input::probe` and read the objective with
+//! `Computable::compute`, so cached subgraphs unaffected by a change are reused.
+//! Gradient methods take derivatives by inputs with reverse-mode differentiation, see `grad`.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    grad::Gradient,
+    input::{settle, Input},
+    node::Computable,
+};
+
+/// Result of minimization.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimum {
+    /// Input values at the best point found, in order of given inputs.
+    pub point: Vec<f32>,
+    /// Objective value at `point`.
+    pub value: f32,
+    /// Amount of iterations performed.
+    pub iterations: usize,
+    /// Amount of objective evaluations performed.
+    pub evaluations: usize,
+}
+
+/// Optimization method.
+pub trait Method {
+    /// Searches for minimum of the problem objective.
+    fn minimize(&self, problem: &mut Problem) -> Minimum;
+}
+
+/// Objective together with inputs it is minimized over.
+pub struct Problem<'a> {
+    objective: &'a mut dyn FnMut() -> f32,
+    /// Derivatives of objective by inputs at current point.
+    gradient: &'a mut dyn FnMut() -> Vec<f32>,
+    inputs: &'a [Rc<RefCell<dyn Input>>],
+    evaluations: usize,
+}
+
+impl<'a> Problem<'a> {
+    /// Returns amount of inputs.
+    pub fn dimension(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Returns current input values.
+    pub fn point(&self) -> Vec<f32> {
+        self.inputs
+            .iter()
+            .map(|input| input.borrow_mut().compute())
+            .collect()
+    }
+
+    /// Returns amount of objective evaluations so far.
+    pub fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+
+    /// Probes inputs at given point and evaluates objective there.
+    pub fn eval(&mut self, point: &[f32]) -> f32 {
+        self.inputs
+            .iter()
+            .zip(point)
+            .for_each(|(input, val)| input.borrow_mut().probe(*val));
+        self.evaluations += 1;
+        (self.objective)()
+    }
+
+    /// Probes inputs at given point and evaluates objective there,
+    /// along with its derivatives by inputs.
+    pub fn eval_gradient(&mut self, point: &[f32]) -> (f32, Vec<f32>) {
+        let value = self.eval(point);
+        (value, (self.gradient)())
+    }
+}
+
+/// Entry point for running optimization methods over graphs.
+pub struct Optimizer;
+
+impl Optimizer {
+    /// Minimizes `loss` over given inputs, starting from their current values.
+    /// Inputs are left set to the best point found, through `Input::set` unless
+    /// the point violates their rules.
+    pub fn minimize<C: Computable + ?Sized>(
+        loss: &Rc<RefCell<C>>,
+        inputs: &[Rc<RefCell<dyn Input>>],
+        method: impl Method,
+    ) -> Minimum {
+        let mut objective = || loss.borrow_mut().compute();
+        let mut gradient = || {
+            let gradient = Gradient::of(loss);
+            inputs.iter().map(|input| gradient.wrt(input)).collect()
+        };
+        let mut problem = Problem {
+            objective: &mut objective,
+            gradient: &mut gradient,
+            inputs,
+            evaluations: 0,
+        };
+        let original = problem.point();
+        let minimum = method.minimize(&mut problem);
+        for ((input, original), val) in inputs.iter().zip(original).zip(&minimum.point) {
+            settle(&mut *input.borrow_mut(), original, *val);
+        }
+        minimum
+    }
+}
+
+/// Derivative-free Nelder–Mead simplex method.
+#[derive(Debug, Clone)]
+pub struct NelderMead {
+    /// Maximum amount of iterations.
+    pub max_iters: usize,
+    /// Search stops when spread of objective values over the simplex falls below this value.
+    pub tolerance: f32,
+    /// Offset of initial simplex vertices from the starting point along each axis.
+    pub initial_step: f32,
+    /// Optional `(lower, upper)` bounds per input, points are clamped into them.
+    pub bounds: Option<Vec<(f32, f32)>>,
+}
+
+impl Default for NelderMead {
+    fn default() -> Self {
+        Self {
+            max_iters: 1000,
+            tolerance: 1e-6,
+            initial_step: 0.5,
+            bounds: None,
+        }
+    }
+}
+
+impl NelderMead {
+    /// Clamps point into configured bounds.
+    fn clamp(&self, point: &mut [f32]) {
+        if let Some(bounds) = &self.bounds {
+            point
+                .iter_mut()
+                .zip(bounds)
+                .for_each(|(x, (lo, hi))| *x = x.clamp(*lo, *hi));
+        }
+    }
+
+    /// Returns `a + t * (b - a)`, clamped into bounds.
+    fn along(&self, a: &[f32], b: &[f32], t: f32) -> Vec<f32> {
+        let mut point: Vec<f32> = a.iter().zip(b).map(|(a, b)| a + t * (b - a)).collect();
+        self.clamp(&mut point);
+        point
+    }
+}
+
+impl Method for NelderMead {
+    fn minimize(&self, problem: &mut Problem) -> Minimum {
+        let n = problem.dimension();
+        let mut start = problem.point();
+        self.clamp(&mut start);
+
+        let mut simplex: Vec<(Vec<f32>, f32)> = Vec::with_capacity(n + 1);
+        let value = problem.eval(&start);
+        simplex.push((start.clone(), value));
+        for i in 0..n {
+            let mut vertex = start.clone();
+            vertex[i] += self.initial_step;
+            self.clamp(&mut vertex);
+            if vertex[i] == start[i] {
+                vertex[i] -= self.initial_step;
+                self.clamp(&mut vertex);
+            }
+            let value = problem.eval(&vertex);
+            simplex.push((vertex, value));
+        }
+
+        let mut iterations = 0;
+        while iterations < self.max_iters {
+            simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+            if simplex[n].1 - simplex[0].1 <= self.tolerance {
+                break;
+            }
+            iterations += 1;
+
+            let mut centroid = vec![0.0; n];
+            for (vertex, _) in &simplex[..n] {
+                centroid
+                    .iter_mut()
+                    .zip(vertex)
+                    .for_each(|(c, x)| *c += x / n as f32);
+            }
+            let worst = simplex[n].clone();
+
+            let reflected = self.along(&centroid, &worst.0, -1.0);
+            let reflected_val = problem.eval(&reflected);
+            if reflected_val < simplex[0].1 {
+                let expanded = self.along(&centroid, &worst.0, -2.0);
+                let expanded_val = problem.eval(&expanded);
+                simplex[n] = if expanded_val < reflected_val {
+                    (expanded, expanded_val)
+                } else {
+                    (reflected, reflected_val)
+                };
+                continue;
+            }
+            if reflected_val < simplex[n - 1].1 {
+                simplex[n] = (reflected, reflected_val);
+                continue;
+            }
+
+            let contracted = if reflected_val < worst.1 {
+                self.along(&centroid, &reflected, 0.5)
+            } else {
+                self.along(&centroid, &worst.0, 0.5)
+            };
+            let contracted_val = problem.eval(&contracted);
+            if contracted_val < worst.1.min(reflected_val) {
+                simplex[n] = (contracted, contracted_val);
+                continue;
+            }
+
+            let best = simplex[0].0.clone();
+            for (vertex, value) in simplex.iter_mut().skip(1) {
+                *vertex = self.along(&best, vertex, 0.5);
+                *value = problem.eval(vertex);
+            }
+        }
+
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (point, value) = simplex.swap_remove(0);
+        Minimum {
+            point,
+            value,
+            iterations,
+            evaluations: problem.evaluations(),
+        }
+    }
+}
+
+/// Gradient descent with fixed learning rate.
+#[derive(Debug, Clone)]
+pub struct GradientDescent {
+    /// Step size along the negative gradient.
+    pub lr: f32,
+    /// Amount of iterations.
+    pub iters: usize,
+}
+
+impl Default for GradientDescent {
+    fn default() -> Self {
+        Self {
+            lr: 0.01,
+            iters: 1000,
+        }
+    }
+}
+
+impl Method for GradientDescent {
+    fn minimize(&self, problem: &mut Problem) -> Minimum {
+        descend(problem, self.iters, |_, point, gradient| {
+            point
+                .iter_mut()
+                .zip(gradient)
+                .for_each(|(x, g)| *x -= self.lr * g);
+        })
+    }
+}
+
+/// Adam, gradient descent with per-input steps adapted to running moments of gradients.
+#[derive(Debug, Clone)]
+pub struct Adam {
+    /// Step size.
+    pub lr: f32,
+    /// Amount of iterations.
+    pub iters: usize,
+    /// Decay rate of the first moment estimate.
+    pub beta1: f32,
+    /// Decay rate of the second moment estimate.
+    pub beta2: f32,
+    /// Term added to denominator for numerical stability.
+    pub epsilon: f32,
+}
+
+impl Default for Adam {
+    fn default() -> Self {
+        Self {
+            lr: 0.001,
+            iters: 1000,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+        }
+    }
+}
+
+impl Method for Adam {
+    fn minimize(&self, problem: &mut Problem) -> Minimum {
+        let n = problem.dimension();
+        let (mut m, mut v) = (vec![0.0; n], vec![0.0; n]);
+        descend(problem, self.iters, |t, point, gradient| {
+            let correction1 = 1.0 - self.beta1.powi(t as i32);
+            let correction2 = 1.0 - self.beta2.powi(t as i32);
+            for i in 0..n {
+                m[i] = self.beta1 * m[i] + (1.0 - self.beta1) * gradient[i];
+                v[i] = self.beta2 * v[i] + (1.0 - self.beta2) * gradient[i] * gradient[i];
+                let m_hat = m[i] / correction1;
+                let v_hat = v[i] / correction2;
+                point[i] -= self.lr * m_hat / (v_hat.sqrt() + self.epsilon);
+            }
+        })
+    }
+}
+
+/// Runs up to `iters` steps updating point from gradient, given along with 1-based
+/// step index, and returns the best point found. Stops early at zero gradient.
+fn descend(
+    problem: &mut Problem,
+    iters: usize,
+    mut update: impl FnMut(usize, &mut [f32], &[f32]),
+) -> Minimum {
+    let mut point = problem.point();
+    let (mut value, mut gradient) = problem.eval_gradient(&point);
+    let mut best = (point.clone(), value);
+    let mut iterations = 0;
+    while iterations < iters && gradient.iter().any(|g| *g != 0.0) {
+        iterations += 1;
+        update(iterations, &mut point, &gradient);
+        (value, gradient) = problem.eval_gradient(&point);
+        if value < best.1 {
+            best = (point.clone(), value);
+        }
+    }
+    Minimum {
+        point: best.0,
+        value: best.1,
+        iterations,
+        evaluations: problem.evaluations(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::InputNode, node::Node};
+
+    /// Builds `(x - a)^2 + (y - b)^2`.
+    fn paraboloid(
+        x: Rc<RefCell<InputNode>>,
+        y: Rc<RefCell<InputNode>>,
+        a: f32,
+        b: f32,
+    ) -> Rc<RefCell<Node>> {
+        Node::add(
+            Node::pow(Node::sub(x, Node::constant(a)), 2.0),
+            Node::pow(Node::sub(y, Node::constant(b)), 2.0),
+        )
+    }
+
+    #[test]
+    fn nelder_mead() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let loss = paraboloid(x.clone(), y.clone(), 3.0, -1.0);
+
+        let min = Optimizer::minimize(
+            &loss,
+            &[x.clone(), y],
+            NelderMead {
+                tolerance: 1e-10,
+                ..Default::default()
+            },
+        );
+        assert!((min.point[0] - 3.0).abs() < 1e-3);
+        assert!((min.point[1] + 1.0).abs() < 1e-3);
+        assert!(min.value < 1e-6);
+        assert!(min.evaluations > min.iterations);
+        assert_eq!(x.borrow_mut().compute(), min.point[0]);
+        assert_eq!(loss.borrow_mut().compute(), min.value);
+    }
+
+    #[test]
+    fn nelder_mead_bounds() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let loss = paraboloid(x.clone(), y.clone(), 3.0, -1.0);
+
+        let min = Optimizer::minimize(
+            &loss,
+            &[x, y],
+            NelderMead {
+                tolerance: 1e-10,
+                bounds: Some(vec![(-5.0, 2.0), (0.0, 5.0)]),
+                ..Default::default()
+            },
+        );
+        assert!((min.point[0] - 2.0).abs() < 1e-3);
+        assert!(min.point[1].abs() < 1e-3);
+        assert!((min.value - 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn nelder_mead_non_smooth() {
+        // |sin(x)| has a kink at the minimum, so gradient methods struggle there
+        let x = InputNode::from_val(0.5);
+        let loss = Node::pow(Node::pow(Node::sin(x.clone()), 2.0), 0.5);
+
+        let min = Optimizer::minimize(&loss, &[x], NelderMead::default());
+        assert!(min.point[0].abs() < 1e-3);
+    }
+
+    #[test]
+    fn gradient_descent() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let loss = paraboloid(x.clone(), y.clone(), 3.0, -1.0);
+
+        let min = Optimizer::minimize(
+            &loss,
+            &[x.clone(), y],
+            GradientDescent {
+                lr: 0.1,
+                iters: 200,
+            },
+        );
+        assert!((min.point[0] - 3.0).abs() < 1e-3);
+        assert!((min.point[1] + 1.0).abs() < 1e-3);
+        assert_eq!(x.borrow_mut().compute(), min.point[0]);
+    }
+
+    #[test]
+    fn adam() {
+        // fit slope and intercept of a line through points of `2 * t + 1`
+        let a = InputNode::from_val(0.0);
+        let b = InputNode::from_val(0.0);
+        let mut terms = Vec::new();
+        for t in [0.0, 1.0, 2.0, 3.0] {
+            let fitted = Node::add(Node::mul(a.clone(), Node::constant(t)), b.clone());
+            terms.push(Node::pow(
+                Node::sub(fitted, Node::constant(2.0 * t + 1.0)),
+                2.0,
+            ));
+        }
+        let loss = terms
+            .into_iter()
+            .reduce(|sum, term| Node::add(sum, term))
+            .unwrap();
+
+        let min = Optimizer::minimize(
+            &loss,
+            &[a, b],
+            Adam {
+                lr: 0.05,
+                iters: 2000,
+                ..Default::default()
+            },
+        );
+        assert!((min.point[0] - 2.0).abs() < 1e-2);
+        assert!((min.point[1] - 1.0).abs() < 1e-2);
+        assert!(min.value < 1e-3);
+    }
+}
@@ -0,0 +1,211 @@
+//! Ownership of graph nodes by a single owner.
+//!
+//! Nodes built directly hold their operands alive, so a cycle through custom nodes is never
+//! freed. `GraphOwner` keeps all nodes it created and hands out plain ids instead,
+//! and on drop cuts every owned node off its operands, freeing the graph even with cycles.
+
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    float::Float,
+    input::{Input, InputNode},
+    node::{Handle, Node},
+    ops::Operation,
+};
+
+/// Source of unique owner identifiers.
+static NEXT_OWNER: AtomicU64 = AtomicU64::new(0);
+
+/// Id of node within its `GraphOwner`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OwnedId {
+    owner: u64,
+    index: usize,
+}
+
+/// Node kept by owner.
+enum Owned<T: Float> {
+    Input(Rc<RefCell<InputNode<T>>>),
+    Node(Rc<RefCell<Node<T>>>),
+    /// Custom node adopted by owner.
+    Custom(Handle<T>),
+}
+
+impl<T: Float> Owned<T> {
+    fn handle(&self) -> Handle<T> {
+        match self {
+            Owned::Input(input) => input.clone(),
+            Owned::Node(node) => node.clone(),
+            Owned::Custom(node) => node.clone(),
+        }
+    }
+}
+
+/// Owner of graph nodes, freeing all of them when dropped.
+/// Nodes are referenced by `OwnedId`s, which can't be used with other owners.
+pub struct GraphOwner<T: Float = f32> {
+    id: u64,
+    nodes: Vec<Owned<T>>,
+}
+
+impl<T: Float> Default for GraphOwner<T> {
+    fn default() -> Self {
+        Self {
+            id: NEXT_OWNER.fetch_add(1, Ordering::Relaxed),
+            nodes: Vec::new(),
+        }
+    }
+}
+
+impl<T: Float> GraphOwner<T> {
+    /// Creates owner without nodes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns amount of owned nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if owner has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns `true` if node with given id belongs to this owner.
+    pub fn owns(&self, id: OwnedId) -> bool {
+        id.owner == self.id
+    }
+
+    fn push(&mut self, node: Owned<T>) -> OwnedId {
+        self.nodes.push(node);
+        OwnedId {
+            owner: self.id,
+            index: self.nodes.len() - 1,
+        }
+    }
+
+    fn get(&self, id: OwnedId) -> &Owned<T> {
+        assert!(self.owns(id), "node {:?} belongs to another owner", id);
+        &self.nodes[id.index]
+    }
+
+    /// Adds input node with given value.
+    pub fn input(&mut self, val: T) -> OwnedId {
+        self.push(Owned::Input(InputNode::from_val(val)))
+    }
+
+    /// Adds node holding constant value.
+    pub fn constant(&mut self, val: T) -> OwnedId {
+        self.operation(Operation::Const(val))
+    }
+
+    /// Adds node computing given operation over owned nodes.
+    /// Panics if some operand belongs to another owner.
+    pub fn operation(&mut self, opp: Operation<OwnedId, T>) -> OwnedId {
+        let opp = opp.map_operands(|x| self.get(*x).handle());
+        self.push(Owned::Node(Node::new(opp)))
+    }
+
+    /// Takes ownership of custom node, so it's dropped along with the owner.
+    /// Cycles through adopted nodes are broken at owned operation nodes.
+    pub fn adopt(&mut self, node: Handle<T>) -> OwnedId {
+        self.push(Owned::Custom(node))
+    }
+
+    /// Returns shared handle to node, e.g. to wire it into custom nodes.
+    /// Handles outliving the owner keep the node alive, computing its last value.
+    pub fn handle(&self, id: OwnedId) -> Handle<T> {
+        self.get(id).handle()
+    }
+
+    /// Returns computation result of given node.
+    pub fn compute(&self, id: OwnedId) -> T {
+        self.get(id).handle().borrow_mut().compute()
+    }
+
+    /// Sets new value of input node.
+    /// Panics if node isn't an input.
+    pub fn set(&self, id: OwnedId, val: T) {
+        match self.get(id) {
+            Owned::Input(input) => input.borrow_mut().set(val),
+            _ => panic!("node {:?} is not an input", id),
+        }
+    }
+}
+
+impl<T: Float> Drop for GraphOwner<T> {
+    fn drop(&mut self) {
+        for node in &self.nodes {
+            if let Owned::Node(node) = node {
+                // node may be borrowed by a handle user still computing it
+                if let Ok(mut node) = node.try_borrow_mut() {
+                    node.drop_operands();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Computable;
+
+    #[test]
+    fn build_compute() {
+        let mut owner = GraphOwner::new();
+        let x = owner.input(2.0f32);
+        let y = owner.input(3.0);
+        let m = owner.operation(Operation::Mul(x, y));
+        let root = owner.operation(Operation::Sin(m));
+        assert_eq!(owner.compute(root), 6f32.sin());
+        owner.set(y, 0.5);
+        assert_eq!(owner.compute(root), 1f32.sin());
+        assert_eq!(owner.len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "belongs to another owner")]
+    fn cross_owner_wiring() {
+        let mut a = GraphOwner::<f32>::new();
+        let mut b = GraphOwner::<f32>::new();
+        let x = a.input(1.0);
+        assert!(!b.owns(x));
+        b.operation(Operation::Sin(x));
+    }
+
+    #[test]
+    fn frees_cycles() {
+        struct Custom {
+            source: Option<Handle>,
+        }
+
+        impl Computable for Custom {
+            fn compute(&mut self) -> f32 {
+                0.0
+            }
+
+            fn add_dependency(&mut self, _dependency: Handle) {}
+
+            fn reset_cache(&mut self) {}
+        }
+
+        let custom = Rc::new(RefCell::new(Custom { source: None }));
+        let weak = Rc::downgrade(&custom);
+        let mut owner = GraphOwner::new();
+        let c = owner.adopt(custom.clone());
+        let root = owner.operation(Operation::Sin(c));
+        custom.borrow_mut().source = Some(owner.handle(root));
+        drop(custom);
+
+        assert_eq!(owner.compute(root), 0.0);
+        drop(owner);
+        assert!(weak.upgrade().is_none());
+    }
+}
@@ -0,0 +1,787 @@
+//! Parser building graphs from formula strings.
+//!
+//! Supported syntax: numbers (`2`, `0.5`, `1e-3`), variables, which become named input
+//! nodes, operators `+`, `-`, `*`, `/`, `^` (with constant exponent), parentheses and
+//! functions named after `Node` constructors: `neg`, `recip`, `detach`, `sin`, `asin`, `acos`, `atan`,
+//! `cos`, `tan`, `exp`, `ln`, `sqrt`, `abs`, `sign`, `fract`, `floor`, `ceil`, `round`, `tanh`,
+//! `sigmoid`, `relu`, `softplus`, `expm1`, `ln_1p`, `erf`, `erfc`, `normal_cdf`, `normal_pdf`,
+//! `pow(x, c)`, `log(x, c)`, `hypot(x, y)`, `atan2(y, x)`, `rem(x, y)`, `clamp(x, lo, hi)`, comparisons `lt(x, y)`, `le(x, y)`, `gt(x, y)`, `ge(x, y)`,
+//! short-circuiting `and(x, y)` and `or(x, y)`,
+//! `eq(x, y)` and `eq(x, y, c)` with tolerance, `select(c, x, y)`, `sum(..)`, `prod(..)`,
+//! `mean(..)`, `min(x, y, ..)`, `max(x, y, ..)`, `norm(..)`, `logsumexp(..)`,
+//! `softmax_component(i, ..)`, `lazy_select(c, x, y)`, `guard(c, x, c)` and
+//! `piecewise(x, c, y, .., z)` with increasing constant breakpoints, each followed by its piece,
+//! `quantize(x, c)` with positive step and `round_dp(x, c)` with integer amount of decimal
+//! places, both rounding half to even, and `polynomial(x, c, ..)` with constant coefficients,
+//! lowest degree first.
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+    input::InputNode,
+    node::{Computable, Handle, Node},
+    ops::{Reduction, Rounding},
+};
+
+/// Error produced when formula can't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Byte offset in formula where error was detected.
+    pub position: usize,
+    /// Error description.
+    pub kind: ParseErrorKind,
+}
+
+/// Kinds of parse errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// Character which can't start any token.
+    UnexpectedChar(char),
+    /// Token which doesn't fit grammar at its position.
+    UnexpectedToken(String),
+    /// Formula ended unexpectedly.
+    UnexpectedEnd,
+    /// Function isn't known.
+    UnknownFunction(String),
+    /// Function is called with wrong amount of arguments.
+    Arity(String),
+    /// Argument must be a constant, but isn't.
+    NonConstant(String),
+    /// Formula is nested deeper than given limit.
+    TooDeep(usize),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParseErrorKind::*;
+
+        match &self.kind {
+            UnexpectedChar(c) => write!(f, "unexpected character '{}'", c)?,
+            UnexpectedToken(t) => write!(f, "unexpected token '{}'", t)?,
+            UnexpectedEnd => write!(f, "unexpected end of formula")?,
+            UnknownFunction(name) => write!(f, "unknown function {}", name)?,
+            Arity(name) => write!(f, "wrong amount of arguments of {}", name)?,
+            NonConstant(what) => write!(f, "non-constant {}", what)?,
+            TooDeep(limit) => write!(f, "formula nested deeper than {}", limit)?,
+        }
+        write!(f, " at position {}", self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Formula token.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Op(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Ident(s) => write!(f, "{}", s),
+            Token::Op(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+/// Splits formula into tokens paired with their byte offsets.
+fn tokenize(formula: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let bytes = formula.as_bytes();
+    let mut pos = 0;
+    while let Some(c) = formula[pos..].chars().next() {
+        if c.is_whitespace() {
+            pos += c.len_utf8();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = pos;
+            while end < bytes.len() && (bytes[end].is_ascii_digit() || bytes[end] == b'.') {
+                end += 1;
+            }
+            // exponent, only if followed by digits, so `2e` stays a syntax error
+            if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+                let mut exp = end + 1;
+                if exp < bytes.len() && (bytes[exp] == b'+' || bytes[exp] == b'-') {
+                    exp += 1;
+                }
+                if exp < bytes.len() && bytes[exp].is_ascii_digit() {
+                    end = exp;
+                    while end < bytes.len() && bytes[end].is_ascii_digit() {
+                        end += 1;
+                    }
+                }
+            }
+            let num = formula[pos..end].parse().map_err(|_| ParseError {
+                position: pos,
+                kind: ParseErrorKind::UnexpectedToken(formula[pos..end].to_string()),
+            })?;
+            tokens.push((pos, Token::Number(num)));
+            pos = end;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = pos;
+            while end < bytes.len() && (bytes[end].is_ascii_alphanumeric() || bytes[end] == b'_') {
+                end += 1;
+            }
+            tokens.push((pos, Token::Ident(formula[pos..end].to_string())));
+            pos = end;
+        } else if "+-*/^(),".contains(c) {
+            tokens.push((pos, Token::Op(c)));
+            pos += 1;
+        } else {
+            return Err(ParseError {
+                position: pos,
+                kind: ParseErrorKind::UnexpectedChar(c),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Intermediate parse result, keeps literals unfolded so they can serve as constant arguments.
+enum Value {
+    Number(f32),
+    Node(Rc<RefCell<dyn Computable>>),
+}
+
+impl Value {
+    fn into_node(self) -> Rc<RefCell<dyn Computable>> {
+        match self {
+            Value::Number(n) => Node::constant(n),
+            Value::Node(node) => node,
+        }
+    }
+}
+
+/// Default limit of graph depth for parsed formulas.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Parses formula, returning its root and input nodes of its variables by name.
+/// Variables are created with value `0.0`.
+/// Formulas producing graphs deeper than `DEFAULT_MAX_DEPTH` are rejected.
+#[allow(clippy::type_complexity)]
+pub fn parse(
+    formula: &str,
+) -> Result<
+    (
+        Rc<RefCell<dyn Computable>>,
+        HashMap<String, Rc<RefCell<InputNode>>>,
+    ),
+    ParseError,
+> {
+    parse_with_limit(formula, DEFAULT_MAX_DEPTH)
+}
+
+/// Parses formula like `parse`, rejecting formulas producing graphs deeper than `max_depth`.
+/// Limits recursion of both parser and evaluation, so untrusted formulas can't overflow the stack.
+#[allow(clippy::type_complexity)]
+pub fn parse_with_limit(
+    formula: &str,
+    max_depth: usize,
+) -> Result<
+    (
+        Rc<RefCell<dyn Computable>>,
+        HashMap<String, Rc<RefCell<InputNode>>>,
+    ),
+    ParseError,
+> {
+    let mut parser = Parser {
+        tokens: tokenize(formula)?,
+        pos: 0,
+        end: formula.len(),
+        vars: HashMap::new(),
+        nesting: 0,
+        max_depth,
+    };
+    let value = parser.expr()?;
+    match parser.tokens.get(parser.pos) {
+        Some((pos, token)) => Err(ParseError {
+            position: *pos,
+            kind: ParseErrorKind::UnexpectedToken(token.to_string()),
+        }),
+        None => Ok((value.into_node(), parser.vars)),
+    }
+}
+
+impl Node {
+    /// Builds graph from formula, see `parse` module for supported syntax.
+    #[allow(clippy::type_complexity)]
+    pub fn parse(
+        formula: &str,
+    ) -> Result<
+        (
+            Rc<RefCell<dyn Computable>>,
+            HashMap<String, Rc<RefCell<InputNode>>>,
+        ),
+        ParseError,
+    > {
+        parse(formula)
+    }
+}
+
+/// Recursive descent parser over formula tokens.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Formula length, reported as position of unexpected end.
+    end: usize,
+    vars: HashMap<String, Rc<RefCell<InputNode>>>,
+    /// Current recursion depth of parser.
+    nesting: usize,
+    max_depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(p, _)| *p)
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            position: self.position(),
+            kind,
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, ParseError> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error(ParseErrorKind::UnexpectedEnd))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, op: char) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(Token::Op(c)) if *c == op => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(t) => Err(self.error(ParseErrorKind::UnexpectedToken(t.to_string()))),
+            None => Err(self.error(ParseErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    fn var(&mut self, name: String) -> Rc<RefCell<dyn Computable>> {
+        self.vars
+            .entry(name.clone())
+            .or_insert_with(|| InputNode::with_label(0.0, name))
+            .clone()
+    }
+
+    /// Fails if value is a node deeper than allowed.
+    fn limit(&self, value: Value) -> Result<Value, ParseError> {
+        match &value {
+            Value::Node(node) if node.borrow().depth() > self.max_depth => {
+                Err(self.error(ParseErrorKind::TooDeep(self.max_depth)))
+            }
+            _ => Ok(value),
+        }
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Value, ParseError> {
+        let mut lhs = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = match (lhs, rhs) {
+                (Value::Number(x), Value::Number(y)) if op == '+' => Value::Number(x + y),
+                (Value::Number(x), Value::Number(y)) => Value::Number(x - y),
+                (x, y) if op == '+' => Value::Node(Node::add(x.into_node(), y.into_node())),
+                (x, y) => Value::Node(Node::sub(x.into_node(), y.into_node())),
+            };
+            lhs = self.limit(lhs)?;
+        }
+        Ok(lhs)
+    }
+
+    /// term := power (('*' | '/') power)*
+    fn term(&mut self) -> Result<Value, ParseError> {
+        let mut lhs = self.power()?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.power()?;
+            lhs = match (lhs, rhs) {
+                (Value::Number(x), Value::Number(y)) if op == '*' => Value::Number(x * y),
+                (Value::Number(x), Value::Number(y)) if y != 0.0 => Value::Number(x / y),
+                (x, y) if op == '*' => Value::Node(Node::mul(x.into_node(), y.into_node())),
+                (x, y) => Value::Node(Node::div(x.into_node(), y.into_node())),
+            };
+            lhs = self.limit(lhs)?;
+        }
+        Ok(lhs)
+    }
+
+    /// power := unary ('^' unary)*, evaluated left to right
+    fn power(&mut self) -> Result<Value, ParseError> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op('^')) = self.peek() {
+            self.pos += 1;
+            let pos = self.position();
+            lhs = match (lhs, self.unary()?) {
+                (Value::Number(x), Value::Number(y)) => Value::Number(x.powf(y)),
+                (x, Value::Number(y)) => Value::Node(Node::pow(x.into_node(), y)),
+                (_, Value::Node(_)) => {
+                    return Err(ParseError {
+                        position: pos,
+                        kind: ParseErrorKind::NonConstant("exponent".into()),
+                    })
+                }
+            };
+            lhs = self.limit(lhs)?;
+        }
+        Ok(lhs)
+    }
+
+    /// unary := ('-' | '+') unary | primary
+    fn unary(&mut self) -> Result<Value, ParseError> {
+        // every nesting level passes here, so limiting it bounds parser recursion
+        self.nesting += 1;
+        if self.nesting > self.max_depth {
+            return Err(self.error(ParseErrorKind::TooDeep(self.max_depth)));
+        }
+        let value = match self.peek() {
+            Some(Token::Op('-')) => {
+                self.pos += 1;
+                match self.unary()? {
+                    Value::Number(n) => Value::Number(-n),
+                    Value::Node(node) => Value::Node(Node::neg(node)),
+                }
+            }
+            Some(Token::Op('+')) => {
+                self.pos += 1;
+                self.unary()?
+            }
+            _ => self.primary()?,
+        };
+        self.nesting -= 1;
+        self.limit(value)
+    }
+
+    /// primary := number | variable | function '(' args ')' | '(' expr ')'
+    fn primary(&mut self) -> Result<Value, ParseError> {
+        let pos = self.position();
+        match self.next()? {
+            Token::Number(n) => Ok(Value::Number(n)),
+            Token::Op('(') => {
+                let value = self.expr()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::Op('(')) => {
+                self.pos += 1;
+                let args = self.args()?;
+                function(name, args, pos)
+            }
+            Token::Ident(name) => Ok(Value::Node(self.var(name))),
+            t => Err(ParseError {
+                position: pos,
+                kind: ParseErrorKind::UnexpectedToken(t.to_string()),
+            }),
+        }
+    }
+
+    /// Parses comma separated function arguments up to closing parenthesis.
+    fn args(&mut self) -> Result<Vec<Value>, ParseError> {
+        let mut args = Vec::new();
+        if self.peek() == Some(&Token::Op(')')) {
+            self.pos += 1;
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expr()?);
+            match self.next()? {
+                Token::Op(',') => continue,
+                Token::Op(')') => return Ok(args),
+                t => {
+                    self.pos -= 1;
+                    return Err(self.error(ParseErrorKind::UnexpectedToken(t.to_string())));
+                }
+            }
+        }
+    }
+}
+
+/// Constructor of node for function of single operand.
+type Unary = fn(Handle) -> Rc<RefCell<Node>>;
+
+/// Returns constructor of single operand function with given name.
+fn unary(name: &str) -> Option<Unary> {
+    match name {
+        "neg" => Some(Node::neg),
+        "recip" => Some(Node::recip),
+        "detach" => Some(Node::detach),
+        "sin" => Some(Node::sin),
+        "asin" => Some(Node::asin),
+        "acos" => Some(Node::acos),
+        "atan" => Some(Node::atan),
+        "cos" => Some(Node::cos),
+        "tan" => Some(Node::tan),
+        "exp" => Some(Node::exp),
+        "ln" => Some(Node::ln),
+        "sqrt" => Some(Node::sqrt),
+        "abs" => Some(Node::abs),
+        "sign" => Some(Node::sign),
+        "fract" => Some(Node::fract),
+        "floor" => Some(Node::floor),
+        "ceil" => Some(Node::ceil),
+        "round" => Some(Node::round),
+        "tanh" => Some(Node::tanh),
+        "sigmoid" => Some(Node::sigmoid),
+        "relu" => Some(Node::relu),
+        "softplus" => Some(Node::softplus),
+        "expm1" => Some(Node::expm1),
+        "ln_1p" => Some(Node::ln_1p),
+        "erf" => Some(Node::erf),
+        "erfc" => Some(Node::erfc),
+        "normal_cdf" => Some(Node::normal_cdf),
+        "normal_pdf" => Some(Node::normal_pdf),
+        _ => None,
+    }
+}
+
+/// Builds node for function call with given arguments.
+fn function(name: String, mut args: Vec<Value>, pos: usize) -> Result<Value, ParseError> {
+    let error = |kind| ParseError {
+        position: pos,
+        kind,
+    };
+    let constant = |val: &Value, what: &str| match val {
+        Value::Number(n) => Ok(*n),
+        Value::Node(_) => Err(error(ParseErrorKind::NonConstant(format!(
+            "{} of {}",
+            what, name
+        )))),
+    };
+    let arity = match name.as_str() {
+        _ if unary(&name).is_some() => 1..=1,
+        "pow" | "log" | "hypot" | "atan2" | "rem" | "lt" | "le" | "gt" | "ge" | "and" | "or" => {
+            2..=2
+        }
+        "quantize" | "round_dp" => 2..=2,
+        "min" | "max" => 2..=usize::MAX,
+        "lazy_select" | "select" | "guard" | "clamp" => 3..=3,
+        "eq" => 2..=3,
+        "sum" | "prod" | "mean" | "norm" | "logsumexp" | "polynomial" => 1..=usize::MAX,
+        "softmax_component" | "piecewise" => 2..=usize::MAX,
+        _ => return Err(error(ParseErrorKind::UnknownFunction(name))),
+    };
+    if !arity.contains(&args.len()) || name == "piecewise" && args.len() % 2 != 0 {
+        return Err(error(ParseErrorKind::Arity(name)));
+    }
+
+    let node = match name.as_str() {
+        "pow" => {
+            let pow = constant(&args[1], "exponent")?;
+            Node::pow(args.remove(0).into_node(), pow)
+        }
+        "log" => {
+            let base = constant(&args[1], "base")?;
+            Node::log(args.remove(0).into_node(), base)
+        }
+        "quantize" => {
+            let step = constant(&args[1], "step")?;
+            if !(step > 0.0 && step.is_finite()) {
+                return Err(error(ParseErrorKind::UnexpectedToken(format!(
+                    "step {}",
+                    step
+                ))));
+            }
+            Node::quantize(args.remove(0).into_node(), step, Rounding::HalfEven)
+        }
+        "polynomial" => {
+            let coeffs = args[1..]
+                .iter()
+                .map(|c| constant(c, "coefficient"))
+                .collect::<Result<_, _>>()?;
+            Node::polynomial(args.remove(0).into_node(), coeffs)
+        }
+        "round_dp" => {
+            let decimals = constant(&args[1], "decimals")?;
+            if decimals.fract() != 0.0 {
+                return Err(error(ParseErrorKind::UnexpectedToken(format!(
+                    "decimals {}",
+                    decimals
+                ))));
+            }
+            Node::round_dp(
+                args.remove(0).into_node(),
+                decimals as i32,
+                Rounding::HalfEven,
+            )
+        }
+        "eq" => {
+            let tolerance = match args.get(2) {
+                Some(val) => constant(val, "tolerance")?,
+                None => 0.0,
+            };
+            Node::eq(
+                args.remove(0).into_node(),
+                args.remove(0).into_node(),
+                tolerance,
+            )
+        }
+        "guard" => {
+            let fallback = constant(&args[2], "fallback")?;
+            Node::guard(
+                args.remove(0).into_node(),
+                args.remove(0).into_node(),
+                fallback,
+            )
+        }
+        "softmax_component" => {
+            let index = constant(&args[0], "index")?;
+            let n = args.len() - 1;
+            if index < 0.0 || index.fract() != 0.0 || index as usize >= n {
+                return Err(error(ParseErrorKind::UnexpectedToken(format!(
+                    "index {}",
+                    index
+                ))));
+            }
+            Node::softmax_component(nodes(args.split_off(1)), index as usize)
+        }
+        "piecewise" => {
+            let default = args.pop().unwrap().into_node();
+            let x = args.remove(0).into_node();
+            let mut pieces: Vec<(f32, Handle)> = Vec::new();
+            let mut args = args.into_iter();
+            while let (Some(breakpoint), Some(piece)) = (args.next(), args.next()) {
+                let breakpoint = constant(&breakpoint, "breakpoint")?;
+                if pieces.last().map_or(false, |(last, _)| *last >= breakpoint) {
+                    return Err(error(ParseErrorKind::UnexpectedToken(format!(
+                        "breakpoint {}",
+                        breakpoint
+                    ))));
+                }
+                pieces.push((breakpoint, piece.into_node()));
+            }
+            Node::piecewise(x, &pieces, default)
+        }
+        _ => {
+            let mut args = nodes(args);
+            match name.as_str() {
+                "hypot" => Node::hypot(args.remove(0), args.remove(0)),
+                "atan2" => Node::atan2(args.remove(0), args.remove(0)),
+                "rem" => Node::rem(args.remove(0), args.remove(0)),
+                "min" if args.len() == 2 => Node::min(args.remove(0), args.remove(0)),
+                "max" if args.len() == 2 => Node::max(args.remove(0), args.remove(0)),
+                "min" => Node::reduce(args, Reduction::Min),
+                "max" => Node::reduce(args, Reduction::Max),
+                "clamp" => Node::clamp(args.remove(0), args.remove(0), args.remove(0)),
+                "lt" => Node::lt(args.remove(0), args.remove(0)),
+                "le" => Node::le(args.remove(0), args.remove(0)),
+                "gt" => Node::gt(args.remove(0), args.remove(0)),
+                "ge" => Node::ge(args.remove(0), args.remove(0)),
+                "and" => Node::and(args.remove(0), args.remove(0)),
+                "or" => Node::or(args.remove(0), args.remove(0)),
+                "select" => Node::select(args.remove(0), args.remove(0), args.remove(0)),
+                "lazy_select" => Node::lazy_select(args.remove(0), args.remove(0), args.remove(0)),
+                "sum" => Node::add_var(args),
+                "prod" => Node::mul_var(args),
+                "mean" => Node::reduce(args, Reduction::Mean),
+                "norm" => Node::norm(args),
+                "logsumexp" => Node::logsumexp(args),
+                _ => unary(&name).unwrap()(args.remove(0)),
+            }
+        }
+    };
+    Ok(Value::Node(node))
+}
+
+fn nodes(args: Vec<Value>) -> Vec<Handle> {
+    args.into_iter().map(Value::into_node).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    fn round(x: f32, precision: u32) -> f32 {
+        let m = 10i32.pow(precision) as f32;
+        (x * m).round() / m
+    }
+
+    #[test]
+    fn arithmetic() {
+        let (root, vars) = Node::parse("x1 + x2 * sin(x3^2) - -x1 / 4").unwrap();
+        assert_eq!(vars.len(), 3);
+        vars["x1"].borrow_mut().set(2.0);
+        vars["x2"].borrow_mut().set(3.0);
+        vars["x3"].borrow_mut().set(1.5);
+        assert_eq!(
+            round(root.borrow_mut().compute(), 4),
+            round(2.0 + 3.0 * 2.25f32.sin() + 0.5, 4)
+        );
+
+        let (root, vars) = parse("2 ^ 3 ^ 2 + 1e-1 * 10").unwrap();
+        assert!(vars.is_empty());
+        assert_eq!(root.borrow_mut().compute(), 65.0);
+    }
+
+    #[test]
+    fn functions() {
+        let (root, vars) =
+            parse("hypot(a, 4) + sum(a, b, 1) + softmax_component(1, a, b) + pow(b, 0.5)").unwrap();
+        vars["a"].borrow_mut().set(3.0);
+        vars["b"].borrow_mut().set(3.0);
+        assert_eq!(root.borrow_mut().compute(), 5.0 + 7.0 + 0.5 + 3f32.sqrt());
+
+        let (root, vars) = parse("sqrt(abs(x)) + log(exp(x), 2.718281828) * cos(0)").unwrap();
+        vars["x"].borrow_mut().set(-4.0);
+        assert_eq!(round(root.borrow_mut().compute(), 5), -2.0);
+        assert!(parse("log(x, y)").is_err());
+
+        let (root, vars) =
+            parse("floor(x) + ceil(x) * 10 + round(x) * 100 + sign(x) * fract(x)").unwrap();
+        vars["x"].borrow_mut().set(-2.5);
+        assert_eq!(root.borrow_mut().compute(), -3.0 - 20.0 - 300.0 + 0.5);
+
+        let (root, _) = parse("atan2(1, 0) * 2 + rem(-7, 3)").unwrap();
+        assert_eq!(root.borrow_mut().compute(), std::f32::consts::PI - 1.0);
+
+        let (root, vars) = parse("select(ge(x, 0), sqrt(x), eq(x, -1, 0.5))").unwrap();
+        vars["x"].borrow_mut().set(4.0);
+        assert_eq!(root.borrow_mut().compute(), 2.0);
+        vars["x"].borrow_mut().set(-1.25);
+        assert_eq!(root.borrow_mut().compute(), 1.0);
+        assert!(parse("eq(x, 1, x)").is_err());
+
+        let (root, vars) = parse("or(lt(x, 0), and(gt(x, 1), lt(x, 2)))").unwrap();
+        vars["x"].borrow_mut().set(1.5);
+        assert_eq!(root.borrow_mut().compute(), 1.0);
+        vars["x"].borrow_mut().set(0.5);
+        assert_eq!(root.borrow_mut().compute(), 0.0);
+
+        let (root, vars) = parse("guard(x, ln_1p(expm1(x)), -1) + lazy_select(x, 1, 2)").unwrap();
+        vars["x"].borrow_mut().set(0.5);
+        assert_eq!(round(root.borrow_mut().compute(), 5), 1.5);
+        vars["x"].borrow_mut().set(-0.5);
+        assert_eq!(root.borrow_mut().compute(), 1.0);
+
+        let (root, vars) = parse("piecewise(x, 0, -x, 1, x^2, 1)").unwrap();
+        vars["x"].borrow_mut().set(-2.0);
+        assert_eq!(root.borrow_mut().compute(), 2.0);
+        vars["x"].borrow_mut().set(0.5);
+        assert_eq!(root.borrow_mut().compute(), 0.25);
+        vars["x"].borrow_mut().set(3.0);
+        assert_eq!(root.borrow_mut().compute(), 1.0);
+        assert_eq!(
+            crate::infix::to_string_expr(&root),
+            "piecewise(x, 0, -x, 1, x^2, 1)"
+        );
+        assert!(parse("piecewise(x, 0, x)").is_err());
+        assert!(parse("piecewise(x, 1, x, 0, x, 1)").is_err());
+
+        let (root, vars) = parse("round_dp(x, 1) + quantize(x, 0.5)").unwrap();
+        vars["x"].borrow_mut().set(1.25);
+        assert_eq!(root.borrow_mut().compute(), 1.2 + 1.0);
+        assert!(parse("quantize(x, 0)").is_err());
+        assert!(parse("round_dp(x, 0.5)").is_err());
+
+        let (root, vars) = parse("prod(x, 2, x) + mean(x, 3) - max(x, 0, -x)").unwrap();
+        vars["x"].borrow_mut().set(-1.0);
+        assert_eq!(root.borrow_mut().compute(), 2.0 + 1.0 - 1.0);
+        assert!(parse("min(x)").is_err());
+
+        for formula in [
+            "erf(x)",
+            "erfc(x)",
+            "normal_cdf(x)",
+            "normal_pdf(x)",
+            "asin(x)",
+            "acos(x)",
+            "atan(x)",
+            "norm(x, x)",
+            "logsumexp(x, x)",
+            "cos(x)",
+            "tan(x)",
+            "exp(x)",
+            "ln(x)",
+            "log(x, 10)",
+            "sqrt(x)",
+            "abs(x)",
+            "tanh(x)",
+            "sigmoid(x)",
+            "relu(x)",
+            "softplus(x)",
+            "min(x, 1)",
+            "max(x, 1)",
+            "clamp(x, 0, 1)",
+            "lt(x, 1)",
+            "le(x, 1)",
+            "gt(x, 1)",
+            "ge(x, 1)",
+            "eq(x, 1)",
+        ] {
+            let (root, _) = parse(formula).unwrap();
+            let name = root.borrow().operation().unwrap().name();
+            assert!(formula.starts_with(name), "{}", formula);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let error = |formula| parse(formula).err().unwrap();
+        assert_eq!(
+            error("x # y"),
+            ParseError {
+                position: 2,
+                kind: ParseErrorKind::UnexpectedChar('#')
+            }
+        );
+        assert_eq!(error("x +").kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(error("(x").position, 2);
+        assert_eq!(
+            error("x y").kind,
+            ParseErrorKind::UnexpectedToken("y".into())
+        );
+        assert_eq!(
+            error("cosh(x)").kind,
+            ParseErrorKind::UnknownFunction("cosh".into())
+        );
+        assert_eq!(error("sin(x, y)").kind, ParseErrorKind::Arity("sin".into()));
+        assert_eq!(
+            error("x ^ y").to_string(),
+            "non-constant exponent at position 4"
+        );
+        assert_eq!(
+            error("pow(x, y)").kind,
+            ParseErrorKind::NonConstant("exponent of pow".into())
+        );
+        assert!(parse("softmax_component(2, x, y)").is_err());
+        assert!(parse("softmax_component(x)").is_err());
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nested = format!("{}x{}", "sin(".repeat(10), ")".repeat(10));
+        let (root, _) = parse_with_limit(&nested, 11).unwrap();
+        assert_eq!(root.borrow().depth(), 11);
+        assert_eq!(
+            parse_with_limit(&nested, 10).err().unwrap().kind,
+            ParseErrorKind::TooDeep(10)
+        );
+
+        // long chains don't nest parser, but build deep graphs
+        let chain = vec!["x"; 300].join(" + ");
+        assert!(parse_with_limit(&chain, 300).is_ok());
+        assert_eq!(
+            parse(&chain).err().unwrap().to_string(),
+            "formula nested deeper than 256 at position 1026"
+        );
+
+        // parentheses and signs are limited without building nodes
+        let parens = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(parse(&parens).err().unwrap().position, 256);
+        assert!(parse(&"-".repeat(100_000)).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Opt-in profiling of evaluation, reporting how often each node was requested,
+//! whether its cached result was used and how long its computation took.
+//!
+//! Traced evaluation walks the graph itself, so it's slower than `compute`: operations
+//! are evaluated once to find operands they take, then computed with operands cached,
+//! which is the measured own time of node. Results are cached as with `compute`.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    float::Float,
+    node::{node_addr, Handle, Node},
+};
+
+/// Measurements of single node during traced evaluation.
+#[derive(Clone)]
+pub struct NodeProfile<T: Float = f32> {
+    /// Profiled node.
+    pub node: Handle<T>,
+    /// Label of node or name of its operation.
+    pub name: String,
+    /// Amount of times node was requested, by its dependents or as the root.
+    pub evaluations: usize,
+    /// Amount of requests served from cache.
+    pub hits: usize,
+    /// Amount of requests which computed the node.
+    pub misses: usize,
+    /// Time spent computing node, excluding its operands.
+    pub own_time: Duration,
+    /// Time spent computing node including operands computed for it.
+    pub total_time: Duration,
+}
+
+impl<T: Float> fmt::Debug for NodeProfile<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeProfile")
+            .field("name", &self.name)
+            .field("evaluations", &self.evaluations)
+            .field("hits", &self.hits)
+            .field("misses", &self.misses)
+            .field("own_time", &self.own_time)
+            .field("total_time", &self.total_time)
+            .finish()
+    }
+}
+
+/// Report of traced evaluation, with nodes in order of their first request.
+#[derive(Debug, Clone)]
+pub struct Profile<T: Float = f32> {
+    nodes: Vec<NodeProfile<T>>,
+}
+
+impl<T: Float> Profile<T> {
+    /// Returns measurements of requested nodes, root first.
+    pub fn nodes(&self) -> &[NodeProfile<T>] {
+        &self.nodes
+    }
+
+    /// Returns measurements of given node, if it was requested.
+    pub fn get(&self, node: &Handle<T>) -> Option<&NodeProfile<T>> {
+        let addr = node_addr(node);
+        self.nodes.iter().find(|x| node_addr(&x.node) == addr)
+    }
+
+    /// Returns up to `n` nodes with the longest own time, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<&NodeProfile<T>> {
+        let mut nodes: Vec<_> = self.nodes.iter().collect();
+        nodes.sort_by(|a, b| b.own_time.cmp(&a.own_time));
+        nodes.truncate(n);
+        nodes
+    }
+
+    /// Returns total time of the root.
+    pub fn total_time(&self) -> Duration {
+        self.nodes.first().map_or(Duration::ZERO, |x| x.total_time)
+    }
+}
+
+impl<T: Float> fmt::Display for Profile<T> {
+    /// Renders table of nodes ordered by own time, slowest first.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>8} {:>8} {:>8} {:>12} {:>12}  node",
+            "evals", "hits", "misses", "own", "total"
+        )?;
+        for x in self.slowest(self.nodes.len()) {
+            writeln!(
+                f,
+                "{:>8} {:>8} {:>8} {:>12.1?} {:>12.1?}  {}",
+                x.evaluations, x.hits, x.misses, x.own_time, x.total_time, x.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Traced evaluation in progress, indexing measurements by node address.
+struct Tracer<T: Float> {
+    nodes: Vec<NodeProfile<T>>,
+    indices: HashMap<usize, usize>,
+}
+
+impl<T: Float> Tracer<T> {
+    /// Requests value of node, computing requested operands first.
+    fn visit(&mut self, node: &Handle<T>) -> T {
+        let i = *self.indices.entry(node_addr(node)).or_insert_with(|| {
+            let name = {
+                let node = node.borrow();
+                node.label()
+                    .map(String::from)
+                    .or_else(|| node.operation().map(|opp| opp.name().to_string()))
+                    .unwrap_or_else(|| "input".to_string())
+            };
+            self.nodes.push(NodeProfile {
+                node: node.clone(),
+                name,
+                evaluations: 0,
+                hits: 0,
+                misses: 0,
+                own_time: Duration::ZERO,
+                total_time: Duration::ZERO,
+            });
+            self.nodes.len() - 1
+        });
+        self.nodes[i].evaluations += 1;
+        if !node.borrow().is_dirty() {
+            self.nodes[i].hits += 1;
+            return node.borrow_mut().compute();
+        }
+        self.nodes[i].misses += 1;
+        let start = Instant::now();
+        let opp = node.borrow().operation().cloned();
+        match opp {
+            Some(opp) => {
+                opp.eval(|x| self.visit(x));
+            }
+            None => {
+                for x in node.borrow().operands() {
+                    self.visit(&x);
+                }
+            }
+        }
+        let own = Instant::now();
+        let val = node.borrow_mut().compute();
+        let end = Instant::now();
+        self.nodes[i].own_time += end - own;
+        self.nodes[i].total_time += end - start;
+        val
+    }
+}
+
+impl<T: Float> Node<T> {
+    /// Computes result of root while measuring evaluation of each node,
+    /// see `profile` module. Graph must be acyclic, see `Graph::validate`.
+    pub fn compute_traced(root: &Handle<T>) -> (T, Profile<T>) {
+        let mut tracer = Tracer {
+            nodes: Vec::new(),
+            indices: HashMap::new(),
+        };
+        let val = tracer.visit(root);
+        (
+            val,
+            Profile {
+                nodes: tracer.nodes,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputNode;
+
+    #[test]
+    fn hits_and_misses() {
+        let x = InputNode::with_label(2.0f32, "x");
+        let square = Node::mul(x.clone(), x.clone());
+        let root: Handle = Node::add(square.clone(), Node::sin(square.clone()));
+        let square: Handle = square;
+
+        let (val, profile) = Node::compute_traced(&root);
+        assert_eq!(val, 4.0 + 4.0f32.sin());
+        assert_eq!(profile.nodes().len(), 4);
+        assert_eq!(profile.nodes()[0].name, "add");
+        let square = profile.get(&square).unwrap();
+        assert_eq!((square.evaluations, square.hits, square.misses), (2, 1, 1));
+        let x = profile.get(&(x as Handle)).unwrap();
+        assert_eq!((x.name.as_str(), x.evaluations), ("x", 2));
+        assert!(profile.total_time() >= profile.nodes()[0].own_time);
+        assert_eq!(profile.to_string().lines().count(), 5);
+
+        // cached root isn't traversed
+        let (_, profile) = Node::compute_traced(&root);
+        assert_eq!(profile.nodes().len(), 1);
+        assert_eq!(profile.nodes()[0].hits, 1);
+    }
+}
@@ -0,0 +1,113 @@
+//! Pruning of orphaned nodes and reachability queries.
+//!
+//! Nodes built and then abandoned in favor of others stay registered as dependents of
+//! their operands as long as something keeps them alive, so each `set` keeps invalidating
+//! them. Pruning disconnects such nodes from their operands.
+
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use crate::{
+    float::Float,
+    graph::Graph,
+    node::{node_addr, post_order, Handle, Node},
+};
+
+impl<T: Float> Graph<T> {
+    /// Disconnects nodes depending on inputs or on nodes reachable from outputs, which
+    /// aren't themselves reachable from outputs or given roots, and returns them.
+    /// Pruned nodes are no longer invalidated, so their cached results may go stale.
+    /// Subscriptions are dependents too, those which should stay active must be passed
+    /// as roots, see `reactive`. Graph must be acyclic, see `Graph::validate`.
+    pub fn prune(&self, roots: &[Handle<T>]) -> Vec<Handle<T>> {
+        let mut kept = self.reachable();
+        let mut seen: HashSet<usize> = kept.iter().map(node_addr).collect();
+        for root in roots {
+            kept.extend(
+                post_order(root)
+                    .into_iter()
+                    .filter(|x| seen.insert(node_addr(x))),
+            );
+        }
+        let live = seen.clone();
+        let mut stack = kept;
+        stack.extend(self.inputs().map(|(_, input)| input.clone() as Handle<T>));
+
+        let mut pruned = Vec::new();
+        while let Some(node) = stack.pop() {
+            for dependent in node.borrow().dependents() {
+                if !seen.insert(node_addr(&dependent)) {
+                    continue;
+                }
+                if !live.contains(&node_addr(&dependent)) {
+                    pruned.push(dependent.clone());
+                }
+                stack.push(dependent);
+            }
+        }
+        for node in &pruned {
+            for x in node.borrow().operands() {
+                x.borrow_mut().remove_dependency(node);
+            }
+        }
+        pruned
+    }
+}
+
+impl<T: Float> Node<T> {
+    /// Returns true if node depends on given one, so changes of it reach the node.
+    /// Node is reachable from itself. Graph must be acyclic, see `Graph::validate`.
+    pub fn is_reachable_from<C: ?Sized>(node: &Handle<T>, input: &Rc<RefCell<C>>) -> bool {
+        let addr = node_addr(input);
+        post_order(node).iter().any(|x| node_addr(x) == addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::InputHandle, node::Computable};
+
+    #[test]
+    fn prunes_orphans() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        let y = g.add_input("y", 2.0);
+        let sum: Handle = Node::add(x.clone(), y.clone());
+        g.add_output("sum", Node::sqrt(sum.clone()));
+        let orphan: Handle = Node::sin(x.clone());
+        let nested: Handle = Node::mul(orphan.clone(), sum.clone());
+        let watched: Handle = Node::cos(y.clone());
+        let subscription: Handle = Node::subscribe(&watched, |_| {});
+
+        let pruned = g.prune(&[subscription]);
+        let addrs: HashSet<usize> = pruned.iter().map(node_addr).collect();
+        assert_eq!(
+            addrs,
+            [node_addr(&orphan), node_addr(&nested)]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(x.borrow().dependents().len(), 1);
+        assert_eq!(sum.borrow().dependents().len(), 1);
+        assert_eq!(y.borrow().dependents().len(), 2);
+
+        // orphans are no longer invalidated
+        nested.borrow_mut().compute();
+        x.set(3.0).unwrap();
+        assert!(!nested.borrow().is_dirty());
+        assert!(g
+            .prune(&[])
+            .iter()
+            .any(|x| node_addr(x) == node_addr(&watched)));
+    }
+
+    #[test]
+    fn reachability() {
+        let x = Node::input(1.0f32);
+        let y = Node::input(2.0);
+        let z: Handle = Node::mul(Node::sin(x.clone()), Node::constant(2.0));
+        assert!(Node::is_reachable_from(&z, &x));
+        assert!(!Node::is_reachable_from(&z, &y));
+        assert!(Node::is_reachable_from(&z, &z));
+    }
+}
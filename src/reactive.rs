@@ -0,0 +1,226 @@
+//! Push-based recomputation of subscribed nodes.
+//!
+//! Subscriptions are dependents of their node, so invalidation reaching them queues
+//! them for recomputation. The queue is flushed once inputs are no longer borrowed:
+//! after `InputHandle::set` and `Graph::set_many`, or explicitly with `flush` after
+//! setting inputs through `RefCell` borrows. Callbacks run only when value changed.
+//! Subscriptions whose node is borrowed during flush stay queued for the next one.
+//! Volatile nodes, which stay dirty after computing, see `CachePolicy::Never`, aren't
+//! reached by invalidation, so subscriptions to them are recomputed on every flush.
+
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    float::Float,
+    node::{Computable, Handle, Node},
+};
+
+thread_local! {
+    /// Subscriptions invalidated since the last flush, in invalidation order.
+    static PENDING: RefCell<Vec<Weak<RefCell<dyn Pending>>>> = RefCell::new(Vec::new());
+}
+
+/// Subscription waiting for recomputation, independent of scalar type.
+trait Pending {
+    /// Recomputes subscription, returning true if it stays queued for the next flush.
+    fn flush(&mut self) -> bool;
+}
+
+/// Subscription to changes of node value, active while it's alive.
+pub struct Subscription<T: Float = f32> {
+    /// Reference to itself, so invalidation can queue it.
+    this: Weak<RefCell<Subscription<T>>>,
+    source: Handle<T>,
+    callback: Box<dyn FnMut(T)>,
+    /// Value passed to callback last, or computed on subscription.
+    last: T,
+    /// Whether subscription is queued.
+    queued: bool,
+}
+
+impl<T: Float> Subscription<T> {
+    /// Returns value of node as of the last recomputation.
+    pub fn value(&self) -> T {
+        self.last
+    }
+}
+
+impl<T: Float> Pending for Subscription<T> {
+    fn flush(&mut self) -> bool {
+        // node borrowed elsewhere, e.g. during its own computation, is retried later
+        let val = match self.source.try_borrow_mut() {
+            Ok(mut source) => {
+                let val = source.compute();
+                self.queued = source.is_dirty();
+                val
+            }
+            Err(_) => return true,
+        };
+        // `NaN`s compare unequal, so only the first one is reported
+        if val != self.last && !(val.to_f64().is_nan() && self.last.to_f64().is_nan()) {
+            self.last = val;
+            (self.callback)(val);
+        }
+        self.queued
+    }
+}
+
+impl<T: Float> Computable<T> for Subscription<T> {
+    /// Returns value as of the last recomputation.
+    fn compute(&mut self) -> T {
+        self.last
+    }
+
+    fn add_dependency(&mut self, _dependency: Handle<T>) {}
+
+    /// Queues recomputation, which happens on the next flush.
+    fn reset_cache(&mut self) {
+        if self.queued {
+            return;
+        }
+        self.queued = true;
+        let this = self.this.clone();
+        PENDING.with(|pending| pending.borrow_mut().push(this));
+    }
+
+    fn operands(&self) -> Vec<Handle<T>> {
+        vec![self.source.clone()]
+    }
+}
+
+impl<T: Float> Node<T> {
+    /// Subscribes `callback` to changes of value of given node, computing it now.
+    /// Once node is invalidated, it's recomputed on the next flush, see `reactive` module,
+    /// and callback receives new value if it differs from the previous one.
+    /// Callback is dropped along with returned subscription.
+    pub fn subscribe(
+        node: &Handle<T>,
+        callback: impl FnMut(T) + 'static,
+    ) -> Rc<RefCell<Subscription<T>>> {
+        let last = node.borrow_mut().compute();
+        let obj = Rc::new_cyclic(|this| {
+            RefCell::new(Subscription {
+                this: this.clone(),
+                source: node.clone(),
+                callback: Box::new(callback),
+                last,
+                queued: false,
+            })
+        });
+        node.borrow_mut().add_dependency(obj.clone());
+        if node.borrow().is_dirty() {
+            obj.borrow_mut().reset_cache();
+        }
+        obj
+    }
+}
+
+/// Recomputes subscribed nodes invalidated since the last flush, calling callbacks
+/// of changed ones. Subscriptions invalidated by callbacks are flushed too.
+pub fn flush() {
+    let mut retry = Vec::new();
+    loop {
+        let pending = PENDING.with(|pending| std::mem::take(&mut *pending.borrow_mut()));
+        if pending.is_empty() {
+            break;
+        }
+        for subscription in pending {
+            if let Some(queued) = subscription.upgrade() {
+                if queued.borrow_mut().flush() {
+                    retry.push(subscription);
+                }
+            }
+        }
+    }
+    PENDING.with(|pending| pending.borrow_mut().extend(retry));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        graph::Graph,
+        input::{Input, InputHandle},
+        node::CachePolicy,
+    };
+
+    #[test]
+    fn pushes_changes() {
+        let x = Node::input(1.0f32);
+        let abs: Handle = Node::abs(x.clone());
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let sink = log.clone();
+        let subscription = Node::subscribe(&abs, move |val| sink.borrow_mut().push(val));
+        assert_eq!(subscription.borrow().value(), 1.0);
+
+        x.set(-2.0).unwrap();
+        x.set(2.0).unwrap();
+        x.set(3.0).unwrap();
+        assert_eq!(*log.borrow(), vec![2.0, 3.0]);
+
+        // borrowed inputs wait for explicit flush
+        x.borrow_mut().set(4.0);
+        assert_eq!(log.borrow().len(), 2);
+        flush();
+        assert_eq!(*log.borrow(), vec![2.0, 3.0, 4.0]);
+
+        // node borrowed during flush is recomputed by the next one
+        x.borrow_mut().set(-5.0);
+        let held = abs.borrow();
+        flush();
+        drop(held);
+        assert_eq!(log.borrow().len(), 3);
+        flush();
+        assert_eq!(*log.borrow(), vec![2.0, 3.0, 4.0, 5.0]);
+        x.set(6.0).unwrap();
+        assert_eq!(*log.borrow(), vec![2.0, 3.0, 4.0, 5.0, 6.0]);
+
+        drop(subscription);
+        x.set(7.0).unwrap();
+        assert_eq!(log.borrow().len(), 5);
+    }
+
+    #[test]
+    fn volatile_nodes() {
+        let reading = Arc::new(Mutex::new(1.0f32));
+        let source = reading.clone();
+        let sensor = Node::nary_fn(vec![], move |_| *source.lock().unwrap());
+        sensor.borrow_mut().set_cache_policy(CachePolicy::Never);
+        let scaled: Handle = Node::mul(sensor, Node::constant(2.0));
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let sink = log.clone();
+        let _subscription = Node::subscribe(&scaled, move |val| sink.borrow_mut().push(val));
+
+        flush();
+        assert!(log.borrow().is_empty());
+        *reading.lock().unwrap() = 3.0;
+        flush();
+        flush();
+        assert_eq!(*log.borrow(), vec![6.0]);
+    }
+
+    #[test]
+    fn graph_inputs() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        let y = g.add_input("y", 2.0);
+        let sum: Handle = Node::add(x, y);
+        g.add_output("sum", sum.clone());
+        let calls = Rc::new(RefCell::new(0));
+        let counter = calls.clone();
+        let _subscription = Node::subscribe(&sum, move |_| *counter.borrow_mut() += 1);
+
+        // recomputed once for both inputs
+        g.set_many(&[("x", 2.0), ("y", 3.0)]).unwrap();
+        assert_eq!(*calls.borrow(), 1);
+        // hypothetical values aren't pushed
+        g.eval_scenarios(&[&[("x", 10.0)]]).unwrap();
+        flush();
+        assert_eq!(*calls.borrow(), 1);
+    }
+}
@@ -0,0 +1,111 @@
+//! Hot reloading of graph files written in binary format, see `binary`.
+//!
+//! `GraphFile` compares modification time and length of the file on each `poll`, so
+//! no watcher thread or platform notifications are needed, hosts poll from their own loop.
+//! New graph is loaded in full before it is swapped in, so a failed or partial write
+//! keeps the current graph until the file is readable again.
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{float::Float, graph::Graph};
+
+/// Graph loaded from file, reloaded when the file changes.
+pub struct GraphFile<T: Float = f32> {
+    path: PathBuf,
+    /// Modification time and length of the file graph was loaded from.
+    stamp: (SystemTime, u64),
+    graph: Graph<T>,
+}
+
+impl<T: Float> GraphFile<T> {
+    /// Loads graph from file at given path.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let stamp = stamp(&path)?;
+        let graph = Graph::load(File::open(&path)?)?;
+        Ok(Self { path, stamp, graph })
+    }
+
+    /// Returns path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns current graph.
+    pub fn graph(&self) -> &Graph<T> {
+        &self.graph
+    }
+
+    /// Reloads graph if the file changed since it was loaded, see `reload`.
+    /// Returns true if graph was swapped.
+    pub fn poll(&mut self) -> io::Result<bool> {
+        if stamp(&self.path)? == self.stamp {
+            return Ok(false);
+        }
+        self.reload()?;
+        Ok(true)
+    }
+
+    /// Loads graph from the file and swaps it in, carrying over current values of inputs
+    /// registered under the same names, unless they violate rules of new inputs.
+    /// Observers and handles of previous graph aren't carried over.
+    /// On failure, current graph is kept.
+    pub fn reload(&mut self) -> io::Result<()> {
+        let stamp = stamp(&self.path)?;
+        let graph: Graph<T> = Graph::load(File::open(&self.path)?)?;
+        for (name, input) in self.graph.inputs() {
+            if let Some(new) = graph.input(name) {
+                let _ = new.borrow_mut().try_set(input.borrow().value());
+            }
+        }
+        self.graph = graph;
+        self.stamp = stamp;
+        Ok(())
+    }
+}
+
+/// Returns modification time and length of file.
+fn stamp(path: &Path) -> io::Result<(SystemTime, u64)> {
+    let meta = fs::metadata(path)?;
+    Ok((meta.modified()?, meta.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Node;
+
+    #[test]
+    fn reloading() {
+        let path = std::env::temp_dir().join(format!("teza-reload-{}.bin", std::process::id()));
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        g.add_output("y", Node::mul(x, Node::constant(2.0)));
+        g.save(File::create(&path).unwrap()).unwrap();
+
+        let mut file = GraphFile::<f32>::open(&path).unwrap();
+        assert!(!file.poll().unwrap());
+        file.graph().set_many(&[("x", 3.0)]).unwrap();
+        assert_eq!(file.graph().outputs(), vec![("y", 6.0)]);
+
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        let z = g.add_input("z", 5.0);
+        g.add_output("y", Node::add(x, z));
+        g.save(File::create(&path).unwrap()).unwrap();
+        assert!(file.poll().unwrap());
+        assert!(!file.poll().unwrap());
+        assert_eq!(file.graph().outputs(), vec![("y", 8.0)]);
+
+        fs::write(&path, b"TEZA").unwrap();
+        assert!(file.poll().is_err());
+        assert_eq!(file.graph().outputs(), vec![("y", 8.0)]);
+        fs::remove_file(&path).unwrap();
+        assert!(file.poll().is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! Evaluation of scenario batches sharing results of unchanged subgraphs.
+//!
+//! Single cached result of each node is lost once a scenario changes its operands, even if
+//! a later scenario sets them back. During a batch, results are additionally remembered
+//! by node and assignment of values to leaves the node depends on, so subgraphs computed
+//! for the same values by any earlier scenario of the batch are restored instead.
+//!
+//! For interactive "what if" analysis, `Graph::snapshot` captures input values,
+//! optionally with cached results, and `Graph::restore` rolls the graph back to them.
+
+use std::collections::HashMap;
+
+use crate::{
+    error::GraphError,
+    float::Float,
+    graph::Graph,
+    node::{node_addr, Handle},
+    reactive,
+};
+
+/// Values of graph inputs captured by `Graph::snapshot`, optionally along with cached results.
+#[derive(Clone)]
+pub struct InputSnapshot<T: Float = f32> {
+    values: Vec<(String, T)>,
+    /// Cached results of nodes, operands first, keeping the nodes alive.
+    caches: Vec<(Handle<T>, T)>,
+}
+
+impl<T: Float> InputSnapshot<T> {
+    /// Returns captured input values by name, in registration order.
+    pub fn values(&self) -> impl Iterator<Item = (&str, T)> {
+        self.values.iter().map(|(name, val)| (name.as_str(), *val))
+    }
+
+    /// Returns true if cached results were captured too.
+    pub fn has_caches(&self) -> bool {
+        !self.caches.is_empty()
+    }
+}
+
+impl<T: Float> Graph<T> {
+    /// Captures current values of all inputs, to be restored with `restore`.
+    pub fn snapshot(&self) -> InputSnapshot<T> {
+        InputSnapshot {
+            values: self
+                .inputs()
+                .map(|(name, input)| (name.to_string(), input.borrow().value()))
+                .collect(),
+            caches: Vec::new(),
+        }
+    }
+
+    /// Captures current values of inputs along with cached results of nodes reachable
+    /// from outputs, so restoring doesn't require recomputing them.
+    pub fn snapshot_with_caches(&self) -> InputSnapshot<T> {
+        let caches = self
+            .reachable()
+            .into_iter()
+            .filter(|node| {
+                let node = node.borrow();
+                !node.operands().is_empty() && !node.is_dirty()
+            })
+            .map(|node| {
+                let val = node.borrow_mut().compute();
+                (node, val)
+            })
+            .collect();
+        InputSnapshot {
+            caches,
+            ..self.snapshot()
+        }
+    }
+
+    /// Sets inputs back to values captured by snapshot, restoring captured caches of nodes
+    /// which became dirty. Inputs registered after snapshot keep their values, inputs
+    /// removed since are skipped. If some value violates current rules of its input,
+    /// no input is changed. Subscribed nodes are recomputed afterwards, see `reactive`.
+    pub fn restore(&self, snapshot: &InputSnapshot<T>) -> Result<(), GraphError> {
+        let values: Vec<(&str, T)> = snapshot
+            .values()
+            .filter(|(name, _)| self.input(name).is_some())
+            .collect();
+        let names: Vec<&str> = values.iter().map(|(name, _)| *name).collect();
+        let values: Vec<T> = values.iter().map(|(_, val)| *val).collect();
+        self.assign(&names, &values)?;
+        for (node, val) in &snapshot.caches {
+            // restored only together with operands, so operands keep resetting it
+            let ready = node
+                .borrow()
+                .operands()
+                .iter()
+                .all(|x| !x.borrow().is_dirty());
+            if ready && node.borrow().is_dirty() {
+                node.borrow_mut().seed_cache(*val);
+            }
+        }
+        reactive::flush();
+        Ok(())
+    }
+
+    /// Computes outputs for each scenario, given as values of some inputs overriding their
+    /// current values, returning outputs in registration order. Inputs are restored afterwards.
+    /// If some value violates rules of its input, inputs are restored and error is returned.
+    /// Fails without changes if some input isn't registered.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn eval_scenarios(
+        &self,
+        scenarios: &[&[(&str, T)]],
+    ) -> Result<Vec<Vec<(&str, T)>>, GraphError> {
+        let names: Vec<&str> = self.inputs().map(|(name, _)| name).collect();
+        let unknown = scenarios
+            .iter()
+            .flat_map(|scenario| scenario.iter())
+            .find(|(name, _)| !names.contains(name));
+        if let Some((name, _)) = unknown {
+            return Err(GraphError::UnknownInput {
+                name: name.to_string(),
+            });
+        }
+        let original: Vec<T> = self
+            .inputs()
+            .map(|(_, input)| input.borrow().value())
+            .collect();
+        let nodes = self.reachable();
+        // results by node address and key of values of leaves it depends on
+        let mut memo: HashMap<(usize, Key), T> = HashMap::new();
+        let mut results = Vec::with_capacity(scenarios.len());
+        for scenario in scenarios {
+            let mut values = original.clone();
+            for (name, val) in scenario.iter() {
+                let i = names
+                    .iter()
+                    .position(|n| n == name)
+                    .expect("names are checked above");
+                values[i] = *val;
+            }
+            if let Err(err) = self.assign(&names, &values) {
+                self.assign(&names, &original)
+                    .expect("original values are valid");
+                return Err(err);
+            }
+
+            let keys = keys(&nodes);
+            for node in &nodes {
+                let key = match &keys[&node_addr(node)] {
+                    Some(key) if node.borrow().is_dirty() => key,
+                    _ => continue,
+                };
+                // restored only together with operands, so operands keep resetting it
+                let ready = node
+                    .borrow()
+                    .operands()
+                    .iter()
+                    .all(|x| !x.borrow().is_dirty());
+                if let (true, Some(val)) = (ready, memo.get(&(node_addr(node), key.clone()))) {
+                    node.borrow_mut().seed_cache(*val);
+                }
+            }
+            results.push(self.outputs());
+            for node in &nodes {
+                if let Some(key) = &keys[&node_addr(node)] {
+                    if !node.borrow().operands().is_empty() && !node.borrow().is_dirty() {
+                        let val = node.borrow_mut().compute();
+                        memo.insert((node_addr(node), key.clone()), val);
+                    }
+                }
+            }
+        }
+        self.assign(&names, &original)
+            .expect("original values are valid");
+        Ok(results)
+    }
+
+    /// Sets inputs with given names to given values, skipping inputs which keep their value.
+    fn assign(&self, names: &[&str], values: &[T]) -> Result<(), GraphError> {
+        let changes: Vec<(&str, T)> = names
+            .iter()
+            .zip(values)
+            .filter(|(name, val)| {
+                self.input(name)
+                    .map_or(false, |input| input.borrow().value() != **val)
+            })
+            .map(|(name, val)| (*name, *val))
+            .collect();
+        self.replace_many(&changes)
+    }
+}
+
+/// Bit patterns of values of leaves a node depends on, by leaf address.
+type Key = Vec<(usize, u64)>;
+
+/// Computes keys of values of leaves each node depends on, merged from keys of operands,
+/// so equal keys mean equal leaf values. Nodes depending on dirty leaves, which may be
+/// volatile, have no key.
+fn keys<T: Float>(nodes: &[Handle<T>]) -> HashMap<usize, Option<Key>> {
+    let mut keys: HashMap<usize, Option<Key>> = HashMap::with_capacity(nodes.len());
+    for node in nodes {
+        let operands = node.borrow().operands();
+        let key = if operands.is_empty() {
+            if node.borrow().is_dirty() {
+                None
+            } else {
+                let val = node.borrow_mut().compute().to_f64().to_bits();
+                Some(vec![(node_addr(node), val)])
+            }
+        } else {
+            operands
+                .iter()
+                .try_fold(Vec::new(), |mut key: Key, x| {
+                    key.extend(keys[&node_addr(x)].as_ref()?);
+                    Some(key)
+                })
+                .map(|mut key| {
+                    key.sort_unstable();
+                    key.dedup();
+                    key
+                })
+        };
+        keys.insert(node_addr(node), key);
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::node::Node;
+
+    #[test]
+    fn shares_results() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 0.0f32);
+        let y = g.add_input("y", 0.0);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let expensive = Node::unary_fn(x.clone(), move |v| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            v * 10.0
+        });
+        g.add_output("z", Node::add(expensive, y.clone()));
+
+        let results = g
+            .eval_scenarios(&[&[("y", 1.0)], &[("x", 1.0)], &[("y", 3.0)]])
+            .unwrap();
+        let z: Vec<f32> = results.iter().map(|outputs| outputs[0].1).collect();
+        assert_eq!(z, vec![1.0, 10.0, 3.0]);
+        // the last scenario reuses result of the first one
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!((x.borrow().value(), y.borrow().value()), (0.0, 0.0));
+        assert_eq!(g.outputs(), vec![("z", 0.0)]);
+
+        y.borrow_mut().add_rule(crate::input::Rule::Finite);
+        assert!(g
+            .eval_scenarios(&[&[("x", 2.0)], &[("y", f32::NAN)]])
+            .is_err());
+        assert_eq!(x.borrow().value(), 0.0);
+        assert_eq!(
+            g.eval_scenarios(&[&[("x", 2.0)], &[("w", 1.0)]]),
+            Err(GraphError::UnknownInput { name: "w".into() })
+        );
+        assert_eq!(x.borrow().value(), 0.0);
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        let y = g.add_input("y", 2.0);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let expensive = Node::unary_fn(x, move |v| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            v * 10.0
+        });
+        g.add_output("z", Node::add(expensive, y));
+        assert_eq!(g.outputs(), vec![("z", 12.0)]);
+
+        let plain = g.snapshot();
+        assert_eq!(
+            plain.values().collect::<Vec<_>>(),
+            vec![("x", 1.0), ("y", 2.0)]
+        );
+        let cached = g.snapshot_with_caches();
+        assert!(cached.has_caches() && !plain.has_caches());
+
+        g.set_many(&[("x", 5.0), ("y", 0.0)]).unwrap();
+        assert_eq!(g.outputs(), vec![("z", 50.0)]);
+        g.restore(&cached).unwrap();
+        assert!(g.dirty_outputs().is_empty());
+        assert_eq!(g.outputs(), vec![("z", 12.0)]);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        g.set_many(&[("x", 5.0)]).unwrap();
+        g.restore(&plain).unwrap();
+        assert_eq!(g.outputs(), vec![("z", 12.0)]);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        // inputs are matched by name, not by position
+        let mut other = Graph::new();
+        other.add_input("z", 5.0f32);
+        let y = other.add_input("y", 3.0);
+        let mut snapshot = Graph::new();
+        snapshot.add_input("x", 1.0f32);
+        snapshot.add_input("y", 5.0);
+        other.restore(&snapshot.snapshot()).unwrap();
+        assert_eq!(y.borrow().value(), 5.0);
+    }
+}
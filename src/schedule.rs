@@ -0,0 +1,131 @@
+//! Coalescing of frequent input updates into periodic recomputations.
+
+use std::time::{Duration, Instant};
+
+use crate::{error::GraphError, float::Float, graph::Graph};
+
+/// Graph wrapper buffering input updates and applying them at most once per window.
+/// Only the latest value set to each input within a window is applied.
+pub struct Debouncer<T: Float = f32> {
+    graph: Graph<T>,
+    /// Minimal time between recomputations.
+    window: Duration,
+    /// Latest values of inputs set since last recomputation.
+    pending: Vec<(String, T)>,
+    /// Moment of first update since last recomputation.
+    since: Option<Instant>,
+}
+
+impl<T: Float> Debouncer<T> {
+    /// Wraps graph, coalescing updates within given window.
+    pub fn new(graph: Graph<T>, window: Duration) -> Self {
+        Self {
+            graph,
+            window,
+            pending: Vec::new(),
+            since: None,
+        }
+    }
+
+    /// Returns wrapped graph.
+    pub fn graph(&self) -> &Graph<T> {
+        &self.graph
+    }
+
+    /// Buffers new value of input, returning `false` if input isn't registered.
+    pub fn set(&mut self, name: &str, val: T) -> bool {
+        if self.graph.input(name).is_none() {
+            return false;
+        }
+        match self.pending.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = val,
+            None => self.pending.push((name.to_string(), val)),
+        }
+        self.since.get_or_insert_with(Instant::now);
+        true
+    }
+
+    /// Returns true if there are updates not applied yet.
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Applies buffered updates and recomputes dirty outputs if window since first update
+    /// has passed, returning names of recomputed outputs. Fails like `flush`.
+    pub fn poll(&mut self) -> Result<Vec<&str>, GraphError> {
+        self.poll_at(Instant::now())
+    }
+
+    /// Applies buffered updates at once with `Graph::set_many` and recomputes dirty outputs
+    /// right away. If some value violates rules of its input, no input is changed and
+    /// buffered updates are discarded.
+    pub fn flush(&mut self) -> Result<Vec<&str>, GraphError> {
+        let pending = std::mem::take(&mut self.pending);
+        self.since = None;
+        let updates: Vec<(&str, T)> = pending
+            .iter()
+            .map(|(name, val)| (name.as_str(), *val))
+            .collect();
+        self.graph.set_many(&updates)?;
+        Ok(self.graph.recompute())
+    }
+
+    fn poll_at(&mut self, now: Instant) -> Result<Vec<&str>, GraphError> {
+        match self.since {
+            Some(since) if now.duration_since(since) >= self.window => self.flush(),
+            _ => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{input::Rule, node::Node};
+
+    #[test]
+    fn coalescing() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0);
+        g.add_output("y", Node::mul(x, Node::constant(2.0)));
+        let computed = Rc::new(RefCell::new(Vec::new()));
+        let sink = computed.clone();
+        g.observe("y", move |v| sink.borrow_mut().push(v));
+        g.recompute();
+
+        let mut d = Debouncer::new(g, Duration::from_millis(100));
+        assert!(!d.set("z", 1.0));
+        (0..1000).for_each(|i| assert!(d.set("x", i as f32)));
+        assert!(d.is_pending());
+        assert!(d.poll().unwrap().is_empty());
+        assert!(d.graph().dirty_outputs().is_empty());
+
+        let start = d.since.unwrap();
+        assert!(d
+            .poll_at(start + Duration::from_millis(50))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            d.poll_at(start + Duration::from_millis(100)).unwrap(),
+            vec!["y"]
+        );
+        assert!(!d.is_pending());
+        assert_eq!(*computed.borrow(), vec![2.0, 1998.0]);
+
+        d.set("x", 0.5);
+        assert_eq!(d.flush().unwrap(), vec!["y"]);
+        assert_eq!(*computed.borrow(), vec![2.0, 1998.0, 1.0]);
+
+        d.graph()
+            .input("x")
+            .unwrap()
+            .borrow_mut()
+            .add_rule(Rule::Finite);
+        d.set("x", f32::NAN);
+        assert!(d.flush().is_err());
+        assert!(!d.is_pending());
+        assert_eq!(d.graph().outputs(), vec![("y", 1.0)]);
+    }
+}
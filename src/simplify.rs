@@ -0,0 +1,353 @@
+//! Constant folding, algebraic simplification and common subexpression elimination,
+//! optionally specializing graphs for fixed values of some inputs.
+//!
+//! Rewrites are applied bottom-up, producing new nodes only where something changed,
+//! so unchanged subgraphs, including inputs, are shared with the original graph.
+//! Rules like `x * 0 = 0` assume finite operands, `NaN`s and infinities aren't preserved.
+
+use std::collections::HashMap;
+
+use crate::{
+    float::Float,
+    input::InputId,
+    node::{node_addr, post_order, Handle, Node},
+    ops::{piece, DivPolicy, Operation},
+};
+
+/// Builds simplified graph computing the same value as graph with given root.
+/// Graph must be acyclic, see `Graph::validate`.
+pub fn simplify<T: Float>(root: &Handle<T>) -> Handle<T> {
+    simplify_with(root, HashMap::new())
+}
+
+/// Builds simplified graph computing the same value as graph with given root for given
+/// values of fixed inputs, which are replaced by constants and folded away.
+/// Remaining inputs are shared with the original graph.
+/// Graph must be acyclic, see `Graph::validate`.
+pub fn specialize<T: Float>(root: &Handle<T>, fixed: &[(InputId, T)]) -> Handle<T> {
+    let done = fixed
+        .iter()
+        .map(|(id, val)| (id.0, Node::constant(*val) as Handle<T>))
+        .collect();
+    simplify_with(root, done)
+}
+
+/// Simplifies graph with given nodes already replaced.
+fn simplify_with<T: Float>(root: &Handle<T>, mut done: HashMap<usize, Handle<T>>) -> Handle<T> {
+    for node in post_order(root) {
+        if done.contains_key(&node_addr(&node)) {
+            continue;
+        }
+        let opp = node.borrow().operation().map(|opp| {
+            let mut changed = false;
+            let opp = opp.map_operands(|x| {
+                let new = done[&node_addr(x)].clone();
+                changed |= node_addr(&new) != node_addr(x);
+                new
+            });
+            (opp, changed)
+        });
+        let new = match opp {
+            Some((opp, changed)) => rewrite(opp).unwrap_or_else(|opp| {
+                if changed {
+                    Node::new(opp)
+                } else {
+                    node.clone()
+                }
+            }),
+            None => node.clone(),
+        };
+        done.insert(node_addr(&node), new);
+    }
+    done[&node_addr(root)].clone()
+}
+
+/// Builds graph where structurally identical subexpressions share a single node,
+/// so they are computed and cached once.
+/// Nodes without operation, such as inputs, are kept as they are.
+/// Graph must be acyclic, see `Graph::validate`.
+pub fn dedup<T: Float>(root: &Handle<T>) -> Handle<T> {
+    // canonical node and its id for each visited node
+    let mut done: HashMap<usize, (usize, Handle<T>)> = HashMap::new();
+    // canonical node and its id for each distinct operation over canonical ids
+    let mut canonical: HashMap<String, (usize, Handle<T>)> = HashMap::new();
+    for node in post_order(root) {
+        let id = done.len();
+        let opp = node.borrow().operation().cloned();
+        let entry = match opp {
+            Some(opp) => {
+                // float parameters have no `Hash`, their debug form tells them apart
+                let key = format!("{:?}", opp.map_operands(|x| done[&node_addr(x)].0));
+                canonical
+                    .entry(key)
+                    .or_insert_with(|| {
+                        let changed = opp
+                            .operands()
+                            .iter()
+                            .any(|x| node_addr(&done[&node_addr(x)].1) != node_addr(x));
+                        if changed {
+                            (
+                                id,
+                                Node::new(opp.map_operands(|x| done[&node_addr(x)].1.clone())),
+                            )
+                        } else {
+                            (id, node.clone())
+                        }
+                    })
+                    .clone()
+            }
+            None => (id, node.clone()),
+        };
+        done.insert(node_addr(&node), entry);
+    }
+    done[&node_addr(root)].1.clone()
+}
+
+impl<T: Float> Node<T> {
+    /// Builds simplified graph computing the same value, see `simplify` module.
+    pub fn simplify(root: &Handle<T>) -> Handle<T> {
+        simplify(root)
+    }
+
+    /// Builds graph computing the same value for given values of fixed inputs,
+    /// see `specialize`.
+    pub fn specialize(root: &Handle<T>, fixed: &[(InputId, T)]) -> Handle<T> {
+        specialize(root, fixed)
+    }
+
+    /// Builds graph sharing identical subexpressions, see `dedup`.
+    pub fn dedup(root: &Handle<T>) -> Handle<T> {
+        dedup(root)
+    }
+}
+
+/// Returns value of node if it's a constant.
+fn constant<T: Float>(node: &Handle<T>) -> Option<T> {
+    match node.borrow().operation() {
+        Some(Operation::Const(val)) => Some(*val),
+        _ => None,
+    }
+}
+
+/// Applies simplification rules to operation over already simplified operands,
+/// returning replacement node or unchanged operation if no rule applies.
+fn rewrite<T: Float>(opp: Operation<Handle<T>, T>) -> Result<Handle<T>, Operation<Handle<T>, T>> {
+    use Operation::*;
+
+    let operands = opp.operands();
+    if !operands.is_empty() && operands.iter().all(|x| constant(x).is_some()) {
+        return Ok(Node::constant(opp.eval(|x| constant(x).unwrap())));
+    }
+    let is = |x: &Handle<T>, val: T| constant(x) == Some(val);
+    match opp {
+        Add(x, y) if is(&y, T::ZERO) => Ok(x),
+        Add(x, y) if is(&x, T::ZERO) => Ok(y),
+        Sub(x, y) if is(&y, T::ZERO) => Ok(x),
+        Sub(x, y) if is(&x, T::ZERO) => Ok(rewrite(Neg(y)).unwrap_or_else(|opp| Node::new(opp))),
+        Mul(x, y) if is(&x, T::ZERO) || is(&y, T::ZERO) => Ok(Node::constant(T::ZERO)),
+        Mul(x, y) if is(&y, T::ONE) => Ok(x),
+        Mul(x, y) if is(&x, T::ONE) => Ok(y),
+        Div(x, y, _) if is(&y, T::ONE) => Ok(x),
+        Div(x, y, DivPolicy::Propagate) if is(&x, T::ONE) => {
+            Ok(rewrite(Recip(y)).unwrap_or_else(|opp| Node::new(opp)))
+        }
+        Neg(x) => match x.borrow().operation() {
+            Some(Neg(y)) => Ok(y.clone()),
+            _ => Err(Neg(x.clone())),
+        },
+        Recip(x) => match x.borrow().operation() {
+            Some(Recip(y)) => Ok(y.clone()),
+            _ => Err(Recip(x.clone())),
+        },
+        Pow(x, pow) if pow == T::ONE => Ok(x),
+        Pow(_, pow) if pow == T::ZERO => Ok(Node::constant(T::ONE)),
+        Select(cond, x, y) if constant(&cond).is_some() => {
+            Ok(if constant(&cond).unwrap() > T::ZERO {
+                x
+            } else {
+                y
+            })
+        }
+        Piecewise(x, pieces, default) if constant(&x).is_some() => {
+            Ok(piece(constant(&x).unwrap(), &pieces, &default).clone())
+        }
+        AddVar(args) => rewrite_sum(args),
+        opp => Err(opp),
+    }
+}
+
+/// Flattens nested sums and folds their constant terms.
+fn rewrite_sum<T: Float>(args: Vec<Handle<T>>) -> Result<Handle<T>, Operation<Handle<T>, T>> {
+    let mut terms = Vec::new();
+    let mut sum = T::ZERO;
+    let mut folded = 0;
+    let mut stack: Vec<_> = args.iter().rev().cloned().collect();
+    while let Some(x) = stack.pop() {
+        let nested = match x.borrow().operation() {
+            Some(Operation::AddVar(args)) => Some(args.clone()),
+            _ => None,
+        };
+        match (nested, constant(&x)) {
+            (Some(args), _) => {
+                folded += 1;
+                stack.extend(args.into_iter().rev());
+            }
+            (None, Some(val)) => {
+                folded += 1;
+                sum = sum + val;
+            }
+            (None, None) => terms.push(x),
+        }
+    }
+    if folded == 0 {
+        return Err(Operation::AddVar(args));
+    }
+    if sum != T::ZERO || terms.is_empty() {
+        terms.push(Node::constant(sum));
+    }
+    Ok(if terms.len() == 1 {
+        terms.pop().unwrap()
+    } else {
+        Node::add_var(terms)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    fn count(root: &Handle) -> usize {
+        let mut seen = std::collections::HashSet::new();
+        let mut stack = vec![root.clone()];
+        while let Some(node) = stack.pop() {
+            if seen.insert(node_addr(&node)) {
+                stack.extend(node.borrow().operands());
+            }
+        }
+        seen.len()
+    }
+
+    #[test]
+    fn identities() {
+        let x = Node::input(3.0f32);
+        let zero = Node::constant(0.0);
+        let one = Node::constant(1.0);
+        let root: Handle = Node::add(
+            Node::mul(Node::pow(x.clone(), 1.0), one.clone()),
+            Node::add(
+                Node::mul(Node::sin(x.clone()), zero.clone()),
+                Node::div(Node::sub(x.clone(), zero), one),
+            ),
+        );
+        let simple = Node::simplify(&root);
+        assert_eq!(simple.borrow().operation().unwrap().name(), "add");
+        assert_eq!(count(&simple), 2);
+        assert_eq!(simple.borrow_mut().compute(), root.borrow_mut().compute());
+
+        x.borrow_mut().set(5.0);
+        assert_eq!(simple.borrow_mut().compute(), 10.0);
+    }
+
+    #[test]
+    fn negation() {
+        let x = Node::input(4.0f32);
+        let root: Handle = Node::add(
+            Node::sub(Node::constant(0.0), Node::neg(x.clone())),
+            Node::recip(Node::div(Node::constant(1.0), x.clone())),
+        );
+        let simple = simplify(&root);
+        let operands = simple.borrow().operands();
+        assert!(operands.iter().all(|op| node_addr(op) == node_addr(&x)));
+        assert_eq!(simple.borrow_mut().compute(), 8.0);
+    }
+
+    #[test]
+    fn constant_folding() {
+        let x = Node::input(2.0f32);
+        let folded = Node::mul(
+            Node::sin(Node::constant(0.5)),
+            Node::add(Node::constant(1.0), Node::constant(2.0)),
+        );
+        let root: Handle = Node::lazy_select(
+            Node::constant(1.0),
+            Node::add(x.clone(), folded),
+            Node::asin(x),
+        );
+        let simple = simplify(&root);
+        assert_eq!(count(&simple), 3);
+        assert_eq!(simple.borrow_mut().compute(), 2.0 + 0.5f32.sin() * 3.0);
+    }
+
+    #[test]
+    fn specialization() {
+        let x = Node::input(2.0f32);
+        let y = Node::input(3.0f32);
+        let root: Handle = Node::add(
+            Node::mul(x.clone(), Node::exp(y.clone())),
+            Node::select(
+                Node::lt(y.clone(), Node::constant(0.0)),
+                x.clone(),
+                y.clone(),
+            ),
+        );
+        let special = Node::specialize(&root, &[(InputId::of(&y), 0.0)]);
+        // x * exp(0) + select(0 < 0, x, 0) folds into x itself
+        assert_eq!(node_addr(&special), node_addr(&x));
+        x.borrow_mut().set(5.0);
+        assert_eq!(special.borrow_mut().compute(), 5.0);
+
+        // fixed inputs keep their values in the original graph
+        assert_eq!(y.borrow().value(), 3.0);
+        assert_eq!(root.borrow_mut().compute(), 5.0 * 3.0f32.exp() + 3.0);
+    }
+
+    #[test]
+    fn nested_sums() {
+        let x = Node::input(1.0f32);
+        let y = Node::input(2.0f32);
+        let root: Handle = Node::add_var(vec![
+            Node::add_var(vec![x.clone(), Node::constant(1.0)]),
+            Node::add_var(vec![Node::add_var(vec![y.clone()]), Node::constant(2.0)]),
+            x.clone(),
+        ]);
+        let simple = simplify(&root);
+        match simple.borrow().operation() {
+            Some(Operation::AddVar(args)) => assert_eq!(args.len(), 4),
+            _ => panic!("expected flat sum"),
+        }
+        assert_eq!(simple.borrow_mut().compute(), 7.0);
+
+        // unchanged graph is returned as is
+        let root: Handle = Node::add(x, y);
+        assert_eq!(node_addr(&simplify(&root)), node_addr(&root));
+    }
+
+    #[test]
+    fn common_subexpressions() {
+        let x = Node::input(2.0f32);
+        let y = Node::input(3.0f32);
+        let left = Node::sin(Node::mul(x.clone(), y.clone()));
+        let right = Node::sin(Node::mul(x.clone(), y.clone()));
+        let root: Handle = Node::add_var(vec![
+            Node::pow(left.clone(), 2.0),
+            Node::pow(right, 2.0),
+            Node::pow(left, 3.0),
+            Node::mul(y.clone(), x.clone()),
+        ]);
+        assert_eq!(count(&root), 11);
+        let shared = Node::dedup(&root);
+        // operand order matters, so y * x stays separate
+        assert_eq!(count(&shared), 8);
+        assert_eq!(shared.borrow_mut().compute(), root.borrow_mut().compute());
+
+        let operands = shared.borrow().operands();
+        assert_eq!(node_addr(&operands[0]), node_addr(&operands[1]));
+        x.borrow_mut().set(1.0);
+        assert_eq!(shared.borrow_mut().compute(), root.borrow_mut().compute());
+
+        // graph without duplicates is returned as is
+        let root: Handle = Node::add(x, y);
+        assert_eq!(node_addr(&dedup(&root)), node_addr(&root));
+    }
+}
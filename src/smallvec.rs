@@ -0,0 +1,168 @@
+//! Small vector with inline storage.
+
+/// Vector that keeps up to `N` elements inline and spills to the heap afterwards.
+#[derive(Clone)]
+pub enum SmallVec<T, const N: usize> {
+    /// Elements stored inline, first `len` slots are occupied.
+    Inline { len: usize, buf: [Option<T>; N] },
+    /// Elements stored on the heap.
+    Heap(Vec<T>),
+}
+
+impl<T, const N: usize> SmallVec<T, N> {
+    /// Creates empty vector without allocating.
+    pub fn new() -> Self {
+        Self::Inline {
+            len: 0,
+            buf: [(); N].map(|_| None),
+        }
+    }
+
+    /// Returns amount of stored elements.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Inline { len, .. } => *len,
+            Self::Heap(vec) => vec.len(),
+        }
+    }
+
+    /// Returns `true` if vector holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if elements are stored on the heap.
+    pub fn spilled(&self) -> bool {
+        matches!(self, Self::Heap(_))
+    }
+
+    /// Appends element, moving storage to the heap once inline capacity is exceeded.
+    pub fn push(&mut self, val: T) {
+        match self {
+            Self::Inline { len, buf } if *len < N => {
+                buf[*len] = Some(val);
+                *len += 1;
+            }
+            Self::Inline { buf, .. } => {
+                let mut vec = Vec::with_capacity(N * 2);
+                vec.extend(buf.iter_mut().filter_map(Option::take));
+                vec.push(val);
+                *self = Self::Heap(vec);
+            }
+            Self::Heap(vec) => vec.push(val),
+        }
+    }
+
+    /// Keeps only elements for which `f` returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        match self {
+            Self::Inline { len, buf } => {
+                let mut kept = 0;
+                for i in 0..*len {
+                    if let Some(val) = buf[i].take() {
+                        if f(&val) {
+                            buf[kept] = Some(val);
+                            kept += 1;
+                        }
+                    }
+                }
+                *len = kept;
+            }
+            Self::Heap(vec) => vec.retain(|val| f(val)),
+        }
+    }
+
+    /// Removes all elements, keeping heap storage if it was allocated.
+    pub fn clear(&mut self) {
+        match self {
+            Self::Inline { len, buf } => {
+                buf.iter_mut().for_each(|v| *v = None);
+                *len = 0;
+            }
+            Self::Heap(vec) => vec.clear(),
+        }
+    }
+
+    /// Returns iterator over stored elements.
+    pub fn iter(&self) -> Iter<'_, T> {
+        match self {
+            Self::Inline { len, buf } => Iter::Inline(buf[..*len].iter()),
+            Self::Heap(vec) => Iter::Heap(vec.iter()),
+        }
+    }
+}
+
+impl<T, const N: usize> Default for SmallVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a SmallVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over `SmallVec` elements.
+pub enum Iter<'a, T> {
+    Inline(std::slice::Iter<'a, Option<T>>),
+    Heap(std::slice::Iter<'a, T>),
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Inline(it) => it.next().and_then(Option::as_ref),
+            Self::Heap(it) => it.next(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_inline() {
+        let mut v = SmallVec::<i32, 2>::new();
+        assert!(v.is_empty());
+
+        v.push(1);
+        v.push(2);
+        assert_eq!(v.len(), 2);
+        assert!(!v.spilled());
+        assert_eq!(v.iter().copied().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn push_spill() {
+        let mut v = SmallVec::<i32, 2>::new();
+        (1..=5).for_each(|i| v.push(i));
+        assert_eq!(v.len(), 5);
+        assert!(v.spilled());
+        assert_eq!(v.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn retain_clear() {
+        let mut v = SmallVec::<i32, 2>::new();
+        v.push(1);
+        v.push(2);
+        v.retain(|i| *i != 1);
+        assert_eq!(v.iter().copied().collect::<Vec<_>>(), vec![2]);
+
+        (3..=5).for_each(|i| v.push(i));
+        v.retain(|i| i % 2 == 0);
+        assert_eq!(v.iter().copied().collect::<Vec<_>>(), vec![2, 4]);
+
+        v.clear();
+        assert!(v.is_empty());
+        assert!(v.spilled());
+    }
+}
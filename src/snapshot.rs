@@ -0,0 +1,281 @@
+//! Structural snapshots of graphs, which can be stored and restored later.
+//!
+//! Nodes are referenced by index, so subgraphs shared by several operands
+//! are restored shared instead of being duplicated.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    error::GraphError,
+    float::Float,
+    graph::Graph,
+    input::{Input, InputNode},
+    node::{node_addr, post_order, Handle, Node, NodeOperation},
+    ops::Operation,
+    visit::NodeId,
+};
+
+/// Node of snapshot, referencing its operands by indices of preceding nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotNode<T: Float = f32> {
+    /// Input with its current and default value.
+    /// Custom nodes without operation are stored as inputs holding their current value.
+    Input { value: T, default: T },
+    /// Operation over preceding nodes.
+    Operation(Operation<usize, T>),
+}
+
+/// Structure and input values of graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<T: Float = f32> {
+    /// Nodes ordered so operands precede nodes using them.
+    pub nodes: Vec<SnapshotNode<T>>,
+    /// Names of inputs with indices of their nodes, in registration order.
+    pub inputs: Vec<(String, usize)>,
+    /// Names of outputs with indices of their nodes, in registration order.
+    pub outputs: Vec<(String, usize)>,
+}
+
+impl<T: Float> Snapshot<T> {
+    /// Takes snapshot of nodes reachable from registered inputs and outputs.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn of(graph: &Graph<T>) -> Self {
+        let inputs: HashMap<_, _> = graph
+            .inputs()
+            .map(|(_, input)| (node_addr(input), input.clone()))
+            .collect();
+        let mut snapshot = Self {
+            nodes: Vec::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        };
+        let mut ids = HashMap::new();
+        for (name, input) in graph.inputs() {
+            let id = snapshot.visit(input.clone(), &inputs, &mut ids);
+            snapshot.inputs.push((name.to_string(), id));
+        }
+        for (name, output) in graph.output_nodes() {
+            let id = snapshot.visit(output.clone(), &inputs, &mut ids);
+            snapshot.outputs.push((name.to_string(), id));
+        }
+        snapshot
+    }
+
+    /// Adds node and its not yet visited operands, returning its index.
+    fn visit(
+        &mut self,
+        root: Handle<T>,
+        inputs: &HashMap<usize, Rc<RefCell<InputNode<T>>>>,
+        ids: &mut HashMap<usize, usize>,
+    ) -> usize {
+        // nodes along with flag whether their operands were already pushed
+        let mut stack = vec![(root.clone(), false)];
+        while let Some((node, expanded)) = stack.pop() {
+            let addr = node_addr(&node);
+            if ids.contains_key(&addr) {
+                continue;
+            }
+            if !expanded {
+                let operands = node.borrow().operands();
+                stack.push((node, true));
+                stack.extend(operands.into_iter().map(|x| (x, false)));
+                continue;
+            }
+            let opp = node.borrow().operation().map(|opp| {
+                opp.map_operands(|x| *ids.get(&node_addr(x)).expect("graph has a cycle"))
+            });
+            let entry = match opp {
+                Some(opp) => SnapshotNode::Operation(opp),
+                None => {
+                    let value = node.borrow_mut().compute();
+                    let default = inputs
+                        .get(&addr)
+                        .map_or(value, |input| input.borrow().default_val());
+                    SnapshotNode::Input { value, default }
+                }
+            };
+            ids.insert(addr, self.nodes.len());
+            self.nodes.push(entry);
+        }
+        ids[&node_addr(&root)]
+    }
+
+    /// Builds new graph with structure and input values of this snapshot.
+    pub fn restore(&self) -> Graph<T> {
+        match self.build(|opp| Ok(Node::new(opp))) {
+            Ok(graph) => graph,
+            Err(err) => unreachable!("{}", err),
+        }
+    }
+
+    /// Builds new graph like `restore`, failing if some operation doesn't accept
+    /// amount or kinds of its operands, see `Node::try_new`.
+    pub fn try_restore(&self) -> Result<Graph<T>, GraphError> {
+        self.build(|opp| Node::try_new(opp, usize::MAX))
+    }
+
+    /// Builds graph creating operation nodes with `node`.
+    fn build(
+        &self,
+        mut node: impl FnMut(NodeOperation<T>) -> Result<Rc<RefCell<Node<T>>>, GraphError>,
+    ) -> Result<Graph<T>, GraphError> {
+        let mut inputs = HashMap::new();
+        let mut handles: Vec<Handle<T>> = Vec::with_capacity(self.nodes.len());
+        for (id, entry) in self.nodes.iter().enumerate() {
+            let handle: Handle<T> = match entry {
+                SnapshotNode::Input { value, default } => {
+                    let input = InputNode::from_val(*default);
+                    input.borrow_mut().set(*value);
+                    inputs.insert(id, input.clone());
+                    input
+                }
+                SnapshotNode::Operation(opp) => node(opp.map_operands(|x| handles[*x].clone()))?,
+            };
+            handles.push(handle);
+        }
+
+        let mut graph = Graph::new();
+        for (name, id) in &self.inputs {
+            graph.register_input(name.clone(), inputs[id].clone());
+        }
+        for (name, id) in &self.outputs {
+            graph.add_output(name.clone(), handles[*id].clone());
+        }
+        Ok(graph)
+    }
+}
+
+impl<T: Float> Node<T> {
+    /// Copies whole graph with given root, keeping subgraphs shared by several operands
+    /// shared in the copy. Returns root of the copy with its inputs by identifiers of the
+    /// original nodes, so the same formula can be evaluated with independent input sets.
+    /// Inputs and custom nodes without operation are copied as inputs holding their current
+    /// value and label. Graph must be acyclic, see `Graph::validate`.
+    #[allow(clippy::type_complexity)]
+    pub fn deep_clone(root: &Handle<T>) -> (Handle<T>, HashMap<NodeId, Rc<RefCell<InputNode<T>>>>) {
+        let mut inputs = HashMap::new();
+        let mut copies: HashMap<usize, Handle<T>> = HashMap::new();
+        for node in post_order(root) {
+            let opp = node.borrow().operation().map(|opp| {
+                opp.map_operands(|x| {
+                    copies
+                        .get(&node_addr(x))
+                        .expect("graph has a cycle")
+                        .clone()
+                })
+            });
+            let label = node.borrow().label().map(String::from);
+            let copy: Handle<T> = match opp {
+                Some(opp) => {
+                    let copy = Node::new(opp);
+                    if let Some(label) = label {
+                        copy.borrow_mut().set_label(label);
+                    }
+                    copy
+                }
+                None => {
+                    let input = InputNode::from_val(node.borrow_mut().compute());
+                    if let Some(label) = label {
+                        input.borrow_mut().set_label(label);
+                    }
+                    inputs.insert(NodeId::of(&node), input.clone());
+                    input
+                }
+            };
+            copies.insert(node_addr(&node), copy);
+        }
+        (copies[&node_addr(root)].clone(), inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Computable;
+
+    #[test]
+    fn round_trip() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        let y = g.add_input("y", 2.0);
+        g.add_input("unused", 5.0);
+        x.borrow_mut().set(3.0);
+        let shared = Node::sin(Node::mul(x.clone(), y.clone()));
+        g.add_output(
+            "a",
+            Node::add(shared.clone(), Node::pow(shared.clone(), 2.0)),
+        );
+        g.add_output("b", Node::softmax_component(vec![shared, x, y.clone()], 1));
+
+        let snapshot = Snapshot::of(&g);
+        // 3 inputs, mul, sin, pow, add and softmax
+        assert_eq!(snapshot.nodes.len(), 8);
+        assert_eq!(
+            snapshot.nodes[snapshot.inputs[0].1],
+            SnapshotNode::Input {
+                value: 3.0,
+                default: 1.0
+            }
+        );
+
+        let restored = snapshot.restore();
+        assert_eq!(restored.outputs(), g.outputs());
+        assert_eq!(Snapshot::of(&restored), snapshot);
+
+        // shared subgraph stays shared
+        let a = restored.output("a").unwrap().borrow().operands();
+        let pow_operand = a[1].borrow().operands()[0].clone();
+        assert_eq!(node_addr(&a[0]), node_addr(&pow_operand));
+
+        restored.input("y").unwrap().borrow_mut().set(4.0);
+        y.borrow_mut().set(4.0);
+        assert_eq!(restored.outputs(), g.outputs());
+        restored.reset_inputs();
+        assert_eq!(restored.input("x").unwrap().borrow_mut().compute(), 1.0);
+    }
+
+    #[test]
+    fn try_restore() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0f32);
+        g.add_output("y", Node::binary_fn(x.clone(), x, |a, b| a + b));
+        let mut snapshot = Snapshot::of(&g);
+        assert_eq!(snapshot.try_restore().unwrap().outputs(), vec![("y", 2.0)]);
+
+        // closure over two values wired to one operand
+        if let Some(SnapshotNode::Operation(Operation::Custom(_, args))) = snapshot.nodes.last_mut()
+        {
+            args.pop();
+        }
+        assert_eq!(
+            snapshot.try_restore().err(),
+            Some(GraphError::Arity {
+                operation: "fn",
+                min: 2,
+                max: 2,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn deep_clone() {
+        let x = InputNode::with_label(2.0f32, "x");
+        let y = InputNode::from_val(3.0);
+        let shared = Node::mul(x.clone(), y.clone());
+        let root: Handle = Node::add(Node::sin(shared.clone()), shared);
+        let (copy, inputs) = Node::deep_clone(&root);
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(Node::stats(&copy), Node::stats(&root));
+        assert_eq!(copy.borrow_mut().compute(), root.borrow_mut().compute());
+
+        let x_copy = &inputs[&NodeId::of(&x)];
+        assert_eq!(x_copy.borrow().label(), Some("x"));
+        x_copy.borrow_mut().set(0.5);
+        inputs[&NodeId::of(&y)].borrow_mut().set(2.0);
+        assert_eq!(copy.borrow_mut().compute(), 1f32.sin() + 1.0);
+        // original keeps its own inputs
+        assert!(!root.borrow().is_dirty());
+        assert_eq!(root.borrow_mut().compute(), 6f32.sin() + 6.0);
+    }
+}
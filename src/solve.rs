@@ -0,0 +1,217 @@
+//! Root finding driving an input so that expression evaluates to zero.
+//!
+//! Newton's method uses derivatives by the input from reverse-mode differentiation,
+//! see `grad`. Steps leaving the known bracket of the root, or taken where derivative
+//! is zero or not finite, like over custom nodes, are replaced with bisection.
+
+use std::{cell::RefCell, error::Error, fmt, rc::Rc};
+
+use crate::{
+    grad::Gradient,
+    input::{settle, Input, InputNode},
+    node::Computable,
+};
+
+/// Maximum amount of iterations, including bracket search.
+const MAX_ITERATIONS: usize = 200;
+
+/// Starting point of root search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Start {
+    /// Initial guess, bracket is searched around it if Newton's method can't proceed.
+    Guess(f32),
+    /// Interval where expression changes sign, so it contains a root.
+    Bracket(f32, f32),
+}
+
+/// Root found by `find_root`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Root {
+    /// Input value at root.
+    pub value: f32,
+    /// Expression value at root.
+    pub residual: f32,
+    /// Amount of iterations performed.
+    pub iterations: usize,
+}
+
+/// Failure of root search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SolveError {
+    /// Expression has the same sign at both ends of given bracket.
+    InvalidBracket {
+        /// Expression values at the ends.
+        values: (f32, f32),
+    },
+    /// Search didn't converge within the iteration limit.
+    NotConverged {
+        /// The last input value tried.
+        value: f32,
+        /// Expression value there.
+        residual: f32,
+    },
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::InvalidBracket { values } => write!(
+                f,
+                "bracket doesn't contain root, values {} and {} have the same sign",
+                values.0, values.1
+            ),
+            SolveError::NotConverged { value, residual } => write!(
+                f,
+                "root search didn't converge, residual at {} is {}",
+                value, residual
+            ),
+        }
+    }
+}
+
+impl Error for SolveError {}
+
+/// Finds value of `input` for which `node` evaluates to zero, leaving input set to it.
+/// Search stops once value is within `tol` of zero, or bracket of the root narrows
+/// below `tol`. Search probes the input, see `Input::probe`, and the root is set through
+/// `Input::set` unless it violates input rules. On failure, input is restored to its
+/// original value.
+pub fn find_root<C: Computable + ?Sized>(
+    node: &Rc<RefCell<C>>,
+    input: &Rc<RefCell<InputNode>>,
+    start: Start,
+    tol: f32,
+) -> Result<Root, SolveError> {
+    let original = input.borrow().value();
+    let res = search(node, input, start, tol);
+    match &res {
+        Ok(root) => settle(&mut *input.borrow_mut(), original, root.value),
+        Err(_) => input.borrow_mut().probe(original),
+    }
+    res
+}
+
+fn search<C: Computable + ?Sized>(
+    node: &Rc<RefCell<C>>,
+    input: &Rc<RefCell<InputNode>>,
+    start: Start,
+    tol: f32,
+) -> Result<Root, SolveError> {
+    let eval = |x: f32| {
+        input.borrow_mut().probe(x);
+        node.borrow_mut().compute()
+    };
+    let (mut x, mut bracket) = match start {
+        Start::Guess(x) => (x, None),
+        Start::Bracket(a, b) => {
+            let (fa, fb) = (eval(a), eval(b));
+            if fa * fb > 0.0 {
+                return Err(SolveError::InvalidBracket { values: (fa, fb) });
+            }
+            let bracket = if fa <= 0.0 { (a, b) } else { (b, a) };
+            ((a + b) / 2.0, Some(bracket))
+        }
+    };
+    // distance of bracket search from initial guess
+    let mut reach = x.abs().max(1.0) / 64.0;
+    for iterations in 1..=MAX_ITERATIONS {
+        let fx = eval(x);
+        if fx.abs() <= tol {
+            return Ok(Root {
+                value: x,
+                residual: fx,
+                iterations,
+            });
+        }
+        // keeps negative value at the first end of bracket
+        if let Some((neg, pos)) = &mut bracket {
+            if fx < 0.0 {
+                *neg = x;
+            } else {
+                *pos = x;
+            }
+            if (*pos - *neg).abs() <= tol {
+                return Ok(Root {
+                    value: x,
+                    residual: fx,
+                    iterations,
+                });
+            }
+        }
+        let derivative = Gradient::of(node).wrt(input);
+        let newton = x - fx / derivative;
+        x = match bracket {
+            Some((neg, pos)) => {
+                let (lo, hi) = (neg.min(pos), neg.max(pos));
+                if newton.is_finite() && lo < newton && newton < hi {
+                    newton
+                } else {
+                    (neg + pos) / 2.0
+                }
+            }
+            None if newton.is_finite() && derivative != 0.0 => newton,
+            None => {
+                // widen search around the guess until sign changes
+                for end in [x - reach, x + reach] {
+                    let f_end = eval(end);
+                    if f_end * fx <= 0.0 {
+                        bracket = Some(if fx < 0.0 { (x, end) } else { (end, x) });
+                        break;
+                    }
+                }
+                reach *= 2.0;
+                match bracket {
+                    Some((neg, pos)) => (neg + pos) / 2.0,
+                    None => x,
+                }
+            }
+        };
+    }
+    Err(SolveError::NotConverged {
+        value: x,
+        residual: eval(x),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Node;
+
+    #[test]
+    fn newton() {
+        // x^2 - 2
+        let x = InputNode::from_val(0.0);
+        let f = Node::sub(Node::pow(x.clone(), 2.0), Node::constant(2.0));
+        let root = find_root(&f, &x, Start::Guess(1.0), 1e-6).unwrap();
+        assert!((root.value - 2f32.sqrt()).abs() < 1e-6);
+        assert!(root.iterations < 10);
+        assert_eq!(x.borrow().value(), root.value);
+    }
+
+    #[test]
+    fn bisection() {
+        // custom function has no derivative, so bisection is used
+        let x = InputNode::from_val(0.0);
+        let f = Node::unary_fn(x.clone(), |v| v * v * v - 8.0);
+        let root = find_root(&f, &x, Start::Bracket(0.0, 5.0), 1e-4).unwrap();
+        assert!((root.value - 2.0).abs() < 1e-4);
+        let root = find_root(&f, &x, Start::Guess(1.0), 1e-4).unwrap();
+        assert!((root.value - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn failures() {
+        let x = InputNode::from_val(3.0);
+        let f = Node::add(Node::pow(x.clone(), 2.0), Node::constant(1.0));
+        assert_eq!(
+            find_root(&f, &x, Start::Bracket(-1.0, 1.0), 1e-6),
+            Err(SolveError::InvalidBracket { values: (2.0, 2.0) })
+        );
+        assert!(matches!(
+            find_root(&f, &x, Start::Guess(1.0), 1e-6),
+            Err(SolveError::NotConverged { .. })
+        ));
+        assert_eq!(x.borrow().value(), 3.0);
+    }
+}
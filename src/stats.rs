@@ -0,0 +1,260 @@
+//! Sink nodes accumulating statistics of their operand across evaluations.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    float::Float,
+    node::{
+        dependents, post_order, remove_dependency, reset_dependencies, Computable, Dependencies,
+        Handle,
+    },
+};
+
+/// Histogram with equal width bins over a range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Lower bound of the first bin.
+    pub min: f64,
+    /// Upper bound of the last bin.
+    pub max: f64,
+    /// Amount of values per bin.
+    pub counts: Vec<u64>,
+    /// Amount of values below `min`.
+    pub underflow: u64,
+    /// Amount of values at or above `max`, and `NaN`s.
+    pub overflow: u64,
+}
+
+impl Histogram {
+    /// Creates empty histogram with given amount of bins over `[min, max)`.
+    pub fn new(min: f64, max: f64, bins: usize) -> Self {
+        assert!(
+            min < max && bins > 0,
+            "histogram needs a non-empty range and bins"
+        );
+        Self {
+            min,
+            max,
+            counts: vec![0; bins],
+            underflow: 0,
+            overflow: 0,
+        }
+    }
+
+    /// Counts value in its bin.
+    pub fn add(&mut self, val: f64) {
+        if val < self.min {
+            self.underflow += 1;
+        } else if val < self.max {
+            let bins = self.counts.len();
+            let bin = ((val - self.min) / (self.max - self.min) * bins as f64) as usize;
+            self.counts[bin.min(bins - 1)] += 1;
+        } else {
+            self.overflow += 1;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.counts.iter_mut().for_each(|c| *c = 0);
+        self.underflow = 0;
+        self.overflow = 0;
+    }
+}
+
+/// Running statistics of values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    count: u64,
+    mean: f64,
+    /// Sum of squared deviations from mean.
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Stats {
+    /// Accounts value, using Welford's algorithm for numerically stable variance.
+    pub fn add(&mut self, val: f64) {
+        self.count += 1;
+        if self.count == 1 {
+            self.min = val;
+            self.max = val;
+        } else {
+            self.min = self.min.min(val);
+            self.max = self.max.max(val);
+        }
+        let delta = val - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (val - self.mean);
+    }
+
+    /// Returns amount of accounted values.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns mean of values, `None` if there are none.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.mean)
+    }
+
+    /// Returns smallest value, `None` if there are none.
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.min)
+    }
+
+    /// Returns largest value, `None` if there are none.
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.max)
+    }
+
+    /// Returns population standard deviation, `None` if there are no values.
+    pub fn std_dev(&self) -> Option<f64> {
+        (self.count > 0).then(|| (self.m2 / self.count as f64).sqrt())
+    }
+}
+
+/// Node passing value of its operand through, while accumulating statistics
+/// of values from each recomputation. Inputs it's computed from count every set,
+/// even of unchanged value, see `Computable::count_every_set`.
+pub struct StatsNode<T: Float = f32> {
+    source: Handle<T>,
+    stats: Stats,
+    histogram: Option<Histogram>,
+    /// Cached result.
+    cache: Option<T>,
+    /// Holds references to nodes that depend from this node.
+    dependencies: Dependencies<T>,
+}
+
+impl<T: Float> StatsNode<T> {
+    /// Builds node accumulating running statistics of given operand.
+    pub fn new(source: Handle<T>) -> Rc<RefCell<Self>> {
+        Self::build(source, None)
+    }
+
+    /// Builds node accumulating running statistics and histogram of given operand.
+    pub fn with_histogram(source: Handle<T>, histogram: Histogram) -> Rc<RefCell<Self>> {
+        Self::build(source, Some(histogram))
+    }
+
+    fn build(source: Handle<T>, histogram: Option<Histogram>) -> Rc<RefCell<Self>> {
+        let obj = Rc::new(RefCell::new(Self {
+            source: source.clone(),
+            stats: Stats::default(),
+            histogram,
+            cache: None,
+            dependencies: Dependencies::default(),
+        }));
+        source.borrow_mut().add_dependency(obj.clone());
+        post_order(&source)
+            .iter()
+            .for_each(|x| x.borrow_mut().count_every_set());
+        obj
+    }
+
+    /// Returns running statistics.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Returns histogram, if node was built with one.
+    pub fn histogram(&self) -> Option<&Histogram> {
+        self.histogram.as_ref()
+    }
+
+    /// Clears accumulated statistics and histogram counts.
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+        if let Some(histogram) = &mut self.histogram {
+            histogram.clear();
+        }
+    }
+}
+
+impl<T: Float> Computable<T> for StatsNode<T> {
+    fn compute(&mut self) -> T {
+        if let Some(val) = self.cache {
+            return val;
+        }
+        let val = self.source.borrow_mut().compute();
+        self.stats.add(val.to_f64());
+        if let Some(histogram) = &mut self.histogram {
+            histogram.add(val.to_f64());
+        }
+        self.cache = Some(val);
+        val
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependencies.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Handle<T>> {
+        dependents(&self.dependencies)
+    }
+
+    fn reset_cache(&mut self) {
+        if self.cache.take().is_none() {
+            return;
+        }
+        reset_dependencies(&mut self.dependencies);
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.cache.is_none()
+    }
+
+    fn operands(&self) -> Vec<Handle<T>> {
+        vec![self.source.clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::Input, node::Node};
+
+    #[test]
+    fn accumulation() {
+        let x = Node::input(0.0f32);
+        let sink = StatsNode::with_histogram(x.clone(), Histogram::new(0.0, 10.0, 5));
+        assert_eq!(sink.borrow().stats().mean(), None);
+
+        for val in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0, -1.0, 10.0] {
+            x.borrow_mut().set(val);
+            assert_eq!(sink.borrow_mut().compute(), val);
+            // cached value isn't counted twice
+            sink.borrow_mut().compute();
+        }
+        let sink = sink.borrow();
+        let stats = sink.stats();
+        assert_eq!(stats.count(), 10);
+        assert!((stats.mean().unwrap() - 4.9).abs() < 1e-12);
+        assert_eq!((stats.min(), stats.max()), (Some(-1.0), Some(10.0)));
+        assert!((stats.std_dev().unwrap() - 3.04795).abs() < 1e-4);
+
+        let histogram = sink.histogram().unwrap();
+        assert_eq!(histogram.counts, vec![0, 1, 5, 1, 1]);
+        assert_eq!((histogram.underflow, histogram.overflow), (1, 1));
+    }
+
+    #[test]
+    fn reset() {
+        let x = Node::input(1.0f32);
+        let sink = StatsNode::new(Node::sin(x.clone()));
+        sink.borrow_mut().compute();
+        assert_eq!(sink.borrow().stats().count(), 1);
+        assert!(sink.borrow().histogram().is_none());
+
+        sink.borrow_mut().reset_stats();
+        assert_eq!(sink.borrow().stats().count(), 0);
+        x.borrow_mut().set(0.0);
+        sink.borrow_mut().compute();
+        assert_eq!(sink.borrow().stats().max(), Some(0.0));
+    }
+}
@@ -0,0 +1,276 @@
+//! Persistence of cached results across process restarts.
+//!
+//! Each node is identified by structural fingerprint of its subgraph, covering operations,
+//! constants and current values of inputs, so a cached result is restored only into a node
+//! computing exactly the same thing. Subgraphs which changed since results were recorded,
+//! e.g. due to new input values, simply don't match and are computed as usual.
+//! `Graph::export_cache` and `Graph::import_cache` transfer results in memory,
+//! `PersistentGraphStore` keeps them in a file.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    float::Float,
+    graph::Graph,
+    node::{node_addr, post_order, Handle},
+    ops::Operation,
+};
+
+/// Computes structural fingerprint of graph with given root, stable across processes.
+/// Returns `None` if graph contains nodes whose structure can't be described,
+/// such as user-defined operations, custom nodes with operands or dirty leaves.
+/// Graph must be acyclic, see `Graph::validate`.
+pub fn fingerprint<T: Float>(root: &Handle<T>) -> Option<u64> {
+    let mut fingerprints = HashMap::new();
+    for node in post_order(root) {
+        let fp = node_fingerprint(&node, &fingerprints);
+        fingerprints.insert(node_addr(&node), fp);
+    }
+    fingerprints[&node_addr(root)]
+}
+
+/// Computes fingerprint of node from already computed fingerprints of its operands.
+fn node_fingerprint<T: Float>(node: &Handle<T>, done: &HashMap<usize, Option<u64>>) -> Option<u64> {
+    let opp = node.borrow().operation().cloned();
+    let key = match opp {
+        // address of user-defined operation differs between processes
+        Some(Operation::Custom(..)) => return None,
+        Some(opp) => {
+            for x in opp.operands() {
+                done[&node_addr(x)]?;
+            }
+            // float parameters have no `Hash`, their debug form is exact
+            format!("{:?}", opp.map_operands(|x| done[&node_addr(x)].unwrap()))
+        }
+        None if node.borrow().operands().is_empty() && !node.borrow().is_dirty() => {
+            format!("leaf {:x}", node.borrow_mut().compute().to_f64().to_bits())
+        }
+        None => return None,
+    };
+    Some(fnv1a(key.as_bytes()))
+}
+
+/// FNV-1a hash, which unlike `DefaultHasher` is guaranteed to stay the same.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Cached results of graph nodes by their fingerprints, kept in a file between runs.
+///
+/// Typical use is to `warm_start` freshly built graph on startup, skipping recomputation
+/// of subgraphs that didn't change, and to `record` and `save` results before exit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistentGraphStore<T: Float = f32> {
+    path: PathBuf,
+    results: HashMap<u64, T>,
+}
+
+impl<T: Float> PersistentGraphStore<T> {
+    /// Opens store kept in given file, which is created on first `save`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        let results = text
+            .lines()
+            .map(|line| {
+                let (fp, val) = line
+                    .split_once(' ')
+                    .ok_or_else(|| invalid(format!("malformed line {:?}", line)))?;
+                let fp = u64::from_str_radix(fp, 16).map_err(|e| invalid(e.to_string()))?;
+                let val = u64::from_str_radix(val, 16).map_err(|e| invalid(e.to_string()))?;
+                Ok((fp, T::from_f64(f64::from_bits(val))))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self { path, results })
+    }
+
+    /// Returns amount of stored results.
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Returns `true` if no results are stored.
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// Fills caches of dirty nodes with stored results, see `Graph::import_cache`,
+    /// returning amount of restored nodes.
+    pub fn warm_start(&self, graph: &Graph<T>) -> usize {
+        graph.import_cache(&self.results)
+    }
+
+    /// Replaces stored results with cached results of graph, see `Graph::export_cache`,
+    /// dropping results of nodes no longer present.
+    pub fn record(&mut self, graph: &Graph<T>) {
+        self.results = graph.export_cache();
+    }
+
+    /// Writes stored results to file.
+    pub fn save(&self) -> io::Result<()> {
+        let mut results: Vec<_> = self.results.iter().collect();
+        results.sort_by_key(|(fp, _)| **fp);
+        let text: String = results
+            .into_iter()
+            .map(|(fp, val)| format!("{:016x} {:016x}\n", fp, val.to_f64().to_bits()))
+            .collect();
+        fs::write(&self.path, text)
+    }
+}
+
+impl<T: Float> Graph<T> {
+    /// Returns cached results of operation nodes reachable from outputs by their
+    /// fingerprints, see `store` module. Constants aren't worth exporting.
+    pub fn export_cache(&self) -> HashMap<u64, T> {
+        let mut results = HashMap::new();
+        visit(self, |node, fp| {
+            let cached = match node.borrow().operation() {
+                Some(Operation::Const(_)) | None => false,
+                Some(_) => !node.borrow().is_dirty(),
+            };
+            if cached {
+                results.insert(fp, node.borrow_mut().compute());
+            }
+        });
+        results
+    }
+
+    /// Fills caches of dirty nodes reachable from outputs with results of matching
+    /// fingerprints exported by `export_cache`, possibly from another graph or process,
+    /// returning amount of restored nodes. Node is restored only together with all its
+    /// operands, so input changes keep invalidating restored results.
+    pub fn import_cache(&self, results: &HashMap<u64, T>) -> usize {
+        let mut restored = 0;
+        visit(self, |node, fp| {
+            if !node.borrow().is_dirty() {
+                return;
+            }
+            let ready = node
+                .borrow()
+                .operands()
+                .iter()
+                .all(|x| !x.borrow().is_dirty());
+            if let (true, Some(val)) = (ready, results.get(&fp)) {
+                if node.borrow_mut().seed_cache(*val) {
+                    restored += 1;
+                }
+            }
+        });
+        restored
+    }
+}
+
+/// Calls `f` with each fingerprinted node reachable from outputs, operands first.
+fn visit<T: Float>(graph: &Graph<T>, mut f: impl FnMut(&Handle<T>, u64)) {
+    let mut fingerprints = HashMap::new();
+    for (_, output) in graph.output_nodes() {
+        for node in post_order(output) {
+            let addr = node_addr(&node);
+            if fingerprints.contains_key(&addr) {
+                continue;
+            }
+            let fp = node_fingerprint(&node, &fingerprints);
+            fingerprints.insert(addr, fp);
+            if let Some(fp) = fp {
+                f(&node, fp);
+            }
+        }
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::Input, node::Node};
+
+    /// Builds the same graph as a freshly started process would.
+    fn build(rate: f32) -> Graph {
+        let mut g = Graph::new();
+        let rate = g.add_input("rate", rate);
+        let spot = g.add_input("spot", 100.0);
+        let curve = Node::exp(Node::mul(rate, Node::constant(-2.0)));
+        g.add_output("curve", curve.clone());
+        g.add_output("price", Node::mul(spot, curve));
+        g
+    }
+
+    #[test]
+    fn fingerprints() {
+        let a = build(0.05);
+        let b = build(0.05);
+        let fp = |g: &Graph| fingerprint(g.output("price").unwrap());
+        assert!(fp(&a).is_some());
+        assert_eq!(fp(&a), fp(&b));
+        b.input("spot").unwrap().borrow_mut().set(101.0);
+        assert_ne!(fp(&a), fp(&b));
+
+        let custom: Handle = Node::unary_fn(Node::constant(1.0), |x| x);
+        assert_eq!(fingerprint(&custom), None);
+    }
+
+    #[test]
+    fn export_import() {
+        let a = build(0.05);
+        a.outputs();
+        let cache = a.export_cache();
+        assert_eq!(cache.len(), 3);
+
+        let b = build(0.05);
+        b.input("spot").unwrap().borrow_mut().set(50.0);
+        // only subgraphs over unchanged inputs are restored
+        assert_eq!(b.import_cache(&cache), 2);
+        assert_eq!(b.dirty_outputs(), vec!["price"]);
+        assert_eq!(b.import_cache(&HashMap::new()), 0);
+    }
+
+    #[test]
+    fn warm_start() {
+        let path = std::env::temp_dir().join(format!("teza-store-{}.txt", std::process::id()));
+        let mut store = PersistentGraphStore::open(&path).unwrap();
+        assert!(store.is_empty());
+
+        let g = build(0.05);
+        let expected = g.outputs();
+        store.record(&g);
+        // mul, exp and price
+        assert_eq!(store.len(), 3);
+        store.save().unwrap();
+
+        let store = PersistentGraphStore::open(&path).unwrap();
+        let g = build(0.05);
+        assert_eq!(store.warm_start(&g), 3);
+        assert!(g.dirty_outputs().is_empty());
+        assert_eq!(g.outputs(), expected);
+        // restored results are still invalidated by inputs
+        g.input("spot").unwrap().borrow_mut().set(50.0);
+        assert_eq!(g.dirty_outputs(), vec!["price"]);
+
+        // changed input only matches subgraphs not depending on it
+        let g = build(0.05);
+        g.input("spot").unwrap().borrow_mut().set(50.0);
+        assert_eq!(store.warm_start(&g), 2);
+        assert_eq!(g.dirty_outputs(), vec!["price"]);
+        let g = build(0.1);
+        assert_eq!(store.warm_start(&g), 0);
+        assert_eq!(g.dirty_outputs(), vec!["curve", "price"]);
+
+        fs::remove_file(&path).unwrap();
+        fs::write(&path, "garbage").unwrap();
+        assert!(PersistentGraphStore::<f32>::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
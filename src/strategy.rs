@@ -0,0 +1,194 @@
+//! Choice of backend for repeated evaluation of a graph.
+//!
+//! The `Node` interpreter recomputes only nodes affected by changed inputs, but pays for
+//! cache bookkeeping and borrow checks on each node, while compiled tape recomputes every
+//! instruction without that overhead, see `compile`. `EvalStrategy::Auto` keeps small
+//! graphs on the interpreter and, for larger ones, measures share of nodes recomputed by
+//! the first calls, switching to compiled tape once most of the graph is recomputed anyway.
+//! Parallel evaluation isn't a candidate, since `Node` graphs can't be shared between
+//! threads, see `sync` for thread-safe nodes.
+
+use crate::{
+    compile::CompiledGraph,
+    differential::Backend,
+    float::Float,
+    node::{post_order, Handle},
+};
+
+/// Graphs with fewer operations stay on the interpreter.
+const MIN_COMPILED_OPERATIONS: usize = 32;
+/// Calls measured before choosing backend, not counting the first one,
+/// which computes the whole graph.
+const MEASURED_CALLS: usize = 8;
+/// Average share of operations recomputed per call from which compiled tape is chosen.
+const COMPILED_SHARE: f64 = 0.5;
+
+/// Strategy of choosing evaluation backend, see `Evaluator`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalStrategy {
+    /// Computes nodes with `Computable::compute`, reusing cached results.
+    Interpreter,
+    /// Evaluates compiled tape, see `CompiledGraph`.
+    Compiled,
+    /// Chooses backend by graph size and share of nodes recomputed by the first calls.
+    Auto,
+}
+
+/// Compiled tape along with leaf nodes feeding its inputs, by input index.
+struct Compiled<T: Float> {
+    tape: CompiledGraph<T>,
+    leaves: Vec<(usize, Handle<T>)>,
+}
+
+/// Evaluator of graph root computed repeatedly as its inputs change.
+/// Compiled tape is built from graph structure once chosen, so structure must not change
+/// afterwards, e.g. by `Node::replace_operand`. Evaluating compiled tape leaves caches
+/// of nodes untouched.
+pub struct Evaluator<T: Float = f32> {
+    root: Handle<T>,
+    /// Nodes reachable from root with operands first, kept while measuring.
+    nodes: Vec<Handle<T>>,
+    backend: Option<Backend>,
+    compiled: Option<Compiled<T>>,
+    /// Amount of calls while measuring.
+    calls: usize,
+    /// Sum of shares of operations recomputed by measured calls.
+    recomputed: f64,
+}
+
+impl<T: Float> Evaluator<T> {
+    /// Creates evaluator of graph with given root.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn new(root: &Handle<T>, strategy: EvalStrategy) -> Self {
+        let nodes = post_order(root);
+        let operations = nodes
+            .iter()
+            .filter(|node| node.borrow().operation().is_some())
+            .count();
+        let mut evaluator = Self {
+            root: root.clone(),
+            nodes,
+            backend: None,
+            compiled: None,
+            calls: 0,
+            recomputed: 0.0,
+        };
+        match strategy {
+            EvalStrategy::Interpreter => evaluator.choose(Backend::Interpreter),
+            EvalStrategy::Compiled => evaluator.choose(Backend::Compiled),
+            EvalStrategy::Auto if operations < MIN_COMPILED_OPERATIONS => {
+                evaluator.choose(Backend::Interpreter)
+            }
+            EvalStrategy::Auto => {}
+        }
+        evaluator
+    }
+
+    /// Returns chosen backend, `None` while `Auto` strategy is still measuring.
+    pub fn backend(&self) -> Option<Backend> {
+        self.backend
+    }
+
+    /// Computes root from current values of inputs.
+    pub fn compute(&mut self) -> T {
+        if self.backend.is_none() {
+            self.measure();
+        }
+        match &mut self.compiled {
+            Some(compiled) => {
+                for (idx, leaf) in &compiled.leaves {
+                    let val = leaf.borrow_mut().compute();
+                    compiled.tape.set_input(*idx, val);
+                }
+                compiled.tape.eval()
+            }
+            None => self.root.borrow_mut().compute(),
+        }
+    }
+
+    /// Accounts operations about to be recomputed, choosing backend after enough calls.
+    fn measure(&mut self) {
+        if self.calls > 0 {
+            let (dirty, total) = self
+                .nodes
+                .iter()
+                .map(|node| node.borrow())
+                .filter(|node| node.operation().is_some())
+                .fold((0, 0), |(dirty, total), node| {
+                    (dirty + node.is_dirty() as usize, total + 1)
+                });
+            self.recomputed += dirty as f64 / total.max(1) as f64;
+        }
+        self.calls += 1;
+        if self.calls > MEASURED_CALLS {
+            let share = self.recomputed / MEASURED_CALLS as f64;
+            self.choose(if share >= COMPILED_SHARE {
+                Backend::Compiled
+            } else {
+                Backend::Interpreter
+            });
+        }
+    }
+
+    fn choose(&mut self, backend: Backend) {
+        if backend == Backend::Compiled {
+            let tape = CompiledGraph::new(&self.root);
+            let leaves = self
+                .nodes
+                .iter()
+                .filter_map(|node| Some((tape.input_index(node)?, node.clone())))
+                .collect();
+            self.compiled = Some(Compiled { tape, leaves });
+        }
+        self.nodes = Vec::new();
+        self.backend = Some(backend);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::{Input, InputNode},
+        node::Node,
+    };
+
+    #[test]
+    fn auto() {
+        let x = InputNode::from_val(0.5f32);
+        let y = InputNode::from_val(1.0);
+        let mut chain: Handle = x.clone();
+        let mut wide: Handle = Node::constant(0.0);
+        for k in 0..40 {
+            chain = Node::add(Node::sin(chain), Node::constant(k as f32));
+            wide = Node::add(wide, Node::cos(Node::constant(k as f32)));
+        }
+
+        // changing x recomputes the whole chain
+        let mut eval = Evaluator::new(&chain, EvalStrategy::Auto);
+        for i in 0..=MEASURED_CALLS {
+            assert_eq!(eval.backend(), None);
+            x.borrow_mut().set(i as f32);
+            eval.compute();
+        }
+        assert_eq!(eval.backend(), Some(Backend::Compiled));
+        x.borrow_mut().set(-1.0);
+        assert_eq!(eval.compute(), chain.borrow_mut().compute());
+
+        // terms not depending on y stay cached
+        let root: Handle = Node::add(wide, Node::mul(y.clone(), Node::constant(2.0)));
+        let mut eval = Evaluator::new(&root, EvalStrategy::Auto);
+        for i in 0..=MEASURED_CALLS {
+            y.borrow_mut().set(i as f32);
+            eval.compute();
+        }
+        assert_eq!(eval.backend(), Some(Backend::Interpreter));
+
+        let small: Handle = Node::sin(x);
+        let eval = Evaluator::new(&small, EvalStrategy::Auto);
+        assert_eq!(eval.backend(), Some(Backend::Interpreter));
+        let mut eval = Evaluator::new(&small, EvalStrategy::Compiled);
+        assert_eq!(eval.backend(), Some(Backend::Compiled));
+        assert_eq!(eval.compute(), (-1.0f32).sin());
+    }
+}
@@ -0,0 +1,313 @@
+//! Randomized checking of cache invalidation.
+//!
+//! Applies random sequence of input changes, computations and structural edits to a graph,
+//! checking after each of them that every cached result matches recomputation from scratch.
+//! Meant for tests of models and of custom nodes, a failure lists actions reproducing it.
+
+use std::{collections::HashMap, error::Error, fmt};
+
+use crate::{
+    eval::Rng,
+    float::Float,
+    graph::{Graph, NAMESPACE_SEPARATOR},
+    node::{node_addr, post_order, Handle, Node},
+    ops::Operation,
+};
+
+/// Action applied to graph by stress test.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StressAction {
+    /// Input with given name was set to given value.
+    Set(String, f64),
+    /// Output with given name was computed.
+    Compute(String),
+    /// Dirty outputs were recomputed with `Graph::recompute`.
+    Recompute,
+    /// Output with given name was added, computing operation over existing nodes.
+    Edit(String),
+}
+
+/// Amounts of actions applied by successful stress test.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StressReport {
+    /// Amount of input changes.
+    pub sets: usize,
+    /// Amount of output computations, including recomputations of all dirty outputs.
+    pub computes: usize,
+    /// Amount of added outputs.
+    pub edits: usize,
+    /// Amount of cached results compared with recomputation.
+    pub checked: usize,
+}
+
+/// Cached result differing from recomputation from scratch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StressFailure {
+    /// Seed of the test.
+    pub seed: u64,
+    /// Applied actions, the last one exposing the failure.
+    pub actions: Vec<StressAction>,
+    /// Name of operation of node holding wrong result, `custom` for nodes without operation.
+    pub operation: &'static str,
+    /// Result of recomputation.
+    pub expected: f64,
+    /// Cached result.
+    pub actual: f64,
+}
+
+impl fmt::Display for StressFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "after {} actions with seed {}, {} node holds {} instead of {}",
+            self.actions.len(),
+            self.seed,
+            self.operation,
+            self.actual,
+            self.expected
+        )
+    }
+}
+
+impl Error for StressFailure {}
+
+/// Randomized test of cache invalidation, see `stress` module.
+///
+/// Edits are made to a copy of the graph, so its registered outputs stay the same,
+/// but input values are changed. Same seed applied to graph in the same state
+/// produces the same actions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StressTest {
+    seed: u64,
+    steps: usize,
+}
+
+impl StressTest {
+    /// Creates test with given seed, applying 1000 actions.
+    pub fn new(seed: u64) -> Self {
+        Self { seed, steps: 1000 }
+    }
+
+    /// Sets amount of applied actions.
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Runs test on graph, failing at the first cached result differing from recomputation.
+    /// Recomputation evaluates operations from scratch, nodes without operation are trusted
+    /// to compute correct results, and their dependents are checked only while they're cached.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn run<T: Float>(&self, graph: &Graph<T>) -> Result<StressReport, StressFailure> {
+        let mut graph = graph.clone();
+        let mut rng = Rng::new(self.seed);
+        let mut report = StressReport::default();
+        let mut actions = Vec::new();
+        for _ in 0..self.steps {
+            let action = match rng.next_u64() % 10 {
+                0..=3 => {
+                    let inputs: Vec<_> = graph.inputs().collect();
+                    if inputs.is_empty() {
+                        continue;
+                    }
+                    let (name, input) = inputs[pick(&mut rng, inputs.len())];
+                    let val = rng.next_f64() * 4.0 - 2.0;
+                    // values rejected by input rules are skipped
+                    if input.borrow_mut().try_set(T::from_f64(val)).is_err() {
+                        continue;
+                    }
+                    report.sets += 1;
+                    StressAction::Set(name.to_string(), val)
+                }
+                4..=6 => {
+                    let outputs: Vec<_> = graph.output_nodes().collect();
+                    if outputs.is_empty() {
+                        continue;
+                    }
+                    let (name, output) = outputs[pick(&mut rng, outputs.len())];
+                    output.borrow_mut().compute();
+                    report.computes += 1;
+                    StressAction::Compute(name.to_string())
+                }
+                7 => {
+                    graph.recompute();
+                    report.computes += 1;
+                    StressAction::Recompute
+                }
+                _ => {
+                    let nodes = nodes(&graph);
+                    if nodes.is_empty() {
+                        continue;
+                    }
+                    let name = format!("stress{}{}", NAMESPACE_SEPARATOR, report.edits);
+                    graph.add_output(name.clone(), edit(&mut rng, &nodes));
+                    report.edits += 1;
+                    StressAction::Edit(name)
+                }
+            };
+            actions.push(action);
+            report.checked += self.check(&graph, &actions)?;
+        }
+        Ok(report)
+    }
+
+    /// Compares cached results of nodes reachable from outputs with recomputation,
+    /// returning amount of compared results.
+    fn check<T: Float>(
+        &self,
+        graph: &Graph<T>,
+        actions: &[StressAction],
+    ) -> Result<usize, StressFailure> {
+        let mut expected: HashMap<usize, Option<T>> = HashMap::new();
+        let mut checked = 0;
+        for node in nodes(graph) {
+            let opp = node.borrow().operation().cloned();
+            let val = match opp {
+                Some(opp)
+                    if opp
+                        .operands()
+                        .iter()
+                        .all(|x| expected[&node_addr(x)].is_some()) =>
+                {
+                    Some(opp.eval(|x| expected[&node_addr(x)].unwrap()))
+                }
+                Some(_) => None,
+                // stateful custom nodes may change on computation, so only their cache is read
+                None if node.borrow().is_dirty() => None,
+                None => Some(node.borrow_mut().compute()),
+            };
+            expected.insert(node_addr(&node), val);
+            let val = match val {
+                Some(val) if !node.borrow().is_dirty() => val,
+                _ => continue,
+            };
+            checked += 1;
+            let cached = node.borrow_mut().compute();
+            if !same(cached, val) {
+                return Err(StressFailure {
+                    seed: self.seed,
+                    actions: actions.to_vec(),
+                    operation: node.borrow().operation().map_or("custom", Operation::name),
+                    expected: val.to_f64(),
+                    actual: cached.to_f64(),
+                });
+            }
+        }
+        Ok(checked)
+    }
+}
+
+/// Returns inputs and nodes reachable from outputs, operands first.
+fn nodes<T: Float>(graph: &Graph<T>) -> Vec<Handle<T>> {
+    let mut seen = std::collections::HashSet::new();
+    let inputs = graph.inputs().map(|(_, input)| input.clone() as Handle<T>);
+    let reachable = graph
+        .output_nodes()
+        .flat_map(|(_, output)| post_order(output));
+    inputs
+        .chain(reachable)
+        .filter(|node| seen.insert(node_addr(node)))
+        .collect()
+}
+
+/// Builds node computing random operation over random existing nodes.
+fn edit<T: Float>(rng: &mut Rng, nodes: &[Handle<T>]) -> Handle<T> {
+    let kind = rng.next_u64() % 6;
+    let mut any = || nodes[pick(rng, nodes.len())].clone();
+    let opp = match kind {
+        0 => Operation::Add(any(), any()),
+        1 => Operation::Sub(any(), any()),
+        2 => Operation::Mul(any(), any()),
+        3 => Operation::Max(any(), any()),
+        4 => Operation::Sin(any()),
+        _ => Operation::Select(any(), any(), any()),
+    };
+    Node::new(opp)
+}
+
+fn pick(rng: &mut Rng, len: usize) -> usize {
+    (rng.next_u64() % len as u64) as usize
+}
+
+/// Returns true if values are equal or both are `NaN`.
+fn same<T: Float>(a: T, b: T) -> bool {
+    a == b || (a.to_f64().is_nan() && b.to_f64().is_nan())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::node::Computable;
+
+    fn model() -> Graph {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0);
+        let y = g.add_input("y", 2.0);
+        let shared = Node::mul(x.clone(), y.clone());
+        g.add_output("a", Node::add(shared.clone(), Node::sin(x.clone())));
+        g.add_output(
+            "b",
+            Node::lazy_select(Node::gt(x, y.clone()), shared, Node::exp(y)),
+        );
+        g
+    }
+
+    #[test]
+    fn coherent() {
+        let g = model();
+        let report = StressTest::new(7).steps(300).run(&g).unwrap();
+        assert!(report.sets > 0 && report.computes > 0 && report.edits > 0);
+        assert!(report.checked > 0);
+        // edits aren't registered in tested graph
+        assert_eq!(g.output_nodes().count(), 2);
+        assert_eq!(StressTest::new(7).steps(300).run(&g), Ok(report));
+    }
+
+    /// Node caching sine of its operand without registering as its dependency.
+    struct Stale {
+        opp: Operation,
+        cache: Option<f32>,
+    }
+
+    impl Computable for Stale {
+        fn compute(&mut self) -> f32 {
+            let opp = &self.opp;
+            *self
+                .cache
+                .get_or_insert_with(|| opp.eval(|x| x.borrow_mut().compute()))
+        }
+
+        fn add_dependency(&mut self, _dependency: Handle) {}
+
+        fn reset_cache(&mut self) {
+            self.cache = None;
+        }
+
+        fn operation(&self) -> Option<&Operation> {
+            Some(&self.opp)
+        }
+
+        fn is_dirty(&self) -> bool {
+            self.cache.is_none()
+        }
+    }
+
+    #[test]
+    fn detects_stale_cache() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 1.0);
+        let stale = Rc::new(RefCell::new(Stale {
+            opp: Operation::Sin(x),
+            cache: None,
+        }));
+        g.add_output("stale", stale);
+        let failure = StressTest::new(1).run(&g).unwrap_err();
+        assert_eq!(failure.operation, "sin");
+        assert!(matches!(
+            failure.actions.last(),
+            Some(StressAction::Set(..))
+        ));
+    }
+}
@@ -0,0 +1,224 @@
+//! Nodes encapsulating whole graphs behind their declared inputs and outputs.
+//!
+//! Inner graph stays usable, and testable, on its own. Outer operands are bound
+//! to inner inputs, which are set from them on recomputation, so inner caches
+//! are kept while bound values don't change. Several nodes of the same inner graph,
+//! one per used output, share its computations. DOT export shows the node collapsed.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    error::GraphError,
+    eval::Evaluation,
+    float::Float,
+    graph::Graph,
+    node::{
+        dependents, remove_dependency, reset_dependencies, Computable, Dependencies, Handle, Kind,
+    },
+    ops::DomainError,
+};
+
+/// Node computing output of inner graph from inputs bound to outer operands.
+pub struct SubgraphNode<T: Float = f32> {
+    name: String,
+    graph: Graph<T>,
+    /// Outer operands by names of inner inputs they are bound to.
+    bindings: Vec<(String, Handle<T>)>,
+    output: Handle<T>,
+    /// Cached result.
+    cache: Option<T>,
+    /// Holds references to nodes that depend from this node.
+    dependencies: Dependencies<T>,
+}
+
+impl<T: Float> SubgraphNode<T> {
+    /// Builds node named `name` computing given output of graph, with inner inputs
+    /// bound to outer operands by name. Unbound inputs keep their inner values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if bound input or output isn't registered in graph.
+    pub fn new(
+        name: impl Into<String>,
+        graph: &Graph<T>,
+        bindings: &[(&str, Handle<T>)],
+        output: &str,
+    ) -> Rc<RefCell<Self>> {
+        for (input, _) in bindings {
+            assert!(
+                graph.input(input).is_some(),
+                "input {} isn't registered",
+                input
+            );
+        }
+        let node = graph
+            .output(output)
+            .unwrap_or_else(|| panic!("output {} isn't registered", output))
+            .clone();
+        let obj = Rc::new(RefCell::new(Self {
+            name: name.into(),
+            graph: graph.clone(),
+            bindings: bindings
+                .iter()
+                .map(|(input, x)| (input.to_string(), x.clone()))
+                .collect(),
+            output: node,
+            cache: None,
+            dependencies: Dependencies::default(),
+        }));
+        for (_, x) in bindings {
+            x.borrow_mut().add_dependency(obj.clone());
+        }
+        obj
+    }
+
+    /// Returns inner graph, sharing nodes with this one.
+    pub fn graph(&self) -> &Graph<T> {
+        &self.graph
+    }
+
+    /// Sets inner inputs to given values of bound operands.
+    fn bind(&self, values: &[T]) -> Result<(), GraphError> {
+        let inputs: Vec<(&str, T)> = self
+            .bindings
+            .iter()
+            .zip(values)
+            .map(|((input, _), val)| (input.as_str(), *val))
+            .collect();
+        self.graph.replace_many(&inputs).map_err(|err| match err {
+            GraphError::Validation(err) => GraphError::Domain(DomainError {
+                operation: "subgraph",
+                value: err.value,
+            }),
+            err => err,
+        })
+    }
+}
+
+impl<T: Float> Computable<T> for SubgraphNode<T> {
+    /// Computes output of inner graph, `NaN` if bound value violates rules of its input.
+    fn compute(&mut self) -> T {
+        if let Some(val) = self.cache {
+            return val;
+        }
+        let values: Vec<T> = self
+            .bindings
+            .iter()
+            .map(|(_, x)| x.borrow_mut().compute())
+            .collect();
+        if self.bind(&values).is_err() {
+            return T::from_f64(f64::NAN);
+        }
+        let val = self.output.borrow_mut().compute();
+        self.cache = Some(val);
+        val
+    }
+
+    fn try_compute(&mut self, eval: &mut Evaluation) -> Result<T, GraphError> {
+        if let Some(val) = self.cache {
+            return Ok(val);
+        }
+        let values = self
+            .bindings
+            .iter()
+            .map(|(_, x)| x.borrow_mut().try_compute(eval))
+            .collect::<Result<Vec<T>, _>>()?;
+        self.bind(&values)?;
+        let val = self.output.borrow_mut().try_compute(eval)?;
+        self.cache = Some(val);
+        Ok(val)
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependencies.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Handle<T>> {
+        dependents(&self.dependencies)
+    }
+
+    fn reset_cache(&mut self) {
+        if self.cache.take().is_none() {
+            return;
+        }
+        reset_dependencies(&mut self.dependencies);
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.cache.is_none()
+    }
+
+    fn operands(&self) -> Vec<Handle<T>> {
+        self.bindings.iter().map(|(_, x)| x.clone()).collect()
+    }
+
+    fn kind(&self) -> Kind {
+        self.output.borrow().kind()
+    }
+
+    fn label(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        dot::to_dot,
+        input::{Input, Rule},
+        node::Node,
+    };
+
+    /// Graph computing `rate * notional` and `rate + 1`.
+    fn interest() -> Graph {
+        let mut g = Graph::new();
+        let rate = g.add_input("rate", 0.0);
+        let notional = g.add_input("notional", 100.0);
+        rate.borrow_mut().add_rule(Rule::Range(-1.0, 1.0));
+        g.add_output("interest", Node::mul(rate.clone(), notional));
+        g.add_output("factor", Node::add(rate, Node::constant(1.0)));
+        g
+    }
+
+    #[test]
+    fn encapsulates() {
+        let inner = interest();
+        assert_eq!(inner.outputs(), vec![("interest", 0.0), ("factor", 1.0)]);
+
+        let x = Node::input(0.5f32);
+        let rate = Node::mul(x.clone(), Node::constant(0.1));
+        let interest = SubgraphNode::new("interest", &inner, &[("rate", rate.clone())], "interest");
+        let factor = SubgraphNode::new("factor", &inner, &[("rate", rate)], "factor");
+        let root: Handle = Node::add(interest, factor);
+        assert_eq!(root.borrow_mut().compute(), 6.05);
+        let dot = to_dot(&root, &HashMap::new());
+        assert!(dot.contains("[label=\"interest = 5\", shape=box3d]"));
+
+        x.borrow_mut().set(1.0);
+        assert_eq!(root.borrow_mut().compute(), 11.1);
+        assert_eq!(inner.input("rate").unwrap().borrow().value(), 0.1);
+
+        x.borrow_mut().set(20.0);
+        assert!(root.borrow_mut().compute().is_nan());
+        assert!(matches!(
+            root.borrow_mut().try_compute(&mut Evaluation::new()),
+            Err(GraphError::Domain(DomainError {
+                operation: "subgraph",
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "output total isn't registered")]
+    fn unknown_output() {
+        SubgraphNode::new("total", &interest(), &[], "total");
+    }
+}
@@ -0,0 +1,964 @@
+//! Exchange of expressions with SymPy using its `srepr` format.
+//!
+//! Exported strings can be evaluated in Python with `from sympy import *`,
+//! and output of `sympy.srepr()` for supported expressions can be imported back.
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+    input::InputNode,
+    node::{node_addr, Computable, Node},
+    ops::{Operation, Reduction, Rounding},
+    parse::DEFAULT_MAX_DEPTH,
+};
+
+/// Error produced by SymPy conversion.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SympyError {
+    /// Node is neither a named input nor an operation node.
+    UnknownNode,
+    /// Expression isn't valid `srepr` output.
+    Syntax { position: usize, message: String },
+    /// Expression has no counterpart among graph operations.
+    Unsupported(String),
+    /// Expression is nested deeper than given limit.
+    TooDeep(usize),
+}
+
+impl fmt::Display for SympyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SympyError::UnknownNode => write!(f, "node is neither a named input nor an operation"),
+            SympyError::Syntax { position, message } => {
+                write!(f, "{} at position {}", message, position)
+            }
+            SympyError::Unsupported(what) => write!(f, "unsupported {}", what),
+            SympyError::TooDeep(limit) => write!(f, "expression nested deeper than {}", limit),
+        }
+    }
+}
+
+impl std::error::Error for SympyError {}
+
+/// Exports graph with given root as SymPy `srepr` string.
+/// `names` maps symbol names to input nodes that appear in the graph.
+pub fn to_sympy_srepr<C: Computable + ?Sized>(
+    root: &Rc<RefCell<C>>,
+    names: &HashMap<String, Rc<RefCell<InputNode>>>,
+) -> Result<String, SympyError> {
+    let names: HashMap<usize, &str> = names
+        .iter()
+        .map(|(name, input)| (node_addr(input), name.as_str()))
+        .collect();
+    if let Some(name) = names.get(&node_addr(root)) {
+        return Ok(symbol(name));
+    }
+    let opp = root
+        .borrow()
+        .operation()
+        .cloned()
+        .ok_or(SympyError::UnknownNode)?;
+    write_operation(&opp, &names)
+}
+
+fn symbol(name: &str) -> String {
+    format!(
+        "Symbol('{}')",
+        name.replace('\\', "\\\\").replace('\'', "\\'")
+    )
+}
+
+fn number(val: f32) -> String {
+    if val.fract() == 0.0 && val.abs() < 1e7 {
+        format!("Integer({})", val as i64)
+    } else {
+        format!("Float('{}', precision=53)", val)
+    }
+}
+
+fn write_node(
+    node: &Rc<RefCell<dyn Computable>>,
+    names: &HashMap<usize, &str>,
+) -> Result<String, SympyError> {
+    if let Some(name) = names.get(&node_addr(node)) {
+        return Ok(symbol(name));
+    }
+    let opp = node
+        .borrow()
+        .operation()
+        .cloned()
+        .ok_or(SympyError::UnknownNode)?;
+    write_operation(&opp, names)
+}
+
+fn write_operation(opp: &Operation, names: &HashMap<usize, &str>) -> Result<String, SympyError> {
+    use Operation::*;
+
+    let args = opp
+        .operands()
+        .into_iter()
+        .map(|x| write_node(x, names))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(match opp {
+        Const(val) => number(*val),
+        Add(..) | AddVar(_) | Reduce(_, Reduction::Sum) => format!("Add({})", args.join(", ")),
+        Sub(..) => format!("Add({}, Mul(Integer(-1), {}))", args[0], args[1]),
+        Mul(..) | MulVar(_) | Reduce(_, Reduction::Prod) => format!("Mul({})", args.join(", ")),
+        Div(..) => format!("Mul({}, Pow({}, Integer(-1)))", args[0], args[1]),
+        Neg(_) => format!("Mul(Integer(-1), {})", args[0]),
+        Recip(_) => format!("Pow({}, Integer(-1))", args[0]),
+        // gradients have no notation, value is the same as of operand
+        Detach(_) => args[0].clone(),
+        Pow(_, pow) => format!("Pow({}, {})", args[0], number(*pow)),
+        Sin(_) => format!("sin({})", args[0]),
+        Asin(_) => format!("asin({})", args[0]),
+        Acos(_) => format!("acos({})", args[0]),
+        Atan(_) => format!("atan({})", args[0]),
+        Cos(_) => format!("cos({})", args[0]),
+        Tan(_) => format!("tan({})", args[0]),
+        Exp(_) => format!("exp({})", args[0]),
+        Ln(_) => format!("log({})", args[0]),
+        Log(_, base) => format!(
+            "Mul(log({}), Pow(log({}), Integer(-1)))",
+            args[0],
+            number(*base)
+        ),
+        Sqrt(_) => format!("Pow({}, Rational(1, 2))", args[0]),
+        Abs(_) => format!("Abs({})", args[0]),
+        Sign(_) => format!("sign({})", args[0]),
+        // `frac` subtracts floor, fractional part keeps sign of value instead
+        Fract(_) => format!(
+            "Add({}, Mul(Integer(-1), sign({}), floor(Abs({}))))",
+            args[0], args[0], args[0]
+        ),
+        Tanh(_) => format!("tanh({})", args[0]),
+        Lt(..) => format!("StrictLessThan({}, {})", args[0], args[1]),
+        Le(..) => format!("LessThan({}, {})", args[0], args[1]),
+        Gt(..) => format!("StrictGreaterThan({}, {})", args[0], args[1]),
+        Ge(..) => format!("GreaterThan({}, {})", args[0], args[1]),
+        Eq(_, _, tolerance) if *tolerance == 0.0 => format!("Equality({}, {})", args[0], args[1]),
+        Eq(_, _, tolerance) => format!(
+            "LessThan(Abs(Add({}, Mul(Integer(-1), {}))), {})",
+            args[0],
+            args[1],
+            number(*tolerance)
+        ),
+        Min(..) | Reduce(_, Reduction::Min) => format!("Min({})", args.join(", ")),
+        Max(..) | Reduce(_, Reduction::Max) => format!("Max({})", args.join(", ")),
+        Reduce(_, Reduction::Mean) => format!(
+            "Mul(Rational(1, {}), Add({}))",
+            args.len(),
+            args.join(", ")
+        ),
+        Clamp(..) => format!("Min(Max({}, {}), {})", args[0], args[1], args[2]),
+        Sigmoid(_) => format!(
+            "Pow(Add(Integer(1), exp(Mul(Integer(-1), {}))), Integer(-1))",
+            args[0]
+        ),
+        Relu(_) => format!("Max({}, Integer(0))", args[0]),
+        Softplus(_) => format!("log(Add(Integer(1), exp({})))", args[0]),
+        ExpM1(_) => format!("expm1({})", args[0]),
+        Ln1p(_) => format!("log1p({})", args[0]),
+        Atan2(..) => format!("atan2({}, {})", args[0], args[1]),
+        // `Mod` takes sign of divisor, remainder truncates quotient instead
+        Rem(..) => {
+            let quotient = format!("Mul({}, Pow({}, Integer(-1)))", args[0], args[1]);
+            format!(
+                "Add({}, Mul(Integer(-1), {}, sign({}), floor(Abs({}))))",
+                args[0], args[1], quotient, quotient
+            )
+        }
+        Erf(_) => format!("erf({})", args[0]),
+        Erfc(_) => format!("erfc({})", args[0]),
+        NormalCdf(_) => format!(
+            "Mul(Rational(1, 2), erfc(Mul(Integer(-1), Pow(Integer(2), Rational(-1, 2)), {})))",
+            args[0]
+        ),
+        NormalPdf(_) => format!(
+            "Mul(Pow(Mul(Integer(2), pi), Rational(-1, 2)), exp(Mul(Rational(-1, 2), Pow({}, Integer(2)))))",
+            args[0]
+        ),
+        Custom(op, _) => format!(
+            "Function('{}')({})",
+            op.0.name().replace('\\', "\\\\").replace('\'', "\\'"),
+            args.join(", ")
+        ),
+        Polynomial(_, coeffs) => {
+            let terms: Vec<String> = coeffs
+                .iter()
+                .enumerate()
+                .map(|(i, c)| match i {
+                    0 => number(*c),
+                    1 => format!("Mul({}, {})", number(*c), args[0]),
+                    _ => format!("Mul({}, Pow({}, Integer({})))", number(*c), args[0], i),
+                })
+                .collect();
+            format!("Add({})", terms.join(", "))
+        }
+        Quantize(_, step, rounding) => {
+            let rounding = match rounding {
+                Rounding::Floor => "floor".to_string(),
+                Rounding::Ceiling => "ceiling".to_string(),
+                rounding => format!("Function('round_{}')", rounding.name()),
+            };
+            format!(
+                "Mul({}, {}(Mul({}, Pow({}, Integer(-1)))))",
+                number(*step),
+                rounding,
+                args[0],
+                number(*step)
+            )
+        }
+        Select(..) => format!(
+            "Piecewise(ExprCondPair({}, StrictGreaterThan({}, Integer(0))), ExprCondPair({}, true))",
+            args[1], args[0], args[2]
+        ),
+        Piecewise(_, pieces, _) => {
+            let mut pairs: Vec<_> = pieces
+                .iter()
+                .zip(&args[1..])
+                .map(|((breakpoint, _), piece)| {
+                    format!(
+                        "ExprCondPair({}, StrictLessThan({}, {}))",
+                        piece,
+                        args[0],
+                        number(*breakpoint)
+                    )
+                })
+                .collect();
+            pairs.push(format!("ExprCondPair({}, true)", args[args.len() - 1]));
+            format!("Piecewise({})", pairs.join(", "))
+        }
+        LogSumExp(_) => {
+            let exps: Vec<_> = args.iter().map(|x| format!("exp({})", x)).collect();
+            format!("log(Add({}))", exps.join(", "))
+        }
+        Softmax(_, i) => {
+            let exps: Vec<_> = args.iter().map(|x| format!("exp({})", x)).collect();
+            format!(
+                "Mul({}, Pow(Add({}), Integer(-1)))",
+                exps[*i],
+                exps.join(", ")
+            )
+        }
+        Hypot(..) | Norm(_) => {
+            let squares: Vec<_> = args
+                .iter()
+                .map(|x| format!("Pow({}, Integer(2))", x))
+                .collect();
+            format!("Pow(Add({}), Rational(1, 2))", squares.join(", "))
+        }
+    })
+}
+
+/// Token of `srepr` string.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f32),
+    Str(String),
+    Punct(char),
+}
+
+fn tokenize(src: &str) -> Result<Vec<(usize, Token)>, SympyError> {
+    let syntax = |position, message: &str| SympyError::Syntax {
+        position,
+        message: message.into(),
+    };
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                ident.push(c);
+                chars.next();
+            }
+            tokens.push((pos, Token::Ident(ident)));
+        } else if c.is_ascii_digit() || c == '-' || c == '.' {
+            let mut end = pos;
+            while let Some(&(i, c)) = chars.peek() {
+                let sign = (c == '-' || c == '+') && (i == pos || src[..i].ends_with(['e', 'E']));
+                if !(c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || sign) {
+                    break;
+                }
+                end = i + 1;
+                chars.next();
+            }
+            let num = src[pos..end]
+                .parse()
+                .map_err(|_| syntax(pos, "invalid number"))?;
+            tokens.push((pos, Token::Number(num)));
+        } else if c == '\'' || c == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '\\')) => {
+                        if let Some((_, c)) = chars.next() {
+                            text.push(c);
+                        }
+                    }
+                    Some((_, q)) if q == c => break,
+                    Some((_, c)) => text.push(c),
+                    None => return Err(syntax(pos, "unterminated string")),
+                }
+            }
+            tokens.push((pos, Token::Str(text)));
+        } else if "(),=".contains(c) {
+            tokens.push((pos, Token::Punct(c)));
+            chars.next();
+        } else {
+            return Err(syntax(pos, &format!("unexpected character '{}'", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parsed `srepr` term, literals are kept apart from nodes.
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Number(f32),
+    Str(String),
+    Call {
+        name: String,
+        args: Vec<Term>,
+        position: usize,
+    },
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+    /// Amount of calls whose arguments are being parsed.
+    nesting: usize,
+    max_depth: usize,
+}
+
+impl Parser {
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(p, _)| *p)
+    }
+
+    fn error(&self, message: &str) -> SympyError {
+        SympyError::Syntax {
+            position: self.position(),
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// term := number | string | ident ('(' (arg (',' arg)*)? ')')?
+    fn term(&mut self) -> Result<Term, SympyError> {
+        let position = self.position();
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error("unexpected end"))?;
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Term::Number(n)),
+            Token::Str(s) => Ok(Term::Str(s)),
+            Token::Ident(name) => {
+                let mut args = Vec::new();
+                if !self.eat('(') {
+                    return Ok(Term::Call {
+                        name,
+                        args,
+                        position,
+                    });
+                }
+                // each call builds at most one level of graph, so limiting nesting of calls
+                // bounds both parser recursion and depth of graph
+                self.nesting += 1;
+                if self.nesting > self.max_depth {
+                    return Err(SympyError::TooDeep(self.max_depth));
+                }
+                if !self.eat(')') {
+                    loop {
+                        // keyword arguments such as `precision=53` carry no value information
+                        let keyword = matches!(
+                            (self.peek(), self.tokens.get(self.pos + 1)),
+                            (Some(Token::Ident(_)), Some((_, Token::Punct('='))))
+                        );
+                        if keyword {
+                            self.pos += 2;
+                            self.term()?;
+                        } else {
+                            args.push(self.term()?);
+                        }
+                        if self.eat(')') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return Err(self.error("expected ',' or ')'"));
+                        }
+                    }
+                }
+                self.nesting -= 1;
+                Ok(Term::Call {
+                    name,
+                    args,
+                    position,
+                })
+            }
+            Token::Punct(c) => Err(SympyError::Syntax {
+                position,
+                message: format!("unexpected '{}'", c),
+            }),
+        }
+    }
+}
+
+/// Built value, literals are kept apart from nodes so they can serve as exponents.
+enum Value {
+    Number(f32),
+    Node(Rc<RefCell<dyn Computable>>),
+}
+
+impl Value {
+    fn into_node(self) -> Rc<RefCell<dyn Computable>> {
+        match self {
+            Value::Number(n) => Node::constant(n),
+            Value::Node(node) => node,
+        }
+    }
+}
+
+/// Imports expression printed by `sympy.srepr()`, returning graph root and inputs created for symbols.
+/// Input nodes are created with value `0.0`.
+/// Expressions nesting calls deeper than `DEFAULT_MAX_DEPTH` are rejected.
+#[allow(clippy::type_complexity)]
+pub fn from_sympy_srepr(
+    src: &str,
+) -> Result<
+    (
+        Rc<RefCell<dyn Computable>>,
+        HashMap<String, Rc<RefCell<InputNode>>>,
+    ),
+    SympyError,
+> {
+    from_sympy_srepr_with_limit(src, DEFAULT_MAX_DEPTH)
+}
+
+/// Imports expression like `from_sympy_srepr`, rejecting expressions nesting calls deeper
+/// than `max_depth`, so untrusted expressions can't overflow the stack.
+#[allow(clippy::type_complexity)]
+pub fn from_sympy_srepr_with_limit(
+    src: &str,
+    max_depth: usize,
+) -> Result<
+    (
+        Rc<RefCell<dyn Computable>>,
+        HashMap<String, Rc<RefCell<InputNode>>>,
+    ),
+    SympyError,
+> {
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        pos: 0,
+        end: src.len(),
+        nesting: 0,
+        max_depth,
+    };
+    let term = parser.term()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(parser.error("unexpected trailing input"));
+    }
+    let mut inputs = HashMap::new();
+    let root = build(&term, &mut inputs)?.into_node();
+    Ok((root, inputs))
+}
+
+/// Returns subtrahend if term has form `Mul(Integer(-1), x)`.
+fn negated(term: &Term) -> Option<&Term> {
+    match term {
+        Term::Call { name, args, .. } if name == "Mul" && args.len() == 2 => match &args[0] {
+            Term::Call { name, args: n, .. } if name == "Integer" && n == &[Term::Number(-1.0)] => {
+                Some(&args[1])
+            }
+            Term::Call { name, .. } if name == "NegativeOne" => Some(&args[1]),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn build(
+    term: &Term,
+    inputs: &mut HashMap<String, Rc<RefCell<InputNode>>>,
+) -> Result<Value, SympyError> {
+    let (name, args, position) = match term {
+        Term::Number(n) => return Ok(Value::Number(*n)),
+        Term::Str(s) => return Err(SympyError::Unsupported(format!("string '{}'", s))),
+        Term::Call {
+            name,
+            args,
+            position,
+        } => (name.as_str(), args.as_slice(), *position),
+    };
+    let arity_error = || SympyError::Syntax {
+        position,
+        message: format!("wrong amount of arguments for {}", name),
+    };
+    match (name, args) {
+        ("Symbol", [Term::Str(s)]) => Ok(Value::Node(
+            inputs
+                .entry(s.clone())
+                .or_insert_with(|| InputNode::with_label(0.0, s.clone()))
+                .clone(),
+        )),
+        ("Integer", [Term::Number(n)]) => Ok(Value::Number(*n)),
+        ("Float", [Term::Str(s)]) => s
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| SympyError::Syntax {
+                position,
+                message: format!("invalid float '{}'", s),
+            }),
+        ("Rational", [Term::Number(p), Term::Number(q)]) => Ok(Value::Number(p / q)),
+        ("Zero", []) => Ok(Value::Number(0.0)),
+        ("One", []) => Ok(Value::Number(1.0)),
+        ("NegativeOne", []) => Ok(Value::Number(-1.0)),
+        ("Half", []) => Ok(Value::Number(0.5)),
+        ("pi", []) => Ok(Value::Number(std::f32::consts::PI)),
+        ("E", []) => Ok(Value::Number(std::f32::consts::E)),
+        ("Add", [x, y]) if negated(y).is_some() => {
+            let x = build(x, inputs)?.into_node();
+            let y = build(negated(y).unwrap(), inputs)?.into_node();
+            Ok(Value::Node(Node::sub(x, y)))
+        }
+        ("Add" | "Mul", []) => Err(arity_error()),
+        ("Add" | "Mul", [x]) => build(x, inputs),
+        ("Mul", [_, _]) if negated(term).is_some() => {
+            match build(negated(term).unwrap(), inputs)? {
+                Value::Number(n) => Ok(Value::Number(-n)),
+                Value::Node(x) => Ok(Value::Node(Node::neg(x))),
+            }
+        }
+        ("Add", [x, y]) => {
+            let x = build(x, inputs)?.into_node();
+            let y = build(y, inputs)?.into_node();
+            Ok(Value::Node(Node::add(x, y)))
+        }
+        ("Add", args) => Ok(Value::Node(Node::add_var(
+            args.iter()
+                .map(|arg| build(arg, inputs).map(Value::into_node))
+                .collect::<Result<_, _>>()?,
+        ))),
+        ("Mul", [x, y]) => {
+            let x = build(x, inputs)?.into_node();
+            let y = build(y, inputs)?.into_node();
+            Ok(Value::Node(Node::mul(x, y)))
+        }
+        ("Mul", args) => Ok(Value::Node(Node::mul_var(
+            args.iter()
+                .map(|arg| build(arg, inputs).map(Value::into_node))
+                .collect::<Result<_, _>>()?,
+        ))),
+        ("Pow", [x, pow]) => match (build(x, inputs)?, build(pow, inputs)?) {
+            (Value::Node(x), Value::Number(pow)) if pow == -1.0 => Ok(Value::Node(Node::recip(x))),
+            (x, Value::Number(pow)) => Ok(Value::Node(Node::pow(x.into_node(), pow))),
+            (_, Value::Node(_)) => Err(SympyError::Unsupported("non-constant exponent".into())),
+        },
+        ("sin", [x]) => Ok(Value::Node(Node::sin(build(x, inputs)?.into_node()))),
+        ("asin", [x]) => Ok(Value::Node(Node::asin(build(x, inputs)?.into_node()))),
+        ("acos", [x]) => Ok(Value::Node(Node::acos(build(x, inputs)?.into_node()))),
+        ("atan", [x]) => Ok(Value::Node(Node::atan(build(x, inputs)?.into_node()))),
+        ("expm1", [x]) => Ok(Value::Node(Node::expm1(build(x, inputs)?.into_node()))),
+        ("log1p", [x]) => Ok(Value::Node(Node::ln_1p(build(x, inputs)?.into_node()))),
+        ("erf", [x]) => Ok(Value::Node(Node::erf(build(x, inputs)?.into_node()))),
+        ("erfc", [x]) => Ok(Value::Node(Node::erfc(build(x, inputs)?.into_node()))),
+        ("cos", [x]) => Ok(Value::Node(Node::cos(build(x, inputs)?.into_node()))),
+        ("tan", [x]) => Ok(Value::Node(Node::tan(build(x, inputs)?.into_node()))),
+        ("exp", [x]) => Ok(Value::Node(Node::exp(build(x, inputs)?.into_node()))),
+        ("log", [x]) => Ok(Value::Node(Node::ln(build(x, inputs)?.into_node()))),
+        ("log", [x, base]) => match (build(x, inputs)?, build(base, inputs)?) {
+            (x, Value::Number(base)) => Ok(Value::Node(Node::log(x.into_node(), base))),
+            (_, Value::Node(_)) => Err(SympyError::Unsupported("non-constant base".into())),
+        },
+        ("atan2", [y, x]) => {
+            let (y, x) = (build(y, inputs)?.into_node(), build(x, inputs)?.into_node());
+            Ok(Value::Node(Node::atan2(y, x)))
+        }
+        ("Abs", [x]) => Ok(Value::Node(Node::abs(build(x, inputs)?.into_node()))),
+        ("sign", [x]) => Ok(Value::Node(Node::sign(build(x, inputs)?.into_node()))),
+        ("tanh", [x]) => Ok(Value::Node(Node::tanh(build(x, inputs)?.into_node()))),
+        (
+            "StrictLessThan" | "LessThan" | "StrictGreaterThan" | "GreaterThan" | "Equality",
+            [x, y],
+        ) => {
+            let (x, y) = (build(x, inputs)?.into_node(), build(y, inputs)?.into_node());
+            Ok(Value::Node(match name {
+                "StrictLessThan" => Node::lt(x, y),
+                "LessThan" => Node::le(x, y),
+                "StrictGreaterThan" => Node::gt(x, y),
+                "GreaterThan" => Node::ge(x, y),
+                _ => Node::eq(x, y, 0.0),
+            }))
+        }
+        ("Min" | "Max", [x, y]) => {
+            let (x, y) = (build(x, inputs)?.into_node(), build(y, inputs)?.into_node());
+            let combine = if name == "Min" { Node::min } else { Node::max };
+            Ok(Value::Node(combine(x, y)))
+        }
+        ("Min" | "Max", args) if args.len() > 2 => {
+            let reduction = if name == "Min" {
+                Reduction::Min
+            } else {
+                Reduction::Max
+            };
+            let args = args
+                .iter()
+                .map(|arg| build(arg, inputs).map(Value::into_node))
+                .collect::<Result<_, _>>()?;
+            Ok(Value::Node(Node::reduce(args, reduction)))
+        }
+        ("Symbol" | "Integer" | "Float" | "Rational" | "Pow", _) => Err(arity_error()),
+        ("sin" | "asin" | "acos" | "atan" | "expm1" | "log1p", _) => Err(arity_error()),
+        ("erf" | "erfc" | "cos" | "tan" | "exp" | "log" | "Abs" | "sign" | "tanh", _)
+        | ("Min" | "Max" | "atan2", _)
+        | ("StrictLessThan" | "LessThan" | "StrictGreaterThan" | "GreaterThan" | "Equality", _) => {
+            Err(arity_error())
+        }
+        ("Zero" | "One" | "NegativeOne" | "Half" | "pi" | "E", _) => Err(arity_error()),
+        (name, _) => Err(SympyError::Unsupported(format!("function {}", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    #[test]
+    fn export() {
+        let x = InputNode::from_val(1.0);
+        let y = InputNode::from_val(2.0);
+        let root = Node::sub(
+            Node::add_var(vec![x.clone(), y.clone(), Node::constant(1.5)]),
+            Node::mul(Node::constant(2.0), Node::pow(Node::sin(x.clone()), 3.0)),
+        );
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        assert_eq!(
+            to_sympy_srepr(&root, &names).unwrap(),
+            "Add(Add(Symbol('x'), Symbol('y'), Float('1.5', precision=53)), \
+             Mul(Integer(-1), Mul(Integer(2), Pow(sin(Symbol('x')), Integer(3)))))"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::sub(Node::pow(x.clone(), 0.5), Node::sin(x.clone()));
+        let names = HashMap::from([("x".to_string(), x)]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+
+        let (imported, inputs) = from_sympy_srepr(&srepr).unwrap();
+        assert_eq!(to_sympy_srepr(&imported, &inputs).unwrap(), srepr);
+        inputs["x"].borrow_mut().set(4.0);
+        assert_eq!(imported.borrow_mut().compute(), 2.0 - 4f32.sin());
+    }
+
+    #[test]
+    fn inverse_trig_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::mul(
+            Node::asin(x.clone()),
+            Node::add(Node::acos(x.clone()), Node::atan(x.clone())),
+        );
+        let names = HashMap::from([("x".to_string(), x)]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+        assert_eq!(
+            srepr,
+            "Mul(asin(Symbol('x')), Add(acos(Symbol('x')), atan(Symbol('x'))))"
+        );
+
+        let (imported, inputs) = from_sympy_srepr(&srepr).unwrap();
+        assert_eq!(to_sympy_srepr(&imported, &inputs).unwrap(), srepr);
+    }
+
+    #[test]
+    fn elementary_functions() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::add_var(vec![
+            Node::mul(Node::cos(x.clone()), Node::tan(x.clone())),
+            Node::exp(Node::abs(x.clone())),
+            Node::ln(x.clone()),
+        ]);
+        let names = HashMap::from([("x".to_string(), x.clone())]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+        assert_eq!(
+            srepr,
+            "Add(Mul(cos(Symbol('x')), tan(Symbol('x'))), exp(Abs(Symbol('x'))), log(Symbol('x')))"
+        );
+        let (imported, inputs) = from_sympy_srepr(&srepr).unwrap();
+        assert_eq!(to_sympy_srepr(&imported, &inputs).unwrap(), srepr);
+
+        let root = Node::add(Node::sqrt(x.clone()), Node::log(x.clone(), 2.0));
+        let names = HashMap::from([("x".to_string(), x)]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+        assert!(srepr.starts_with("Add(Pow(Symbol('x'), Rational(1, 2)), Mul(log(Symbol('x'))"));
+        let (imported, inputs) = from_sympy_srepr(&srepr).unwrap();
+        inputs["x"].borrow_mut().set(4.0);
+        assert_eq!(imported.borrow_mut().compute(), 4.0);
+
+        let (imported, _) = from_sympy_srepr("log(Integer(8), Integer(2))").unwrap();
+        assert_eq!(imported.borrow_mut().compute(), 3.0);
+    }
+
+    #[test]
+    fn export_activations() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::add_var(vec![
+            Node::tanh(x.clone()),
+            Node::sigmoid(x.clone()),
+            Node::relu(x.clone()),
+            Node::softplus(x.clone()),
+        ]);
+        let names = HashMap::from([("x".to_string(), x)]);
+        assert_eq!(
+            to_sympy_srepr(&root, &names).unwrap(),
+            "Add(tanh(Symbol('x')), ".to_string()
+                + "Pow(Add(Integer(1), exp(Mul(Integer(-1), Symbol('x')))), Integer(-1)), "
+                + "Max(Symbol('x'), Integer(0)), log(Add(Integer(1), exp(Symbol('x')))))"
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let root = Node::add_var(vec![
+            Node::lt(x.clone(), y.clone()),
+            Node::le(x.clone(), y.clone()),
+            Node::gt(x.clone(), y.clone()),
+            Node::ge(x.clone(), y.clone()),
+            Node::eq(x.clone(), y.clone(), 0.0),
+        ]);
+        let names = HashMap::from([("x".to_string(), x.clone()), ("y".to_string(), y.clone())]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+        assert!(srepr.contains("StrictLessThan(Symbol('x'), Symbol('y'))"));
+        assert!(srepr.contains("Equality(Symbol('x'), Symbol('y'))"));
+        let (imported, inputs) = from_sympy_srepr(&srepr).unwrap();
+        assert_eq!(to_sympy_srepr(&imported, &inputs).unwrap(), srepr);
+
+        let root = Node::eq(x, y, 0.5);
+        assert_eq!(
+            to_sympy_srepr(&root, &names).unwrap(),
+            "LessThan(Abs(Add(Symbol('x'), Mul(Integer(-1), Symbol('y')))), Float('0.5', precision=53))"
+        );
+    }
+
+    #[test]
+    fn min_max_clamp() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let root = Node::add(
+            Node::clamp(x.clone(), Node::min(x.clone(), y.clone()), y.clone()),
+            Node::max(x.clone(), Node::constant(1.0)),
+        );
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+        assert_eq!(
+            srepr,
+            "Add(Min(Max(Symbol('x'), Min(Symbol('x'), Symbol('y'))), Symbol('y')), ".to_string()
+                + "Max(Symbol('x'), Integer(1)))"
+        );
+
+        let (imported, inputs) =
+            from_sympy_srepr("Min(Symbol('x'), Integer(3), Symbol('y'))").unwrap();
+        inputs["x"].borrow_mut().set(5.0);
+        inputs["y"].borrow_mut().set(4.0);
+        assert_eq!(imported.borrow_mut().compute(), 3.0);
+        assert!(from_sympy_srepr("Max(Symbol('x'))").is_err());
+    }
+
+    #[test]
+    fn neg_recip() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::neg(Node::recip(x.clone()));
+        let names = HashMap::from([("x".to_string(), x)]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+        assert_eq!(srepr, "Mul(Integer(-1), Pow(Symbol('x'), Integer(-1)))");
+
+        let (imported, inputs) = from_sympy_srepr(&srepr).unwrap();
+        assert_eq!(to_sympy_srepr(&imported, &inputs).unwrap(), srepr);
+        inputs["x"].borrow_mut().set(2.0);
+        assert_eq!(imported.borrow_mut().compute(), -0.5);
+    }
+
+    #[test]
+    fn reductions() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let args = || -> Vec<Rc<RefCell<dyn Computable>>> { vec![x.clone(), y.clone()] };
+        let root = Node::add(Node::mul_var(args()), Node::reduce(args(), Reduction::Mean));
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        assert_eq!(
+            to_sympy_srepr(&root, &names).unwrap(),
+            "Add(Mul(Symbol('x'), Symbol('y')), Mul(Rational(1, 2), Add(Symbol('x'), Symbol('y'))))"
+        );
+
+        let (imported, inputs) =
+            from_sympy_srepr("Mul(Symbol('x'), Integer(2), Symbol('y'))").unwrap();
+        assert_eq!(imported.borrow().operation().unwrap().name(), "mul_var");
+        inputs["x"].borrow_mut().set(3.0);
+        inputs["y"].borrow_mut().set(4.0);
+        assert_eq!(imported.borrow_mut().compute(), 24.0);
+    }
+
+    #[test]
+    fn precision_functions_round_trip() {
+        // SymPy provides these in `sympy.codegen.cfunctions`
+        let x = InputNode::from_val(0.0);
+        let root = Node::ln_1p(Node::expm1(x.clone()));
+        let names = HashMap::from([("x".to_string(), x)]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+        assert_eq!(srepr, "log1p(expm1(Symbol('x')))");
+
+        let (imported, inputs) = from_sympy_srepr(&srepr).unwrap();
+        inputs["x"].borrow_mut().set(0.5);
+        assert_eq!(imported.borrow_mut().compute(), 0.5);
+    }
+
+    #[test]
+    fn export_norm() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let root = Node::norm(vec![x.clone(), y.clone()]);
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+        assert_eq!(
+            srepr,
+            "Pow(Add(Pow(Symbol('x'), Integer(2)), Pow(Symbol('y'), Integer(2))), Rational(1, 2))"
+        );
+
+        let (imported, inputs) = from_sympy_srepr(&srepr).unwrap();
+        inputs["x"].borrow_mut().set(3.0);
+        inputs["y"].borrow_mut().set(4.0);
+        assert_eq!(imported.borrow_mut().compute(), 5.0);
+    }
+
+    #[test]
+    fn normal_cdf() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::add(Node::normal_cdf(x.clone()), Node::erf(x.clone()));
+        let names = HashMap::from([("x".to_string(), x)]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+
+        let (imported, inputs) = from_sympy_srepr(&srepr).unwrap();
+        inputs["x"].borrow_mut().set(1.0);
+        let expected = 0.8413447 + 0.8427008;
+        assert!((imported.borrow_mut().compute() - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn export_softmax() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let root = Node::softmax_component(vec![x.clone(), y.clone()], 0);
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        assert_eq!(
+            to_sympy_srepr(&root, &names).unwrap(),
+            "Mul(exp(Symbol('x')), Pow(Add(exp(Symbol('x')), exp(Symbol('y'))), Integer(-1)))"
+        );
+    }
+
+    #[test]
+    fn export_select() {
+        let x = InputNode::from_val(0.0);
+        let root = Node::lazy_select(x.clone(), x.clone(), Node::constant(0.0));
+        let names = HashMap::from([("x".to_string(), x)]);
+        assert_eq!(
+            to_sympy_srepr(&root, &names).unwrap(),
+            "Piecewise(ExprCondPair(Symbol('x'), StrictGreaterThan(Symbol('x'), Integer(0))), \
+             ExprCondPair(Integer(0), true))"
+        );
+    }
+
+    #[test]
+    fn division_round_trip() {
+        let x = InputNode::from_val(0.0);
+        let y = InputNode::from_val(0.0);
+        let root = Node::div(x.clone(), y.clone());
+        let names = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        let srepr = to_sympy_srepr(&root, &names).unwrap();
+        assert_eq!(srepr, "Mul(Symbol('x'), Pow(Symbol('y'), Integer(-1)))");
+
+        let (imported, inputs) = from_sympy_srepr(&srepr).unwrap();
+        inputs["x"].borrow_mut().set(3.0);
+        inputs["y"].borrow_mut().set(4.0);
+        assert_eq!(imported.borrow_mut().compute(), 0.75);
+    }
+
+    #[test]
+    fn import_sympy_output() {
+        // srepr(2*x**2 + y/2 - 1)
+        let src = "Add(Mul(Integer(2), Pow(Symbol('x'), Integer(2))), \
+                   Mul(Rational(1, 2), Symbol('y', real=True)), Integer(-1))";
+        let (root, inputs) = from_sympy_srepr(src).unwrap();
+        inputs["x"].borrow_mut().set(3.0);
+        inputs["y"].borrow_mut().set(4.0);
+        assert_eq!(root.borrow_mut().compute(), 19.0);
+    }
+
+    #[test]
+    fn import_errors() {
+        assert_eq!(
+            from_sympy_srepr("cosh(Symbol('x'))").err(),
+            Some(SympyError::Unsupported("function cosh".into()))
+        );
+        assert_eq!(
+            from_sympy_srepr("Pow(Symbol('x'), Symbol('y'))").err(),
+            Some(SympyError::Unsupported("non-constant exponent".into()))
+        );
+        assert!(matches!(
+            from_sympy_srepr("sin(Integer(1), Integer(2))"),
+            Err(SympyError::Syntax { .. })
+        ));
+        assert!(matches!(
+            from_sympy_srepr("Add(Symbol('x')"),
+            Err(SympyError::Syntax { .. })
+        ));
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nested = |n| {
+            format!(
+                "{}Symbol('x'){}",
+                "Mul(Integer(2), ".repeat(n),
+                ")".repeat(n)
+            )
+        };
+        assert_eq!(
+            from_sympy_srepr(&nested(2000)).err(),
+            Some(SympyError::TooDeep(DEFAULT_MAX_DEPTH))
+        );
+        let (root, _) = from_sympy_srepr_with_limit(&nested(10), 11).unwrap();
+        assert_eq!(root.borrow().depth(), 11);
+        assert_eq!(
+            from_sympy_srepr_with_limit(&nested(10), 10).err(),
+            Some(SympyError::TooDeep(10))
+        );
+    }
+}
@@ -0,0 +1,428 @@
+//! Thread-safe graph nodes, which can be built on one thread and evaluated from others.
+//!
+//! Unlike `Node`, each node locks only its own cache and dependency list,
+//! so evaluations and input updates from different threads never wait on each other
+//! while holding locks of other nodes.
+//!
+//! `SyncGraph` adds single-writer publishing on top: the writer updates inputs
+//! and publishes computed outputs as an immutable epoch, while any amount of `SyncView`s
+//! read the latest published epoch without waiting for computation.
+
+use std::sync::{Arc, Mutex, RwLock, Weak};
+
+use crate::{
+    float::Float,
+    ops::{DivPolicy, Operation},
+};
+
+/// Thread-safe counterpart of `Computable`.
+pub trait SyncComputable<T: Float = f32>: Send + Sync {
+    /// Computes result of this type.
+    fn compute(&self) -> T;
+    /// Adds dependency from another `SyncComputable` object.
+    fn add_dependency(&self, dependency: SyncHandle<T>);
+    /// Resets cache for this node.
+    fn reset_cache(&self);
+}
+
+/// Shared handle to thread-safe node.
+pub type SyncHandle<T = f32> = Arc<dyn SyncComputable<T>>;
+
+/// Weak references to dependent nodes, which hold strong references to their operands.
+type SyncDependencies<T> = RwLock<Vec<Weak<dyn SyncComputable<T>>>>;
+
+/// Resets caches of dependent nodes, dropping references to already freed ones.
+fn reset_dependencies<T: Float>(dependencies: &SyncDependencies<T>) {
+    let mut freed = false;
+    dependencies
+        .read()
+        .unwrap()
+        .iter()
+        .for_each(|d| match d.upgrade() {
+            Some(d) => d.reset_cache(),
+            None => freed = true,
+        });
+    if freed {
+        dependencies
+            .write()
+            .unwrap()
+            .retain(|d| d.strong_count() > 0);
+    }
+}
+
+/// Cached result along with count of resets,
+/// so results computed from stale operands aren't cached.
+struct Cache<T> {
+    val: Option<T>,
+    generation: u64,
+}
+
+/// Thread-safe graph expression node.
+pub struct SyncNode<T: Float = f32> {
+    /// Cached result.
+    cache: Mutex<Cache<T>>,
+    /// Holds references to nodes that depend from this node.
+    dependencies: SyncDependencies<T>,
+    /// Holds operation for this node.
+    opp: Operation<SyncHandle<T>, T>,
+}
+
+impl<T: Float> SyncNode<T> {
+    /// Builds node computing given operation and registers it as dependency of its operands.
+    pub fn new(opp: Operation<SyncHandle<T>, T>) -> Arc<Self> {
+        let obj = Arc::new(Self {
+            cache: Mutex::new(Cache {
+                val: None,
+                generation: 0,
+            }),
+            dependencies: RwLock::default(),
+            opp,
+        });
+        obj.opp
+            .operands()
+            .into_iter()
+            .for_each(|x| x.add_dependency(obj.clone()));
+        obj
+    }
+
+    /// Builds node holding constant value.
+    pub fn constant(val: T) -> Arc<Self> {
+        Self::new(Operation::Const(val))
+    }
+
+    /// Builds node for sum of two nodes.
+    pub fn add(x: SyncHandle<T>, y: SyncHandle<T>) -> Arc<Self> {
+        Self::new(Operation::Add(x, y))
+    }
+
+    /// Builds node for subtraction of two nodes.
+    pub fn sub(x: SyncHandle<T>, y: SyncHandle<T>) -> Arc<Self> {
+        Self::new(Operation::Sub(x, y))
+    }
+
+    /// Builds node for multiplication of two nodes.
+    pub fn mul(x: SyncHandle<T>, y: SyncHandle<T>) -> Arc<Self> {
+        Self::new(Operation::Mul(x, y))
+    }
+
+    /// Builds node for division of two nodes, producing `inf` or `NaN` on division by zero.
+    pub fn div(x: SyncHandle<T>, y: SyncHandle<T>) -> Arc<Self> {
+        Self::new(Operation::Div(x, y, DivPolicy::default()))
+    }
+
+    /// Returns operation computed by this node.
+    pub fn operation(&self) -> &Operation<SyncHandle<T>, T> {
+        &self.opp
+    }
+}
+
+impl<T: Float> SyncComputable<T> for SyncNode<T> {
+    /// Returns cached result or computes it, without holding the cache lock
+    /// while operands are computed.
+    fn compute(&self) -> T {
+        let generation = {
+            let cache = self.cache.lock().unwrap();
+            if let Some(val) = cache.val {
+                return val;
+            }
+            cache.generation
+        };
+        let val = self.opp.eval(|x| x.compute());
+        let mut cache = self.cache.lock().unwrap();
+        if cache.generation == generation {
+            cache.val = Some(val);
+        }
+        val
+    }
+
+    fn add_dependency(&self, dependency: SyncHandle<T>) {
+        self.dependencies
+            .write()
+            .unwrap()
+            .push(Arc::downgrade(&dependency));
+    }
+
+    fn reset_cache(&self) {
+        {
+            let mut cache = self.cache.lock().unwrap();
+            cache.val = None;
+            cache.generation += 1;
+        }
+        reset_dependencies(&self.dependencies);
+    }
+}
+
+/// Output values published by `SyncGraph::publish`, which never change afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Published<T: Float = f32> {
+    epoch: u64,
+    values: Vec<(String, T)>,
+}
+
+impl<T: Float> Published<T> {
+    /// Returns number of publication, starting from zero for graph that was never published.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns value of output with given name.
+    pub fn get(&self, name: &str) -> Option<T> {
+        self.values
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, val)| *val)
+    }
+
+    /// Returns names and values of outputs in registration order.
+    pub fn values(&self) -> impl Iterator<Item = (&str, T)> {
+        self.values.iter().map(|(name, val)| (name.as_str(), *val))
+    }
+}
+
+/// Latest publication shared between writer and views.
+/// Lock is held only to swap or clone the pointer, never during computation.
+type Current<T> = Arc<RwLock<Arc<Published<T>>>>;
+
+/// Graph of thread-safe nodes with named inputs and outputs, updated by single owner
+/// and read concurrently through `SyncView`s.
+pub struct SyncGraph<T: Float = f32> {
+    inputs: Vec<(String, Arc<SyncInputNode<T>>)>,
+    outputs: Vec<(String, SyncHandle<T>)>,
+    current: Current<T>,
+}
+
+impl<T: Float> Default for SyncGraph<T> {
+    fn default() -> Self {
+        Self {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            current: Arc::new(RwLock::new(Arc::new(Published {
+                epoch: 0,
+                values: Vec::new(),
+            }))),
+        }
+    }
+}
+
+impl<T: Float> SyncGraph<T> {
+    /// Creates empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates input node with given value and registers it under given name.
+    /// Registering existing name replaces the input.
+    pub fn add_input(&mut self, name: impl Into<String>, val: T) -> Arc<SyncInputNode<T>> {
+        let input = SyncInputNode::from_val(val);
+        let name = name.into();
+        self.inputs.retain(|(n, _)| *n != name);
+        self.inputs.push((name, input.clone()));
+        input
+    }
+
+    /// Registers node as output under given name.
+    /// Registering existing name replaces the output.
+    /// Views see the output after next publication.
+    pub fn add_output(&mut self, name: impl Into<String>, node: SyncHandle<T>) {
+        let name = name.into();
+        self.outputs.retain(|(n, _)| *n != name);
+        self.outputs.push((name, node));
+    }
+
+    /// Sets value of input with given name, returning `false` if it isn't registered.
+    /// Views keep seeing previous values until next publication.
+    pub fn set(&mut self, name: &str, val: T) -> bool {
+        match self.inputs.iter().find(|(n, _)| n == name) {
+            Some((_, input)) => {
+                input.set(val);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Computes all outputs and publishes them to views as new epoch, returning its number.
+    pub fn publish(&mut self) -> u64 {
+        let epoch = self.current.read().unwrap().epoch + 1;
+        let values = self
+            .outputs
+            .iter()
+            .map(|(name, node)| (name.clone(), node.compute()))
+            .collect();
+        *self.current.write().unwrap() = Arc::new(Published { epoch, values });
+        epoch
+    }
+
+    /// Returns latest publication.
+    pub fn published(&self) -> Arc<Published<T>> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Creates read-only view of publications, which can be sent to other threads.
+    pub fn view(&self) -> SyncView<T> {
+        SyncView {
+            current: self.current.clone(),
+        }
+    }
+}
+
+/// Cheap read-only handle to publications of `SyncGraph`.
+#[derive(Clone)]
+pub struct SyncView<T: Float = f32> {
+    current: Current<T>,
+}
+
+impl<T: Float> SyncView<T> {
+    /// Returns latest publication, whose values stay consistent with each other
+    /// however long it's kept.
+    pub fn load(&self) -> Arc<Published<T>> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Returns latest published value of output with given name.
+    pub fn output(&self, name: &str) -> Option<T> {
+        self.load().get(name)
+    }
+
+    /// Returns number of latest publication.
+    pub fn epoch(&self) -> u64 {
+        self.load().epoch()
+    }
+}
+
+/// Thread-safe graph input node.
+pub struct SyncInputNode<T: Float = f32> {
+    val: RwLock<T>,
+    /// Holds references to nodes that depend from this node.
+    dependencies: SyncDependencies<T>,
+}
+
+impl<T: Float> SyncInputNode<T> {
+    pub fn from_val(val: T) -> Arc<Self> {
+        Arc::new(Self {
+            val: RwLock::new(val),
+            dependencies: RwLock::default(),
+        })
+    }
+
+    /// Sets new input value and resets cache for all dependable nodes.
+    pub fn set(&self, val: T) {
+        *self.val.write().unwrap() = val;
+        reset_dependencies(&self.dependencies);
+    }
+}
+
+impl<T: Float> SyncComputable<T> for SyncInputNode<T> {
+    /// Just returns stored value.
+    fn compute(&self) -> T {
+        *self.val.read().unwrap()
+    }
+
+    fn add_dependency(&self, dependency: SyncHandle<T>) {
+        self.dependencies
+            .write()
+            .unwrap()
+            .push(Arc::downgrade(&dependency));
+    }
+
+    /// Doesn't have cache, so doing nothing.
+    fn reset_cache(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn send_sync() {
+        fn check<S: Send + Sync>() {}
+        check::<SyncNode>();
+        check::<SyncInputNode<f64>>();
+        check::<SyncHandle>();
+        check::<SyncView>();
+    }
+
+    #[test]
+    fn compute_set() {
+        let x1 = SyncInputNode::from_val(2.0);
+        let x2 = SyncInputNode::from_val(3.0);
+        let x = SyncNode::div(
+            SyncNode::mul(x1.clone(), x2.clone()),
+            SyncNode::sub(x2.clone(), SyncNode::constant(1.0)),
+        );
+        assert_eq!(x.compute(), 3.0);
+        assert_eq!(x.operation().name(), "div");
+
+        x1.set(4.0);
+        assert_eq!(x.compute(), 6.0);
+        x2.set(5.0);
+        assert_eq!(x.compute(), 5.0);
+    }
+
+    #[test]
+    fn threads() {
+        let x1 = SyncInputNode::from_val(1.0);
+        let x: SyncHandle = SyncNode::add(x1.clone(), SyncNode::constant(1.0));
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let x = x.clone();
+                thread::spawn(move || x.compute())
+            })
+            .collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), 2.0);
+        }
+
+        thread::spawn(move || x1.set(2.0)).join().unwrap();
+        assert_eq!(thread::spawn(move || x.compute()).join().unwrap(), 3.0);
+    }
+
+    #[test]
+    fn views() {
+        let mut graph = SyncGraph::new();
+        let x = graph.add_input("x", 1.0);
+        graph.add_output("x", x.clone());
+        graph.add_output("double", SyncNode::add(x.clone(), x));
+        let view = graph.view();
+        assert_eq!((view.epoch(), view.output("x")), (0, None));
+
+        assert_eq!(graph.publish(), 1);
+        let first = view.load();
+        assert!(graph.set("x", 2.0));
+        assert!(!graph.set("y", 2.0));
+        // views see updates only once they are published
+        assert_eq!(view.output("double"), Some(2.0));
+        graph.publish();
+        assert_eq!(view.output("double"), Some(4.0));
+        assert_eq!(
+            first.values().collect::<Vec<_>>(),
+            vec![("x", 1.0), ("double", 2.0)]
+        );
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let view = view.clone();
+                thread::spawn(move || {
+                    let mut last = 0;
+                    while last < 100 {
+                        let published = view.load();
+                        assert!(published.epoch() >= last);
+                        assert_eq!(published.get("double"), published.get("x").map(|x| x * 2.0));
+                        last = published.epoch();
+                    }
+                })
+            })
+            .collect();
+        for i in 0..98 {
+            graph.set("x", i as f32);
+            graph.publish();
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(graph.published().get("x"), Some(97.0));
+    }
+}
@@ -0,0 +1,213 @@
+//! Stateful nodes holding values across evaluation ticks, for streaming pipelines.
+//!
+//! Temporal nodes sample their operand when the tick advances with `Graph::step`,
+//! and keep their value until the next tick, so changes of operands within a tick
+//! don't affect them. Each sample is taken after upstream temporal nodes advanced,
+//! so a sample flows through the whole pipeline in a single step.
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use crate::{
+    float::Float,
+    graph::Graph,
+    node::{
+        dependents, remove_dependency, reset_dependencies, Computable, Dependencies, Handle, Node,
+    },
+};
+
+/// State kept across ticks and the value computed from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Temporal {
+    /// Sample from given amount of ticks ago, zero until there's one.
+    Delay(usize),
+    /// Sum of all samples.
+    Accumulate,
+    /// Mean of samples from given amount of the latest ticks, zero before the first one.
+    MovingAverage(usize),
+}
+
+/// Node computing value from samples of its operand taken at each tick.
+pub struct TemporalNode<T: Float = f32> {
+    source: Handle<T>,
+    kind: Temporal,
+    /// The latest samples, as many as value depends on.
+    history: VecDeque<T>,
+    /// Value computed from samples.
+    value: T,
+    /// Holds references to nodes that depend from this node.
+    dependencies: Dependencies<T>,
+}
+
+impl<T: Float> TemporalNode<T> {
+    /// Builds node of given kind sampling given operand.
+    ///
+    /// # Panics
+    ///
+    /// Panics if moving average window is empty.
+    pub fn new(source: Handle<T>, kind: Temporal) -> Rc<RefCell<Self>> {
+        assert!(
+            kind != Temporal::MovingAverage(0),
+            "moving average window is empty"
+        );
+        let obj = Rc::new(RefCell::new(Self {
+            source: source.clone(),
+            kind,
+            history: VecDeque::new(),
+            value: T::ZERO,
+            dependencies: Dependencies::default(),
+        }));
+        source.borrow_mut().add_dependency(obj.clone());
+        obj
+    }
+
+    /// Returns kind of this node.
+    pub fn kind(&self) -> Temporal {
+        self.kind
+    }
+
+    /// Forgets all samples, as if no tick happened, resetting dependents.
+    pub fn reset_state(&mut self) {
+        self.history.clear();
+        self.value = T::ZERO;
+        reset_dependencies(&mut self.dependencies);
+    }
+}
+
+impl<T: Float> Computable<T> for TemporalNode<T> {
+    /// Returns value as of the latest tick.
+    fn compute(&mut self) -> T {
+        self.value
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependencies.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Handle<T>> {
+        dependents(&self.dependencies)
+    }
+
+    /// Value changes only on ticks, so doing nothing.
+    fn reset_cache(&mut self) {}
+
+    fn operands(&self) -> Vec<Handle<T>> {
+        vec![self.source.clone()]
+    }
+
+    /// Samples operand and updates value, resetting dependents.
+    fn advance(&mut self) {
+        let sample = self.source.borrow_mut().compute();
+        self.value = match self.kind {
+            Temporal::Delay(n) => {
+                self.history.push_back(sample);
+                if self.history.len() > n + 1 {
+                    self.history.pop_front();
+                }
+                if self.history.len() > n {
+                    self.history[0]
+                } else {
+                    T::ZERO
+                }
+            }
+            Temporal::Accumulate => self.value + sample,
+            Temporal::MovingAverage(window) => {
+                self.history.push_back(sample);
+                if self.history.len() > window {
+                    self.history.pop_front();
+                }
+                // summed anew, so rounding errors don't build up over ticks
+                let sum: T = self.history.iter().copied().sum();
+                sum / T::from_f64(self.history.len() as f64)
+            }
+        };
+        reset_dependencies(&mut self.dependencies);
+    }
+}
+
+impl<T: Float> Node<T> {
+    /// Builds node returning sample of `x` from `n` ticks ago, see `temporal` module.
+    pub fn delay(x: Handle<T>, n: usize) -> Rc<RefCell<TemporalNode<T>>> {
+        TemporalNode::new(x, Temporal::Delay(n))
+    }
+
+    /// Builds node summing samples of `x` over ticks, see `temporal` module.
+    pub fn accumulate(x: Handle<T>) -> Rc<RefCell<TemporalNode<T>>> {
+        TemporalNode::new(x, Temporal::Accumulate)
+    }
+
+    /// Builds node averaging samples of `x` over given amount of the latest ticks,
+    /// see `temporal` module.
+    pub fn moving_average(x: Handle<T>, window: usize) -> Rc<RefCell<TemporalNode<T>>> {
+        TemporalNode::new(x, Temporal::MovingAverage(window))
+    }
+}
+
+impl<T: Float> Graph<T> {
+    /// Advances tick of temporal nodes reachable from outputs, upstream ones first,
+    /// see `temporal` module. Graph must be acyclic, see `Graph::validate`.
+    pub fn step(&self) {
+        let mut nodes = self.reachable();
+        // stable, so nodes of the same depth keep post-order
+        nodes.sort_by_key(|node| node.borrow().depth());
+        for node in nodes {
+            node.borrow_mut().advance();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    #[test]
+    fn pipeline() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 0.0f32);
+        let smooth = Node::moving_average(x.clone(), 2);
+        let total = Node::accumulate(smooth.clone());
+        g.add_output("previous", Node::delay(x.clone(), 1));
+        g.add_output("total", Node::mul(total, Node::constant(10.0)));
+        g.add_output("smooth", smooth);
+
+        let mut ticks = Vec::new();
+        for val in [2.0, 4.0, 8.0] {
+            x.borrow_mut().set(val);
+            // values change only with ticks
+            let before = g.outputs();
+            g.step();
+            assert_ne!(g.outputs(), before);
+            ticks.push(g.outputs().iter().map(|(_, v)| *v).collect::<Vec<_>>());
+        }
+        assert_eq!(
+            ticks,
+            vec![
+                vec![0.0, 20.0, 2.0],
+                vec![2.0, 50.0, 3.0],
+                vec![4.0, 110.0, 6.0]
+            ]
+        );
+    }
+
+    #[test]
+    fn delay_and_reset() {
+        let x = Node::input(1.0f32);
+        let delayed = Node::delay(x.clone(), 2);
+        let y = Node::add(delayed.clone(), Node::constant(0.5));
+        assert_eq!(y.borrow_mut().compute(), 0.5);
+        for val in [1.0, 2.0, 3.0] {
+            x.borrow_mut().set(val);
+            delayed.borrow_mut().advance();
+        }
+        assert!(y.borrow().is_dirty());
+        assert_eq!(y.borrow_mut().compute(), 1.5);
+
+        delayed.borrow_mut().reset_state();
+        assert_eq!(y.borrow_mut().compute(), 0.5);
+        assert_eq!(delayed.borrow().kind(), Temporal::Delay(2));
+    }
+}
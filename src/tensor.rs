@@ -0,0 +1,692 @@
+//! Vector and matrix valued nodes applying scalar operations element-wise, for evaluating
+//! the same formula over a whole column of data with a single graph.
+//!
+//! Values are matrices stored by rows, vectors being single-column matrices.
+//! Vector nodes compute operations of `Operation` for each index of their operands,
+//! whose shapes must match, except for dimensions of size one, which are broadcast.
+//! Shapes are checked on construction and fixed, so input updates can't break them.
+//! `MatrixNode`s multiply and transpose matrices, and `ReduceNode`s reduce vectors
+//! back to scalar nodes.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    error::GraphError,
+    float::Float,
+    node::{
+        dependents, node_addr, remove_dependency, reset_dependencies, Computable, Dependencies,
+        Handle,
+    },
+    ops::{Operation, Reduction},
+};
+
+/// Amounts of rows and columns of matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Shape {
+    /// Amount of rows.
+    pub rows: usize,
+    /// Amount of columns.
+    pub cols: usize,
+}
+
+impl Shape {
+    /// Returns shape of matrix with given amounts of rows and columns.
+    pub fn matrix(rows: usize, cols: usize) -> Self {
+        Self { rows, cols }
+    }
+
+    /// Returns shape of column vector with given length.
+    pub fn vector(len: usize) -> Self {
+        Self::matrix(len, 1)
+    }
+
+    /// Returns amount of elements.
+    pub fn len(&self) -> usize {
+        self.rows * self.cols
+    }
+
+    /// Returns true if shape has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns shape of result of element-wise operation over both shapes, if compatible.
+    /// Each dimension must match, unless it's one in either shape, which is then repeated.
+    pub fn broadcast(self, other: Shape) -> Option<Shape> {
+        fn dim(a: usize, b: usize) -> Option<usize> {
+            match (a, b) {
+                _ if a == b => Some(a),
+                (1, _) => Some(b),
+                (_, 1) => Some(a),
+                _ => None,
+            }
+        }
+        Some(Shape::matrix(
+            dim(self.rows, other.rows)?,
+            dim(self.cols, other.cols)?,
+        ))
+    }
+
+    /// Returns index of element of matrix with this shape used for element with given
+    /// row and column of broadcast result.
+    fn index(&self, row: usize, col: usize) -> usize {
+        let row = if self.rows == 1 { 0 } else { row };
+        let col = if self.cols == 1 { 0 } else { col };
+        row * self.cols + col
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.rows, self.cols)
+    }
+}
+
+/// Counterpart of `Computable` for nodes computing vectors and matrices.
+pub trait VecComputable<T: Float = f32> {
+    /// Computes elements of this node, by rows.
+    fn compute(&mut self) -> Rc<[T]>;
+    /// Returns shape of computed values, which doesn't change.
+    fn shape(&self) -> Shape;
+    /// Returns amount of computed elements.
+    fn len(&self) -> usize {
+        self.shape().len()
+    }
+    /// Returns true if computed vectors are empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Adds dependent vector node.
+    fn add_vec_dependency(&mut self, dependency: VecHandle<T>);
+    /// Adds dependent scalar node, such as reduction of this vector.
+    fn add_dependency(&mut self, dependency: Handle<T>);
+    /// Drops reference to dependent scalar node which no longer uses this node.
+    fn remove_dependency(&mut self, _dependency: &Handle<T>) {}
+    /// Resets cache for this node, passing reset on to dependents.
+    fn reset_cache(&mut self);
+}
+
+/// Shared handle to node computing vectors of type `T`.
+pub type VecHandle<T = f32> = Rc<RefCell<dyn VecComputable<T>>>;
+
+/// Vector operation, applying scalar operation to elements with the same index.
+pub type VecOperation<T = f32> = Operation<VecHandle<T>, T>;
+
+/// Dependent vector and scalar nodes.
+struct Dependents<T: Float> {
+    vectors: Vec<Weak<RefCell<dyn VecComputable<T>>>>,
+    scalars: Dependencies<T>,
+}
+
+impl<T: Float> Default for Dependents<T> {
+    fn default() -> Self {
+        Self {
+            vectors: Vec::new(),
+            scalars: Dependencies::default(),
+        }
+    }
+}
+
+impl<T: Float> Dependents<T> {
+    /// Resets caches of dependent nodes, dropping references to already freed ones.
+    fn reset(&mut self) {
+        self.vectors.retain(|d| match d.upgrade() {
+            Some(d) => {
+                d.borrow_mut().reset_cache();
+                true
+            }
+            None => false,
+        });
+        reset_dependencies(&mut self.scalars);
+    }
+}
+
+/// Vector graph expression node.
+pub struct VecNode<T: Float = f32> {
+    /// Cached result.
+    cache: Option<Rc<[T]>>,
+    shape: Shape,
+    dependents: Dependents<T>,
+    /// Holds operation for this node.
+    opp: VecOperation<T>,
+}
+
+impl<T: Float> VecNode<T> {
+    /// Builds node applying operation element-wise and registers it as dependency
+    /// of its operands. Fails if operation has no operands or their shapes can't be
+    /// broadcast together, see `Shape::broadcast`.
+    pub fn try_new(opp: VecOperation<T>) -> Result<Rc<RefCell<VecNode<T>>>, GraphError> {
+        let shapes: Vec<Shape> = opp
+            .operands()
+            .into_iter()
+            .map(|x| x.borrow().shape())
+            .collect();
+        if shapes.is_empty() {
+            return Err(GraphError::Arity {
+                operation: opp.name(),
+                min: 1,
+                max: usize::MAX,
+                actual: 0,
+            });
+        }
+        let shape = shapes[1..]
+            .iter()
+            .try_fold(shapes[0], |acc, s| acc.broadcast(*s))
+            .ok_or_else(|| GraphError::ShapeMismatch {
+                operation: opp.name(),
+                shapes: shapes.clone(),
+            })?;
+        let obj = Rc::new(RefCell::new(VecNode {
+            cache: None,
+            shape,
+            dependents: Dependents::default(),
+            opp,
+        }));
+        for x in obj.borrow().opp.operands() {
+            x.borrow_mut().add_vec_dependency(obj.clone());
+        }
+        Ok(obj)
+    }
+
+    /// Builds node applying operation element-wise, see `try_new`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if operation has no operands or their shapes don't match.
+    pub fn new(opp: VecOperation<T>) -> Rc<RefCell<VecNode<T>>> {
+        Self::try_new(opp).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns operation applied by this node.
+    pub fn operation(&self) -> &VecOperation<T> {
+        &self.opp
+    }
+}
+
+impl<T: Float> VecComputable<T> for VecNode<T> {
+    fn compute(&mut self) -> Rc<[T]> {
+        if let Some(val) = &self.cache {
+            return val.clone();
+        }
+        let operands: Vec<(usize, Shape, Rc<[T]>)> = self
+            .opp
+            .operands()
+            .into_iter()
+            .map(|x| {
+                let mut x_ref = x.borrow_mut();
+                (node_addr(x), x_ref.shape(), x_ref.compute())
+            })
+            .collect();
+        let cols = self.shape.cols;
+        let val: Rc<[T]> = (0..self.shape.len())
+            .map(|i| {
+                self.opp.eval(|x| {
+                    let addr = node_addr(x);
+                    let (_, shape, values) = operands.iter().find(|(a, ..)| *a == addr).unwrap();
+                    values[shape.index(i / cols, i % cols)]
+                })
+            })
+            .collect();
+        self.cache = Some(val.clone());
+        val
+    }
+
+    fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    fn add_vec_dependency(&mut self, dependency: VecHandle<T>) {
+        self.dependents.vectors.push(Rc::downgrade(&dependency));
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependents.scalars.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependents.scalars, dependency);
+    }
+
+    fn reset_cache(&mut self) {
+        if self.cache.take().is_some() {
+            self.dependents.reset();
+        }
+    }
+}
+
+/// Vector or matrix graph input node.
+pub struct VecInputNode<T: Float = f32> {
+    val: Rc<[T]>,
+    shape: Shape,
+    dependents: Dependents<T>,
+}
+
+impl<T: Float> VecInputNode<T> {
+    /// Builds vector input holding given values, whose amount stays fixed.
+    pub fn from_vec(values: Vec<T>) -> Rc<RefCell<VecInputNode<T>>> {
+        let shape = Shape::vector(values.len());
+        Self::matrix(shape, values)
+    }
+
+    /// Builds matrix input of given shape holding given values by rows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if amount of values doesn't match the shape.
+    pub fn matrix(shape: Shape, values: Vec<T>) -> Rc<RefCell<VecInputNode<T>>> {
+        assert_eq!(
+            values.len(),
+            shape.len(),
+            "amount of values doesn't match shape {}",
+            shape
+        );
+        Rc::new(RefCell::new(VecInputNode {
+            val: values.into(),
+            shape,
+            dependents: Dependents::default(),
+        }))
+    }
+
+    /// Returns current values.
+    pub fn value(&self) -> &[T] {
+        &self.val
+    }
+
+    /// Sets new values and resets cache for all dependable nodes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if amount of values differs from the current one.
+    pub fn set(&mut self, values: Vec<T>) {
+        assert_eq!(values.len(), self.val.len(), "vector input length changed");
+        self.val = values.into();
+        self.dependents.reset();
+    }
+}
+
+impl<T: Float> VecComputable<T> for VecInputNode<T> {
+    /// Just returns stored values.
+    fn compute(&mut self) -> Rc<[T]> {
+        self.val.clone()
+    }
+
+    fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    fn add_vec_dependency(&mut self, dependency: VecHandle<T>) {
+        self.dependents.vectors.push(Rc::downgrade(&dependency));
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependents.scalars.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependents.scalars, dependency);
+    }
+
+    /// Doesn't have cache, so doing nothing.
+    fn reset_cache(&mut self) {}
+}
+
+/// Operation over whole matrices.
+#[derive(Clone)]
+pub enum MatrixOperation<T: Float = f32> {
+    /// Matrix product of two matrices.
+    MatMul(VecHandle<T>, VecHandle<T>),
+    /// Transposed matrix.
+    Transpose(VecHandle<T>),
+}
+
+impl<T: Float> MatrixOperation<T> {
+    /// Returns operands of operation.
+    pub fn operands(&self) -> Vec<&VecHandle<T>> {
+        match self {
+            MatrixOperation::MatMul(x, y) => vec![x, y],
+            MatrixOperation::Transpose(x) => vec![x],
+        }
+    }
+
+    /// Returns name of operation.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MatrixOperation::MatMul(..) => "matmul",
+            MatrixOperation::Transpose(_) => "transpose",
+        }
+    }
+}
+
+/// Matrix graph expression node.
+pub struct MatrixNode<T: Float = f32> {
+    /// Cached result.
+    cache: Option<Rc<[T]>>,
+    shape: Shape,
+    dependents: Dependents<T>,
+    /// Holds operation for this node.
+    opp: MatrixOperation<T>,
+}
+
+impl<T: Float> MatrixNode<T> {
+    /// Builds node computing operation and registers it as dependency of its operands.
+    /// Fails if amount of columns of the first matrix of product differs from amount
+    /// of rows of the second one.
+    pub fn try_new(opp: MatrixOperation<T>) -> Result<Rc<RefCell<MatrixNode<T>>>, GraphError> {
+        let shape = match &opp {
+            MatrixOperation::MatMul(x, y) => {
+                let (x, y) = (x.borrow().shape(), y.borrow().shape());
+                if x.cols != y.rows {
+                    return Err(GraphError::ShapeMismatch {
+                        operation: opp.name(),
+                        shapes: vec![x, y],
+                    });
+                }
+                Shape::matrix(x.rows, y.cols)
+            }
+            MatrixOperation::Transpose(x) => {
+                let x = x.borrow().shape();
+                Shape::matrix(x.cols, x.rows)
+            }
+        };
+        let obj = Rc::new(RefCell::new(MatrixNode {
+            cache: None,
+            shape,
+            dependents: Dependents::default(),
+            opp,
+        }));
+        for x in obj.borrow().opp.operands() {
+            x.borrow_mut().add_vec_dependency(obj.clone());
+        }
+        Ok(obj)
+    }
+
+    /// Builds node for matrix product, see `try_new`.
+    pub fn matmul(
+        x: VecHandle<T>,
+        y: VecHandle<T>,
+    ) -> Result<Rc<RefCell<MatrixNode<T>>>, GraphError> {
+        Self::try_new(MatrixOperation::MatMul(x, y))
+    }
+
+    /// Builds node for transposed matrix.
+    pub fn transpose(x: VecHandle<T>) -> Rc<RefCell<MatrixNode<T>>> {
+        Self::try_new(MatrixOperation::Transpose(x)).expect("any matrix can be transposed")
+    }
+
+    /// Returns operation computed by this node.
+    pub fn operation(&self) -> &MatrixOperation<T> {
+        &self.opp
+    }
+}
+
+impl<T: Float> VecComputable<T> for MatrixNode<T> {
+    fn compute(&mut self) -> Rc<[T]> {
+        if let Some(val) = &self.cache {
+            return val.clone();
+        }
+        let val: Rc<[T]> = match &self.opp {
+            MatrixOperation::MatMul(x, y) => {
+                let inner = x.borrow().shape().cols;
+                let (x, y) = (x.borrow_mut().compute(), y.borrow_mut().compute());
+                let cols = self.shape.cols;
+                (0..self.shape.len())
+                    .map(|i| {
+                        let (row, col) = (i / cols, i % cols);
+                        Reduction::Sum
+                            .apply((0..inner).map(|k| x[row * inner + k] * y[k * cols + col]))
+                    })
+                    .collect()
+            }
+            MatrixOperation::Transpose(x) => {
+                let x = x.borrow_mut().compute();
+                let (rows, cols) = (self.shape.rows, self.shape.cols);
+                (0..self.shape.len())
+                    .map(|i| x[(i % cols) * rows + i / cols])
+                    .collect()
+            }
+        };
+        self.cache = Some(val.clone());
+        val
+    }
+
+    fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    fn add_vec_dependency(&mut self, dependency: VecHandle<T>) {
+        self.dependents.vectors.push(Rc::downgrade(&dependency));
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependents.scalars.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependents.scalars, dependency);
+    }
+
+    fn reset_cache(&mut self) {
+        if self.cache.take().is_some() {
+            self.dependents.reset();
+        }
+    }
+}
+
+/// Scalar computed from vectors.
+#[derive(Clone)]
+enum Source<T: Float> {
+    Reduce(VecHandle<T>, Reduction),
+    Dot(VecHandle<T>, VecHandle<T>),
+    Element(VecHandle<T>, usize),
+}
+
+/// Scalar node computed from vector nodes, usable as operand of `Node`s.
+pub struct ReduceNode<T: Float = f32> {
+    cache: Option<T>,
+    dependencies: Dependencies<T>,
+    source: Source<T>,
+}
+
+impl<T: Float> ReduceNode<T> {
+    /// Builds node and registers it as dependency of its vectors.
+    fn build(source: Source<T>) -> Rc<RefCell<ReduceNode<T>>> {
+        let vectors = match &source {
+            Source::Reduce(x, _) | Source::Element(x, _) => vec![x.clone()],
+            Source::Dot(x, y) => vec![x.clone(), y.clone()],
+        };
+        let obj = Rc::new(RefCell::new(ReduceNode {
+            cache: None,
+            dependencies: Dependencies::default(),
+            source,
+        }));
+        for x in vectors {
+            x.borrow_mut().add_dependency(obj.clone());
+        }
+        obj
+    }
+
+    /// Builds node reducing elements of vector, e.g. summing them.
+    pub fn reduce(x: VecHandle<T>, reduction: Reduction) -> Rc<RefCell<ReduceNode<T>>> {
+        Self::build(Source::Reduce(x, reduction))
+    }
+
+    /// Builds node for sum of elements of vector.
+    pub fn sum(x: VecHandle<T>) -> Rc<RefCell<ReduceNode<T>>> {
+        Self::reduce(x, Reduction::Sum)
+    }
+
+    /// Builds node for arithmetic mean of elements of vector, `NaN` for empty ones.
+    pub fn mean(x: VecHandle<T>) -> Rc<RefCell<ReduceNode<T>>> {
+        Self::reduce(x, Reduction::Mean)
+    }
+
+    /// Builds node for dot product of two vectors, failing if their shapes differ.
+    pub fn dot(x: VecHandle<T>, y: VecHandle<T>) -> Result<Rc<RefCell<ReduceNode<T>>>, GraphError> {
+        let shapes = vec![x.borrow().shape(), y.borrow().shape()];
+        if shapes[0] != shapes[1] {
+            return Err(GraphError::ShapeMismatch {
+                operation: "dot",
+                shapes,
+            });
+        }
+        Ok(Self::build(Source::Dot(x, y)))
+    }
+
+    /// Builds node taking element with given index, counting by rows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if index is out of bounds.
+    pub fn element(x: VecHandle<T>, index: usize) -> Rc<RefCell<ReduceNode<T>>> {
+        let len = x.borrow().len();
+        assert!(
+            index < len,
+            "index {} is out of bounds of length {}",
+            index,
+            len
+        );
+        Self::build(Source::Element(x, index))
+    }
+}
+
+impl<T: Float> Computable<T> for ReduceNode<T> {
+    fn compute(&mut self) -> T {
+        if let Some(val) = self.cache {
+            return val;
+        }
+        let val = match &self.source {
+            Source::Reduce(x, reduction) => {
+                let values = x.borrow_mut().compute();
+                reduction.apply(values.iter().copied())
+            }
+            Source::Dot(x, y) => {
+                let (x, y) = (x.borrow_mut().compute(), y.borrow_mut().compute());
+                Reduction::Sum.apply(x.iter().zip(y.iter()).map(|(a, b)| *a * *b))
+            }
+            Source::Element(x, i) => x.borrow_mut().compute()[*i],
+        };
+        self.cache = Some(val);
+        val
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependencies.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Handle<T>> {
+        dependents(&self.dependencies)
+    }
+
+    fn reset_cache(&mut self) {
+        if self.cache.take().is_none() {
+            return;
+        }
+        reset_dependencies(&mut self.dependencies);
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.cache.is_none()
+    }
+
+    fn label(&self) -> Option<&str> {
+        Some(match &self.source {
+            Source::Reduce(_, Reduction::Sum) => "vec_sum",
+            Source::Reduce(_, Reduction::Prod) => "vec_prod",
+            Source::Reduce(_, Reduction::Min) => "vec_min",
+            Source::Reduce(_, Reduction::Max) => "vec_max",
+            Source::Reduce(_, Reduction::Mean) => "vec_mean",
+            Source::Dot(..) => "dot",
+            Source::Element(..) => "element",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Node;
+
+    #[test]
+    fn element_wise() {
+        let x = VecInputNode::from_vec(vec![1.0f32, 2.0, 3.0]);
+        let y = VecInputNode::from_vec(vec![10.0, 20.0, 30.0]);
+        let sum = VecNode::new(Operation::Add(x.clone(), y.clone()));
+        let scaled = VecNode::new(Operation::Mul(sum, x.clone()));
+        assert_eq!(&*scaled.borrow_mut().compute(), &[11.0, 44.0, 99.0]);
+
+        let total = ReduceNode::sum(scaled.clone());
+        let mean = ReduceNode::mean(x.clone());
+        let dot = ReduceNode::dot(x.clone(), y).unwrap();
+        let last = ReduceNode::element(scaled, 2);
+        let z = Node::add(total.clone(), Node::mul(mean, dot));
+        assert_eq!(z.borrow_mut().compute(), 154.0 + 2.0 * 140.0);
+
+        x.borrow_mut().set(vec![0.0, 0.0, 1.0]);
+        assert_eq!(total.borrow_mut().compute(), 31.0);
+        assert_eq!(last.borrow_mut().compute(), 31.0);
+        assert!((z.borrow_mut().compute() - (31.0 + 30.0 / 3.0)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn lengths() {
+        let x = VecInputNode::from_vec(vec![1.0f32, 2.0]);
+        let y = VecInputNode::from_vec(vec![1.0, 2.0, 3.0]);
+        let err = VecNode::try_new(Operation::Add(x.clone(), y.clone()))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            GraphError::ShapeMismatch {
+                operation: "add",
+                shapes: vec![Shape::vector(2), Shape::vector(3)]
+            }
+        );
+        assert!(ReduceNode::dot(x, y).is_err());
+        assert!(VecNode::<f32>::try_new(Operation::Const(1.0)).is_err());
+    }
+
+    #[test]
+    fn matrices() {
+        let a = VecInputNode::matrix(Shape::matrix(2, 3), vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let x = VecInputNode::from_vec(vec![1.0, 0.0, -1.0]);
+        let bias = VecInputNode::from_vec(vec![0.5, -10.0]);
+        // relu(a * x + bias)
+        let ax = MatrixNode::matmul(a.clone(), x.clone()).unwrap();
+        assert_eq!(ax.borrow().shape(), Shape::vector(2));
+        let layer = VecNode::new(Operation::Relu(VecNode::new(Operation::Add(ax, bias))));
+        assert_eq!(&*layer.borrow_mut().compute(), &[0.0, 0.0]);
+        x.borrow_mut().set(vec![1.0, 1.0, 1.0]);
+        assert_eq!(&*layer.borrow_mut().compute(), &[6.5, 5.0]);
+
+        let at = MatrixNode::transpose(a.clone());
+        assert_eq!(at.borrow().shape(), Shape::matrix(3, 2));
+        assert_eq!(&*at.borrow_mut().compute(), &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+        assert!(MatrixNode::matmul(a.clone(), at.clone()).is_ok());
+        assert!(MatrixNode::matmul(a.clone(), a.clone()).is_err());
+
+        // column vector broadcast across columns, row across rows
+        let row = VecInputNode::matrix(Shape::matrix(1, 3), vec![10.0, 20.0, 30.0]);
+        let col = VecInputNode::from_vec(vec![1.0, 2.0]);
+        let sum = VecNode::new(Operation::Add(a.clone(), row));
+        assert_eq!(
+            &*sum.borrow_mut().compute(),
+            &[11.0, 22.0, 33.0, 14.0, 25.0, 36.0]
+        );
+        let scaled = VecNode::new(Operation::Mul(a, col));
+        assert_eq!(
+            &*scaled.borrow_mut().compute(),
+            &[1.0, 2.0, 3.0, 8.0, 10.0, 12.0]
+        );
+        assert!(
+            VecNode::try_new(Operation::Add(at, VecInputNode::from_vec(vec![0.0; 2]))).is_err()
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Checking that incremental results match recomputation from scratch.
+//!
+//! Meant for tests of custom `Computable` implementations, which must reset their caches
+//! when operands change. Unlike `stress`, which recomputes operations itself, nodes are
+//! recomputed by resetting caches of all of them, so custom nodes without operation
+//! are checked too, provided they compute the same result from the same operands.
+
+use std::{cell::RefCell, collections::HashMap, error::Error, fmt, rc::Rc};
+
+use crate::{
+    eval::Rng,
+    float::Float,
+    input::InputNode,
+    node::{node_addr, post_order, Handle},
+};
+
+/// Cached result differing from recomputation, see `check_incremental_consistency`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsistencyFailure {
+    /// Index of the sample exposing the failure.
+    pub sample: usize,
+    /// Nodes from root to the one holding wrong result, by label or operation name,
+    /// `custom` for nodes without either.
+    pub path: Vec<String>,
+    /// Result of recomputation.
+    pub expected: f64,
+    /// Cached result.
+    pub actual: f64,
+}
+
+impl fmt::Display for ConsistencyFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "at sample {}, {} holds {} instead of {}",
+            self.sample,
+            self.path.join(" > "),
+            self.actual,
+            self.expected
+        )
+    }
+}
+
+impl Error for ConsistencyFailure {}
+
+/// Sets random inputs among given ones to random values between -2 and 2 for each
+/// of `samples` samples, computes root, and checks that results cached by nodes
+/// reachable from it match their results after resetting caches of all of them.
+/// Values rejected by input rules are skipped. Volatile nodes, which stay dirty,
+/// aren't checked. Samples are deterministic, so failures are reproducible.
+/// Graph must be acyclic, see `Graph::validate`.
+pub fn check_incremental_consistency<T: Float>(
+    root: &Handle<T>,
+    inputs: &[Rc<RefCell<InputNode<T>>>],
+    samples: usize,
+) -> Result<(), ConsistencyFailure> {
+    let mut rng = Rng::new(samples as u64);
+    for sample in 0..samples {
+        if !inputs.is_empty() {
+            for _ in 0..=pick(&mut rng, inputs.len()) {
+                let input = &inputs[pick(&mut rng, inputs.len())];
+                let val = rng.next_f64() * 4.0 - 2.0;
+                let _ = input.borrow_mut().try_set(T::from_f64(val));
+            }
+        }
+        root.borrow_mut().compute();
+        check(root, sample)?;
+    }
+    Ok(())
+}
+
+/// Compares cached results of nodes reachable from root with their recomputation.
+fn check<T: Float>(root: &Handle<T>, sample: usize) -> Result<(), ConsistencyFailure> {
+    let nodes = post_order(root);
+    let cached: Vec<Option<T>> = nodes
+        .iter()
+        .map(|node| {
+            let dirty = node.borrow().is_dirty();
+            (!dirty).then(|| node.borrow_mut().compute())
+        })
+        .collect();
+    // caches of dirty nodes stop the walk, so each node is reset by itself
+    for node in &nodes {
+        node.borrow_mut().reset_cache();
+    }
+    root.borrow_mut().compute();
+    for (node, cached) in nodes.iter().zip(cached) {
+        let cached = match cached {
+            Some(val) if !node.borrow().is_dirty() => val,
+            _ => continue,
+        };
+        let val = node.borrow_mut().compute();
+        if !same(cached, val) {
+            return Err(ConsistencyFailure {
+                sample,
+                path: path(root, node),
+                expected: val.to_f64(),
+                actual: cached.to_f64(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Describes nodes along a path from root to given node.
+fn path<T: Float>(root: &Handle<T>, node: &Handle<T>) -> Vec<String> {
+    let mut parents: HashMap<usize, Handle<T>> = HashMap::new();
+    for parent in post_order(root) {
+        for x in parent.borrow().operands() {
+            parents
+                .entry(node_addr(&x))
+                .or_insert_with(|| parent.clone());
+        }
+    }
+    let mut path = vec![describe(node)];
+    let mut node = node.clone();
+    while let Some(parent) = parents.get(&node_addr(&node)) {
+        path.push(describe(parent));
+        node = parent.clone();
+    }
+    path.reverse();
+    path
+}
+
+fn describe<T: Float>(node: &Handle<T>) -> String {
+    let node = node.borrow();
+    match (node.label(), node.operation()) {
+        (Some(label), _) => label.to_string(),
+        (None, Some(opp)) => opp.name().to_string(),
+        (None, None) => "custom".to_string(),
+    }
+}
+
+fn pick(rng: &mut Rng, len: usize) -> usize {
+    (rng.next_u64() % len as u64) as usize
+}
+
+/// Returns true if values are equal or both are `NaN`.
+fn same<T: Float>(a: T, b: T) -> bool {
+    a == b || (a.to_f64().is_nan() && b.to_f64().is_nan())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{Computable, Node};
+
+    /// Node caching square of its operand without registering as its dependency.
+    struct Stale {
+        source: Handle,
+        cache: Option<f32>,
+    }
+
+    impl Computable for Stale {
+        fn compute(&mut self) -> f32 {
+            let source = &self.source;
+            *self
+                .cache
+                .get_or_insert_with(|| source.borrow_mut().compute().powi(2))
+        }
+
+        fn add_dependency(&mut self, _dependency: Handle) {}
+
+        fn reset_cache(&mut self) {
+            self.cache = None;
+        }
+
+        fn is_dirty(&self) -> bool {
+            self.cache.is_none()
+        }
+
+        fn operands(&self) -> Vec<Handle> {
+            vec![self.source.clone()]
+        }
+    }
+
+    #[test]
+    fn consistency() {
+        let x = InputNode::with_label(1.0f32, "x");
+        let y = Node::input(2.0);
+        let shared: Handle = Node::mul(x.clone(), y.clone());
+        let root: Handle = Node::add(
+            Node::lazy_select(
+                Node::gt(x.clone(), y.clone()),
+                shared.clone(),
+                Node::exp(y.clone()),
+            ),
+            Node::sin(shared),
+        );
+        let inputs = [x.clone(), y];
+        assert_eq!(check_incremental_consistency(&root, &inputs, 100), Ok(()));
+
+        let stale: Handle = Rc::new(RefCell::new(Stale {
+            source: Node::sin(x.clone()),
+            cache: None,
+        }));
+        let root: Handle = Node::neg(Node::add(stale, Node::constant(1.0)));
+        let failure = check_incremental_consistency(&root, &[x], 10).unwrap_err();
+        assert_eq!(failure.sample, 1);
+        assert_eq!(failure.path, vec!["neg", "add", "custom"]);
+        assert!(failure
+            .to_string()
+            .starts_with("at sample 1, neg > add > custom holds"));
+    }
+}
@@ -0,0 +1,113 @@
+//! Node of simulation time provided by evaluation context.
+
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    error::GraphError,
+    eval::Evaluation,
+    float::Float,
+    node::{
+        dependents, remove_dependency, reset_dependencies, Computable, Dependencies, Handle, Node,
+    },
+};
+
+/// Node computing simulation time of evaluation, see `Evaluation::time`.
+/// Time is only available through `try_compute`, `compute` returns time of the last
+/// evaluation, or zero before the first one.
+/// Advancing time with `Evaluation::step` invalidates the node and its dependents.
+pub struct TimeNode<T: Float = f32> {
+    /// Reference to itself, so evaluation can invalidate the node.
+    this: Weak<RefCell<TimeNode<T>>>,
+    /// Time of the last evaluation.
+    last: T,
+    /// Cached result.
+    cache: Option<T>,
+    /// Holds references to nodes that depend from this node.
+    dependencies: Dependencies<T>,
+}
+
+impl<T: Float> Node<T> {
+    /// Builds node computing simulation time of evaluation.
+    pub fn time() -> Rc<RefCell<TimeNode<T>>> {
+        Rc::new_cyclic(|this| {
+            RefCell::new(TimeNode {
+                this: this.clone(),
+                last: T::ZERO,
+                cache: None,
+                dependencies: Dependencies::default(),
+            })
+        })
+    }
+}
+
+impl<T: Float> Computable<T> for TimeNode<T> {
+    fn compute(&mut self) -> T {
+        self.last
+    }
+
+    fn add_dependency(&mut self, dependency: Handle<T>) {
+        self.dependencies.push(Rc::downgrade(&dependency));
+    }
+
+    fn remove_dependency(&mut self, dependency: &Handle<T>) {
+        remove_dependency(&mut self.dependencies, dependency);
+    }
+
+    fn dependents(&self) -> Vec<Handle<T>> {
+        dependents(&self.dependencies)
+    }
+
+    fn reset_cache(&mut self) {
+        if self.cache.take().is_none() {
+            return;
+        }
+        reset_dependencies(&mut self.dependencies);
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.cache.is_none()
+    }
+
+    fn try_compute(&mut self, eval: &mut Evaluation) -> Result<T, GraphError> {
+        if let Some(val) = self.cache {
+            return Ok(val);
+        }
+        let this = self.this.clone();
+        eval.on_step(self.this.as_ptr() as *const () as usize, move || {
+            if let Some(node) = this.upgrade() {
+                node.borrow_mut().reset_cache();
+            }
+        });
+        self.last = T::from_f64(eval.time());
+        self.cache = Some(self.last);
+        Ok(self.last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulation() {
+        let t = Node::time();
+        let root = Node::add(
+            Node::mul(t.clone(), Node::constant(2.0f32)),
+            Node::constant(1.0),
+        );
+        let mut eval = Evaluation::new().at_time(1.0);
+        assert_eq!(root.borrow_mut().try_compute(&mut eval), Ok(3.0));
+
+        eval.step(0.5);
+        assert!(root.borrow().is_dirty());
+        assert_eq!(root.borrow_mut().try_compute(&mut eval), Ok(4.0));
+        assert_eq!((eval.time(), eval.step_index()), (1.5, 1));
+
+        // plain computation sees time of the last evaluation
+        assert_eq!(t.borrow_mut().compute(), 1.5);
+        assert_eq!(root.borrow_mut().compute(), 4.0);
+    }
+}
@@ -0,0 +1,117 @@
+//! Strongly typed wrappers over graphs with named inputs and outputs.
+
+/// Declares struct wrapping `Graph` with accessor methods for listed inputs and outputs.
+/// Each input is given as `name: setter`, outputs are computed by methods of the same name.
+///
+/// ```
+/// use teza::{graph::Graph, node::Node, typed_graph};
+///
+/// typed_graph! {
+///     pub struct Pricing {
+///         inputs { rate: set_rate, amount: set_amount }
+///         outputs { price }
+///     }
+/// }
+///
+/// let mut g = Graph::new();
+/// let rate = g.add_input("rate", 2.0);
+/// let amount = g.add_input("amount", 3.0);
+/// g.add_output("price", Node::mul(rate, amount));
+///
+/// let model = Pricing::from_graph(g).unwrap();
+/// model.set_rate(4.0);
+/// assert_eq!(model.price(), 12.0);
+/// ```
+#[macro_export]
+macro_rules! typed_graph {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            inputs { $($input:ident: $setter:ident),* $(,)? }
+            outputs { $($output:ident),* $(,)? }
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            graph: $crate::graph::Graph,
+        }
+
+        impl $name {
+            /// Wraps given graph, returning `None` if any listed input or output
+            /// isn't registered in it.
+            pub fn from_graph(graph: $crate::graph::Graph) -> Option<Self> {
+                $(graph.input(stringify!($input))?;)*
+                $(graph.output(stringify!($output))?;)*
+                Some(Self { graph })
+            }
+
+            /// Returns wrapped graph.
+            pub fn graph(&self) -> &$crate::graph::Graph {
+                &self.graph
+            }
+
+            $(
+                /// Returns current value of input.
+                pub fn $input(&self) -> f32 {
+                    use $crate::node::Computable;
+                    self.graph.input(stringify!($input)).unwrap().borrow_mut().compute()
+                }
+
+                /// Sets value of input.
+                pub fn $setter(&self, val: f32) {
+                    use $crate::input::Input;
+                    self.graph.input(stringify!($input)).unwrap().borrow_mut().set(val)
+                }
+            )*
+
+            $(
+                /// Computes output.
+                pub fn $output(&self) -> f32 {
+                    self.graph.output(stringify!($output)).unwrap().borrow_mut().compute()
+                }
+            )*
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{graph::Graph, node::Node};
+
+    typed_graph! {
+        struct Model {
+            inputs { x: set_x, y: set_y }
+            outputs { sum, product }
+        }
+    }
+
+    #[test]
+    fn accessors() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 2.0);
+        let y = g.add_input("y", 3.0);
+        g.add_output("sum", Node::add(x.clone(), y.clone()));
+        g.add_output("product", Node::mul(x, y));
+
+        let model = Model::from_graph(g).unwrap();
+        assert_eq!((model.sum(), model.product()), (5.0, 6.0));
+        model.set_y(4.0);
+        assert_eq!(model.y(), 4.0);
+        assert_eq!((model.sum(), model.product()), (6.0, 8.0));
+        model.set_x(1.0);
+        assert_eq!((model.x(), model.sum()), (1.0, 5.0));
+        assert_eq!(model.graph().outputs().len(), 2);
+    }
+
+    #[test]
+    fn missing_names() {
+        let mut g = Graph::new();
+        let x = g.add_input("x", 2.0);
+        g.add_output("sum", x.clone());
+        g.add_output("product", x);
+        assert!(Model::from_graph(g.clone()).is_none());
+
+        g.add_input("y", 1.0);
+        assert!(Model::from_graph(g).is_some());
+    }
+}
@@ -0,0 +1,239 @@
+//! Physical units of values, checked for dimensional consistency.
+//!
+//! Inputs can be tagged with units, see `InputNode::set_unit`, which operations combine:
+//! sums and comparisons require matching units, products and quotients combine them,
+//! and functions like `sin` or `exp` require dimensionless operands. Untagged values,
+//! including constants, have unknown unit, taking unit of other operands of sums and
+//! acting as dimensionless factors of products, so graphs without units are unaffected.
+//! `Node::try_new` rejects operations over inconsistent units, `Node::check_units`
+//! checks graphs built otherwise.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::{Div, Mul},
+};
+
+use crate::{
+    error::GraphError,
+    float::Float,
+    node::{node_addr, post_order, Handle, Node},
+    ops::{Operation, Reduction},
+};
+
+/// Unit as exponents of meter, second and kilogram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Unit {
+    exponents: [i8; 3],
+}
+
+impl Unit {
+    /// Unit of pure numbers.
+    pub const DIMENSIONLESS: Unit = Unit::new(0, 0, 0);
+    /// Meter.
+    pub const METER: Unit = Unit::new(1, 0, 0);
+    /// Second.
+    pub const SECOND: Unit = Unit::new(0, 1, 0);
+    /// Kilogram.
+    pub const KILOGRAM: Unit = Unit::new(0, 0, 1);
+
+    /// Creates unit with given exponents of meter, second and kilogram.
+    pub const fn new(m: i8, s: i8, kg: i8) -> Self {
+        Self {
+            exponents: [m, s, kg],
+        }
+    }
+
+    /// Returns unit raised to given power.
+    pub fn powi(self, pow: i8) -> Self {
+        Self {
+            exponents: self.exponents.map(|e| e * pow),
+        }
+    }
+
+    /// Returns unit whose square is this unit, if exponents are even.
+    pub fn sqrt(self) -> Option<Self> {
+        if self.exponents.iter().all(|e| e % 2 == 0) {
+            Some(Self {
+                exponents: self.exponents.map(|e| e / 2),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl Mul for Unit {
+    type Output = Unit;
+
+    fn mul(self, other: Unit) -> Unit {
+        let [m, s, kg] = self.exponents;
+        let [m2, s2, kg2] = other.exponents;
+        Unit::new(m + m2, s + s2, kg + kg2)
+    }
+}
+
+impl Div for Unit {
+    type Output = Unit;
+
+    fn div(self, other: Unit) -> Unit {
+        self * other.powi(-1)
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let factors: Vec<String> = ["m", "s", "kg"]
+            .iter()
+            .zip(self.exponents)
+            .filter(|(_, e)| *e != 0)
+            .map(|(name, e)| match e {
+                1 => name.to_string(),
+                e => format!("{}^{}", name, e),
+            })
+            .collect();
+        if factors.is_empty() {
+            f.write_str("1")
+        } else {
+            f.write_str(&factors.join("*"))
+        }
+    }
+}
+
+/// Returns unit of operation result from units of its operands, `None` if unknown,
+/// failing if operands have inconsistent units.
+pub(crate) fn unit_of<H, T: Float>(
+    opp: &Operation<H, T>,
+    get: impl Fn(&H) -> Option<Unit>,
+) -> Result<Option<Unit>, GraphError> {
+    use Operation::*;
+
+    let mismatch = |units: Vec<Unit>| GraphError::UnitMismatch {
+        operation: opp.name(),
+        units,
+    };
+    // common unit of operands, unknown ones taking it too
+    let same = |args: Vec<&H>| {
+        let units: Vec<Unit> = args.into_iter().filter_map(&get).collect();
+        match units.first() {
+            Some(first) if units.iter().any(|u| u != first) => Err(mismatch(units)),
+            first => Ok(first.copied()),
+        }
+    };
+    let dimensionless = |args: Vec<&H>| match same(args)? {
+        Some(unit) if unit != Unit::DIMENSIONLESS => Err(mismatch(vec![unit])),
+        _ => Ok(Some(Unit::DIMENSIONLESS)),
+    };
+    // unknown factors are dimensionless, unless all of them are unknown
+    let product = |args: Vec<&H>| args.into_iter().filter_map(&get).reduce(Unit::mul);
+    Ok(match opp {
+        Const(_) | Custom(..) => None,
+        Add(..) | Sub(..) | AddVar(_) | Min(..) | Max(..) | Clamp(..) | Hypot(..) | Norm(_) => {
+            same(opp.operands())?
+        }
+        Reduce(_, Reduction::Sum | Reduction::Min | Reduction::Max | Reduction::Mean) | Rem(..) => {
+            same(opp.operands())?
+        }
+        Mul(..) | MulVar(_) | Reduce(_, Reduction::Prod) => product(opp.operands()),
+        Div(x, y, _) => match (get(x), get(y)) {
+            (None, None) => None,
+            (x, y) => Some(x.unwrap_or(Unit::DIMENSIONLESS) / y.unwrap_or(Unit::DIMENSIONLESS)),
+        },
+        Recip(x) => get(x).map(|unit| unit.powi(-1)),
+        Neg(x) | Detach(x) | Abs(x) | Relu(x) | Fract(x) | Quantize(x, ..) => get(x),
+        Pow(x, pow) => match get(x) {
+            Some(unit) if *pow == T::from_f64(pow.to_f64().round()) => {
+                Some(unit.powi(pow.to_f64() as i8))
+            }
+            Some(unit) if unit != Unit::DIMENSIONLESS => return Err(mismatch(vec![unit])),
+            unit => unit,
+        },
+        Sqrt(x) => match get(x) {
+            Some(unit) => Some(unit.sqrt().ok_or_else(|| mismatch(vec![unit]))?),
+            None => None,
+        },
+        Sign(_) => Some(Unit::DIMENSIONLESS),
+        Lt(x, y) | Le(x, y) | Gt(x, y) | Ge(x, y) | Eq(x, y, _) | Atan2(x, y) => {
+            same(vec![x, y])?;
+            Some(Unit::DIMENSIONLESS)
+        }
+        Select(_, x, y) => same(vec![x, y])?,
+        Piecewise(_, pieces, default) => same(
+            pieces
+                .iter()
+                .map(|(_, piece)| piece)
+                .chain([default])
+                .collect(),
+        )?,
+        Sin(_) | Asin(_) | Acos(_) | Atan(_) | Cos(_) | Tan(_) | Exp(_) | Ln(_) | Log(..) => {
+            dimensionless(opp.operands())?
+        }
+        Tanh(_) | Sigmoid(_) | Softplus(_) | ExpM1(_) | Ln1p(_) | Erf(_) | Erfc(_) => {
+            dimensionless(opp.operands())?
+        }
+        NormalCdf(_) | NormalPdf(_) | LogSumExp(_) | Softmax(..) | Polynomial(..) => {
+            dimensionless(opp.operands())?
+        }
+    })
+}
+
+impl<T: Float> Node<T> {
+    /// Checks units of operations reachable from root, see `units` module, returning unit
+    /// of root, `None` if unknown. Fails at the first operation over inconsistent units.
+    /// Graph must be acyclic, see `Graph::validate`.
+    pub fn check_units(root: &Handle<T>) -> Result<Option<Unit>, GraphError> {
+        let mut done: HashMap<usize, Option<Unit>> = HashMap::new();
+        for node in post_order(root) {
+            let unit = match node.borrow().operation() {
+                Some(opp) => unit_of(opp, |x| done[&node_addr(x)])?,
+                None => node.borrow().unit(),
+            };
+            done.insert(node_addr(&node), unit);
+        }
+        Ok(done[&node_addr(root)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::InputNode, ops::Operation};
+
+    #[test]
+    fn units() {
+        let tagged = |val: f32, unit: Unit| {
+            let x = InputNode::from_val(val);
+            x.borrow_mut().set_unit(unit);
+            x
+        };
+        let distance = tagged(10.0, Unit::METER);
+        let time = tagged(2.0, Unit::SECOND);
+        let speed: Handle = Node::div(distance.clone(), time.clone());
+        let accel: Handle = Node::div(speed.clone(), time.clone());
+        assert_eq!(Node::check_units(&accel), Ok(Some(Unit::new(1, -2, 0))));
+        assert_eq!(accel.borrow().unit(), Some(Unit::new(1, -2, 0)));
+        assert_eq!(Unit::new(1, -2, 0).to_string(), "m*s^-2");
+
+        // constants scale and shift values of any unit
+        let doubled: Handle = Node::mul(Node::constant(2.0), distance.clone());
+        let shifted: Handle = Node::add(doubled, Node::constant(1.0));
+        assert_eq!(Node::check_units(&shifted), Ok(Some(Unit::METER)));
+        let side: Handle = Node::sqrt(Node::pow(distance.clone(), 2.0));
+        assert_eq!(Node::check_units(&side), Ok(Some(Unit::METER)));
+
+        let mixed: Handle = Node::add(Node::sin(time.clone()), distance.clone());
+        assert_eq!(
+            Node::check_units(&mixed),
+            Err(GraphError::UnitMismatch {
+                operation: "sin",
+                units: vec![Unit::SECOND],
+            })
+        );
+        let err = Node::try_new(Operation::Add(distance.clone(), time), usize::MAX).err();
+        assert_eq!(
+            err.map(|err| err.to_string()),
+            Some("operands of add have incompatible units m, s".to_string())
+        );
+        assert!(Node::try_new(Operation::Add(distance, speed), usize::MAX).is_err());
+    }
+}
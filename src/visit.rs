@@ -0,0 +1,277 @@
+//! Traversal and inspection of built graphs.
+//!
+//! Nodes are walked through `Computable::operands`, so custom nodes reporting their
+//! operands are traversed too. Each node reachable from root is visited once.
+
+use std::{
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+use crate::{
+    float::Float,
+    node::{node_addr, post_order, Handle, Node},
+};
+
+/// Identifier of graph node, valid while the node is alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Returns identifier of given node.
+    pub fn of<C: ?Sized>(node: &Rc<RefCell<C>>) -> Self {
+        Self(node_addr(node))
+    }
+}
+
+/// Order of visiting graph nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// Depth-first, each node before its operands, starting with root.
+    PreOrder,
+    /// Depth-first, each node after its operands, ending with root.
+    PostOrder,
+    /// By increasing depth, so each node comes after its operands and nodes
+    /// of the same depth, which can be computed independently, are adjacent.
+    Topological,
+}
+
+/// Structural statistics of graph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GraphStats {
+    /// Amount of distinct nodes.
+    pub nodes: usize,
+    /// Amount of nodes along the longest path from root to a leaf.
+    pub depth: usize,
+    /// Amount of leaves without operation, such as inputs.
+    pub inputs: usize,
+    /// Amount of operation nodes used as operand more than once.
+    pub shared: usize,
+}
+
+/// Returns nodes reachable from root, each once, in given order.
+/// Graph must be acyclic, see `Graph::validate`.
+pub fn walk<T: Float>(root: &Handle<T>, order: Order) -> Vec<Handle<T>> {
+    match order {
+        Order::PreOrder => {
+            let mut nodes = Vec::new();
+            let mut visited = HashSet::new();
+            let mut stack = vec![root.clone()];
+            while let Some(node) = stack.pop() {
+                if !visited.insert(node_addr(&node)) {
+                    continue;
+                }
+                // reversed, so the first operand is visited first
+                stack.extend(node.borrow().operands().into_iter().rev());
+                nodes.push(node);
+            }
+            nodes
+        }
+        Order::PostOrder => {
+            let mut nodes = Vec::new();
+            let mut visited = HashSet::new();
+            // nodes along with flag whether their operands were already pushed
+            let mut stack = vec![(root.clone(), false)];
+            while let Some((node, expanded)) = stack.pop() {
+                if expanded {
+                    nodes.push(node);
+                    continue;
+                }
+                if !visited.insert(node_addr(&node)) {
+                    continue;
+                }
+                let operands = node.borrow().operands();
+                stack.push((node, true));
+                stack.extend(operands.into_iter().rev().map(|x| (x, false)));
+            }
+            nodes
+        }
+        Order::Topological => {
+            let mut nodes = walk(root, Order::PostOrder);
+            // stable, so nodes of the same depth keep post-order
+            nodes.sort_by_key(|node| node.borrow().depth());
+            nodes
+        }
+    }
+}
+
+/// Calls `f` with each node reachable from root, in given order.
+pub fn visit<T: Float>(root: &Handle<T>, order: Order, f: impl FnMut(&Handle<T>)) {
+    walk(root, order).iter().for_each(f)
+}
+
+/// Computes structural statistics of graph with given root.
+pub fn stats<T: Float>(root: &Handle<T>) -> GraphStats {
+    let nodes = post_order(root);
+    let mut uses: HashMap<usize, usize> = HashMap::new();
+    for node in &nodes {
+        for x in node.borrow().operands() {
+            *uses.entry(node_addr(&x)).or_default() += 1;
+        }
+    }
+    let mut stats = GraphStats {
+        nodes: nodes.len(),
+        depth: root.borrow().depth(),
+        ..GraphStats::default()
+    };
+    for node in &nodes {
+        let node_ref = node.borrow();
+        if node_ref.operation().is_none() && node_ref.operands().is_empty() {
+            stats.inputs += 1;
+        } else if uses.get(&node_addr(node)).copied().unwrap_or_default() > 1 {
+            stats.shared += 1;
+        }
+    }
+    stats
+}
+
+/// Describes each node reachable from root in post-order, referring to operands
+/// by their position in the order, so shared nodes are told apart from equal copies.
+/// Nodes without operation are described only by their label and kind, not value.
+fn structure<T: Float>(root: &Handle<T>) -> Vec<String> {
+    let nodes = post_order(root);
+    let ids: HashMap<usize, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node_addr(node), i))
+        .collect();
+    nodes
+        .iter()
+        .map(|node| {
+            let node = node.borrow();
+            match node.operation() {
+                // float parameters have no `Hash`, their debug form tells them apart
+                Some(opp) => format!("{:?}", opp.map_operands(|x| ids[&node_addr(x)])),
+                None => {
+                    let operands: Vec<usize> =
+                        node.operands().iter().map(|x| ids[&node_addr(x)]).collect();
+                    format!("leaf {:?} {:?} {:?}", node.label(), node.kind(), operands)
+                }
+            }
+        })
+        .collect()
+}
+
+/// Returns true if graphs with given roots have the same structure: the same operations
+/// over operands with the same structure, shared the same way. Values of inputs and
+/// caches are ignored, inputs are matched by position. Graphs must be acyclic.
+pub fn structural_eq<T: Float>(a: &Handle<T>, b: &Handle<T>) -> bool {
+    node_addr(a) == node_addr(b) || structure(a) == structure(b)
+}
+
+/// Computes hash of graph structure, equal for graphs which are `structural_eq`.
+/// Stable within a process only, see `store::fingerprint` for persistent one.
+pub fn structural_hash<T: Float>(root: &Handle<T>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    structure(root).hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<T: Float> Node<T> {
+    /// Returns nodes reachable from root in given order, see `visit` module.
+    pub fn walk(root: &Handle<T>, order: Order) -> Vec<Handle<T>> {
+        walk(root, order)
+    }
+
+    /// Calls `f` with each node reachable from root in given order, see `visit` module.
+    pub fn visit(root: &Handle<T>, order: Order, f: impl FnMut(&Handle<T>)) {
+        visit(root, order, f)
+    }
+
+    /// Computes structural statistics of graph with given root.
+    pub fn stats(root: &Handle<T>) -> GraphStats {
+        stats(root)
+    }
+
+    /// Returns true if graphs have the same structure, see `structural_eq`.
+    pub fn structural_eq(a: &Handle<T>, b: &Handle<T>) -> bool {
+        structural_eq(a, b)
+    }
+
+    /// Computes hash of graph structure, see `structural_hash`.
+    pub fn structural_hash(root: &Handle<T>) -> u64 {
+        structural_hash(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputNode;
+
+    #[test]
+    fn orders() {
+        let x = InputNode::from_val(1.0f32);
+        let y = InputNode::from_val(2.0);
+        let shared = Node::mul(x.clone(), y.clone());
+        let root: Handle = Node::add(Node::sin(shared.clone()), shared.clone());
+        let ids = |order| -> Vec<NodeId> { walk(&root, order).iter().map(NodeId::of).collect() };
+        let (x, y, shared, sin) = (
+            NodeId::of(&x),
+            NodeId::of(&y),
+            NodeId::of(&shared),
+            NodeId::of(&root.borrow().operands()[0]),
+        );
+        let root_id = NodeId::of(&root);
+        assert_eq!(ids(Order::PreOrder), vec![root_id, sin, shared, x, y]);
+        assert_eq!(ids(Order::PostOrder), vec![x, y, shared, sin, root_id]);
+        assert_eq!(ids(Order::Topological), vec![x, y, shared, sin, root_id]);
+
+        let mut count = 0;
+        Node::visit(&root, Order::PreOrder, |_| count += 1);
+        assert_eq!(count, 5);
+    }
+
+    #[test]
+    fn statistics() {
+        let x = InputNode::from_val(1.0f32);
+        let y = InputNode::from_val(2.0);
+        let shared = Node::mul(x.clone(), y);
+        let root: Handle = Node::add(
+            Node::sin(shared.clone()),
+            Node::mul(shared, Node::add(x, Node::constant(1.0))),
+        );
+        assert_eq!(
+            Node::stats(&root),
+            GraphStats {
+                nodes: 8,
+                depth: 4,
+                inputs: 2,
+                shared: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn structure() {
+        let build = |x: f32, y: f32, shared: bool| -> Handle {
+            let x = InputNode::from_val(x);
+            let y = InputNode::from_val(y);
+            let square = Node::mul(x.clone(), x.clone());
+            let other: Handle = if shared {
+                square.clone()
+            } else {
+                Node::mul(x, Node::constant(2.0))
+            };
+            Node::add(Node::sin(square), Node::div(other, y))
+        };
+        let a = build(1.0, 2.0, true);
+        let b = build(3.0, 4.0, true);
+        b.borrow_mut().compute();
+        assert!(Node::structural_eq(&a, &b));
+        assert_eq!(Node::structural_hash(&a), Node::structural_hash(&b));
+        assert!(!Node::structural_eq(&a, &build(1.0, 2.0, false)));
+
+        // equal copies aren't the same as a shared node
+        let x = InputNode::from_val(1.0f32);
+        let sum = || -> Handle { Node::add(x.clone(), Node::constant(1.0)) };
+        let shared = sum();
+        let copies: Handle = Node::mul(sum(), sum());
+        let shared: Handle = Node::mul(shared.clone(), shared);
+        assert!(!Node::structural_eq(&copies, &shared));
+        let other: Handle = Node::mul(sum(), sum());
+        assert!(Node::structural_eq(&copies, &other));
+    }
+}
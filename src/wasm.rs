@@ -0,0 +1,128 @@
+//! Graph wrapper with a flat API over numeric handles, shaped for JavaScript bindings.
+//! Available with `wasm` feature.
+//!
+//! Nodes are referred to by `u32` handles and failures are reported as messages,
+//! so each method maps directly to an exported JavaScript function, e.g. `add_input`
+//! to `addInput`, without exposing `Rc` handles across the boundary.
+//! The `#[wasm_bindgen]` exports themselves need the `wasm-bindgen` crate, which this
+//! dependency-free crate doesn't pull in, so they belong to a binding crate wrapping `JsGraph`.
+
+use crate::{
+    graph::Graph,
+    node::{Handle, Node, NodeHandle},
+};
+
+/// Graph built and computed through numeric node handles.
+#[derive(Default)]
+pub struct JsGraph {
+    graph: Graph<f64>,
+    nodes: Vec<Handle<f64>>,
+}
+
+impl JsGraph {
+    /// Creates empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores node, returning its handle.
+    fn push(&mut self, node: Handle<f64>) -> u32 {
+        self.nodes.push(node);
+        (self.nodes.len() - 1) as u32
+    }
+
+    /// Returns node with given handle.
+    fn node(&self, handle: u32) -> Result<Handle<f64>, String> {
+        self.nodes
+            .get(handle as usize)
+            .cloned()
+            .ok_or_else(|| format!("unknown node {}", handle))
+    }
+
+    /// Adds input registered under given name, returning its handle.
+    /// Fails if name is already used by another input.
+    pub fn add_input(&mut self, name: &str, val: f64) -> Result<u32, String> {
+        if self.graph.input(name).is_some() {
+            return Err(format!("input {} is already registered", name));
+        }
+        let input = self.graph.add_input(name, val);
+        Ok(self.push(input))
+    }
+
+    /// Adds node holding constant value, returning its handle.
+    pub fn constant(&mut self, val: f64) -> u32 {
+        self.push(Node::constant(val))
+    }
+
+    /// Adds node for sum of two nodes.
+    pub fn add(&mut self, x: u32, y: u32) -> Result<u32, String> {
+        let node = Node::add(self.node(x)?, self.node(y)?);
+        Ok(self.push(node))
+    }
+
+    /// Adds node for subtraction of two nodes.
+    pub fn sub(&mut self, x: u32, y: u32) -> Result<u32, String> {
+        let node = Node::sub(self.node(x)?, self.node(y)?);
+        Ok(self.push(node))
+    }
+
+    /// Adds node for multiplication of two nodes.
+    pub fn mul(&mut self, x: u32, y: u32) -> Result<u32, String> {
+        let node = Node::mul(self.node(x)?, self.node(y)?);
+        Ok(self.push(node))
+    }
+
+    /// Adds node for division of two nodes.
+    pub fn div(&mut self, x: u32, y: u32) -> Result<u32, String> {
+        let node = Node::div(self.node(x)?, self.node(y)?);
+        Ok(self.push(node))
+    }
+
+    /// Sets new value of input with given name, resetting caches of its dependents.
+    pub fn set_input(&self, name: &str, val: f64) -> Result<(), String> {
+        self.graph
+            .set_many(&[(name, val)])
+            .map_err(|err| err.to_string())
+    }
+
+    /// Computes node with given handle, recomputing only what changed since last time.
+    pub fn compute(&self, handle: u32) -> Result<f64, String> {
+        NodeHandle::compute(&self.node(handle)?).map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles() {
+        let mut g = JsGraph::new();
+        let price = g.add_input("price", 10.0).unwrap();
+        let qty = g.add_input("qty", 3.0).unwrap();
+        let fee = g.constant(1.5);
+        let gross = g.mul(price, qty).unwrap();
+        let total = g.add(gross, fee).unwrap();
+        assert_eq!(g.compute(total), Ok(31.5));
+
+        g.set_input("qty", 4.0).unwrap();
+        assert_eq!(g.compute(total), Ok(41.5));
+        assert_eq!(g.add(total, 99), Err("unknown node 99".to_string()));
+        assert!(g.set_input("tax", 1.0).is_err());
+        assert_eq!(
+            g.add_input("qty", 5.0),
+            Err("input qty is already registered".to_string())
+        );
+        assert_eq!(g.compute(qty), Ok(4.0));
+
+        g.set_input("qty", 2.0).unwrap();
+        let held = g.nodes[qty as usize].clone();
+        let borrow = held.borrow_mut();
+        assert_eq!(
+            g.compute(total),
+            Err("operand of mul is already borrowed".to_string())
+        );
+        drop(borrow);
+        assert_eq!(g.compute(total), Ok(21.5));
+    }
+}